@@ -3,12 +3,14 @@ use bytemuck::Zeroable;
 use hf_hub::Repo;
 use psyche_centralized_shared::{ClientId, ClientToServerMessage, ServerToClientMessage};
 use psyche_client::{
-    CheckpointConfig, Client, ClientTUI, ClientTUIState, RunInitConfig, WandBInfo, NC,
+    CheckpointConfig, Client, ClientTUI, ClientTUIState, InitFrom, RunInitConfig, SpecialTokens,
+    WandBInfo, NC,
 };
 use psyche_coordinator::{model, Coordinator, HealthChecks};
 use psyche_network::{
-    allowlist, psyche_relay_map, AuthenticatableIdentity, DiscoveryMode, NetworkTUIState,
-    NetworkTui, NodeId, RelayMode, SecretKey, TcpClient,
+    allowlist, psyche_relay_map, AuthenticatableIdentity, BlobStoreConfig, ConnectRetry,
+    DiscoveryMode, GcPolicy, GossipConfig, NetworkTUIState, NetworkTui, NodeId,
+    ParameterServeLimit, RelayMode, SecretKey, SparseValueDtype, TcpClient,
 };
 use psyche_tui::logging::LoggerWidget;
 use psyche_tui::{CustomWidget, TabbedWidget};
@@ -28,6 +30,7 @@ pub enum ToSend {
     Witness(Box<OpportunisticData>),
     HealthCheck(HealthChecks<ClientId>),
     Checkpoint(model::HubRepo),
+    Withdraw,
 }
 
 struct Backend {
@@ -69,6 +72,11 @@ impl WatcherBackend<ClientId> for Backend {
         self.tx.send(ToSend::Checkpoint(checkpoint))?;
         Ok(())
     }
+
+    async fn send_withdraw(&mut self) -> Result<()> {
+        self.tx.send(ToSend::Withdraw)?;
+        Ok(())
+    }
 }
 
 pub struct App {
@@ -91,7 +99,11 @@ pub struct AppParams {
     pub data_parallelism: usize,
     pub tensor_parallelism: usize,
     pub micro_batch_size: usize,
+    pub max_micro_batches: Option<usize>,
     pub write_gradients_dir: Option<PathBuf>,
+    pub write_run_summary: Option<PathBuf>,
+    pub write_round_results: Option<PathBuf>,
+    pub log_every_n_steps: u32,
     pub p2p_port: Option<u16>,
     pub p2p_interface: Option<String>,
     pub eval_tasks: Vec<psyche_eval::Task>,
@@ -101,10 +113,28 @@ pub struct AppParams {
     pub wandb_info: Option<WandBInfo>,
     pub optim_stats: Option<u32>,
     pub grad_accum_in_fp32: bool,
+    pub sparse_value_dtype: SparseValueDtype,
     pub dummy_training_delay_secs: Option<u64>,
+    pub init_from: Option<InitFrom>,
+    pub special_tokens: Option<SpecialTokens>,
     pub discovery_mode: DiscoveryMode,
     pub max_concurrent_parameter_requests: usize,
+    pub max_queued_distro_results: usize,
+    pub min_free_device_memory_mb: Option<u64>,
+    pub verify_checkpoint_numerics: bool,
+    pub cpu_fallback: bool,
     pub max_concurrent_downloads: usize,
+    pub max_concurrent_downloads_per_peer: usize,
+    pub parameter_serve_limit: ParameterServeLimit,
+    pub upload_rate_limit: Option<u64>,
+    pub p2p_idle_timeout: Option<Duration>,
+    pub blob_store: BlobStoreConfig,
+    pub compression_level: u32,
+    pub gossip_config: GossipConfig,
+    pub nat_probe_timeout: Option<Duration>,
+    pub health_probe_interval: Duration,
+    pub health_probe_timeout: Duration,
+    pub model_request_connect_retry: ConnectRetry,
 }
 
 impl AppBuilder {
@@ -141,9 +171,19 @@ impl AppBuilder {
             vec![],
             Some(p.identity_secret_key.clone()),
             allowlist.clone(),
+            p.blob_store,
             p.max_concurrent_downloads,
+            p.max_concurrent_downloads_per_peer,
+            p.parameter_serve_limit,
+            p.upload_rate_limit,
+            p.p2p_idle_timeout,
+            p.compression_level,
+            p.gossip_config,
+            vec![],
+            p.nat_probe_timeout,
         )
         .await?;
+        p2p.configure_gc(GcPolicy::default())?;
 
         let app = App {
             cancel: p.cancel,
@@ -157,7 +197,11 @@ impl AppBuilder {
             data_parallelism: p.data_parallelism,
             tensor_parallelism: p.tensor_parallelism,
             micro_batch_size: p.micro_batch_size,
+            max_micro_batches: p.max_micro_batches,
             write_gradients_dir: p.write_gradients_dir,
+            write_run_summary: p.write_run_summary,
+            write_round_results: p.write_round_results,
+            log_every_n_steps: p.log_every_n_steps,
             eval_tasks: p.eval_tasks,
             eval_task_max_docs: p.eval_task_max_docs,
             checkpoint_config: p.checkpoint_upload_info,
@@ -168,8 +212,18 @@ impl AppBuilder {
             private_key: p.identity_secret_key,
             optim_stats_every_n_steps: p.optim_stats,
             grad_accum_in_fp32: p.grad_accum_in_fp32,
+            sparse_value_dtype: p.sparse_value_dtype,
             dummy_training_delay_secs: p.dummy_training_delay_secs,
+            init_from: p.init_from,
+            special_tokens: p.special_tokens,
             max_concurrent_parameter_requests: p.max_concurrent_parameter_requests,
+            model_request_connect_retry: p.model_request_connect_retry,
+            max_queued_distro_results: p.max_queued_distro_results,
+            min_free_device_memory_mb: p.min_free_device_memory_mb,
+            verify_checkpoint_numerics: p.verify_checkpoint_numerics,
+            cpu_fallback: p.cpu_fallback,
+            health_probe_interval: p.health_probe_interval,
+            health_probe_timeout: p.health_probe_timeout,
         };
 
         Ok((app, allowlist, p2p, state_options))
@@ -242,6 +296,7 @@ impl App {
                         ToSend::Witness(witness) => self.server_conn.send(ClientToServerMessage::Witness(witness)).await?,
                         ToSend::HealthCheck(health_checks) => self.server_conn.send(ClientToServerMessage::HealthCheck(health_checks)).await?,
                         ToSend::Checkpoint(checkpoint) => self.server_conn.send(ClientToServerMessage::Checkpoint(checkpoint)).await?,
+                        ToSend::Withdraw => self.server_conn.send(ClientToServerMessage::Withdraw).await?,
                     };
                 }
             }
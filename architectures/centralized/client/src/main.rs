@@ -5,7 +5,7 @@ use clap::{Parser, Subcommand};
 use psyche_client::{print_identity_keys, read_identity_secret_key, TrainArgs};
 use psyche_network::{DiscoveryMode, SecretKey};
 use psyche_tui::{maybe_start_render_loop, LogOutput};
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 use time::OffsetDateTime;
 use tokio::runtime::Builder;
 use tracing::{info, Level};
@@ -56,6 +56,7 @@ async fn async_main() -> Result<()> {
             let hub_read_token = std::env::var("HF_TOKEN").ok();
             let checkpoint_upload_info = args.checkpoint_config()?;
             let eval_tasks = args.eval_tasks()?;
+            let (data_parallelism, tensor_parallelism) = args.parallelism()?;
 
             info!(
                 "============ Client Startup at {} ============",
@@ -95,10 +96,14 @@ async fn async_main() -> Result<()> {
                 run_id: args.run_id,
                 p2p_port: args.bind_p2p_port,
                 p2p_interface: args.bind_p2p_interface,
-                data_parallelism: args.data_parallelism,
-                tensor_parallelism: args.tensor_parallelism,
+                data_parallelism,
+                tensor_parallelism,
                 micro_batch_size: args.micro_batch_size,
+                max_micro_batches: args.max_micro_batches_per_step.map(|x| x as usize),
                 write_gradients_dir: args.write_gradients_dir,
+                write_run_summary: args.write_run_summary,
+                write_round_results: args.write_round_results,
+                log_every_n_steps: args.log_every_n_steps,
                 eval_task_max_docs: args.eval_task_max_docs,
                 eval_tasks,
                 checkpoint_upload_info,
@@ -106,10 +111,28 @@ async fn async_main() -> Result<()> {
                 wandb_info,
                 optim_stats: args.optim_stats_steps,
                 grad_accum_in_fp32: args.grad_accum_in_fp32,
+                sparse_value_dtype: args.sparse_value_dtype,
                 dummy_training_delay_secs: args.dummy_training_delay_secs,
+                init_from: args.init_from(),
+                special_tokens: args.special_tokens(),
                 discovery_mode: DiscoveryMode::N0,
                 max_concurrent_parameter_requests: args.max_concurrent_parameter_requests,
+                max_queued_distro_results: args.max_queued_distro_results as usize,
+                min_free_device_memory_mb: args.min_free_device_memory_mb,
+                verify_checkpoint_numerics: args.verify_checkpoint_numerics,
+                cpu_fallback: args.cpu_fallback,
                 max_concurrent_downloads: args.max_concurrent_downloads,
+                max_concurrent_downloads_per_peer: args.max_concurrent_downloads_per_peer as usize,
+                parameter_serve_limit: args.parameter_serve_limit(),
+                upload_rate_limit: args.upload_rate_limit,
+                p2p_idle_timeout: args.p2p_idle_timeout_secs.map(Duration::from_secs),
+                blob_store: args.blob_store(),
+                compression_level: args.compression,
+                gossip_config: args.gossip_config(),
+                nat_probe_timeout: args.nat_probe_timeout(),
+                health_probe_interval: Duration::from_secs(args.health_probe_interval_secs),
+                health_probe_timeout: Duration::from_secs(args.health_probe_timeout_secs),
+                model_request_connect_retry: args.model_request_connect_retry(),
             })
             .build()
             .await
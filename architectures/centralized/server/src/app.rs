@@ -6,7 +6,7 @@ use psyche_coordinator::model::{
 };
 use psyche_coordinator::{
     Client, ClientState, Coordinator, CoordinatorError, HealthChecks, Round, RunState, TickResult,
-    SOLANA_MAX_NUM_CLIENTS,
+    MAX_STORED_ROUNDS, SOLANA_MAX_NUM_CLIENTS,
 };
 
 use psyche_core::{FixedVec, Shuffle, SizedIterator, TokenSize};
@@ -17,7 +17,7 @@ use psyche_network::{ClientNotification, TcpServer};
 use psyche_tui::{
     logging::LoggerWidget, maybe_start_render_loop, CustomWidget, MaybeTui, TabbedWidget,
 };
-use psyche_watcher::{CoordinatorTui, OpportunisticData};
+use psyche_watcher::{CoordinatorTui, CoordinatorTuiState, HealthHistory, OpportunisticData};
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
@@ -85,6 +85,10 @@ impl psyche_watcher::Backend<ClientId> for ChannelCoordinatorBackend {
     async fn send_checkpoint(&mut self, _checkpoint: model::HubRepo) -> Result<()> {
         bail!("Server does not send checkpoints");
     }
+
+    async fn send_withdraw(&mut self) -> Result<()> {
+        bail!("Server does not withdraw");
+    }
 }
 
 type DataServer =
@@ -96,6 +100,7 @@ pub struct App {
     tick_interval: Interval,
     update_tui_interval: Interval,
     coordinator: Coordinator<ClientId>,
+    health_history: HealthHistory<ClientId>,
     backend: Backend,
     training_data_server: Option<(Sender<Coordinator<ClientId>>, DataServer)>,
     save_state_dir: Option<PathBuf>,
@@ -122,7 +127,7 @@ impl App {
         self.coordinator.run_state
     }
 
-    pub fn get_rounds(&self) -> [Round; 4] {
+    pub fn get_rounds(&self) -> [Round; MAX_STORED_ROUNDS] {
         self.coordinator.epoch_state.rounds
     }
 
@@ -166,10 +171,35 @@ impl App {
         coordinator_server_port: Option<u16>,
         save_state_dir: Option<PathBuf>,
         init_warmup_time: Option<u64>,
+        max_total_steps: Option<u32>,
         withdraw_on_disconnect: bool,
     ) -> Result<Self> {
-        if !coordinator.config.check() {
-            bail!("Coordinator sanity check failed");
+        if let Some(max_total_steps) = max_total_steps {
+            let Model::LLM(llm) = &coordinator.model;
+            if max_total_steps <= llm.lr_schedule.get_warmup_steps() {
+                bail!(
+                    "max total steps ({max_total_steps}) must be past the learning rate warmup ({} steps)",
+                    llm.lr_schedule.get_warmup_steps()
+                );
+            }
+            let configured_total_steps = coordinator.config.total_steps;
+            let total_steps = coordinator.override_total_steps(max_total_steps);
+            if total_steps < max_total_steps {
+                warn!("Can't extend the run past its configured {configured_total_steps} total steps, ignoring --max-total-steps {max_total_steps}");
+            } else {
+                info!("Overriding total steps: {configured_total_steps} -> {total_steps}");
+            }
+        }
+
+        if let Err(violations) = coordinator.config.validate_with_model(&coordinator.model) {
+            bail!(
+                "Coordinator sanity check failed: {}",
+                violations
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
         }
 
         async {
@@ -283,6 +313,7 @@ impl App {
                 tick_interval,
                 update_tui_interval,
                 coordinator,
+                health_history: HealthHistory::default(),
                 backend: Backend {
                     net_server,
                     pending_clients: HashSet::new(),
@@ -345,7 +376,8 @@ impl App {
         if let Some(tx_tui_state) = &self.tx_tui_state {
             let states = (
                 (&*self).into(),
-                (&self.coordinator).into(),
+                CoordinatorTuiState::from(&self.coordinator)
+                    .with_health_history(&self.coordinator, &self.health_history),
                 self.training_data_server.as_ref().map(|o| (&o.1).into()),
                 Default::default(),
             );
@@ -438,6 +470,30 @@ impl App {
                 }
                 true
             }
+            ClientToServerMessage::Withdraw => {
+                let position = self
+                    .coordinator
+                    .epoch_state
+                    .clients
+                    .iter()
+                    .position(|x| x.id == from);
+                match position {
+                    Some(index) => match self.coordinator.withdraw(index as u64) {
+                        Ok(()) => {
+                            info!("Withdrew {from} at its request");
+                            true
+                        }
+                        Err(error) => {
+                            warn!("Error when processing withdraw: {error}");
+                            false
+                        }
+                    },
+                    None => {
+                        warn!("Got withdraw but could not find {from} in client list");
+                        false
+                    }
+                }
+            }
         };
         self.post_state_change(broadcast).await;
     }
@@ -493,6 +549,7 @@ impl App {
     }
 
     async fn post_state_change(&mut self, broadcast: bool) {
+        self.health_history.record(&self.coordinator);
         if self.coordinator.active() {
             // reset to original values if we changed them to something special for init
             self.coordinator.config.warmup_time = self.original_warmup_time;
@@ -72,6 +72,11 @@ struct RunArgs {
     #[clap(long)]
     init_warmup_time: Option<u64>,
 
+    /// Ends the run after this many steps, with the learning rate schedule ending there too.
+    /// Can only shorten the run: values above the `total_steps` declared in the state file are clamped to it.
+    #[clap(long)]
+    max_total_steps: Option<u32>,
+
     /// Automatically withdraw clients that disconenct from the server
     #[clap(
         long,
@@ -134,7 +139,16 @@ async fn main() -> Result<()> {
             let config = load_config_state(state_path.clone(), data_config_path);
             let _ = psyche_tui::init_logging(LogOutput::Console, Level::INFO, None, false, None);
             match config {
-                Ok(_) => info!("Configs are OK!"),
+                Ok((coordinator, _)) => {
+                    match coordinator.config.validate_with_model(&coordinator.model) {
+                        Ok(()) => info!("Configs are OK!"),
+                        Err(violations) => {
+                            for violation in violations {
+                                error!("Error found in config: {}", violation);
+                            }
+                        }
+                    }
+                }
                 Err(error) => error!("Error found in config: {}", error),
             }
         }
@@ -160,6 +174,7 @@ async fn main() -> Result<()> {
                         run_args.server_port,
                         run_args.save_state_dir,
                         run_args.init_warmup_time,
+                        run_args.max_total_steps,
                         run_args.withdraw_on_disconnect,
                     )
                     .await?
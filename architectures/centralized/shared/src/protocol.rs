@@ -16,6 +16,7 @@ pub enum ClientToServerMessage {
     Witness(Box<OpportunisticData>),
     HealthCheck(HealthChecks<ClientId>),
     Checkpoint(model::HubRepo),
+    Withdraw,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -5,8 +5,10 @@ use psyche_coordinator::{
     model::{Checkpoint, Model, LLM},
     Coordinator, CoordinatorConfig, CoordinatorEpochState, RunState, SOLANA_MAX_NUM_CLIENTS,
 };
-use psyche_coordinator::{Client, Round};
-use psyche_core::FixedVec;
+use psyche_coordinator::{
+    Client, ClientState, DataAssignmentStrategy, PendingClientsFullPolicy, Round, MAX_STORED_ROUNDS,
+};
+use psyche_core::{FixedString, FixedVec};
 use std::{collections::HashSet, mem::Discriminant, ops::ControlFlow};
 use tokio::{
     select,
@@ -37,7 +39,7 @@ enum TestingQueryMsg {
         respond_to: oneshot::Sender<RunState>,
     },
     Rounds {
-        respond_to: oneshot::Sender<[Round; 4]>,
+        respond_to: oneshot::Sender<[Round; MAX_STORED_ROUNDS]>,
     },
     RoundsHead {
         respond_to: oneshot::Sender<u32>,
@@ -73,13 +75,26 @@ impl CoordinatorServer {
             rounds_per_epoch: 4,
             max_round_train_time: MAX_ROUND_TRAIN_TIME,
             round_witness_time: ROUND_WITNESS_TIME,
+            witness_timeout: 0,
             min_clients,
             init_min_clients: min_clients,
             global_batch_size_start: global_batch_size,
             global_batch_size_end: global_batch_size,
             global_batch_size_warmup_tokens: 0,
             verification_percent: 0,
+            witness_quorum_percent: 0,
+            committee_salt: FixedString::new(),
+            witness_salt: FixedString::new(),
+            num_stored_rounds: 0,
+            data_assignment_strategy: DataAssignmentStrategy::Contiguous,
+            max_pending_clients: 0,
+            pending_clients_full_policy: PendingClientsFullPolicy::Reject,
+            dataset_samples: 0,
+            max_data_epochs: 0,
+            auto_resume_after: 0,
+            warmup_grace_period: 0,
             witness_nodes,
+            witness_quorum: 0,
             total_steps: 10,
         };
 
@@ -106,6 +121,7 @@ impl CoordinatorServer {
             None,
             None,
             Some(WARMUP_TIME),
+            None,
             true,
         )
         .await
@@ -262,8 +278,8 @@ impl CoordinatorServerHandle {
         recv.await.expect("Coordinator actor task has been killed")
     }
 
-    pub async fn get_rounds(&self) -> [Round; 4] {
-        let (send, recv) = oneshot::channel::<[Round; 4]>();
+    pub async fn get_rounds(&self) -> [Round; MAX_STORED_ROUNDS] {
+        let (send, recv) = oneshot::channel::<[Round; MAX_STORED_ROUNDS]>();
         let msg = TestingQueryMsg::Rounds { respond_to: send };
         let _ = self.query_chan_sender.send(msg).await;
         recv.await.expect("Coordinator actor task has been killed")
@@ -298,4 +314,14 @@ impl CoordinatorServerHandle {
         let _ = self.query_chan_sender.send(msg).await;
         recv.await.expect("Coordinator actor task has been killed")
     }
+
+    pub async fn get_exited_clients(&self) -> Vec<(ClientId, ClientState)> {
+        self.get_coordinator()
+            .await
+            .epoch_state
+            .exited_clients
+            .iter()
+            .map(|client| (client.id, client.state))
+            .collect()
+    }
 }
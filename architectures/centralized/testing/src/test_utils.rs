@@ -4,7 +4,10 @@ use std::time::Duration;
 use crate::client::ClientHandle;
 use crate::server::CoordinatorServerHandle;
 use psyche_centralized_client::app::AppParams;
-use psyche_network::{DiscoveryMode, SecretKey};
+use psyche_network::{
+    BlobStoreConfig, ConnectRetry, DiscoveryMode, GossipConfig, ParameterServeLimit, SecretKey,
+    SparseValueDtype,
+};
 use rand::distributions::{Alphanumeric, DistString};
 use std::env;
 use tokio_util::sync::CancellationToken;
@@ -124,7 +127,11 @@ pub fn dummy_client_app_params_with_training_delay(
         data_parallelism: 1,
         tensor_parallelism: 1,
         micro_batch_size: 1,
+        max_micro_batches: None,
         write_gradients_dir: None,
+        write_run_summary: None,
+        write_round_results: None,
+        log_every_n_steps: 1,
         p2p_port: None,
         p2p_interface: None,
         eval_tasks: Vec::new(),
@@ -134,10 +141,28 @@ pub fn dummy_client_app_params_with_training_delay(
         wandb_info: None,
         optim_stats: None,
         grad_accum_in_fp32: false,
+        sparse_value_dtype: SparseValueDtype::Full,
         dummy_training_delay_secs: Some(training_delay_secs),
+        init_from: None,
+        special_tokens: None,
         discovery_mode: DiscoveryMode::Local,
         max_concurrent_parameter_requests: 10,
+        max_queued_distro_results: 8,
+        min_free_device_memory_mb: None,
+        verify_checkpoint_numerics: false,
+        cpu_fallback: false,
         max_concurrent_downloads: 10,
+        max_concurrent_downloads_per_peer: 1,
+        parameter_serve_limit: ParameterServeLimit::Unlimited,
+        upload_rate_limit: None,
+        p2p_idle_timeout: None,
+        blob_store: BlobStoreConfig::Memory,
+        compression_level: 2,
+        gossip_config: GossipConfig::default(),
+        nat_probe_timeout: None,
+        health_probe_interval: Duration::from_secs(30),
+        health_probe_timeout: Duration::from_secs(5),
+        model_request_connect_retry: ConnectRetry::default(),
     }
 }
 
@@ -151,7 +176,11 @@ pub fn dummy_client_app_params_default(server_port: u16, run_id: &str) -> AppPar
         data_parallelism: 1,
         tensor_parallelism: 1,
         micro_batch_size: 1,
+        max_micro_batches: None,
         write_gradients_dir: None,
+        write_run_summary: None,
+        write_round_results: None,
+        log_every_n_steps: 1,
         p2p_port: None,
         p2p_interface: None,
         eval_tasks: Vec::new(),
@@ -161,9 +190,27 @@ pub fn dummy_client_app_params_default(server_port: u16, run_id: &str) -> AppPar
         wandb_info: None,
         optim_stats: None,
         grad_accum_in_fp32: false,
+        sparse_value_dtype: SparseValueDtype::Full,
         dummy_training_delay_secs: None,
+        init_from: None,
+        special_tokens: None,
         discovery_mode: DiscoveryMode::Local,
         max_concurrent_parameter_requests: 10,
+        max_queued_distro_results: 8,
+        min_free_device_memory_mb: None,
+        verify_checkpoint_numerics: false,
+        cpu_fallback: false,
         max_concurrent_downloads: 10,
+        max_concurrent_downloads_per_peer: 1,
+        parameter_serve_limit: ParameterServeLimit::Unlimited,
+        upload_rate_limit: None,
+        p2p_idle_timeout: None,
+        blob_store: BlobStoreConfig::Memory,
+        compression_level: 2,
+        gossip_config: GossipConfig::default(),
+        nat_probe_timeout: None,
+        health_probe_interval: Duration::from_secs(30),
+        health_probe_timeout: Duration::from_secs(5),
+        model_request_connect_retry: ConnectRetry::default(),
     }
 }
@@ -1,5 +1,6 @@
-use std::time::Duration;
+use std::{collections::HashSet, time::Duration};
 
+use psyche_centralized_shared::{ClientId, ClientToServerMessage, ServerToClientMessage};
 use psyche_centralized_testing::{
     client::ClientHandle,
     server::CoordinatorServerHandle,
@@ -11,8 +12,9 @@ use psyche_centralized_testing::{
 };
 use psyche_coordinator::{
     model::{Checkpoint, HubRepo},
-    RunState,
+    ClientState, RunState,
 };
+use psyche_network::{SecretKey, TcpClient};
 use tracing::info;
 
 #[test_log::test(tokio::test(flavor = "multi_thread"))]
@@ -738,3 +740,78 @@ async fn two_clients_join_in_training_and_get_model_using_p2p() {
     // check that the clients length shows the new joined client trained with new p2p shared model
     assert_with_retries(|| server_handle.get_clients_len(), 4).await;
 }
+
+#[test_log::test(tokio::test(flavor = "multi_thread"))]
+async fn withdraw_removes_only_the_client_that_asked() {
+    let init_min_clients = 3;
+    let global_batch_size = 4;
+    let witness_nodes = 1;
+    let server_handle =
+        CoordinatorServerHandle::new(init_min_clients, global_batch_size, witness_nodes).await;
+    let server_addr = format!("localhost:{}", server_handle.server_port);
+
+    // talk to the server directly, so the test decides who withdraws and when
+    let server_addr = &server_addr;
+    let connect = || async move {
+        let secret_key = SecretKey::generate(&mut rand::rngs::OsRng);
+        let id: ClientId = secret_key.public().into();
+        let connection =
+            TcpClient::<ClientId, ClientToServerMessage, ServerToClientMessage>::connect(
+                server_addr,
+                id,
+                secret_key,
+            )
+            .await
+            .unwrap();
+        (id, connection)
+    };
+
+    let mut clients = Vec::new();
+    for _ in 0..init_min_clients {
+        let (id, mut connection) = connect().await;
+        connection
+            .send(ClientToServerMessage::Join {
+                run_id: server_handle.run_id.clone(),
+            })
+            .await
+            .unwrap();
+        clients.push((id, connection));
+    }
+    assert_with_retries(|| server_handle.get_run_state(), RunState::Warmup).await;
+    assert_with_retries(
+        || server_handle.get_clients_len(),
+        init_min_clients as usize,
+    )
+    .await;
+
+    // a connection that never joined isn't in the client list, so its withdraw is ignored
+    let (_, mut outsider) = connect().await;
+    outsider
+        .send(ClientToServerMessage::Withdraw)
+        .await
+        .unwrap();
+
+    clients[0]
+        .1
+        .send(ClientToServerMessage::Withdraw)
+        .await
+        .unwrap();
+
+    // the withdrawn client exits the epoch and won't be part of the next one,
+    // which leaves too few clients to keep going
+    assert_with_retries(
+        || server_handle.get_exited_clients(),
+        vec![(clients[0].0, ClientState::Withdrawn)],
+    )
+    .await;
+    assert_with_retries(
+        || server_handle.get_pending_clients(),
+        HashSet::from([clients[1].0, clients[2].0]),
+    )
+    .await;
+    assert_with_retries(
+        || server_handle.get_run_state(),
+        RunState::WaitingForMembers,
+    )
+    .await;
+}
@@ -1,5 +1,5 @@
 use crate::{
-    backend::SolanaBackend,
+    backend::{SolanaBackend, SubscriptionState},
     network_identity::NetworkIdentity,
     retry::{retry_function, RetryError},
 };
@@ -14,14 +14,17 @@ use anchor_client::{
 };
 use anyhow::{anyhow, Result};
 use psyche_client::{
-    CheckpointConfig, Client, ClientTUI, ClientTUIState, RunInitConfig, WandBInfo, NC,
+    CheckpointConfig, Client, ClientTUI, ClientTUIState, InitFrom, RunInitConfig, SpecialTokens,
+    WandBInfo, NC,
 };
 use psyche_coordinator::{ClientState, Coordinator, CoordinatorError, RunState};
 use psyche_network::{
-    allowlist, psyche_relay_map, DiscoveryMode, NetworkTUIState, NetworkTui, RelayMode, SecretKey,
+    allowlist, psyche_relay_map, BlobStoreConfig, ConnectRetry, DiscoveryMode, GcPolicy,
+    GossipConfig, NetworkTUIState, NetworkTui, ParameterServeLimit, RelayMode, SecretKey,
+    SparseValueDtype,
 };
 use psyche_tui::{logging::LoggerWidget, CustomWidget, TabbedWidget};
-use psyche_watcher::CoordinatorTui;
+use psyche_watcher::{CoordinatorTui, CoordinatorTuiState};
 use rand::{thread_rng, Rng, RngCore};
 use std::{path::PathBuf, time::Duration};
 use std::{
@@ -49,6 +52,7 @@ pub struct App {
     update_tui_interval: Interval,
     tx_tui_state: Option<Sender<TabsData>>,
     authorizer: Option<Pubkey>,
+    confirmation_retries: usize,
 }
 
 pub struct AppBuilder(AppParams);
@@ -64,7 +68,11 @@ pub struct AppParams {
     pub data_parallelism: usize,
     pub tensor_parallelism: usize,
     pub micro_batch_size: usize,
+    pub max_micro_batches: Option<usize>,
     pub write_gradients_dir: Option<PathBuf>,
+    pub write_run_summary: Option<PathBuf>,
+    pub write_round_results: Option<PathBuf>,
+    pub log_every_n_steps: u32,
     pub p2p_port: Option<u16>,
     pub p2p_interface: Option<String>,
     pub eval_tasks: Vec<psyche_eval::Task>,
@@ -74,10 +82,29 @@ pub struct AppParams {
     pub wandb_info: Option<WandBInfo>,
     pub optim_stats: Option<u32>,
     pub grad_accum_in_fp32: bool,
+    pub sparse_value_dtype: SparseValueDtype,
     pub dummy_training_delay_secs: Option<u64>,
+    pub init_from: Option<InitFrom>,
+    pub special_tokens: Option<SpecialTokens>,
     pub max_concurrent_parameter_requests: usize,
+    pub max_queued_distro_results: usize,
+    pub min_free_device_memory_mb: Option<u64>,
+    pub verify_checkpoint_numerics: bool,
+    pub cpu_fallback: bool,
     pub max_concurrent_downloads: usize,
+    pub max_concurrent_downloads_per_peer: usize,
+    pub parameter_serve_limit: ParameterServeLimit,
+    pub upload_rate_limit: Option<u64>,
+    pub p2p_idle_timeout: Option<Duration>,
+    pub blob_store: BlobStoreConfig,
+    pub compression_level: u32,
+    pub gossip_config: GossipConfig,
+    pub nat_probe_timeout: Option<Duration>,
+    pub health_probe_interval: Duration,
+    pub health_probe_timeout: Duration,
+    pub model_request_connect_retry: ConnectRetry,
     pub authorizer: Option<Pubkey>,
+    pub confirmation_retries: usize,
 }
 
 impl AppBuilder {
@@ -106,9 +133,19 @@ impl AppBuilder {
             vec![],
             Some(p.identity_secret_key.clone()),
             allowlist.clone(),
+            p.blob_store,
             p.max_concurrent_downloads,
+            p.max_concurrent_downloads_per_peer,
+            p.parameter_serve_limit,
+            p.upload_rate_limit,
+            p.p2p_idle_timeout,
+            p.compression_level,
+            p.gossip_config,
+            vec![],
+            p.nat_probe_timeout,
         )
         .await?;
+        p2p.configure_gc(GcPolicy::default())?;
 
         let app = App {
             run_id: p.run_id.clone(),
@@ -123,6 +160,7 @@ impl AppBuilder {
             tx_tui_state: p.tx_tui_state,
             update_tui_interval: interval(Duration::from_millis(150)),
             authorizer: p.authorizer,
+            confirmation_retries: p.confirmation_retries,
         };
         let identity = psyche_solana_coordinator::ClientId::new(
             p.wallet_keypair.pubkey(),
@@ -133,7 +171,11 @@ impl AppBuilder {
                 data_parallelism: p.data_parallelism,
                 tensor_parallelism: p.tensor_parallelism,
                 micro_batch_size: p.micro_batch_size,
+                max_micro_batches: p.max_micro_batches,
                 write_gradients_dir: p.write_gradients_dir,
+                write_run_summary: p.write_run_summary,
+                write_round_results: p.write_round_results,
+                log_every_n_steps: p.log_every_n_steps,
                 eval_tasks: p.eval_tasks,
                 eval_task_max_docs: p.eval_task_max_docs,
                 checkpoint_config: p.checkpoint_upload_info,
@@ -144,8 +186,18 @@ impl AppBuilder {
                 private_key: (p.wallet_keypair.clone(), p.identity_secret_key),
                 optim_stats_every_n_steps: p.optim_stats,
                 grad_accum_in_fp32: p.grad_accum_in_fp32,
+                sparse_value_dtype: p.sparse_value_dtype,
                 dummy_training_delay_secs: p.dummy_training_delay_secs,
+                init_from: p.init_from,
+                special_tokens: p.special_tokens,
                 max_concurrent_parameter_requests: p.max_concurrent_parameter_requests,
+                model_request_connect_retry: p.model_request_connect_retry,
+                max_queued_distro_results: p.max_queued_distro_results,
+                min_free_device_memory_mb: p.min_free_device_memory_mb,
+                verify_checkpoint_numerics: p.verify_checkpoint_numerics,
+                cpu_fallback: p.cpu_fallback,
+                health_probe_interval: p.health_probe_interval,
+                health_probe_timeout: p.health_probe_timeout,
             };
 
         Ok((app, allowlist, p2p, state_options))
@@ -164,7 +216,8 @@ impl App {
             self.backup_clusters.clone(),
             state_options.private_key.0.clone(),
             CommitmentConfig::confirmed(),
-        )?;
+        )?
+        .with_confirmation_retries(self.confirmation_retries);
         let coordinator_instance =
             psyche_solana_coordinator::find_coordinator_instance(&self.run_id);
         let coordinator_instance_state = backend
@@ -177,12 +230,15 @@ impl App {
             .start(self.run_id.clone(), coordinator_account)
             .await?;
 
-        let backend = Arc::new(SolanaBackend::new(
-            self.cluster.clone(),
-            self.backup_clusters.clone(),
-            state_options.private_key.0.clone(),
-            CommitmentConfig::confirmed(),
-        )?);
+        let backend = Arc::new(
+            SolanaBackend::new(
+                self.cluster.clone(),
+                self.backup_clusters.clone(),
+                state_options.private_key.0.clone(),
+                CommitmentConfig::confirmed(),
+            )?
+            .with_confirmation_retries(self.confirmation_retries),
+        );
         let signer = state_options.private_key.0.pubkey();
         let p2p_identity = state_options.private_key.1.public();
 
@@ -232,6 +288,7 @@ impl App {
 
         let mut latest_update = coordinator_state.coordinator;
         let mut updates = backend_runner.updates();
+        let subscription_status = backend_runner.subscription_status();
         let mut client = Client::new(backend_runner, allowlist, p2p, state_options);
 
         let id = psyche_solana_coordinator::ClientId {
@@ -246,7 +303,7 @@ impl App {
                 }
                 _ = self.update_tui_interval.tick() => {
                     let (client_tui_state, network_tui_state) = client.tui_states().await;
-                    self.update_tui(client_tui_state, &latest_update, network_tui_state).await?;
+                    self.update_tui(client_tui_state, &latest_update, network_tui_state, subscription_status.current()).await?;
                 }
                 _ = self.tick_check_interval.tick() => {
                     let mut ticked = latest_update;
@@ -355,11 +412,14 @@ impl App {
         client_tui_state: ClientTUIState,
         coordinator_state: &Coordinator<psyche_solana_coordinator::ClientId>,
         network_tui_state: NetworkTUIState,
+        subscription_state: SubscriptionState,
     ) -> Result<()> {
         if let Some(tx_tui_state) = &self.tx_tui_state {
+            let mut coordinator_tui_state = CoordinatorTuiState::from(coordinator_state);
+            coordinator_tui_state.connection = Some(subscription_state.to_string());
             let states = (
                 client_tui_state,
-                coordinator_state.into(),
+                coordinator_tui_state,
                 network_tui_state,
                 Default::default(),
             );
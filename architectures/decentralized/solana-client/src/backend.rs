@@ -2,19 +2,23 @@ use crate::retry::RetryError;
 use anchor_client::{
     anchor_lang::system_program,
     solana_client::{
+        client_error::ClientErrorKind,
         nonblocking::pubsub_client::PubsubClient,
         rpc_config::{RpcAccountInfoConfig, RpcSendTransactionConfig, RpcTransactionConfig},
-        rpc_response::Response as RpcResponse,
+        rpc_request::RpcError,
     },
     solana_sdk::{
+        account::from_account,
+        clock::Clock,
         commitment_config::CommitmentConfig,
         pubkey::Pubkey,
         signature::{Keypair, Signature, Signer},
-        system_instruction,
+        system_instruction, sysvar,
+        transaction::TransactionError,
     },
-    Client, Cluster, Program,
+    Client, ClientError, Cluster, Program,
 };
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use futures_util::StreamExt;
 use psyche_client::IntegrationTestLogMarker;
 use psyche_coordinator::{
@@ -23,22 +27,24 @@ use psyche_coordinator::{
 };
 use psyche_solana_coordinator::RunMetadata;
 use psyche_watcher::{Backend as WatcherBackend, OpportunisticData};
-use solana_account_decoder_client_types::{UiAccount, UiAccountEncoding};
+use solana_account_decoder_client_types::UiAccountEncoding;
 use solana_transaction_status_client_types::UiTransactionEncoding;
-use std::{cmp::min, sync::Arc, time::Duration};
+use std::{cmp::min, future::Future, sync::Arc, time::Duration};
 use tokio::{
-    sync::{broadcast, mpsc},
+    sync::{broadcast, mpsc, oneshot, watch},
     time::timeout,
 };
 use tracing::{debug, error, info, trace, warn};
 
 const SEND_RETRIES: usize = 3;
+const DEFAULT_CONFIRMATION_RETRIES: usize = 3;
 
 pub struct SolanaBackend {
     program_authorizer: Program<Arc<Keypair>>,
     program_coordinators: Vec<Arc<Program<Arc<Keypair>>>>,
     cluster: Cluster,
     backup_clusters: Vec<Cluster>,
+    confirmation_retries: usize,
 }
 
 pub struct SolanaBackendRunner {
@@ -47,6 +53,7 @@ pub struct SolanaBackendRunner {
     account: Pubkey,
     updates: broadcast::Receiver<Coordinator<psyche_solana_coordinator::ClientId>>,
     init: Option<Coordinator<psyche_solana_coordinator::ClientId>>,
+    subscription_status: SubscriptionStatus,
 }
 
 #[derive(Debug, Clone)]
@@ -56,32 +63,67 @@ pub struct CreatedRun {
     pub create_signatures: Vec<Signature>,
 }
 
+/// How a subscription to the coordinator account is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionState {
+    Connecting,
+    Connected,
+    /// The websocket dropped, and we're subscribing again.
+    Reconnecting,
+}
+
+impl std::fmt::Display for SubscriptionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscriptionState::Connecting => write!(f, "connecting"),
+            SubscriptionState::Connected => write!(f, "connected"),
+            SubscriptionState::Reconnecting => write!(f, "reconnecting"),
+        }
+    }
+}
+
+/// The states of the subscriptions to the coordinator account, one per RPC endpoint.
+#[derive(Debug, Clone)]
+pub struct SubscriptionStatus(Vec<watch::Receiver<SubscriptionState>>);
+
+impl SubscriptionStatus {
+    /// Connected if any endpoint is, since updates from one are as good as from another.
+    pub fn current(&self) -> SubscriptionState {
+        let states: Vec<_> = self.0.iter().map(|state| *state.borrow()).collect();
+        if states.contains(&SubscriptionState::Connected) {
+            SubscriptionState::Connected
+        } else if states.contains(&SubscriptionState::Reconnecting) {
+            SubscriptionState::Reconnecting
+        } else {
+            SubscriptionState::Connecting
+        }
+    }
+}
+
+const RECONNECT_BACKOFF_BASE: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// How long to wait before subscribing again after `failures` failed attempts in a row.
+fn reconnect_backoff(failures: u32) -> Duration {
+    min(
+        RECONNECT_BACKOFF_BASE.saturating_mul(2u32.saturating_pow(failures)),
+        RECONNECT_BACKOFF_MAX,
+    )
+}
+
+/// Subscribes to the account at `url`, returning its data on every change until the websocket closes.
 async fn subscribe_to_account(
     url: String,
     commitment: CommitmentConfig,
-    coordinator_account: &Pubkey,
-    tx: mpsc::UnboundedSender<RpcResponse<UiAccount>>,
-    id: u64,
-) {
-    let mut retries: u64 = 0;
-    loop {
-        // wait a time before we try a reconnection
-        let sleep_time = min(600, retries.saturating_mul(5));
-        tokio::time::sleep(Duration::from_secs(sleep_time)).await;
-        retries += 1;
-        let Ok(sub_client) = PubsubClient::new(&url).await else {
-            warn!(
-                integration_test_log_marker = %IntegrationTestLogMarker::SolanaSubscription,
-                url = url,
-                subscription_number = id,
-                "Solana subscription error, could not connect to url: {url}",
-            );
-            continue;
-        };
-
+    coordinator_account: Pubkey,
+) -> Result<mpsc::UnboundedReceiver<Vec<u8>>> {
+    let sub_client = PubsubClient::new(&url).await?;
+    let (tx, rx) = mpsc::unbounded_channel();
+    let (tx_subscribed, rx_subscribed) = oneshot::channel();
+    tokio::spawn(async move {
         let mut notifications = match sub_client
             .account_subscribe(
-                coordinator_account,
+                &coordinator_account,
                 Some(RpcAccountInfoConfig {
                     encoding: Some(UiAccountEncoding::Base64Zstd),
                     commitment: Some(commitment),
@@ -90,14 +132,62 @@ async fn subscribe_to_account(
             )
             .await
         {
-            Ok((notifications, _)) => notifications,
+            Ok((notifications, _)) => {
+                let _ = tx_subscribed.send(Ok(()));
+                notifications
+            }
+            Err(err) => {
+                let _ = tx_subscribed.send(Err(err));
+                return;
+            }
+        };
+        while let Some(update) = notifications.next().await {
+            match update.value.data.decode() {
+                Some(data) => {
+                    if tx.send(data).is_err() {
+                        return;
+                    }
+                }
+                None => error!("Error decoding coordinator account"),
+            }
+        }
+    });
+    rx_subscribed.await??;
+    Ok(rx)
+}
+
+/// Forwards the coordinator account's data from `subscribe` to `tx`, subscribing again with backoff whenever
+/// the subscription fails or ends. Updates sent while we weren't subscribed are lost,
+/// so after every reconnect the account is fetched with `fetch` to catch up on them.
+async fn keep_subscribed<Sub, SubFut, Fetch, FetchFut>(
+    mut subscribe: Sub,
+    mut fetch: Fetch,
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+    state: watch::Sender<SubscriptionState>,
+    url: String,
+    id: u64,
+) where
+    Sub: FnMut() -> SubFut,
+    SubFut: Future<Output = Result<mpsc::UnboundedReceiver<Vec<u8>>>>,
+    Fetch: FnMut() -> FetchFut,
+    FetchFut: Future<Output = Result<Vec<u8>>>,
+{
+    let mut failures = 0;
+    let mut reconnecting = false;
+    loop {
+        let mut updates = match subscribe().await {
+            Ok(updates) => updates,
             Err(err) => {
-                error!(
+                let backoff = reconnect_backoff(failures);
+                warn!(
+                    integration_test_log_marker = %IntegrationTestLogMarker::SolanaSubscription,
                     url = url,
                     subscription_number = id,
                     error = err.to_string(),
-                    "Solana account subscribe error",
+                    "Solana subscription error, could not subscribe to {url}, retrying in {backoff:?}",
                 );
+                failures += 1;
+                tokio::time::sleep(backoff).await;
                 continue;
             }
         };
@@ -108,30 +198,77 @@ async fn subscribe_to_account(
             subscription_number = id,
             "Correctly subscribe to Solana url: {url}",
         );
+        failures = 0;
+        state.send_replace(SubscriptionState::Connected);
 
-        retries = 0;
-
-        loop {
-            tokio::select! {
-                update = notifications.next() => {
-                    match update {
-                        Some(data) => {
-                                if tx.send(data).is_err() {
-                                    break;
-                                }
-                        }
-                        None => {
-                            warn!(
-                                integration_test_log_marker = %IntegrationTestLogMarker::SolanaSubscription,
-                                url = url,
-                                subscription_number = id,
-                                "Solana subscription error, websocket closed");
-                            break
-                        }
+        if reconnecting {
+            match fetch().await {
+                Ok(data) => {
+                    if tx.send(data).is_err() {
+                        return;
                     }
                 }
+                Err(err) => warn!(
+                    url = url,
+                    subscription_number = id,
+                    "Couldn't fetch the coordinator account after reconnecting, updates may have been missed: {err}"
+                ),
+            }
+        }
+
+        while let Some(data) = updates.recv().await {
+            if tx.send(data).is_err() {
+                return;
             }
         }
+        warn!(
+            integration_test_log_marker = %IntegrationTestLogMarker::SolanaSubscription,
+            url = url,
+            subscription_number = id,
+            "Solana subscription error, websocket closed"
+        );
+        state.send_replace(SubscriptionState::Reconnecting);
+        reconnecting = true;
+    }
+}
+
+/// Whether the transaction never landed, so it's safe to build it again with a fresh blockhash.
+fn is_unconfirmed(err: &ClientError) -> bool {
+    match err {
+        ClientError::SolanaClientError(err) => {
+            err.get_transaction_error() == Some(TransactionError::BlockhashNotFound)
+                || match &err.kind {
+                    ClientErrorKind::RpcError(RpcError::ForUser(msg))
+                    | ClientErrorKind::Custom(msg) => msg.contains("unable to confirm transaction"),
+                    _ => false,
+                }
+        }
+        _ => false,
+    }
+}
+
+/// Calls `send` again whenever its transaction fails to confirm, up to `confirmation_retries` times.
+/// `send` must build and sign a new transaction on every call, which fetches a fresh blockhash.
+async fn send_with_confirmation_retries<F, Fut>(
+    confirmation_retries: usize,
+    mut send: F,
+) -> Result<Signature, ClientError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Signature, ClientError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match send().await {
+            Err(err) if attempt < confirmation_retries && is_unconfirmed(&err) => {
+                attempt += 1;
+                warn!(
+                    attempt,
+                    "Transaction failed to confirm, resubmitting with a fresh blockhash: {err}"
+                );
+            }
+            result => return result,
+        }
     }
 }
 
@@ -163,9 +300,17 @@ impl SolanaBackend {
             program_coordinators,
             cluster,
             backup_clusters,
+            confirmation_retries: DEFAULT_CONFIRMATION_RETRIES,
         })
     }
 
+    /// How many times a transaction that fails to confirm (expired blockhash or confirmation timeout)
+    /// is resubmitted with a fresh blockhash before giving up on that RPC.
+    pub fn with_confirmation_retries(mut self, confirmation_retries: usize) -> Self {
+        self.confirmation_retries = confirmation_retries;
+        self
+    }
+
     pub async fn start(
         self,
         run_id: String,
@@ -176,59 +321,58 @@ impl SolanaBackend {
 
         let (tx_subscribe, mut rx_subscribe) = mpsc::unbounded_channel();
 
-        let tx_subscribe_ = tx_subscribe.clone();
-
-        let mut subscription_number = 1;
-        let url = self.cluster.clone().ws_url().to_string();
-        tokio::spawn(async move {
-            subscribe_to_account(
-                url,
-                commitment,
-                &coordinator_account,
-                tx_subscribe_,
-                subscription_number,
-            )
-            .await
-        });
-
-        for cluster in self.backup_clusters.clone() {
-            subscription_number += 1;
-            let tx_subscribe_ = tx_subscribe.clone();
+        // the backup clusters are subscribed to alongside the main one, so if one drops we keep getting updates from the others.
+        let clusters = std::iter::once(self.cluster.clone()).chain(self.backup_clusters.clone());
+        let mut subscription_states = vec![];
+        for ((index, cluster), program_coordinator) in
+            clusters.enumerate().zip(self.program_coordinators.clone())
+        {
+            let (tx_state, rx_state) = watch::channel(SubscriptionState::Connecting);
+            subscription_states.push(rx_state);
+            let url = cluster.ws_url().to_string();
+            let subscription_number = index as u64 + 1;
+            let tx_subscribe = tx_subscribe.clone();
             tokio::spawn(async move {
-                subscribe_to_account(
-                    cluster.ws_url().to_string().clone(),
-                    commitment,
-                    &coordinator_account,
-                    tx_subscribe_,
+                keep_subscribed(
+                    || subscribe_to_account(url.clone(), commitment, coordinator_account),
+                    || {
+                        let program_coordinator = program_coordinator.clone();
+                        async move {
+                            Ok(program_coordinator
+                                .rpc()
+                                .get_account_data(&coordinator_account)
+                                .await?)
+                        }
+                    },
+                    tx_subscribe,
+                    tx_state,
+                    url.clone(),
                     subscription_number,
                 )
                 .await
             });
         }
+        drop(tx_subscribe);
         tokio::spawn(async move {
             let mut last_nonce = 0;
-            while let Some(update) = rx_subscribe.recv().await {
-                match update.value.data.decode() {
-                    Some(data) => {
-                        match psyche_solana_coordinator::coordinator_account_from_bytes(&data) {
-                            Ok(account) => {
-                                if account.nonce > last_nonce {
-                                    trace!(
-                                        nonce = account.nonce,
-                                        last_nonce = last_nonce,
-                                        "Coordinator account update"
-                                    );
-                                    if let Err(err) = tx_update.send(account.state.coordinator) {
-                                        error!("Error sending coordinator update: {err}");
-                                        break;
-                                    }
-                                    last_nonce = account.nonce;
-                                }
+            while let Some(data) = rx_subscribe.recv().await {
+                match psyche_solana_coordinator::coordinator_account_from_bytes(&data) {
+                    Ok(account) => {
+                        // every subscription sends the same updates, and refetches after reconnecting repeat them.
+                        if account.nonce > last_nonce {
+                            trace!(
+                                nonce = account.nonce,
+                                last_nonce = last_nonce,
+                                "Coordinator account update"
+                            );
+                            if let Err(err) = tx_update.send(account.state.coordinator) {
+                                error!("Error sending coordinator update: {err}");
+                                break;
                             }
-                            Err(err) => error!("Error deserializing coordinator account: {err}"),
+                            last_nonce = account.nonce;
                         }
                     }
-                    None => error!("Error decoding coordinator account"),
+                    Err(err) => error!("Error deserializing coordinator account: {err}"),
                 }
             }
             error!("No subscriptions available");
@@ -257,6 +401,7 @@ impl SolanaBackend {
             instance: coordinator_instance,
             account: coordinator_account,
             init: Some(init),
+            subscription_status: SubscriptionStatus(subscription_states),
         })
     }
 
@@ -608,23 +753,46 @@ impl SolanaBackend {
         Ok(signature)
     }
 
+    /// The timestamp and random seed the coordinator would tick with, read from the on-chain clock
+    /// as of the latest slot our commitment level sees, to simulate what a tick sent now would do.
+    /// A tick that's actually sent lands a few slots later, so its random seed will differ.
+    pub async fn next_tick_inputs(&self) -> Result<(u64, u64)> {
+        let clock_account = self.program_coordinators[0]
+            .rpc()
+            .get_account(&sysvar::clock::ID)
+            .await
+            .context("Failed to fetch the clock sysvar")?;
+        let clock: Clock =
+            from_account(&clock_account).ok_or(anyhow!("Unable to decode the clock sysvar"))?;
+        Ok((
+            clock.unix_timestamp as u64,
+            psyche_solana_coordinator::CoordinatorInstanceState::random_seed(
+                clock.unix_timestamp,
+                clock.slot,
+            ),
+        ))
+    }
+
     pub fn send_tick(&self, coordinator_instance: Pubkey, coordinator_account: Pubkey) {
         let program_coordinators = self.program_coordinators.clone();
+        let confirmation_retries = self.confirmation_retries;
         tokio::task::spawn(async move {
             for _ in 0..SEND_RETRIES {
                 for program_coordinator in &program_coordinators {
                     let payer = program_coordinator.payer();
-                    let pending_tx = program_coordinator
-                        .request()
-                        .accounts(
-                            psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
-                                user: payer,
-                                coordinator_instance,
-                                coordinator_account,
-                            },
-                        )
-                        .args(psyche_solana_coordinator::instruction::Tick {})
-                        .send();
+                    let pending_tx = send_with_confirmation_retries(confirmation_retries, || {
+                        program_coordinator
+                            .request()
+                            .accounts(
+                                psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
+                                    user: payer,
+                                    coordinator_instance,
+                                    coordinator_account,
+                                },
+                            )
+                            .args(psyche_solana_coordinator::instruction::Tick {})
+                            .send()
+                    });
                     match pending_tx.await {
                         Ok(tx) => {
                             info!(from = %payer, tx = %tx, "Tick transaction");
@@ -645,43 +813,46 @@ impl SolanaBackend {
         opportunistic_data: OpportunisticData,
     ) {
         let program_coordinators = self.program_coordinators.clone();
+        let confirmation_retries = self.confirmation_retries;
         tokio::task::spawn(async move {
             for _ in 0..SEND_RETRIES {
                 for program_coordinator in &program_coordinators {
                     let payer = program_coordinator.payer();
-                    let pending_tx = match opportunistic_data {
-                        OpportunisticData::WitnessStep(witness, metadata) => program_coordinator
-                            .request()
-                            .accounts(
-                                psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
-                                    user: payer,
-                                    coordinator_instance,
-                                    coordinator_account,
-                                },
-                            )
-                            .args(psyche_solana_coordinator::instruction::Witness {
-                                proof: witness.proof,
-                                participant_bloom: witness.participant_bloom,
-                                broadcast_bloom: witness.broadcast_bloom,
-                                broadcast_merkle: witness.broadcast_merkle,
-                                metadata,
-                            }),
-                        OpportunisticData::WarmupStep(witness) => program_coordinator
-                            .request()
-                            .accounts(
-                                psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
-                                    user: payer,
-                                    coordinator_instance,
-                                    coordinator_account,
-                                },
-                            )
-                            .args(psyche_solana_coordinator::instruction::WarmupWitness {
-                                proof: witness.proof,
-                                participant_bloom: witness.participant_bloom,
-                                broadcast_bloom: witness.broadcast_bloom,
-                                broadcast_merkle: witness.broadcast_merkle,
-                            }),
-                    }.send();
+                    let pending_tx = send_with_confirmation_retries(confirmation_retries, || {
+                        match opportunistic_data {
+                            OpportunisticData::WitnessStep(witness, metadata) => program_coordinator
+                                .request()
+                                .accounts(
+                                    psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
+                                        user: payer,
+                                        coordinator_instance,
+                                        coordinator_account,
+                                    },
+                                )
+                                .args(psyche_solana_coordinator::instruction::Witness {
+                                    proof: witness.proof,
+                                    participant_bloom: witness.participant_bloom,
+                                    broadcast_bloom: witness.broadcast_bloom,
+                                    broadcast_merkle: witness.broadcast_merkle,
+                                    metadata,
+                                }),
+                            OpportunisticData::WarmupStep(witness) => program_coordinator
+                                .request()
+                                .accounts(
+                                    psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
+                                        user: payer,
+                                        coordinator_instance,
+                                        coordinator_account,
+                                    },
+                                )
+                                .args(psyche_solana_coordinator::instruction::WarmupWitness {
+                                    proof: witness.proof,
+                                    participant_bloom: witness.participant_bloom,
+                                    broadcast_bloom: witness.broadcast_bloom,
+                                    broadcast_merkle: witness.broadcast_merkle,
+                                }),
+                        }.send()
+                    });
                     match pending_tx.await {
                         Ok(tx) => {
                             info!(from = %payer, tx = %tx, "Witness transaction");
@@ -705,25 +876,28 @@ impl SolanaBackend {
         check: CommitteeProof,
     ) {
         let program_coordinators = self.program_coordinators.clone();
+        let confirmation_retries = self.confirmation_retries;
         tokio::task::spawn(async move {
             for _ in 0..SEND_RETRIES {
                 for program_coordinator in &program_coordinators {
                     let payer = program_coordinator.payer();
-                    let pending_tx = program_coordinator
-                        .request()
-                        .accounts(
-                            psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
-                                user: payer,
-                                coordinator_instance,
-                                coordinator_account,
-                            },
-                        )
-                        .args(psyche_solana_coordinator::instruction::HealthCheck {
-                            id,
-                            committee: check.committee,
-                            position: check.position,
-                            index: check.index,
-                        }).send();
+                    let pending_tx = send_with_confirmation_retries(confirmation_retries, || {
+                        program_coordinator
+                            .request()
+                            .accounts(
+                                psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
+                                    user: payer,
+                                    coordinator_instance,
+                                    coordinator_account,
+                                },
+                            )
+                            .args(psyche_solana_coordinator::instruction::HealthCheck {
+                                id,
+                                committee: check.committee,
+                                position: check.position,
+                                index: check.index,
+                            }).send()
+                    });
                     match pending_tx.await {
                         Ok(tx) => {
                             info!(from = %payer, tx = %tx, "Health check transaction");
@@ -746,21 +920,24 @@ impl SolanaBackend {
         repo: HubRepo,
     ) {
         let program_coordinators = self.program_coordinators.clone();
+        let confirmation_retries = self.confirmation_retries;
         tokio::task::spawn(async move {
             for _ in 0..SEND_RETRIES {
                 for program_coordinator in &program_coordinators {
                     let payer = program_coordinator.payer();
-                    let pending_tx = program_coordinator
-                        .request()
-                        .accounts(
-                            psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
-                                user: payer,
-                                coordinator_instance,
-                                coordinator_account,
-                            },
-                        )
-                        .args(psyche_solana_coordinator::instruction::Checkpoint { repo })
-                        .send();
+                    let pending_tx = send_with_confirmation_retries(confirmation_retries, || {
+                        program_coordinator
+                            .request()
+                            .accounts(
+                                psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
+                                    user: payer,
+                                    coordinator_instance,
+                                    coordinator_account,
+                                },
+                            )
+                            .args(psyche_solana_coordinator::instruction::Checkpoint { repo })
+                            .send()
+                    });
                     match pending_tx.await {
                         Ok(tx) => {
                             info!(from = %payer, tx = %tx, "Checkpoint transaction");
@@ -776,6 +953,43 @@ impl SolanaBackend {
         });
     }
 
+    /// Unlike the other transactions, this waits for the withdraw to land,
+    /// since the client exits right after.
+    pub async fn send_withdraw(
+        &self,
+        coordinator_instance: Pubkey,
+        coordinator_account: Pubkey,
+    ) -> Result<()> {
+        for _ in 0..SEND_RETRIES {
+            for program_coordinator in &self.program_coordinators {
+                let payer = program_coordinator.payer();
+                let pending_tx = send_with_confirmation_retries(self.confirmation_retries, || {
+                    program_coordinator
+                        .request()
+                        .accounts(
+                            psyche_solana_coordinator::accounts::PermissionlessCoordinatorAccounts {
+                                user: payer,
+                                coordinator_instance,
+                                coordinator_account,
+                            },
+                        )
+                        .args(psyche_solana_coordinator::instruction::Withdraw {})
+                        .send()
+                });
+                match pending_tx.await {
+                    Ok(tx) => {
+                        info!(from = %payer, tx = %tx, "Withdraw transaction");
+                        return Ok(());
+                    }
+                    Err(err) => {
+                        warn!(from = %payer, "Error sending withdraw transaction: {err}")
+                    }
+                }
+            }
+        }
+        bail!("All attempts to send withdraw transaction failed")
+    }
+
     pub async fn get_coordinator_instance(
         &self,
         coordinator_instance: &Pubkey,
@@ -869,10 +1083,158 @@ impl WatcherBackend<psyche_solana_coordinator::ClientId> for SolanaBackendRunner
             .send_checkpoint(self.instance, self.account, checkpoint);
         Ok(())
     }
+
+    async fn send_withdraw(&mut self) -> Result<()> {
+        self.backend
+            .send_withdraw(self.instance, self.account)
+            .await
+    }
 }
 
 impl SolanaBackendRunner {
     pub fn updates(&self) -> broadcast::Receiver<Coordinator<psyche_solana_coordinator::ClientId>> {
         self.updates.resubscribe()
     }
+
+    /// How the subscriptions to the coordinator account are doing, e.g. to show when they're reconnecting.
+    pub fn subscription_status(&self) -> SubscriptionStatus {
+        self.subscription_status.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn blockhash_not_found() -> ClientError {
+        ClientError::SolanaClientError(TransactionError::BlockhashNotFound.into())
+    }
+
+    fn confirmation_timeout() -> ClientError {
+        ClientError::SolanaClientError(
+            RpcError::ForUser("unable to confirm transaction".to_string()).into(),
+        )
+    }
+
+    /// A mock RPC that fails each transaction with the given errors, in order, then confirms it.
+    async fn mock_send(
+        attempts: &AtomicUsize,
+        failures: &[fn() -> ClientError],
+    ) -> Result<Signature, ClientError> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+        match failures.get(attempt) {
+            Some(failure) => Err(failure()),
+            None => Ok(Signature::new_unique()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unconfirmed_transaction_is_resubmitted() {
+        let attempts = AtomicUsize::new(0);
+        let result =
+            send_with_confirmation_retries(3, || mock_send(&attempts, &[blockhash_not_found]))
+                .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        let attempts = AtomicUsize::new(0);
+        let result = send_with_confirmation_retries(3, || {
+            mock_send(&attempts, &[confirmation_timeout, blockhash_not_found])
+        })
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_confirmation_retries_are_bounded() {
+        let attempts = AtomicUsize::new(0);
+        let result = send_with_confirmation_retries(1, || {
+            mock_send(&attempts, &[blockhash_not_found, blockhash_not_found])
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_other_errors_are_not_resubmitted() {
+        let attempts = AtomicUsize::new(0);
+        let result = send_with_confirmation_retries(3, || {
+            mock_send(&attempts, &[|| ClientError::AccountNotFound])
+        })
+        .await;
+        assert!(matches!(result, Err(ClientError::AccountNotFound)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_resubscribes_and_refetches_after_drop() {
+        let (tx_senders, mut rx_senders) = mpsc::unbounded_channel();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (tx_state, mut rx_state) = watch::channel(SubscriptionState::Connecting);
+
+        tokio::spawn({
+            let attempts = attempts.clone();
+            let fetches = fetches.clone();
+            keep_subscribed(
+                move || {
+                    let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                    let (tx, rx) = mpsc::unbounded_channel();
+                    tx_senders.send(tx).unwrap();
+                    async move {
+                        // the first attempt to reconnect fails, like a ws endpoint that's still down
+                        if attempt == 1 {
+                            bail!("connection refused");
+                        }
+                        Ok(rx)
+                    }
+                },
+                move || {
+                    fetches.fetch_add(1, Ordering::SeqCst);
+                    async { Ok(vec![9]) }
+                },
+                tx,
+                tx_state,
+                "ws://mock".to_string(),
+                1,
+            )
+        });
+
+        let first = rx_senders.recv().await.unwrap();
+        first.send(vec![1]).unwrap();
+        assert_eq!(rx.recv().await.unwrap(), vec![1]);
+        assert_eq!(*rx_state.borrow_and_update(), SubscriptionState::Connected);
+        assert_eq!(fetches.load(Ordering::SeqCst), 0);
+
+        // the websocket drops mid-stream
+        drop(first);
+        rx_state.changed().await.unwrap();
+        assert_eq!(
+            *rx_state.borrow_and_update(),
+            SubscriptionState::Reconnecting
+        );
+
+        // the failed attempt's stream is never used
+        drop(rx_senders.recv().await.unwrap());
+        let second = rx_senders.recv().await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // whatever changed while we were gone is fetched before new updates
+        assert_eq!(rx.recv().await.unwrap(), vec![9]);
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert_eq!(*rx_state.borrow_and_update(), SubscriptionState::Connected);
+        second.send(vec![2]).unwrap();
+        assert_eq!(rx.recv().await.unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn test_reconnect_backoff_is_capped() {
+        assert_eq!(reconnect_backoff(0), RECONNECT_BACKOFF_BASE);
+        assert_eq!(reconnect_backoff(3), RECONNECT_BACKOFF_BASE * 8);
+        assert_eq!(reconnect_backoff(100), RECONNECT_BACKOFF_MAX);
+    }
 }
@@ -1,6 +1,7 @@
 use crate::{
     app::{AppBuilder, AppParams, Tabs, TAB_NAMES},
     backend::SolanaBackend,
+    run_metadata::{update_run_metadata, validate_run_id},
 };
 
 use anchor_client::{
@@ -14,13 +15,12 @@ use anchor_client::{
     Cluster,
 };
 use anyhow::{bail, Context, Result};
-use bytemuck::Zeroable;
 use clap::{Args, Parser, Subcommand};
 use psyche_client::{print_identity_keys, read_identity_secret_key, TrainArgs};
 use psyche_coordinator::{
     get_data_index_for_step,
     model::{Checkpoint, Model},
-    CoordinatorConfig, CoordinatorProgress,
+    CoordinatorProgress, RunConfig, RunState,
 };
 use psyche_core::sha256;
 use psyche_network::SecretKey;
@@ -28,7 +28,6 @@ use psyche_solana_coordinator::find_coordinator_instance;
 use psyche_tui::{maybe_start_render_loop, LogOutput};
 use rand::SeedableRng;
 use rand_chacha::ChaCha8Rng;
-use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::{io::Cursor, path::PathBuf, time::Duration};
@@ -43,6 +42,7 @@ mod app;
 mod backend;
 mod network_identity;
 mod retry;
+mod run_metadata;
 
 #[derive(Parser, Debug)]
 struct CliArgs {
@@ -65,12 +65,6 @@ struct ClusterArgs {
     ws_rpc: String,
 }
 
-#[derive(Serialize, Deserialize, Zeroable)]
-struct State {
-    pub config: CoordinatorConfig,
-    pub model: Model,
-}
-
 #[derive(clap::ValueEnum, Clone, Debug)]
 enum ShowChoices {
     Config,
@@ -144,15 +138,19 @@ enum Commands {
         switch_to_hub: bool,
 
         // metadata
+        /// Human-readable name of the run, at most 64 bytes.
         #[clap(long)]
         name: Option<String>,
 
+        /// Description of the run, at most 280 bytes.
         #[clap(long)]
         description: Option<String>,
 
+        /// Number of parameters of the model, for display only.
         #[clap(long)]
         num_parameters: Option<u64>,
 
+        /// Vocabulary size of the model's tokenizer, for display only.
         #[clap(long)]
         vocab_size: Option<u64>,
         // end metadata
@@ -172,6 +170,10 @@ enum Commands {
 
         #[clap(long, env)]
         count: Option<u64>,
+
+        /// Print what the next tick would do to the run instead of sending it.
+        #[clap(long, default_value_t = false)]
+        dry_run: bool,
     },
     SetFutureEpochRates {
         #[clap(flatten)]
@@ -218,6 +220,9 @@ enum Commands {
         ws_rpc_3: String,
         #[clap(long, env)]
         authorizer: Option<Pubkey>,
+        /// How many times to resubmit a transaction, with a fresh blockhash, when it fails to confirm.
+        #[clap(long, env, default_value_t = 3)]
+        confirmation_retries: usize,
     },
 
     // Prints the help, optionally as markdown. Used for docs generation.
@@ -286,6 +291,7 @@ async fn async_main() -> Result<()> {
             join_authority,
         } => {
             let run_id = run_id.trim_matches('"').to_string(); // Trim quotes, if any
+            validate_run_id(&run_id)?;
             let key_pair: Arc<Keypair> = Arc::new(wallet.try_into()?);
             let backend = SolanaBackend::new(
                 cluster.into(),
@@ -379,7 +385,7 @@ async fn async_main() -> Result<()> {
 
             let (config, mut model) = match config_path {
                 Some(config_path) => {
-                    let state: State = toml::from_str(std::str::from_utf8(
+                    let state = RunConfig::from_toml(std::str::from_utf8(
                         &std::fs::read(&config_path).with_context(|| {
                             format!("failed to read config toml file {config_path:?}")
                         })?,
@@ -406,25 +412,13 @@ async fn async_main() -> Result<()> {
 
             let metadata = {
                 let mut metadata = account.state.metadata;
-
-                if let Some(name) = name {
-                    metadata.name = name
-                        .as_str()
-                        .try_into()
-                        .context("run metadata: name failed to convert to FixedString")?;
-                }
-                if let Some(description) = description {
-                    metadata.description = description
-                        .as_str()
-                        .try_into()
-                        .context("run metadata: description failed to convert to FixedString")?;
-                }
-                if let Some(num_parameters) = num_parameters {
-                    metadata.num_parameters = num_parameters;
-                }
-                if let Some(vocab_size) = vocab_size {
-                    metadata.vocab_size = vocab_size;
-                }
+                update_run_metadata(
+                    &mut metadata,
+                    name.as_deref(),
+                    description.as_deref(),
+                    num_parameters,
+                    vocab_size,
+                )?;
                 // only include if it's different
                 (metadata != account.state.metadata).then_some(metadata)
             };
@@ -490,6 +484,7 @@ async fn async_main() -> Result<()> {
             run_id,
             ms_interval,
             count,
+            dry_run,
         } => {
             let run_id = run_id.trim_matches('"').to_string(); // Trim quotes, if any
             let key_pair: Arc<Keypair> = Arc::new(wallet.try_into()?);
@@ -505,6 +500,33 @@ async fn async_main() -> Result<()> {
                 .get_coordinator_instance(&coordinator_instance)
                 .await?;
             let coordinator_account = coordinator_instance_state.coordinator_account;
+            if dry_run {
+                let state = backend
+                    .get_coordinator_account(&coordinator_account)
+                    .await?
+                    .state;
+                let pending_clients = match state.coordinator.run_state {
+                    RunState::WaitingForMembers => {
+                        Some(state.clients_state.get_active_clients_ids())
+                    }
+                    _ => None,
+                };
+                let (unix_timestamp, random_seed) = backend.next_tick_inputs().await?;
+                match state
+                    .coordinator
+                    .simulate_tick(pending_clients, unix_timestamp, random_seed)
+                {
+                    Ok(simulated) => {
+                        println!(
+                            "Ticking run {} now would result in {:?}:",
+                            run_id, simulated.result
+                        );
+                        print!("{}", simulated.summary);
+                    }
+                    Err(err) => println!("Ticking run {} now would fail: {}", run_id, err),
+                }
+                return Ok(());
+            }
             let mut interval = interval(Duration::from_millis(ms_interval));
             interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
             for _ in 0..count.unwrap_or(u64::MAX) {
@@ -612,12 +634,14 @@ async fn async_main() -> Result<()> {
             rpc_3,
             ws_rpc_3,
             authorizer,
+            confirmation_retries,
         } => {
             psyche_client::prepare_environment();
 
             let hub_read_token = std::env::var("HF_TOKEN").ok();
             let checkpoint_upload_info = args.checkpoint_config()?;
             let eval_tasks = args.eval_tasks()?;
+            let (data_parallelism, tensor_parallelism) = args.parallelism()?;
 
             info!(
                 "============ Client Startup at {} ============",
@@ -677,10 +701,14 @@ async fn async_main() -> Result<()> {
                 run_id,
                 p2p_port: args.bind_p2p_port,
                 p2p_interface: args.bind_p2p_interface,
-                data_parallelism: args.data_parallelism,
-                tensor_parallelism: args.tensor_parallelism,
+                data_parallelism,
+                tensor_parallelism,
                 micro_batch_size: args.micro_batch_size,
+                max_micro_batches: args.max_micro_batches_per_step.map(|x| x as usize),
                 write_gradients_dir: args.write_gradients_dir,
+                write_run_summary: args.write_run_summary,
+                write_round_results: args.write_round_results,
+                log_every_n_steps: args.log_every_n_steps,
                 eval_task_max_docs: args.eval_task_max_docs,
                 eval_tasks,
                 checkpoint_upload_info,
@@ -688,10 +716,29 @@ async fn async_main() -> Result<()> {
                 wandb_info,
                 optim_stats: args.optim_stats_steps,
                 grad_accum_in_fp32: args.grad_accum_in_fp32,
+                sparse_value_dtype: args.sparse_value_dtype,
                 dummy_training_delay_secs: args.dummy_training_delay_secs,
+                init_from: args.init_from(),
+                special_tokens: args.special_tokens(),
                 max_concurrent_parameter_requests: args.max_concurrent_parameter_requests,
+                max_queued_distro_results: args.max_queued_distro_results as usize,
+                min_free_device_memory_mb: args.min_free_device_memory_mb,
+                verify_checkpoint_numerics: args.verify_checkpoint_numerics,
+                cpu_fallback: args.cpu_fallback,
                 max_concurrent_downloads: args.max_concurrent_downloads,
+                max_concurrent_downloads_per_peer: args.max_concurrent_downloads_per_peer as usize,
+                parameter_serve_limit: args.parameter_serve_limit(),
+                upload_rate_limit: args.upload_rate_limit,
+                p2p_idle_timeout: args.p2p_idle_timeout_secs.map(Duration::from_secs),
+                blob_store: args.blob_store(),
+                compression_level: args.compression,
+                gossip_config: args.gossip_config(),
+                nat_probe_timeout: args.nat_probe_timeout(),
+                health_probe_interval: Duration::from_secs(args.health_probe_interval_secs),
+                health_probe_timeout: Duration::from_secs(args.health_probe_timeout_secs),
+                model_request_connect_retry: args.model_request_connect_retry(),
                 authorizer,
+                confirmation_retries,
             })
             .build()
             .await
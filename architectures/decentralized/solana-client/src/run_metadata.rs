@@ -0,0 +1,82 @@
+use anyhow::{bail, Result};
+use psyche_coordinator::SOLANA_MAX_STRING_LEN;
+use psyche_core::FixedString;
+use psyche_solana_coordinator::RunMetadata;
+
+/// Converts a user-provided string into an on-chain field,
+/// erroring instead of truncating it if it's too long to fit.
+pub fn fixed_string_arg<const L: usize>(field: &str, value: &str) -> Result<FixedString<L>> {
+    if value.len() > L {
+        bail!(
+            "{field} is {} bytes long, but at most {L} bytes fit on-chain",
+            value.len()
+        );
+    }
+    Ok(FixedString::try_from(value)?)
+}
+
+/// The coordinator instance address is derived from the first [`SOLANA_MAX_STRING_LEN`] bytes of the run id,
+/// so longer ids would silently collide with any other run sharing that prefix.
+pub fn validate_run_id(run_id: &str) -> Result<()> {
+    fixed_string_arg::<SOLANA_MAX_STRING_LEN>("run id", run_id).map(|_| ())
+}
+
+/// Updates the given fields of `metadata`, leaving the others as they are.
+///
+/// `num_parameters` and `vocab_size` are informational, for displaying the run. They're not checked against the model.
+pub fn update_run_metadata(
+    metadata: &mut RunMetadata,
+    name: Option<&str>,
+    description: Option<&str>,
+    num_parameters: Option<u64>,
+    vocab_size: Option<u64>,
+) -> Result<()> {
+    if let Some(name) = name {
+        metadata.name = fixed_string_arg("run metadata name", name)?;
+    }
+    if let Some(description) = description {
+        metadata.description = fixed_string_arg("run metadata description", description)?;
+    }
+    if let Some(num_parameters) = num_parameters {
+        metadata.num_parameters = num_parameters;
+    }
+    if let Some(vocab_size) = vocab_size {
+        metadata.vocab_size = vocab_size;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+
+    #[test]
+    fn test_overlong_run_name_is_an_error() {
+        let mut metadata = RunMetadata::zeroed();
+        let name = "n".repeat(SOLANA_MAX_STRING_LEN + 1);
+        let err = update_run_metadata(&mut metadata, Some(&name), None, Some(7), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("run metadata name is 65 bytes long, but at most {SOLANA_MAX_STRING_LEN} bytes fit on-chain")
+        );
+        // nothing was applied
+        assert_eq!(metadata, RunMetadata::zeroed());
+
+        let name = "n".repeat(SOLANA_MAX_STRING_LEN);
+        update_run_metadata(&mut metadata, Some(&name), Some("a run"), Some(7), Some(32)).unwrap();
+        assert_eq!(metadata.name.to_string(), name);
+        assert_eq!(metadata.description.to_string(), "a run");
+        assert_eq!(metadata.num_parameters, 7);
+        assert_eq!(metadata.vocab_size, 32);
+    }
+
+    #[test]
+    fn test_overlong_run_id_is_an_error() {
+        assert!(validate_run_id(&"r".repeat(SOLANA_MAX_STRING_LEN)).is_ok());
+        assert!(validate_run_id(&"r".repeat(SOLANA_MAX_STRING_LEN + 1))
+            .unwrap_err()
+            .to_string()
+            .starts_with("run id is 65 bytes long"));
+    }
+}
@@ -25,7 +25,8 @@ use ts_rs::TS;
 #[ts(rename = "SolanaClient")]
 pub struct Client {
     pub id: ClientId,
-    pub _unused: [u8; 8],
+    /// When the client last started waiting to join, in order of joins. Used to find the oldest pending client.
+    pub join_order: u64,
     pub earned: u64,
     pub slashed: u64,
     pub active: u64,
@@ -38,6 +39,7 @@ impl Debug for Client {
             .field("earned", &self.earned)
             .field("slashed", &self.slashed)
             .field("active", &self.active)
+            .field("join_order", &self.join_order)
             .finish()
     }
 }
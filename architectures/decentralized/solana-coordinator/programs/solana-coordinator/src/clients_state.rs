@@ -1,6 +1,8 @@
 use anchor_lang::prelude::*;
 use bytemuck::Pod;
 use bytemuck::Zeroable;
+use psyche_coordinator::epoch_settlement;
+use psyche_coordinator::PendingClientsFullPolicy;
 use psyche_core::FixedVec;
 use psyche_core::SizedIterator;
 use serde::Deserialize;
@@ -69,6 +71,144 @@ impl ClientsState {
         SizedIterator::new(iter, size)
     }
 
+    /// Marks `id` as waiting to join the next epoch, adding it if it's new.
+    /// Returns whether it was new. Fails with `PendingClientsFull` if
+    /// `max_pending` clients are already waiting, unless `policy` lets it take
+    /// the place of the one that's been waiting the longest. `max_pending` is
+    /// kept below our capacity, so there's always a place to evict into.
+    /// An evicted client's entry is removed, freeing its place, unless it still
+    /// has rewards to claim or is part of the running epoch (`in_epoch`), in
+    /// which case it's only taken out of line.
+    pub fn join(
+        &mut self,
+        id: ClientId,
+        max_pending: usize,
+        policy: PendingClientsFullPolicy,
+        in_epoch: impl Fn(&ClientId) -> bool,
+    ) -> Result<bool> {
+        let max_pending = max_pending.min(self.clients.capacity() - 1);
+        let mut existing =
+            self.clients.iter().position(|x| x.id.signer == id.signer);
+        if let Some(index) = existing {
+            let client = &self.clients[index];
+            if client.id != id {
+                return err!(ProgramError::ClientIdMismatch);
+            }
+            if client.active == self.next_active {
+                // already waiting, so it keeps its place in line
+                self.clients[index].id = id; // IMPORTANT. Equality is on wallet key but includes ephemeral p2p key
+                return Ok(false);
+            }
+        }
+
+        let next_active = self.next_active;
+        let pending = self
+            .clients
+            .iter()
+            .filter(|x| x.active == next_active)
+            .count();
+        if pending >= max_pending {
+            match policy {
+                PendingClientsFullPolicy::Reject => {
+                    return err!(ProgramError::PendingClientsFull);
+                },
+                PendingClientsFullPolicy::EvictOldest => {
+                    let oldest = self
+                        .clients
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, x)| x.active == next_active)
+                        .min_by_key(|(_, x)| x.join_order)
+                        .map(|(index, _)| index);
+                    if let Some(oldest) = oldest {
+                        let evicted = self.clients[oldest];
+                        msg!("Evicting pending client {}", evicted.id.signer);
+                        if evicted.earned == 0
+                            && evicted.slashed == 0
+                            && !in_epoch(&evicted.id)
+                        {
+                            self.clients.remove(oldest);
+                            if let Some(index) = existing.as_mut() {
+                                if *index > oldest {
+                                    *index -= 1;
+                                }
+                            }
+                        } else {
+                            // as if it had last joined for a previous epoch
+                            self.clients[oldest].active =
+                                next_active.wrapping_sub(1);
+                        }
+                    }
+                },
+            }
+        }
+
+        let join_order = self
+            .clients
+            .iter()
+            .map(|x| x.join_order + 1)
+            .max()
+            .unwrap_or_default();
+        match existing {
+            Some(index) => {
+                let client = &mut self.clients[index];
+                client.id = id; // IMPORTANT. Equality is on wallet key but includes ephemeral p2p key
+                client.active = next_active;
+                client.join_order = join_order;
+                Ok(false)
+            },
+            None => {
+                if self
+                    .clients
+                    .push(Client {
+                        id,
+                        join_order,
+                        earned: 0,
+                        slashed: 0,
+                        active: next_active,
+                    })
+                    .is_err()
+                {
+                    return err!(ProgramError::ClientsFull);
+                }
+                Ok(true)
+            },
+        }
+    }
+
+    /// Credits the clients of an epoch that just ended with their earnings and slashes, see `epoch_settlement`.
+    /// `finished_clients` and `exited_clients` must be in the same order as our own clients.
+    pub fn settle_epoch(
+        &mut self,
+        finished_clients: &[psyche_coordinator::Client<ClientId>],
+        exited_clients: &[psyche_coordinator::Client<ClientId>],
+    ) {
+        let rates = self.current_epoch_rates;
+        let mut i = 0;
+        let mut j = 0;
+        for client in self.clients.iter_mut() {
+            let mut finished = None;
+            if i < finished_clients.len() && client.id == finished_clients[i].id
+            {
+                finished = Some(finished_clients[i].state);
+                i += 1;
+            }
+            let mut exited = None;
+            if j < exited_clients.len() && client.id == exited_clients[j].id {
+                exited = Some(exited_clients[j].state);
+                j += 1;
+            }
+            let (earned, slashed) = epoch_settlement(
+                finished,
+                exited,
+                rates.earning_rate,
+                rates.slashing_rate,
+            );
+            client.earned += earned;
+            client.slashed += slashed;
+        }
+    }
+
     pub fn find_signer(&self, signer: &Pubkey) -> Result<&ClientId> {
         match self.clients.iter().find(|x| x.id.signer == *signer) {
             Some(client) => Ok(&client.id),
@@ -76,3 +216,251 @@ impl ClientsState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_id(i: u8) -> ClientId {
+        ClientId::new(Pubkey::new_from_array([i; 32]), [i; 32])
+    }
+
+    fn pending(state: &ClientsState) -> Vec<ClientId> {
+        state.get_active_clients_ids().copied().collect()
+    }
+
+    #[test]
+    fn test_join_past_pending_limit() {
+        let mut state = ClientsState::zeroed();
+        for i in 0..3 {
+            assert!(state
+                .join(client_id(i), 3, PendingClientsFullPolicy::Reject, |_| {
+                    false
+                })
+                .unwrap());
+        }
+        // joining again while already waiting doesn't take another place
+        assert!(!state
+            .join(client_id(1), 3, PendingClientsFullPolicy::Reject, |_| false)
+            .unwrap());
+
+        assert_eq!(
+            state
+                .join(client_id(3), 3, PendingClientsFullPolicy::Reject, |_| {
+                    false
+                })
+                .unwrap_err(),
+            ProgramError::PendingClientsFull.into()
+        );
+        assert_eq!(
+            pending(&state),
+            vec![client_id(0), client_id(1), client_id(2)]
+        );
+
+        // clients that aren't waiting for the next epoch don't count
+        state.next_active += 1;
+        assert!(state
+            .join(client_id(3), 3, PendingClientsFullPolicy::Reject, |_| false)
+            .unwrap());
+        assert!(!state
+            .join(client_id(0), 3, PendingClientsFullPolicy::Reject, |_| false)
+            .unwrap());
+        assert_eq!(pending(&state), vec![client_id(0), client_id(3)]);
+    }
+
+    #[test]
+    fn test_join_evicts_oldest_pending() {
+        let mut state = ClientsState::zeroed();
+        for i in 0..3 {
+            state
+                .join(
+                    client_id(i),
+                    3,
+                    PendingClientsFullPolicy::EvictOldest,
+                    |_| false,
+                )
+                .unwrap();
+        }
+        state
+            .join(
+                client_id(3),
+                3,
+                PendingClientsFullPolicy::EvictOldest,
+                |_| false,
+            )
+            .unwrap();
+        assert_eq!(
+            pending(&state),
+            vec![client_id(1), client_id(2), client_id(3)]
+        );
+        // the evicted client had nothing to keep, so its place is freed
+        assert_eq!(state.clients.len(), 3);
+
+        // it can join again as a new client, evicting the next oldest
+        assert!(state
+            .join(
+                client_id(0),
+                3,
+                PendingClientsFullPolicy::EvictOldest,
+                |_| false
+            )
+            .unwrap());
+        assert_eq!(
+            pending(&state),
+            vec![client_id(2), client_id(3), client_id(0)]
+        );
+        assert_eq!(state.clients.len(), 3);
+    }
+
+    #[test]
+    fn test_evicted_clients_keep_rewards_and_epoch_entries() {
+        let mut state = ClientsState::zeroed();
+        for i in 0..3 {
+            state
+                .join(
+                    client_id(i),
+                    3,
+                    PendingClientsFullPolicy::EvictOldest,
+                    |_| false,
+                )
+                .unwrap();
+        }
+        state.clients[0].earned = 10;
+        let in_epoch = |id: &ClientId| *id == client_id(1);
+
+        state
+            .join(
+                client_id(3),
+                3,
+                PendingClientsFullPolicy::EvictOldest,
+                in_epoch,
+            )
+            .unwrap();
+        state
+            .join(
+                client_id(4),
+                3,
+                PendingClientsFullPolicy::EvictOldest,
+                in_epoch,
+            )
+            .unwrap();
+        assert_eq!(
+            pending(&state),
+            vec![client_id(2), client_id(3), client_id(4)]
+        );
+        // client 0 still has rewards to claim, and client 1 is being trained with, so neither entry goes
+        assert_eq!(state.clients.len(), 5);
+        assert_eq!(state.clients[0].earned, 10);
+
+        // the next one has neither, so it's removed
+        state
+            .join(
+                client_id(5),
+                3,
+                PendingClientsFullPolicy::EvictOldest,
+                in_epoch,
+            )
+            .unwrap();
+        assert_eq!(state.clients.len(), 5);
+        assert_eq!(
+            pending(&state),
+            vec![client_id(3), client_id(4), client_id(5)]
+        );
+    }
+
+    #[test]
+    fn test_pending_limit_stays_below_capacity() {
+        let mut state = ClientsState::zeroed();
+        let capacity = state.clients.capacity();
+        for i in 0..capacity - 1 {
+            let mut signer = [1; 32];
+            signer[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            let id = ClientId::new(Pubkey::new_from_array(signer), [0; 32]);
+            state
+                .join(id, capacity, PendingClientsFullPolicy::Reject, |_| false)
+                .unwrap();
+        }
+        assert_eq!(
+            state
+                .join(
+                    client_id(0),
+                    capacity,
+                    PendingClientsFullPolicy::Reject,
+                    |_| false
+                )
+                .unwrap_err(),
+            ProgramError::PendingClientsFull.into()
+        );
+        // evicting makes room even when the limit asked for is the whole capacity
+        assert!(state
+            .join(
+                client_id(0),
+                capacity,
+                PendingClientsFullPolicy::EvictOldest,
+                |_| false
+            )
+            .unwrap());
+        assert_eq!(state.clients.len(), capacity - 1);
+    }
+
+    #[test]
+    fn test_settle_epoch_matches_expected_client_delta() {
+        use psyche_coordinator::ClientState;
+        use psyche_coordinator::Coordinator;
+
+        const EARNING_RATE: u64 = 100;
+        const SLASHING_RATE: u64 = 40;
+
+        let mut state = ClientsState::zeroed();
+        state.current_epoch_rates = ClientsEpochRates {
+            earning_rate: EARNING_RATE,
+            slashing_rate: SLASHING_RATE,
+        };
+        for i in 0..6 {
+            state
+                .join(client_id(i), 6, PendingClientsFullPolicy::Reject, |_| {
+                    false
+                })
+                .unwrap();
+        }
+
+        let mut coordinator = Coordinator::<ClientId>::zeroed();
+        let finished = [(0, ClientState::Healthy), (2, ClientState::Healthy)];
+        let exited = [
+            (1, ClientState::Ejected),
+            (3, ClientState::Dropped),
+            (4, ClientState::Withdrawn),
+        ];
+        for (i, client_state) in finished {
+            let mut client = psyche_coordinator::Client::new(client_id(i));
+            client.state = client_state;
+            coordinator.epoch_state.clients.push(client).unwrap();
+        }
+        for (i, client_state) in exited {
+            let mut client = psyche_coordinator::Client::new(client_id(i));
+            client.state = client_state;
+            coordinator.epoch_state.exited_clients.push(client).unwrap();
+        }
+
+        let expected = (0..6)
+            .map(|i| {
+                coordinator.expected_client_delta(
+                    &client_id(i),
+                    EARNING_RATE,
+                    SLASHING_RATE,
+                )
+            })
+            .collect::<Vec<_>>();
+        state.settle_epoch(
+            &coordinator.epoch_state.clients,
+            &coordinator.epoch_state.exited_clients,
+        );
+        let settled = state
+            .clients
+            .iter()
+            .map(|client| client.earned as i64 - client.slashed as i64)
+            .collect::<Vec<_>>();
+        assert_eq!(settled, expected);
+        assert_eq!(settled, vec![100, -40, 100, 0, 0, 0]);
+    }
+}
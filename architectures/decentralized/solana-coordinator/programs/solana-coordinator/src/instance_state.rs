@@ -3,7 +3,6 @@ use bytemuck::Pod;
 use bytemuck::Zeroable;
 use psyche_coordinator::model::HubRepo;
 use psyche_coordinator::model::Model;
-use psyche_coordinator::ClientState;
 use psyche_coordinator::Coordinator;
 use psyche_coordinator::CoordinatorConfig;
 use psyche_coordinator::CoordinatorProgress;
@@ -19,7 +18,6 @@ use serde::Deserialize;
 use serde::Serialize;
 use ts_rs::TS;
 
-use crate::client::Client;
 use crate::clients_state::ClientsState;
 use crate::ClientId;
 use crate::ProgramError;
@@ -73,10 +71,13 @@ unsafe impl Pod for CoordinatorInstanceState {}
 
 impl CoordinatorInstanceState {
     fn get_random_seed(clock: &Clock) -> u64 {
-        let random_seed_bytes = sha256v(&[
-            &clock.unix_timestamp.to_ne_bytes(),
-            &clock.slot.to_ne_bytes(),
-        ]);
+        Self::random_seed(clock.unix_timestamp, clock.slot)
+    }
+
+    /// The random seed a tick at `unix_timestamp` in `slot` runs with.
+    pub fn random_seed(unix_timestamp: i64, slot: u64) -> u64 {
+        let random_seed_bytes =
+            sha256v(&[&unix_timestamp.to_ne_bytes(), &slot.to_ne_bytes()]);
 
         let mut random_seed: [u8; 8] = [0; 8];
         random_seed.copy_from_slice(&random_seed_bytes[..8]);
@@ -103,6 +104,7 @@ impl CoordinatorInstanceState {
 
         msg!("Pre-tick run state: {}", self.coordinator.run_state);
 
+        let was_paused = self.coordinator.run_state == RunState::Paused;
         let clock: Clock = Clock::get()?;
         match self.coordinator.tick(
             active_clients_ids,
@@ -110,7 +112,11 @@ impl CoordinatorInstanceState {
             Self::get_random_seed(&clock),
         ) {
             Ok(TickResult::Ticked) => {
-                if self.coordinator.is_warmup_just_starting()
+                if was_paused {
+                    msg!("Auto-resumed, clients have to join again");
+                    // same as resuming with set_paused(false)
+                    self.clients_state.next_active += 1;
+                } else if self.coordinator.is_warmup_just_starting()
                     && self.is_warmup_first_tick.is_true()
                 {
                     msg!("New epoch just starting, save epoch rewards rate");
@@ -128,37 +134,10 @@ impl CoordinatorInstanceState {
             Ok(TickResult::EpochEnd(success)) => {
                 msg!("Epoch end, sucecsss: {}", success);
 
-                let mut i = 0;
-                let mut j = 0;
-                let finished_clients = &self.coordinator.epoch_state.clients;
-                let exited_clients =
-                    &self.coordinator.epoch_state.exited_clients;
-
-                for client in self.clients_state.clients.iter_mut() {
-                    if i < finished_clients.len()
-                        && client.id == finished_clients[i].id
-                    {
-                        if finished_clients[i].state == ClientState::Healthy {
-                            client.earned += self
-                                .clients_state
-                                .current_epoch_rates
-                                .earning_rate;
-                        }
-                        i += 1;
-                    }
-
-                    if j < exited_clients.len()
-                        && client.id == exited_clients[j].id
-                    {
-                        if exited_clients[j].state == ClientState::Ejected {
-                            client.slashed += self
-                                .clients_state
-                                .current_epoch_rates
-                                .slashing_rate;
-                        }
-                        j += 1;
-                    }
-                }
+                self.clients_state.settle_epoch(
+                    &self.coordinator.epoch_state.clients,
+                    &self.coordinator.epoch_state.exited_clients,
+                );
             },
             Err(err) => return err!(ProgramError::from(err)),
         };
@@ -172,7 +151,14 @@ impl CoordinatorInstanceState {
         if let Err(err) = match paused {
             true => self.coordinator.pause(unix_timestamp),
             false => {
-                if !self.coordinator.config.check() {
+                if let Err(violations) = self
+                    .coordinator
+                    .config
+                    .validate_with_model(&self.coordinator.model)
+                {
+                    for violation in violations {
+                        msg!("Config sanity check failed: {}", violation);
+                    }
                     return err!(ProgramError::ConfigSanityCheckFailed);
                 }
                 if !self.coordinator.model.check() {
@@ -278,7 +264,12 @@ impl CoordinatorInstanceState {
         }
 
         if let Some(config) = config {
-            if !config.check() {
+            // a model updated alongside the config replaces the current one
+            let run_model = model.as_ref().unwrap_or(&self.coordinator.model);
+            if let Err(violations) = config.validate_with_model(run_model) {
+                for violation in violations {
+                    msg!("Config sanity check failed: {}", violation);
+                }
                 return err!(ProgramError::ConfigSanityCheckFailed);
             }
 
@@ -305,44 +296,24 @@ impl CoordinatorInstanceState {
     }
 
     pub fn join_run(&mut self, id: ClientId) -> Result<()> {
-        let exisiting = match self
-            .clients_state
-            .clients
-            .iter_mut()
-            .find(|x| x.id.signer == id.signer)
-        {
-            Some(client) => {
-                if client.id != id {
-                    return err!(ProgramError::ClientIdMismatch);
-                }
-                client.id = id; // IMPORTANT. Equality is on wallet key but includes ephemeral p2p key
-                client.active = self.clients_state.next_active;
-                msg!("Exisiting client {} re-joined", id.signer);
-                true
+        let new = self.clients_state.join(
+            id,
+            self.coordinator.config.max_pending_clients(),
+            self.coordinator.config.pending_clients_full_policy,
+            |id| {
+                let epoch_state = &self.coordinator.epoch_state;
+                epoch_state.clients.iter().any(|x| x.id == *id)
+                    || epoch_state.exited_clients.iter().any(|x| x.id == *id)
             },
-            None => false,
-        };
-
-        if !exisiting
-            && self
-                .clients_state
-                .clients
-                .push(Client {
-                    id,
-                    earned: 0,
-                    slashed: 0,
-                    active: self.clients_state.next_active,
-                    _unused: Default::default(),
-                })
-                .is_err()
-        {
-            return err!(ProgramError::ClientsFull);
-        } else {
+        )?;
+        if new {
             msg!(
                 "New client {} joined, {} total clients",
                 id.signer,
                 self.clients_state.clients.len()
             );
+        } else {
+            msg!("Exisiting client {} re-joined", id.signer);
         }
 
         if !self.coordinator.halted() {
@@ -382,4 +353,20 @@ impl CoordinatorInstanceState {
             .map_err(|err| anchor_lang::error!(ProgramError::from(err)))?;
         self.tick()
     }
+
+    pub fn withdraw(&mut self, payer: &Pubkey) -> Result<()> {
+        let id = self.clients_state.find_signer(payer)?;
+        let index = self
+            .coordinator
+            .epoch_state
+            .clients
+            .iter()
+            .position(|x| x.id == *id)
+            .ok_or(ProgramError::SignerNotAClient)?;
+
+        self.coordinator
+            .withdraw(index as u64)
+            .map_err(|err| anchor_lang::error!(ProgramError::from(err)))?;
+        self.tick()
+    }
 }
@@ -102,6 +102,12 @@ impl CoordinatorAccount {
     }
 }
 
+// the round history is sized for `MAX_STORED_ROUNDS`, make sure that still fits in an account
+const _: () = assert!(
+    CoordinatorAccount::DISCRIMINATOR.len() + std::mem::size_of::<CoordinatorAccount>()
+        <= anchor_lang::solana_program::system_instruction::MAX_PERMITTED_DATA_LENGTH as usize
+);
+
 #[derive(Debug, InitSpace)]
 #[account]
 pub struct CoordinatorInstance {
@@ -255,6 +261,14 @@ pub mod psyche_solana_coordinator {
         account.increment_nonce();
         account.state.checkpoint(ctx.accounts.user.key, repo)
     }
+
+    pub fn withdraw(
+        ctx: Context<PermissionlessCoordinatorAccounts>,
+    ) -> Result<()> {
+        let mut account = ctx.accounts.coordinator_account.load_mut()?;
+        account.increment_nonce();
+        account.state.withdraw(ctx.accounts.user.key)
+    }
 }
 
 #[derive(Accounts)]
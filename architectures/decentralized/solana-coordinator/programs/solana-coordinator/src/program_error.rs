@@ -71,6 +71,9 @@ pub enum ProgramError {
 
     #[msg("Coordinator error: Invalid committee proof")]
     CoordinatorErrorInvalidCommitteeProof,
+
+    #[msg("Pending clients full")]
+    PendingClientsFull,
 }
 
 impl From<CoordinatorError> for ProgramError {
@@ -15,6 +15,7 @@ use psyche_solana_coordinator::instruction::JoinRun;
 use psyche_solana_coordinator::instruction::SetPaused;
 use psyche_solana_coordinator::instruction::Tick;
 use psyche_solana_coordinator::instruction::Update;
+use psyche_solana_coordinator::instruction::Withdraw;
 use psyche_solana_coordinator::instruction::Witness;
 use psyche_solana_coordinator::logic::FreeCoordinatorParams;
 use psyche_solana_coordinator::logic::InitCoordinatorParams;
@@ -207,3 +208,25 @@ pub async fn process_coordinator_witness(
         .process_instruction_with_signers(instruction, payer, &[user])
         .await
 }
+
+pub async fn process_coordinator_withdraw(
+    endpoint: &mut ToolboxEndpoint,
+    payer: &Keypair,
+    user: &Keypair,
+    coordinator_instance: &Pubkey,
+    coordinator_account: &Pubkey,
+) -> Result<Signature, ToolboxEndpointError> {
+    let accounts = PermissionlessCoordinatorAccounts {
+        user: user.pubkey(),
+        coordinator_instance: *coordinator_instance,
+        coordinator_account: *coordinator_account,
+    };
+    let instruction = Instruction {
+        accounts: accounts.to_account_metas(None),
+        data: Withdraw {}.data(),
+        program_id: psyche_solana_coordinator::ID,
+    };
+    endpoint
+        .process_instruction_with_signers(instruction, payer, &[user])
+        .await
+}
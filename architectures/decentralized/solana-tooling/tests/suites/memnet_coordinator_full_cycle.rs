@@ -1,14 +1,20 @@
 use psyche_coordinator::model::Checkpoint;
+use psyche_coordinator::model::GradientStaleness;
 use psyche_coordinator::model::HubRepo;
 use psyche_coordinator::model::LLMArchitecture;
 use psyche_coordinator::model::LLMTrainingDataLocation;
 use psyche_coordinator::model::LLMTrainingDataType;
 use psyche_coordinator::model::Model;
 use psyche_coordinator::model::LLM;
+use psyche_coordinator::ClientState;
 use psyche_coordinator::CoordinatorConfig;
+use psyche_coordinator::DataAssignmentStrategy;
+use psyche_coordinator::PendingClientsFullPolicy;
 use psyche_coordinator::RunState;
 use psyche_coordinator::WitnessProof;
 use psyche_core::ConstantLR;
+use psyche_core::DistroConfig;
+use psyche_core::FixedString;
 use psyche_core::LearningRateSchedule;
 use psyche_core::OptimizerDefinition;
 use psyche_solana_authorizer::logic::AuthorizationGrantorUpdateParams;
@@ -25,6 +31,7 @@ use psyche_solana_tooling::process_coordinator_instructions::process_coordinator
 use psyche_solana_tooling::process_coordinator_instructions::process_coordinator_join_run;
 use psyche_solana_tooling::process_coordinator_instructions::process_coordinator_set_paused;
 use psyche_solana_tooling::process_coordinator_instructions::process_coordinator_tick;
+use psyche_solana_tooling::process_coordinator_instructions::process_coordinator_withdraw;
 use psyche_solana_tooling::process_coordinator_instructions::process_coordinator_witness;
 use psyche_solana_tooling::process_coordinator_instructions::process_update;
 use solana_sdk::signature::Keypair;
@@ -95,13 +102,26 @@ pub async fn run() {
             cooldown_time: 1,
             max_round_train_time: 3,
             round_witness_time: 1,
+            witness_timeout: 0,
             min_clients: 1,
             init_min_clients: 1,
             global_batch_size_start: 1,
             global_batch_size_end: 1,
             global_batch_size_warmup_tokens: 0,
             verification_percent: 0,
+            witness_quorum_percent: 0,
+            committee_salt: FixedString::new(),
+            witness_salt: FixedString::new(),
+            num_stored_rounds: 0,
+            data_assignment_strategy: DataAssignmentStrategy::Contiguous,
+            max_pending_clients: 0,
+            pending_clients_full_policy: PendingClientsFullPolicy::Reject,
+            dataset_samples: 0,
+            max_data_epochs: 0,
+            auto_resume_after: 0,
+            warmup_grace_period: 0,
             witness_nodes: 1,
+            witness_quorum: 0,
             rounds_per_epoch: 10,
             total_steps: 100,
         }),
@@ -112,15 +132,20 @@ pub async fn run() {
             data_type: LLMTrainingDataType::Pretraining,
             data_location: LLMTrainingDataLocation::default(),
             lr_schedule: LearningRateSchedule::Constant(ConstantLR::default()),
-            optimizer: OptimizerDefinition::Distro {
+            optimizer: OptimizerDefinition::Distro(DistroConfig {
                 clip_grad_norm: None,
                 compression_decay: 1.0,
                 compression_topk: 1,
                 compression_chunk: 1,
                 quantize_1bit: false,
+                momentum: 0.0,
+                dampening: 0.0,
+                nesterov: false,
                 weight_decay: None,
-            },
+            }),
             cold_start_warmup_steps: 0,
+            config_hash: FixedString::new(),
+            gradient_staleness: GradientStaleness::default(),
         })),
         None, // no explicit progress
     )
@@ -327,4 +352,46 @@ pub async fn run() {
             .run_state,
         RunState::RoundWitness
     );
+
+    // Only a client of the run can withdraw
+    assert!(process_coordinator_withdraw(
+        &mut endpoint,
+        &payer,
+        &ticker,
+        &coordinator_instance,
+        &coordinator_account,
+    )
+    .await
+    .is_err());
+    process_coordinator_withdraw(
+        &mut endpoint,
+        &payer,
+        &client,
+        &coordinator_instance,
+        &coordinator_account,
+    )
+    .await
+    .unwrap();
+
+    // The client is now withdrawn, and can't withdraw twice
+    assert_eq!(
+        get_coordinator_account_state(&mut endpoint, &coordinator_account)
+            .await
+            .unwrap()
+            .unwrap()
+            .coordinator
+            .epoch_state
+            .clients[0]
+            .state,
+        ClientState::Withdrawn
+    );
+    assert!(process_coordinator_withdraw(
+        &mut endpoint,
+        &payer,
+        &client,
+        &coordinator_instance,
+        &coordinator_account,
+    )
+    .await
+    .is_err());
 }
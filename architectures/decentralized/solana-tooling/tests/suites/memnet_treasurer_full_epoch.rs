@@ -1,4 +1,5 @@
 use psyche_coordinator::model::Checkpoint;
+use psyche_coordinator::model::GradientStaleness;
 use psyche_coordinator::model::HubRepo;
 use psyche_coordinator::model::LLMArchitecture;
 use psyche_coordinator::model::LLMTrainingDataLocation;
@@ -6,8 +7,12 @@ use psyche_coordinator::model::LLMTrainingDataType;
 use psyche_coordinator::model::Model;
 use psyche_coordinator::model::LLM;
 use psyche_coordinator::CoordinatorConfig;
+use psyche_coordinator::DataAssignmentStrategy;
+use psyche_coordinator::PendingClientsFullPolicy;
 use psyche_coordinator::WitnessProof;
 use psyche_core::ConstantLR;
+use psyche_core::DistroConfig;
+use psyche_core::FixedString;
 use psyche_core::LearningRateSchedule;
 use psyche_core::OptimizerDefinition;
 use psyche_solana_authorizer::logic::AuthorizationGranteeUpdateParams;
@@ -196,13 +201,26 @@ pub async fn run() {
                 cooldown_time: 1,
                 max_round_train_time: 3,
                 round_witness_time: 1,
+                witness_timeout: 0,
                 min_clients: 1,
                 init_min_clients: 1,
                 global_batch_size_start: 1,
                 global_batch_size_end: 1,
                 global_batch_size_warmup_tokens: 0,
                 verification_percent: 0,
+                witness_quorum_percent: 0,
+                committee_salt: FixedString::new(),
+                witness_salt: FixedString::new(),
+                num_stored_rounds: 0,
+                data_assignment_strategy: DataAssignmentStrategy::Contiguous,
+                max_pending_clients: 0,
+                pending_clients_full_policy: PendingClientsFullPolicy::Reject,
+                dataset_samples: 0,
+                max_data_epochs: 0,
+                auto_resume_after: 0,
+                warmup_grace_period: 0,
                 witness_nodes: 1,
+                witness_quorum: 0,
                 rounds_per_epoch: 4,
                 total_steps: 100,
             }),
@@ -215,15 +233,20 @@ pub async fn run() {
                 lr_schedule: LearningRateSchedule::Constant(
                     ConstantLR::default(),
                 ),
-                optimizer: OptimizerDefinition::Distro {
+                optimizer: OptimizerDefinition::Distro(DistroConfig {
                     clip_grad_norm: None,
                     compression_decay: 1.0,
                     compression_topk: 1,
                     compression_chunk: 1,
                     quantize_1bit: false,
+                    momentum: 0.0,
+                    dampening: 0.0,
+                    nesterov: false,
                     weight_decay: None,
-                },
+                }),
                 cold_start_warmup_steps: 0,
+                config_hash: FixedString::new(),
+                gradient_staleness: GradientStaleness::default(),
             })),
             progress: None,
             epoch_earning_rate: Some(earned_point_per_epoch),
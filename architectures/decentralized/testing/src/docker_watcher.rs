@@ -28,6 +28,7 @@ pub enum Response {
     UntrainedBatches(Vec<u64>),
     SolanaSubscription(String, String),
     WitnessElected(String),
+    RunFinished(String, u64),
     Error(ObservedErrorKind, String),
 }
 
@@ -296,6 +297,18 @@ impl DockerWatcher {
                             println!("Probably the test ended so we drop the log sender");
                         }
                     }
+                    IntegrationTestLogMarker::RunFinished => {
+                        let client_id = parsed_log
+                            .get("client_id")
+                            .and_then(|v| v.as_str())
+                            .unwrap()
+                            .to_string();
+                        let step = parsed_log.get("step").and_then(|v| v.as_u64()).unwrap();
+                        let response = Response::RunFinished(client_id, step);
+                        if log_sender.send(response).await.is_err() {
+                            println!("Probably the test ended so we drop the log sender");
+                        }
+                    }
                     IntegrationTestLogMarker::Error => {
                         let Some(message) = parsed_log.get("message") else {
                             continue;
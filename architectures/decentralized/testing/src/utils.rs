@@ -6,7 +6,7 @@ use anchor_client::{
 };
 use psyche_coordinator::{
     model::{Checkpoint, Model},
-    Round, RunState, NUM_STORED_ROUNDS,
+    Round, RunState, MAX_STORED_ROUNDS,
 };
 use psyche_core::FixedVec;
 use psyche_solana_coordinator::{ClientId, SOLANA_MAX_NUM_PENDING_CLIENTS};
@@ -81,7 +81,7 @@ impl SolanaTestClient {
         coordinator.state.coordinator.run_state
     }
 
-    pub async fn get_rounds(&self) -> [Round; NUM_STORED_ROUNDS] {
+    pub async fn get_rounds(&self) -> [Round; MAX_STORED_ROUNDS] {
         let coordinator = self.get_coordinator_account().await;
         coordinator.state.coordinator.epoch_state.rounds
     }
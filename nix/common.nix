@@ -81,6 +81,10 @@ let
         pname = name;
         cargoExtraArgs = "--bin ${name}";
         doCheck = false;
+        # recorded in checkpoint manifests. only set here, so the deps don't rebuild on every commit
+        env = env // {
+          PSYCHE_GIT_REVISION = gitcommit;
+        };
       }
     );
 
@@ -30,6 +30,9 @@
         "psyche-centralized-local-testnet"
         "expand-distro"
         "preview-lr"
+        "replay-gradients"
+        "simulate-coordinator"
+        "validate-checkpoint"
       ];
 
       rustPackages = builtins.listToAttrs (
@@ -1,11 +1,17 @@
-use crate::{CheckpointConfig, HubUploadInfo, WandBInfo};
+use crate::{CheckpointConfig, HubUploadInfo, InitFrom, LowDiskSpacePolicy, WandBInfo};
 
 use anyhow::{anyhow, bail, Result};
 use clap::Args;
+use psyche_coordinator::model::HubRepo;
+use psyche_core::FixedString;
 use psyche_eval::tasktype_from_name;
-use psyche_network::SecretKey;
+use psyche_modeling::{EosToks, SpecialTokens};
+use psyche_network::{
+    BlobStoreConfig, ConnectRetry, GossipConfig, ParameterServeLimit, SecretKey, SparseValueDtype,
+    MAX_COMPRESSION_LEVEL,
+};
 use psyche_tui::LogOutput;
-use std::path::PathBuf;
+use std::{fmt::Display, path::PathBuf, str::FromStr, time::Duration};
 
 pub fn read_identity_secret_key(
     identity_secret_key_path: Option<&PathBuf>,
@@ -42,6 +48,70 @@ pub fn print_identity_keys(key: Option<&PathBuf>) -> Result<()> {
     Ok(())
 }
 
+/// How many devices to use for one kind of parallelism.
+/// `auto` derives the value from the number of visible CUDA devices, see [`resolve_parallelism`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parallelism {
+    Auto,
+    Fixed(usize),
+}
+
+impl FromStr for Parallelism {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Parallelism::Auto);
+        }
+        match s.parse::<usize>() {
+            Ok(0) | Err(_) => Err(format!(
+                "invalid parallelism \"{s}\", expected \"auto\" or a positive integer"
+            )),
+            Ok(n) => Ok(Parallelism::Fixed(n)),
+        }
+    }
+}
+
+impl Display for Parallelism {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Parallelism::Auto => write!(f, "auto"),
+            Parallelism::Fixed(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+/// Resolves `auto` data / tensor parallelism against the number of visible CUDA devices,
+/// returning `(data_parallelism, tensor_parallelism)`.
+///
+/// If only one of the two is `auto`, it's set so that `data_parallelism * tensor_parallelism`
+/// uses every visible device. If both are `auto`, the default split is to use every device
+/// for data parallelism, with no tensor parallelism.
+/// With no visible CUDA devices, `auto` resolves to 1 (i.e. train on the CPU).
+pub fn resolve_parallelism(
+    data_parallelism: Parallelism,
+    tensor_parallelism: Parallelism,
+    device_count: usize,
+) -> Result<(usize, usize)> {
+    let split = |fixed: usize, name: &str| -> Result<usize> {
+        if device_count == 0 && fixed == 1 {
+            return Ok(1);
+        }
+        if device_count < fixed || device_count % fixed != 0 {
+            bail!(
+                "can't auto-detect parallelism: {device_count} visible CUDA devices can't be evenly split with {name} parallelism of {fixed}"
+            );
+        }
+        Ok(device_count / fixed)
+    };
+    match (data_parallelism, tensor_parallelism) {
+        (Parallelism::Fixed(dp), Parallelism::Fixed(tp)) => Ok((dp, tp)),
+        (Parallelism::Auto, Parallelism::Fixed(tp)) => Ok((split(tp, "tensor")?, tp)),
+        (Parallelism::Fixed(dp), Parallelism::Auto) => Ok((dp, split(dp, "data")?)),
+        (Parallelism::Auto, Parallelism::Auto) => Ok((device_count.max(1), 1)),
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct TrainArgs {
     /// Path to the clients secret key. Create a new random one running `openssl rand 32 > secret.key`. If not provided a random one will be generated.
@@ -73,19 +143,38 @@ pub struct TrainArgs {
     #[clap(long, env)]
     pub run_id: String,
 
-    #[clap(long, default_value_t = 1, env)]
-    pub data_parallelism: usize,
+    /// Number of data parallel model replicas, or `auto` to use every visible CUDA device not used for tensor parallelism.
+    #[clap(long, default_value_t = Parallelism::Fixed(1), env)]
+    pub data_parallelism: Parallelism,
 
-    #[clap(long, default_value_t = 1, env)]
-    pub tensor_parallelism: usize,
+    /// Number of devices each model replica is sharded across, or `auto` to use every visible CUDA device not used for data parallelism.
+    /// Must evenly divide the model's number of attention heads, and be at most its number of layers.
+    #[clap(long, default_value_t = Parallelism::Fixed(1), env)]
+    pub tensor_parallelism: Parallelism,
 
     #[clap(long, env, default_value_t = 1)]
     pub micro_batch_size: usize,
 
+    /// If provided, train on at most this many micro-batches per step. Samples past the cap are deferred to the next step.
+    #[clap(long, env, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_micro_batches_per_step: Option<u64>,
+
     /// If provided, every shared gradient this client sees will be written to this directory.
     #[clap(long, env)]
     pub write_gradients_dir: Option<PathBuf>,
 
+    /// If provided, a JSON summary of the run (final step, loss, evals, ...) will be written to this file when the run finishes.
+    #[clap(long, env)]
+    pub write_run_summary: Option<PathBuf>,
+
+    /// If provided, each round's result from this client (loss, tokens, batch ids, ...) will be appended to this file as a line of JSON.
+    #[clap(long, env)]
+    pub write_round_results: Option<PathBuf>,
+
+    /// Only emit the per-step training logs every N steps. Stats are still collected on every step.
+    #[clap(long, default_value_t = 1, env, value_parser = clap::value_parser!(u32).range(1..))]
+    pub log_every_n_steps: u32,
+
     #[clap(long, env)]
     pub eval_tasks: Option<String>,
 
@@ -102,6 +191,14 @@ pub struct TrainArgs {
     #[clap(long, env)]
     pub checkpoint_dir: Option<PathBuf>,
 
+    /// Free space, in MiB, to leave on the checkpoint disk on top of the estimated checkpoint size.
+    #[clap(long, default_value_t = 1024, env)]
+    pub checkpoint_min_free_disk_mib: u64,
+
+    /// What to do if the checkpoint disk doesn't have room for a checkpoint: skip it with a warning, or error out.
+    #[clap(long, value_enum, default_value_t = LowDiskSpacePolicy::Skip, env)]
+    pub checkpoint_low_disk_space: LowDiskSpacePolicy,
+
     /// Path to the Hugging Face repository containing model data and configuration.
     #[clap(long, env)]
     pub hub_repo: Option<String>,
@@ -130,24 +227,211 @@ pub struct TrainArgs {
     #[clap(long, env)]
     pub dummy_training_delay_secs: Option<u64>,
 
+    /// If provided, randomly initialize the model with this seed instead of loading the checkpoint's weights.
+    /// Only allowed if the run sets a `config_hash` and is still at its initial Hub checkpoint.
+    #[clap(long, env, conflicts_with = "init_from_checkpoint")]
+    pub init_from_scratch_seed: Option<u64>,
+
+    /// If provided, load the initial model weights from this Hugging Face repository instead of the run's checkpoint.
+    /// Only allowed if the run sets a `config_hash`, which the repository's config must match, and is still at its initial Hub checkpoint.
+    #[clap(long, env)]
+    pub init_from_checkpoint: Option<String>,
+
+    /// If provided, use this BOS token id instead of the one in the model's config.
+    #[clap(long, env)]
+    pub bos_token_id: Option<i64>,
+
+    /// If provided, use these EOS token ids (comma separated) instead of the ones in the model's config,
+    /// e.g. a chat template's end-of-turn token.
+    #[clap(long, env, value_delimiter = ',')]
+    pub eos_token_ids: Vec<i64>,
+
     #[clap(long, default_value_t = 8, env)]
     pub max_concurrent_parameter_requests: usize,
 
+    /// Maximum number of DisTrO results waiting to be broadcast. Once it's reached, training waits for the network to catch up.
+    #[clap(long, default_value_t = 8, env, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_queued_distro_results: u64,
+
+    /// If set, withdraw from the run after the current step once any device has less than this much free memory (in MiB), instead of risking an OOM mid-step.
+    #[clap(long, env)]
+    pub min_free_device_memory_mb: Option<u64>,
+
+    /// After loading a checkpoint, run a forward pass on a fixed probe input and warn if the outputs are non-finite or implausibly large.
+    #[clap(long, env)]
+    pub verify_checkpoint_numerics: bool,
+
+    /// If no CUDA device is available, run on the CPU instead of failing. Very slow, only meant for smoke tests and small evals.
+    #[clap(long, default_value_t = false, env)]
+    pub cpu_fallback: bool,
+
     #[clap(long, default_value_t = 8, env)]
     pub max_concurrent_downloads: usize,
 
+    /// Maximum number of blob downloads from a single peer at once. Raising it can speed up transfers over high-bandwidth links.
+    #[clap(long, default_value_t = 1, env, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_concurrent_downloads_per_peer: u64,
+
+    /// How many times to try requesting the model from a peer when the connection fails, before moving on to another peer.
+    #[clap(long, default_value_t = 3, env, value_parser = clap::value_parser!(u32).range(1..))]
+    pub model_request_connect_attempts: u32,
+
+    /// Wait between the first two attempts of a model request, in milliseconds. Doubled after every attempt, and randomly shortened by up to half.
+    #[clap(long, default_value_t = 500, env)]
+    pub model_request_connect_backoff_ms: u64,
+
+    /// Maximum number of model parameter requests from other peers to serve at once. 0 means unlimited.
+    #[clap(long, default_value_t = 0, env)]
+    pub max_concurrent_parameter_serves: usize,
+
+    /// Queue parameter requests beyond `--max-concurrent-parameter-serves` instead of rejecting them.
+    #[clap(long, default_value_t = false, env)]
+    pub queue_parameter_serves: bool,
+
+    /// If provided, cap the upload bandwidth used to serve training results and other blobs to peers, in bytes per second. 0 means unlimited.
+    #[clap(long, env)]
+    pub upload_rate_limit: Option<u64>,
+
+    /// If provided, p2p connections with no traffic for this many seconds are closed, and reopened when needed again.
+    #[clap(long, env)]
+    pub p2p_idle_timeout_secs: Option<u64>,
+
+    /// If provided, keep the blobs we share and download in this directory instead of in memory,
+    /// so after a restart we can still serve what we shared before without downloading it again.
+    #[clap(long, env)]
+    pub blob_store_path: Option<PathBuf>,
+
+    /// How often to check that a few of the run's other clients answer over p2p, in seconds.
+    /// A trainer that answers isn't reported in our health checks, even if its results didn't reach us.
+    #[clap(long, default_value_t = 30, env, value_parser = clap::value_parser!(u64).range(1..))]
+    pub health_probe_interval_secs: u64,
+
+    /// How long a peer has to answer a health probe before it counts as unreachable, in seconds.
+    /// Raise this on high-latency links.
+    #[clap(long, default_value_t = 5, env, value_parser = clap::value_parser!(u64).range(1..))]
+    pub health_probe_timeout_secs: u64,
+
+    /// How many peers to keep direct gossip connections to. Every message is pushed to all of them.
+    #[clap(long, default_value_t = GossipConfig::default().fanout, env)]
+    pub gossip_fanout: usize,
+
+    /// How many hops a gossip join request is forwarded for. Raise it to spread out connections in large meshes.
+    #[clap(long, default_value_t = GossipConfig::default().join_ttl, env)]
+    pub gossip_join_ttl: u16,
+
+    /// Compress gossip messages bigger than this many bytes. 0 never compresses them.
+    #[clap(long, default_value_t = GossipConfig::default().compress_above.unwrap_or_default(), env)]
+    pub gossip_compress_above: usize,
+
+    /// The biggest gossip message to send or accept, in bytes. Bigger broadcasts are split into fragments.
+    #[clap(long, default_value_t = GossipConfig::default().max_message_size, env)]
+    pub gossip_max_message_size: usize,
+
+    /// How long to wait for the rest of a fragmented gossip message before dropping it, in seconds.
+    #[clap(long, default_value_t = GossipConfig::default().fragment_timeout.as_secs(), env, value_parser = clap::value_parser!(u64).range(1..))]
+    pub gossip_fragment_timeout_secs: u64,
+
+    /// The biggest gossip message to send or put back together from fragments, in bytes.
+    #[clap(long, default_value_t = GossipConfig::default().max_reassembled_size, env)]
+    pub gossip_max_reassembled_size: usize,
+
+    /// At startup, wait up to this many seconds for each relay's STUN server while probing what kind of NAT we're behind.
+    /// Behind a symmetric NAT, direct connections fail and traffic goes through relays. 0 skips the probe.
+    #[clap(long, default_value_t = 3, env)]
+    pub nat_probe_timeout_secs: u64,
+
     // how hard to compress parameters and DisTrO results.
     // if you have fast upload and a slow CPU, set this low.
     // if you have slow upload and a fast CPU, set this high.
-    // range is from 1-9, but there's seriously diminishing returns after `2`.
+    // range is from 0 (no compression) to 9, but there's seriously diminishing returns after `2`.
     // you can do `cargo run -p psyche-network --example compress_distro_result_comparison <distro_results_postcard_file>`,
     // where that postcard file is one from `--write-gradients-dir` (use some step a few 10s or 100s in)
     // to benchmark the tradeoffs for your specific machine.
-    #[clap(long, default_value_t = 2, env)]
+    #[clap(long, default_value_t = 2, env, value_parser = clap::value_parser!(u32).range(..=MAX_COMPRESSION_LEVEL as i64))]
     pub compression: u32,
+
+    /// Precision used to send DisTrO sparse values: full, fp16, bf16 or int8.
+    /// Lower precisions make smaller payloads, but add some error. Ignored when the run quantizes to 1 bit.
+    #[clap(long, default_value_t = SparseValueDtype::Full, env)]
+    pub sparse_value_dtype: SparseValueDtype,
 }
 
 impl TrainArgs {
+    /// Returns `(data_parallelism, tensor_parallelism)`, resolving `auto` against the visible CUDA devices.
+    pub fn parallelism(&self) -> Result<(usize, usize)> {
+        resolve_parallelism(
+            self.data_parallelism,
+            self.tensor_parallelism,
+            tch::Cuda::device_count() as usize,
+        )
+    }
+
+    pub fn parameter_serve_limit(&self) -> ParameterServeLimit {
+        match (
+            self.max_concurrent_parameter_serves,
+            self.queue_parameter_serves,
+        ) {
+            (0, _) => ParameterServeLimit::Unlimited,
+            (max, false) => ParameterServeLimit::Reject(max),
+            (max, true) => ParameterServeLimit::Queue(max),
+        }
+    }
+
+    pub fn gossip_config(&self) -> GossipConfig {
+        GossipConfig {
+            fanout: self.gossip_fanout,
+            join_ttl: self.gossip_join_ttl,
+            max_message_size: self.gossip_max_message_size,
+            compress_above: (self.gossip_compress_above != 0).then_some(self.gossip_compress_above),
+            fragment_timeout: Duration::from_secs(self.gossip_fragment_timeout_secs),
+            max_reassembled_size: self.gossip_max_reassembled_size,
+        }
+    }
+
+    pub fn blob_store(&self) -> BlobStoreConfig {
+        match &self.blob_store_path {
+            Some(path) => BlobStoreConfig::Persistent { path: path.clone() },
+            None => BlobStoreConfig::Memory,
+        }
+    }
+
+    pub fn nat_probe_timeout(&self) -> Option<Duration> {
+        (self.nat_probe_timeout_secs != 0).then(|| Duration::from_secs(self.nat_probe_timeout_secs))
+    }
+
+    pub fn model_request_connect_retry(&self) -> ConnectRetry {
+        ConnectRetry {
+            max_attempts: self.model_request_connect_attempts,
+            initial_backoff: Duration::from_millis(self.model_request_connect_backoff_ms),
+        }
+    }
+
+    pub fn init_from(&self) -> Option<InitFrom> {
+        match (self.init_from_scratch_seed, &self.init_from_checkpoint) {
+            (Some(seed), _) => Some(InitFrom::Scratch { seed }),
+            (None, Some(repo_id)) => Some(InitFrom::Checkpoint(HubRepo {
+                repo_id: FixedString::from_str_truncated(repo_id),
+                revision: None,
+            })),
+            (None, None) => None,
+        }
+    }
+
+    pub fn special_tokens(&self) -> Option<SpecialTokens> {
+        let eos_token_id = match self.eos_token_ids.as_slice() {
+            [] => None,
+            [eos_token_id] => Some(EosToks::Single(*eos_token_id)),
+            eos_token_ids => Some(EosToks::Multiple(eos_token_ids.to_vec())),
+        };
+        if self.bos_token_id.is_none() && eos_token_id.is_none() {
+            return None;
+        }
+        Some(SpecialTokens {
+            bos_token_id: self.bos_token_id,
+            eos_token_id,
+        })
+    }
+
     pub fn wandb_info(&self, run_name: String) -> Result<Option<WandBInfo>> {
         let wandb_info = match std::env::var("WANDB_API_KEY") {
             Ok(wandb_api_key) => Some(WandBInfo {
@@ -186,6 +470,8 @@ impl TrainArgs {
                     hub_repo: repo,
                     hub_token: token.to_string(),
                 }),
+                min_free_disk_bytes: self.checkpoint_min_free_disk_mib * 1024 * 1024,
+                low_disk_space: self.checkpoint_low_disk_space,
             }),
             (None, Some(_), Some(_)) => {
                 bail!("hub-repo and checkpoint-dir set, but no HF_TOKEN env variable.")
@@ -196,6 +482,8 @@ impl TrainArgs {
             (_, None, Some(dir)) => Some(CheckpointConfig {
                 checkpoint_dir: dir,
                 hub_upload: None,
+                min_free_disk_bytes: self.checkpoint_min_free_disk_mib * 1024 * 1024,
+                low_disk_space: self.checkpoint_low_disk_space,
             }),
             (_, None, _) => None,
         };
@@ -245,3 +533,47 @@ pub fn prepare_environment() {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_parallelism() {
+        assert_eq!("auto".parse::<Parallelism>(), Ok(Parallelism::Auto));
+        assert_eq!("AUTO".parse::<Parallelism>(), Ok(Parallelism::Auto));
+        assert_eq!("4".parse::<Parallelism>(), Ok(Parallelism::Fixed(4)));
+        assert!("0".parse::<Parallelism>().is_err());
+        assert!("two".parse::<Parallelism>().is_err());
+    }
+
+    #[test]
+    fn test_resolve_fixed_parallelism() {
+        let (dp, tp) =
+            resolve_parallelism(Parallelism::Fixed(2), Parallelism::Fixed(4), 0).unwrap();
+        assert_eq!((dp, tp), (2, 4));
+    }
+
+    #[test]
+    fn test_resolve_auto_parallelism() {
+        // default split is all data parallel
+        let auto = |devices| resolve_parallelism(Parallelism::Auto, Parallelism::Auto, devices);
+        assert_eq!(auto(8).unwrap(), (8, 1));
+        assert_eq!(auto(1).unwrap(), (1, 1));
+        assert_eq!(auto(0).unwrap(), (1, 1));
+
+        let auto_dp =
+            |tp, devices| resolve_parallelism(Parallelism::Auto, Parallelism::Fixed(tp), devices);
+        assert_eq!(auto_dp(2, 8).unwrap(), (4, 2));
+        assert_eq!(auto_dp(8, 8).unwrap(), (1, 8));
+        assert_eq!(auto_dp(1, 0).unwrap(), (1, 1));
+        assert!(auto_dp(3, 8).is_err());
+        assert!(auto_dp(16, 8).is_err());
+        assert!(auto_dp(2, 0).is_err());
+
+        let auto_tp =
+            |dp, devices| resolve_parallelism(Parallelism::Fixed(dp), Parallelism::Auto, devices);
+        assert_eq!(auto_tp(2, 8).unwrap(), (2, 4));
+        assert!(auto_tp(3, 8).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::{
+    memory_watchdog::{CudaDeviceMemory, MemoryWatchdog},
+    peer_probes::PeerProbes,
     state::{DistroBroadcastAndPayload, FinishedBroadcast, RunManager},
     Broadcast, BroadcastType, ClientTUIState, Finished, IntegrationTestLogMarker, RunInitConfig,
     RunInitConfigAndIO, TrainingResult, NC,
@@ -8,9 +10,10 @@ use futures::future::join_all;
 use psyche_coordinator::{Commitment, Coordinator, RunState};
 use psyche_core::NodeIdentity;
 use psyche_network::{
-    allowlist, param_request_task, raw_p2p_verify, AuthenticatableIdentity, BlobTicket,
-    DownloadComplete, ModelRequestType, NetworkConnection, NetworkEvent, NetworkTUIState,
-    Networkable, NodeAddr, NodeId, SharableModel, TransmittableDownload,
+    allowlist, param_request_task, probe_peer, raw_p2p_verify, AuthenticatableIdentity, BlobTicket,
+    BroadcastError, DownloadComplete, DownloadPriority, Endpoint, ModelRequestType,
+    NetworkConnection, NetworkEvent, NetworkTUIState, Networkable, NodeAddr, NodeId, SharableModel,
+    TransmittableDownload,
 };
 use psyche_watcher::{Backend, BackendWatcher};
 use tokenizers::Tokenizer;
@@ -29,7 +32,7 @@ use tokio::{
     time::interval,
 };
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, info, trace, trace_span, warn, Instrument};
 
 pub type TUIStates = (ClientTUIState, NetworkTUIState);
 
@@ -46,6 +49,7 @@ struct DownloadRetryInfo {
     retry_time: Option<Instant>,
     ticket: BlobTicket,
     tag: u32,
+    priority: DownloadPriority,
 }
 
 const MAX_DOWNLOAD_RETRIES: usize = 3;
@@ -53,6 +57,9 @@ const REBROADCAST_SHAREABLE: Duration = Duration::from_secs(2);
 const DOWNLOAD_RETRY_BACKOFF_BASE: Duration = Duration::from_secs(2);
 const DOWNLOAD_RETRY_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 const OPPROTUNISTIC_WITNESS_INTERVAL: Duration = Duration::from_millis(500);
+const MEMORY_WATCHDOG_INTERVAL: Duration = Duration::from_secs(5);
+/// Coordinator timings are in whole seconds, so a peer clock off by more than this can make us witness at the wrong time.
+const MAX_PEER_CLOCK_SKEW_SECS: f64 = 1.0;
 
 impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'static>
     Client<T, A, B>
@@ -89,7 +96,9 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                 let (tx_health_check, mut rx_health_check) = mpsc::unbounded_channel();
                 let (tx_checkpoint, mut rx_checkpoint) = mpsc::unbounded_channel();
                 let (tx_model, mut rx_model) = mpsc::unbounded_channel();
-                let (tx_distro_result, mut rx_distro_result) = mpsc::unbounded_channel();
+                // bounded, so training slows down instead of piling up results when the network is saturated
+                let (tx_distro_result, mut rx_distro_result) =
+                    mpsc::channel(init_config.max_queued_distro_results);
                 let (tx_request_download, mut rx_request_download) = mpsc::unbounded_channel();
                 let (tx_parameters_req, mut rx_parameters_req) = mpsc::unbounded_channel();
                 let (tx_config, mut rx_config) = mpsc::unbounded_channel();
@@ -99,12 +108,32 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                 let (tx_broadcast_finished, mut rx_broadcast_finished) = mpsc::unbounded_channel();
 
                 let max_concurrent_downloads = init_config.max_concurrent_parameter_requests;
+                let model_request_connect_retry = init_config.model_request_connect_retry;
+                let health_probe_timeout = init_config.health_probe_timeout;
+                let mut health_probe_interval = interval(init_config.health_probe_interval);
+                let peer_probes = PeerProbes::new(init_config.health_probe_interval);
+
+                let mut rx_low_device_memory = match init_config.min_free_device_memory_mb {
+                    Some(min_free_mb) => match CudaDeviceMemory::new(
+                        init_config.data_parallelism * init_config.tensor_parallelism,
+                    ) {
+                        Ok(memory) => MemoryWatchdog::new(memory, min_free_mb * 1024 * 1024)
+                            .spawn(MEMORY_WATCHDOG_INTERVAL),
+                        Err(err) => {
+                            warn!("Can't read device memory, memory watchdog disabled: {err}");
+                            mpsc::channel(1).1
+                        }
+                    },
+                    None => mpsc::channel(1).1,
+                };
+                let mut low_device_memory = None;
 
                 let mut run = RunManager::<T, A>::new(RunInitConfigAndIO {
                     init_config,
 
                     tx_witness,
                     tx_health_check,
+                    peer_probes: peer_probes.clone(),
                     tx_checkpoint,
                     tx_model,
                     tx_parameters_req,
@@ -195,6 +224,15 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                             }
 
                             if old_state.map(|s| s.run_state) != Some(new_state.run_state) && new_state.run_state == RunState::RoundTrain {
+                                // the previous step is done, don't start another one we might not be able to finish
+                                if let Some(low_device_memory) = &low_device_memory {
+                                    warn!("Withdrawing from the run before step {}: {low_device_memory}", new_state.progress.step);
+                                    watcher.backend_mut().send_withdraw().await?;
+                                    if run.doing_checkpoint() {
+                                        wait_for_checkpoint = true;
+                                    }
+                                    break;
+                                }
                                 trace!(num_peers = connected_p2p_nodes.len(), "Updating p2p");
                                 let last_needed_step_blobs = new_state.progress.step.saturating_sub(2);
                                 p2p.remove_blobs_with_tag_less_than(last_needed_step_blobs);
@@ -242,10 +280,11 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                         match download_data {
                                             TransmittableDownload::DistroResult(distro_result) => {
                                                 trace!("Download complete: step {} batch id {}", distro_result.step, distro_result.batch_id);
-                                                run.apply_distro_result(hash, distro_result, None).await;
+                                                let span = trace_span!("apply_downloaded_result", step = distro_result.step, batch_id = %distro_result.batch_id);
+                                                run.apply_distro_result(hash, distro_result, None).instrument(span).await;
                                             },
                                             TransmittableDownload::ModelParameter(parameter) => {
-                                                info!("Download complete: parameter {}", parameter.name()?);
+                                                info!("Download complete: parameter {} (chunk {}/{})", parameter.name()?, parameter.chunk_index() + 1, parameter.num_chunks());
                                                 sharable_model.add_parameter(parameter).await?;
                                                 if sharable_model.is_download_complete() {
                                                     sharable_model.send_init_parameters()?;
@@ -264,8 +303,8 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                         let info = retried_downloads.get(&hash);
                                         let retries = info.map(|i| i.retries).unwrap_or(0);
 
-                                        if retries >= MAX_DOWNLOAD_RETRIES {
-                                            warn!("Download failed (not retrying): {}", dl.error);
+                                        if retries >= MAX_DOWNLOAD_RETRIES || !dl.kind.is_retryable() {
+                                            warn!("Download failed (not retrying, {:?}): {}", dl.kind, dl.error);
                                             retried_downloads.remove(&hash);
                                         } else {
                                             let backoff_duration = DOWNLOAD_RETRY_BACKOFF_BASE.mul_f32(2_f32.powi(retries as i32));
@@ -282,6 +321,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                                 retry_time,
                                                 ticket: dl.blob_ticket,
                                                 tag: dl.tag,
+                                                priority: dl.priority,
                                             });
                                         }
                                     }
@@ -294,10 +334,10 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                                     warn!("Could not send model parameter {parameter_name} blob ticket. Error: {e:?}");
                                                 }
                                             },
-                                            Ok(ticket) => {
-                                                info!(parameter = parameter_name, "Sending requested model parameter blob ticket");
-                                                if let Err(e) = protocol_req_tx.send(Ok(ticket)) {
-                                                    warn!("Could not send model parameter {parameter_name} blob ticket. Error: {e:?}");
+                                            Ok(tickets) => {
+                                                info!(parameter = parameter_name, chunks = tickets.len(), "Sending requested model parameter blob tickets");
+                                                if let Err(e) = protocol_req_tx.send(Ok(tickets)) {
+                                                    warn!("Could not send model parameter {parameter_name} blob tickets. Error: {e:?}");
                                                 };
                                             }
                                         }
@@ -318,6 +358,10 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                             }
                                         }
                                     }
+                                    NetworkEvent::ChannelMessage(channel, (from, _)) => {
+                                        // the client doesn't subscribe to any side channels itself
+                                        trace!("NetworkEvent::ChannelMessage({channel}) from {from}");
+                                    }
                                 }
                             }
                         }
@@ -335,7 +379,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                 broadcast_merkle: merkle, warmup
                             })};
 
-                            p2p.broadcast(&training_result).await?;
+                            allow_no_peers(p2p.broadcast(&training_result).await)?;
                             broadcasts.push((training_result.clone(), step));
 
                             // simulate us recving it & apply like anyone else's
@@ -344,8 +388,9 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
 
                         Some(DistroBroadcastAndPayload { step, batch_id, commitment_data_hash, proof, distro_result, original_distro_result }) = rx_distro_result.recv() => {
 
+                            let span = trace_span!("broadcast_result", step, batch_id = %batch_id);
                             let transmittable_distro_result = TransmittableDownload::DistroResult(distro_result.clone());
-                            let ticket = p2p.add_downloadable(transmittable_distro_result, step).await?;
+                            let ticket = p2p.add_downloadable(transmittable_distro_result, step).instrument(span.clone()).await?;
                             let hash = ticket.hash();
                             trace!(
                                 client_id = %identity, step = step,
@@ -357,7 +402,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                             let commitment = Commitment { data_hash: commitment_data_hash, signature};
                             let training_result = Broadcast { step, proof, nonce: thread_rng().next_u32(), commitment, data: BroadcastType::TrainingResult(TrainingResult { batch_id, ticket })};
 
-                            p2p.broadcast(&training_result).await?;
+                            allow_no_peers(p2p.broadcast(&training_result).instrument(span).await)?;
                             broadcasts.push((training_result.clone(), step));
 
                             // simulate us recving it & apply like anyone else's
@@ -385,19 +430,19 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                         BroadcastType::TrainingResult(training_result) => trace!(client_id = %identity, step = broadcast.step, nonce = broadcast.nonce, batch_id = %training_result.batch_id, "Rebroadcasting training result"),
                                         BroadcastType::Finished(finished) => trace!(client_id = %identity, step = broadcast.step, nonce = broadcast.nonce, warmup = finished.warmup, "Rebroadcasting finished"),
                                     }
-                                    p2p.broadcast(broadcast).await?;
+                                    allow_no_peers(p2p.broadcast(broadcast).await)?;
                                 }
                             }
                         }
 
                         _ = retry_check_interval.tick() => {
                             let now = Instant::now();
-                            let pending_retries: Vec<(psyche_network::Hash, BlobTicket, u32)> = retried_downloads.iter()
+                            let pending_retries: Vec<(psyche_network::Hash, BlobTicket, u32, DownloadPriority)> = retried_downloads.iter()
                                 .filter(|(_, info)| info.retry_time.map(|retry_time| now >= retry_time).unwrap_or(false) && info.retries <= MAX_DOWNLOAD_RETRIES)
-                                .map(|(hash, info)| (*hash, info.ticket.clone(), info.tag))
+                                .map(|(hash, info)| (*hash, info.ticket.clone(), info.tag, info.priority))
                                 .collect();
 
-                            for (hash, ticket, tag) in pending_retries {
+                            for (hash, ticket, tag, priority) in pending_retries {
                                 if let Some(info) = retried_downloads.get_mut(&hash) {
                                     info.retry_time = None;
 
@@ -405,7 +450,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                         hex::encode(hash), info.retries);
 
                                     let other_possible_nodes = run.coordinator_state().map(all_node_addrs_shuffled).unwrap_or_default();
-                                    p2p.start_download(ticket, tag, &other_possible_nodes).await?;
+                                    p2p.start_download_with_priority(ticket, tag, &other_possible_nodes, priority).await?;
                                 }
                             }
                         }
@@ -414,6 +459,14 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                             run.try_send_opportunistic_witness().await?;
                         }
 
+                        _ = health_probe_interval.tick() => {
+                            if let Some(state) = run.coordinator_state() {
+                                let me = NodeId::from_bytes(identity.get_p2p_public_key())?;
+                                let peers: Vec<_> = participating_node_ids(state).into_iter().filter(|peer| peer != &me).collect();
+                                tokio::spawn(probe_peers(p2p.router().endpoint().clone(), PeerProbes::sample(&peers), health_probe_timeout, peer_probes.clone()));
+                            }
+                        }
+
                         Some((download_ticket, tag)) = rx_request_download.recv() => {
                             let other_possible_nodes = run.coordinator_state().map(all_node_addrs_shuffled).unwrap_or_default();
                             p2p.start_download(download_ticket, tag, &other_possible_nodes).await?;
@@ -484,6 +537,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                             busy_peers,
                                             errored_peers,
                                             num_peers,
+                                            model_request_connect_retry,
                                             param_requests_cancel_token.clone()
                                         )
                                     );
@@ -525,12 +579,15 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                             };
                             let router = p2p.router();
                             let me = NodeId::from_bytes(identity.get_p2p_public_key())?;
-                            let peer_ids: Vec<NodeId> = coordinator_state.epoch_state.clients.iter().map(|client| {
+                            let mut peer_ids: Vec<NodeId> = coordinator_state.epoch_state.clients.iter().map(|client| {
                                 let peer_id_bytes = client.id.get_p2p_public_key();
                                 NodeId::from_bytes(peer_id_bytes).unwrap()
                             })
                             .filter(|peer_id| peer_id != &me)
                             .collect();
+                            // there's just the one config to fetch, so ask the healthiest peers first
+                            let score = |peer_id: &NodeId| p2p.peer_quality(*peer_id).map_or(0.0, |quality| quality.score);
+                            peer_ids.sort_by(|a, b| score(b).total_cmp(&score(a)));
 
                             // initialize variables to request model config
                             let parameter_blob_tickets = Arc::new(std::sync::Mutex::new(Vec::new()));
@@ -551,6 +608,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
                                 busy_peers,
                                 errored_peers,
                                 num_peers,
+                                model_request_connect_retry,
                                 param_requests_cancel_token.clone()
                             ).await;
 
@@ -561,16 +619,21 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static, B: Backend<T> + 'sta
 
                             for ticket in parameter_blob_tickets {
                                 // tag 0 means when we enter a train step, it'll get wiped.
-                                p2p.start_download(ticket, 0, &[]).await?;
+                                // we can't train until we've caught up, so the checkpoint goes ahead of round data.
+                                p2p.start_download_with_priority(ticket, 0, &[], DownloadPriority::High).await?;
                             }
 
                         }
                         Some(param_blob_tickets) = rx_params_download.recv() => {
                             for ticket in param_blob_tickets {
                                 // tag 0 means when we enter a train step, it'll get wiped.
-                                p2p.start_download(ticket, 0, &[]).await?;
+                                p2p.start_download_with_priority(ticket, 0, &[], DownloadPriority::High).await?;
                             }
                         }
+                        Some(low_memory) = rx_low_device_memory.recv() => {
+                            warn!("{low_memory}, withdrawing from the run after the current step");
+                            low_device_memory = Some(low_memory);
+                        }
                         _ = param_requests_cancel_token.cancelled() => bail!("Peers were unreachable for P2P parameter requests. Try joining again"),
                         else => break
                     }
@@ -656,6 +719,18 @@ where
         .collect())
 }
 
+/// Having no gossip peers yet isn't fatal, since we keep rebroadcasting our results while they matter.
+/// Anything else that makes a broadcast fail is.
+fn allow_no_peers(result: Result<(), BroadcastError>) -> Result<(), BroadcastError> {
+    match result {
+        Err(BroadcastError::NoPeers) => {
+            debug!("No gossip peers to broadcast to yet");
+            Ok(())
+        }
+        result => result,
+    }
+}
+
 fn participating_node_ids<T: NodeIdentity>(state: &Coordinator<T>) -> Vec<NodeId> {
     state
         .epoch_state
@@ -665,6 +740,41 @@ fn participating_node_ids<T: NodeIdentity>(state: &Coordinator<T>) -> Vec<NodeId
         .collect()
 }
 
+/// Checks whether each of `peers` answers over p2p within `probe_timeout`, recording the outcome in `peer_probes`
+/// and warning about the peers whose clock is too far off from ours.
+async fn probe_peers(
+    endpoint: Endpoint,
+    peers: Vec<NodeId>,
+    probe_timeout: Duration,
+    peer_probes: PeerProbes,
+) {
+    let probes = peers.into_iter().map(|peer| {
+        let endpoint = endpoint.clone();
+        async move { (peer, probe_peer(&endpoint, peer, probe_timeout).await) }
+    });
+    for (peer, result) in join_all(probes).await {
+        peer_probes.record(peer, result.is_ok(), Instant::now());
+        match result {
+            Ok(probe) => {
+                trace!(
+                    peer = %peer.fmt_short(),
+                    "Health probe answered in {:?}, clock offset {:.3}s",
+                    probe.round_trip,
+                    probe.clock_offset_secs
+                );
+                if probe.clock_offset_secs.abs() > MAX_PEER_CLOCK_SKEW_SECS {
+                    warn!(
+                        peer = %peer.fmt_short(),
+                        "Our clock differs from this peer's by {:.1}s, which can mis-time rounds and witnessing. Check that both clocks are synced over NTP",
+                        probe.clock_offset_secs
+                    );
+                }
+            }
+            Err(err) => debug!(peer = %peer.fmt_short(), "Peer failed health probe: {err}"),
+        }
+    }
+}
+
 fn all_node_addrs_shuffled<T: NodeIdentity>(state: &Coordinator<T>) -> Vec<NodeAddr> {
     let mut addrs = participating_node_ids(state)
         .into_iter()
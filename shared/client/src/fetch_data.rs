@@ -1,6 +1,6 @@
-use psyche_coordinator::{get_batch_ids_for_node, Coordinator};
+use psyche_coordinator::{get_batch_ids_for_node, wrap_batch_id, Coordinator};
 use psyche_core::{BatchId, NodeIdentity};
-use psyche_data_provider::{DataProvider, TokenizedDataProvider};
+use psyche_data_provider::{DataProvider, PrefetchDataProvider, TokenizedDataProvider};
 use psyche_modeling::{Batch, BatchData};
 use psyche_network::AuthenticatableIdentity;
 use std::{
@@ -22,8 +22,24 @@ pub type BatchIdSet = HashSet<BatchId>;
 const MAX_RETRIES: u32 = 5;
 const BASE_DELAY_MS: u64 = 1000;
 
+/// Fetches a batch, reading it from the start of the dataset again where it's past the end of it.
+async fn get_samples(
+    data_provider: &mut impl TokenizedDataProvider,
+    batch_id: BatchId,
+    dataset_samples: Option<u64>,
+) -> anyhow::Result<Vec<Vec<i32>>> {
+    let Some(dataset_samples) = dataset_samples else {
+        return data_provider.get_samples(batch_id).await;
+    };
+    let mut samples = Vec::with_capacity(batch_id.len());
+    for wrapped in wrap_batch_id(batch_id, dataset_samples) {
+        samples.extend(data_provider.get_samples(wrapped).await?);
+    }
+    Ok(samples)
+}
+
 pub struct DataFetcher<T: NodeIdentity, A: AuthenticatableIdentity> {
-    data_provider: Arc<Mutex<DataProvider<A>>>,
+    data_provider: Arc<Mutex<PrefetchDataProvider<DataProvider<A>>>>,
     active_fetch_task: Option<(BatchStep, JoinHandle<()>)>,
     buffer_size: usize,
     _phantom: PhantomData<T>,
@@ -32,7 +48,7 @@ pub struct DataFetcher<T: NodeIdentity, A: AuthenticatableIdentity> {
 impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> DataFetcher<T, A> {
     pub fn new(data_provider: DataProvider<A>, buffer_size: usize) -> Self {
         Self {
-            data_provider: Arc::new(Mutex::new(data_provider)),
+            data_provider: Arc::new(Mutex::new(PrefetchDataProvider::new(data_provider))),
             active_fetch_task: None,
             buffer_size,
             _phantom: Default::default(),
@@ -59,6 +75,16 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> DataFetcher<T, A> {
         );
 
         let (tx_next_sample, next_sample) = mpsc::channel(self.buffer_size);
+        let dataset_samples = state.config.wrapping_dataset_samples();
+        // batches are popped off the end, and wrapping ones are read in parts.
+        let fetch_order: Vec<BatchId> = assigned_batch_ids
+            .iter()
+            .rev()
+            .flat_map(|batch_id| match dataset_samples {
+                Some(dataset_samples) => wrap_batch_id(*batch_id, dataset_samples),
+                None => vec![*batch_id],
+            })
+            .collect();
 
         if let Some((last_step, task)) = self.active_fetch_task.take() {
             trace!("Killing previous fetch task from step {last_step}.");
@@ -72,6 +98,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> DataFetcher<T, A> {
                 let data_provider = self.data_provider.clone(); // only one of these tasks will acquire the lock at once. once one dies, the lock is released for sure.
 
                 async move {
+                    data_provider.lock().await.prefetch(fetch_order);
                     loop {
                         let batch_id = {
                             match assigned_batch_ids.pop() {
@@ -85,7 +112,13 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> DataFetcher<T, A> {
 
                         let mut retry_count = 0;
                         let batch = loop {
-                            match data_provider.lock().await.get_samples(batch_id).await {
+                            match get_samples(
+                                &mut *data_provider.lock().await,
+                                batch_id,
+                                dataset_samples,
+                            )
+                            .await
+                            {
                                 Ok(batch) => break batch,
                                 Err(err) if retry_count < MAX_RETRIES => {
                                     retry_count += 1;
@@ -117,7 +150,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> DataFetcher<T, A> {
                         }
                     }
                 }
-                .instrument(trace_span!("fetch_data"))
+                .instrument(trace_span!("fetch_data", step))
             }),
         ));
 
@@ -1,15 +1,25 @@
 mod cli;
 mod client;
 mod fetch_data;
+mod memory_watchdog;
+mod peer_probes;
 mod protocol;
 mod state;
 mod testing;
 mod tui;
 
-pub use cli::{prepare_environment, print_identity_keys, read_identity_secret_key, TrainArgs};
+pub use cli::{
+    prepare_environment, print_identity_keys, read_identity_secret_key, resolve_parallelism,
+    Parallelism, TrainArgs,
+};
 pub use client::Client;
 pub use protocol::{Broadcast, BroadcastType, Finished, TrainingResult, NC};
-pub use state::{CheckpointConfig, HubUploadInfo, InitRunError, RunInitConfig, RunInitConfigAndIO};
+pub use psyche_modeling::SpecialTokens;
+pub use state::{
+    CheckpointConfig, CheckpointManifest, HubUploadInfo, InitFrom, InitRunError,
+    LowDiskSpacePolicy, RoundResult, RunInitConfig, RunInitConfigAndIO, RunSummary,
+    CHECKPOINT_MANIFEST_FILE_NAME,
+};
 pub use testing::IntegrationTestLogMarker;
 pub use tui::{ClientTUI, ClientTUIState};
 
@@ -0,0 +1,211 @@
+use anyhow::{bail, Result};
+use nvml_wrapper::Nvml;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::{sync::mpsc, time::interval};
+use tracing::{debug, warn};
+
+const MIB: u64 = 1024 * 1024;
+
+/// A source of free memory readings, one per device this client trains on.
+pub trait DeviceMemory: Send + 'static {
+    fn free_bytes(&mut self) -> Result<Vec<u64>>;
+}
+
+/// How to find one of CUDA's devices among NVML's.
+#[derive(Debug, Clone, PartialEq)]
+enum NvmlDevice {
+    Index(u32),
+    Uuid(String),
+}
+
+/// The NVML devices behind CUDA devices `0..num_devices`. NVML ignores `CUDA_VISIBLE_DEVICES`,
+/// so CUDA's device `i` is the `i`th entry there, given as an NVML index or a UUID.
+/// Indices assume the PCI bus ordering NVML uses, i.e. `CUDA_DEVICE_ORDER=PCI_BUS_ID`.
+fn nvml_devices(cuda_visible_devices: Option<&str>, num_devices: usize) -> Result<Vec<NvmlDevice>> {
+    let Some(cuda_visible_devices) = cuda_visible_devices else {
+        return Ok((0..num_devices as u32).map(NvmlDevice::Index).collect());
+    };
+    let visible = cuda_visible_devices
+        .split(',')
+        .map(str::trim)
+        .filter(|device| !device.is_empty())
+        .map(|device| match device.parse() {
+            Ok(index) => NvmlDevice::Index(index),
+            Err(_) => NvmlDevice::Uuid(device.to_string()),
+        })
+        .take(num_devices)
+        .collect::<Vec<_>>();
+    if visible.len() < num_devices {
+        bail!("training on {num_devices} devices, but CUDA_VISIBLE_DEVICES={cuda_visible_devices} only makes {} visible", visible.len());
+    }
+    Ok(visible)
+}
+
+pub struct CudaDeviceMemory {
+    nvml: Nvml,
+    devices: Vec<NvmlDevice>,
+}
+
+impl CudaDeviceMemory {
+    pub fn new(num_devices: usize) -> Result<Self> {
+        let cuda_visible_devices = std::env::var("CUDA_VISIBLE_DEVICES").ok();
+        Ok(Self {
+            nvml: Nvml::init()?,
+            devices: nvml_devices(cuda_visible_devices.as_deref(), num_devices)?,
+        })
+    }
+}
+
+impl DeviceMemory for CudaDeviceMemory {
+    fn free_bytes(&mut self) -> Result<Vec<u64>> {
+        self.devices
+            .iter()
+            .map(|device| {
+                let device = match device {
+                    NvmlDevice::Index(index) => self.nvml.device_by_index(*index)?,
+                    NvmlDevice::Uuid(uuid) => self.nvml.device_by_uuid(uuid.as_str())?,
+                };
+                Ok(device.memory_info()?.free)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Error, PartialEq)]
+#[error("device {device} has {} MiB of free memory, below the minimum of {} MiB", .free_bytes / MIB, .min_free_bytes / MIB)]
+pub struct LowDeviceMemory {
+    pub device: usize,
+    pub free_bytes: u64,
+    pub min_free_bytes: u64,
+}
+
+/// Polls device memory and reports the first time any device drops below `min_free_bytes`,
+/// so the client can withdraw between steps instead of OOMing in the middle of one.
+pub struct MemoryWatchdog<M: DeviceMemory> {
+    memory: M,
+    min_free_bytes: u64,
+}
+
+impl<M: DeviceMemory> MemoryWatchdog<M> {
+    pub fn new(memory: M, min_free_bytes: u64) -> Self {
+        Self {
+            memory,
+            min_free_bytes,
+        }
+    }
+
+    pub fn check(&mut self) -> Result<Option<LowDeviceMemory>> {
+        Ok(self
+            .memory
+            .free_bytes()?
+            .into_iter()
+            .enumerate()
+            .find(|(_, free_bytes)| *free_bytes < self.min_free_bytes)
+            .map(|(device, free_bytes)| LowDeviceMemory {
+                device,
+                free_bytes,
+                min_free_bytes: self.min_free_bytes,
+            }))
+    }
+
+    pub fn spawn(mut self, poll_interval: Duration) -> mpsc::Receiver<LowDeviceMemory> {
+        let (tx, rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut poll_interval = interval(poll_interval);
+            loop {
+                poll_interval.tick().await;
+                match self.check() {
+                    Ok(Some(low_memory)) => {
+                        let _ = tx.send(low_memory).await;
+                        return;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        warn!("Failed to read device memory, stopping memory watchdog: {err}");
+                        return;
+                    }
+                }
+                if tx.is_closed() {
+                    debug!("Memory watchdog receiver dropped, stopping");
+                    return;
+                }
+            }
+        });
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    struct MockDeviceMemory(VecDeque<Vec<u64>>);
+
+    impl DeviceMemory for MockDeviceMemory {
+        fn free_bytes(&mut self) -> Result<Vec<u64>> {
+            self.0
+                .pop_front()
+                .ok_or_else(|| anyhow::anyhow!("no more readings"))
+        }
+    }
+
+    fn watchdog(readings: &[&[u64]]) -> MemoryWatchdog<MockDeviceMemory> {
+        MemoryWatchdog::new(
+            MockDeviceMemory(readings.iter().map(|r| r.to_vec()).collect()),
+            1024 * MIB,
+        )
+    }
+
+    #[test]
+    fn test_nvml_devices_follow_cuda_visible_devices() {
+        use NvmlDevice::*;
+        assert_eq!(nvml_devices(None, 2).unwrap(), [Index(0), Index(1)]);
+        assert_eq!(
+            nvml_devices(Some("3,1,2"), 2).unwrap(),
+            [Index(3), Index(1)]
+        );
+        assert_eq!(
+            nvml_devices(Some("GPU-8f6b, 0"), 2).unwrap(),
+            [Uuid("GPU-8f6b".to_string()), Index(0)]
+        );
+        assert!(nvml_devices(Some("2"), 2).is_err());
+        assert!(nvml_devices(Some(""), 1).is_err());
+    }
+
+    #[test]
+    fn test_check_threshold() {
+        let mut watchdog = watchdog(&[&[4096 * MIB, 1024 * MIB], &[4096 * MIB, 1023 * MIB]]);
+        assert_eq!(watchdog.check().unwrap(), None);
+        assert_eq!(
+            watchdog.check().unwrap(),
+            Some(LowDeviceMemory {
+                device: 1,
+                free_bytes: 1023 * MIB,
+                min_free_bytes: 1024 * MIB,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_reports_low_memory() {
+        let mut rx =
+            watchdog(&[&[4096 * MIB], &[2048 * MIB], &[512 * MIB]]).spawn(Duration::from_millis(1));
+        let low_memory = rx.recv().await.unwrap();
+        assert_eq!(low_memory.device, 0);
+        assert_eq!(low_memory.free_bytes, 512 * MIB);
+        assert_eq!(
+            low_memory.to_string(),
+            "device 0 has 512 MiB of free memory, below the minimum of 1024 MiB"
+        );
+        // it only reports once
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_watchdog_stops_on_read_error() {
+        let mut rx = watchdog(&[&[4096 * MIB]]).spawn(Duration::from_millis(1));
+        assert!(rx.recv().await.is_none());
+    }
+}
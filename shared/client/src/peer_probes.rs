@@ -0,0 +1,104 @@
+use psyche_network::NodeId;
+use rand::{seq::SliceRandom, thread_rng};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How many peers are probed per health probe interval, so probing stays cheap in large runs.
+pub const MAX_PEERS_PROBED_PER_INTERVAL: usize = 8;
+
+/// When each peer last answered one of our p2p health probes.
+///
+/// A trainer whose results didn't reach us but that answered a recent probe is alive,
+/// just slow to reach us, so we don't report it in our health checks.
+#[derive(Debug, Clone)]
+pub struct PeerProbes {
+    answered: Arc<Mutex<HashMap<NodeId, Instant>>>,
+    probe_interval: Duration,
+}
+
+impl PeerProbes {
+    pub fn new(probe_interval: Duration) -> Self {
+        Self {
+            answered: Default::default(),
+            probe_interval,
+        }
+    }
+
+    /// How long a probe answer counts for: two full passes over `num_peers`, sampled a few per interval.
+    fn max_age(&self, num_peers: usize) -> Duration {
+        let intervals_per_pass = num_peers.div_ceil(MAX_PEERS_PROBED_PER_INTERVAL).max(1);
+        self.probe_interval * 2 * intervals_per_pass as u32
+    }
+
+    /// Picks the peers to probe next, at most [`MAX_PEERS_PROBED_PER_INTERVAL`] of them.
+    pub fn sample(peers: &[NodeId]) -> Vec<NodeId> {
+        peers
+            .choose_multiple(&mut thread_rng(), MAX_PEERS_PROBED_PER_INTERVAL)
+            .copied()
+            .collect()
+    }
+
+    pub fn record(&self, peer: NodeId, answered: bool, now: Instant) {
+        let mut probes = self.answered.lock().unwrap();
+        if answered {
+            probes.insert(peer, now);
+        } else {
+            probes.remove(&peer);
+        }
+    }
+
+    /// Whether `peer`, one of `num_peers` we take turns probing, answered a probe lately.
+    pub fn answered_recently(&self, peer: &NodeId, num_peers: usize, now: Instant) -> bool {
+        let max_age = self.max_age(num_peers);
+        self.answered
+            .lock()
+            .unwrap()
+            .get(peer)
+            .is_some_and(|at| now.duration_since(*at) <= max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_network::SecretKey;
+
+    fn node_id(seed: u8) -> NodeId {
+        SecretKey::from_bytes(&[seed; 32]).public()
+    }
+
+    #[test]
+    fn test_probe_answers_expire() {
+        let probes = PeerProbes::new(Duration::from_secs(15));
+        let (alive, silent) = (node_id(1), node_id(2));
+        let start = Instant::now();
+        let after = |secs| start + Duration::from_secs(secs);
+
+        probes.record(alive, true, start);
+        probes.record(silent, false, start);
+        assert!(probes.answered_recently(&alive, 2, after(30)));
+        assert!(!probes.answered_recently(&alive, 2, after(31)));
+        assert!(!probes.answered_recently(&silent, 2, start));
+
+        // with more peers than we probe per interval, each one is probed less often
+        let many_peers = 2 * MAX_PEERS_PROBED_PER_INTERVAL;
+        assert!(probes.answered_recently(&alive, many_peers, after(60)));
+        assert!(!probes.answered_recently(&alive, many_peers, after(61)));
+
+        // a failed probe overrides an earlier answer
+        probes.record(alive, false, after(1));
+        assert!(!probes.answered_recently(&alive, 2, after(1)));
+    }
+
+    #[test]
+    fn test_sample_is_bounded() {
+        let peers: Vec<_> = (0..20).map(node_id).collect();
+        let sample = PeerProbes::sample(&peers);
+        assert_eq!(sample.len(), MAX_PEERS_PROBED_PER_INTERVAL);
+        assert!(sample.iter().all(|peer| peers.contains(peer)));
+        assert_eq!(PeerProbes::sample(&peers[..3]).len(), 3);
+    }
+}
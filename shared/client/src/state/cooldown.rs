@@ -5,19 +5,24 @@ use psyche_coordinator::{
     Coordinator,
 };
 use psyche_core::{FixedString, NodeIdentity};
-use psyche_data_provider::{upload_model_repo_async, UploadModelError};
+use psyche_data_provider::{upload_model_repo_async, UploadModelError, UploadOptions};
 use psyche_modeling::{
     save_tensors_into_safetensors, SaveSafetensorsError, Trainer, TrainerThreadCommunicationError,
 };
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
 use tch::Tensor;
 use thiserror::Error;
 use tokio::{sync::mpsc, task::JoinHandle};
-use tracing::{error, info, info_span, Instrument};
+use tracing::{error, info, info_span, warn, Instrument};
 
 use super::{
     evals::{EvalRunner, RunningEvals},
-    CheckpointConfig,
+    manifest::CheckpointManifest,
+    CheckpointConfig, LowDiskSpacePolicy,
 };
 
 #[derive(Error, Debug)]
@@ -76,11 +81,81 @@ pub enum CheckpointError {
     #[error("Writing extra file to disk failed: {0}")]
     WriteExtraFile(#[from] tokio::io::Error),
 
+    #[error("Writing checkpoint manifest to disk failed: {0}")]
+    WriteManifest(std::io::Error),
+
     #[error("Couldn't upload model to huggingface: {0}")]
     UploadError(#[from] UploadModelError),
 
     #[error("Couldn't send checkpoint - channel closed")]
     SendCheckpoint,
+
+    #[error("Not enough disk space in {} for checkpoint: {available_bytes} bytes available, {required_bytes} required", .dir.display())]
+    InsufficientDiskSpace {
+        dir: PathBuf,
+        available_bytes: u64,
+        required_bytes: u64,
+    },
+}
+
+/// A source of free disk space readings, for checking there's room for a checkpoint before writing it.
+pub trait DiskSpace {
+    fn available_bytes(&self, path: &Path) -> io::Result<u64>;
+}
+
+pub struct StatvfsDiskSpace;
+
+impl DiskSpace for StatvfsDiskSpace {
+    fn available_bytes(&self, path: &Path) -> io::Result<u64> {
+        // the checkpoint dir is created on first save, so measure the closest ancestor that exists
+        let existing = path
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(Path::new("."));
+        let stat = nix::sys::statvfs::statvfs(existing)?;
+        Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+    }
+}
+
+/// Returns whether `dir` has room for a checkpoint of `checkpoint_bytes` plus `min_free_disk_bytes` of headroom.
+/// If it doesn't, either warns and returns `false` or errors, depending on `policy`.
+fn has_room_for_checkpoint(
+    disk: &impl DiskSpace,
+    dir: &Path,
+    checkpoint_bytes: u64,
+    min_free_disk_bytes: u64,
+    policy: LowDiskSpacePolicy,
+) -> Result<bool, CheckpointError> {
+    let available_bytes = match disk.available_bytes(dir) {
+        Ok(available_bytes) => available_bytes,
+        Err(err) => {
+            warn!(
+                "Couldn't read free disk space for {}, checkpointing anyway: {err}",
+                dir.display()
+            );
+            return Ok(true);
+        }
+    };
+    let required_bytes = checkpoint_bytes.saturating_add(min_free_disk_bytes);
+    if available_bytes >= required_bytes {
+        return Ok(true);
+    }
+    match policy {
+        LowDiskSpacePolicy::Skip => {
+            warn!(
+                available_bytes,
+                required_bytes,
+                "Not enough disk space in {} for checkpoint, skipping it",
+                dir.display()
+            );
+            Ok(false)
+        }
+        LowDiskSpacePolicy::Error => Err(CheckpointError::InsufficientDiskSpace {
+            dir: dir.to_path_buf(),
+            available_bytes,
+            required_bytes,
+        }),
+    }
 }
 
 impl CooldownStepMetadata {
@@ -95,6 +170,7 @@ impl CooldownStepMetadata {
 
         let step = state.progress.step - 1;
         let run_id = String::from(&state.run_id);
+        let manifest = CheckpointManifest::new(state, step);
         let checkpoint_extra_files = self.checkpoint_extra_files.clone();
         let checkpoint_info = self.checkpoint_info.clone();
         let tx_checkpoint = self.tx_checkpoint.clone();
@@ -129,12 +205,33 @@ impl CooldownStepMetadata {
                 let Some(CheckpointConfig {
                     hub_upload,
                     checkpoint_dir,
+                    min_free_disk_bytes,
+                    low_disk_space,
                 }) = checkpoint_info
                 else {
                     // If there was no HF checkpointing configuration, return immediately
                     return Ok(evals);
                 };
 
+                let checkpoint_bytes = variables
+                    .values()
+                    .map(|var| (var.numel() * var.kind().elt_size_in_bytes()) as u64)
+                    .chain(
+                        checkpoint_extra_files
+                            .iter()
+                            .map(|extra| std::fs::metadata(extra).map_or(0, |m| m.len())),
+                    )
+                    .sum();
+                if !has_room_for_checkpoint(
+                    &StatvfsDiskSpace,
+                    &checkpoint_dir,
+                    checkpoint_bytes,
+                    min_free_disk_bytes,
+                    low_disk_space,
+                )? {
+                    return Ok(evals);
+                }
+
                 // Start the upload process of the updated model parameters in a separate task
                 tokio::task::spawn(async move {
                     let path = checkpoint_dir.join(format!("{run_id}-step{step}"));
@@ -154,6 +251,12 @@ impl CooldownStepMetadata {
                         local.push(to);
                     }
 
+                    local.push(
+                        manifest
+                            .write(&path)
+                            .map_err(CheckpointError::WriteManifest)?,
+                    );
+
                     let Some(HubUploadInfo {
                         hub_repo,
                         hub_token,
@@ -169,6 +272,8 @@ impl CooldownStepMetadata {
                         hub_token.clone(),
                         Some(format!("step {step}")),
                         None,
+                        // a checkpoint is many gigabytes, so don't start over when an upload dies midway
+                        UploadOptions { resume: true },
                     )
                     .await
                     {
@@ -223,3 +328,33 @@ impl CooldownStep {
         self.doing_checkpoint
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedDiskSpace(u64);
+
+    impl DiskSpace for FixedDiskSpace {
+        fn available_bytes(&self, _path: &Path) -> io::Result<u64> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn test_skips_checkpoint_when_disk_is_low() {
+        let dir = Path::new("/checkpoints");
+        let disk = FixedDiskSpace(1000);
+
+        assert!(has_room_for_checkpoint(&disk, dir, 800, 200, LowDiskSpacePolicy::Skip).unwrap());
+        assert!(!has_room_for_checkpoint(&disk, dir, 900, 200, LowDiskSpacePolicy::Skip).unwrap());
+        assert!(matches!(
+            has_room_for_checkpoint(&disk, dir, 900, 200, LowDiskSpacePolicy::Error),
+            Err(CheckpointError::InsufficientDiskSpace {
+                available_bytes: 1000,
+                required_bytes: 1100,
+                ..
+            })
+        ));
+    }
+}
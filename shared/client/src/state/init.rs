@@ -1,31 +1,37 @@
-use crate::{fetch_data::DataFetcher, IntegrationTestLogMarker, WandBInfo};
+use crate::{
+    fetch_data::DataFetcher, peer_probes::PeerProbes, IntegrationTestLogMarker, WandBInfo,
+};
 use psyche_coordinator::{
     model::{self, HttpLLMTrainingDataLocation, LLMTrainingDataLocation},
     Coordinator, HealthChecks,
 };
-use psyche_core::{CancellableBarrier, NodeIdentity, TokenSize};
+use psyche_core::{sha256, CancellableBarrier, NodeIdentity, TokenSize};
 use psyche_data_provider::{
-    download_model_repo_async,
+    check_repo_access, download_model_repo_async,
     http::{FileURLs, HttpDataProvider},
-    DataProvider, DataProviderTcpClient, DummyDataProvider, WeightedDataProvider,
+    DataProvider, DataProviderTcpClient, DummyDataProvider, RepoAccessError, WeightedDataProvider,
 };
 use psyche_modeling::{
-    auto_tokenizer, AutoConfig, AutoTokenizerError, CausalLM, CommunicatorId, DataParallel,
-    DeepseekForCausalLM, DummyModel, LlamaConfig, LlamaForCausalLM, ModelConfig, ModelLoadError,
-    ParallelModels, PretrainedSource, Trainer,
+    auto_tokenizer, check_forward_numerics, cuda_or_cpu_fallback, AutoConfig, AutoTokenizerError,
+    CausalLM, CommunicatorId, DataParallel, DeepseekForCausalLM, DeviceSelectionError, DummyModel,
+    LlamaConfig, LlamaForCausalLM, ModelConfig, ModelLoadError, ParallelModels, PretrainedSource,
+    SpecialTokens, Trainer,
 };
-use psyche_network::{AuthenticatableIdentity, BlobTicket};
+use psyche_network::{AuthenticatableIdentity, BlobTicket, ConnectRetry, SparseValueDtype};
 use psyche_watcher::OpportunisticData;
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
 use tch::{Device, Kind, Tensor};
 use thiserror::Error;
 use tokenizers::{models::wordlevel::WordLevel, ModelWrapper, Tokenizer};
 use tokio::{
     io,
-    sync::{mpsc::UnboundedSender, oneshot},
+    sync::{
+        mpsc::{Sender, UnboundedSender},
+        oneshot,
+    },
     task::{JoinError, JoinHandle},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use super::{
     cooldown::CooldownStepMetadata, evals::EvalRunner, stats::StatsLogger, steps::StepStateMachine,
@@ -41,14 +47,29 @@ pub struct RunInitConfig<T: NodeIdentity, A: AuthenticatableIdentity> {
 
     // p2p model parameters sharing config
     pub max_concurrent_parameter_requests: usize,
+    pub model_request_connect_retry: ConnectRetry,
+
+    // how many DisTrO results can wait to be broadcast before training blocks
+    pub max_queued_distro_results: usize,
+    pub min_free_device_memory_mb: Option<u64>,
+    pub verify_checkpoint_numerics: bool,
+    // run on the CPU if there's no CUDA device, instead of failing
+    pub cpu_fallback: bool,
+
+    // p2p health probes, raise the timeout on high-latency links
+    pub health_probe_interval: Duration,
+    pub health_probe_timeout: Duration,
 
     // model & dataload
     pub hub_read_token: Option<String>,
     pub data_parallelism: usize,
     pub tensor_parallelism: usize,
     pub micro_batch_size: usize,
+    /// Train on at most this many micro-batches per step, deferring the rest of the batch to the next step.
+    pub max_micro_batches: Option<usize>,
     pub optim_stats_every_n_steps: Option<u32>,
     pub grad_accum_in_fp32: bool,
+    pub sparse_value_dtype: SparseValueDtype,
 
     // evaluation
     pub eval_task_max_docs: Option<usize>,
@@ -56,6 +77,9 @@ pub struct RunInitConfig<T: NodeIdentity, A: AuthenticatableIdentity> {
 
     // logging
     pub wandb_info: Option<WandBInfo>,
+    pub log_every_n_steps: u32,
+    pub write_run_summary: Option<PathBuf>,
+    pub write_round_results: Option<PathBuf>,
 
     // debugging
     pub write_gradients_dir: Option<PathBuf>,
@@ -65,6 +89,105 @@ pub struct RunInitConfig<T: NodeIdentity, A: AuthenticatableIdentity> {
 
     // configurable dummy training time (in seconds) for this client - relevant just for testing
     pub dummy_training_delay_secs: Option<u64>,
+
+    // where this client's initial weights come from. if unset, follow the coordinator's checkpoint
+    pub init_from: Option<InitFrom>,
+    // token ids that take precedence over the ones in the model's config
+    pub special_tokens: Option<SpecialTokens>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitFrom {
+    /// Randomly initialize the weights with this seed, taking only the model config from the checkpoint.
+    Scratch { seed: u64 },
+    /// Load the weights from this Hub repo, whatever the coordinator's checkpoint is.
+    Checkpoint(model::HubRepo),
+}
+
+/// The checkpoint to get the model config (and weights, unless starting from scratch) from.
+/// Overriding the run's checkpoint is refused unless the run pins a `config_hash` for the loaded config to be
+/// checked against, and once the run's weights live on its peers, since nothing else has them.
+fn init_checkpoint(
+    init_from: Option<&InitFrom>,
+    llm: &model::LLM,
+) -> Result<model::Checkpoint, InitRunError> {
+    let Some(init_from) = init_from else {
+        return Ok(llm.checkpoint);
+    };
+    if llm.config_hash.is_empty() {
+        return Err(InitRunError::InitOverrideRefused(
+            "the run has no config_hash to check the model against",
+        ));
+    }
+    if matches!(llm.checkpoint, model::Checkpoint::P2P(_)) {
+        return Err(InitRunError::InitOverrideRefused(
+            "the run is past its initial checkpoint, its weights can only come from its peers",
+        ));
+    }
+    Ok(match init_from {
+        InitFrom::Checkpoint(hub_repo) => model::Checkpoint::Hub(*hub_repo),
+        InitFrom::Scratch { .. } => llm.checkpoint,
+    })
+}
+
+/// Only warns, since a model that looks off on the probe input may still be what the run intends to train.
+fn verify_checkpoint_numerics(models: &mut [Box<dyn CausalLM>], tensor_parallelism: usize) {
+    if tensor_parallelism != 1 {
+        // every rank would have to run the probe in lockstep
+        warn!("Skipping checkpoint numerics check, it's not supported with tensor parallelism");
+        return;
+    }
+    for (index, model) in models.iter_mut().enumerate() {
+        match check_forward_numerics(model.as_mut()) {
+            Ok(()) => debug!(model = index, "Checkpoint numerics check passed"),
+            Err(err) => warn!(
+                model = index,
+                "Checkpoint numerics check failed, the loaded weights may be corrupt: {err}"
+            ),
+        }
+    }
+}
+
+fn parse_model_config(
+    architecture: model::LLMArchitecture,
+    model_config: &str,
+) -> Result<AutoConfig, serde_json::Error> {
+    Ok(match architecture {
+        model::LLMArchitecture::HfLlama => AutoConfig::Llama(serde_json::from_str(model_config)?),
+        model::LLMArchitecture::HfDeepseek => {
+            AutoConfig::Deepseek(serde_json::from_str(model_config)?)
+        }
+    })
+}
+
+/// Hashes the config as this client will actually use it, so it doesn't matter whether it came from
+/// a config.json on disk or the hub, or was re-serialized by a peer.
+fn model_config_hash(
+    architecture: model::LLMArchitecture,
+    model_config: &str,
+) -> Result<String, serde_json::Error> {
+    let canonical = serde_json::to_string(&parse_model_config(architecture, model_config)?)?;
+    Ok(hex::encode(sha256(canonical.as_bytes())))
+}
+
+fn check_model_config_hash(llm: &model::LLM, model_config: &str) -> Result<(), InitRunError> {
+    let actual = model_config_hash(llm.architecture, model_config)?;
+    info!("Model config hash: {actual}");
+    if llm.config_hash.is_empty() {
+        return Ok(());
+    }
+    let expected = String::from(&llm.config_hash);
+    if !expected.eq_ignore_ascii_case(&actual) {
+        return Err(InitRunError::ModelConfigMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+fn random_init_source(model_config: AutoConfig, seed: u64) -> PretrainedSource<AutoConfig> {
+    info!("Initializing model from scratch with seed {seed}");
+    let parameters = model_config.random_init_parameters(seed as i64);
+    #[allow(clippy::arc_with_non_send_sync)]
+    PretrainedSource::ConfigAndTensors(model_config, Arc::new(parameters))
 }
 
 #[derive(Debug, Error)]
@@ -81,12 +204,18 @@ pub enum InitRunError {
     #[error("failed to read HF model info: {0}")]
     HfModelLoad(#[from] hf_hub::api::tokio::ApiError),
 
+    #[error("can't download the model: {0}")]
+    HfRepoAccess(#[from] RepoAccessError),
+
     #[error("model loading thread crashed")]
     ModelLoadingThreadCrashed(JoinError),
 
     #[error("failed to load model: {0}")]
     ModelLoad(#[from] ModelLoadError),
 
+    #[error("no device to load the model on: {0}")]
+    DeviceSelection(#[from] DeviceSelectionError),
+
     #[error("Couldn't load tokenizer: {0}")]
     TokenizerLoad(#[from] AutoTokenizerError),
 
@@ -102,6 +231,15 @@ pub enum InitRunError {
 
     #[error("could not parse config: {0}")]
     FailedToParseConfig(#[from] serde_json::Error),
+
+    #[error("Model config mismatch: the run expects config hash {expected}, but the loaded config hashes to {actual}. Is the checkpoint stale?")]
+    ModelConfigMismatch { expected: String, actual: String },
+
+    #[error("Dataset length mismatch: the run expects {expected} samples, but the data provider has {actual}.")]
+    DatasetLengthMismatch { expected: u64, actual: u64 },
+
+    #[error("Can't override the model initialization: {0}")]
+    InitOverrideRefused(&'static str),
 }
 
 struct RawLoadedModel {
@@ -109,6 +247,8 @@ struct RawLoadedModel {
     tokenizer: Arc<Tokenizer>,
     eval_runner: EvalRunner,
     checkpoint_extra_files: Vec<PathBuf>,
+    // `None` for the dummy model, which ignores its inputs
+    vocab_size: Option<usize>,
 }
 
 type OneshotModelParameterSender = oneshot::Sender<HashMap<String, Tensor>>;
@@ -118,12 +258,13 @@ pub struct RunInitConfigAndIO<T: NodeIdentity, A: AuthenticatableIdentity> {
     pub init_config: RunInitConfig<T, A>,
 
     pub tx_health_check: UnboundedSender<HealthChecks<T>>,
+    pub peer_probes: PeerProbes,
     pub tx_witness: UnboundedSender<OpportunisticData>,
     pub tx_checkpoint: UnboundedSender<model::HubRepo>,
     pub tx_model: UnboundedSender<HashMap<String, Tensor>>,
     pub tx_parameters_req: UnboundedSender<(Vec<String>, OneshotModelParameterSender)>,
     pub tx_config: UnboundedSender<(String, String)>,
-    pub tx_distro_result: UnboundedSender<DistroBroadcastAndPayload>,
+    pub tx_distro_result: Sender<DistroBroadcastAndPayload>,
     pub tx_request_download: UnboundedSender<(BlobTicket, u32)>,
     pub tx_request_model_config: UnboundedSender<OneShotModelConfigSender>,
     pub tx_broadcast_finished: UnboundedSender<FinishedBroadcast>,
@@ -139,6 +280,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
             init_config,
             tx_witness,
             tx_health_check,
+            peer_probes,
             tx_checkpoint,
             tx_model,
             tx_config,
@@ -150,6 +292,26 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
         } = self;
 
         let model::Model::LLM(llm) = state.model;
+        let llm = model::LLM {
+            checkpoint: init_checkpoint(init_config.init_from.as_ref(), &llm)?,
+            ..llm
+        };
+
+        // fail now, rather than after connecting to the data provider, if our token can't download the model
+        if let model::Checkpoint::Hub(hub_repo) = &llm.checkpoint {
+            let repo_id: String = (&hub_repo.repo_id).into();
+            let revision: Option<String> = hub_repo.revision.map(|bytes| (&bytes).into());
+            // a repo id that's a local directory is loaded from disk instead
+            if revision.is_some() || !PathBuf::from(&repo_id).exists() {
+                check_repo_access(
+                    &repo_id,
+                    hf_hub::RepoType::Model,
+                    revision.as_deref(),
+                    init_config.hub_read_token.as_deref(),
+                )
+                .await?;
+            }
+        }
 
         let data_future = async {
             debug!("Setting up data provider from {:?}", llm.data_location);
@@ -164,7 +326,13 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                 ),
                 LLMTrainingDataLocation::Local(_) => todo!(),
                 LLMTrainingDataLocation::Dummy => {
-                    DataProvider::Dummy(DummyDataProvider::new(TokenSize::TwoBytes, 2048, u64::MAX))
+                    // fixed seed, so every client sees the same data for a given batch
+                    DataProvider::Dummy(DummyDataProvider::new(
+                        TokenSize::TwoBytes,
+                        2048,
+                        u64::MAX,
+                        [0; 32],
+                    ))
                 }
                 LLMTrainingDataLocation::Http(HttpLLMTrainingDataLocation {
                     location,
@@ -215,6 +383,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                         tokenizer: tokenizer.clone(),
                         checkpoint_extra_files: vec![],
                         eval_runner: EvalRunner::new(vec![], tokenizer.clone(), None, 0),
+                        vocab_size: None,
                     };
                     #[allow(clippy::arc_with_non_send_sync)]
                     let config = &PretrainedSource::ConfigAndTensors(
@@ -228,9 +397,9 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                     Ok(model)
                 }),
                 model::Checkpoint::Hub(_) | model::Checkpoint::P2P(_) => {
-                    let checkpoint = llm.checkpoint;
+                    let init_from = init_config.init_from;
                     tokio::spawn(async move {
-                        let (source, tokenizer, checkpoint_extra_files) = match checkpoint {
+                        let (source, tokenizer, checkpoint_extra_files) = match llm.checkpoint {
                             model::Checkpoint::Hub(hub_repo) => {
                                 let repo_id: String = (&hub_repo.repo_id).into();
                                 let potential_local_path = PathBuf::from(repo_id.clone());
@@ -279,11 +448,18 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                                     .cloned()
                                     .collect();
                                 let tokenizer = Arc::new(auto_tokenizer(&repo_files)?);
-                                (
-                                    PretrainedSource::<AutoConfig>::RepoFiles(repo_files),
-                                    tokenizer,
-                                    checkpoint_extra_files,
-                                )
+                                let source = PretrainedSource::<AutoConfig>::RepoFiles(repo_files);
+                                let source = match init_from {
+                                    Some(InitFrom::Scratch { seed }) => {
+                                        let model_config = parse_model_config(
+                                            llm.architecture,
+                                            &source.serialize_config()?,
+                                        )?;
+                                        random_init_source(model_config, seed)
+                                    }
+                                    _ => source,
+                                };
+                                (source, tokenizer, checkpoint_extra_files)
                             }
                             model::Checkpoint::P2P(_) => {
                                 let (tx_model_config_response, rx_model_config_response) =
@@ -298,39 +474,45 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                                     rx_model_config_response.await.unwrap();
                                 debug!("Got p2p info, model_config: {}", model_config);
 
-                                let model_config = match llm.architecture {
-                                    model::LLMArchitecture::HfLlama => {
-                                        AutoConfig::Llama(serde_json::from_str(&model_config)?)
+                                let model_config =
+                                    parse_model_config(llm.architecture, &model_config)?;
+                                let source = match init_from {
+                                    Some(InitFrom::Scratch { seed }) => {
+                                        random_init_source(model_config, seed)
                                     }
-                                    model::LLMArchitecture::HfDeepseek => {
-                                        AutoConfig::Deepseek(serde_json::from_str(&model_config)?)
+                                    _ => {
+                                        let parameter_names = model_config.get_parameter_names();
+                                        info!(
+                                            "Requesting {} parameters over p2p network",
+                                            parameter_names.len()
+                                        );
+
+                                        let (tx_params_response, rx_params_response) =
+                                            oneshot::channel();
+                                        tx_parameters_req
+                                            .send((parameter_names, tx_params_response))
+                                            .unwrap();
+                                        #[allow(clippy::arc_with_non_send_sync)]
+                                        let parameters =
+                                            Arc::new(rx_params_response.await.unwrap());
+
+                                        PretrainedSource::<AutoConfig>::ConfigAndTensors(
+                                            model_config,
+                                            parameters,
+                                        )
                                     }
                                 };
-                                let parameter_names = model_config.get_parameter_names();
-                                info!(
-                                    "Requesting {} parameters over p2p network",
-                                    parameter_names.len()
-                                );
 
-                                let (tx_params_response, rx_params_response) = oneshot::channel();
-                                tx_parameters_req
-                                    .send((parameter_names, tx_params_response))
-                                    .unwrap();
-                                #[allow(clippy::arc_with_non_send_sync)]
-                                let parameters = Arc::new(rx_params_response.await.unwrap());
-
-                                (
-                                    PretrainedSource::<AutoConfig>::ConfigAndTensors(
-                                        model_config,
-                                        parameters,
-                                    ),
-                                    Arc::new(tokenizer),
-                                    vec![],
-                                )
+                                (source, Arc::new(tokenizer), vec![])
                             }
                             _ => unreachable!(),
                         };
 
+                        let model_config = source.serialize_config()?;
+                        check_model_config_hash(&llm, &model_config)?;
+                        let vocab_size =
+                            parse_model_config(llm.architecture, &model_config)?.vocab_size();
+
                         info!("Loading model...");
                         let mut futures: Vec<
                             JoinHandle<Result<Box<dyn CausalLM>, ModelLoadError>>,
@@ -352,17 +534,16 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                                         )
                                     });
                                 let source = source.clone();
+                                let special_tokens = init_config.special_tokens.clone();
+                                let device = cuda_or_cpu_fallback(
+                                    dp * init_config.tensor_parallelism + tp,
+                                    init_config.data_parallelism * init_config.tensor_parallelism,
+                                    init_config.cpu_fallback,
+                                )?;
+                                if device == Device::Cpu {
+                                    warn!("CUDA is not available, running the model on the CPU. This is very slow and only meant for testing.");
+                                }
                                 futures.push(tokio::task::spawn_blocking(move || {
-                                    // let this run on CPU if tp is 1 and no cuda is available
-                                    let device = if init_config.tensor_parallelism == 1 {
-                                        if dp == 0 {
-                                            Device::cuda_if_available()
-                                        } else {
-                                            Device::Cuda(dp)
-                                        }
-                                    } else {
-                                        Device::Cuda(dp * init_config.tensor_parallelism + tp)
-                                    };
                                     match llm.architecture {
                                         model::LLMArchitecture::HfLlama => {
                                             LlamaForCausalLM::from_pretrained(
@@ -372,6 +553,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                                                 Some(device),
                                                 tensor_parallelism_world,
                                                 Some(llm.max_seq_len as usize),
+                                                special_tokens,
                                             )
                                             .map(|x| Box::new(x) as Box<dyn CausalLM>)
                                         }
@@ -383,6 +565,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                                                 Some(device),
                                                 tensor_parallelism_world,
                                                 Some(llm.max_seq_len as usize),
+                                                special_tokens,
                                             )
                                             .map(|x| Box::new(x) as Box<dyn CausalLM>)
                                         }
@@ -408,6 +591,17 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                             tx_config.send((config, tokenizer)).unwrap();
                             models.push(model);
                         }
+                        if init_config.verify_checkpoint_numerics {
+                            models = tokio::task::spawn_blocking(move || {
+                                verify_checkpoint_numerics(
+                                    &mut models,
+                                    init_config.tensor_parallelism,
+                                );
+                                models
+                            })
+                            .await
+                            .map_err(InitRunError::ModelLoadingThreadCrashed)?;
+                        }
                         info!(
                             integration_test_log_marker = %IntegrationTestLogMarker::LoadedModel,
                             checkpoint = %llm.checkpoint,
@@ -422,6 +616,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                             tokenizer,
                             eval_runner,
                             checkpoint_extra_files,
+                            vocab_size: Some(vocab_size),
                         })
                     })
                 }
@@ -429,29 +624,26 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
             },
         };
 
+        let hyperparameters = super::stats::hyperparameters(
+            &state,
+            &llm,
+            init_config.data_parallelism,
+            init_config.tensor_parallelism,
+            init_config.micro_batch_size,
+        );
         let wandb_future: JoinHandle<Result<Option<wandb::Run>, wandb::ApiError>> = tokio::spawn({
-            let run_id = String::from(&state.run_id);
             async move {
                 match init_config.wandb_info {
                     Some(wandb_info) => {
                         let wandb =
                             wandb::WandB::new(wandb::BackendOptions::new(wandb_info.api_key));
+                        let mut config = wandb::LogData::new();
+                        for (key, value) in hyperparameters {
+                            config.insert(key, value);
+                        }
                         let mut run_info = wandb::RunInfo::new(wandb_info.project)
                             .name(wandb_info.run)
-                            .config((
-                                (
-                                    "global_batch_size_start",
-                                    state.config.global_batch_size_start,
-                                ),
-                                ("global_batch_size_end", state.config.global_batch_size_end),
-                                (
-                                    "global_batch_size_warmup_tokens",
-                                    state.config.global_batch_size_warmup_tokens,
-                                ),
-                                ("total_steps", state.config.total_steps),
-                                ("rounds_per_epoch", state.config.rounds_per_epoch),
-                                ("run_id", run_id),
-                            ));
+                            .config(config);
                         if let Some(entity) = wandb_info.entity {
                             run_info = run_info.entity(entity);
                         }
@@ -471,6 +663,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
             tokenizer,
             checkpoint_extra_files,
             eval_runner,
+            vocab_size,
         } = models.map_err(InitRunError::ModelLoadingThreadCrashed)??;
 
         let mut tp_models: Vec<Vec<Box<dyn CausalLM>>> = Vec::new();
@@ -486,7 +679,21 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
         }
 
         // TODO add data fetching for verifying, too..
-        let data_provider = data.map_err(InitRunError::DataProviderConnect)?;
+        let mut data_provider = data.map_err(InitRunError::DataProviderConnect)?;
+        if let (DataProvider::Dummy(dummy), Some(vocab_size)) = (&mut data_provider, vocab_size) {
+            dummy.set_vocab_size(vocab_size);
+        }
+        if let (Some(expected), Some(actual)) = (
+            state.config.wrapping_dataset_samples(),
+            data_provider.num_sequences(),
+        ) {
+            if expected != actual as u64 {
+                return Err(InitRunError::DatasetLengthMismatch {
+                    expected,
+                    actual: actual as u64,
+                });
+            }
+        }
 
         let data_fetcher =
             DataFetcher::<T, A>::new(data_provider, init_config.data_parallelism * 2);
@@ -527,6 +734,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
                     llm.lr_schedule,
                     llm.optimizer,
                     init_config.micro_batch_size,
+                    init_config.max_micro_batches,
                     init_config.optim_stats_every_n_steps,
                     init_config.grad_accum_in_fp32,
                     data_parallel,
@@ -536,8 +744,13 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
 
         let wandb_run = wandb_run.map_err(InitRunError::WandbThreadCrashed)??;
 
-        let stats_logger =
-            StatsLogger::new(tokenizer, eval_runner.clone(), llm.lr_schedule, wandb_run);
+        let stats_logger = StatsLogger::new(
+            tokenizer,
+            eval_runner.clone(),
+            llm.lr_schedule,
+            wandb_run,
+            init_config.log_every_n_steps,
+        );
 
         let warmup = WarmupStepMetadata {
             eval_runner: eval_runner.clone(),
@@ -547,7 +760,10 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
             data_fetcher,
             identity: init_config.identity,
             write_gradients_dir: init_config.write_gradients_dir,
+            sparse_value_dtype: init_config.sparse_value_dtype,
+            log_every_n_steps: init_config.log_every_n_steps,
             tx_health_check,
+            peer_probes,
             tx_distro_result,
 
             eval_runner: eval_runner.clone(),
@@ -579,6 +795,124 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunInitConfigAndIO<T
             tx_witness,
             tx_broadcast_finished,
             stats_logger,
+            init_config.write_run_summary,
+            init_config.write_round_results,
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_core::FixedString;
+    use psyche_modeling::tiny_llama_config;
+
+    fn parameters(source: &PretrainedSource<AutoConfig>) -> &HashMap<String, Tensor> {
+        match source {
+            PretrainedSource::ConfigAndTensors(_, parameters) => parameters,
+            PretrainedSource::RepoFiles(_) => panic!("expected in-memory parameters"),
+        }
+    }
+
+    #[test]
+    fn test_scratch_init_is_seeded() {
+        let config = AutoConfig::Llama(tiny_llama_config());
+        let a = random_init_source(config.clone(), 42);
+        let b = random_init_source(config.clone(), 42);
+        let c = random_init_source(config.clone(), 43);
+
+        let (a, b, c) = (parameters(&a), parameters(&b), parameters(&c));
+        let mut names = config.get_parameter_names();
+        names.sort();
+        let mut loaded_names = a.keys().cloned().collect::<Vec<_>>();
+        loaded_names.sort();
+        assert_eq!(names, loaded_names);
+
+        for name in &names {
+            assert!(a[name].equal(&b[name]), "{name} differs with the same seed");
+        }
+        let embed = "model.embed_tokens.weight";
+        assert!(!a[embed].equal(&c[embed]));
+    }
+
+    #[test]
+    fn test_model_config_hash_mismatch() {
+        let config = serde_json::to_string(&AutoConfig::Llama(tiny_llama_config())).unwrap();
+        let hash = model_config_hash(model::LLMArchitecture::HfLlama, &config).unwrap();
+
+        // formatting differences in the config.json don't matter
+        let pretty = serde_json::to_string_pretty(&AutoConfig::Llama(tiny_llama_config())).unwrap();
+        assert_eq!(
+            model_config_hash(model::LLMArchitecture::HfLlama, &pretty).unwrap(),
+            hash
+        );
+
+        let mut llm = model::LLM::dummy();
+        check_model_config_hash(&llm, &config).unwrap();
+
+        llm.config_hash = FixedString::from_str_truncated(&hash);
+        check_model_config_hash(&llm, &config).unwrap();
+
+        let stale = tiny_llama_config();
+        let stale = serde_json::to_string(&LlamaConfig {
+            vocab_size: stale.vocab_size * 2,
+            ..stale
+        })
+        .unwrap();
+        match check_model_config_hash(&llm, &stale) {
+            Err(InitRunError::ModelConfigMismatch { expected, actual }) => {
+                assert_eq!(expected, hash);
+                assert_ne!(actual, hash);
+            }
+            other => panic!("expected a config mismatch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_init_from_checkpoint_overrides_coordinator() {
+        let repo = model::HubRepo {
+            repo_id: FixedString::from_str_truncated("emozilla/llama2-20m-init"),
+            revision: None,
+        };
+        let mut llm = model::LLM::dummy();
+        llm.checkpoint = model::Checkpoint::Hub(model::HubRepo::dummy());
+        llm.config_hash = FixedString::from_str_truncated(&"0".repeat(64));
+
+        match init_checkpoint(Some(&InitFrom::Checkpoint(repo)), &llm).unwrap() {
+            model::Checkpoint::Hub(hub_repo) => assert_eq!(hub_repo, repo),
+            other => panic!("expected hub checkpoint, got {other}"),
+        }
+        for init_from in [None, Some(InitFrom::Scratch { seed: 1 })] {
+            match init_checkpoint(init_from.as_ref(), &llm).unwrap() {
+                model::Checkpoint::Hub(hub_repo) => assert_eq!(hub_repo, model::HubRepo::dummy()),
+                other => panic!("expected the run's checkpoint, got {other}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_init_override_refused_without_consistency_check() {
+        let init_from = InitFrom::Scratch { seed: 1 };
+        let mut llm = model::LLM::dummy();
+        llm.checkpoint = model::Checkpoint::Hub(model::HubRepo::dummy());
+
+        // nothing to check the overriding model's config against
+        assert!(matches!(
+            init_checkpoint(Some(&init_from), &llm),
+            Err(InitRunError::InitOverrideRefused(_))
+        ));
+
+        // the run's current weights are only on its peers
+        llm.config_hash = FixedString::from_str_truncated(&"0".repeat(64));
+        llm.checkpoint = model::Checkpoint::P2P(model::HubRepo::dummy());
+        assert!(matches!(
+            init_checkpoint(Some(&init_from), &llm),
+            Err(InitRunError::InitOverrideRefused(_))
+        ));
+        // following the run is always fine
+        assert!(matches!(
+            init_checkpoint(None, &llm),
+            Ok(model::Checkpoint::P2P(_))
+        ));
+    }
+}
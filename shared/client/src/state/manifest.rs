@@ -0,0 +1,94 @@
+use psyche_coordinator::{
+    model::{LLMTrainingDataLocation, Model},
+    Coordinator,
+};
+use psyche_core::{LearningRateSchedule, NodeIdentity};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+pub const CHECKPOINT_MANIFEST_FILE_NAME: &str = "psyche_manifest.json";
+
+/// Where a checkpoint came from, written next to its safetensors so it describes itself.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckpointManifest {
+    pub run_id: String,
+    pub step: u32,
+    pub epoch: u16,
+    pub num_clients: usize,
+    /// where the training data came from, including the mixture weights for weighted datasets.
+    pub data_location: LLMTrainingDataLocation,
+    pub lr_schedule: LearningRateSchedule,
+    pub psyche_version: String,
+    /// only known if the build sets `PSYCHE_GIT_REVISION`, as the nix packages do.
+    pub git_revision: Option<String>,
+}
+
+impl CheckpointManifest {
+    pub fn new<T: NodeIdentity>(state: &Coordinator<T>, step: u32) -> Self {
+        let Model::LLM(llm) = &state.model;
+        Self {
+            run_id: state.run_id.to_string(),
+            step,
+            epoch: state.progress.epoch,
+            num_clients: state.epoch_state.clients.len(),
+            data_location: llm.data_location,
+            lr_schedule: llm.lr_schedule,
+            psyche_version: env!("CARGO_PKG_VERSION").to_string(),
+            git_revision: option_env!("PSYCHE_GIT_REVISION").map(str::to_string),
+        }
+    }
+
+    /// Writes the manifest into the checkpoint directory `dir`, returning its path.
+    pub fn write(&self, dir: &Path) -> io::Result<PathBuf> {
+        let path = dir.join(CHECKPOINT_MANIFEST_FILE_NAME);
+        let writer = BufWriter::new(File::create(&path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+    use psyche_coordinator::{model::LLM, Client};
+    use psyche_core::{CosineLR, FixedString};
+
+    #[test]
+    fn test_manifest_has_provenance() {
+        let mut state = Coordinator::<ts_rs::Dummy>::zeroed();
+        state.run_id = FixedString::from_str_truncated("manifest-test");
+        let mut llm = LLM::dummy();
+        llm.lr_schedule = CosineLR::new(4e-4, 100, 0.0, 1000, 4e-5).into();
+        state.model = Model::LLM(llm);
+        state.progress.epoch = 3;
+        for _ in 0..4 {
+            state
+                .epoch_state
+                .clients
+                .push(Client::new(ts_rs::Dummy))
+                .unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = CheckpointManifest::new(&state, 41)
+            .write(dir.path())
+            .unwrap();
+        assert_eq!(path, dir.path().join(CHECKPOINT_MANIFEST_FILE_NAME));
+
+        let written: serde_json::Value =
+            serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(written["run_id"], "manifest-test");
+        assert_eq!(written["step"], 41);
+        assert_eq!(written["epoch"], 3);
+        assert_eq!(written["num_clients"], 4);
+        assert_eq!(written["data_location"], "Dummy");
+        assert_eq!(written["lr_schedule"]["Cosine"]["total_steps"], 1000);
+        assert_eq!(written["psyche_version"], env!("CARGO_PKG_VERSION"));
+        assert!(written.get("git_revision").is_some());
+    }
+}
@@ -5,12 +5,21 @@ mod steps;
 mod cooldown;
 mod evals;
 mod init;
+mod manifest;
+mod round_results;
 mod round_state;
 mod stats;
+mod summary;
 mod train;
 mod warmup;
 mod witness;
 
-pub use init::{InitRunError, RunInitConfig, RunInitConfigAndIO};
+pub use init::{InitFrom, InitRunError, RunInitConfig, RunInitConfigAndIO};
+pub use manifest::{CheckpointManifest, CHECKPOINT_MANIFEST_FILE_NAME};
+pub use round_results::RoundResult;
 pub use steps::RunManager;
-pub use types::{CheckpointConfig, DistroBroadcastAndPayload, FinishedBroadcast, HubUploadInfo};
+pub use summary::RunSummary;
+pub use types::{
+    CheckpointConfig, DistroBroadcastAndPayload, FinishedBroadcast, HubUploadInfo,
+    LowDiskSpacePolicy,
+};
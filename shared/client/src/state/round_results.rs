@@ -0,0 +1,112 @@
+use psyche_coordinator::{get_batch_ids_for_node, Coordinator};
+use psyche_core::{BatchId, NodeIdentity};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+/// One line of the `--write-round-results` JSONL file: what this client trained on in a round.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RoundResult {
+    pub run_id: String,
+    pub epoch: u16,
+    pub step: u32,
+    pub round_height: u32,
+    /// mean loss over this client's batches, if it trained on any
+    pub loss: Option<f32>,
+    /// tokens in the batches assigned to this client
+    pub tokens: u64,
+    /// batch ids assigned to this client, formatted like `B[0, 3]`
+    pub batch_ids: Vec<String>,
+    pub train_secs: f64,
+}
+
+impl RoundResult {
+    pub fn new<T: NodeIdentity>(
+        state: &Coordinator<T>,
+        identity: &T,
+        round_height: u32,
+        data_assignments: &BTreeMap<BatchId, T>,
+        loss: Option<f32>,
+        train_duration: Duration,
+    ) -> Self {
+        let batch_ids = get_batch_ids_for_node(data_assignments, identity);
+        let samples: u64 = batch_ids.iter().map(|id| id.len() as u64).sum();
+        Self {
+            run_id: state.run_id.to_string(),
+            epoch: state.progress.epoch,
+            step: state.progress.step,
+            round_height,
+            loss,
+            tokens: samples * state.get_sequence_length() as u64,
+            batch_ids: batch_ids.iter().map(ToString::to_string).collect(),
+            train_secs: train_duration.as_secs_f64(),
+        }
+    }
+
+    /// Appends this result to `path` as a single JSON line, creating the file if needed.
+    pub fn append(&self, path: &Path) -> io::Result<()> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(&line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+    use psyche_coordinator::model::Model;
+    use psyche_core::{ClosedInterval, FixedString};
+    use std::fs;
+
+    #[test]
+    fn test_round_result_is_appended_as_jsonl() {
+        let mut state = Coordinator::<ts_rs::Dummy>::zeroed();
+        state.run_id = FixedString::from_str_truncated("rounds-test");
+        state.model = Model::LLM(psyche_coordinator::model::LLM::dummy());
+        state.progress.epoch = 1;
+        state.progress.step = 7;
+
+        let mine = BatchId(ClosedInterval::new(0, 3));
+        let data_assignments = BTreeMap::from([(mine, ts_rs::Dummy)]);
+        let result = RoundResult::new(
+            &state,
+            &ts_rs::Dummy,
+            4,
+            &data_assignments,
+            Some(2.5),
+            Duration::from_millis(1500),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rounds.jsonl");
+        result.append(&path).unwrap();
+        result.append(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let written: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(written["run_id"], "rounds-test");
+        assert_eq!(written["epoch"], 1);
+        assert_eq!(written["step"], 7);
+        assert_eq!(written["round_height"], 4);
+        assert_eq!(written["loss"], 2.5);
+        assert_eq!(written["tokens"], 4 * state.get_sequence_length() as u64);
+        assert_eq!(written["batch_ids"][0], mine.to_string());
+        assert_eq!(written["train_secs"], 1.5);
+
+        let read_back: RoundResult = serde_json::from_value(written).unwrap();
+        assert_eq!(read_back, result);
+    }
+}
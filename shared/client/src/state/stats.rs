@@ -22,6 +22,7 @@ pub struct StatsLogger {
     last_optim_stats: HashMap<String, f64>,
     eval_history: HashMap<String, Vec<f64>>,
     lr_schedule: LearningRateSchedule,
+    log_every_n_steps: u32,
 
     pub node_info: HashMap<String, P2PNodeInfo>,
 }
@@ -32,6 +33,7 @@ impl StatsLogger {
         eval_runner: EvalRunner,
         lr_schedule: LearningRateSchedule,
         wandb_run: Option<wandb::Run>,
+        log_every_n_steps: u32,
     ) -> Self {
         Self {
             tokenizer,
@@ -44,9 +46,15 @@ impl StatsLogger {
             eval_history: HashMap::new(),
             last_optim_stats: HashMap::new(),
             node_info: HashMap::new(),
+            log_every_n_steps,
         }
     }
 
+    /// stats are accumulated every step, but per-step logs are only emitted on these steps
+    pub fn should_log_step(&self, step: u32) -> bool {
+        should_log_step(step, self.log_every_n_steps)
+    }
+
     pub fn publish_round_stats<T: NodeIdentity>(&self, state: &Coordinator<T>) {
         let mut round_log = LogData::new();
 
@@ -259,6 +267,52 @@ impl StatsLogger {
     }
 }
 
+pub(super) fn should_log_step(step: u32, log_every_n_steps: u32) -> bool {
+    step % log_every_n_steps.max(1) == 0
+}
+
+/// The run's hyperparameters, flattened to `/`-separated keys, for the wandb run config.
+pub(super) fn hyperparameters<T: NodeIdentity>(
+    state: &Coordinator<T>,
+    llm: &model::LLM,
+    data_parallelism: usize,
+    tensor_parallelism: usize,
+    micro_batch_size: usize,
+) -> Vec<(String, DataValue)> {
+    let hyperparameters = serde_json::json!({
+        "run_id": String::from(&state.run_id),
+        "coordinator": state.config,
+        "model": llm,
+        "client": {
+            "data_parallelism": data_parallelism,
+            "tensor_parallelism": tensor_parallelism,
+            "micro_batch_size": micro_batch_size,
+        },
+    });
+    let mut flattened = Vec::new();
+    flatten_json(String::new(), hyperparameters, &mut flattened);
+    flattened
+}
+
+fn flatten_json(key: String, value: serde_json::Value, out: &mut Vec<(String, DataValue)>) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (field, value) in fields {
+                let key = match key.is_empty() {
+                    true => field,
+                    false => format!("{key}/{field}"),
+                };
+                flatten_json(key, value, out);
+            }
+        }
+        serde_json::Value::Number(number) => {
+            out.push((key, DataValue::from(number.as_f64().unwrap_or_default())))
+        }
+        serde_json::Value::String(string) => out.push((key, DataValue::from(string))),
+        other => out.push((key, DataValue::from(other.to_string()))),
+    }
+}
+
 fn total_tokens<T: NodeIdentity>(state: &Coordinator<T>) -> u64 {
     state
         .current_round()
@@ -287,3 +341,90 @@ fn no_nan(val: f32, replacement: f32) -> f32 {
 fn token_batch_size<T: NodeIdentity>(state: &Coordinator<T>) -> u32 {
     state.get_target_global_batch_size(state.current_round()) as u32 * state.get_sequence_length()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+    use psyche_core::{ConstantLR, FixedString};
+    use tokenizers::{models::wordlevel::WordLevel, ModelWrapper};
+
+    #[tokio::test]
+    async fn test_log_cadence_still_accumulates_stats() {
+        let tokenizer = Arc::new(Tokenizer::new(ModelWrapper::WordLevel(
+            WordLevel::builder().build().unwrap(),
+        )));
+        let mut stats_logger = StatsLogger::new(
+            tokenizer.clone(),
+            EvalRunner::new(vec![], tokenizer, None, 0),
+            LearningRateSchedule::Constant(ConstantLR::default()),
+            None,
+            10,
+        );
+
+        let mut logged_steps = Vec::new();
+        for step in 1..=30 {
+            let loss = stats_logger.push_round_stats(
+                &[step as f32, step as f32 + 1.0],
+                Duration::from_secs(1),
+                Some(Duration::from_secs(2)),
+                HashMap::new(),
+            );
+            assert_eq!(loss, Some(step as f32 + 0.5));
+            if stats_logger.should_log_step(step) {
+                logged_steps.push(step);
+            }
+        }
+
+        assert_eq!(logged_steps, vec![10, 20, 30]);
+        assert_eq!(stats_logger.losses().len(), 30);
+        assert_eq!(stats_logger.losses().last(), Some(&30.5));
+        assert_eq!(stats_logger.step_durations.len(), 16);
+        assert_eq!(stats_logger.efficency(), 0.5);
+    }
+
+    #[test]
+    fn test_log_every_step_by_default() {
+        assert!((1..=5).all(|step| should_log_step(step, 1)));
+        // a cadence of 0 is treated as logging every step
+        assert!(should_log_step(3, 0));
+    }
+
+    #[test]
+    fn test_hyperparameters_contain_run_config() {
+        let mut state = Coordinator::<ts_rs::Dummy>::zeroed();
+        state.run_id = FixedString::from_str_truncated("hyperparameters-test");
+        state.config.total_steps = 1000;
+        state.config.global_batch_size_start = 64;
+        let llm = model::LLM {
+            lr_schedule: LearningRateSchedule::Constant(ConstantLR::new(3e-4, 100, 0.0)),
+            ..model::LLM::dummy()
+        };
+
+        let hyperparameters: HashMap<String, DataValue> =
+            hyperparameters(&state, &llm, 2, 4, 8).into_iter().collect();
+        for key in [
+            "run_id",
+            "coordinator/total_steps",
+            "coordinator/rounds_per_epoch",
+            "coordinator/global_batch_size_start",
+            "coordinator/global_batch_size_end",
+            "coordinator/global_batch_size_warmup_tokens",
+            "coordinator/min_clients",
+            "model/max_seq_len",
+            "model/architecture",
+            "model/data_type",
+            "model/optimizer",
+            "model/lr_schedule/Constant/base_lr",
+            "model/lr_schedule/Constant/warmup_steps",
+            "client/data_parallelism",
+            "client/tensor_parallelism",
+            "client/micro_batch_size",
+        ] {
+            assert!(hyperparameters.contains_key(key), "missing {key}");
+        }
+        // only leaves are logged, nested values are flattened into their own keys
+        assert!(!hyperparameters.contains_key("model"));
+        assert!(!hyperparameters.contains_key("model/lr_schedule"));
+    }
+}
@@ -1,17 +1,24 @@
 use crate::{
     client::P2PNodeInfo,
-    state::{train::FinishedTrainers, types::DeserializeError},
+    state::{
+        train::FinishedTrainers,
+        types::{DeserializeError, DeserializedPayload},
+    },
     Broadcast, BroadcastType, ClientTUIState, IntegrationTestLogMarker,
 };
 
-use psyche_coordinator::{Committee, Coordinator, RunState, Witness, WitnessProof};
-use psyche_core::{sha256, MerkleRoot, MerkleTree, NodeIdentity};
+use psyche_coordinator::{
+    assign_data_for_state, get_batch_ids_for_node, Committee, CommitteeSelection, Coordinator,
+    RunState, Witness, WitnessProof,
+};
+use psyche_core::{sha256, BatchId, MerkleRoot, MerkleTree, NodeIdentity};
 use psyche_modeling::{DistroResult, Trainer};
 use psyche_network::{AuthenticatableIdentity, BlobTicket, Hash, TransmittableDistroResult};
 use psyche_watcher::OpportunisticData;
 use std::{
     collections::HashMap,
     fmt,
+    path::PathBuf,
     sync::{Arc, Mutex},
     time::Instant,
 };
@@ -27,8 +34,10 @@ use super::{
     cooldown::{CooldownError, CooldownStep, CooldownStepMetadata},
     evals::EvalError,
     init::InitRunError,
+    round_results::RoundResult,
     round_state::RoundState,
     stats::StatsLogger,
+    summary::RunSummary,
     train::{TrainError, TrainingStep, TrainingStepMetadata},
     types::PayloadState,
     warmup::{WarmupStep, WarmupStepMetadata},
@@ -58,6 +67,10 @@ pub struct StepStateMachine<T: NodeIdentity, A: AuthenticatableIdentity + 'stati
     sent_warmup_finished: bool,
     sent_warmup_witness: bool,
 
+    started_at: Instant,
+    write_run_summary: Option<PathBuf>,
+    write_round_results: Option<PathBuf>,
+
     coordinator_state: Coordinator<T>,
 }
 
@@ -120,6 +133,8 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> StepStateMachine<T,
         tx_opportunistic_data: mpsc::UnboundedSender<OpportunisticData>,
         tx_broadcast_finished: mpsc::UnboundedSender<FinishedBroadcast>,
         stats_logger: StatsLogger,
+        write_run_summary: Option<PathBuf>,
+        write_round_results: Option<PathBuf>,
     ) -> Self {
         let mut previous_round = RoundState::default();
         let mut current_round = RoundState::default();
@@ -150,6 +165,10 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> StepStateMachine<T,
             step_finish_time: None,
             sent_warmup_finished: false,
             sent_warmup_witness: false,
+
+            started_at: Instant::now(),
+            write_run_summary,
+            write_round_results,
         }
     }
 
@@ -623,7 +642,11 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> StepStateMachine<T,
                         .iter()
                         .map(|x| x.try_into())
                         .collect::<Result<Vec<DistroResult>, TchError>>()
-                        .map(|x| (x, distro_result.trainer_nonce));
+                        .map(|distro_results| DeserializedPayload {
+                            distro_results,
+                            trainer_nonce: distro_result.trainer_nonce,
+                            step: distro_result.step,
+                        });
                     trace!(
                         hash = %hash,
                         batch_id = %batch_id,
@@ -731,19 +754,42 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> StepStateMachine<T,
                     .step_finish_time
                     .map(|step_finish_time| Instant::now() - step_finish_time);
                 self.step_finish_time = Some(Instant::now());
-                let loss = self
-                    .stats_logger
-                    .lock()
-                    .map_err(|_| StepError::StatsLoggerMutex)?
-                    .push_round_stats(&round_losses, round_duration, step_duration, optim_stats);
-                info!(
-                    integration_test_log_marker = %IntegrationTestLogMarker::Loss,
-                    client_id = %self.identity,
-                    epoch = state.progress.epoch,
-                    step = state.progress.step,
-                    loss = loss.unwrap_or(f32::NAN),
-                    "client_loss",
-                );
+                let (loss, should_log) = {
+                    let mut stats_logger = self
+                        .stats_logger
+                        .lock()
+                        .map_err(|_| StepError::StatsLoggerMutex)?;
+                    let loss = stats_logger.push_round_stats(
+                        &round_losses,
+                        round_duration,
+                        step_duration,
+                        optim_stats,
+                    );
+                    (loss, stats_logger.should_log_step(state.progress.step))
+                };
+                if let Some(path) = &self.write_round_results {
+                    let result = RoundResult::new(
+                        &state,
+                        &self.identity,
+                        self.current_round.height,
+                        &self.current_round.data_assignments,
+                        loss,
+                        round_duration,
+                    );
+                    if let Err(err) = result.append(path) {
+                        warn!("Failed to write round result to {}: {err}", path.display());
+                    }
+                }
+                if should_log {
+                    info!(
+                        integration_test_log_marker = %IntegrationTestLogMarker::Loss,
+                        client_id = %self.identity,
+                        epoch = state.progress.epoch,
+                        step = state.progress.step,
+                        loss = loss.unwrap_or(f32::NAN),
+                        "client_loss",
+                    );
+                }
                 self.stats_logger
                     .lock()
                     .map_err(|_| StepError::StatsLoggerMutex)?
@@ -779,6 +825,16 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> StepStateMachine<T,
                 let trainers = witnessing.finish().await?.stop_evals().await?;
                 ActiveStep::Cooldown(self.cooldown.start(trainers, &state)?)
             }
+            // the last epoch's cooldown is done, the run is over.
+            (ActiveStep::Cooldown(cooldown), RunState::Finished) => {
+                let trainers = cooldown.finish().await?;
+                self.finish_run(&state)?;
+                ActiveStep::Warmup(self.warmup.start(
+                    trainers,
+                    &mut self.previous_round,
+                    &mut self.current_round,
+                ))
+            }
             // cooldown is done, we consider waiting for members and warmup to be basically the same
             (ActiveStep::Cooldown(cooldown), RunState::WaitingForMembers)
             | (ActiveStep::Cooldown(cooldown), RunState::Warmup)
@@ -810,6 +866,36 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> StepStateMachine<T,
         Ok(())
     }
 
+    fn finish_run(&self, state: &Coordinator<T>) -> Result<(), StepError> {
+        let summary = {
+            let stats_logger = self
+                .stats_logger
+                .lock()
+                .map_err(|_| StepError::StatsLoggerMutex)?;
+            RunSummary::new(
+                state,
+                stats_logger.losses(),
+                stats_logger.eval_history(),
+                self.started_at.elapsed(),
+            )
+        };
+        info!(
+            integration_test_log_marker = %IntegrationTestLogMarker::RunFinished,
+            client_id = %self.identity,
+            step = summary.final_step,
+            total_tokens = summary.total_tokens,
+            loss = summary.final_loss.unwrap_or(f32::NAN),
+            "Run finished: {summary:?}",
+        );
+        if let Some(path) = &self.write_run_summary {
+            match summary.write(path) {
+                Ok(()) => info!("Wrote run summary to {}", path.display()),
+                Err(err) => warn!("Failed to write run summary to {}: {err}", path.display()),
+            }
+        }
+        Ok(())
+    }
+
     pub fn set_node_info(&mut self, node_info: HashMap<String, P2PNodeInfo>) -> anyhow::Result<()> {
         self.stats_logger
             .lock()
@@ -838,7 +924,10 @@ impl ActiveStep {
             }
             (
                 ActiveStep::Warmup(..),
-                RunState::Warmup | RunState::WaitingForMembers | RunState::Paused,
+                RunState::Warmup
+                | RunState::WaitingForMembers
+                | RunState::Paused
+                | RunState::Finished,
             ) => true,
             (ActiveStep::Cooldown(..), RunState::Cooldown) => true,
             (ActiveStep::Training(..), RunState::RoundTrain) => true,
@@ -1021,6 +1110,19 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunManager<T, A> {
         Ok(())
     }
 
+    /// The batches this client is assigned to train on in `round`, according to the latest coordinator state.
+    /// Empty if `round` isn't the current round, or if the run hasn't been initialized yet.
+    pub fn my_assigned_batches(&self, round: u32) -> Vec<BatchId> {
+        match &self.0 {
+            InitStage::Running(state_machine) => assigned_batches(
+                &state_machine.coordinator_state,
+                round,
+                &state_machine.identity,
+            ),
+            _ => Vec::new(),
+        }
+    }
+
     pub fn doing_checkpoint(&self) -> bool {
         match &self.0 {
             InitStage::Running(step_state_machine) => match &step_state_machine.active_step {
@@ -1032,6 +1134,27 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> RunManager<T, A> {
     }
 }
 
+fn assigned_batches<T: NodeIdentity>(
+    state: &Coordinator<T>,
+    round: u32,
+    identity: &T,
+) -> Vec<BatchId> {
+    let Some(current_round) = state.current_round() else {
+        return Vec::new();
+    };
+    // the last two rounds of an epoch don't train
+    if current_round.height != round || round >= state.config.rounds_per_epoch.saturating_sub(2) {
+        return Vec::new();
+    }
+    match CommitteeSelection::from_coordinator(state, 0) {
+        Ok(committee_selection) => get_batch_ids_for_node(
+            &assign_data_for_state(state, &committee_selection),
+            identity,
+        ),
+        Err(_) => Vec::new(),
+    }
+}
+
 impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> From<&RunManager<T, A>>
     for ClientTUIState
 {
@@ -1084,3 +1207,69 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> From<&RunManager<T,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+    use psyche_coordinator::{model, test_utils::TestClientId, Client};
+
+    fn training_state(num_clients: u64) -> Coordinator<TestClientId> {
+        let mut state = Coordinator::<TestClientId>::zeroed();
+        state.run_state = RunState::RoundTrain;
+        state.model = model::Model::LLM(model::LLM::dummy());
+        state.config.rounds_per_epoch = 10;
+        state.config.global_batch_size_start = 10;
+        state.config.global_batch_size_end = 10;
+        for i in 0..num_clients {
+            state
+                .epoch_state
+                .clients
+                .push(Client::new(TestClientId(i)))
+                .unwrap();
+        }
+        let round = &mut state.epoch_state.rounds[0];
+        round.height = 3;
+        round.data_index = 100;
+        round.random_seed = 42;
+        round.clients_len = num_clients as u16;
+        state
+    }
+
+    #[test]
+    fn test_assigned_batches_match_data_assignment() {
+        let state = training_state(4);
+        let committee_selection = CommitteeSelection::from_coordinator(&state, 0).unwrap();
+        let data_assignments = assign_data_for_state(&state, &committee_selection);
+
+        let mut all_batches = Vec::new();
+        for i in 0..4 {
+            let id = TestClientId(i);
+            let batches = assigned_batches(&state, 3, &id);
+            assert!(!batches.is_empty());
+            assert_eq!(batches, get_batch_ids_for_node(&data_assignments, &id));
+            all_batches.extend(batches);
+        }
+        // every batch of the round is assigned to exactly one client
+        all_batches.sort();
+        assert_eq!(
+            all_batches,
+            data_assignments.keys().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            all_batches.iter().map(|batch| batch.len()).sum::<usize>(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_no_assigned_batches_outside_current_training_round() {
+        let state = training_state(4);
+        assert!(assigned_batches(&state, 2, &TestClientId(0)).is_empty());
+        assert!(assigned_batches(&state, 3, &TestClientId(7)).is_empty());
+
+        let mut state = training_state(4);
+        state.epoch_state.rounds[0].height = 8;
+        assert!(assigned_batches(&state, 8, &TestClientId(0)).is_empty());
+    }
+}
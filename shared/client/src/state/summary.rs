@@ -0,0 +1,117 @@
+use psyche_coordinator::Coordinator;
+use psyche_core::NodeIdentity;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+    time::Duration,
+};
+
+/// A summary of a finished run, as seen by this client.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub final_step: u32,
+    pub epochs: u16,
+    pub total_tokens: u64,
+    pub final_loss: Option<f32>,
+    /// the most recent score of each eval task
+    pub eval_scores: BTreeMap<String, f64>,
+    /// time from when this client started participating until the run finished
+    pub wall_clock_secs: f64,
+    pub participating_clients: Vec<String>,
+}
+
+impl RunSummary {
+    pub fn new<T: NodeIdentity>(
+        state: &Coordinator<T>,
+        losses: &[f32],
+        eval_history: &HashMap<String, Vec<f64>>,
+        wall_clock: Duration,
+    ) -> Self {
+        Self {
+            run_id: state.run_id.to_string(),
+            final_step: state.progress.step,
+            epochs: state.progress.epoch,
+            total_tokens: state.total_tokens_processed(state.current_round()),
+            final_loss: losses.last().copied(),
+            eval_scores: eval_history
+                .iter()
+                .filter_map(|(task, scores)| scores.last().map(|score| (task.clone(), *score)))
+                .collect(),
+            wall_clock_secs: wall_clock.as_secs_f64(),
+            participating_clients: state
+                .epoch_state
+                .clients
+                .iter()
+                .map(|client| client.id.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytemuck::Zeroable;
+    use psyche_coordinator::{model::Model, Client, RunState};
+    use psyche_core::FixedString;
+
+    #[test]
+    fn test_finished_run_writes_summary() {
+        let mut state = Coordinator::<ts_rs::Dummy>::zeroed();
+        state.run_id = FixedString::from_str_truncated("summary-test");
+        state.run_state = RunState::Finished;
+        state.model = Model::LLM(psyche_coordinator::model::LLM::dummy());
+        state.progress.step = 10;
+        state.progress.epoch = 2;
+        state.epoch_state.rounds[0].data_index = 40;
+        for _ in 0..2 {
+            state
+                .epoch_state
+                .clients
+                .push(Client::new(ts_rs::Dummy))
+                .unwrap();
+        }
+
+        let losses = [3.5, 3.0, 2.5];
+        let eval_history = HashMap::from([
+            ("Hellaswag".to_string(), vec![0.25, 0.3]),
+            ("ARC-Easy".to_string(), vec![]),
+        ]);
+        let summary = RunSummary::new(&state, &losses, &eval_history, Duration::from_secs(90));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("summary.json");
+        summary.write(&path).unwrap();
+
+        let written: serde_json::Value =
+            serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(written["run_id"], "summary-test");
+        assert_eq!(written["final_step"], 10);
+        assert_eq!(written["epochs"], 2);
+        assert_eq!(
+            written["total_tokens"],
+            40 * state.get_sequence_length() as u64
+        );
+        assert_eq!(written["final_loss"], 2.5);
+        assert_eq!(written["eval_scores"]["Hellaswag"], 0.3);
+        assert!(written["eval_scores"].get("ARC-Easy").is_none());
+        assert_eq!(written["wall_clock_secs"], 90.0);
+        assert_eq!(
+            written["participating_clients"].as_array().unwrap().len(),
+            2
+        );
+
+        let read_back: RunSummary = serde_json::from_value(written).unwrap();
+        assert_eq!(read_back, summary);
+    }
+}
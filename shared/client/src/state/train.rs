@@ -1,6 +1,7 @@
 use crate::{
     fetch_data::{BatchIdSet, DataFetcher, TrainingDataForStep},
-    state::types::{DeserializeError, PayloadState},
+    peer_probes::PeerProbes,
+    state::types::{DeserializeError, DeserializedPayload, PayloadState},
     IntegrationTestLogMarker,
 };
 
@@ -15,8 +16,8 @@ use psyche_modeling::{
     TrainerThreadCommunicationError,
 };
 use psyche_network::{
-    distro_results_to_bytes, AuthenticatableIdentity, Hash, SerializeDistroResultError,
-    SerializedDistroResult, TransmittableDistroResult,
+    distro_results_to_bytes, AuthenticatableIdentity, Hash, NodeId, SerializeDistroResultError,
+    SerializedDistroResult, SparseValueDtype, TransmittableDistroResult,
 };
 use std::{
     collections::{BTreeMap, HashMap},
@@ -29,7 +30,10 @@ use std::{
 };
 use thiserror::Error;
 use tokio::{
-    sync::{mpsc, Mutex},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Mutex,
+    },
     task::JoinHandle,
 };
 use tokio_util::sync::CancellationToken;
@@ -38,6 +42,7 @@ use tracing::{debug, error, info, trace, trace_span, warn, Instrument};
 use super::{
     evals::{EvalRunner, MaybeRunningEvals},
     round_state::RoundState,
+    stats::should_log_step,
     types::DistroBroadcastAndPayload,
 };
 
@@ -98,9 +103,12 @@ pub struct TrainingStepMetadata<T: NodeIdentity, A: AuthenticatableIdentity> {
     pub identity: T,
     pub data_fetcher: DataFetcher<T, A>,
     pub tx_health_check: mpsc::UnboundedSender<HealthChecks<T>>,
-    pub tx_distro_result: mpsc::UnboundedSender<DistroBroadcastAndPayload>,
+    pub peer_probes: PeerProbes,
+    pub tx_distro_result: mpsc::Sender<DistroBroadcastAndPayload>,
 
     pub write_gradients_dir: Option<PathBuf>,
+    pub sparse_value_dtype: SparseValueDtype,
+    pub log_every_n_steps: u32,
 
     pub eval_runner: EvalRunner,
 }
@@ -154,8 +162,12 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
         }
 
         let applying = self.apply_results(trainers, state, previous_round, current_round)?;
-        let sending_health_checks =
-            start_sending_health_checks(current_round, state, self.tx_health_check.clone())?;
+        let sending_health_checks = start_sending_health_checks(
+            current_round,
+            state,
+            self.tx_health_check.clone(),
+            self.peer_probes.clone(),
+        )?;
 
         debug!("Transitioning to train step {}", state.progress.step);
 
@@ -173,7 +185,8 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
             state.epoch_state.clients.len(),
             round.random_seed,
         )
-        .map_err(TrainError::CoordinatorError)?;
+        .map_err(TrainError::CoordinatorError)?
+        .with_config_salts(&state.config);
 
         let have_training = round.height < state.config.rounds_per_epoch - 2;
         let (data_assignments, num_all_batch_ids, batch_ids_not_yet_trained_on) =
@@ -238,21 +251,23 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
         let zero_optim = warmup_lr_between.is_some_and(|_| round.height == 0);
         let epoch = state.progress.epoch;
 
-        info!(
-            integration_test_log_marker = %IntegrationTestLogMarker::WitnessElected,
-            step = state.progress.step,
-            round = round.height,
-            epoch = epoch,
-            index = client_index,
-            comittee_position = committee_proof.position,
-            committee = %committee_proof.committee,
-            witness_position = witness_proof.position,
-            witness = %witness_proof.witness,
-            warmup_lr_between = ?warmup_lr_between,
-            assigned_batches = ?get_batch_ids_for_node(&data_assignments, &self.identity),
-            "Got training assignment for step {} (round {}/epoch {}): index={} committee position={} committee={} witness position={} witness={} warmup_lr_between={:?}",
-            state.progress.step, round.height, epoch, client_index, committee_proof.position, committee_proof.committee, witness_proof.position, witness_proof.witness, warmup_lr_between
-        );
+        if should_log_step(state.progress.step, self.log_every_n_steps) {
+            info!(
+                integration_test_log_marker = %IntegrationTestLogMarker::WitnessElected,
+                step = state.progress.step,
+                round = round.height,
+                epoch = epoch,
+                index = client_index,
+                comittee_position = committee_proof.position,
+                committee = %committee_proof.committee,
+                witness_position = witness_proof.position,
+                witness = %witness_proof.witness,
+                warmup_lr_between = ?warmup_lr_between,
+                assigned_batches = ?get_batch_ids_for_node(&data_assignments, &self.identity),
+                "Got training assignment for step {} (round {}/epoch {}): index={} committee position={} committee={} witness position={} witness={} warmup_lr_between={:?}",
+                state.progress.step, round.height, epoch, client_index, committee_proof.position, committee_proof.committee, witness_proof.position, witness_proof.witness, warmup_lr_between
+            );
+        }
         let eval_runner = self.eval_runner.clone();
         let finished = Arc::new(AtomicBool::new(false));
 
@@ -280,10 +295,11 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                 let identity = self.identity;
                 let cancel_training = cancel_training.clone();
                 let write_gradients_dir = self.write_gradients_dir.clone();
+                let sparse_value_dtype = self.sparse_value_dtype;
                 let tx_distro_result = self.tx_distro_result.clone();
                 let quantize = match &state.model {
                     model::Model::LLM(llm) => match llm.optimizer {
-                        OptimizerDefinition::Distro { quantize_1bit, .. } => quantize_1bit,
+                        OptimizerDefinition::Distro(distro) => distro.quantize_1bit,
                         _ => false,
                     },
                 };
@@ -413,7 +429,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                                         batch_id,
                                         distro_results: to_transmit
                                             .into_iter()
-                                            .map(|x| SerializedDistroResult::try_from(&x))
+                                            .map(|x| SerializedDistroResult::encode(&x, sparse_value_dtype))
                                             .collect::<std::result::Result<Vec<_>, _>>()
                                             .map_err(TrainError::SerializeDistroResult)?,
                                         trainer_nonce: nonce,
@@ -434,16 +450,17 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                                     let commitment_data_hash = transmittable_distro_result.comptue_hash();
 
                                     trace!("trying to queue tx distro result...");
-                                    tx_distro_result
-                                        .send(DistroBroadcastAndPayload {
+                                    queue_distro_result(
+                                        &tx_distro_result,
+                                        DistroBroadcastAndPayload {
                                             step,
                                             batch_id,
                                             commitment_data_hash,
                                             proof: committee_proof,
                                             distro_result: transmittable_distro_result,
                                             original_distro_result: distro_results,
-                                        })
-                                        .map_err(|_| TrainError::SendDistroResult)?;
+                                        },
+                                    )?;
                                     trace!("successfully queued tx distro result");
                                     Ok(())
                                 }).await.map_err(|_| TrainError::TransmitCrashed)?;
@@ -471,7 +488,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                         optim_stats,
                         round_duration,
                     })
-                })
+                }.instrument(trace_span!("train_round", step, round = round.height)))
             };
 
         Ok(TrainingStep {
@@ -500,15 +517,10 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
 
         let apply_start = Instant::now();
         let step = state.progress.step;
-        let witness_quorum = state.witness_quorum(
-            state
-                .previous_round()
-                .ok_or(ApplyError::NoActiveRound)?
-                .witnesses
-                .len() as u16,
-        );
-        let cold_start_warmup_steps = match &state.model {
-            model::Model::LLM(llm) => llm.cold_start_warmup_steps,
+        let witness_quorum =
+            state.witness_quorum(state.previous_round().ok_or(ApplyError::NoActiveRound)?);
+        let (cold_start_warmup_steps, gradient_staleness) = match &state.model {
+            model::Model::LLM(llm) => (llm.cold_start_warmup_steps, llm.gradient_staleness),
         };
         let warmup_lr_between = state.get_cold_start_warmup_bounds();
         let epoch = state.progress.epoch;
@@ -525,10 +537,12 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
             .previous_round()
             .ok_or(ApplyError::NoActiveRound)?
             .witnesses;
+        let trained_round = state
+            .previous_previous_round()
+            .ok_or(ApplyError::NoActiveRound)?;
+        let tie_break_seed = trained_round.random_seed;
         let batch_ids = get_batch_ids_for_round(
-            state
-                .previous_previous_round()
-                .ok_or(ApplyError::NoActiveRound)?,
+            trained_round,
             state,
             previous_round
                 .committee_info
@@ -539,6 +553,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
         );
 
         let data_assignments = previous_round.data_assignments.clone();
+        let round_step = previous_round.step;
 
         Ok(tokio::task::spawn(async move {
                 let mut distro_results: Vec<Vec<DistroResult>> = Vec::new();
@@ -568,6 +583,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                             .collect::<Vec<_>>(),
                         &witnesses,
                         witness_quorum,
+                        tie_break_seed,
                     ) {
                         Some(x) => x,
                         None => {
@@ -578,7 +594,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                     trace!("Consensus commitment for batch {batch_id}: {consensus:?}");
 
                     let (commitment, result) = &batch_commitments[consensus].1;
-                    let maybe_results: Result<(Vec<DistroResult>, u32), DeserializeError> = match payloads.remove(&result.ticket.hash()) {
+                    let maybe_results: Result<DeserializedPayload, DeserializeError> = match payloads.remove(&result.ticket.hash()) {
                         Some(PayloadState::Deserializing(x)) => match x.is_finished() {
                             true => x.await.unwrap(),
                             false => {
@@ -604,7 +620,7 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                     };
 
                     match maybe_results {
-                        Ok((results, trainer_nonce)) => {
+                        Ok(DeserializedPayload { distro_results: results, trainer_nonce, step: result_step }) => {
                             if trainer_nonce < cold_start_warmup_steps && epoch != 0 && warmup_lr_between.is_none()  {
                                 // results are not actually applied for the first cold_start_warmup_steps of a trainer's lifetime
                                 // note, we are relying on honest communication of this value here -- will need to harden with verification.
@@ -612,7 +628,17 @@ impl<T: NodeIdentity, A: AuthenticatableIdentity + 'static> TrainingStepMetadata
                                 // or when doing a cold start (warmup_lr_between.is_some())
                                 info!("Skipping apply of batch {batch_id}, trainer warming up ({trainer_nonce}/{cold_start_warmup_steps})");
                             } else {
-                                distro_results.push(results);
+                                match gradient_staleness.weight(round_step, result_step) {
+                                    Some(weight) if weight == 1.0 => distro_results.push(results),
+                                    Some(weight) => {
+                                        debug!("Applying batch {batch_id} from step {result_step} in step {round_step}, discounted to {weight}");
+                                        distro_results.push(results.iter().map(|result| result.scaled(weight as f64)).collect());
+                                    }
+                                    None => warn!(
+                                        "Skipping apply of batch {batch_id}, its results are from step {result_step} but this round is step {round_step} (max staleness {})",
+                                        gradient_staleness.max_steps
+                                    ),
+                                }
                             }
                         }
                         Err(err) => warn!("DESYNC: Got the following error when deserializing results for commitment 0x{}: {}", hex::encode(commitment.data_hash), err),
@@ -649,6 +675,7 @@ fn start_sending_health_checks<T: NodeIdentity>(
     round_state: &mut RoundState<T>,
     state: &Coordinator<T>,
     tx_health_check: mpsc::UnboundedSender<HealthChecks<T>>,
+    peer_probes: PeerProbes,
 ) -> Result<Option<JoinHandle<Result<(), TrainError>>>, TrainError> {
     // we won't have any information to health check with until at least one round of training has finished
     if round_state.height == 0 {
@@ -665,9 +692,22 @@ fn start_sending_health_checks<T: NodeIdentity>(
             let state = *state;
             Some(tokio::task::spawn(async move {
                 let mut checks = HealthChecks::new();
+                let now = Instant::now();
                 for (index, client) in clients.iter().enumerate() {
                     let proof = committee_selection.get_committee(index as u64);
                     if !state.healthy(&client.id, &proof).unwrap_or(false) {
+                        let answered_probe = NodeId::from_bytes(client.id.get_p2p_public_key())
+                            .is_ok_and(|peer| {
+                                peer_probes.answered_recently(&peer, clients.len(), now)
+                            });
+                        if answered_probe {
+                            debug!(
+                                index = index,
+                                client_id = %&client.id,
+                                "Not reporting trainer at index {index}: its results didn't reach the witnesses, but it answers p2p health probes",
+                            );
+                            continue;
+                        }
                         warn!(
                             integration_test_log_marker = %IntegrationTestLogMarker::HealthCheck,
                             index = index,
@@ -727,6 +767,24 @@ enum WriteGradientsError {
     },
 }
 
+/// Must be called from a blocking thread. If the network can't keep up and the queue is full,
+/// this blocks until there's room, slowing training down instead of buffering results without bound.
+fn queue_distro_result(
+    tx_distro_result: &mpsc::Sender<DistroBroadcastAndPayload>,
+    distro_result: DistroBroadcastAndPayload,
+) -> Result<(), TrainError> {
+    match tx_distro_result.try_send(distro_result) {
+        Ok(()) => Ok(()),
+        Err(TrySendError::Full(distro_result)) => {
+            debug!("Distro result queue is full, waiting for the network to catch up");
+            tx_distro_result
+                .blocking_send(distro_result)
+                .map_err(|_| TrainError::SendDistroResult)
+        }
+        Err(TrySendError::Closed(_)) => Err(TrainError::SendDistroResult),
+    }
+}
+
 async fn write_gradients_to_disk<T: NodeIdentity>(
     write_gradients_dir: PathBuf,
     identity: T,
@@ -757,3 +815,73 @@ async fn write_gradients_to_disk<T: NodeIdentity>(
     debug!("Wrote distro result {fname}.");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_coordinator::CommitteeProof;
+    use psyche_core::ClosedInterval;
+    use std::sync::atomic::AtomicUsize;
+
+    fn distro_result(step: u32) -> DistroBroadcastAndPayload {
+        let batch_id = BatchId(ClosedInterval::new(step as u64, step as u64));
+        DistroBroadcastAndPayload {
+            step,
+            batch_id,
+            commitment_data_hash: [0; 32],
+            proof: CommitteeProof::default(),
+            distro_result: TransmittableDistroResult {
+                step,
+                trainer_nonce: 0,
+                batch_id,
+                distro_results: vec![],
+            },
+            original_distro_result: vec![],
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_slow_network_blocks_training() {
+        let (tx_distro_result, mut rx_distro_result) = mpsc::channel(2);
+        let queued = Arc::new(AtomicUsize::new(0));
+
+        let producer = tokio::task::spawn_blocking({
+            let queued = queued.clone();
+            move || {
+                for step in 0..10 {
+                    queue_distro_result(&tx_distro_result, distro_result(step))?;
+                    queued.fetch_add(1, Ordering::SeqCst);
+                }
+                Ok::<_, TrainError>(())
+            }
+        });
+
+        // nothing is being broadcast, so the producer stops once the queue is full
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(queued.load(Ordering::SeqCst), 2);
+        assert!(!producer.is_finished());
+
+        // a slow network drains one result at a time, and the producer only ever gets one ahead
+        for step in 0..10 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            assert!(queued.load(Ordering::SeqCst) <= step as usize + 2);
+            let received = rx_distro_result.recv().await.unwrap();
+            assert_eq!(received.step, step);
+        }
+
+        producer.await.unwrap().unwrap();
+        assert_eq!(queued.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn test_queue_distro_result_closed_channel() {
+        let (tx_distro_result, rx_distro_result) = mpsc::channel(1);
+        drop(rx_distro_result);
+        let result = tokio::task::spawn_blocking(move || {
+            queue_distro_result(&tx_distro_result, distro_result(0))
+        })
+        .await
+        .unwrap();
+        assert!(matches!(result, Err(TrainError::SendDistroResult)));
+    }
+}
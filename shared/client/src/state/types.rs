@@ -18,12 +18,33 @@ pub struct HubUploadInfo {
 pub struct CheckpointConfig {
     pub hub_upload: Option<HubUploadInfo>,
     pub checkpoint_dir: PathBuf,
+    /// free space to leave on the checkpoint disk on top of the checkpoint itself
+    pub min_free_disk_bytes: u64,
+    pub low_disk_space: LowDiskSpacePolicy,
+}
+
+/// What to do when the checkpoint disk doesn't have room for a checkpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum LowDiskSpacePolicy {
+    /// warn and skip this checkpoint, keep training
+    #[default]
+    Skip,
+    /// fail the cooldown step
+    Error,
 }
 
 #[derive(Debug)]
 pub enum PayloadState<T: NodeIdentity> {
     Downloading((T, BatchId, BlobTicket)),
-    Deserializing(JoinHandle<Result<(Vec<DistroResult>, u32), DeserializeError>>),
+    Deserializing(JoinHandle<Result<DeserializedPayload, DeserializeError>>),
+}
+
+#[derive(Debug)]
+pub struct DeserializedPayload {
+    pub distro_results: Vec<DistroResult>,
+    pub trainer_nonce: u32,
+    /// the step the trainer says it computed these results at
+    pub step: u32,
 }
 
 #[derive(Error, Debug)]
@@ -1,8 +1,10 @@
-use crate::{Client, Coordinator, CoordinatorError, SOLANA_MAX_NUM_WITNESSES};
+use crate::{Client, Coordinator, CoordinatorConfig, CoordinatorError, SOLANA_MAX_NUM_WITNESSES};
 
 use anchor_lang::{prelude::borsh, AnchorDeserialize, AnchorSerialize, InitSpace};
 use bytemuck::Zeroable;
-use psyche_core::{compute_shuffled_index, sha256, sha256v, NodeIdentity, SmallBoolean};
+use psyche_core::{
+    compute_shuffled_index, sha256, sha256v, FixedString, NodeIdentity, SmallBoolean,
+};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
@@ -36,6 +38,8 @@ pub struct CommitteeSelection {
     total_nodes: u64,
     witness_nodes: u64,
     seed: [u8; 32],
+    committee_salt: String,
+    witness_salt: String,
 }
 
 #[derive(
@@ -81,6 +85,13 @@ pub struct WitnessProof {
     pub witness: SmallBoolean,
 }
 
+/// One client's committee and witness membership for a round, with the proofs the coordinator checks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClientAssignment {
+    pub committee: CommitteeProof,
+    pub witness: WitnessProof,
+}
+
 impl CommitteeSelection {
     pub fn new(
         tie_breaker_nodes: usize,
@@ -116,9 +127,31 @@ impl CommitteeSelection {
             total_nodes: total_nodes as u64,
             witness_nodes: witness_nodes as u64,
             seed,
+            committee_salt: COMMITTEE_SALT.to_string(),
+            witness_salt: WITNESS_SALT.to_string(),
         })
     }
 
+    pub fn with_salts(mut self, committee_salt: &str, witness_salt: &str) -> Self {
+        self.committee_salt = committee_salt.to_string();
+        self.witness_salt = witness_salt.to_string();
+        self
+    }
+
+    /// Applies the run's salt overrides, keeping the default salts for any that are unset.
+    pub fn with_config_salts(self, config: &CoordinatorConfig) -> Self {
+        fn salt_or(salt: &FixedString<32>, default: &str) -> String {
+            match salt.is_empty() {
+                true => default.to_string(),
+                false => String::from(salt),
+            }
+        }
+        self.with_salts(
+            &salt_or(&config.committee_salt, COMMITTEE_SALT),
+            &salt_or(&config.witness_salt, WITNESS_SALT),
+        )
+    }
+
     pub fn from_coordinator<T: NodeIdentity>(
         coordinator: &Coordinator<T>,
         offset: isize,
@@ -132,17 +165,53 @@ impl CommitteeSelection {
             }
         }
         .ok_or(CoordinatorError::NoActiveRound)?;
-        Self::new(
-            round.tie_breaker_tasks as usize,
-            coordinator.config.witness_nodes as usize,
-            coordinator.config.verification_percent,
-            round.clients_len as usize,
+        Self::from_round_inputs(
             round.random_seed,
+            round.clients_len as usize,
+            round.tie_breaker_tasks as usize,
+            &coordinator.config,
+        )
+    }
+
+    /// The selection the coordinator uses for a round, built only from values that are on chain:
+    /// the round's `random_seed`, `clients_len` and `tie_breaker_tasks`, and the run's config.
+    pub fn from_round_inputs(
+        seed: u64,
+        num_clients: usize,
+        tie_breaker_nodes: usize,
+        config: &CoordinatorConfig,
+    ) -> Result<Self, CoordinatorError> {
+        Self::new(
+            tie_breaker_nodes,
+            config.witness_nodes as usize,
+            config.verification_percent,
+            num_clients,
+            seed,
         )
+        .map(|selection| selection.with_config_salts(config))
+    }
+
+    /// Every client's committee and witness proofs for a round, indexed like `epoch_state.clients`,
+    /// exactly as the coordinator assigns and verifies them, so anyone can audit a round from chain data.
+    /// Only integer math and sha256 over little-endian bytes go into it, so every build agrees.
+    /// Inputs the coordinator would reject are an error, never a panic.
+    pub fn compute_assignment(
+        seed: u64,
+        num_clients: usize,
+        tie_breaker_nodes: usize,
+        config: &CoordinatorConfig,
+    ) -> Result<Vec<ClientAssignment>, CoordinatorError> {
+        let selection = Self::from_round_inputs(seed, num_clients, tie_breaker_nodes, config)?;
+        Ok((0..num_clients as u64)
+            .map(|index| ClientAssignment {
+                committee: selection.get_committee(index),
+                witness: selection.get_witness(index),
+            })
+            .collect())
     }
 
     pub fn get_witness(&self, index: u64) -> WitnessProof {
-        let position = self.compute_shuffled_index(index, WITNESS_SALT);
+        let position = self.compute_shuffled_index(index, &self.witness_salt);
         let witness = self.get_witness_from_position(position);
         WitnessProof {
             witness: witness.into(),
@@ -152,7 +221,7 @@ impl CommitteeSelection {
     }
 
     pub fn get_committee(&self, index: u64) -> CommitteeProof {
-        let position = self.compute_shuffled_index(index, COMMITTEE_SALT);
+        let position = self.compute_shuffled_index(index, &self.committee_salt);
         let committee = self.get_committee_from_position(position);
         CommitteeProof {
             committee,
@@ -201,12 +270,12 @@ impl CommitteeSelection {
     }
 
     fn verify_committee(&self, proof: &CommitteeProof) -> bool {
-        let position = self.compute_shuffled_index(proof.index, COMMITTEE_SALT);
+        let position = self.compute_shuffled_index(proof.index, &self.committee_salt);
         proof.position == position && proof.committee == self.get_committee_from_position(position)
     }
 
     fn verify_witness(&self, proof: &WitnessProof) -> bool {
-        let position = self.compute_shuffled_index(proof.index, WITNESS_SALT);
+        let position = self.compute_shuffled_index(proof.index, &self.witness_salt);
         proof.position == position
             && proof.witness == self.get_witness_from_position(position).into()
     }
@@ -379,6 +448,45 @@ mod tests {
         assert_eq!(cs1.get_seed(), cs2.get_seed());
     }
 
+    #[test]
+    fn test_salt_overrides() {
+        let positions = |cs: &CommitteeSelection| {
+            (0..100)
+                .map(|i| (cs.get_committee(i).committee, cs.get_witness(i).witness))
+                .collect::<Vec<_>>()
+        };
+        let default = CommitteeSelection::new(10, 5, 20, 100, 12345).unwrap();
+        let explicit = CommitteeSelection::new(10, 5, 20, 100, 12345)
+            .unwrap()
+            .with_salts(COMMITTEE_SALT, WITNESS_SALT);
+        assert_eq!(positions(&default), positions(&explicit));
+
+        let salted_a = CommitteeSelection::new(10, 5, 20, 100, 12345)
+            .unwrap()
+            .with_salts("test-committee", "test-witness");
+        let salted_b = CommitteeSelection::new(10, 5, 20, 100, 12345)
+            .unwrap()
+            .with_salts("test-committee", "test-witness");
+        assert_eq!(positions(&salted_a), positions(&salted_b));
+        assert_ne!(positions(&default), positions(&salted_a));
+
+        let config = CoordinatorConfig {
+            committee_salt: FixedString::from_str_truncated("test-committee"),
+            ..CoordinatorConfig::zeroed()
+        };
+        let from_config = CommitteeSelection::new(10, 5, 20, 100, 12345)
+            .unwrap()
+            .with_config_salts(&config);
+        assert_eq!(
+            from_config.committee_salt, "test-committee",
+            "configured committee salt should be used"
+        );
+        assert_eq!(
+            from_config.witness_salt, WITNESS_SALT,
+            "unset witness salt should fall back to the default"
+        );
+    }
+
     #[test]
     fn test_invalid_total_nodes() {
         assert!(CommitteeSelection::new(10, 5, 20, 9, 12345).is_err());
@@ -421,4 +529,51 @@ mod tests {
         assert_eq!(tie_breaker_count, 10);
         assert_eq!(trainer_count, 90);
     }
+
+    #[test]
+    fn test_assignment_is_pinned() {
+        // computed independently of this crate, so a change to the shuffle, the hashing,
+        // or the salts on any platform shows up here
+        let config = CoordinatorConfig {
+            witness_nodes: 3,
+            verification_percent: 50,
+            ..CoordinatorConfig::zeroed()
+        };
+        let assignment = CommitteeSelection::compute_assignment(12345, 10, 2, &config).unwrap();
+        let committee_positions: Vec<u64> =
+            assignment.iter().map(|a| a.committee.position).collect();
+        let witness_positions: Vec<u64> = assignment.iter().map(|a| a.witness.position).collect();
+        assert_eq!(committee_positions, [1, 7, 2, 9, 5, 0, 8, 3, 4, 6]);
+        assert_eq!(witness_positions, [7, 4, 9, 2, 6, 1, 0, 3, 5, 8]);
+
+        // 2 tie breakers, then (10 - 2) * 50% = 4 verifiers
+        assert_eq!(assignment[1].committee.committee, Committee::Trainer);
+        assert_eq!(assignment[5].committee.committee, Committee::TieBreaker);
+        assert_eq!(assignment[8].committee.committee, Committee::Verifier);
+        assert!(assignment[6].witness.witness.is_true());
+        assert!(!assignment[0].witness.witness.is_true());
+        for (index, a) in assignment.iter().enumerate() {
+            assert_eq!(
+                (a.committee.index, a.witness.index),
+                (index as u64, index as u64)
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_assignment_inputs_are_errors() {
+        let config = CoordinatorConfig {
+            verification_percent: 101,
+            ..CoordinatorConfig::zeroed()
+        };
+        assert!(CommitteeSelection::compute_assignment(1, 10, 0, &config).is_err());
+        assert!(
+            CommitteeSelection::compute_assignment(1, 5, 10, &CoordinatorConfig::zeroed()).is_err()
+        );
+        assert!(
+            CommitteeSelection::compute_assignment(1, 0, 0, &CoordinatorConfig::zeroed())
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -1,13 +1,16 @@
 use crate::{
     model::{Checkpoint, HubRepo, Model},
-    Commitment, Committee, CommitteeProof, CommitteeSelection, WitnessProof,
+    Commitment, Committee, CommitteeProof, CommitteeSelection, DataAssignmentStrategy,
+    WitnessProof,
 };
 
 use anchor_lang::{prelude::borsh, AnchorDeserialize, AnchorSerialize, InitSpace};
 use bytemuck::{Pod, Zeroable};
-use psyche_core::{sha256, Bloom, FixedString, FixedVec, MerkleRoot, NodeIdentity, SmallBoolean};
+use psyche_core::{
+    sha256, sha256v, Bloom, FixedString, FixedVec, MerkleRoot, NodeIdentity, SmallBoolean,
+};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, hash::Hash};
+use std::hash::Hash;
 use ts_rs::TS;
 
 pub const SOLANA_MAX_STRING_LEN: usize = 64;
@@ -129,6 +132,8 @@ pub struct Round {
     pub height: u32,
     pub clients_len: u16,
     pub tie_breaker_tasks: u16,
+    /// Set if the round failed to reach witness quorum and was advanced past by the witness timeout
+    pub degraded: SmallBoolean,
 }
 
 #[derive(
@@ -217,14 +222,158 @@ pub enum CoordinatorError {
     InvalidCommitteeProof,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TickResult {
     Ticked,
     EpochEnd(bool), // if successfully finished
 }
 
+/// What a tick would do, as computed by [`Coordinator::simulate_tick`].
+#[derive(Debug, Clone)]
+pub struct SimulatedTick<T> {
+    pub result: TickResult,
+    /// The coordinator as it would be after the tick.
+    pub coordinator: Box<Coordinator<T>>,
+    pub summary: TickSummary<T>,
+}
+
+/// The parts of a coordinator's state a tick changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickSummary<T> {
+    pub run_state: (RunState, RunState),
+    pub epoch: (u16, u16),
+    pub step: (u32, u32),
+    /// Clients that are in the epoch after the tick, but weren't before.
+    pub joined: Vec<T>,
+    /// Clients that were in the epoch before the tick, but aren't after.
+    pub left: Vec<T>,
+    /// Clients whose state the tick changed from healthy, with their new state.
+    pub unhealthy: Vec<(T, ClientState)>,
+    /// The witnesses of the round the tick starts, if it starts one.
+    pub witnesses: Option<Vec<T>>,
+}
+
+impl<T: NodeIdentity> TickSummary<T> {
+    fn between(before: &Coordinator<T>, after: &Coordinator<T>) -> Self {
+        let in_epoch = |coordinator: &Coordinator<T>, id: &T| {
+            coordinator.epoch_state.clients.iter().any(|c| c.id == *id)
+        };
+        let state_of = |coordinator: &Coordinator<T>, id: &T| {
+            coordinator
+                .epoch_state
+                .clients
+                .iter()
+                .chain(coordinator.epoch_state.exited_clients.iter())
+                .find(|c| c.id == *id)
+                .map(|c| c.state)
+        };
+        let round_height =
+            |coordinator: &Coordinator<T>| coordinator.current_round().map(|round| round.height);
+        let starts_round = after.run_state == RunState::RoundTrain
+            && (before.run_state != RunState::RoundTrain
+                || round_height(before) != round_height(after));
+        Self {
+            run_state: (before.run_state, after.run_state),
+            epoch: (before.progress.epoch, after.progress.epoch),
+            step: (before.progress.step, after.progress.step),
+            joined: after
+                .epoch_state
+                .clients
+                .iter()
+                .filter(|c| !in_epoch(before, &c.id))
+                .map(|c| c.id)
+                .collect(),
+            left: before
+                .epoch_state
+                .clients
+                .iter()
+                .filter(|c| !in_epoch(after, &c.id))
+                .map(|c| c.id)
+                .collect(),
+            unhealthy: after
+                .epoch_state
+                .clients
+                .iter()
+                .chain(after.epoch_state.exited_clients.iter())
+                .filter(|c| {
+                    c.state != ClientState::Healthy
+                        && state_of(before, &c.id).unwrap_or_default() == ClientState::Healthy
+                })
+                .map(|c| (c.id, c.state))
+                .collect(),
+            witnesses: starts_round
+                .then(|| CommitteeSelection::from_coordinator(after, 0).ok())
+                .flatten()
+                .map(|selection| {
+                    after
+                        .epoch_state
+                        .clients
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, _)| selection.get_witness(*index as u64).witness.is_true())
+                        .map(|(_, c)| c.id)
+                        .collect()
+                }),
+        }
+    }
+}
+
+/// Which of a round's clients its witnesses saw train, as computed by [`Coordinator::witness_participation`].
+/// Read off the witnesses' participant blooms, so an absent client is misreported as participating
+/// about [`BLOOM_FALSE_RATE`] of the time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(bound = "T: NodeIdentity")]
+pub struct WitnessParticipation<T> {
+    pub height: u32,
+    /// The clients selected to witness the round that sent their witness.
+    pub witnesses: Vec<T>,
+    /// The clients selected to witness the round that didn't.
+    pub absent_witnesses: Vec<T>,
+    /// The clients at least one witness saw, with how many of them did.
+    pub participating: Vec<(T, u16)>,
+    /// The clients no witness saw.
+    pub absent: Vec<T>,
+}
+
+impl<T: NodeIdentity> std::fmt::Display for TickSummary<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list<T: std::fmt::Display>(items: impl Iterator<Item = T>) -> String {
+            items
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        writeln!(f, "run state: {} -> {}", self.run_state.0, self.run_state.1)?;
+        writeln!(f, "epoch: {} -> {}", self.epoch.0, self.epoch.1)?;
+        writeln!(f, "step: {} -> {}", self.step.0, self.step.1)?;
+        if !self.joined.is_empty() {
+            writeln!(f, "joined: {}", list(self.joined.iter()))?;
+        }
+        if !self.left.is_empty() {
+            writeln!(f, "left: {}", list(self.left.iter()))?;
+        }
+        if !self.unhealthy.is_empty() {
+            let unhealthy = self
+                .unhealthy
+                .iter()
+                .map(|(id, state)| format!("{id} ({state})"));
+            writeln!(f, "unhealthy: {}", list(unhealthy))?;
+        }
+        if let Some(witnesses) = &self.witnesses {
+            writeln!(f, "witnesses: {}", list(witnesses.iter()))?;
+        }
+        Ok(())
+    }
+}
+
 pub type HealthChecks<T> = Vec<(T, CommitteeProof)>;
 
+/// Default number of rounds of history kept, see `CoordinatorConfig::num_stored_rounds`.
 pub const NUM_STORED_ROUNDS: usize = 4;
+/// Capacity of the round history in the coordinator account.
+pub const MAX_STORED_ROUNDS: usize = 8;
+/// Current, previous and previous-previous rounds are needed for overlapped pipelining.
+pub const MIN_STORED_ROUNDS: usize = 3;
 
 #[derive(
     Clone, Debug, Zeroable, Copy, Serialize, Deserialize, AnchorDeserialize, AnchorSerialize, TS,
@@ -236,6 +385,11 @@ pub struct CoordinatorConfig {
 
     pub max_round_train_time: u64,
     pub round_witness_time: u64,
+    /// Extra time, in seconds, to wait for witnesses after `round_witness_time` if quorum was not reached.
+    /// Once elapsed, the round is marked as degraded and the next round starts anyway.
+    /// If zero, failing to reach quorum ends the epoch instead.
+    #[serde(default)]
+    pub witness_timeout: u64,
     pub global_batch_size_warmup_tokens: u64,
 
     pub rounds_per_epoch: u32,
@@ -245,10 +399,109 @@ pub struct CoordinatorConfig {
     pub min_clients: u16,
     pub witness_nodes: u16,
 
+    /// Time, in seconds, to keep waiting for more clients once `init_min_clients` have joined.
+    /// Clients that join within this window take part in the epoch from its first round,
+    /// later ones wait for the next epoch. If zero, warmup starts as soon as the minimum is reached.
+    #[serde(default)]
+    pub warmup_grace_period: u64,
+
+    /// Absolute number of witnesses required to advance past a round.
+    /// If zero, `witness_quorum_percent` (or the default two-thirds quorum) is used.
+    #[serde(default)]
+    pub witness_quorum: u16,
+
     pub global_batch_size_start: u16,
     pub global_batch_size_end: u16,
 
     pub verification_percent: u8,
+
+    /// Percentage of the round's witness committee, as selected from its clients, required to advance past a round.
+    /// Only used if `witness_quorum` is zero. If both are zero, defaults to two thirds.
+    #[serde(default)]
+    pub witness_quorum_percent: u8,
+
+    /// Overrides the salt used to shuffle clients into committees. If empty, `COMMITTEE_SALT` is used.
+    /// Lives in the run config so every client derives the same committees.
+    #[serde(default)]
+    pub committee_salt: FixedString<32>,
+
+    /// Overrides the salt used to select witnesses. If empty, `WITNESS_SALT` is used.
+    #[serde(default)]
+    pub witness_salt: FixedString<32>,
+
+    /// How many rounds of history the coordinator keeps, between `MIN_STORED_ROUNDS` and `MAX_STORED_ROUNDS`.
+    /// Older rounds are overwritten. If zero, `NUM_STORED_ROUNDS` is used.
+    #[serde(default)]
+    pub num_stored_rounds: u8,
+
+    /// How each round's samples are split between trainers. Defaults to contiguous ranges.
+    #[serde(default)]
+    pub data_assignment_strategy: DataAssignmentStrategy,
+
+    /// How many clients can be waiting to join the next epoch. If zero, one less than `SOLANA_MAX_NUM_CLIENTS`,
+    /// so the limit is always reached before the Solana coordinator runs out of room for clients.
+    #[serde(default)]
+    pub max_pending_clients: u16,
+
+    /// What happens to a client joining while `max_pending_clients` are already waiting. Defaults to rejecting it.
+    #[serde(default)]
+    pub pending_clients_full_policy: PendingClientsFullPolicy,
+
+    /// How many samples the run's dataset has, i.e. the length of its data provider.
+    /// Only used with `max_data_epochs`. Clients refuse to join if their data provider's length differs.
+    #[serde(default)]
+    pub dataset_samples: u64,
+
+    /// Finish the run once it has trained on the dataset this many times, even before `total_steps`.
+    /// Each pass over the dataset reads its samples from the start again.
+    /// The last round isn't cut short at the limit, so unless the batch sizes line up with it,
+    /// it reads up to one batch past the final pass, from the start of the dataset.
+    /// If zero, only `total_steps` ends the run.
+    #[serde(default)]
+    pub max_data_epochs: u16,
+
+    /// Time, in seconds, after which a paused run resumes on its own, as if its owner had unpaused it.
+    /// Lets a run pause for a maintenance window without someone having to be around to resume it.
+    /// If zero, a paused run stays paused until it's resumed.
+    #[serde(default)]
+    pub auto_resume_after: u64,
+}
+
+/// One problem found when sanity checking a [`CoordinatorConfig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigViolation {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// What to do when a client joins a run whose pending clients are already at `CoordinatorConfig::max_pending_clients`.
+#[repr(u8)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Zeroable,
+    AnchorDeserialize,
+    AnchorSerialize,
+    Serialize,
+    Deserialize,
+    InitSpace,
+    TS,
+)]
+pub enum PendingClientsFullPolicy {
+    /// The join fails.
+    #[default]
+    Reject = 0,
+    /// The client that has been waiting the longest loses its place to the new one, and has to join again.
+    EvictOldest = 1,
 }
 
 #[derive(
@@ -257,7 +510,7 @@ pub struct CoordinatorConfig {
 #[repr(C)]
 #[serde(bound = "T: NodeIdentity")]
 pub struct CoordinatorEpochState<T> {
-    pub rounds: [Round; NUM_STORED_ROUNDS],
+    pub rounds: [Round; MAX_STORED_ROUNDS],
     /// **WARNING**: Using this can be a footgun:
     /// If you need to access the clients list for a particular round,
     /// e.g. when applying a message that could be from the previous round,
@@ -306,6 +559,11 @@ pub struct Coordinator<T> {
     #[serde(default)]
     pub run_state_start_unix_timestamp: u64,
 
+    /// When `init_min_clients` were first present while waiting for members, zero if they aren't.
+    /// The `warmup_grace_period` is counted from here.
+    #[serde(default)]
+    pub min_clients_reached_unix_timestamp: u64,
+
     #[serde(default)]
     pub pending_pause: SmallBoolean,
 }
@@ -438,6 +696,10 @@ impl<T: NodeIdentity> Coordinator<T> {
         random_seed: u64,
     ) -> std::result::Result<TickResult, CoordinatorError> {
         let ret = match self.run_state {
+            RunState::Paused if self.auto_resume_due(unix_timestamp) => {
+                self.start_waiting_for_members(unix_timestamp);
+                Ok(TickResult::Ticked)
+            }
             RunState::Uninitialized | RunState::Finished | RunState::Paused => {
                 Err(CoordinatorError::Halted)
             }
@@ -461,6 +723,28 @@ impl<T: NodeIdentity> Coordinator<T> {
         Ok(ret)
     }
 
+    /// Computes what [`Coordinator::tick`] would do with the same arguments, without changing anything.
+    /// The tick runs on a copy of the coordinator, with the given `random_seed` and nothing else random,
+    /// so ticking the real coordinator with the same arguments ends up in the same state.
+    pub fn simulate_tick<'a>(
+        &self,
+        new_clients: Option<impl ExactSizeIterator<Item = &'a T>>,
+        unix_timestamp: u64,
+        random_seed: u64,
+    ) -> std::result::Result<SimulatedTick<T>, CoordinatorError>
+    where
+        T: 'a,
+    {
+        let mut coordinator = Box::new(*self);
+        let result = coordinator.tick(new_clients, unix_timestamp, random_seed)?;
+        let summary = TickSummary::between(self, &coordinator);
+        Ok(SimulatedTick {
+            result,
+            coordinator,
+            summary,
+        })
+    }
+
     pub fn warmup_witness(
         &mut self,
         from: &T,
@@ -551,8 +835,16 @@ impl<T: NodeIdentity> Coordinator<T> {
             .witnesses
             .push(witness)
             .map_err(|_| CoordinatorError::WitnessesFull)?;
-
-        if round.witnesses.len() == witness_nodes && !(self.run_state == RunState::RoundWitness) {
+        let num_witnesses = round.witnesses.len();
+
+        // if a quorum is configured we can advance as soon as it's met,
+        // otherwise we wait for the full witness committee
+        let round = self.current_round_unchecked();
+        let quorum_reached = self.config.has_witness_quorum()
+            && num_witnesses >= self.witness_quorum(round) as usize;
+        if (num_witnesses == witness_nodes || quorum_reached)
+            && !(self.run_state == RunState::RoundWitness)
+        {
             self.change_state(unix_timestamp, RunState::RoundWitness);
         }
         Ok(())
@@ -657,6 +949,17 @@ impl<T: NodeIdentity> Coordinator<T> {
         }
     }
 
+    /// Ends the run at `total_steps` instead of the configured `total_steps`, including its learning rate schedule.
+    /// Can only shorten a run: extending it means changing the config itself, which is up to the run owner.
+    /// Returns the total steps now in effect.
+    pub fn override_total_steps(&mut self, total_steps: u32) -> u32 {
+        let total_steps = total_steps.min(self.config.total_steps);
+        self.config.total_steps = total_steps;
+        let Model::LLM(llm) = &mut self.model;
+        llm.lr_schedule = llm.lr_schedule.with_total_steps(total_steps);
+        total_steps
+    }
+
     pub fn resume(&mut self, unix_timestamp: u64) -> Result<(), CoordinatorError> {
         if self.run_state != RunState::Paused {
             return Err(CoordinatorError::CannotResume);
@@ -694,9 +997,19 @@ impl<T: NodeIdentity> Coordinator<T> {
         }
     }
 
-    pub fn witness_quorum(&self, num_witnesses: u16) -> u16 {
+    /// How many of `round`'s witnesses have to agree for it to count.
+    pub fn witness_quorum(&self, round: &Round) -> u16 {
+        if self.config.witness_quorum != 0 {
+            return self.config.witness_quorum;
+        }
+        if self.config.witness_quorum_percent != 0 {
+            let quorum = (Self::selected_witnesses(&self.config, round) as u32
+                * self.config.witness_quorum_percent as u32)
+                .div_ceil(100);
+            return (quorum as u16).max(1);
+        }
         let witness_nodes = match self.config.witness_nodes {
-            0 => num_witnesses,
+            0 => round.witnesses.len() as u16,
             witness_nodes => witness_nodes,
         };
         match witness_nodes {
@@ -708,14 +1021,45 @@ impl<T: NodeIdentity> Coordinator<T> {
         }
     }
 
+    /// How many clients the committee selection picked as witnesses for `round`.
+    fn selected_witnesses(config: &CoordinatorConfig, round: &Round) -> u16 {
+        let clients = round.clients_len.min(SOLANA_MAX_NUM_WITNESSES as u16);
+        match config.witness_nodes {
+            0 => clients,
+            witness_nodes => witness_nodes.min(clients),
+        }
+    }
+
     pub fn trainer_healthy(&self, id: &T) -> Result<bool, CoordinatorError> {
-        let prev_round_witnesses = &self
+        let prev_round = self
             .previous_round()
-            .ok_or(CoordinatorError::NoActiveRound)?
-            .witnesses;
+            .ok_or(CoordinatorError::NoActiveRound)?;
 
-        let score = Self::trainer_healthy_score_by_witnesses(id, prev_round_witnesses);
-        Ok(score >= self.witness_quorum(prev_round_witnesses.len() as u16))
+        // a degraded round never reached quorum, so we can't judge anyone's health from it
+        if prev_round.degraded.is_true() {
+            return Ok(true);
+        }
+
+        let score = Self::trainer_healthy_score_by_witnesses(id, &prev_round.witnesses);
+        Ok(score >= self.witness_quorum(prev_round))
+    }
+
+    /// The change in a client's rewards that settlement would make if the epoch ended now.
+    /// Mirrors the on-chain `EpochEnd` settlement: see `epoch_settlement`.
+    pub fn expected_client_delta(&self, id: &T, earning_rate: u64, slashing_rate: u64) -> i64 {
+        let state_in = |clients: &[Client<T>]| {
+            clients
+                .iter()
+                .find(|client| client.id == *id)
+                .map(|client| client.state)
+        };
+        let (earned, slashed) = epoch_settlement(
+            state_in(&self.epoch_state.clients),
+            state_in(&self.epoch_state.exited_clients),
+            earning_rate,
+            slashing_rate,
+        );
+        earned as i64 - slashed as i64
     }
 
     /// Computes the health score of a client based on witness confirmations.
@@ -733,10 +1077,56 @@ impl<T: NodeIdentity> Coordinator<T> {
         score
     }
 
+    /// Splits the clients of `round` into those its witnesses saw train and those they didn't, e.g. for participation heatmaps.
+    pub fn witness_participation(&self, round: &Round) -> WitnessParticipation<T> {
+        let clients = self.get_historical_clients(round.clients_len);
+        let witnesses: &[Witness] = &round.witnesses;
+        let mut participating = Vec::new();
+        let mut absent = Vec::new();
+        for client in &clients {
+            match Self::trainer_healthy_score_by_witnesses(&client.id, witnesses) {
+                0 => absent.push(client.id),
+                score => participating.push((client.id, score)),
+            }
+        }
+        let selection = CommitteeSelection::from_round_inputs(
+            round.random_seed,
+            round.clients_len as usize,
+            round.tie_breaker_tasks as usize,
+            &self.config,
+        )
+        .ok();
+        let (present_witnesses, absent_witnesses): (Vec<_>, Vec<_>) = clients
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                selection
+                    .as_ref()
+                    .is_some_and(|selection| selection.get_witness(*index as u64).witness.is_true())
+            })
+            .partition(|(index, _)| {
+                witnesses
+                    .iter()
+                    .any(|witness| witness.proof.index == *index as u64)
+            });
+        WitnessParticipation {
+            height: round.height,
+            witnesses: present_witnesses.into_iter().map(|(_, c)| c.id).collect(),
+            absent_witnesses: absent_witnesses.into_iter().map(|(_, c)| c.id).collect(),
+            participating,
+            absent,
+        }
+    }
+
+    /// Picks the commitment seen by the most witnesses, out of those seen by at least `witness_quorum`.
+    /// Commitments seen by equally many witnesses are ranked by hashing them with `tie_break_seed`
+    /// (the round's random seed), because the order commitments arrive in differs between nodes
+    /// and every node has to apply the same one.
     pub fn select_consensus_commitment_by_witnesses(
         commitments: &[Commitment],
         witnesses: &[Witness],
         witness_quorum: u16,
+        tie_break_seed: u64,
     ) -> Option<usize> {
         let mut scores = vec![0; commitments.len()];
         for witness in witnesses {
@@ -747,11 +1137,22 @@ impl<T: NodeIdentity> Coordinator<T> {
                 }
             }
         }
+        let tie_break_seed = tie_break_seed.to_le_bytes();
         scores
             .into_iter()
             .enumerate()
             .filter(|(_, score)| *score >= witness_quorum)
-            .max_by_key(|(_, score)| *score)
+            .max_by_key(|(index, score)| {
+                let commitment = &commitments[*index];
+                (
+                    *score,
+                    sha256v(&[
+                        &tie_break_seed,
+                        &commitment.data_hash,
+                        &commitment.signature,
+                    ]),
+                )
+            })
             .map(|(index, _)| index)
     }
 
@@ -780,7 +1181,7 @@ impl<T: NodeIdentity> Coordinator<T> {
             Some(round) => match self.epoch_state.rounds_head == 0 && round.height == 0 {
                 true => None,
                 false => match self.epoch_state.rounds_head == 0 {
-                    true => Some(&self.epoch_state.rounds[NUM_STORED_ROUNDS - 1]),
+                    true => Some(&self.epoch_state.rounds[self.config.num_stored_rounds() - 1]),
                     false => {
                         Some(&self.epoch_state.rounds[self.epoch_state.rounds_head as usize - 1])
                     }
@@ -795,8 +1196,8 @@ impl<T: NodeIdentity> Coordinator<T> {
             Some(round) => match self.epoch_state.rounds_head == 0 && round.height <= 1 {
                 true => None,
                 false => match self.epoch_state.rounds_head {
-                    0 => Some(&self.epoch_state.rounds[NUM_STORED_ROUNDS - 2]),
-                    1 => Some(&self.epoch_state.rounds[NUM_STORED_ROUNDS - 1]),
+                    0 => Some(&self.epoch_state.rounds[self.config.num_stored_rounds() - 2]),
+                    1 => Some(&self.epoch_state.rounds[self.config.num_stored_rounds() - 1]),
                     n => Some(&self.epoch_state.rounds[n as usize - 2]),
                 },
             },
@@ -886,11 +1287,26 @@ impl<T: NodeIdentity> Coordinator<T> {
             return Ok(TickResult::Ticked);
         };
 
-        if pending_clients.len() as u16 >= self.config.init_min_clients
+        let enough_clients = pending_clients.len() as u16 >= self.config.init_min_clients;
+        if !enough_clients {
+            self.min_clients_reached_unix_timestamp = 0;
+        } else if self.min_clients_reached_unix_timestamp == 0 {
+            self.min_clients_reached_unix_timestamp = unix_timestamp;
+        }
+
+        if enough_clients
             && self.check_timeout(unix_timestamp, WAITING_FOR_MEMBERS_EXTRA_SECONDS)
-        // This extra time allows for more clients to join even if the minimum number of clients is reached
+            // This extra time allows for more clients to join even if the minimum number of clients is reached
+            && unix_timestamp
+                >= self.min_clients_reached_unix_timestamp + self.config.warmup_grace_period
         {
-            let pending_clients: HashSet<_> = pending_clients.collect();
+            self.min_clients_reached_unix_timestamp = 0;
+
+            // Keep clients in a canonical order so that committee selection and data assignment
+            // don't depend on the order in which the backend happened to hand them to us.
+            let mut pending_clients: Vec<_> = pending_clients.collect();
+            pending_clients.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+            pending_clients.dedup();
 
             // Ensure every client in self.epoch_state.clients is present in pending_clients
             // If all clients are no longer present we need to use a Hub checkpoint since there
@@ -898,7 +1314,10 @@ impl<T: NodeIdentity> Coordinator<T> {
             let mut all_prev_clients_disconnected = true;
             for client in self.epoch_state.clients.iter() {
                 // If there's at least one client then we can use P2P
-                if pending_clients.contains(&client.id) {
+                if pending_clients
+                    .binary_search_by(|x| x.as_ref().cmp(client.id.as_ref()))
+                    .is_ok()
+                {
                     all_prev_clients_disconnected = false;
                     break;
                 }
@@ -980,13 +1399,27 @@ impl<T: NodeIdentity> Coordinator<T> {
         random_seed: u64,
     ) -> std::result::Result<TickResult, CoordinatorError> {
         if self.check_timeout(unix_timestamp, self.config.round_witness_time) {
-            // TODO: Punish idle witnesses
-            self.epoch_state.first_round = false.into();
-            self.progress.step += 1;
-
             let current_round = self.current_round_unchecked();
             let height = current_round.height;
             let num_witnesses = current_round.witnesses.len() as u16;
+            let quorum_reached =
+                num_witnesses != 0 && num_witnesses >= self.witness_quorum(current_round);
+
+            // give late witnesses some extra time before we consider the round stalled
+            if num_witnesses != 0
+                && !quorum_reached
+                && self.config.witness_timeout != 0
+                && !self.check_timeout(
+                    unix_timestamp,
+                    self.config.round_witness_time + self.config.witness_timeout,
+                )
+            {
+                return Ok(TickResult::Ticked);
+            }
+
+            // TODO: Punish idle witnesses
+            self.epoch_state.first_round = false.into();
+            self.progress.step += 1;
             self.move_clients_to_exited(height);
 
             // If there are not witnesses, then we can't distinguish from
@@ -1002,14 +1435,20 @@ impl<T: NodeIdentity> Coordinator<T> {
             // If we reach the end of an epoch or if we don't reach the min number of
             // clients or registered witnesses for the current round, we change to Cooldown
             if height == self.config.rounds_per_epoch - 1
+                || self.is_data_exhausted(self.next_data_index())
                 || self.epoch_state.clients.len() < self.config.min_clients as usize
-                || num_witnesses < self.witness_quorum(num_witnesses)
+                || (!quorum_reached && self.config.witness_timeout == 0)
                 || self.pending_pause.is_true()
             {
                 self.start_cooldown(unix_timestamp);
                 return Ok(TickResult::Ticked);
             }
 
+            // the witness timeout elapsed without reaching quorum, move on without it
+            if !quorum_reached {
+                self.current_round_mut_unchecked().degraded = true.into();
+            }
+
             self.start_round_train(unix_timestamp, random_seed, 0);
         }
         Ok(TickResult::Ticked)
@@ -1020,9 +1459,7 @@ impl<T: NodeIdentity> Coordinator<T> {
         unix_timestamp: u64,
     ) -> std::result::Result<TickResult, CoordinatorError> {
         if self.check_timeout(unix_timestamp, self.config.cooldown_time) {
-            let last_round_batch_size = self.get_target_global_batch_size(self.current_round());
-            self.progress.epoch_start_data_index =
-                self.current_round_unchecked().data_index + last_round_batch_size as u64;
+            self.progress.epoch_start_data_index = self.next_data_index();
             self.progress.epoch += 1;
 
             let current_round = self.current_round_unchecked();
@@ -1053,6 +1490,24 @@ impl<T: NodeIdentity> Coordinator<T> {
         }
     }
 
+    /// Where the round after the current one starts reading data.
+    fn next_data_index(&self) -> u64 {
+        let last_round_batch_size = self.get_target_global_batch_size(self.current_round());
+        self.current_round_unchecked().data_index + last_round_batch_size as u64
+    }
+
+    fn is_data_exhausted(&self, data_index: u64) -> bool {
+        self.config
+            .data_index_limit()
+            .is_some_and(|limit| data_index >= limit)
+    }
+
+    /// Whether a paused run has been paused for `auto_resume_after`, and should resume.
+    fn auto_resume_due(&self, unix_timestamp: u64) -> bool {
+        self.config.auto_resume_after != 0
+            && self.check_timeout(unix_timestamp, self.config.auto_resume_after)
+    }
+
     fn check_timeout(&self, unix_timestamp: u64, duration: u64) -> bool {
         self.run_state_start_unix_timestamp != unix_timestamp
             && unix_timestamp >= duration + self.run_state_start_unix_timestamp
@@ -1075,7 +1530,7 @@ impl<T: NodeIdentity> Coordinator<T> {
                 let prev_round_batch_size =
                     self.get_global_batch_size_for_tokens(prev_round_start_tokens);
                 (
-                    (self.epoch_state.rounds_head + 1) as usize % self.epoch_state.rounds.len(),
+                    (self.epoch_state.rounds_head + 1) as usize % self.config.num_stored_rounds(),
                     prev_round.height + 1,
                     prev_round.data_index + prev_round_batch_size as u64,
                 )
@@ -1087,6 +1542,7 @@ impl<T: NodeIdentity> Coordinator<T> {
         round.data_index = next_data_index;
         round.tie_breaker_tasks = tie_breaker_tasks;
         round.random_seed = random_seed;
+        round.degraded = false.into();
         round.witnesses.clear();
         self.change_state(unix_timestamp, RunState::RoundTrain);
     }
@@ -1096,9 +1552,12 @@ impl<T: NodeIdentity> Coordinator<T> {
     }
 
     fn start_waiting_for_members(&mut self, unix_timestamp: u64) {
+        self.min_clients_reached_unix_timestamp = 0;
         self.change_state(
             unix_timestamp,
-            if self.progress.step < self.config.total_steps {
+            if self.progress.step < self.config.total_steps
+                && !self.is_data_exhausted(self.progress.epoch_start_data_index)
+            {
                 RunState::WaitingForMembers
             } else {
                 RunState::Finished
@@ -1140,19 +1599,249 @@ impl<T: NodeIdentity> Coordinator<T> {
 
 impl CoordinatorConfig {
     pub fn check(&self) -> bool {
-        self.max_round_train_time != 0
-            && self.round_witness_time != 0
-            && self.min_clients != 0
-            && self.init_min_clients >= self.min_clients
-            && self.init_min_clients as usize <= SOLANA_MAX_NUM_CLIENTS
-            && self.global_batch_size_start != 0
-            && self.global_batch_size_end != 0
-            && self.global_batch_size_end >= self.global_batch_size_start
-            && self.rounds_per_epoch >= 4 // need at least 4 rounds per epoch for overlapped pipeling
-            && self.total_steps != 0
-            && self.witness_nodes <= self.min_clients
-            && self.witness_nodes as usize <= SOLANA_MAX_NUM_WITNESSES
-            && self.cooldown_time > 0
+        self.validate().is_ok()
+    }
+
+    /// Sanity checks the config, returning every problem found rather than just the first.
+    pub fn validate(&self) -> Result<(), Vec<ConfigViolation>> {
+        let mut violations = Vec::new();
+        // messages are only formatted for failed checks, this also runs on-chain
+        let mut require = |ok: bool, field: &'static str, message: &dyn Fn() -> String| {
+            if !ok {
+                violations.push(ConfigViolation {
+                    field,
+                    message: message(),
+                });
+            }
+        };
+
+        // with no warmup, the first tick would start training before any client has loaded the model
+        require(self.warmup_time != 0, "warmup_time", &|| {
+            "must be greater than 0".to_string()
+        });
+        require(self.cooldown_time != 0, "cooldown_time", &|| {
+            "must be greater than 0".to_string()
+        });
+        require(
+            self.max_round_train_time != 0,
+            "max_round_train_time",
+            &|| "must be greater than 0".to_string(),
+        );
+        require(self.round_witness_time != 0, "round_witness_time", &|| {
+            "must be greater than 0".to_string()
+        });
+        require(self.min_clients != 0, "min_clients", &|| {
+            "must be greater than 0".to_string()
+        });
+        require(
+            self.init_min_clients >= self.min_clients,
+            "init_min_clients",
+            &|| {
+                format!(
+                    "{} is less than min_clients ({})",
+                    self.init_min_clients, self.min_clients
+                )
+            },
+        );
+        require(
+            self.init_min_clients as usize <= SOLANA_MAX_NUM_CLIENTS,
+            "init_min_clients",
+            &|| {
+                format!(
+                    "{} is more than the maximum of {SOLANA_MAX_NUM_CLIENTS} clients",
+                    self.init_min_clients
+                )
+            },
+        );
+        require(
+            (self.max_pending_clients as usize) < SOLANA_MAX_NUM_CLIENTS,
+            "max_pending_clients",
+            &|| {
+                format!(
+                    "{} must be less than the maximum of {SOLANA_MAX_NUM_CLIENTS} clients",
+                    self.max_pending_clients
+                )
+            },
+        );
+        require(
+            self.max_pending_clients() >= self.init_min_clients as usize,
+            "max_pending_clients",
+            &|| {
+                format!(
+                    "{} is less than init_min_clients ({}), so the run could never start",
+                    self.max_pending_clients(),
+                    self.init_min_clients
+                )
+            },
+        );
+        require(
+            self.global_batch_size_start != 0,
+            "global_batch_size_start",
+            &|| "must be greater than 0".to_string(),
+        );
+        require(
+            self.global_batch_size_end != 0,
+            "global_batch_size_end",
+            &|| "must be greater than 0".to_string(),
+        );
+        require(
+            self.global_batch_size_end >= self.global_batch_size_start,
+            "global_batch_size_end",
+            &|| {
+                format!(
+                    "{} is less than global_batch_size_start ({})",
+                    self.global_batch_size_end, self.global_batch_size_start
+                )
+            },
+        );
+        // need at least 4 rounds per epoch for overlapped pipeling
+        require(self.rounds_per_epoch >= 4, "rounds_per_epoch", &|| {
+            format!("{} is less than 4", self.rounds_per_epoch)
+        });
+        require(self.total_steps != 0, "total_steps", &|| {
+            "must be greater than 0".to_string()
+        });
+        require(
+            self.witness_nodes <= self.min_clients,
+            "witness_nodes",
+            &|| {
+                format!(
+                    "{} is more than min_clients ({})",
+                    self.witness_nodes, self.min_clients
+                )
+            },
+        );
+        require(
+            self.witness_nodes as usize <= SOLANA_MAX_NUM_WITNESSES,
+            "witness_nodes",
+            &|| {
+                format!(
+                    "{} is more than the maximum of {SOLANA_MAX_NUM_WITNESSES} witnesses",
+                    self.witness_nodes
+                )
+            },
+        );
+        require(
+            self.witness_quorum_percent <= 100,
+            "witness_quorum_percent",
+            &|| format!("{} is more than 100", self.witness_quorum_percent),
+        );
+        require(
+            self.witness_quorum == 0 || self.witness_quorum_percent == 0,
+            "witness_quorum",
+            &|| "can't be set together with witness_quorum_percent".to_string(),
+        );
+        require(
+            self.witness_quorum <= self.max_witness_committee_size(),
+            "witness_quorum",
+            &|| {
+                format!(
+                    "{} is more than the {} witnesses in a round",
+                    self.witness_quorum,
+                    self.max_witness_committee_size()
+                )
+            },
+        );
+        require(
+            self.max_data_epochs == 0 || self.dataset_samples != 0,
+            "dataset_samples",
+            &|| "must be greater than 0 when max_data_epochs is set".to_string(),
+        );
+        require(
+            self.dataset_samples
+                .checked_mul(self.max_data_epochs as u64)
+                .is_some(),
+            "max_data_epochs",
+            &|| {
+                format!(
+                    "{} passes over {} samples overflow the data index",
+                    self.max_data_epochs, self.dataset_samples
+                )
+            },
+        );
+        require(
+            self.num_stored_rounds == 0
+                || (MIN_STORED_ROUNDS..=MAX_STORED_ROUNDS)
+                    .contains(&(self.num_stored_rounds as usize)),
+            "num_stored_rounds",
+            &|| {
+                format!(
+                    "{} is not between {MIN_STORED_ROUNDS} and {MAX_STORED_ROUNDS}",
+                    self.num_stored_rounds
+                )
+            },
+        );
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
+    /// Like [`CoordinatorConfig::validate`], plus the checks that need the run's model.
+    pub fn validate_with_model(&self, model: &Model) -> Result<(), Vec<ConfigViolation>> {
+        let mut violations = self.validate().err().unwrap_or_default();
+        let Model::LLM(llm) = model;
+        let warmup_steps = llm.lr_schedule.get_warmup_steps();
+        if self.total_steps != 0 && warmup_steps >= self.total_steps {
+            violations.push(ConfigViolation {
+                field: "total_steps",
+                message: format!(
+                    "{} is not more than the LR schedule's {warmup_steps} warmup steps, so the run would end before warmup does",
+                    self.total_steps
+                ),
+            });
+        }
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
+    pub fn max_pending_clients(&self) -> usize {
+        match self.max_pending_clients {
+            0 => SOLANA_MAX_NUM_CLIENTS - 1,
+            max_pending_clients => (max_pending_clients as usize).min(SOLANA_MAX_NUM_CLIENTS - 1),
+        }
+    }
+
+    /// The data index the run finishes at, once it has trained on `max_data_epochs` passes over the dataset.
+    /// The round that crosses it still trains on its whole batch.
+    pub fn data_index_limit(&self) -> Option<u64> {
+        match self.max_data_epochs {
+            0 => None,
+            // validate() rejects limits that overflow, so this never ends a run early.
+            max_data_epochs => Some(
+                self.dataset_samples
+                    .checked_mul(max_data_epochs as u64)
+                    .unwrap_or(u64::MAX),
+            ),
+        }
+    }
+
+    /// How many samples to wrap data indices around, if later passes over the dataset reuse its samples.
+    pub fn wrapping_dataset_samples(&self) -> Option<u64> {
+        (self.max_data_epochs != 0).then_some(self.dataset_samples)
+    }
+
+    pub fn num_stored_rounds(&self) -> usize {
+        match self.num_stored_rounds {
+            0 => NUM_STORED_ROUNDS,
+            num_stored_rounds => num_stored_rounds as usize,
+        }
+    }
+
+    /// Whether a witness quorum was explicitly configured, rather than relying on the default.
+    pub fn has_witness_quorum(&self) -> bool {
+        self.witness_quorum != 0 || self.witness_quorum_percent != 0
+    }
+
+    /// The largest witness committee that is guaranteed to exist for a running epoch.
+    fn max_witness_committee_size(&self) -> u16 {
+        match self.witness_nodes {
+            0 => self.min_clients.min(SOLANA_MAX_NUM_WITNESSES as u16),
+            witness_nodes => witness_nodes,
+        }
     }
 
     pub fn get_batch_size(&self, total_tokens_processed: u64) -> u16 {
@@ -1174,3 +1863,903 @@ impl CoordinatorProgress {
         self.step > 0
     }
 }
+
+/// What a client is credited with at the end of an epoch, as `(earned, slashed)`, given its state among
+/// the epoch's clients that finished it and among those that exited it (if it's in either).
+/// Finishing `Healthy` earns `earning_rate` once for the whole epoch, and exiting `Ejected` is slashed `slashing_rate`.
+/// `Dropped` and `Withdrawn` clients are neither paid nor slashed.
+pub fn epoch_settlement(
+    finished: Option<ClientState>,
+    exited: Option<ClientState>,
+    earning_rate: u64,
+    slashing_rate: u64,
+) -> (u64, u64) {
+    let earned = match finished {
+        Some(ClientState::Healthy) => earning_rate,
+        _ => 0,
+    };
+    let slashed = match exited {
+        Some(ClientState::Ejected) => slashing_rate,
+        _ => 0,
+    };
+    (earned, slashed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{model::LLM, test_utils::TestClientId};
+    use psyche_core::CosineLR;
+    use std::collections::HashSet;
+
+    const WARMUP_TIME: u64 = 10;
+    const MAX_ROUND_TRAIN_TIME: u64 = 20;
+    const ROUND_WITNESS_TIME: u64 = 5;
+
+    fn test_config(num_clients: u16) -> CoordinatorConfig {
+        CoordinatorConfig {
+            warmup_time: WARMUP_TIME,
+            cooldown_time: 5,
+            max_round_train_time: MAX_ROUND_TRAIN_TIME,
+            round_witness_time: ROUND_WITNESS_TIME,
+            witness_timeout: 0,
+            global_batch_size_warmup_tokens: 0,
+            rounds_per_epoch: 10,
+            total_steps: 100,
+            init_min_clients: num_clients,
+            min_clients: num_clients,
+            witness_nodes: num_clients,
+            witness_quorum: 0,
+            global_batch_size_start: 8,
+            global_batch_size_end: 8,
+            verification_percent: 0,
+            witness_quorum_percent: 0,
+            committee_salt: FixedString::new(),
+            witness_salt: FixedString::new(),
+            num_stored_rounds: 0,
+            data_assignment_strategy: DataAssignmentStrategy::Contiguous,
+            max_pending_clients: 0,
+            pending_clients_full_policy: PendingClientsFullPolicy::Reject,
+            warmup_grace_period: 0,
+            dataset_samples: 0,
+            max_data_epochs: 0,
+            auto_resume_after: 0,
+        }
+    }
+
+    fn test_clients(num_clients: u64) -> Vec<TestClientId> {
+        (0..num_clients).map(TestClientId).collect()
+    }
+
+    fn new_coordinator(config: CoordinatorConfig) -> Coordinator<TestClientId> {
+        let mut coordinator = Coordinator::<TestClientId>::zeroed();
+        coordinator.run_id = FixedString::from_str_truncated("test");
+        coordinator.run_state = RunState::WaitingForMembers;
+        coordinator.model = Model::LLM(LLM::dummy());
+        coordinator.config = config;
+        coordinator.progress = CoordinatorProgress::default();
+        coordinator.epoch_state = CoordinatorEpochState::default();
+        assert!(coordinator.config.check());
+        coordinator
+    }
+
+    /// Ticks a fresh coordinator through WaitingForMembers and Warmup into the first training round.
+    /// Returns the timestamp at which training started.
+    fn start_training(
+        coordinator: &mut Coordinator<TestClientId>,
+        clients: &[TestClientId],
+    ) -> u64 {
+        let mut now = 100;
+        coordinator.tick(Some(clients.iter()), now, 1234).unwrap();
+        assert_eq!(coordinator.run_state, RunState::Warmup);
+        now += WARMUP_TIME;
+        coordinator
+            .tick(None::<std::slice::Iter<'_, TestClientId>>, now, 1234)
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundTrain);
+        now
+    }
+
+    fn send_witness(
+        coordinator: &mut Coordinator<TestClientId>,
+        client: &TestClientId,
+        unix_timestamp: u64,
+    ) {
+        let index = coordinator
+            .epoch_state
+            .clients
+            .iter()
+            .position(|x| x.id == *client)
+            .unwrap();
+        let proof = CommitteeSelection::from_coordinator(coordinator, 0)
+            .unwrap()
+            .get_witness(index as u64);
+        assert!(proof.witness.is_true());
+        coordinator
+            .witness(
+                client,
+                Witness {
+                    proof,
+                    ..Default::default()
+                },
+                unix_timestamp,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_witness_quorum_config_check() {
+        let mut config = test_config(4);
+        config.witness_quorum = 3;
+        assert!(config.check());
+        config.witness_quorum = 5;
+        assert!(!config.check());
+
+        let mut config = test_config(4);
+        config.witness_quorum_percent = 75;
+        assert!(config.check());
+        config.witness_quorum_percent = 101;
+        assert!(!config.check());
+
+        // absolute and fractional quorums are mutually exclusive
+        let mut config = test_config(4);
+        config.witness_quorum = 3;
+        config.witness_quorum_percent = 75;
+        assert!(!config.check());
+    }
+
+    #[test]
+    fn test_num_stored_rounds_config_check() {
+        let mut config = test_config(4);
+        assert_eq!(config.num_stored_rounds(), NUM_STORED_ROUNDS);
+        config.num_stored_rounds = MIN_STORED_ROUNDS as u8;
+        assert!(config.check());
+        config.num_stored_rounds = MAX_STORED_ROUNDS as u8;
+        assert!(config.check());
+        config.num_stored_rounds = MIN_STORED_ROUNDS as u8 - 1;
+        assert!(!config.check());
+        config.num_stored_rounds = MAX_STORED_ROUNDS as u8 + 1;
+        assert!(!config.check());
+    }
+
+    fn violated_fields(result: Result<(), Vec<ConfigViolation>>) -> Vec<&'static str> {
+        result
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|violation| violation.field)
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation() {
+        assert_eq!(test_config(4).validate(), Ok(()));
+
+        let mut config = test_config(4);
+        config.warmup_time = 0;
+        config.min_clients = 6;
+        config.rounds_per_epoch = 3;
+        assert_eq!(
+            violated_fields(config.validate()),
+            ["warmup_time", "init_min_clients", "rounds_per_epoch"]
+        );
+
+        let mut config = test_config(4);
+        config.global_batch_size_start = 16;
+        config.witness_quorum = 2;
+        config.witness_quorum_percent = 101;
+        config.num_stored_rounds = MAX_STORED_ROUNDS as u8 + 1;
+        let violations = config.validate().unwrap_err();
+        assert_eq!(
+            violations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "global_batch_size_end: 8 is less than global_batch_size_start (16)",
+                "witness_quorum_percent: 101 is more than 100",
+                "witness_quorum: can't be set together with witness_quorum_percent",
+                "num_stored_rounds: 9 is not between 3 and 8",
+            ]
+        );
+        assert!(!config.check());
+    }
+
+    #[test]
+    fn test_validate_with_model() {
+        let config = test_config(4);
+        let mut llm = LLM::dummy();
+        llm.lr_schedule = CosineLR::new(4e-4, 50, 0.0, 100, 4e-5).into();
+        assert_eq!(config.validate_with_model(&Model::LLM(llm)), Ok(()));
+
+        // the run is over before warmup ends
+        llm.lr_schedule = CosineLR::new(4e-4, 100, 0.0, 1000, 4e-5).into();
+        let mut broken = config;
+        broken.cooldown_time = 0;
+        assert_eq!(
+            violated_fields(broken.validate_with_model(&Model::LLM(llm))),
+            ["cooldown_time", "total_steps"]
+        );
+    }
+
+    #[test]
+    fn test_keeps_configured_number_of_rounds() {
+        let clients = test_clients(4);
+        let mut config = test_config(4);
+        config.rounds_per_epoch = 20;
+        config.num_stored_rounds = 6;
+        let mut coordinator = new_coordinator(config);
+        let mut now = start_training(&mut coordinator, &clients);
+
+        for height in 1..=8 {
+            for client in &clients {
+                send_witness(&mut coordinator, client, now + 1);
+            }
+            assert_eq!(coordinator.run_state, RunState::RoundWitness);
+            now += 1 + ROUND_WITNESS_TIME;
+            coordinator
+                .tick(None::<std::slice::Iter<'_, TestClientId>>, now, 5678)
+                .unwrap();
+            assert_eq!(coordinator.run_state, RunState::RoundTrain);
+
+            let current = coordinator.current_round().unwrap();
+            assert_eq!(current.height, height);
+            assert_eq!(coordinator.epoch_state.rounds_head, height % 6);
+            assert_eq!(coordinator.previous_round().unwrap().height, height - 1);
+        }
+
+        // heights 0 to 2 were evicted, 3 to 8 are retained, and the slots past 6 were never touched
+        let mut heights = coordinator.epoch_state.rounds[..6]
+            .iter()
+            .map(|round| round.height)
+            .collect::<Vec<_>>();
+        heights.sort();
+        assert_eq!(heights, (3..=8).collect::<Vec<_>>());
+        assert!(coordinator.epoch_state.rounds[6..]
+            .iter()
+            .all(|round| round.height == 0));
+    }
+
+    #[test]
+    fn test_expected_client_delta() {
+        const EARNING_RATE: u64 = 100;
+        const SLASHING_RATE: u64 = 40;
+
+        let clients = test_clients(4);
+        let mut coordinator = new_coordinator(test_config(4));
+        start_training(&mut coordinator, &clients);
+
+        let delta = |coordinator: &Coordinator<TestClientId>, client: &TestClientId| {
+            coordinator.expected_client_delta(client, EARNING_RATE, SLASHING_RATE)
+        };
+
+        // healthy clients earn once for the epoch, however many rounds it has
+        assert_eq!(delta(&coordinator, &clients[0]), EARNING_RATE as i64);
+        assert_eq!(delta(&coordinator, &TestClientId(42)), 0);
+
+        // dropped clients are neither paid nor slashed
+        coordinator.epoch_state.clients[1].state = ClientState::Dropped;
+        assert_eq!(delta(&coordinator, &clients[1]), 0);
+        coordinator.epoch_state.clients[1].state = ClientState::Withdrawn;
+        assert_eq!(delta(&coordinator, &clients[1]), 0);
+
+        // only clients that exited the epoch ejected are slashed
+        let mut ejected = coordinator.epoch_state.clients[2];
+        ejected.state = ClientState::Ejected;
+        coordinator
+            .epoch_state
+            .exited_clients
+            .push(ejected)
+            .unwrap();
+        coordinator.epoch_state.clients.remove(2);
+        assert_eq!(delta(&coordinator, &clients[2]), -(SLASHING_RATE as i64));
+
+        let mut dropped = coordinator.epoch_state.clients[2];
+        dropped.state = ClientState::Dropped;
+        coordinator
+            .epoch_state
+            .exited_clients
+            .push(dropped)
+            .unwrap();
+        coordinator.epoch_state.clients.remove(2);
+        assert_eq!(delta(&coordinator, &clients[3]), 0);
+    }
+
+    #[test]
+    fn test_witness_quorum_values() {
+        let round = Round {
+            clients_len: 4,
+            ..Round::zeroed()
+        };
+        let mut config = test_config(4);
+        assert_eq!(new_coordinator(config).witness_quorum(&round), 2);
+
+        config.witness_quorum = 3;
+        assert_eq!(new_coordinator(config).witness_quorum(&round), 3);
+
+        config.witness_quorum = 0;
+        config.witness_quorum_percent = 50;
+        assert_eq!(new_coordinator(config).witness_quorum(&round), 2);
+
+        config.witness_quorum_percent = 51;
+        assert_eq!(new_coordinator(config).witness_quorum(&round), 3);
+
+        // without a fixed committee size, the percentage is of every client selected as a witness,
+        // not of those that have witnessed so far
+        config.witness_nodes = 0;
+        config.witness_quorum_percent = 50;
+        let mut round = Round {
+            clients_len: 6,
+            ..Round::zeroed()
+        };
+        round.witnesses.push(Witness::default()).unwrap();
+        round.witnesses.push(Witness::default()).unwrap();
+        assert_eq!(new_coordinator(config).witness_quorum(&round), 3);
+
+        // nor of a committee bigger than the round has clients
+        config.witness_nodes = 10;
+        assert_eq!(new_coordinator(config).witness_quorum(&round), 3);
+    }
+
+    #[test]
+    fn test_waits_for_configured_witness_quorum() {
+        let clients = test_clients(4);
+        let mut config = test_config(4);
+        config.witness_quorum = 3;
+        let mut coordinator = new_coordinator(config);
+        let now = start_training(&mut coordinator, &clients);
+
+        send_witness(&mut coordinator, &clients[0], now + 1);
+        send_witness(&mut coordinator, &clients[1], now + 1);
+        assert_eq!(coordinator.run_state, RunState::RoundTrain);
+
+        send_witness(&mut coordinator, &clients[2], now + 2);
+        assert_eq!(coordinator.run_state, RunState::RoundWitness);
+
+        coordinator
+            .tick(
+                None::<std::slice::Iter<'_, TestClientId>>,
+                now + 2 + ROUND_WITNESS_TIME,
+                5678,
+            )
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundTrain);
+        assert_eq!(coordinator.current_round().unwrap().height, 1);
+    }
+
+    #[test]
+    fn test_unmet_witness_quorum_times_out() {
+        let clients = test_clients(4);
+        let mut config = test_config(4);
+        config.witness_quorum = 3;
+        let mut coordinator = new_coordinator(config);
+        let now = start_training(&mut coordinator, &clients);
+
+        send_witness(&mut coordinator, &clients[0], now + 1);
+        send_witness(&mut coordinator, &clients[1], now + 1);
+
+        coordinator
+            .tick(
+                None::<std::slice::Iter<'_, TestClientId>>,
+                now + MAX_ROUND_TRAIN_TIME - 1,
+                5678,
+            )
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundTrain);
+
+        let now = now + MAX_ROUND_TRAIN_TIME;
+        coordinator
+            .tick(None::<std::slice::Iter<'_, TestClientId>>, now, 5678)
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundWitness);
+
+        coordinator
+            .tick(
+                None::<std::slice::Iter<'_, TestClientId>>,
+                now + ROUND_WITNESS_TIME,
+                5678,
+            )
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::Cooldown);
+    }
+
+    #[test]
+    fn test_witness_timeout_advances_degraded_round() {
+        let clients = test_clients(4);
+        let mut config = test_config(4);
+        config.witness_quorum = 3;
+        config.witness_timeout = 10;
+        let mut coordinator = new_coordinator(config);
+        let now = start_training(&mut coordinator, &clients);
+
+        send_witness(&mut coordinator, &clients[0], now + 1);
+        send_witness(&mut coordinator, &clients[1], now + 1);
+
+        let now = now + MAX_ROUND_TRAIN_TIME;
+        coordinator
+            .tick(None::<std::slice::Iter<'_, TestClientId>>, now, 5678)
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundWitness);
+
+        // quorum not reached, but we keep waiting for late witnesses instead of ending the epoch
+        coordinator
+            .tick(
+                None::<std::slice::Iter<'_, TestClientId>>,
+                now + ROUND_WITNESS_TIME,
+                5678,
+            )
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundWitness);
+
+        coordinator
+            .tick(
+                None::<std::slice::Iter<'_, TestClientId>>,
+                now + ROUND_WITNESS_TIME + 10,
+                5678,
+            )
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundTrain);
+        assert_eq!(coordinator.current_round().unwrap().height, 1);
+        assert!(coordinator.current_round().unwrap().degraded.is_false());
+        assert!(coordinator.previous_round().unwrap().degraded.is_true());
+        assert_eq!(coordinator.epoch_state.clients.len(), 4);
+    }
+
+    #[test]
+    fn test_clients_joining_within_grace_period_train_from_first_round() {
+        let mut config = test_config(2);
+        config.warmup_grace_period = 10;
+        let mut coordinator = new_coordinator(config);
+        let clients = test_clients(4);
+
+        let mut now = 100;
+        coordinator
+            .tick(Some(clients[..2].iter()), now, 1234)
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::WaitingForMembers);
+
+        // a third client shows up before the grace period is over
+        now += 9;
+        coordinator
+            .tick(Some(clients[..3].iter()), now, 1234)
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::WaitingForMembers);
+
+        now += 1;
+        coordinator
+            .tick(Some(clients[..3].iter()), now, 1234)
+            .unwrap();
+        assert_eq!(coordinator.run_state, RunState::Warmup);
+        assert_eq!(coordinator.epoch_state.clients.len(), 3);
+
+        // the fourth one is too late for this epoch
+        now += WARMUP_TIME;
+        coordinator.tick(Some(clients.iter()), now, 1234).unwrap();
+        assert_eq!(coordinator.run_state, RunState::RoundTrain);
+        assert_eq!(coordinator.current_round().unwrap().clients_len, 3);
+        assert!(!coordinator
+            .epoch_state
+            .clients
+            .iter()
+            .any(|client| client.id == clients[3]));
+    }
+
+    #[test]
+    fn test_grace_period_restarts_when_clients_drop_below_minimum() {
+        let mut config = test_config(2);
+        config.warmup_grace_period = 10;
+        let mut coordinator = new_coordinator(config);
+        let clients = test_clients(2);
+
+        coordinator.tick(Some(clients.iter()), 100, 1234).unwrap();
+        coordinator
+            .tick(Some(clients[..1].iter()), 105, 1234)
+            .unwrap();
+        coordinator.tick(Some(clients.iter()), 108, 1234).unwrap();
+        coordinator.tick(Some(clients.iter()), 117, 1234).unwrap();
+        assert_eq!(coordinator.run_state, RunState::WaitingForMembers);
+        coordinator.tick(Some(clients.iter()), 118, 1234).unwrap();
+        assert_eq!(coordinator.run_state, RunState::Warmup);
+    }
+
+    #[test]
+    fn test_total_steps_override() {
+        let mut coordinator = new_coordinator(test_config(1));
+        let Model::LLM(llm) = &mut coordinator.model;
+        llm.lr_schedule = CosineLR::new(1e-3, 10, 0.0, 100, 1e-4).into();
+        let lr_before = llm.lr_schedule.get_lr(40);
+
+        assert_eq!(coordinator.override_total_steps(50), 50);
+        assert_eq!(coordinator.config.total_steps, 50);
+        let Model::LLM(llm) = &coordinator.model;
+        assert!(llm.lr_schedule.get_lr(40) < lr_before);
+        assert_eq!(llm.lr_schedule.get_lr(50), 1e-4);
+
+        // can't be extended past the config
+        assert_eq!(coordinator.override_total_steps(200), 50);
+
+        // the run now finishes at step 50 instead of 100
+        coordinator.progress.step = 49;
+        coordinator.start_waiting_for_members(100);
+        assert_eq!(coordinator.run_state, RunState::WaitingForMembers);
+        coordinator.progress.step = 50;
+        coordinator.start_waiting_for_members(200);
+        assert_eq!(coordinator.run_state, RunState::Finished);
+    }
+
+    #[test]
+    fn test_run_finishes_after_max_data_epochs() {
+        let clients = test_clients(4);
+        let mut config = test_config(4);
+        // 2 passes over 20 samples are 5 rounds of 8 samples
+        config.dataset_samples = 20;
+        config.max_data_epochs = 2;
+        let mut coordinator = new_coordinator(config);
+        let mut now = start_training(&mut coordinator, &clients);
+
+        for height in 0..5 {
+            assert_eq!(coordinator.run_state, RunState::RoundTrain);
+            assert_eq!(coordinator.current_round().unwrap().height, height);
+            assert_eq!(
+                coordinator.current_round().unwrap().data_index,
+                height as u64 * 8
+            );
+            for client in &clients {
+                send_witness(&mut coordinator, client, now + 1);
+            }
+            now += 1 + ROUND_WITNESS_TIME;
+            coordinator
+                .tick(None::<std::slice::Iter<'_, TestClientId>>, now, 5678)
+                .unwrap();
+        }
+
+        // the epoch ends early, right at the data epoch boundary, and the run with it
+        assert_eq!(coordinator.run_state, RunState::Cooldown);
+        assert_eq!(coordinator.progress.step, 5);
+        now += config.cooldown_time;
+        coordinator
+            .tick(None::<std::slice::Iter<'_, TestClientId>>, now, 5678)
+            .unwrap();
+        assert_eq!(coordinator.progress.epoch_start_data_index, 40);
+        assert_eq!(coordinator.run_state, RunState::Finished);
+
+        let mut config = test_config(4);
+        config.max_data_epochs = 2;
+        assert_eq!(violated_fields(config.validate()), ["dataset_samples"]);
+        config.dataset_samples = u64::MAX / 2 + 1;
+        assert_eq!(violated_fields(config.validate()), ["max_data_epochs"]);
+    }
+
+    #[test]
+    fn test_simulate_tick_matches_tick() {
+        let clients = test_clients(4);
+        let mut coordinator = new_coordinator(test_config(4));
+        let untouched = coordinator;
+
+        let simulated = coordinator
+            .simulate_tick(Some(clients.iter()), 100, 1234)
+            .unwrap();
+        assert_eq!(
+            bytemuck::bytes_of(&coordinator),
+            bytemuck::bytes_of(&untouched)
+        );
+        let result = coordinator.tick(Some(clients.iter()), 100, 1234).unwrap();
+        assert_eq!(simulated.result, result);
+        assert_eq!(
+            bytemuck::bytes_of(&coordinator),
+            bytemuck::bytes_of(simulated.coordinator.as_ref())
+        );
+        assert_eq!(
+            simulated.summary.run_state,
+            (RunState::WaitingForMembers, RunState::Warmup)
+        );
+        assert_eq!(
+            simulated.summary.joined.iter().collect::<HashSet<_>>(),
+            clients.iter().collect()
+        );
+        assert_eq!(simulated.summary.witnesses, None);
+
+        // starting the first round picks its witnesses from the seed
+        let now = 100 + WARMUP_TIME;
+        let simulated = coordinator
+            .simulate_tick(None::<std::slice::Iter<'_, TestClientId>>, now, 5678)
+            .unwrap();
+        coordinator
+            .tick(None::<std::slice::Iter<'_, TestClientId>>, now, 5678)
+            .unwrap();
+        assert_eq!(
+            bytemuck::bytes_of(&coordinator),
+            bytemuck::bytes_of(simulated.coordinator.as_ref())
+        );
+        assert_eq!(simulated.summary.run_state.1, RunState::RoundTrain);
+        let witnesses = simulated.summary.witnesses.unwrap();
+        assert!(!witnesses.is_empty());
+        let selection = CommitteeSelection::from_coordinator(&coordinator, 0).unwrap();
+        for (index, client) in coordinator.epoch_state.clients.iter().enumerate() {
+            assert_eq!(
+                witnesses.contains(&client.id),
+                selection.get_witness(index as u64).witness.is_true()
+            );
+        }
+
+        // a tick that would fail fails the same way
+        let mut paused = new_coordinator(test_config(4));
+        paused.pause(100).unwrap();
+        assert!(matches!(
+            paused.simulate_tick(None::<std::slice::Iter<'_, TestClientId>>, 200, 1234),
+            Err(CoordinatorError::Halted)
+        ));
+    }
+
+    #[test]
+    fn test_paused_run_auto_resumes() {
+        const AUTO_RESUME_AFTER: u64 = 600;
+        let mut config = test_config(4);
+        config.auto_resume_after = AUTO_RESUME_AFTER;
+        let mut coordinator = new_coordinator(config);
+        let paused_at = 100;
+        coordinator.pause(paused_at).unwrap();
+        assert_eq!(coordinator.run_state, RunState::Paused);
+
+        assert!(matches!(
+            coordinator.tick(
+                None::<std::slice::Iter<'_, TestClientId>>,
+                paused_at + AUTO_RESUME_AFTER - 1,
+                5678
+            ),
+            Err(CoordinatorError::Halted)
+        ));
+        assert!(matches!(
+            coordinator.tick(
+                None::<std::slice::Iter<'_, TestClientId>>,
+                paused_at + AUTO_RESUME_AFTER,
+                5678
+            ),
+            Ok(TickResult::Ticked)
+        ));
+        assert_eq!(coordinator.run_state, RunState::WaitingForMembers);
+
+        // without it, a paused run waits for someone to resume it
+        let mut coordinator = new_coordinator(test_config(4));
+        coordinator.pause(100).unwrap();
+        assert!(matches!(
+            coordinator.tick(None::<std::slice::Iter<'_, TestClientId>>, 100_000, 5678),
+            Err(CoordinatorError::Halted)
+        ));
+        assert_eq!(coordinator.run_state, RunState::Paused);
+    }
+
+    #[test]
+    fn test_client_order_is_canonical() {
+        let clients = test_clients(8);
+        let mut reversed = clients.clone();
+        reversed.reverse();
+        let mut interleaved: Vec<_> = clients.iter().step_by(2).copied().collect();
+        interleaved.extend(clients.iter().skip(1).step_by(2));
+
+        let mut config = test_config(8);
+        config.witness_nodes = 2;
+        config.verification_percent = 25;
+
+        let mut coordinators = [
+            new_coordinator(config),
+            new_coordinator(config),
+            new_coordinator(config),
+        ];
+        for (coordinator, clients) in
+            coordinators
+                .iter_mut()
+                .zip([&clients, &reversed, &interleaved])
+        {
+            start_training(coordinator, clients);
+        }
+
+        let [first, rest @ ..] = &coordinators;
+        let first_selection = CommitteeSelection::from_coordinator(first, 0).unwrap();
+        for other in rest {
+            assert_eq!(first.epoch_state.clients[..], other.epoch_state.clients[..]);
+            let other_selection = CommitteeSelection::from_coordinator(other, 0).unwrap();
+            for index in 0..first.epoch_state.clients.len() as u64 {
+                assert_eq!(
+                    first_selection.get_committee(index),
+                    other_selection.get_committee(index)
+                );
+                assert_eq!(
+                    first_selection.get_witness(index),
+                    other_selection.get_witness(index)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_computed_assignment_matches_coordinator() {
+        let clients = test_clients(8);
+        let mut config = test_config(8);
+        config.witness_nodes = 3;
+        config.verification_percent = 25;
+        config.committee_salt = FixedString::from_str_truncated("audit-committee");
+        let mut coordinator = new_coordinator(config);
+        start_training(&mut coordinator, &clients);
+
+        let round = coordinator.current_round().unwrap();
+        let assignment = CommitteeSelection::compute_assignment(
+            round.random_seed,
+            round.clients_len as usize,
+            round.tie_breaker_tasks as usize,
+            &coordinator.config,
+        )
+        .unwrap();
+        let selection = CommitteeSelection::from_coordinator(&coordinator, 0).unwrap();
+        assert_eq!(assignment.len(), coordinator.epoch_state.clients.len());
+        for (index, (computed, client)) in assignment
+            .iter()
+            .zip(coordinator.epoch_state.clients.iter())
+            .enumerate()
+        {
+            assert_eq!(computed.committee, selection.get_committee(index as u64));
+            assert_eq!(computed.witness, selection.get_witness(index as u64));
+            assert!(selection.verify_committee_for_client(
+                &client.id,
+                &computed.committee,
+                &coordinator.epoch_state.clients
+            ));
+            assert!(selection.verify_witness_for_client(
+                &client.id,
+                &computed.witness,
+                &coordinator.epoch_state.clients
+            ));
+        }
+    }
+
+    #[test]
+    fn test_consensus_commitment_ties_are_broken_by_seed() {
+        let commitments: Vec<_> = (0..4u8)
+            .map(|i| Commitment {
+                data_hash: [i; 32],
+                signature: [i; 64],
+            })
+            .collect();
+        // every witness saw every commitment once, so they all tie
+        let witnesses: Vec<_> = commitments
+            .iter()
+            .map(|commitment| {
+                let mut broadcast_bloom =
+                    WitnessBloom::new(WitnessBloom::max_bits(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+                broadcast_bloom.add(&commitment.data_hash);
+                Witness {
+                    broadcast_bloom,
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        let selected = |commitments: &[Commitment], seed| {
+            let index = Coordinator::<TestClientId>::select_consensus_commitment_by_witnesses(
+                commitments,
+                &witnesses,
+                1,
+                seed,
+            )
+            .unwrap();
+            commitments[index].data_hash
+        };
+
+        // nodes receive the commitments in different orders, but agree given the seed
+        let mut selected_by_seed = HashSet::new();
+        for seed in 0..16 {
+            let mut reversed = commitments.clone();
+            reversed.reverse();
+            let mut rotated = commitments.clone();
+            rotated.rotate_left(1);
+
+            let expected = selected(&commitments, seed);
+            assert_eq!(selected(&reversed, seed), expected);
+            assert_eq!(selected(&rotated, seed), expected);
+            selected_by_seed.insert(expected);
+        }
+        // and the seed, not a fixed ordering, decides the winner
+        assert!(selected_by_seed.len() > 1);
+
+        // a strictly higher witness count still wins over the tie-break
+        let mut witnesses = witnesses.clone();
+        witnesses[0].broadcast_bloom = {
+            let mut bloom = WitnessBloom::new(WitnessBloom::max_bits(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+            bloom.add(&commitments[3].data_hash);
+            bloom
+        };
+        for seed in 0..16 {
+            assert_eq!(
+                Coordinator::<TestClientId>::select_consensus_commitment_by_witnesses(
+                    &commitments,
+                    &witnesses,
+                    1,
+                    seed,
+                ),
+                Some(3)
+            );
+        }
+    }
+
+    #[test]
+    fn test_witness_participation() {
+        let clients = test_clients(4);
+        let mut coordinator = new_coordinator(test_config(4));
+        start_training(&mut coordinator, &clients);
+
+        // every client is selected to witness, but client 3 doesn't.
+        // of the rest, clients 0 and 2 saw everyone but client 3 train, and client 1 saw only client 0 and itself
+        let witness = |index: u64, seen: &[TestClientId]| {
+            let mut participant_bloom =
+                WitnessBloom::new(WitnessBloom::max_bits(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+            for id in seen {
+                participant_bloom.add(&sha256(id.as_ref()));
+            }
+            Witness {
+                proof: WitnessProof {
+                    index,
+                    ..Default::default()
+                },
+                participant_bloom,
+                ..Default::default()
+            }
+        };
+        let round = coordinator.current_round_mut_unchecked();
+        round
+            .witnesses
+            .push(witness(0, &[clients[0], clients[1], clients[2]]))
+            .unwrap();
+        round
+            .witnesses
+            .push(witness(1, &[clients[0], clients[1]]))
+            .unwrap();
+        round
+            .witnesses
+            .push(witness(2, &[clients[0], clients[1], clients[2]]))
+            .unwrap();
+
+        let participation =
+            coordinator.witness_participation(coordinator.current_round_unchecked());
+        assert_eq!(
+            participation,
+            WitnessParticipation {
+                height: coordinator.current_round_unchecked().height,
+                witnesses: vec![clients[0], clients[1], clients[2]],
+                absent_witnesses: vec![clients[3]],
+                participating: vec![(clients[0], 3), (clients[1], 3), (clients[2], 2)],
+                absent: vec![clients[3]],
+            }
+        );
+    }
+
+    #[test]
+    fn test_gradient_staleness_tolerance() {
+        use crate::model::GradientStaleness;
+
+        let staleness = GradientStaleness {
+            max_steps: 2,
+            discount: 0.5,
+        };
+        assert!(staleness.is_valid());
+        assert_eq!(staleness.weight(10, 10), Some(1.0));
+        // within tolerance, accepted but discounted per step of staleness
+        assert_eq!(staleness.weight(10, 9), Some(0.5));
+        assert_eq!(staleness.weight(10, 8), Some(0.25));
+        // too old, or from a step we haven't reached, rejected
+        assert_eq!(staleness.weight(10, 7), None);
+        assert_eq!(staleness.weight(10, 11), None);
+
+        // the default only accepts gradients from the round's own step
+        let default = GradientStaleness::default();
+        assert_eq!(default.weight(10, 10), Some(1.0));
+        assert_eq!(default.weight(10, 9), None);
+
+        for discount in [0.0, -0.5, 1.5, f32::NAN] {
+            assert!(!GradientStaleness {
+                max_steps: 2,
+                discount
+            }
+            .is_valid());
+        }
+    }
+}
@@ -1,7 +1,43 @@
 use crate::{Committee, CommitteeSelection, Coordinator, Round};
 
+use anchor_lang::{prelude::borsh, AnchorDeserialize, AnchorSerialize, InitSpace};
+use bytemuck::Zeroable;
 use psyche_core::{deterministic_shuffle, BatchId, ClosedInterval, NodeIdentity};
+use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, fmt};
+use ts_rs::TS;
+
+/// How a round's samples are split between its trainers.
+/// Every strategy is a deterministic function of the round, so all clients agree on the assignment.
+#[repr(u8)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Zeroable,
+    AnchorDeserialize,
+    AnchorSerialize,
+    Serialize,
+    Deserialize,
+    InitSpace,
+    TS,
+)]
+pub enum DataAssignmentStrategy {
+    /// Each trainer gets one contiguous range of samples.
+    #[default]
+    Contiguous = 0,
+    /// The round is cut into `DATA_ASSIGNMENT_CHUNKS_PER_TRAINER` contiguous chunks per trainer,
+    /// and the `i`th trainer gets every `num_trainers`th chunk, starting from the `i`th.
+    Strided = 1,
+    /// The round is cut into chunks as in `Strided`, but the chunks are shuffled with the round's random seed
+    /// before being dealt out.
+    Random = 2,
+}
+
+/// How many contiguous chunks of a round each trainer gets with the `Strided` and `Random` strategies.
+pub const DATA_ASSIGNMENT_CHUNKS_PER_TRAINER: u64 = 4;
 
 /// Assigns data batches to nodes based on committee roles.  
 pub fn assign_data_for_state<T: NodeIdentity>(
@@ -35,24 +71,12 @@ pub fn assign_data_for_state<T: NodeIdentity>(
     let mut trainer_nodes = trainer_nodes;
     deterministic_shuffle(&mut trainer_nodes, round.random_seed);
 
-    let total_size = coordinator.get_target_global_batch_size(coordinator.current_round()) as u64;
-    let num_trainers = trainer_nodes.len() as u64;
-    let base_size = total_size / num_trainers;
-    let remainder = total_size % num_trainers;
+    let batch_ids = get_batch_ids_per_trainer(round, coordinator, trainer_nodes.len() as u64);
 
     let mut assignments = BTreeMap::new();
-    let mut current_index = round.data_index;
-
-    for (i, node) in trainer_nodes.iter().enumerate() {
-        let node_batch_size = base_size + if (i as u64) < remainder { 1 } else { 0 };
-
-        if node_batch_size > 0 {
-            let end_index = current_index + node_batch_size - 1;
-            assignments.insert(
-                BatchId(ClosedInterval::new(current_index, end_index)),
-                node.id,
-            );
-            current_index = end_index + 1;
+    for (node, batch_ids) in trainer_nodes.iter().zip(batch_ids) {
+        for batch_id in batch_ids {
+            assignments.insert(batch_id, node.id);
         }
     }
 
@@ -64,33 +88,91 @@ pub fn get_batch_ids_for_round<T: NodeIdentity>(
     coordinator: &Coordinator<T>,
     num_trainer_nodes: u64,
 ) -> Vec<BatchId> {
+    let mut batch_ids: Vec<BatchId> =
+        get_batch_ids_per_trainer(round, coordinator, num_trainer_nodes)
+            .into_iter()
+            .flatten()
+            .collect();
+    batch_ids.sort();
+    batch_ids
+}
+
+/// Splits the round's samples into the batches of each trainer slot, according to the run's `DataAssignmentStrategy`.
+fn get_batch_ids_per_trainer<T: NodeIdentity>(
+    round: &Round,
+    coordinator: &Coordinator<T>,
+    num_trainer_nodes: u64,
+) -> Vec<Vec<BatchId>> {
     let start = round.data_index;
     let total_size = coordinator.get_target_global_batch_size(Some(round)) as u64;
     let end = start + total_size;
 
-    let base_size = total_size / num_trainer_nodes;
-    let remainder = total_size % num_trainer_nodes;
-
-    let mut batch_ids = Vec::with_capacity(num_trainer_nodes as usize);
-    let mut current = start;
-
-    for i in 0..num_trainer_nodes {
-        let node_size = base_size + if i < remainder { 1 } else { 0 };
-
-        if node_size > 0 {
-            let batch_end = current + node_size - 1;
-            batch_ids.push(BatchId(ClosedInterval::new(current, batch_end)));
-            current = batch_end + 1;
-
-            if current >= end {
-                break;
+    match coordinator.config.data_assignment_strategy {
+        DataAssignmentStrategy::Contiguous => split_evenly(start..end, num_trainer_nodes)
+            .into_iter()
+            .map(|batch_id| vec![batch_id])
+            .collect(),
+        strategy => {
+            let mut chunks = split_evenly(
+                start..end,
+                num_trainer_nodes * DATA_ASSIGNMENT_CHUNKS_PER_TRAINER,
+            );
+            if strategy == DataAssignmentStrategy::Random {
+                deterministic_shuffle(&mut chunks, round.random_seed);
+            }
+            let mut batch_ids = vec![Vec::new(); num_trainer_nodes as usize];
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                batch_ids[i % num_trainer_nodes as usize].push(chunk);
             }
+            for node_batch_ids in &mut batch_ids {
+                node_batch_ids.sort();
+            }
+            batch_ids
         }
     }
+}
 
+/// Cuts `samples` into `num_parts` contiguous batches whose sizes differ by at most one, earlier batches first.
+/// Batches that would be empty are left out.
+fn split_evenly(samples: std::ops::Range<u64>, num_parts: u64) -> Vec<BatchId> {
+    let total_size = samples.end - samples.start;
+    let base_size = total_size / num_parts;
+    let remainder = total_size % num_parts;
+    let mut current = samples.start;
+    (0..num_parts)
+        .filter_map(|i| {
+            let size = base_size + if i < remainder { 1 } else { 0 };
+            if size == 0 {
+                return None;
+            }
+            let batch_id = BatchId(ClosedInterval::new(current, current + size - 1));
+            current += size;
+            Some(batch_id)
+        })
+        .collect()
+}
+
+/// Turns ascending sample indices into batches, merging consecutive samples into a single batch.
+fn merge_into_batch_ids(samples: impl IntoIterator<Item = u64>) -> Vec<BatchId> {
+    let mut batch_ids: Vec<BatchId> = Vec::new();
+    for sample in samples {
+        match batch_ids.last_mut() {
+            Some(BatchId(interval)) if interval.end + 1 == sample => interval.end = sample,
+            _ => batch_ids.push(BatchId(ClosedInterval::new(sample, sample))),
+        }
+    }
     batch_ids
 }
 
+/// Maps a batch of run-wide data indices onto a dataset of `dataset_samples` samples that later data epochs
+/// read from the start again, splitting the batch where it wraps around the end of the dataset.
+pub fn wrap_batch_id(batch_id: BatchId, dataset_samples: u64) -> Vec<BatchId> {
+    if dataset_samples == 0 {
+        return vec![batch_id];
+    }
+    merge_into_batch_ids(batch_id.iter().map(|sample| sample % dataset_samples))
+}
+
 /// Retrieves all batch IDs assigned to a specific node from an interval tree, converting data indices to batches.
 pub fn get_batch_ids_for_node<V: fmt::Display + Eq + std::hash::Hash>(
     tree: &BTreeMap<BatchId, V>,
@@ -130,3 +212,121 @@ pub fn get_data_index_for_step<T: NodeIdentity>(
 
     current_data_index
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{model, test_utils::TestClientId, Client, RunState};
+
+    fn assignments(strategy: DataAssignmentStrategy) -> Vec<(u64, u64, u64)> {
+        let mut state = Coordinator::<TestClientId>::zeroed();
+        state.run_state = RunState::RoundTrain;
+        state.model = model::Model::LLM(model::LLM::dummy());
+        state.config.global_batch_size_start = 24;
+        state.config.global_batch_size_end = 24;
+        state.config.data_assignment_strategy = strategy;
+        for i in 0..3 {
+            state
+                .epoch_state
+                .clients
+                .push(Client::new(TestClientId(i)))
+                .unwrap();
+        }
+        let round = &mut state.epoch_state.rounds[0];
+        round.data_index = 100;
+        round.random_seed = 42;
+        round.clients_len = 3;
+
+        let committee_selection = CommitteeSelection::from_coordinator(&state, 0).unwrap();
+        let assignments = assign_data_for_state(&state, &committee_selection);
+        assert_eq!(
+            assignments.keys().copied().collect::<Vec<_>>(),
+            get_batch_ids_for_round(
+                state.current_round().unwrap(),
+                &state,
+                committee_selection.get_num_trainer_nodes()
+            ),
+        );
+        assignments
+            .into_iter()
+            .map(|(BatchId(interval), id)| (interval.start, interval.end, id.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_evenly() {
+        let batch = |start, end| BatchId(ClosedInterval::new(start, end));
+        assert_eq!(
+            split_evenly(0..10, 3),
+            [batch(0, 3), batch(4, 6), batch(7, 9)]
+        );
+        // more parts than samples leaves the extra parts out
+        assert_eq!(split_evenly(5..7, 4), [batch(5, 5), batch(6, 6)]);
+        assert_eq!(split_evenly(5..5, 4), []);
+    }
+
+    #[test]
+    fn test_wrap_batch_id() {
+        let batch = |start, end| BatchId(ClosedInterval::new(start, end));
+        assert_eq!(wrap_batch_id(batch(3, 7), 0), [batch(3, 7)]);
+        assert_eq!(wrap_batch_id(batch(3, 7), 20), [batch(3, 7)]);
+        assert_eq!(wrap_batch_id(batch(24, 31), 20), [batch(4, 11)]);
+        assert_eq!(
+            wrap_batch_id(batch(16, 23), 20),
+            [batch(16, 19), batch(0, 3)]
+        );
+    }
+
+    #[test]
+    fn test_data_assignment_strategies() {
+        // with seed 42, the trainers are shuffled into the order [1, 0, 2]
+        assert_eq!(
+            assignments(DataAssignmentStrategy::Contiguous),
+            vec![(100, 107, 1), (108, 115, 0), (116, 123, 2)]
+        );
+        // 24 samples in 3 * 4 chunks of 2, dealt out in order
+        assert_eq!(
+            assignments(DataAssignmentStrategy::Strided),
+            vec![
+                (100, 101, 1),
+                (102, 103, 0),
+                (104, 105, 2),
+                (106, 107, 1),
+                (108, 109, 0),
+                (110, 111, 2),
+                (112, 113, 1),
+                (114, 115, 0),
+                (116, 117, 2),
+                (118, 119, 1),
+                (120, 121, 0),
+                (122, 123, 2),
+            ]
+        );
+        // the same chunks, shuffled into [100, 104, 106, 116, 112, 114, 102, 118, 108, 122, 120, 110] before being dealt out
+        assert_eq!(
+            assignments(DataAssignmentStrategy::Random),
+            vec![
+                (100, 101, 1),
+                (102, 103, 1),
+                (104, 105, 0),
+                (106, 107, 2),
+                (108, 109, 2),
+                (110, 111, 2),
+                (112, 113, 0),
+                (114, 115, 2),
+                (116, 117, 1),
+                (118, 119, 0),
+                (120, 121, 0),
+                (122, 123, 1),
+            ]
+        );
+
+        for strategy in [
+            DataAssignmentStrategy::Contiguous,
+            DataAssignmentStrategy::Strided,
+            DataAssignmentStrategy::Random,
+        ] {
+            assert_eq!(assignments(strategy), assignments(strategy));
+        }
+    }
+}
@@ -5,17 +5,27 @@ mod committee_selection;
 mod coordinator;
 mod data_selection;
 pub mod model;
+#[cfg(feature = "toml")]
+mod run_config;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
 pub use commitment::Commitment;
 pub use committee_selection::{
-    Committee, CommitteeProof, CommitteeSelection, WitnessProof, COMMITTEE_SALT, WITNESS_SALT,
+    ClientAssignment, Committee, CommitteeProof, CommitteeSelection, WitnessProof, COMMITTEE_SALT,
+    WITNESS_SALT,
 };
 pub use coordinator::{
-    Client, ClientState, Coordinator, CoordinatorConfig, CoordinatorEpochState, CoordinatorError,
-    CoordinatorProgress, HealthChecks, Round, RunState, TickResult, Witness, WitnessBloom,
-    WitnessEvalResult, WitnessMetadata, BLOOM_FALSE_RATE, NUM_STORED_ROUNDS,
-    SOLANA_MAX_NUM_CLIENTS, SOLANA_MAX_NUM_WITNESSES, SOLANA_MAX_STRING_LEN,
+    epoch_settlement, Client, ClientState, ConfigViolation, Coordinator, CoordinatorConfig,
+    CoordinatorEpochState, CoordinatorError, CoordinatorProgress, HealthChecks,
+    PendingClientsFullPolicy, Round, RunState, SimulatedTick, TickResult, TickSummary, Witness,
+    WitnessBloom, WitnessEvalResult, WitnessMetadata, WitnessParticipation, BLOOM_FALSE_RATE,
+    MAX_STORED_ROUNDS, MIN_STORED_ROUNDS, NUM_STORED_ROUNDS, SOLANA_MAX_NUM_CLIENTS,
+    SOLANA_MAX_NUM_WITNESSES, SOLANA_MAX_STRING_LEN, WAITING_FOR_MEMBERS_EXTRA_SECONDS,
 };
 pub use data_selection::{
-    assign_data_for_state, get_batch_ids_for_node, get_batch_ids_for_round, get_data_index_for_step,
+    assign_data_for_state, get_batch_ids_for_node, get_batch_ids_for_round,
+    get_data_index_for_step, wrap_batch_id, DataAssignmentStrategy,
 };
+#[cfg(feature = "toml")]
+pub use run_config::{RunConfig, RunConfigError};
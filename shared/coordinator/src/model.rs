@@ -184,6 +184,57 @@ pub struct LLM {
     pub data_location: LLMTrainingDataLocation,
     pub lr_schedule: LearningRateSchedule,
     pub optimizer: OptimizerDefinition,
+    /// Hex-encoded sha256 of the model config every client must load. Clients log theirs while loading the model.
+    /// Clients whose config doesn't match refuse to join, since they would produce incompatible gradients.
+    /// If empty, the check is skipped.
+    #[serde(default)]
+    pub config_hash: FixedString<64>,
+    #[serde(default)]
+    pub gradient_staleness: GradientStaleness,
+}
+
+/// How many steps behind the round it's applied in a peer's gradient may be, and how much it's discounted for it.
+/// Every client applies the same results, so this lives in the run's config rather than each client's.
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Zeroable,
+    Copy,
+    PartialEq,
+    TS,
+)]
+#[repr(C)]
+pub struct GradientStaleness {
+    /// gradients more than this many steps old are rejected. 0 only accepts gradients for the round's own step.
+    pub max_steps: u32,
+    /// a gradient `n` steps old is weighted by `discount^n`.
+    pub discount: f32,
+}
+
+impl Default for GradientStaleness {
+    fn default() -> Self {
+        Self {
+            max_steps: 0,
+            discount: 0.5,
+        }
+    }
+}
+
+impl GradientStaleness {
+    /// How much to weight a gradient computed at `result_step` when applying the round for `round_step`,
+    /// or `None` if it's too old (or claims to be from a later step) to be applied at all.
+    pub fn weight(&self, round_step: u32, result_step: u32) -> Option<f32> {
+        let staleness = round_step.checked_sub(result_step)?;
+        (staleness <= self.max_steps).then(|| self.discount.powi(staleness as i32))
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.discount > 0.0 && self.discount <= 1.0
+    }
 }
 
 impl LLM {
@@ -197,6 +248,8 @@ impl LLM {
             max_seq_len: 2048,
             optimizer: OptimizerDefinition::Dummy,
             cold_start_warmup_steps: 0,
+            config_hash: FixedString::new(),
+            gradient_staleness: GradientStaleness::default(),
         }
     }
 }
@@ -301,10 +354,18 @@ impl Model {
                     msg!("model check failed: bad checkpoint");
                     return false;
                 }
+                if !llm.lr_schedule.is_valid() {
+                    msg!("model check failed: bad lr schedule");
+                    return false;
+                }
+                if !llm.gradient_staleness.is_valid() {
+                    msg!("model check failed: gradient staleness discount must be in (0, 1]");
+                    return false;
+                }
                 if !match llm.optimizer {
                     OptimizerDefinition::Dummy => false,
                     OptimizerDefinition::AdamW { .. } => true,
-                    OptimizerDefinition::Distro { .. } => true,
+                    OptimizerDefinition::Distro(config) => config.is_valid(),
                 } {
                     msg!("model check failed: bad optimizer");
                     return false;
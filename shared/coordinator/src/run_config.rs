@@ -0,0 +1,265 @@
+use crate::{model::Model, ConfigViolation, CoordinatorConfig};
+
+use serde::{Deserialize, Serialize};
+
+/// What an operator writes in a run's config file: the `[config]` and `[model]` tables,
+/// as read by `update-config` on Solana and `validate-config` on the centralized server.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RunConfig {
+    pub config: CoordinatorConfig,
+    pub model: Model,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RunConfigError {
+    #[error("coordinator config failed its sanity check: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    InvalidConfig(Vec<ConfigViolation>),
+
+    #[error("model failed its sanity check")]
+    InvalidModel,
+
+    #[error("failed to serialize run config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+
+    #[error("failed to parse run config: {0}")]
+    Parse(#[from] toml::de::Error),
+}
+
+/// Comments written above each `[config]` key, so exported files explain themselves.
+const CONFIG_COMMENTS: &[(&str, &str)] = &[
+    (
+        "warmup_time",
+        "time, in seconds, to let nodes bring the model from disk to GPU",
+    ),
+    (
+        "cooldown_time",
+        "time, in seconds, to let nodes checkpoint the model at the end of an epoch",
+    ),
+    (
+        "max_round_train_time",
+        "maximum time, in seconds, to allow nodes to train in one round",
+    ),
+    (
+        "round_witness_time",
+        "time, in seconds, to allow witnesses to publish their messages before next round",
+    ),
+    (
+        "witness_timeout",
+        "extra time, in seconds, to wait for late witnesses if quorum wasn't reached. 0 ends the epoch instead",
+    ),
+    (
+        "global_batch_size_warmup_tokens",
+        "tokens over which the batch size grows from global_batch_size_start to global_batch_size_end",
+    ),
+    (
+        "rounds_per_epoch",
+        "how many training rounds in one epoch, from warmup to cooldown",
+    ),
+    (
+        "total_steps",
+        "the total number of training steps to partake in",
+    ),
+    (
+        "init_min_clients",
+        "minimum number of clients required before we transition from WaitingForMembers to Warmup",
+    ),
+    (
+        "min_clients",
+        "number of clients that need to be active for an epoch to continue on",
+    ),
+    (
+        "witness_nodes",
+        "how many nodes are selected each round to publish witness proofs",
+    ),
+    (
+        "warmup_grace_period",
+        "time, in seconds, to keep waiting for more clients once init_min_clients have joined",
+    ),
+    (
+        "witness_quorum",
+        "absolute number of witnesses required to advance past a round. 0 uses witness_quorum_percent",
+    ),
+    (
+        "global_batch_size_start",
+        "the number of training data batches per-step at the start of training",
+    ),
+    (
+        "global_batch_size_end",
+        "the number of training data batches per-step once warmed up",
+    ),
+    (
+        "verification_percent",
+        "what percent of nodes are dedicated to verifying correctness",
+    ),
+    (
+        "witness_quorum_percent",
+        "percentage of the witness committee required to advance past a round. 0 defaults to two thirds",
+    ),
+    (
+        "committee_salt",
+        "overrides the salt used to shuffle clients into committees. empty uses the default",
+    ),
+    (
+        "witness_salt",
+        "overrides the salt used to select witnesses. empty uses the default",
+    ),
+    (
+        "num_stored_rounds",
+        "how many rounds of history the coordinator keeps, between 3 and 8. 0 keeps 4",
+    ),
+    (
+        "data_assignment_strategy",
+        "how each round's samples are split between trainers: Contiguous, Strided or Random",
+    ),
+    (
+        "max_pending_clients",
+        "how many clients can be waiting to join the next epoch. 0 allows as many as a run can have",
+    ),
+    (
+        "pending_clients_full_policy",
+        "what happens to a client joining past max_pending_clients: Reject or EvictOldest",
+    ),
+    (
+        "dataset_samples",
+        "how many samples the dataset has. only used with max_data_epochs",
+    ),
+    (
+        "max_data_epochs",
+        "finish the run after this many passes over the dataset. 0 only stops at total_steps",
+    ),
+    (
+        "auto_resume_after",
+        "seconds after which a paused run resumes on its own. 0 stays paused until resumed",
+    ),
+];
+
+impl RunConfig {
+    /// Sanity checks the config and model, then writes them as commented TOML
+    /// that [`RunConfig::from_toml`] reads back unchanged.
+    pub fn to_toml(&self) -> Result<String, RunConfigError> {
+        self.check()?;
+        let toml = toml::to_string_pretty(self)?;
+
+        let mut commented = String::with_capacity(toml.len() * 2);
+        let mut in_config = false;
+        for line in toml.lines() {
+            if line.starts_with('[') {
+                in_config = line == "[config]";
+            } else if in_config {
+                let key = line.split('=').next().unwrap_or_default().trim();
+                if let Some((_, comment)) = CONFIG_COMMENTS.iter().find(|(k, _)| *k == key) {
+                    commented.push_str("# ");
+                    commented.push_str(comment);
+                    commented.push('\n');
+                }
+            }
+            commented.push_str(line);
+            commented.push('\n');
+        }
+        Ok(commented)
+    }
+
+    /// Parses a run config file, like `update-config` does.
+    pub fn from_toml(toml: &str) -> Result<Self, RunConfigError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    pub fn check(&self) -> Result<(), RunConfigError> {
+        self.config
+            .validate_with_model(&self.model)
+            .map_err(RunConfigError::InvalidConfig)?;
+        if !self.model.check() {
+            return Err(RunConfigError::InvalidModel);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        model::{Checkpoint, HubRepo, LLM},
+        DataAssignmentStrategy, PendingClientsFullPolicy,
+    };
+    use psyche_core::{CosineLR, DistroConfig, FixedString, OptimizerDefinition};
+
+    fn run_config() -> RunConfig {
+        let mut llm = LLM::dummy();
+        llm.checkpoint = Checkpoint::Hub(HubRepo {
+            repo_id: FixedString::from_str_truncated("emozilla/llama2-20m-init"),
+            revision: None,
+        });
+        llm.lr_schedule = CosineLR::new(4e-4, 250, 0.0, 25000, 4e-5).into();
+        llm.optimizer = OptimizerDefinition::Distro(DistroConfig {
+            clip_grad_norm: Some(1.0),
+            compression_decay: 0.999,
+            compression_topk: 8,
+            compression_chunk: 64,
+            quantize_1bit: true,
+            momentum: 0.0,
+            dampening: 0.0,
+            nesterov: false,
+            weight_decay: None,
+        });
+        RunConfig {
+            config: CoordinatorConfig {
+                warmup_time: 30,
+                cooldown_time: 30,
+                max_round_train_time: 30,
+                round_witness_time: 1,
+                witness_timeout: 5,
+                global_batch_size_warmup_tokens: 0,
+                rounds_per_epoch: 20,
+                total_steps: 25000,
+                init_min_clients: 2,
+                min_clients: 1,
+                witness_nodes: 1,
+                warmup_grace_period: 10,
+                witness_quorum: 0,
+                global_batch_size_start: 8,
+                global_batch_size_end: 16,
+                verification_percent: 0,
+                witness_quorum_percent: 0,
+                committee_salt: FixedString::from_str_truncated("salty"),
+                witness_salt: FixedString::new(),
+                num_stored_rounds: 0,
+                data_assignment_strategy: DataAssignmentStrategy::Strided,
+                max_pending_clients: 0,
+                pending_clients_full_policy: PendingClientsFullPolicy::Reject,
+                dataset_samples: 0,
+                max_data_epochs: 0,
+                auto_resume_after: 0,
+            },
+            model: Model::LLM(llm),
+        }
+    }
+
+    #[test]
+    fn test_export_then_reload_is_identical() {
+        let run_config = run_config();
+        let toml = run_config.to_toml().unwrap();
+        assert!(toml.contains("# how many training rounds in one epoch, from warmup to cooldown\n"));
+
+        let reloaded = RunConfig::from_toml(&toml).unwrap();
+        assert_eq!(
+            toml::to_string(&reloaded).unwrap(),
+            toml::to_string(&run_config).unwrap()
+        );
+        assert_eq!(reloaded.to_toml().unwrap(), toml);
+        assert_eq!(
+            reloaded.config.committee_salt,
+            FixedString::<32>::from_str_truncated("salty")
+        );
+    }
+
+    #[test]
+    fn test_export_rejects_invalid_config() {
+        let mut run_config = run_config();
+        run_config.config.min_clients = 0;
+        assert!(matches!(
+            run_config.to_toml(),
+            Err(RunConfigError::InvalidConfig(violations)) if violations[0].field == "min_clients"
+        ));
+    }
+}
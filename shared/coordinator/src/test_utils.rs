@@ -0,0 +1,47 @@
+use psyche_core::NodeIdentity;
+
+use anchor_lang::{prelude::borsh, AnchorDeserialize, AnchorSerialize};
+use bytemuck::Zeroable;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use ts_rs::TS;
+
+/// A bare numeric client identity for tests that build a `Coordinator` by hand.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Zeroable,
+    Serialize,
+    Deserialize,
+    AnchorSerialize,
+    AnchorDeserialize,
+    TS,
+)]
+pub struct TestClientId(pub u64);
+
+impl fmt::Display for TestClientId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl AsRef<[u8]> for TestClientId {
+    fn as_ref(&self) -> &[u8] {
+        bytemuck::bytes_of(&self.0)
+    }
+}
+
+impl anchor_lang::Space for TestClientId {
+    const INIT_SPACE: usize = 8;
+}
+
+impl NodeIdentity for TestClientId {
+    fn get_p2p_public_key(&self) -> &[u8; 32] {
+        unimplemented!("test clients have no p2p identity")
+    }
+}
@@ -1,4 +1,4 @@
-use anchor_lang::prelude::{borsh::BorshSerialize, *};
+use anchor_lang::prelude::{borsh::BorshSerialize, thiserror, *};
 use anchor_lang_idl::{
     build::IdlBuild,
     types::{
@@ -21,6 +21,10 @@ pub trait BloomHashIndex {
     fn hash_at_index(&self, hash_index: u64) -> u64;
 }
 
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+#[error("can't combine blooms with different hash keys")]
+pub struct BloomKeysMismatch;
+
 #[derive(Clone, PartialEq, Eq, Copy, Zeroable, TS)]
 #[repr(C)]
 pub struct Bloom<const U: usize, const K: usize> {
@@ -310,6 +314,40 @@ impl<const U: usize, const K: usize> Bloom<U, K> {
         }
         true
     }
+
+    /// A bloom containing everything either bloom contains, with the false positive rate
+    /// of a single bloom holding the items of both. Both must use the same hash keys;
+    /// their sizes match by construction.
+    pub fn union(&self, other: &Self) -> Result<Self, BloomKeysMismatch> {
+        self.combine(other, |a, b| a | b)
+    }
+
+    /// A bloom containing everything both blooms contain. Both must use the same hash keys.
+    /// Its false positive rate is at most that of either bloom.
+    pub fn intersect(&self, other: &Self) -> Result<Self, BloomKeysMismatch> {
+        self.combine(other, |a, b| a & b)
+    }
+
+    fn combine(
+        &self,
+        other: &Self,
+        op: impl Fn(u64, u64) -> u64,
+    ) -> Result<Self, BloomKeysMismatch> {
+        if self.keys != other.keys {
+            return Err(BloomKeysMismatch);
+        }
+        let mut combined = *self;
+        for (word, other) in combined
+            .bits
+            .0
+            .as_raw_mut_slice()
+            .iter_mut()
+            .zip(other.bits.0.as_raw_slice())
+        {
+            *word = op(*word, *other);
+        }
+        Ok(combined)
+    }
 }
 
 fn slice_hash(slice: &[u8], hash_index: u64) -> u64 {
@@ -377,4 +415,50 @@ mod tests {
         let non_existing = vec![1, 4, 7];
         assert!(!bloom.contains(&non_existing));
     }
+
+    #[test]
+    fn test_union_contains_both() {
+        let mut a = Bloom::<16, 3>::new(1000, &[1, 2, 3]);
+        let mut b = Bloom::<16, 3>::new(1000, &[1, 2, 3]);
+        let items_a = [vec![1, 2, 3], vec![4, 5, 6]];
+        let items_b = [vec![7, 8, 9], vec![10, 11, 12]];
+        items_a.iter().for_each(|item| a.add(item));
+        items_b.iter().for_each(|item| b.add(item));
+
+        let union = a.union(&b).unwrap();
+        for item in items_a.iter().chain(&items_b) {
+            assert!(union.contains(item));
+        }
+        // the same as adding everything to one bloom
+        let mut both = Bloom::<16, 3>::new(1000, &[1, 2, 3]);
+        items_a
+            .iter()
+            .chain(&items_b)
+            .for_each(|item| both.add(item));
+        assert_eq!(union, both);
+    }
+
+    #[test]
+    fn test_intersect() {
+        let shared = vec![1, 2, 3];
+        let mut a = Bloom::<16, 3>::new(1000, &[1, 2, 3]);
+        let mut b = Bloom::<16, 3>::new(1000, &[1, 2, 3]);
+        a.add(&shared);
+        a.add(&vec![4, 5, 6]);
+        b.add(&shared);
+        b.add(&vec![7, 8, 9]);
+
+        let intersection = a.intersect(&b).unwrap();
+        assert!(intersection.contains(&shared));
+        assert!(!intersection.contains(&vec![4, 5, 6]));
+        assert!(!intersection.contains(&vec![7, 8, 9]));
+    }
+
+    #[test]
+    fn test_combining_different_keys_fails() {
+        let a = Bloom::<8, 2>::new(100, &[1, 2]);
+        let b = Bloom::<8, 2>::new(100, &[1, 3]);
+        assert_eq!(a.union(&b), Err(BloomKeysMismatch));
+        assert_eq!(a.intersect(&b), Err(BloomKeysMismatch));
+    }
 }
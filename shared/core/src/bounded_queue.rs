@@ -1,10 +1,18 @@
 use std::collections::VecDeque;
 
-#[derive(Default)]
 pub struct BoundedQueue<T, const U: usize> {
     queue: VecDeque<T>,
 }
 
+// not derived, so items don't need to be `Default`
+impl<T, const U: usize> Default for BoundedQueue<T, U> {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
 impl<T, const U: usize> BoundedQueue<T, U> {
     pub fn push(&mut self, item: T) {
         self.queue.push_back(item);
@@ -24,6 +32,20 @@ impl<T, const U: usize> BoundedQueue<T, U> {
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
+
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= U
+    }
+
+    /// Removes and returns the oldest item matching `f`.
+    pub fn take_first(&mut self, mut f: impl FnMut(&T) -> bool) -> Option<T> {
+        let index = self.queue.iter().position(&mut f)?;
+        self.queue.remove(index)
+    }
+
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.queue.retain(f);
+    }
 }
 
 impl<T, const U: usize> IntoIterator for BoundedQueue<T, U> {
@@ -91,6 +113,23 @@ mod tests {
         assert_eq!(queue.iter().cloned().collect::<Vec<_>>(), vec![2, 3, 4]);
     }
 
+    #[test]
+    fn test_take_first_and_retain() {
+        let mut queue = BoundedQueue::<i32, 4>::default();
+        for i in 1..=4 {
+            queue.push(i);
+        }
+        assert!(queue.is_full());
+
+        assert_eq!(queue.take_first(|x| x % 2 == 0), Some(2));
+        assert_eq!(queue.take_first(|x| *x == 7), None);
+        assert_eq!(queue.iter().cloned().collect::<Vec<_>>(), vec![1, 3, 4]);
+        assert!(!queue.is_full());
+
+        queue.retain(|x| *x != 3);
+        assert_eq!(queue.iter().cloned().collect::<Vec<_>>(), vec![1, 4]);
+    }
+
     #[test]
     fn test_queue_with_max_len_zero() {
         let mut queue = BoundedQueue::<i32, 0>::default();
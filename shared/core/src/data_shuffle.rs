@@ -19,6 +19,9 @@ use ts_rs::TS;
 pub enum Shuffle {
     DontShuffle,
     Seeded([u8; 32]),
+    /// Only for weighted providers: interleaves the providers' samples as evenly as their weights allow,
+    /// without any randomness. Other providers keep their samples in order.
+    Interleaved,
 }
 
 impl Default for Shuffle {
@@ -1,3 +1,4 @@
+use crate::FixedVec;
 use anchor_lang::{prelude::borsh, AnchorDeserialize, AnchorSerialize, InitSpace};
 use bytemuck::Zeroable;
 use serde::{Deserialize, Serialize};
@@ -272,6 +273,155 @@ impl LearningRateScheduler for WarmupStableDecayLR {
     }
 }
 
+/// A schedule that can be one segment of a [`PiecewiseLR`].
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    InitSpace,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Zeroable,
+    Copy,
+    TS,
+)]
+#[repr(C)]
+pub enum SegmentSchedule {
+    Constant(ConstantLR),
+    Linear(LinearLR),
+    Cosine(CosineLR),
+    WarmupStableDecay(WarmupStableDecayLR),
+}
+
+impl Default for SegmentSchedule {
+    fn default() -> Self {
+        Self::Constant(ConstantLR::default())
+    }
+}
+
+impl LearningRateScheduler for SegmentSchedule {
+    fn get_lr(&self, step: u32) -> f64 {
+        match self {
+            Self::Constant(l) => l.get_lr(step),
+            Self::Linear(l) => l.get_lr(step),
+            Self::Cosine(l) => l.get_lr(step),
+            Self::WarmupStableDecay(l) => l.get_lr(step),
+        }
+    }
+}
+
+impl SegmentSchedule {
+    fn get_warmup_steps(&self) -> u32 {
+        match self {
+            Self::Constant(l) => l.get_warmup_steps(),
+            Self::Linear(l) => l.get_warmup_steps(),
+            Self::Cosine(l) => l.get_warmup_steps(),
+            Self::WarmupStableDecay(l) => l.get_warmup_steps(),
+        }
+    }
+
+    fn get_warmup_init_lr(&self) -> f64 {
+        match self {
+            Self::Constant(l) => l.get_warmup_init_lr(),
+            Self::Linear(l) => l.get_warmup_init_lr(),
+            Self::Cosine(l) => l.get_warmup_init_lr(),
+            Self::WarmupStableDecay(l) => l.get_warmup_init_lr(),
+        }
+    }
+}
+
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    InitSpace,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Default,
+    Zeroable,
+    Copy,
+    TS,
+)]
+#[repr(C)]
+pub struct LRSegment {
+    /// the global step this segment takes over at. its schedule sees steps counted from here.
+    pub start_step: u32,
+    pub schedule: SegmentSchedule,
+}
+
+pub const MAX_LR_SEGMENTS: usize = 4;
+
+/// Chains schedules one after another, e.g. a linear warmup, then a cosine decay, then a constant tail.
+/// Each step goes to the last segment starting at or before it, offset into that segment's own steps.
+/// To keep the LR continuous at a transition, start each segment where the previous one ends,
+/// e.g. by setting its `warmup_init_lr` or `base_lr` to the previous segment's final LR.
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    InitSpace,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Zeroable,
+    Copy,
+    TS,
+)]
+#[repr(C)]
+pub struct PiecewiseLR {
+    segments: FixedVec<LRSegment, MAX_LR_SEGMENTS>,
+}
+
+impl PiecewiseLR {
+    pub fn new(segments: &[LRSegment]) -> Result<Self, &'static str> {
+        let piecewise = Self {
+            segments: segments.try_into()?,
+        };
+        if !piecewise.is_valid() {
+            return Err("segments must start at step 0 and have increasing start steps");
+        }
+        Ok(piecewise)
+    }
+
+    pub fn segments(&self) -> &[LRSegment] {
+        &self.segments
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.segments.first().is_some_and(|s| s.start_step == 0)
+            && self
+                .segments
+                .windows(2)
+                .all(|pair| pair[0].start_step < pair[1].start_step)
+    }
+
+    fn segment_at(&self, step: u32) -> Option<&LRSegment> {
+        self.segments.iter().rev().find(|s| s.start_step <= step)
+    }
+
+    pub fn get_warmup_steps(&self) -> u32 {
+        self.segment_at(0)
+            .map(|s| s.schedule.get_warmup_steps())
+            .unwrap_or_default()
+    }
+
+    pub fn get_warmup_init_lr(&self) -> f64 {
+        self.segment_at(0)
+            .map(|s| s.schedule.get_warmup_init_lr())
+            .unwrap_or_default()
+    }
+}
+
+impl LearningRateScheduler for PiecewiseLR {
+    fn get_lr(&self, step: u32) -> f64 {
+        self.segment_at(step)
+            .map(|s| s.schedule.get_lr(step - s.start_step))
+            .unwrap_or_default()
+    }
+}
+
 #[derive(
     AnchorSerialize,
     AnchorDeserialize,
@@ -290,6 +440,7 @@ pub enum LearningRateSchedule {
     Linear(LinearLR),
     Cosine(CosineLR),
     WarmupStableDecay(WarmupStableDecayLR),
+    Piecewise(PiecewiseLR),
 }
 
 impl LearningRateSchedule {
@@ -299,6 +450,7 @@ impl LearningRateSchedule {
             Self::Linear(l) => l.get_lr(step),
             Self::Cosine(l) => l.get_lr(step),
             Self::WarmupStableDecay(l) => l.get_lr(step),
+            Self::Piecewise(l) => l.get_lr(step),
         }
     }
 
@@ -308,6 +460,7 @@ impl LearningRateSchedule {
             Self::Linear(l) => l.get_warmup_steps(),
             Self::Cosine(l) => l.get_warmup_steps(),
             Self::WarmupStableDecay(l) => l.get_warmup_steps(),
+            Self::Piecewise(l) => l.get_warmup_steps(),
         }
     }
 
@@ -317,6 +470,96 @@ impl LearningRateSchedule {
             Self::Linear(l) => l.get_warmup_init_lr(),
             Self::Cosine(l) => l.get_warmup_init_lr(),
             Self::WarmupStableDecay(l) => l.get_warmup_init_lr(),
+            Self::Piecewise(l) => l.get_warmup_init_lr(),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::Piecewise(l) => l.is_valid(),
+            _ => true,
+        }
+    }
+
+    /// The same schedule, stretched or squeezed to end at `total_steps`.
+    /// For warmup-stable-decay, the stable phase absorbs the difference so the decay keeps its length.
+    /// For piecewise schedules, only the last segment is stretched or squeezed.
+    pub fn with_total_steps(&self, total_steps: u32) -> Self {
+        match *self {
+            Self::Piecewise(mut l) => {
+                if let Some(last) = l.segments.last_mut() {
+                    last.schedule = last
+                        .schedule
+                        .with_total_steps(total_steps.saturating_sub(last.start_step));
+                }
+                Self::Piecewise(l)
+            }
+            _ => SegmentSchedule::try_from(*self)
+                .expect("only piecewise schedules aren't segment schedules")
+                .with_total_steps(total_steps)
+                .into(),
+        }
+    }
+}
+
+impl SegmentSchedule {
+    fn with_total_steps(&self, total_steps: u32) -> Self {
+        match *self {
+            Self::Constant(l) => Self::Constant(l),
+            Self::Linear(l) => Self::Linear(LinearLR { total_steps, ..l }),
+            Self::Cosine(l) => Self::Cosine(CosineLR { total_steps, ..l }),
+            Self::WarmupStableDecay(l) => Self::WarmupStableDecay(l.with_total_steps(total_steps)),
+        }
+    }
+}
+
+impl WarmupStableDecayLR {
+    /// The stable phase absorbs the difference. If even dropping it doesn't fit the schedule in `total_steps`,
+    /// the decay phases are squeezed into what's left after warmup, keeping their proportions, then the warmup too.
+    fn with_total_steps(&self, total_steps: u32) -> Self {
+        let warmup_steps = self.warmup_steps.min(total_steps);
+        let decay_steps = self.cosine_decay_steps as u64 + self.linear_decay_steps as u64;
+        let after_warmup = (total_steps - warmup_steps) as u64;
+        let (cosine_decay_steps, linear_decay_steps) = if decay_steps <= after_warmup {
+            (self.cosine_decay_steps, self.linear_decay_steps)
+        } else {
+            let cosine_decay_steps = self.cosine_decay_steps as u64 * after_warmup / decay_steps;
+            (
+                cosine_decay_steps as u32,
+                (after_warmup - cosine_decay_steps) as u32,
+            )
+        };
+        Self {
+            warmup_steps,
+            stable_steps: total_steps - warmup_steps - cosine_decay_steps - linear_decay_steps,
+            cosine_decay_steps,
+            linear_decay_steps,
+            ..*self
+        }
+    }
+}
+
+impl From<SegmentSchedule> for LearningRateSchedule {
+    fn from(value: SegmentSchedule) -> Self {
+        match value {
+            SegmentSchedule::Constant(l) => Self::Constant(l),
+            SegmentSchedule::Linear(l) => Self::Linear(l),
+            SegmentSchedule::Cosine(l) => Self::Cosine(l),
+            SegmentSchedule::WarmupStableDecay(l) => Self::WarmupStableDecay(l),
+        }
+    }
+}
+
+impl TryFrom<LearningRateSchedule> for SegmentSchedule {
+    type Error = &'static str;
+
+    fn try_from(value: LearningRateSchedule) -> Result<Self, Self::Error> {
+        match value {
+            LearningRateSchedule::Constant(l) => Ok(Self::Constant(l)),
+            LearningRateSchedule::Linear(l) => Ok(Self::Linear(l)),
+            LearningRateSchedule::Cosine(l) => Ok(Self::Cosine(l)),
+            LearningRateSchedule::WarmupStableDecay(l) => Ok(Self::WarmupStableDecay(l)),
+            LearningRateSchedule::Piecewise(_) => Err("piecewise schedules can't be nested"),
         }
     }
 }
@@ -339,6 +582,12 @@ impl From<ConstantLR> for LearningRateSchedule {
     }
 }
 
+impl From<PiecewiseLR> for LearningRateSchedule {
+    fn from(value: PiecewiseLR) -> Self {
+        Self::Piecewise(value)
+    }
+}
+
 #[derive(
     AnchorSerialize,
     AnchorDeserialize,
@@ -359,15 +608,62 @@ pub enum OptimizerDefinition {
         weight_decay: f32,
         eps: f32,
         clip_grad_norm: Option<f32>,
+        /// don't apply weight decay to 1-D parameters (biases and norm weights)
+        #[serde(default)]
+        exclude_1d_from_weight_decay: bool,
     },
-    Distro {
-        clip_grad_norm: Option<f32>,
-        weight_decay: Option<f32>,
-        compression_decay: f32,
-        compression_topk: u16,
-        compression_chunk: u16,
-        quantize_1bit: bool,
-    },
+    Distro(DistroConfig),
+}
+
+/// DisTrO's hyperparameters. Every client has to use the same ones for their results to be compatible.
+#[derive(
+    AnchorSerialize,
+    AnchorDeserialize,
+    InitSpace,
+    Serialize,
+    Deserialize,
+    Clone,
+    Debug,
+    Zeroable,
+    Copy,
+    PartialEq,
+    TS,
+)]
+#[repr(C)]
+pub struct DistroConfig {
+    pub clip_grad_norm: Option<f32>,
+    pub weight_decay: Option<f32>,
+    /// how much of the untransmitted gradient (the error feedback) is carried over to the next step
+    pub compression_decay: f32,
+    /// how many DCT coefficients of each chunk are transmitted
+    pub compression_topk: u16,
+    /// size of the chunks tensors are split into before the DCT
+    pub compression_chunk: u16,
+    /// transmit only the sign of each coefficient
+    pub quantize_1bit: bool,
+    /// SGD momentum applied to the aggregated update, 0 for plain SGD
+    #[serde(default)]
+    pub momentum: f32,
+    /// how much of each new update is left out of the momentum
+    #[serde(default)]
+    pub dampening: f32,
+    /// use Nesterov momentum, which needs a non-zero `momentum` and no `dampening`
+    #[serde(default)]
+    pub nesterov: bool,
+}
+
+impl DistroConfig {
+    pub fn is_valid(&self) -> bool {
+        (0.0..1.0).contains(&self.momentum)
+            && (0.0..=1.0).contains(&self.dampening)
+            && (!self.nesterov || (self.momentum > 0.0 && self.dampening == 0.0))
+    }
+}
+
+impl From<DistroConfig> for OptimizerDefinition {
+    fn from(value: DistroConfig) -> Self {
+        Self::Distro(value)
+    }
 }
 
 #[cfg(test)]
@@ -459,6 +755,147 @@ mod tests {
         assert_relative_eq!(scheduler.get_lr(250), 0.0);
     }
 
+    #[test]
+    fn test_with_total_steps() {
+        let cosine: LearningRateSchedule = CosineLR::new(0.01, 10, 0.0, 100, 0.001).into();
+        let shortened = cosine.with_total_steps(50);
+        assert_relative_eq!(shortened.get_lr(5), cosine.get_lr(5));
+        assert!(shortened.get_lr(30) < cosine.get_lr(30));
+        assert_relative_eq!(shortened.get_lr(50), 0.001);
+
+        let wsd = LearningRateSchedule::WarmupStableDecay(WarmupStableDecayLR::new(
+            0.01, 10, 0.0, 80, 10, 0.001, 0, 0.001,
+        ));
+        let shortened = wsd.with_total_steps(50);
+        assert_relative_eq!(shortened.get_lr(39), 0.01);
+        assert!(shortened.get_lr(45) < 0.01);
+        assert_relative_eq!(shortened.get_lr(50), 0.001);
+    }
+
+    #[test]
+    fn test_wsd_with_total_steps_shorter_than_its_decay() {
+        // 10 warmup, 20 stable, 60 cosine decay, 30 linear decay
+        let wsd = WarmupStableDecayLR::new(0.01, 10, 0.0, 20, 60, 0.002, 30, 0.001);
+
+        // no room for the stable phase, and the decays are squeezed 2:1 into the 45 steps after warmup
+        let squeezed = wsd.with_total_steps(55);
+        assert_eq!(squeezed.warmup_steps, 10);
+        assert_eq!(squeezed.stable_steps, 0);
+        assert_eq!(squeezed.cosine_decay_steps, 30);
+        assert_eq!(squeezed.linear_decay_steps, 15);
+        assert_relative_eq!(squeezed.get_lr(10), 0.01);
+        assert_relative_eq!(squeezed.get_lr(25), 0.006);
+        assert_relative_eq!(squeezed.get_lr(40), 0.002);
+        assert_relative_eq!(squeezed.get_lr(55), 0.001);
+
+        // exactly enough room for warmup and decay
+        let exact = wsd.with_total_steps(100);
+        assert_eq!(exact.stable_steps, 0);
+        assert_eq!(exact.cosine_decay_steps, 60);
+        assert_eq!(exact.linear_decay_steps, 30);
+        assert_relative_eq!(exact.get_lr(100), 0.001);
+
+        // shorter than the warmup, which is all that's left
+        let warmup_only = wsd.with_total_steps(4);
+        assert_eq!(warmup_only.warmup_steps, 4);
+        assert_eq!(
+            warmup_only.stable_steps
+                + warmup_only.cosine_decay_steps
+                + warmup_only.linear_decay_steps,
+            0
+        );
+        assert_relative_eq!(warmup_only.get_lr(4), 0.001);
+    }
+
+    fn warmup_cosine_tail() -> LearningRateSchedule {
+        PiecewiseLR::new(&[
+            LRSegment {
+                start_step: 0,
+                schedule: SegmentSchedule::Constant(ConstantLR::new(0.01, 100, 0.0)),
+            },
+            LRSegment {
+                start_step: 100,
+                schedule: SegmentSchedule::Cosine(CosineLR::new(0.01, 0, 0.01, 900, 0.001)),
+            },
+            LRSegment {
+                start_step: 1000,
+                schedule: SegmentSchedule::Constant(ConstantLR::new(0.001, 0, 0.001)),
+            },
+        ])
+        .unwrap()
+        .into()
+    }
+
+    #[test]
+    fn test_piecewise_lr() {
+        let scheduler = warmup_cosine_tail();
+        assert_eq!(scheduler.get_warmup_steps(), 100);
+        assert_relative_eq!(scheduler.get_warmup_init_lr(), 0.0);
+
+        // linear warmup
+        assert_relative_eq!(scheduler.get_lr(0), 0.0);
+        assert_relative_eq!(scheduler.get_lr(50), 0.005);
+
+        // cosine decay, in its own steps
+        assert_relative_eq!(scheduler.get_lr(100), 0.01);
+        assert_relative_eq!(scheduler.get_lr(550), 0.0055);
+
+        // constant tail
+        assert_relative_eq!(scheduler.get_lr(1000), 0.001);
+        assert_relative_eq!(scheduler.get_lr(5000), 0.001);
+
+        // no jumps at the transitions
+        for boundary in [100, 1000] {
+            let values: Vec<_> = (boundary - 2..=boundary + 2)
+                .map(|step| scheduler.get_lr(step))
+                .collect();
+            for pair in values.windows(2) {
+                assert!(
+                    (pair[1] - pair[0]).abs() <= 1.5e-4,
+                    "jump at step {boundary}: {values:?}"
+                );
+            }
+        }
+
+        let encoded = postcard::to_stdvec(&scheduler).unwrap();
+        let decoded: LearningRateSchedule = postcard::from_bytes(&encoded).unwrap();
+        for step in [0, 99, 100, 999, 1000, 2000] {
+            assert_relative_eq!(decoded.get_lr(step), scheduler.get_lr(step));
+        }
+    }
+
+    #[test]
+    fn test_piecewise_lr_validation_and_total_steps() {
+        let segment = |start_step| LRSegment {
+            start_step,
+            schedule: SegmentSchedule::Constant(ConstantLR::new(0.01, 0, 0.01)),
+        };
+        assert!(PiecewiseLR::new(&[]).is_err());
+        assert!(PiecewiseLR::new(&[segment(10)]).is_err());
+        assert!(PiecewiseLR::new(&[segment(0), segment(10), segment(10)]).is_err());
+        assert!(PiecewiseLR::new(&[segment(0); MAX_LR_SEGMENTS + 1]).is_err());
+        assert!(PiecewiseLR::new(&[segment(0), segment(10)]).is_ok());
+
+        // only the last segment is stretched
+        let warmup_then_cosine: LearningRateSchedule = PiecewiseLR::new(&[
+            LRSegment {
+                start_step: 0,
+                schedule: SegmentSchedule::Constant(ConstantLR::new(0.01, 10, 0.0)),
+            },
+            LRSegment {
+                start_step: 10,
+                schedule: SegmentSchedule::Cosine(CosineLR::new(0.01, 0, 0.01, 90, 0.001)),
+            },
+        ])
+        .unwrap()
+        .into();
+        let stretched = warmup_then_cosine.with_total_steps(200);
+        assert_relative_eq!(stretched.get_lr(5), warmup_then_cosine.get_lr(5));
+        assert_relative_eq!(warmup_then_cosine.get_lr(100), 0.001);
+        assert!(stretched.get_lr(100) > 0.001);
+        assert_relative_eq!(stretched.get_lr(200), 0.001);
+    }
+
     #[test]
     fn test_edge_cases() {
         // zero warmup steps
@@ -477,4 +914,50 @@ mod tests {
         let scheduler = WarmupStableDecayLR::new(0.01, 0, 0.001, 0, 0, 0.001, 0, 0.001);
         assert_relative_eq!(scheduler.get_lr(0), 0.001);
     }
+
+    #[test]
+    fn test_distro_momentum_validation() {
+        let plain = DistroConfig {
+            clip_grad_norm: None,
+            weight_decay: None,
+            compression_decay: 0.999,
+            compression_topk: 8,
+            compression_chunk: 64,
+            quantize_1bit: true,
+            momentum: 0.0,
+            dampening: 0.0,
+            nesterov: false,
+        };
+        assert!(plain.is_valid());
+        assert!(DistroConfig {
+            momentum: 0.9,
+            nesterov: true,
+            ..plain
+        }
+        .is_valid());
+        assert!(DistroConfig {
+            momentum: 0.9,
+            dampening: 0.1,
+            ..plain
+        }
+        .is_valid());
+
+        assert!(!DistroConfig {
+            momentum: 1.0,
+            ..plain
+        }
+        .is_valid());
+        assert!(!DistroConfig {
+            nesterov: true,
+            ..plain
+        }
+        .is_valid());
+        assert!(!DistroConfig {
+            momentum: 0.9,
+            dampening: 0.1,
+            nesterov: true,
+            ..plain
+        }
+        .is_valid());
+    }
 }
@@ -12,6 +12,47 @@ pub fn deterministic_shuffle<T>(items: &mut [T], seed: u64) {
     }
 }
 
+/// The index in the original order of the item [`deterministic_shuffle`] moves to `shuffled_pos`,
+/// without shuffling a whole array of `len` items. Still O(`len`) time, since every swap of the shuffle
+/// can touch it - use [`crate::compute_shuffled_index`] where constant-time lookups matter.
+pub fn deterministic_unshuffle_index(len: usize, seed: u64, shuffled_pos: usize) -> usize {
+    deterministic_unshuffle_indices(len, seed, &[shuffled_pos])[0]
+}
+
+/// [`deterministic_unshuffle_index`] for each of `shuffled_positions`, in one pass over the shuffle.
+pub fn deterministic_unshuffle_indices(
+    len: usize,
+    seed: u64,
+    shuffled_positions: &[usize],
+) -> Vec<usize> {
+    assert!(
+        shuffled_positions.iter().all(|&pos| pos < len),
+        "shuffled position out of range for {len} items"
+    );
+    let mut positions = shuffled_positions.to_vec();
+    if len < 2 {
+        return positions;
+    }
+
+    // run the generator to the end of the shuffle, then undo its swaps last to first,
+    // following where each item was before every one of them.
+    let mut rng = LCG::new(seed);
+    for _ in 1..len {
+        rng.next_u64();
+    }
+    for i in 1..len {
+        let j = (rng.prev_u64() % (i as u64 + 1)) as usize;
+        for pos in positions.iter_mut() {
+            if *pos == i {
+                *pos = j;
+            } else if *pos == j {
+                *pos = i;
+            }
+        }
+    }
+    positions
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,6 +106,34 @@ mod tests {
         assert_eq!(vec, vec![1]);
     }
 
+    #[test]
+    fn test_unshuffle_inverts_shuffle() {
+        // random seeds and lengths, from a fixed seed so failures reproduce
+        let mut rng = LCG::new(7);
+        for _ in 0..200 {
+            let len = rng.next_range(300);
+            let seed = rng.next_u64();
+
+            let mut shuffled: Vec<usize> = (0..len).collect();
+            deterministic_shuffle(&mut shuffled, seed);
+
+            let all_positions: Vec<usize> = (0..len).collect();
+            assert_eq!(
+                deterministic_unshuffle_indices(len, seed, &all_positions),
+                shuffled,
+                "len {len}, seed {seed}"
+            );
+            if len > 0 {
+                let pos = rng.next_range(len);
+                assert_eq!(
+                    deterministic_unshuffle_index(len, seed, pos),
+                    shuffled[pos],
+                    "len {len}, seed {seed}, pos {pos}"
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_deterministic_shuffle_large_vec() {
         let mut vec: Vec<i32> = (1..1000).collect();
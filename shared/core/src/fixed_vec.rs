@@ -2,7 +2,7 @@ use crate as psyche_core;
 use anchor_lang::{prelude::borsh, AnchorDeserialize, AnchorSerialize};
 use bytemuck::Zeroable;
 use serde::{Deserialize, Serialize};
-use std::ops::{Deref, DerefMut, Range, RangeFrom, RangeFull, RangeTo};
+use std::ops::{Bound, Deref, DerefMut, Range, RangeBounds, RangeFrom, RangeFull, RangeTo};
 use ts_rs::TS;
 
 #[derive(Clone, Copy, Zeroable, AnchorSerialize, AnchorDeserialize, TS)]
@@ -166,6 +166,39 @@ impl<T: Default + Copy, const N: usize> FixedVec<T, N> {
         }
         self.len = write as u64;
     }
+
+    /// Removes the elements in `range` and returns them in order, shifting the ones after it down.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> Vec<T> {
+        let len = self.len as usize;
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+        if start > end || end > len {
+            panic!("Index out of bounds");
+        }
+
+        let drained = self.data[start..end].to_vec();
+        self.data.copy_within(end..len, start);
+
+        // zero-out the rest of the positions which are now unused
+        let new_len = len - drained.len();
+        for i in new_len..len {
+            self.data[i] = T::default();
+        }
+        self.len = new_len as u64;
+        drained
+    }
+}
+
+impl<T: anchor_lang::Space, const N: usize> anchor_lang::Space for FixedVec<T, N> {
+    const INIT_SPACE: usize = T::INIT_SPACE * N + 8;
 }
 
 impl<T: std::fmt::Debug, const N: usize> std::fmt::Debug for FixedVec<T, N> {
@@ -453,6 +486,62 @@ mod tests {
         assert_eq!(vec[0], 2);
         assert_eq!(vec[1], 4);
         assert_eq!(vec[2], 6);
+        assert_eq!(vec.data[3..], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_retain_edge_cases() {
+        let mut vec: FixedVec<u32, 6> = FixedVec::new();
+        vec.extend([1, 2, 3, 4]).unwrap();
+
+        vec.retain(|_| true);
+        assert_eq!(vec[..], [1, 2, 3, 4]);
+        assert_eq!(vec.data[4..], [0, 0]);
+
+        vec.retain(|_| false);
+        assert!(vec.is_empty());
+        assert_eq!(vec.data, [0; 6]);
+
+        vec.retain(|_| true);
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut vec: FixedVec<u32, 6> = FixedVec::new();
+        vec.extend([1, 2, 3, 4, 5]).unwrap();
+
+        assert_eq!(vec.drain(1..3), vec![2, 3]);
+        assert_eq!(vec.len(), 3);
+        assert_eq!(vec[..], [1, 4, 5]);
+        assert_eq!(vec.data[3..], [0, 0, 0]);
+
+        assert_eq!(vec.drain(2..=2), vec![5]);
+        assert_eq!(vec[..], [1, 4]);
+        assert_eq!(vec.data[2..], [0, 0, 0, 0]);
+
+        // an empty range removes nothing
+        assert_eq!(vec.drain(1..1), Vec::<u32>::new());
+        assert_eq!(vec[..], [1, 4]);
+
+        assert_eq!(vec.drain(..), vec![1, 4]);
+        assert!(vec.is_empty());
+        assert_eq!(vec.data, [0; 6]);
+        assert_eq!(vec.drain(..), Vec::<u32>::new());
+
+        // draining a full vec leaves room to push again
+        vec.extend([1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(vec.drain(4..), vec![5, 6]);
+        vec.push(7).unwrap();
+        assert_eq!(vec[..], [1, 2, 3, 4, 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index out of bounds")]
+    fn test_drain_out_of_bounds() {
+        let mut vec: FixedVec<u32, 6> = FixedVec::new();
+        vec.extend([1, 2, 3]).unwrap();
+        vec.drain(2..4);
     }
 
     #[test]
@@ -3,6 +3,7 @@ use std::{
     collections::{BTreeMap, HashMap},
     fmt,
     hash::{Hash, Hasher},
+    ops::{Add, Sub},
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, PartialOrd, Ord)]
@@ -141,6 +142,78 @@ impl<T: Copy + Ord, V> IntervalTree<T, V> {
     }
 }
 
+impl<T: Copy + Ord + Add<Output = T> + Sub<Output = T> + From<u8>, V> IntervalTree<T, V> {
+    /// Removes every point in `range`, truncating or splitting the stored intervals it overlaps.
+    /// Returns the removed parts of each stored interval with their values, in order.
+    pub fn remove_range(&mut self, range: &ClosedInterval<T>) -> Vec<(ClosedInterval<T>, V)>
+    where
+        V: Clone,
+    {
+        let one = T::from(1);
+        // only the interval starting right before the range can reach into it
+        let before = self
+            .tree
+            .range(..range.start)
+            .next_back()
+            .filter(|(_, (interval, _))| interval.end >= range.start)
+            .map(|(start, _)| *start);
+        let overlapping: Vec<T> = before
+            .into_iter()
+            .chain(
+                self.tree
+                    .range(range.start..=range.end)
+                    .map(|(start, _)| *start),
+            )
+            .collect();
+
+        let mut removed = Vec::with_capacity(overlapping.len());
+        for start in overlapping {
+            let (interval, value) = self.tree.remove(&start).unwrap();
+            if interval.start < range.start {
+                let left = ClosedInterval::new(interval.start, range.start - one);
+                self.tree.insert(left.start, (left, value.clone()));
+            }
+            if interval.end > range.end {
+                let right = ClosedInterval::new(range.end + one, interval.end);
+                self.tree.insert(right.start, (right, value.clone()));
+            }
+            removed.push((
+                ClosedInterval::new(interval.start.max(range.start), interval.end.min(range.end)),
+                value,
+            ));
+        }
+        removed
+    }
+
+    /// Joins stored intervals that touch (one ends right before the next starts) and share a value.
+    pub fn merge_adjacent(&mut self)
+    where
+        V: PartialEq,
+    {
+        let one = T::from(1);
+        let mut merged = BTreeMap::new();
+        let mut current: Option<(ClosedInterval<T>, V)> = None;
+        for (_, (interval, value)) in std::mem::take(&mut self.tree) {
+            match &mut current {
+                Some((prev, prev_value))
+                    if *prev_value == value && prev.end >= interval.start - one =>
+                {
+                    prev.end = prev.end.max(interval.end);
+                }
+                _ => {
+                    if let Some((prev, prev_value)) = current.replace((interval, value)) {
+                        merged.insert(prev.start, (prev, prev_value));
+                    }
+                }
+            }
+        }
+        if let Some((prev, prev_value)) = current {
+            merged.insert(prev.start, (prev, prev_value));
+        }
+        self.tree = merged;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -222,4 +295,76 @@ mod tests {
         assert_eq!(iter.next(), Some((&ClosedInterval::new(7, 10), &"B")));
         assert_eq!(iter.next(), None);
     }
+
+    fn intervals<V: Copy>(tree: &IntervalTree<u64, V>) -> Vec<(u64, u64, V)> {
+        tree.iter()
+            .map(|(interval, value)| (interval.start, interval.end, *value))
+            .collect()
+    }
+
+    #[test]
+    fn test_interval_tree_remove_range_partial_overlap() {
+        let mut tree = IntervalTree::new();
+        tree.insert(ClosedInterval::new(1, 5), "A").unwrap();
+        tree.insert(ClosedInterval::new(7, 10), "B").unwrap();
+
+        let removed = tree.remove_range(&ClosedInterval::new(4, 8));
+        assert_eq!(
+            removed,
+            vec![
+                (ClosedInterval::new(4, 5), "A"),
+                (ClosedInterval::new(7, 8), "B")
+            ]
+        );
+        assert_eq!(intervals(&tree), vec![(1, 3, "A"), (9, 10, "B")]);
+        assert_eq!(tree.get(4), None);
+        assert_eq!(tree.get(9), Some(&"B"));
+    }
+
+    #[test]
+    fn test_interval_tree_remove_range_containment() {
+        let mut tree = IntervalTree::new();
+        tree.insert(ClosedInterval::new(0, 3), "A").unwrap();
+        tree.insert(ClosedInterval::new(4, 6), "B").unwrap();
+        tree.insert(ClosedInterval::new(10, 20), "C").unwrap();
+
+        // a range covering whole intervals removes them
+        tree.remove_range(&ClosedInterval::new(0, 7));
+        assert_eq!(intervals(&tree), vec![(10, 20, "C")]);
+
+        // removing the middle of an interval splits it in two
+        let removed = tree.remove_range(&ClosedInterval::new(14, 15));
+        assert_eq!(removed, vec![(ClosedInterval::new(14, 15), "C")]);
+        assert_eq!(intervals(&tree), vec![(10, 13, "C"), (16, 20, "C")]);
+
+        // removing nothing is a no-op
+        assert!(tree.remove_range(&ClosedInterval::new(30, 40)).is_empty());
+        assert!(tree.remove_range(&ClosedInterval::new(14, 15)).is_empty());
+        assert_eq!(intervals(&tree), vec![(10, 13, "C"), (16, 20, "C")]);
+    }
+
+    #[test]
+    fn test_interval_tree_merge_adjacent() {
+        let mut tree = IntervalTree::new();
+        tree.insert(ClosedInterval::new(0, 3), "A").unwrap();
+        tree.insert(ClosedInterval::new(4, 6), "A").unwrap();
+        tree.insert(ClosedInterval::new(7, 7), "B").unwrap();
+        tree.insert(ClosedInterval::new(9, 12), "B").unwrap();
+        tree.insert(ClosedInterval::new(13, 15), "B").unwrap();
+
+        tree.merge_adjacent();
+        // touching intervals with different values, or a gap between them, stay apart
+        assert_eq!(
+            intervals(&tree),
+            vec![(0, 6, "A"), (7, 7, "B"), (9, 15, "B")]
+        );
+        assert_eq!(tree.get(5), Some(&"A"));
+        assert_eq!(tree.get(13), Some(&"B"));
+
+        // splitting and merging back gives the original interval
+        tree.remove_range(&ClosedInterval::new(2, 2));
+        tree.insert(ClosedInterval::new(2, 2), "A").unwrap();
+        tree.merge_adjacent();
+        assert_eq!(intervals(&tree)[0], (0, 6, "A"));
+    }
 }
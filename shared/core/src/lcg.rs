@@ -3,6 +3,18 @@
 
 const LCG_A: u64 = 6364136223846793005;
 const LCG_C: u64 = 1442695040888963407;
+// LCG_A is odd, so it has a multiplicative inverse mod 2^64, which makes the generator reversible.
+// Each Newton step doubles the number of correct low bits, starting from the 3 that x * x = 1 (mod 8) gives.
+const LCG_A_INV: u64 = {
+    let mut inv = LCG_A;
+    let mut i = 0;
+    while i < 5 {
+        inv = inv.wrapping_mul(2u64.wrapping_sub(LCG_A.wrapping_mul(inv)));
+        i += 1;
+    }
+    inv
+};
+
 pub struct LCG {
     state: u64,
 }
@@ -20,6 +32,13 @@ impl LCG {
     pub fn next_range(&mut self, max: usize) -> usize {
         (self.next_u64() % max as u64) as usize
     }
+
+    /// The value the last [`Self::next_u64`] returned, stepping the generator back so the call before it is next.
+    pub fn prev_u64(&mut self) -> u64 {
+        let value = self.state;
+        self.state = self.state.wrapping_sub(LCG_C).wrapping_mul(LCG_A_INV);
+        value
+    }
 }
 
 #[cfg(test)]
@@ -80,6 +99,15 @@ mod tests {
         assert!(counts.iter().all(|&count| count > 0));
     }
 
+    #[test]
+    fn test_lcg_prev_reverses_next() {
+        let mut lcg = LCG::new(12345);
+        let sequence: Vec<u64> = (0..100).map(|_| lcg.next_u64()).collect();
+        let reversed: Vec<u64> = (0..100).map(|_| lcg.prev_u64()).collect();
+        assert!(sequence.iter().eq(reversed.iter().rev()));
+        assert_eq!(lcg.state, 12345);
+    }
+
     #[test]
     fn test_lcg_next_range_edge_cases() {
         let mut lcg = LCG::new(12345);
@@ -24,21 +24,26 @@ mod swap_or_not;
 mod token_size;
 
 pub use batch_id::BatchId;
-pub use bloom::Bloom;
+pub use bloom::{Bloom, BloomKeysMismatch};
 pub use bounded_queue::BoundedQueue;
 pub use boxed_future::BoxedFuture;
 pub use cancellable_barrier::{CancellableBarrier, CancelledBarrier};
 pub use data_shuffle::Shuffle;
 pub use definitions::{
-    ConstantLR, CosineLR, LearningRateSchedule, LearningRateScheduler, LinearLR,
-    OptimizerDefinition,
+    ConstantLR, CosineLR, DistroConfig, LRSegment, LearningRateSchedule, LearningRateScheduler,
+    LinearLR, OptimizerDefinition, PiecewiseLR, SegmentSchedule, WarmupStableDecayLR,
+    MAX_LR_SEGMENTS,
+};
+pub use deterministic_shuffle::{
+    deterministic_shuffle, deterministic_unshuffle_index, deterministic_unshuffle_indices,
 };
-pub use deterministic_shuffle::deterministic_shuffle;
 pub use fixed_string::FixedString;
 pub use fixed_vec::FixedVec;
 pub use interval_tree::{ClosedInterval, IntervalTree};
 pub use lcg::LCG;
-pub use merkle_tree::{HashWrapper as MerkleRoot, MerkleTree, OwnedProof, Proof};
+pub use merkle_tree::{
+    verify_multiproof, HashWrapper as MerkleRoot, MerkleTree, OwnedMultiProof, OwnedProof, Proof,
+};
 pub use node_identity::NodeIdentity;
 pub use running_average::RunningAverage;
 pub use serde_utils::{
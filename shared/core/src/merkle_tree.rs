@@ -183,6 +183,88 @@ impl OwnedProof {
     }
 }
 
+/// Proves several leaves of one tree at once.
+/// Interior nodes that can be computed from the proven leaves aren't included,
+/// so leaves close to each other share most of their path.
+#[derive(
+    Debug, Default, PartialEq, Eq, Clone, AnchorDeserialize, AnchorSerialize, Deserialize, Serialize,
+)]
+pub struct OwnedMultiProof {
+    leaf_count: u64,
+    /// the sibling hashes the verifier can't compute, level by level from the leaves up,
+    /// left to right within a level.
+    hashes: Vec<HashWrapper>,
+}
+
+impl OwnedMultiProof {
+    pub fn hashes(&self) -> &[HashWrapper] {
+        &self.hashes
+    }
+}
+
+/// Checks that every `(index, item)` in `leaves` is in the tree with `root` that `proof` was made for,
+/// with [`MerkleTree::multiproof`] on the same set of indices. `leaves` can be in any order,
+/// and can repeat a leaf as long as it's the same item every time.
+pub fn verify_multiproof<T: AsRef<[u8]>>(
+    root: &HashWrapper,
+    leaves: &[(usize, T)],
+    proof: &OwnedMultiProof,
+) -> bool {
+    let mut level: Vec<(usize, HashWrapper)> = leaves
+        .iter()
+        .map(|(index, item)| {
+            let item = item.as_ref();
+            (*index, HashWrapper::new(hash_leaf!(item)))
+        })
+        .collect();
+    level.sort_by_key(|(index, _)| *index);
+    let len = level.len();
+    level.dedup();
+    // the same index with different items
+    if level.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return false;
+    }
+    let Ok(leaf_count) = usize::try_from(proof.leaf_count) else {
+        return false;
+    };
+    if len == 0 || level.last().is_some_and(|(index, _)| *index >= leaf_count) {
+        return false;
+    }
+
+    let mut hashes = proof.hashes.iter();
+    let mut level_len = leaf_count;
+    while level_len > 1 {
+        let mut parents = Vec::with_capacity(level.len());
+        let mut i = 0;
+        while i < level.len() {
+            let (index, hash) = level[i];
+            let sibling = index ^ 1;
+            let sibling_hash = if level.get(i + 1).is_some_and(|(next, _)| *next == sibling) {
+                i += 1;
+                level[i].1
+            } else if sibling >= level_len {
+                // the last node of an odd level is paired with itself
+                hash
+            } else {
+                match hashes.next() {
+                    Some(sibling_hash) => *sibling_hash,
+                    None => return false,
+                }
+            };
+            let (lsib, rsib) = if index % 2 == 0 {
+                (hash, sibling_hash)
+            } else {
+                (sibling_hash, hash)
+            };
+            parents.push((index / 2, HashWrapper::new(hash_intermediate!(lsib, rsib))));
+            i += 1;
+        }
+        level = parents;
+        level_len = MerkleTree::next_level_len(level_len);
+    }
+    hashes.next().is_none() && level[0].1 == *root
+}
+
 impl<'a> Proof<'a> {
     pub fn push(&mut self, entry: ProofEntry<'a>) {
         self.0.push(entry)
@@ -326,11 +408,51 @@ impl MerkleTree {
         }
         Some(path)
     }
+
+    /// One proof for all the leaves at `indices`, checked with [`verify_multiproof`].
+    /// Order and repeats in `indices` don't matter.
+    /// Returns `None` if there are no indices or any of them is out of range.
+    pub fn multiproof(&self, indices: &[usize]) -> Option<OwnedMultiProof> {
+        let mut level: Vec<usize> = indices.to_vec();
+        level.sort_unstable();
+        level.dedup();
+        if level.is_empty() || level.last().is_some_and(|index| *index >= self.leaf_count) {
+            return None;
+        }
+
+        let mut hashes = Vec::new();
+        let mut level_len = self.leaf_count;
+        let mut level_start = 0;
+        while level_len > 1 {
+            let mut parents = Vec::with_capacity(level.len());
+            let mut i = 0;
+            while i < level.len() {
+                let sibling = level[i] ^ 1;
+                if level.get(i + 1) == Some(&sibling) {
+                    // the verifier has both children already
+                    i += 1;
+                } else if sibling < level_len {
+                    hashes.push(self.nodes[level_start + sibling]);
+                }
+                parents.push(level[i] / 2);
+                i += 1;
+            }
+            level = parents;
+            level_start += level_len;
+            level_len = MerkleTree::next_level_len(level_len);
+        }
+
+        Some(OwnedMultiProof {
+            leaf_count: self.leaf_count as u64,
+            hashes,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lcg::LCG;
 
     const TEST: &[&[u8]] = &[
         b"my", b"very", b"eager", b"mother", b"just", b"served", b"us", b"nine", b"pizzas",
@@ -386,6 +508,105 @@ mod tests {
         }
     }
 
+    fn leaves<'a>(indices: &[usize], items: &[&'a [u8]]) -> Vec<(usize, &'a [u8])> {
+        indices.iter().map(|i| (*i, items[*i])).collect()
+    }
+
+    #[test]
+    fn test_multiproof_matches_per_leaf_proofs() {
+        let mut rng = LCG::new(1234);
+        for _ in 0..200 {
+            let leaf_count = 1 + rng.next_range(100);
+            let items: Vec<Vec<u8>> = (0..leaf_count)
+                .map(|_| rng.next_u64().to_le_bytes().to_vec())
+                .collect();
+            let items: Vec<&[u8]> = items.iter().map(Vec::as_slice).collect();
+            let mt = MerkleTree::new(&items);
+            let root = mt.get_root().unwrap();
+
+            // unsorted, possibly with repeats
+            let indices: Vec<usize> = (0..1 + rng.next_range(leaf_count))
+                .map(|_| rng.next_range(leaf_count))
+                .collect();
+            let proof = mt.multiproof(&indices).unwrap();
+            assert!(verify_multiproof(root, &leaves(&indices, &items), &proof));
+            for &i in &indices {
+                assert!(mt.find_path(i).unwrap().verify_item(&items[i]));
+            }
+
+            // a wrong item for any of the leaves fails it
+            let mut tampered = leaves(&indices, &items);
+            let target = rng.next_range(tampered.len());
+            tampered[target].1 = &b"tampered"[..];
+            assert!(!verify_multiproof(root, &tampered, &proof));
+
+            // as does proving a different set of leaves with it
+            let other = rng.next_range(leaf_count);
+            if !indices.contains(&other) {
+                let mut more = leaves(&indices, &items);
+                more.push((other, items[other]));
+                assert!(!verify_multiproof(root, &more, &proof));
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiproof_bad_indices() {
+        let mt = MerkleTree::new(TEST);
+        let root = mt.get_root().unwrap();
+        assert_eq!(mt.multiproof(&[]), None);
+        assert_eq!(mt.multiproof(&[0, TEST.len()]), None);
+
+        let proof = mt.multiproof(&[3, 1, 3]).unwrap();
+        assert_eq!(proof, mt.multiproof(&[1, 3]).unwrap());
+        assert!(verify_multiproof(root, &leaves(&[3, 1, 3], TEST), &proof));
+        // one index claimed to hold two different items
+        assert!(!verify_multiproof(
+            root,
+            &[(1, TEST[1]), (3, TEST[3]), (3, TEST[4])],
+            &proof
+        ));
+        assert!(!verify_multiproof::<&[u8]>(root, &[], &proof));
+        assert!(!verify_multiproof(
+            root,
+            &[(1, TEST[1]), (TEST.len() + 1, TEST[3])],
+            &proof
+        ));
+    }
+
+    #[test]
+    fn test_multiproof_smaller_than_separate_proofs() {
+        let items: Vec<[u8; 8]> = (0..1024u64).map(u64::to_le_bytes).collect();
+        let mt = MerkleTree::new(&items);
+        let indices: Vec<usize> = (512..528).collect();
+
+        let multiproof = mt.multiproof(&indices).unwrap();
+        let separate: Vec<OwnedProof> = indices
+            .iter()
+            .map(|i| mt.find_path(*i).unwrap().into())
+            .collect();
+        let multiproof_size = postcard::to_stdvec(&multiproof).unwrap().len();
+        let separate_size: usize = separate
+            .iter()
+            .map(|proof| postcard::to_stdvec(proof).unwrap().len())
+            .sum();
+        // 16 neighbouring leaves only need the 6 siblings above their subtree
+        assert_eq!(multiproof.hashes().len(), 6);
+        assert!(multiproof_size * 20 < separate_size);
+    }
+
+    #[test]
+    fn test_multiproof_single_leaf_tree() {
+        let mt = MerkleTree::new(&[b"only"]);
+        let proof = mt.multiproof(&[0]).unwrap();
+        assert!(proof.hashes().is_empty());
+        assert!(verify_multiproof(
+            mt.get_root().unwrap(),
+            &[(0, b"only")],
+            &proof
+        ));
+    }
+
     #[test]
     fn test_proof_entry_instantiation_lsib_set() {
         ProofEntry::new(&HashWrapper::default(), Some(&HashWrapper::default()), None);
@@ -39,6 +39,10 @@ impl<T: NodeIdentity> WatcherBackend<T> for DummyBackend<T> {
     async fn send_checkpoint(&mut self, _checkpoint: model::HubRepo) -> anyhow::Result<()> {
         bail!("Data provider does not send checkpoints");
     }
+
+    async fn send_withdraw(&mut self) -> anyhow::Result<()> {
+        bail!("Data provider does not withdraw");
+    }
 }
 
 #[derive(
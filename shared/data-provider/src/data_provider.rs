@@ -1,6 +1,6 @@
 use crate::{
-    http::HttpDataProvider, DataProviderTcpClient, DummyDataProvider, TokenizedDataProvider,
-    WeightedDataProvider,
+    http::HttpDataProvider, DataProviderTcpClient, DummyDataProvider, LengthKnownDataProvider,
+    TokenizedDataProvider, WeightedDataProvider,
 };
 
 use psyche_core::BatchId;
@@ -13,6 +13,17 @@ pub enum DataProvider<T: AuthenticatableIdentity> {
     WeightedHttp(WeightedDataProvider<HttpDataProvider>),
 }
 
+impl<T: AuthenticatableIdentity> DataProvider<T> {
+    /// How many samples the provider has, if it knows. Dummy providers make up samples for any index.
+    pub fn num_sequences(&self) -> Option<usize> {
+        match self {
+            DataProvider::Http(provider) => Some(provider.num_sequences()),
+            DataProvider::WeightedHttp(provider) => Some(provider.num_sequences()),
+            DataProvider::Server(_) | DataProvider::Dummy(_) => None,
+        }
+    }
+}
+
 impl<T: AuthenticatableIdentity> TokenizedDataProvider for DataProvider<T> {
     async fn get_samples(&mut self, data_ids: BatchId) -> anyhow::Result<Vec<Vec<i32>>> {
         match self {
@@ -1,11 +1,15 @@
 use crate::{traits::TokenizedDataProvider, LengthKnownDataProvider};
 use anyhow::{bail, Result};
 use psyche_core::{BatchId, TokenSize};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 pub struct DummyDataProvider {
     seq_len: usize,
     token_size_in_bytes: TokenSize,
     num_sequences: u64,
+    seed: [u8; 32],
+    vocab_size: Option<u32>,
 }
 
 impl DummyDataProvider {
@@ -13,33 +17,41 @@ impl DummyDataProvider {
         token_size_in_bytes: TokenSize,
         num_tokens_per_sequence: usize, // num tokens per sequence
         num_sequences: u64,
+        seed: [u8; 32],
     ) -> Self {
         Self {
             seq_len: num_tokens_per_sequence,
             token_size_in_bytes,
             num_sequences,
+            seed,
+            vocab_size: None,
         }
     }
 
-    fn internal_get_samples(&self, num_samples: usize) -> Result<Vec<Vec<i32>>> {
-        let mut ret: Vec<_> = Vec::new();
-        for _ in 0..num_samples {
-            let data_len = usize::from(self.token_size_in_bytes) * (self.seq_len + 1);
-            let data = vec![0; data_len];
+    /// Keeps the token ids below `vocab_size`, so they're valid inputs for a model with that vocabulary.
+    pub fn set_vocab_size(&mut self, vocab_size: usize) {
+        self.vocab_size = Some(vocab_size.clamp(1, u32::MAX as usize) as u32);
+    }
 
-            let tokens: Vec<i32> = data
-                .chunks(self.token_size_in_bytes.into())
-                .map(|t| {
-                    use TokenSize::*;
-                    match self.token_size_in_bytes {
-                        TwoBytes => u16::from_le_bytes(t.try_into().unwrap()) as i32,
-                        FourBytes => u32::from_le_bytes(t.try_into().unwrap()) as i32,
-                    }
-                })
-                .collect();
-            ret.push(tokens);
-        }
-        Ok(ret)
+    /// each sequence only depends on the seed and its id, so any batch containing it gets the same tokens
+    fn internal_get_sample(&self, id: u64) -> Vec<i32> {
+        let mut rng = ChaCha8Rng::from_seed(self.seed);
+        rng.set_stream(id);
+
+        (0..self.seq_len + 1)
+            .map(|_| {
+                use TokenSize::*;
+                let token = match self.token_size_in_bytes {
+                    TwoBytes => rng.next_u32() as u16 as u32,
+                    // keep tokens non-negative
+                    FourBytes => rng.next_u32() >> 1,
+                };
+                match self.vocab_size {
+                    Some(vocab_size) => (token % vocab_size) as i32,
+                    None => token as i32,
+                }
+            })
+            .collect()
     }
 }
 
@@ -50,7 +62,10 @@ impl TokenizedDataProvider for DummyDataProvider {
                 bail!("id {id} > self.num_sequences {}", self.num_sequences)
             }
         }
-        self.internal_get_samples(data_ids.len())
+        Ok(data_ids
+            .iter()
+            .map(|id| self.internal_get_sample(id))
+            .collect())
     }
 }
 
@@ -5,9 +5,12 @@ use hf_hub::{
     },
     Cache, Repo, RepoType,
 };
-use std::{path::PathBuf, time::Instant};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, fs::File, io, path::PathBuf, time::Instant};
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, info};
 
 const MODEL_EXTENSIONS: [&str; 3] = [".safetensors", ".json", ".py"];
 const DATASET_EXTENSIONS: [&str; 1] = [".parquet"];
@@ -202,6 +205,123 @@ pub enum UploadModelError {
 
     #[error("failed to commit files: {0}")]
     Commit(#[from] CommitError),
+
+    #[error("failed to list the files already in the repo: {0}")]
+    ListFiles(#[from] reqwest::Error),
+
+    #[error("failed to hash {0}: {1}")]
+    Hash(PathBuf, io::Error),
+
+    #[error("{0} doesn't match the local file after uploading it")]
+    Mismatch(String),
+
+    #[error("upload stopped after committing {uploaded:?}: {source}")]
+    Incomplete {
+        /// Files that are in the repo, whether we committed them or they were there already.
+        uploaded: Vec<String>,
+        source: Box<UploadModelError>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UploadOptions {
+    /// Commit files one at a time, skipping any the repo already has with the same contents,
+    /// so re-running an upload that died midway only sends what's missing.
+    /// Otherwise, everything goes in a single commit.
+    pub resume: bool,
+}
+
+/// A file in a hub repo, as listed by the hub API with `blobs=true`.
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteFile {
+    rfilename: String,
+    size: Option<u64>,
+    lfs: Option<RemoteLfs>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RemoteLfs {
+    sha256: String,
+    size: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteRepo {
+    sha: String,
+    siblings: Vec<RemoteFile>,
+}
+
+#[derive(Debug, Clone)]
+struct LocalFile {
+    name: String,
+    path: PathBuf,
+    size: u64,
+    sha256: String,
+}
+
+impl LocalFile {
+    fn hash(name: String, path: PathBuf) -> Result<Self, UploadModelError> {
+        let hash = || {
+            let mut file = File::open(&path)?;
+            let mut hasher = Sha256::new();
+            let size = io::copy(&mut file, &mut hasher)?;
+            io::Result::Ok((size, hasher.finalize()))
+        };
+        let (size, sha256) = hash().map_err(|err| UploadModelError::Hash(path.clone(), err))?;
+        Ok(Self {
+            name,
+            path,
+            size,
+            sha256: format!("{sha256:x}"),
+        })
+    }
+
+    /// Whether the repo already has this exact file. Only LFS files carry a sha256 we can compare,
+    /// so anything else (the small json files) is always considered out of date.
+    fn matches(&self, remote: &RemoteFile) -> bool {
+        remote.lfs.as_ref().is_some_and(|lfs| {
+            lfs.size == self.size && lfs.sha256.eq_ignore_ascii_case(&self.sha256)
+        })
+    }
+}
+
+/// The local files the repo doesn't already have, in their original order.
+fn files_to_upload(local: Vec<LocalFile>, remote: &HashMap<String, RemoteFile>) -> Vec<LocalFile> {
+    local
+        .into_iter()
+        .filter(|file| {
+            !remote
+                .get(&file.name)
+                .is_some_and(|remote| file.matches(remote))
+        })
+        .collect()
+}
+
+/// The hub to talk to, which hf-hub also reads from `HF_ENDPOINT`.
+fn hub_endpoint() -> String {
+    std::env::var("HF_ENDPOINT").unwrap_or_else(|_| "https://huggingface.co".to_string())
+}
+
+async fn list_remote_files(
+    repo_id: &str,
+    token: &str,
+) -> Result<(String, HashMap<String, RemoteFile>), UploadModelError> {
+    let endpoint = hub_endpoint();
+    let repo: RemoteRepo = reqwest::Client::new()
+        .get(format!("{endpoint}/api/models/{repo_id}"))
+        .query(&[("blobs", "true")])
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let files = repo
+        .siblings
+        .into_iter()
+        .map(|file| (file.rfilename.clone(), file))
+        .collect();
+    Ok((repo.sha, files))
 }
 
 pub async fn upload_model_repo_async(
@@ -210,14 +330,15 @@ pub async fn upload_model_repo_async(
     token: String,
     commit_message: Option<String>,
     commit_description: Option<String>,
+    options: UploadOptions,
 ) -> Result<String, UploadModelError> {
     let api = hf_hub::api::tokio::ApiBuilder::new()
-        .with_token(Some(token))
+        .with_token(Some(token.clone()))
         .build()?;
     let repo = Repo::model(repo_id.clone());
     let api_repo = api.repo(repo);
 
-    let files: Result<Vec<(UploadSource, String)>, _> = files
+    let files: Result<Vec<(PathBuf, String)>, _> = files
         .into_iter()
         .map(|path| {
             path.file_name()
@@ -227,16 +348,30 @@ pub async fn upload_model_repo_async(
                         .ok_or(UploadModelError::InvalidFilename(path.clone()))
                         .map(|s| s.to_string())
                 })
-                .map(|name| (path.into(), name))
+                .map(|name| (path, name))
         })
         .collect();
 
     let files = files?;
+    if options.resume {
+        return upload_incrementally(
+            &api_repo,
+            &repo_id,
+            files,
+            &token,
+            commit_message,
+            commit_description,
+        )
+        .await;
+    }
     debug!("Committing to {}: {:?}", repo_id, files);
 
     let commit_info = api_repo
         .upload_files(
-            files,
+            files
+                .into_iter()
+                .map(|(path, name)| (UploadSource::from(path), name))
+                .collect(),
             commit_message.clone(),
             commit_description.clone(),
             false,
@@ -244,3 +379,397 @@ pub async fn upload_model_repo_async(
         .await?;
     Ok(commit_info.oid)
 }
+
+async fn upload_incrementally(
+    api_repo: &hf_hub::api::tokio::ApiRepo,
+    repo_id: &str,
+    files: Vec<(PathBuf, String)>,
+    token: &str,
+    commit_message: Option<String>,
+    commit_description: Option<String>,
+) -> Result<String, UploadModelError> {
+    let (mut revision, remote) = list_remote_files(repo_id, token).await?;
+    let local = tokio::task::spawn_blocking(move || {
+        files
+            .into_iter()
+            .map(|(path, name)| LocalFile::hash(name, path))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+    .expect("hashing files panicked")?;
+
+    let mut uploaded: Vec<String> = local.iter().map(|file| file.name.clone()).collect();
+    let pending = files_to_upload(local, &remote);
+    uploaded.retain(|name| !pending.iter().any(|file| &file.name == name));
+    info!(
+        repo = repo_id,
+        "{} files already uploaded, committing {} more",
+        uploaded.len(),
+        pending.len()
+    );
+
+    for file in pending {
+        let committed = async {
+            debug!("Committing {} to {repo_id}", file.name);
+            let commit_info = api_repo
+                .upload_files(
+                    vec![(file.path.clone().into(), file.name.clone())],
+                    commit_message.clone(),
+                    commit_description.clone(),
+                    false,
+                )
+                .await?;
+            let (_, remote) = list_remote_files(repo_id, token).await?;
+            if !remote
+                .get(&file.name)
+                .is_some_and(|remote| verify_upload(&file, remote))
+            {
+                return Err(UploadModelError::Mismatch(file.name.clone()));
+            }
+            Ok(commit_info.oid)
+        }
+        .await;
+        match committed {
+            Ok(oid) => {
+                revision = oid;
+                uploaded.push(file.name);
+            }
+            Err(err) => {
+                return Err(UploadModelError::Incomplete {
+                    uploaded,
+                    source: Box::new(err),
+                })
+            }
+        }
+    }
+    Ok(revision)
+}
+
+/// Whether a file we just committed arrived intact. Non-LFS files only list their size.
+fn verify_upload(local: &LocalFile, remote: &RemoteFile) -> bool {
+    match &remote.lfs {
+        Some(_) => local.matches(remote),
+        None => remote.size == Some(local.size),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RepoAccessError {
+    #[error("{repo} doesn't exist, or is private and {}", token_hint(.with_token, "your HF_TOKEN can't read it", "needs an HF_TOKEN to read"))]
+    NotFound { repo: String, with_token: bool },
+
+    #[error("{repo} is gated, and {}", token_hint(.with_token, "your HF_TOKEN hasn't been granted access to it. Request access on its hub page", "needs an HF_TOKEN with access to it"))]
+    Gated { repo: String, with_token: bool },
+
+    #[error("the hub rejected your HF_TOKEN while checking access to {0}. Is it valid?")]
+    InvalidToken(String),
+
+    #[error("failed to check access to {repo}: {source}")]
+    Request {
+        repo: String,
+        source: reqwest::Error,
+    },
+}
+
+fn token_hint(with_token: &bool, with: &'static str, without: &'static str) -> &'static str {
+    if *with_token {
+        with
+    } else {
+        without
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Gated {
+    Flag(bool),
+    /// "auto" or "manual" approval
+    Mode(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoAccessInfo {
+    gated: Option<Gated>,
+    #[serde(default)]
+    siblings: Vec<RemoteFile>,
+}
+
+/// Checks that `token` (or no token, if `None`) can download the repo, so a missing or
+/// insufficient token is reported before anything else happens, instead of midway through a download.
+pub async fn check_repo_access(
+    repo_id: &str,
+    repo_type: RepoType,
+    revision: Option<&str>,
+    token: Option<&str>,
+) -> Result<(), RepoAccessError> {
+    check_repo_access_at(&hub_endpoint(), repo_id, repo_type, revision, token).await
+}
+
+async fn check_repo_access_at(
+    endpoint: &str,
+    repo_id: &str,
+    repo_type: RepoType,
+    revision: Option<&str>,
+    token: Option<&str>,
+) -> Result<(), RepoAccessError> {
+    let (api_kind, url_prefix) = match repo_type {
+        RepoType::Model => ("models", ""),
+        RepoType::Dataset => ("datasets", "datasets/"),
+        RepoType::Space => ("spaces", "spaces/"),
+    };
+    let revision = revision.unwrap_or("main");
+    let with_token = token.is_some();
+    let request_error = |source| RepoAccessError::Request {
+        repo: repo_id.to_string(),
+        source,
+    };
+    let client = reqwest::Client::new();
+    let authed = |request: reqwest::RequestBuilder| match token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+
+    let response = authed(client.get(format!(
+        "{endpoint}/api/{api_kind}/{repo_id}/revision/{revision}"
+    )))
+    .send()
+    .await
+    .map_err(request_error)?;
+    match response.status() {
+        // the hub hides repos we can't read, and answers 401 for them when there's no token
+        StatusCode::NOT_FOUND => {
+            return Err(RepoAccessError::NotFound {
+                repo: repo_id.to_string(),
+                with_token,
+            })
+        }
+        StatusCode::UNAUTHORIZED if with_token => {
+            return Err(RepoAccessError::InvalidToken(repo_id.to_string()))
+        }
+        StatusCode::UNAUTHORIZED => {
+            return Err(RepoAccessError::NotFound {
+                repo: repo_id.to_string(),
+                with_token,
+            })
+        }
+        _ => {}
+    }
+    let info: RepoAccessInfo = response
+        .error_for_status()
+        .map_err(request_error)?
+        .json()
+        .await
+        .map_err(request_error)?;
+
+    // the repo's info is public even when it's gated, only downloading its files needs access
+    let gated = matches!(info.gated, Some(Gated::Mode(_)) | Some(Gated::Flag(true)));
+    let Some(file) = info.siblings.first().filter(|_| gated) else {
+        return Ok(());
+    };
+    let response = authed(client.head(format!(
+        "{endpoint}/{url_prefix}{repo_id}/resolve/{revision}/{}",
+        file.rfilename
+    )))
+    .send()
+    .await
+    .map_err(request_error)?;
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(RepoAccessError::Gated {
+            repo: repo_id.to_string(),
+            with_token,
+        }),
+        _ => response
+            .error_for_status()
+            .map(|_| ())
+            .map_err(request_error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_files(dir: &std::path::Path, contents: &[(&str, &[u8])]) -> Vec<LocalFile> {
+        contents
+            .iter()
+            .map(|(name, content)| {
+                let path = dir.join(name);
+                std::fs::write(&path, content).unwrap();
+                LocalFile::hash(name.to_string(), path).unwrap()
+            })
+            .collect()
+    }
+
+    /// What the hub lists for `file` once it's committed.
+    fn committed(file: &LocalFile, lfs: bool) -> (String, RemoteFile) {
+        let remote = RemoteFile {
+            rfilename: file.name.clone(),
+            size: Some(file.size),
+            lfs: lfs.then(|| RemoteLfs {
+                sha256: file.sha256.clone(),
+                size: file.size,
+            }),
+        };
+        (file.name.clone(), remote)
+    }
+
+    #[test]
+    fn test_resumed_upload_only_resends_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let local = local_files(
+            dir.path(),
+            &[
+                ("model-00001-of-00002.safetensors", b"first half"),
+                ("model-00002-of-00002.safetensors", b"second half"),
+            ],
+        );
+
+        // the first upload died before committing the last file
+        let remote = HashMap::from([committed(&local[0], true)]);
+        let pending = files_to_upload(local.clone(), &remote);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].name, "model-00002-of-00002.safetensors");
+
+        let remote = HashMap::from([committed(&local[0], true), committed(&local[1], true)]);
+        assert!(files_to_upload(local, &remote).is_empty());
+    }
+
+    #[test]
+    fn test_changed_and_non_lfs_files_are_resent() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = local_files(dir.path(), &[("model.safetensors", b"old weights")]);
+        let remote = HashMap::from([committed(&old[0], true)]);
+        let new = local_files(dir.path(), &[("model.safetensors", b"new weights")]);
+        assert_eq!(files_to_upload(new, &remote).len(), 1);
+
+        let config = local_files(dir.path(), &[("config.json", b"{}")]);
+        let remote = HashMap::from([committed(&config[0], false)]);
+        assert_eq!(files_to_upload(config.clone(), &remote).len(), 1);
+        assert!(verify_upload(&config[0], &remote["config.json"]));
+    }
+
+    #[test]
+    fn test_hashes_match_sha256sum() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = &local_files(dir.path(), &[("hello", b"hello")])[0];
+        assert_eq!(file.size, 5);
+        assert_eq!(
+            file.sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    /// A stand-in for the hub API, answering each request with the first route whose
+    /// `"METHOD /path"` prefix matches, or a 404.
+    async fn mock_hub(routes: Vec<(&'static str, u16, &'static str)>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let request_line = request.lines().next().unwrap_or_default();
+                let (status, body) = routes
+                    .iter()
+                    .find(|(route, _, _)| request_line.starts_with(route))
+                    .map(|(_, status, body)| (*status, *body))
+                    .unwrap_or((404, ""));
+                let body = if request_line.starts_with("HEAD") {
+                    ""
+                } else {
+                    body
+                };
+                let response = format!(
+                    "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        endpoint
+    }
+
+    const GATED_MODEL: &str = r#"{"gated":"manual","siblings":[{"rfilename":"config.json"}]}"#;
+
+    #[tokio::test]
+    async fn test_gated_repo_without_access_is_reported() {
+        let endpoint = mock_hub(vec![
+            ("GET /api/models/org/gated/revision/main", 200, GATED_MODEL),
+            ("HEAD /org/gated/resolve/main/config.json", 403, ""),
+        ])
+        .await;
+        let result =
+            check_repo_access_at(&endpoint, "org/gated", RepoType::Model, None, Some("hf_x")).await;
+        assert!(
+            matches!(result, Err(RepoAccessError::Gated { ref repo, with_token: true }) if repo == "org/gated"),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gated_repo_with_access_passes() {
+        let endpoint = mock_hub(vec![
+            ("GET /api/models/org/gated/revision/main", 200, GATED_MODEL),
+            ("HEAD /org/gated/resolve/main/config.json", 200, ""),
+        ])
+        .await;
+        check_repo_access_at(&endpoint, "org/gated", RepoType::Model, None, Some("hf_x"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_private_repo_and_bad_token_are_reported() {
+        let endpoint = mock_hub(vec![
+            ("GET /api/datasets/org/private/revision/v1", 404, ""),
+            ("GET /api/models/org/model/revision/main", 401, ""),
+        ])
+        .await;
+        let private = check_repo_access_at(
+            &endpoint,
+            "org/private",
+            RepoType::Dataset,
+            Some("v1"),
+            Some("hf_x"),
+        )
+        .await;
+        assert!(
+            matches!(
+                private,
+                Err(RepoAccessError::NotFound {
+                    with_token: true,
+                    ..
+                })
+            ),
+            "{private:?}"
+        );
+
+        let bad_token =
+            check_repo_access_at(&endpoint, "org/model", RepoType::Model, None, Some("hf_x")).await;
+        assert!(
+            matches!(bad_token, Err(RepoAccessError::InvalidToken(_))),
+            "{bad_token:?}"
+        );
+        let no_token =
+            check_repo_access_at(&endpoint, "org/model", RepoType::Model, None, None).await;
+        assert!(
+            matches!(
+                no_token,
+                Err(RepoAccessError::NotFound {
+                    with_token: false,
+                    ..
+                })
+            ),
+            "{no_token:?}"
+        );
+    }
+}
@@ -5,6 +5,7 @@ mod file_extensions;
 pub mod http;
 mod hub;
 mod local;
+mod prefetch;
 mod remote;
 mod traits;
 mod weighted;
@@ -13,11 +14,13 @@ pub use data_provider::DataProvider;
 pub use dataset::{Dataset, Field, Row, Split};
 pub use dummy::DummyDataProvider;
 pub use hub::{
-    download_dataset_repo_async, download_dataset_repo_sync, download_model_repo_async,
-    download_model_repo_sync, upload_model_repo_async, UploadModelError,
+    check_repo_access, download_dataset_repo_async, download_dataset_repo_sync,
+    download_model_repo_async, download_model_repo_sync, upload_model_repo_async, RepoAccessError,
+    UploadModelError, UploadOptions,
 };
 pub use local::LocalDataProvider;
 pub use parquet::record::{ListAccessor, MapAccessor, RowAccessor};
+pub use prefetch::{PrefetchDataProvider, DEFAULT_PREFETCH_BATCHES};
 pub use remote::{DataProviderTcpClient, DataProviderTcpServer, DataServerTui};
 pub use traits::{LengthKnownDataProvider, TokenizedDataProvider};
 pub use weighted::{http::WeightedHttpProvidersConfig, WeightedDataProvider};
@@ -76,7 +76,7 @@ impl LocalDataProvider {
 
         let deterministic_rng = match shuffle {
             Shuffle::Seeded(random_seed) => Some(ChaCha8Rng::from_seed(random_seed)),
-            Shuffle::DontShuffle => None,
+            Shuffle::DontShuffle | Shuffle::Interleaved => None,
         };
         let seq_len_in_bytes = num_tokens_per_sequence * usize::from(token_size_in_bytes);
 
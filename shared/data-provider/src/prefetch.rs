@@ -0,0 +1,171 @@
+use crate::traits::TokenizedDataProvider;
+use anyhow::Result;
+use psyche_core::{BatchId, BoundedQueue};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex as StdMutex},
+};
+use tokio::{
+    sync::{Mutex, Notify},
+    task::JoinHandle,
+};
+use tracing::{trace, warn};
+
+pub const DEFAULT_PREFETCH_BATCHES: usize = 4;
+
+type Samples = Vec<Vec<i32>>;
+
+struct Prefetched<const K: usize> {
+    /// batches we expect to be asked for, in order, that haven't been asked for yet
+    upcoming: VecDeque<BatchId>,
+    ready: BoundedQueue<(BatchId, Samples), K>,
+}
+
+impl<const K: usize> Default for Prefetched<K> {
+    fn default() -> Self {
+        Self {
+            upcoming: VecDeque::new(),
+            ready: BoundedQueue::default(),
+        }
+    }
+}
+
+impl<const K: usize> Prefetched<K> {
+    fn next_to_fetch(&self) -> Option<BatchId> {
+        if self.ready.is_full() {
+            return None;
+        }
+        self.upcoming
+            .iter()
+            .take(K)
+            .find(|batch_id| !self.ready.iter().any(|(ready, _)| ready == *batch_id))
+            .copied()
+    }
+
+    fn take(&mut self, batch_id: BatchId) -> Option<Samples> {
+        self.ready
+            .take_first(|(ready, _)| *ready == batch_id)
+            .map(|(_, samples)| samples)
+    }
+}
+
+/// Fetches the next `K` of a predicted sequence of batches in the background,
+/// so asking for them in order doesn't wait on a slow provider like [`crate::DataProviderTcpClient`].
+///
+/// Batches that weren't predicted, or weren't fetched yet, are fetched from the inner provider as usual.
+/// Asking for a predicted batch skips the ones predicted before it, and drops anything prefetched for them.
+pub struct PrefetchDataProvider<T, const K: usize = DEFAULT_PREFETCH_BATCHES> {
+    inner: Arc<Mutex<T>>,
+    prefetched: Arc<StdMutex<Prefetched<K>>>,
+    wake: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl<T, const K: usize> PrefetchDataProvider<T, K>
+where
+    T: TokenizedDataProvider + Send + 'static,
+{
+    pub fn new(inner: T) -> Self {
+        let inner = Arc::new(Mutex::new(inner));
+        let prefetched: Arc<StdMutex<Prefetched<K>>> = Default::default();
+        let wake = Arc::new(Notify::new());
+        let task = tokio::spawn(prefetch_loop(
+            inner.clone(),
+            prefetched.clone(),
+            wake.clone(),
+        ));
+        Self {
+            inner,
+            prefetched,
+            wake,
+            task,
+        }
+    }
+
+    /// Replaces the predicted sequence of batches. Anything already prefetched that's still predicted is kept.
+    pub fn prefetch(&self, upcoming: impl IntoIterator<Item = BatchId>) {
+        let mut prefetched = self.prefetched.lock().unwrap();
+        prefetched.upcoming = upcoming.into_iter().collect();
+        let Prefetched { upcoming, ready } = &mut *prefetched;
+        ready.retain(|(batch_id, _)| upcoming.contains(batch_id));
+        drop(prefetched);
+        self.wake.notify_one();
+    }
+
+    /// How many batches are prefetched and ready to be returned instantly.
+    pub fn num_ready(&self) -> usize {
+        self.prefetched.lock().unwrap().ready.len()
+    }
+
+    fn take_prefetched(&self, batch_id: BatchId) -> Option<Samples> {
+        let mut prefetched = self.prefetched.lock().unwrap();
+        if let Some(position) = prefetched.upcoming.iter().position(|b| *b == batch_id) {
+            prefetched.upcoming.drain(..=position);
+            let Prefetched { upcoming, ready } = &mut *prefetched;
+            ready.retain(|(b, _)| *b == batch_id || upcoming.contains(b));
+        }
+        let samples = prefetched.take(batch_id);
+        drop(prefetched);
+        // the window moved along, or something was dropped, either way there's room to fetch more
+        self.wake.notify_one();
+        samples
+    }
+}
+
+async fn prefetch_loop<T: TokenizedDataProvider, const K: usize>(
+    inner: Arc<Mutex<T>>,
+    prefetched: Arc<StdMutex<Prefetched<K>>>,
+    wake: Arc<Notify>,
+) {
+    loop {
+        let next = prefetched.lock().unwrap().next_to_fetch();
+        let Some(batch_id) = next else {
+            wake.notified().await;
+            continue;
+        };
+
+        // hold the inner provider until the result is queued,
+        // so a request for this batch waiting on it finds it afterwards.
+        let mut provider = inner.lock().await;
+        let still_wanted = prefetched.lock().unwrap().next_to_fetch() == Some(batch_id);
+        if !still_wanted {
+            continue;
+        }
+        trace!("prefetching {batch_id}");
+        match provider.get_samples(batch_id).await {
+            Ok(samples) => prefetched.lock().unwrap().ready.push((batch_id, samples)),
+            Err(err) => {
+                // stop predicting it, so the request for it goes to the inner provider and sees the error
+                warn!("Failed to prefetch {batch_id}: {err:#}");
+                prefetched
+                    .lock()
+                    .unwrap()
+                    .upcoming
+                    .retain(|upcoming| *upcoming != batch_id);
+            }
+        }
+    }
+}
+
+impl<T, const K: usize> TokenizedDataProvider for PrefetchDataProvider<T, K>
+where
+    T: TokenizedDataProvider + Send + 'static,
+{
+    async fn get_samples(&mut self, data_ids: BatchId) -> Result<Vec<Vec<i32>>> {
+        if let Some(samples) = self.take_prefetched(data_ids) {
+            return Ok(samples);
+        }
+        let mut inner = self.inner.lock().await;
+        // it might have been prefetched while we waited for the inner provider
+        if let Some(samples) = self.prefetched.lock().unwrap().take(data_ids) {
+            return Ok(samples);
+        }
+        inner.get_samples(data_ids).await
+    }
+}
+
+impl<T, const K: usize> Drop for PrefetchDataProvider<T, K> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
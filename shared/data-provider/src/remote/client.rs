@@ -26,6 +26,26 @@ impl<T: AuthenticatableIdentity> DataProviderTcpClient<T> {
         })
     }
 
+    /// Connects to a [`crate::DataProviderTcpServer::listen_websocket`] at a `ws://` url instead of over TCP,
+    /// for clients that can only open WebSockets.
+    pub async fn connect_websocket(
+        url: String,
+        identity: T,
+        private_key: T::PrivateKey,
+    ) -> Result<Self> {
+        let tcp_client =
+            TcpClient::<T, ClientToServerMessage, ServerToClientMessage>::connect_websocket(
+                &url,
+                identity,
+                private_key,
+            )
+            .await?;
+        Ok(Self {
+            tcp_client,
+            address: url,
+        })
+    }
+
     async fn receive_training_data(&mut self, data_ids: BatchId) -> Result<Vec<Vec<i32>>> {
         self.tcp_client
             .send(ClientToServerMessage::RequestTrainingData { data_ids })
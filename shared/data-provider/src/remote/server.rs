@@ -4,7 +4,10 @@ use psyche_coordinator::Coordinator;
 use psyche_core::{BatchId, NodeIdentity};
 use psyche_network::{AuthenticatableIdentity, ClientNotification, TcpServer};
 use psyche_watcher::Backend;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
 use tracing::{debug, warn};
 
 use crate::traits::{LengthKnownDataProvider, TokenizedDataProvider};
@@ -50,6 +53,19 @@ where
         })
     }
 
+    pub fn local_addr(&self) -> &SocketAddr {
+        self.tcp_server.local_addr()
+    }
+
+    /// Also serves clients that connect over WebSocket on `port`, returning the address it listens on.
+    /// Requests and responses are framed the same way as over TCP.
+    pub async fn listen_websocket(&self, port: u16) -> Result<SocketAddr> {
+        Ok(self
+            .tcp_server
+            .listen_websocket(format!("0.0.0.0:{port}").parse()?)
+            .await?)
+    }
+
     pub async fn poll(&mut self) {
         tokio::select! {
             new_state = self.backend.wait_for_new_state() => {
@@ -69,8 +69,16 @@ impl<T: TokenizedDataProvider + LengthKnownDataProvider> WeightedDataProvider<T>
         let samples_per_epoch: usize = dataset_lengths.iter().sum();
         let num_epochs = (num_samples as f64 / samples_per_epoch as f64).ceil() as usize;
 
+        let provider_order = match shuffle_kind {
+            Shuffle::Interleaved => {
+                interleaved_order(samples_per_epoch, &weights, &dataset_lengths)
+            }
+            Shuffle::DontShuffle | Shuffle::Seeded(_) => {
+                error_diffusion_order(samples_per_epoch, &weights, &dataset_lengths)
+            }
+        };
         let (mut dataset_index, mut dataset_sample_index) =
-            build_weighted_index(samples_per_epoch, &weights, &dataset_lengths);
+            build_weighted_index(&provider_order, &dataset_lengths);
 
         if let Shuffle::Seeded(random_seed) = shuffle_kind {
             let mut rng = ChaCha8Rng::from_seed(random_seed);
@@ -176,18 +184,11 @@ fn normalize(weights: &[f64]) -> Vec<f64> {
     weights.iter().map(|w| w / sum).collect()
 }
 
-fn build_weighted_index(
-    n_samples: usize,
-    weights: &[f64],
-    dataset_sizes: &[usize],
-) -> (Vec<usize>, Vec<u64>) {
+/// Picks a provider for each of `n_samples` samples, always the one furthest behind its weighted share.
+fn error_diffusion_order(n_samples: usize, weights: &[f64], dataset_sizes: &[usize]) -> Vec<usize> {
     let num_providers = weights.len();
-    let mut dataset_index = Vec::with_capacity(n_samples);
-    let mut dataset_sample_index = Vec::with_capacity(n_samples);
-
+    let mut provider_order = Vec::with_capacity(n_samples);
     let mut total_samples_drawn = vec![0u64; num_providers];
-    let mut next_unique_index = vec![0u64; num_providers];
-    let mut is_exhausted = vec![false; num_providers];
 
     for sample_idx in 0..n_samples {
         let sample_idx_float = (sample_idx as f64).max(1.0);
@@ -206,6 +207,69 @@ fn build_weighted_index(
             }
         }
 
+        provider_order.push(chosen_provider_idx);
+        total_samples_drawn[chosen_provider_idx] += 1;
+    }
+
+    provider_order
+}
+
+/// Picks a provider for each of `n_samples` samples, spreading each provider's samples as evenly as its weight allows.
+/// The heaviest provider's samples are spaced out evenly, and the others are interleaved the same way in the slots between them.
+fn interleaved_order(n_samples: usize, weights: &[f64], dataset_sizes: &[usize]) -> Vec<usize> {
+    let providers: Vec<usize> = (0..weights.len())
+        .filter(|&i| dataset_sizes[i] != 0)
+        .collect();
+    interleave(n_samples, weights, &providers)
+}
+
+fn interleave(n_samples: usize, weights: &[f64], providers: &[usize]) -> Vec<usize> {
+    let Some(&heaviest) = providers
+        .iter()
+        .max_by(|&&a, &&b| weights[a].total_cmp(&weights[b]).then(b.cmp(&a)))
+    else {
+        return vec![0; n_samples];
+    };
+    let rest: Vec<usize> = providers
+        .iter()
+        .copied()
+        .filter(|&i| i != heaviest)
+        .collect();
+    let rest_weight: f64 = rest.iter().map(|&i| weights[i]).sum();
+    if rest.is_empty() || rest_weight <= 0.0 {
+        return vec![heaviest; n_samples];
+    }
+
+    // the heaviest provider's share of these samples, spread out like a line on a pixel grid
+    let share = weights[heaviest] / (weights[heaviest] + rest_weight);
+    let is_heaviest: Vec<bool> = (0..n_samples)
+        .map(|i| ((i + 1) as f64 * share + 0.5).floor() > (i as f64 * share + 0.5).floor())
+        .collect();
+    let mut others =
+        interleave(is_heaviest.iter().filter(|&&h| !h).count(), weights, &rest).into_iter();
+    is_heaviest
+        .into_iter()
+        .map(|h| match h {
+            true => heaviest,
+            false => others.next().unwrap(),
+        })
+        .collect()
+}
+
+/// Assigns each pick of a provider its next sample, going through the provider's samples once before repeating any.
+fn build_weighted_index(
+    provider_order: &[usize],
+    dataset_sizes: &[usize],
+) -> (Vec<usize>, Vec<u64>) {
+    let num_providers = dataset_sizes.len();
+    let mut dataset_index = Vec::with_capacity(provider_order.len());
+    let mut dataset_sample_index = Vec::with_capacity(provider_order.len());
+
+    let mut total_samples_drawn = vec![0u64; num_providers];
+    let mut next_unique_index = vec![0u64; num_providers];
+    let mut is_exhausted = vec![false; num_providers];
+
+    for &chosen_provider_idx in provider_order {
         // determine the sample index
         let provider_size = dataset_sizes[chosen_provider_idx] as u64;
         let sample_to_yield: u64;
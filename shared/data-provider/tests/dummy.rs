@@ -0,0 +1,69 @@
+use anyhow::Result;
+use psyche_core::{BatchId, ClosedInterval, TokenSize};
+use psyche_data_provider::{DummyDataProvider, TokenizedDataProvider};
+use test_log::test;
+
+const SEED: [u8; 32] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
+    27, 28, 29, 30, 31, 32,
+];
+
+#[test(tokio::test)]
+async fn test_dummy_data_provider_same_seed_same_samples() -> Result<()> {
+    let mut provider1 = DummyDataProvider::new(TokenSize::TwoBytes, 16, 100, SEED);
+    let mut provider2 = DummyDataProvider::new(TokenSize::TwoBytes, 16, 100, SEED);
+
+    let batch_id = BatchId(ClosedInterval { start: 10, end: 19 });
+    let samples1 = provider1.get_samples(batch_id).await?;
+    let samples2 = provider2.get_samples(batch_id).await?;
+
+    assert_eq!(samples1.len(), 10);
+    assert_eq!(samples1, samples2);
+    for sample in &samples1 {
+        assert_eq!(sample.len(), 17);
+    }
+    // not just a constant sequence
+    assert_ne!(samples1[0], samples1[1]);
+
+    // asking again, or as part of a different batch, gives the same sequence
+    assert_eq!(provider1.get_samples(batch_id).await?, samples1);
+    let overlapping = provider2
+        .get_samples(BatchId(ClosedInterval { start: 15, end: 24 }))
+        .await?;
+    assert_eq!(overlapping[..5], samples1[5..]);
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_dummy_data_provider_different_seed_different_samples() -> Result<()> {
+    let mut provider1 = DummyDataProvider::new(TokenSize::FourBytes, 16, 100, SEED);
+    let mut provider2 = DummyDataProvider::new(TokenSize::FourBytes, 16, 100, [0; 32]);
+
+    let batch_id = BatchId(ClosedInterval { start: 0, end: 3 });
+    let samples1 = provider1.get_samples(batch_id).await?;
+    let samples2 = provider2.get_samples(batch_id).await?;
+
+    assert_ne!(samples1, samples2);
+    assert!(samples1.iter().flatten().all(|token| *token >= 0));
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_dummy_data_provider_respects_vocab_size() -> Result<()> {
+    let mut unlimited = DummyDataProvider::new(TokenSize::FourBytes, 64, 100, SEED);
+    let mut limited = DummyDataProvider::new(TokenSize::FourBytes, 64, 100, SEED);
+    limited.set_vocab_size(1000);
+
+    let batch_id = BatchId(ClosedInterval { start: 0, end: 7 });
+    let unlimited = unlimited.get_samples(batch_id).await?;
+    let limited = limited.get_samples(batch_id).await?;
+
+    assert!(unlimited.iter().flatten().any(|token| *token >= 1000));
+    for (unlimited, limited) in unlimited.iter().flatten().zip(limited.iter().flatten()) {
+        assert_eq!(*limited, unlimited % 1000);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use psyche_core::{BatchId, ClosedInterval};
+use psyche_data_provider::{PrefetchDataProvider, TokenizedDataProvider};
+use std::time::Duration;
+use test_log::test;
+use tokio::time::Instant;
+
+const DELAY: Duration = Duration::from_millis(50);
+
+/// Takes `DELAY` to return each batch, like a provider on the other end of a network.
+struct SlowDataProvider;
+
+impl TokenizedDataProvider for SlowDataProvider {
+    async fn get_samples(&mut self, data_ids: BatchId) -> Result<Vec<Vec<i32>>> {
+        tokio::time::sleep(DELAY).await;
+        Ok(data_ids.iter().map(|id| vec![id as i32; 4]).collect())
+    }
+}
+
+fn batch(index: u64) -> BatchId {
+    BatchId(ClosedInterval::new(index * 2, index * 2 + 1))
+}
+
+fn expected(batch_id: BatchId) -> Vec<Vec<i32>> {
+    batch_id.iter().map(|id| vec![id as i32; 4]).collect()
+}
+
+// with the clock paused, sleeps finish as soon as every task is waiting, so timings are exact.
+#[test(tokio::test(start_paused = true))]
+async fn test_prefetch_hides_inner_latency() -> Result<()> {
+    let mut provider = PrefetchDataProvider::<_, 4>::new(SlowDataProvider);
+    provider.prefetch((0..8).map(batch));
+
+    let start = Instant::now();
+    for index in 0..8 {
+        // simulate training on the batch, which gives the prefetcher time to get ahead
+        tokio::time::sleep(DELAY).await;
+        let batch_id = batch(index);
+        assert_eq!(provider.get_samples(batch_id).await?, expected(batch_id));
+    }
+    let prefetched = start.elapsed();
+
+    let mut unprefetched_provider = SlowDataProvider;
+    let start = Instant::now();
+    for index in 0..8 {
+        tokio::time::sleep(DELAY).await;
+        let batch_id = batch(index);
+        assert_eq!(
+            unprefetched_provider.get_samples(batch_id).await?,
+            expected(batch_id)
+        );
+    }
+    let unprefetched = start.elapsed();
+
+    // without prefetching every batch costs two delays, with it fetching overlaps training.
+    assert!(
+        unprefetched >= DELAY * 16,
+        "unprefetched took {unprefetched:?}"
+    );
+    assert!(prefetched <= DELAY * 9, "prefetched took {prefetched:?}");
+    Ok(())
+}
+
+// with the clock paused, sleeps finish as soon as every task is waiting, so timings are exact.
+#[test(tokio::test(start_paused = true))]
+async fn test_out_of_order_requests_drop_stale_prefetches() -> Result<()> {
+    let mut provider = PrefetchDataProvider::<_, 4>::new(SlowDataProvider);
+    provider.prefetch((0..8).map(batch));
+    tokio::time::sleep(DELAY * 6).await;
+    assert_eq!(provider.num_ready(), 4);
+
+    // skipping ahead drops everything predicted before it
+    assert_eq!(provider.get_samples(batch(2)).await?, expected(batch(2)));
+    assert_eq!(provider.num_ready(), 1);
+
+    // a batch we already skipped past, or never predicted, still comes from the inner provider
+    assert_eq!(provider.get_samples(batch(0)).await?, expected(batch(0)));
+    assert_eq!(
+        provider.get_samples(batch(100)).await?,
+        expected(batch(100))
+    );
+
+    // replacing the prediction drops what's no longer predicted
+    provider.prefetch([batch(50), batch(51)]);
+    assert_eq!(provider.num_ready(), 0);
+    assert_eq!(provider.get_samples(batch(51)).await?, expected(batch(51)));
+    Ok(())
+}
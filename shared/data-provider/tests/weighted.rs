@@ -184,8 +184,8 @@ async fn test_weighted_data_provider_consistency() -> Result<()> {
 
 #[test(tokio::test)]
 async fn test_weighted_data_provider_with_dummy_provider() -> Result<()> {
-    let dummy1 = DummyDataProvider::new(TokenSize::TwoBytes, 10, 50); // 10 tokens per sequence
-    let dummy2 = DummyDataProvider::new(TokenSize::TwoBytes, 10, 50);
+    let dummy1 = DummyDataProvider::new(TokenSize::TwoBytes, 10, 50, [1; 32]); // 10 tokens per sequence
+    let dummy2 = DummyDataProvider::new(TokenSize::TwoBytes, 10, 50, [2; 32]);
 
     let mut weighted_provider = WeightedDataProvider::new(
         vec![(dummy1, 0.5), (dummy2, 0.5)],
@@ -391,3 +391,112 @@ async fn test_weighted_data_provider_exhausts_small_dataset_before_repeat() -> R
 
     Ok(())
 }
+
+/// The longest run of consecutive samples from each provider.
+fn longest_runs(provider_ids: &[i32], num_providers: usize) -> Vec<usize> {
+    let mut longest = vec![0; num_providers];
+    let mut run = 0;
+    for (i, &id) in provider_ids.iter().enumerate() {
+        run = match i > 0 && provider_ids[i - 1] == id {
+            true => run + 1,
+            false => 1,
+        };
+        longest[id as usize] = longest[id as usize].max(run);
+    }
+    longest
+}
+
+#[test(tokio::test)]
+async fn test_weighted_data_provider_interleaved_spreads_providers() -> Result<()> {
+    for weights in [
+        vec![0.25, 0.25, 0.5],
+        vec![0.5, 0.5],
+        vec![0.1, 0.2, 0.3, 0.4],
+        vec![0.7, 0.2, 0.1],
+        vec![0.45, 0.45, 0.1],
+        vec![0.15, 0.15, 0.15, 0.55],
+        vec![0.9, 0.1],
+    ] {
+        let providers: Vec<_> = weights
+            .iter()
+            .enumerate()
+            .map(|(id, &weight)| (MockDataProvider::new(id, 400, vec![0]), weight))
+            .collect();
+        let mut weighted_provider = WeightedDataProvider::new(providers, Shuffle::Interleaved);
+        let num_samples = weighted_provider.num_sequences();
+        let samples = weighted_provider
+            .get_samples(BatchId(ClosedInterval::new(0, num_samples as u64 - 1)))
+            .await?;
+        let provider_ids: Vec<i32> = samples.iter().map(|sample| sample[0] / 1000).collect();
+
+        // a provider with weight w can't do better than runs of w / (1 - w) between the others' samples
+        let longest = longest_runs(&provider_ids, weights.len());
+        for (provider, (&weight, &run)) in weights.iter().zip(&longest).enumerate() {
+            let best_possible = (weight / (1.0 - weight)).ceil() as usize;
+            assert!(
+                run <= best_possible + 1,
+                "provider {provider} with weight {weight} had a run of {run} in {weights:?}"
+            );
+            let count = provider_ids
+                .iter()
+                .filter(|&&id| id == provider as i32)
+                .count();
+            assert!((count as f64 - weight * num_samples as f64).abs() <= 1.0);
+        }
+        if weights == [0.25, 0.25, 0.5] {
+            assert_eq!(longest, [1, 1, 1]);
+        }
+
+        // and it doesn't depend on any randomness
+        let providers: Vec<_> = weights
+            .iter()
+            .enumerate()
+            .map(|(id, &weight)| (MockDataProvider::new(id, 400, vec![0]), weight))
+            .collect();
+        let mut again = WeightedDataProvider::new(providers, Shuffle::Interleaved);
+        assert_eq!(
+            again
+                .get_samples(BatchId(ClosedInterval::new(0, num_samples as u64 - 1)))
+                .await?,
+            samples
+        );
+    }
+
+    Ok(())
+}
+
+#[test(tokio::test)]
+async fn test_weighted_data_provider_interleaved_exhausts_small_dataset_before_repeat() -> Result<()>
+{
+    let provider1 = MockDataProvider::new(1, 5, vec![0]);
+    let provider2 = MockDataProvider::new(2, 20, vec![0]);
+    let mut weighted_provider = WeightedDataProvider::new(
+        vec![(provider1, 0.5), (provider2, 0.5)],
+        Shuffle::Interleaved,
+    );
+    let samples = weighted_provider
+        .get_samples(BatchId(ClosedInterval::new(0, 24)))
+        .await?;
+
+    let provider1_ids: Vec<i32> = samples
+        .iter()
+        .filter(|sample| sample[0] / 1000 == 1)
+        .map(|sample| sample[0] % 1000)
+        .collect();
+    let provider2_ids: Vec<i32> = samples
+        .iter()
+        .filter(|sample| sample[0] / 1000 == 2)
+        .map(|sample| sample[0] % 1000)
+        .collect();
+
+    // provider 1 goes through all 5 of its samples before wrapping around, provider 2 never repeats
+    assert_eq!(provider1_ids[..5], [0, 1, 2, 3, 4]);
+    assert_eq!(provider1_ids[5..10], [0, 1, 2, 3, 4]);
+    assert_eq!(
+        provider2_ids,
+        (0..provider2_ids.len() as i32).collect::<Vec<_>>()
+    );
+    assert_eq!(provider1_ids.len() + provider2_ids.len(), 25);
+
+    Ok(())
+}
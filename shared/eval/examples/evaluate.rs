@@ -2,7 +2,9 @@ use anyhow::Result;
 use clap::Parser;
 use psyche_data_provider::download_model_repo_sync;
 use psyche_eval::{tasktype_from_name, EvalTaskOptions, Task, ALL_TASK_NAMES};
-use psyche_modeling::{auto_model_for_causal_lm_from_pretrained, auto_tokenizer};
+use psyche_modeling::{
+    auto_model_for_causal_lm_from_pretrained, auto_tokenizer, cuda_or_cpu_fallback, Sampling,
+};
 use tch::{Device, Kind};
 
 #[derive(Parser, Debug, Clone)]
@@ -19,25 +21,50 @@ struct Args {
     #[arg(long, default_value_t = 42)]
     seed: u64,
 
+    /// Sample the answers of generation tasks at this temperature instead of greedily. Seeded with `--seed`.
+    #[arg(long)]
+    temperature: Option<f64>,
+
+    /// With `--temperature`, sample from the smallest set of tokens whose probability exceeds this.
+    #[arg(long, requires = "temperature")]
+    top_p: Option<f64>,
+
     #[arg(long, default_value_t = false)]
     quiet: bool,
+
+    /// Run on the CPU if CUDA isn't available. Very slow, only meant for small evals.
+    #[arg(long, default_value_t = false)]
+    cpu_fallback: bool,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let sampling = match (args.temperature, args.top_p) {
+        (None, _) => Sampling::ArgMax,
+        (Some(temperature), None) => Sampling::All { temperature },
+        (Some(temperature), Some(p)) => Sampling::TopP { p, temperature },
+    };
     let tasks: Result<Vec<Task>> = args
         .tasks
         .split(",")
-        .map(|x| tasktype_from_name(x).map(|y| Task::new(y, args.num_fewshot, args.seed)))
+        .map(|x| {
+            tasktype_from_name(x)
+                .map(|y| Task::new(y, args.num_fewshot, args.seed).with_sampling(sampling.clone()))
+        })
         .collect();
     let tasks = tasks?;
+    let device = cuda_or_cpu_fallback(0, 1, args.cpu_fallback)?;
+    if device == Device::Cpu {
+        println!("CUDA is not available, running on the CPU. This will be very slow.");
+    }
     let repo = download_model_repo_sync(&args.model, None, None, None, true)?;
     let tokenizer = auto_tokenizer(&repo)?;
     let mut model = auto_model_for_causal_lm_from_pretrained(
         repo,
         Some(Kind::BFloat16),
         None,
-        Some(Device::cuda_if_available()),
+        Some(device),
+        None,
         None,
         None,
     )?;
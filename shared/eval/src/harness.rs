@@ -1,26 +1,32 @@
-use crate::traits::{Document, LogLikelihoodTask};
+use crate::traits::{
+    Aggregation, Document, GenerateUntilTask, GenerationDocument, LogLikelihoodTask,
+};
 use indicatif::{ProgressBar, ProgressStyle};
 use psyche_core::RunningAverage;
-use psyche_modeling::CausalLM;
+use psyche_modeling::{CausalLM, LogitsProcessor, Sampling, StopCondition};
 use rand::{seq::SliceRandom, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 use std::{collections::HashMap, fmt::Display, sync::Arc};
 use tch::{Kind, Tensor};
 use tokenizers::Tokenizer;
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, warn};
 
 pub enum TaskType {
     LogLikelihood(Box<dyn LogLikelihoodTask>),
+    GenerateUntil(Box<dyn GenerateUntilTask>),
 }
 
 pub struct Task {
     task_type: TaskType,
     num_fewshot: usize,
     rand: ChaCha8Rng,
+    sampling: Sampling,
+    sampling_seed: u64,
 }
 
 impl Task {
+    /// `random_seed` picks the documents and fewshot examples, and seeds the sampler of generation tasks.
     pub fn new(task_type: TaskType, num_fewshot: usize, random_seed: u64) -> Self {
         let mut seed = [0u8; 32];
         seed[24..32].copy_from_slice(&random_seed.to_be_bytes());
@@ -28,14 +34,23 @@ impl Task {
             task_type,
             num_fewshot,
             rand: ChaCha8Rng::from_seed(seed),
+            sampling: Sampling::ArgMax,
+            sampling_seed: random_seed,
         }
     }
+
+    /// How generation tasks sample their answers. Greedy by default.
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
 }
 
 impl Display for Task {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.task_type {
             TaskType::LogLikelihood(x) => write!(f, "{x}"),
+            TaskType::GenerateUntil(x) => write!(f, "{x}"),
         }
     }
 }
@@ -45,6 +60,16 @@ enum PreparedTaskType {
     LogLikelihood {
         docs: Vec<TokenizedLLHDocument>,
         tokenized_fewshot: Vec<i64>,
+        aggregation: Aggregation,
+    },
+    GenerateUntil {
+        docs: Vec<TokenizedGenerationDocument>,
+        tokenized_fewshot: Vec<i64>,
+        /// the stop condition's EOS tokens are the model's, filled in when running
+        generation: GenerationOptions,
+        stop_sequences: Vec<String>,
+        tokenizer: Tokenizer,
+        extract_answer: fn(&str) -> Option<String>,
     },
 }
 
@@ -66,6 +91,7 @@ struct TokenizedLLHDocument {
     text: Vec<i64>,
     choices: Vec<Vec<i64>>,
     answer: usize,
+    category: Option<String>,
 }
 
 impl TokenizedLLHDocument {
@@ -95,10 +121,27 @@ impl TokenizedLLHDocument {
             text,
             choices,
             answer: doc.answer,
+            category: doc.category,
         }
     }
 }
 
+#[derive(Debug)]
+struct TokenizedGenerationDocument {
+    text: Vec<i64>,
+    answer: String,
+}
+
+fn tokenize(tokenizer: &Tokenizer, text: &str) -> Vec<i64> {
+    tokenizer
+        .encode(text, false)
+        .unwrap()
+        .get_ids()
+        .iter()
+        .map(|x| *x as i64)
+        .collect()
+}
+
 impl Task {
     pub fn prepare(
         mut self,
@@ -110,6 +153,7 @@ impl Task {
         info!("Preparing {name}");
         match self.task_type {
             TaskType::LogLikelihood(llh) => {
+                let aggregation = llh.aggregation();
                 let mut docs = llh.get_documents();
                 docs.shuffle(&mut self.rand);
                 if let Some(limit) = limit {
@@ -151,6 +195,65 @@ impl Task {
                     prepared_task_type: PreparedTaskType::LogLikelihood {
                         docs,
                         tokenized_fewshot,
+                        aggregation,
+                    },
+                }
+            }
+            TaskType::GenerateUntil(task) => {
+                let mut docs = task.get_documents();
+                docs.shuffle(&mut self.rand);
+                if let Some(limit) = limit {
+                    docs.truncate(limit);
+                }
+                let fewshot = if self.num_fewshot > 0 {
+                    let mut fewshot_docs = task.get_fewshot_documents();
+                    fewshot_docs.shuffle(&mut self.rand);
+                    fewshot_docs
+                        .into_iter()
+                        .take(self.num_fewshot)
+                        .map(|x| format!("{}{}", x.text, x.target))
+                        .collect::<Vec<_>>()
+                        .join("\n\n")
+                        + "\n\n"
+                } else {
+                    String::new()
+                };
+                let mut tokenized_fewshot = match bos_token_id {
+                    Some(bos_token_id) => vec![bos_token_id],
+                    None => Vec::new(),
+                };
+                tokenized_fewshot.extend(tokenize(tokenizer, &fewshot));
+                let docs = docs
+                    .into_iter()
+                    .map(
+                        |GenerationDocument { text, answer, .. }| TokenizedGenerationDocument {
+                            text: tokenize(tokenizer, &text),
+                            answer,
+                        },
+                    )
+                    .collect::<Vec<_>>();
+                let stop_sequences = task.stop_sequences();
+                PreparedTask {
+                    name,
+                    num: docs.len(),
+                    prepared_task_type: PreparedTaskType::GenerateUntil {
+                        docs,
+                        tokenized_fewshot,
+                        generation: GenerationOptions {
+                            sampling: self.sampling,
+                            sampling_seed: self.sampling_seed,
+                            max_new_tokens: task.max_new_tokens(),
+                            stop: StopCondition {
+                                eos_token_ids: None,
+                                stop_sequences: stop_sequences
+                                    .iter()
+                                    .map(|sequence| tokenize(tokenizer, sequence))
+                                    .collect(),
+                            },
+                        },
+                        stop_sequences,
+                        tokenizer: tokenizer.clone(),
+                        extract_answer: task.answer_extractor(),
                     },
                 }
             }
@@ -167,6 +270,56 @@ pub struct EvalTaskOptions<'a> {
     pub loop_if_empty: bool,
 }
 
+/// Name of the score of `metric` over only the documents in `category`.
+fn category_metric(metric: &str, category: &str) -> String {
+    format!("{metric}/{category}")
+}
+
+/// The mean of the per-category scores of `metric`, or `None` if there aren't any.
+fn macro_average(scores: &HashMap<String, f64>, metric: &str) -> Option<f64> {
+    let prefix = category_metric(metric, "");
+    let per_category = scores
+        .iter()
+        .filter(|(key, _)| key.starts_with(&prefix))
+        .map(|(_, score)| *score)
+        .collect::<Vec<_>>();
+    (!per_category.is_empty()).then(|| per_category.iter().sum::<f64>() / per_category.len() as f64)
+}
+
+/// How a generation-based eval samples its answers.
+#[derive(Clone, Debug)]
+pub struct GenerationOptions {
+    pub sampling: Sampling,
+    /// Seeds the sampler, so that an eval with stochastic sampling gives the same generations every run.
+    pub sampling_seed: u64,
+    pub max_new_tokens: usize,
+    pub stop: StopCondition,
+}
+
+/// Generates a continuation of `prompt`, stopping after `max_new_tokens`
+/// or at the first EOS token or stop sequence (which is included).
+pub fn generate(
+    model: &mut dyn CausalLM,
+    prompt: &[i64],
+    options: &GenerationOptions,
+) -> anyhow::Result<Vec<i64>> {
+    let mut logits_processor =
+        LogitsProcessor::from_sampling(options.sampling_seed, options.sampling.clone());
+    let mut tokens = prompt.to_vec();
+    let mut generated = Vec::with_capacity(options.max_new_tokens);
+    while generated.len() < options.max_new_tokens {
+        let ids = Tensor::from_slice(&tokens).to(model.device()).unsqueeze(0);
+        let (logits, _) = model.forward(&ids, None, Some(1));
+        let next_token = logits_processor.sample(&logits.squeeze())? as i64;
+        tokens.push(next_token);
+        generated.push(next_token);
+        if options.stop.should_stop(&generated) {
+            break;
+        }
+    }
+    Ok(generated)
+}
+
 impl PreparedTask {
     pub fn run(&self, options: EvalTaskOptions, progress_bar: bool) -> PreparedTaskResult {
         let pbar = match progress_bar {
@@ -186,7 +339,122 @@ impl PreparedTask {
             PreparedTaskType::LogLikelihood {
                 docs,
                 tokenized_fewshot,
-            } => Self::run_log_likelihood(options, docs, tokenized_fewshot, pbar),
+                aggregation,
+            } => Self::run_log_likelihood(options, docs, tokenized_fewshot, *aggregation, pbar),
+            PreparedTaskType::GenerateUntil {
+                docs,
+                tokenized_fewshot,
+                generation,
+                stop_sequences,
+                tokenizer,
+                extract_answer,
+            } => Self::run_generate_until(
+                options,
+                docs,
+                tokenized_fewshot,
+                generation,
+                stop_sequences,
+                tokenizer,
+                *extract_answer,
+                pbar,
+            ),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_generate_until(
+        options: EvalTaskOptions,
+        docs: &[TokenizedGenerationDocument],
+        tokenized_fewshot: &[i64],
+        generation: &GenerationOptions,
+        stop_sequences: &[String],
+        tokenizer: &Tokenizer,
+        extract_answer: fn(&str) -> Option<String>,
+        pbar: Option<ProgressBar>,
+    ) -> PreparedTaskResult {
+        let results = options.live_results.unwrap_or_default();
+        let (mut skip, step_by) = options.skip_and_step_by.unwrap_or((0, 1));
+        results.add_entry_if_needed("exact_match", docs.len());
+        let mut next_index = skip;
+        let fast_forward = (skip / docs.len()) * docs.len();
+        skip -= fast_forward;
+        let mut cancelled = false;
+        let mut generation = generation.clone();
+        generation.stop.eos_token_ids = options.model.eos_token_ids();
+
+        for (num_iterations, (doc_index, doc)) in docs
+            .iter()
+            .cycle()
+            .enumerate()
+            .skip(skip)
+            .step_by(step_by)
+            .enumerate()
+        {
+            next_index = doc_index;
+            if let Some(cancel) = options.cancel.as_ref() {
+                if cancel.is_cancelled() {
+                    cancelled = true;
+                    break;
+                }
+            }
+            if !options.loop_if_empty && doc_index >= docs.len() {
+                break;
+            }
+            if let Some(limit) = options.limit {
+                if num_iterations >= limit {
+                    break;
+                }
+            }
+            let mut context = tokenized_fewshot.to_vec();
+            context.extend_from_slice(&doc.text);
+            // seeded per document, so a document's generation doesn't depend on which client runs it, or when
+            let doc_generation = GenerationOptions {
+                sampling_seed: generation
+                    .sampling_seed
+                    .wrapping_add((doc_index % docs.len()) as u64),
+                ..generation.clone()
+            };
+            let score = match generate(options.model, &context, &doc_generation) {
+                Ok(generated) => {
+                    let generated = generated.iter().map(|x| *x as u32).collect::<Vec<_>>();
+                    let mut text = tokenizer.decode(&generated, true).unwrap_or_default();
+                    // stop sequences can tokenize differently in context, so cut at them in the text too
+                    if let Some(end) = stop_sequences
+                        .iter()
+                        .filter_map(|sequence| text.find(sequence.as_str()))
+                        .min()
+                    {
+                        text.truncate(end);
+                    }
+                    match extract_answer(&text).as_deref() == Some(doc.answer.as_str()) {
+                        true => 1.,
+                        false => 0.,
+                    }
+                }
+                Err(err) => {
+                    warn!("Failed to generate an answer: {err:#}");
+                    0.
+                }
+            };
+            results.push("exact_match", score);
+
+            if let Some(pbar) = &pbar {
+                pbar.set_message(format!(
+                    "exact_match: {:.3}",
+                    results.sample("exact_match").unwrap()
+                ));
+                pbar.inc(1);
+            };
+        }
+        let scores: HashMap<String, f64> = results
+            .get_all_averages()
+            .into_iter()
+            .map(|(key, value)| (key, value.unwrap_or_default()))
+            .collect();
+        PreparedTaskResult {
+            scores,
+            next_index: next_index + fast_forward,
+            cancelled,
         }
     }
 
@@ -194,6 +462,7 @@ impl PreparedTask {
         options: EvalTaskOptions,
         docs: &[TokenizedLLHDocument],
         tokenized_fewshot: &[i64],
+        aggregation: Aggregation,
         pbar: Option<ProgressBar>,
     ) -> PreparedTaskResult {
         let results = options.live_results.unwrap_or_default();
@@ -292,20 +561,18 @@ impl PreparedTask {
             .try_into()
             .unwrap();
 
-            results.push(
-                "acc",
-                match selected as usize == doc.answer {
+            for (metric, selected) in [("acc", selected), ("acc_norm", selected_norm)] {
+                let score = match selected as usize == doc.answer {
                     true => 1.,
                     false => 0.,
-                },
-            );
-            results.push(
-                "acc_norm",
-                match selected_norm as usize == doc.answer {
-                    true => 1.,
-                    false => 0.,
-                },
-            );
+                };
+                results.push(metric, score);
+                if let Some(category) = &doc.category {
+                    let category_metric = category_metric(metric, category);
+                    results.add_entry_if_needed(&category_metric, docs.len());
+                    results.push(&category_metric, score);
+                }
+            }
 
             if let Some(pbar) = &pbar {
                 pbar.set_message(format!(
@@ -315,12 +582,20 @@ impl PreparedTask {
                 pbar.inc(1);
             };
         }
+        let mut scores: HashMap<String, f64> = results
+            .get_all_averages()
+            .into_iter()
+            .map(|(key, value)| (key, value.unwrap_or_default()))
+            .collect();
+        if aggregation == Aggregation::Macro {
+            for metric in ["acc", "acc_norm"] {
+                if let Some(score) = macro_average(&scores, metric) {
+                    scores.insert(metric.to_string(), score);
+                }
+            }
+        }
         PreparedTaskResult {
-            scores: results
-                .get_all_averages()
-                .into_iter()
-                .map(|(key, value)| (key, value.unwrap_or_default()))
-                .collect(),
+            scores,
             next_index: next_index + fast_forward,
             cancelled,
         }
@@ -332,10 +607,278 @@ impl PreparedTask {
 
     pub fn main_metric_name(&self) -> &str {
         match &self.prepared_task_type {
-            PreparedTaskType::LogLikelihood {
-                docs: _,
-                tokenized_fewshot: _,
-            } => "acc_norm",
+            PreparedTaskType::LogLikelihood { .. } => "acc_norm",
+            PreparedTaskType::GenerateUntil { .. } => "exact_match",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_modeling::{tiny_llama_config, tiny_model_for_causal_lm, AutoConfig, EosToks};
+    use tch::Device;
+    use tokenizers::{models::wordlevel::WordLevel, ModelWrapper};
+
+    fn tiny_model() -> Box<dyn CausalLM> {
+        tiny_model_for_causal_lm(
+            AutoConfig::Llama(tiny_llama_config()),
+            42,
+            Some(Device::Cpu),
+        )
+        .unwrap()
+    }
+
+    fn generations(sampling_seed: u64) -> Vec<Vec<i64>> {
+        let mut model = tiny_model();
+        let options = GenerationOptions {
+            sampling: Sampling::TopP {
+                p: 0.9,
+                temperature: 1.0,
+            },
+            sampling_seed,
+            max_new_tokens: 16,
+            stop: StopCondition::default(),
+        };
+        let _no_grad = tch::no_grad_guard();
+        [[1i64, 5, 9].as_slice(), &[2, 7, 11, 13]]
+            .into_iter()
+            .map(|prompt| generate(model.as_mut(), prompt, &options).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sampling_seed_makes_generations_reproducible() {
+        let a = generations(1234);
+        assert!(a.iter().all(|x| x.len() == 16));
+        assert_eq!(a, generations(1234));
+        assert_ne!(a, generations(4321));
+    }
+
+    #[test]
+    fn test_generation_stops_at_eos_and_stop_sequences() {
+        let mut model = tiny_model();
+        let _no_grad = tch::no_grad_guard();
+        let prompt = [1i64, 5, 9];
+        let mut options = GenerationOptions {
+            sampling: Sampling::ArgMax,
+            sampling_seed: 0,
+            max_new_tokens: 16,
+            stop: StopCondition::default(),
+        };
+        let unstopped = generate(model.as_mut(), &prompt, &options).unwrap();
+        assert_eq!(unstopped.len(), 16);
+        // greedy generation is deterministic, so stopping just cuts it short.
+        let stops_after = |len: usize| -> Vec<i64> { unstopped[..len].to_vec() };
+
+        let eos = unstopped[5];
+        let first_eos = unstopped.iter().position(|t| *t == eos).unwrap();
+        for eos_token_ids in [EosToks::Single(eos), EosToks::Multiple(vec![-1, eos])] {
+            options.stop = StopCondition {
+                eos_token_ids: Some(eos_token_ids),
+                stop_sequences: Vec::new(),
+            };
+            let stopped = generate(model.as_mut(), &prompt, &options).unwrap();
+            assert_eq!(stopped, stops_after(first_eos + 1));
+        }
+
+        let sequence = unstopped[8..11].to_vec();
+        let first_sequence_end = (sequence.len()..=unstopped.len())
+            .find(|end| unstopped[..*end].ends_with(&sequence))
+            .unwrap();
+        options.stop = StopCondition {
+            eos_token_ids: Some(EosToks::Single(-1)),
+            stop_sequences: vec![vec![], sequence],
+        };
+        let stopped = generate(model.as_mut(), &prompt, &options).unwrap();
+        assert_eq!(stopped, stops_after(first_sequence_end));
+        assert!(stopped.len() < unstopped.len());
+    }
+
+    #[test]
+    fn test_generation_task_samples_with_its_seed() {
+        let vocab = (0..128).map(|id| (format!("t{id}"), id)).collect();
+        let tokenizer = Tokenizer::new(ModelWrapper::WordLevel(
+            WordLevel::builder()
+                .vocab(vocab)
+                .unk_token("t0".to_string())
+                .build()
+                .unwrap(),
+        ));
+        let mut model = tiny_model();
+        let _no_grad = tch::no_grad_guard();
+        let generation = GenerationOptions {
+            sampling: Sampling::TopP {
+                p: 0.9,
+                temperature: 1.0,
+            },
+            sampling_seed: 1234,
+            max_new_tokens: 8,
+            stop: StopCondition {
+                eos_token_ids: model.eos_token_ids(),
+                stop_sequences: Vec::new(),
+            },
+        };
+        // each document's answer is what it generates when sampled with the eval's seed plus its index
+        let docs = [vec![1i64, 5, 9], vec![2, 7, 11, 13]]
+            .into_iter()
+            .enumerate()
+            .map(|(index, text)| {
+                let options = GenerationOptions {
+                    sampling_seed: 1234 + index as u64,
+                    ..generation.clone()
+                };
+                let generated = generate(model.as_mut(), &text, &options).unwrap();
+                let generated = generated.iter().map(|x| *x as u32).collect::<Vec<_>>();
+                TokenizedGenerationDocument {
+                    text,
+                    answer: tokenizer.decode(&generated, true).unwrap(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let run = |sampling_seed, skip_and_step_by| {
+            let task = PreparedTask {
+                name: "tiny".to_string(),
+                num: docs.len(),
+                prepared_task_type: PreparedTaskType::GenerateUntil {
+                    docs: docs
+                        .iter()
+                        .map(|doc| TokenizedGenerationDocument {
+                            text: doc.text.clone(),
+                            answer: doc.answer.clone(),
+                        })
+                        .collect(),
+                    tokenized_fewshot: vec![],
+                    generation: GenerationOptions {
+                        sampling_seed,
+                        ..generation.clone()
+                    },
+                    stop_sequences: vec![],
+                    tokenizer: tokenizer.clone(),
+                    extract_answer: |text| Some(text.to_string()),
+                },
+            };
+            let mut model = tiny_model();
+            task.run(
+                EvalTaskOptions {
+                    model: model.as_mut(),
+                    skip_and_step_by,
+                    live_results: None,
+                    cancel: None,
+                    limit: None,
+                    loop_if_empty: false,
+                },
+                false,
+            )
+            .scores["exact_match"]
+        };
+        assert_eq!(run(1234, None), 1.0);
+        // a document samples the same no matter which others are run before it
+        assert_eq!(run(1234, Some((1, 2))), 1.0);
+        assert!(run(4321, None) < 1.0);
+    }
+
+    fn run_on_tiny_model(
+        docs: Vec<TokenizedLLHDocument>,
+        aggregation: Aggregation,
+    ) -> PreparedTaskResult {
+        let mut model = tiny_model_for_causal_lm(
+            AutoConfig::Llama(tiny_llama_config()),
+            42,
+            Some(Device::Cpu),
+        )
+        .unwrap();
+        let task = PreparedTask {
+            name: "tiny".to_string(),
+            num: docs.len(),
+            prepared_task_type: PreparedTaskType::LogLikelihood {
+                docs,
+                tokenized_fewshot: vec![1],
+                aggregation,
+            },
+        };
+        let _no_grad = tch::no_grad_guard();
+        task.run(
+            EvalTaskOptions {
+                model: model.as_mut(),
+                skip_and_step_by: None,
+                live_results: None,
+                cancel: None,
+                limit: None,
+                loop_if_empty: false,
+            },
+            false,
+        )
+    }
+
+    fn doc(text: Vec<i64>, choices: Vec<Vec<i64>>, category: Option<&str>) -> TokenizedLLHDocument {
+        TokenizedLLHDocument {
+            text,
+            choices,
+            answer: 0,
+            category: category.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_eval_runs_on_cpu() {
+        let docs = vec![
+            // single token choices
+            doc(vec![3, 4, 5], vec![vec![6], vec![7], vec![8]], None),
+            // multi token choices
+            doc(vec![9, 10], vec![vec![11, 12], vec![13, 14, 15]], None),
+        ];
+        let result = run_on_tiny_model(docs, Aggregation::Micro);
+        assert!(!result.cancelled);
+        for metric in ["acc", "acc_norm"] {
+            let score = result.scores[metric];
+            assert!((0.0..=1.0).contains(&score), "{metric}: {score}");
+        }
+    }
+
+    #[test]
+    fn test_macro_average() {
+        let scores = HashMap::from([
+            ("acc".to_string(), 0.75),
+            ("acc/anatomy".to_string(), 1.0),
+            ("acc/astronomy".to_string(), 0.0),
+            ("acc_norm".to_string(), 0.25),
+        ]);
+        assert_eq!(macro_average(&scores, "acc"), Some(0.5));
+        assert_eq!(macro_average(&scores, "acc_norm"), None);
+    }
+
+    #[test]
+    fn test_aggregation() {
+        // three documents in one category, one in another
+        let docs = || {
+            vec![
+                doc(vec![3, 4], vec![vec![6], vec![7]], Some("anatomy")),
+                doc(vec![5, 4], vec![vec![8], vec![9]], Some("anatomy")),
+                doc(vec![2, 9], vec![vec![6], vec![8]], Some("anatomy")),
+                doc(vec![3, 7], vec![vec![10], vec![11]], Some("astronomy")),
+            ]
+        };
+        let per_document = run_on_tiny_model(docs(), Aggregation::Micro);
+        let per_category = run_on_tiny_model(docs(), Aggregation::Macro);
+
+        for metric in ["acc", "acc_norm"] {
+            let anatomy = per_document.scores[&category_metric(metric, "anatomy")];
+            let astronomy = per_document.scores[&category_metric(metric, "astronomy")];
+            assert_eq!(
+                anatomy,
+                per_category.scores[&category_metric(metric, "anatomy")]
+            );
+            assert_eq!(
+                astronomy,
+                per_category.scores[&category_metric(metric, "astronomy")]
+            );
+
+            let expected_micro = (anatomy * 3.0 + astronomy) / 4.0;
+            let expected_macro = (anatomy + astronomy) / 2.0;
+            assert!((per_document.scores[metric] - expected_micro).abs() < 1e-9);
+            assert!((per_category.scores[metric] - expected_macro).abs() < 1e-9);
         }
     }
 }
@@ -5,20 +5,27 @@ mod harness;
 mod tasks;
 mod traits;
 
-pub use harness::{EvalTaskOptions, PreparedTask, PreparedTaskResult, Task, TaskType};
-pub use tasks::{ArcChallenge, ArcEasy, Hellaswag, MMLUPro, MMLU};
+pub use harness::{
+    generate, EvalTaskOptions, GenerationOptions, PreparedTask, PreparedTaskResult, Task, TaskType,
+};
+pub use tasks::{
+    ArcChallenge, ArcEasy, Hellaswag, MMLUPro, TruthfulQA, GSM8K, MMLU, MMLU_SUBJECTS,
+};
+pub use traits::Aggregation;
 
 pub const ASCII_UPPERCASE: [&str; 26] = [
     "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S",
     "T", "U", "V", "W", "X", "Y", "Z",
 ];
 
-pub const ALL_TASK_NAMES: [&str; 5] = [
+pub const ALL_TASK_NAMES: [&str; 7] = [
     ArcChallenge::name(),
     ArcEasy::name(),
+    GSM8K::name(),
     Hellaswag::name(),
     MMLUPro::name(),
     MMLU::name(),
+    TruthfulQA::name(),
 ];
 
 pub fn load_dataset(
@@ -37,19 +44,29 @@ pub fn load_dataset(
     Dataset::load_dataset(&repo_files, Some(split), subset)
 }
 
+/// Besides the names in [`ALL_TASK_NAMES`], accepts `mmlu_macro` for MMLU averaged per subject,
+/// and `mmlu_<subject>` for a single one of [`MMLU_SUBJECTS`].
 pub fn tasktype_from_name(name: &str) -> Result<TaskType> {
-    match name
+    let normalized = name
         .to_lowercase()
         .chars()
         .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
-        .collect::<String>()
-        .as_str()
-    {
+        .collect::<String>();
+    match normalized.as_str() {
         "arc_challenge" => ArcChallenge::load(),
         "arc_easy" => ArcEasy::load(),
+        "gsm8k" => GSM8K::load(),
         "hellaswag" => Hellaswag::load(),
         "mmlu_pro" => MMLUPro::load(),
         "mmlu" => MMLU::load(),
-        _ => bail!("Unknown task {name}"),
+        "mmlu_macro" => MMLU::load_with(None, Aggregation::Macro),
+        "truthfulqa" | "truthfulqa_mc1" => TruthfulQA::load(),
+        other => match other
+            .strip_prefix("mmlu_")
+            .filter(|subject| MMLU_SUBJECTS.contains(subject))
+        {
+            Some(subject) => MMLU::load_with(Some(subject), Aggregation::Micro),
+            None => bail!("Unknown task {name}"),
+        },
     }
 }
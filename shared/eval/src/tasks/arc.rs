@@ -72,6 +72,7 @@ impl Arc {
             text,
             choices,
             answer,
+            category: None,
         }
     }
 }
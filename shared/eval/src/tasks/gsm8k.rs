@@ -0,0 +1,115 @@
+use crate::{
+    load_dataset,
+    traits::{GenerateUntilTask, GenerationDocument},
+    TaskType,
+};
+use anyhow::Result;
+use psyche_data_provider::{Dataset, Row, RowAccessor, Split};
+use regex::Regex;
+use std::fmt::Display;
+
+/// Solutions end with a line like `#### 1,234`.
+const ANSWER_MARKER: &str = "####";
+
+pub struct GSM8K {
+    test_dataset: Dataset,
+    train_dataset: Dataset,
+}
+
+impl GSM8K {
+    pub fn load() -> Result<TaskType> {
+        let ret = Self {
+            test_dataset: load_dataset(
+                "openai/gsm8k",
+                None,
+                Split::Test,
+                Some("main".to_string()),
+            )?,
+            train_dataset: load_dataset(
+                "openai/gsm8k",
+                None,
+                Split::Train,
+                Some("main".to_string()),
+            )?,
+        };
+        Ok(TaskType::GenerateUntil(Box::new(ret)))
+    }
+
+    pub const fn name() -> &'static str {
+        "GSM8K"
+    }
+
+    fn row_to_document(dataset: &Dataset, row: Row) -> GenerationDocument {
+        let question = row
+            .get_string(dataset.get_column_id("question").unwrap())
+            .unwrap();
+        let solution = row
+            .get_string(dataset.get_column_id("answer").unwrap())
+            .unwrap();
+        GenerationDocument {
+            text: format!("Question: {question}\nAnswer:"),
+            target: format!(" {solution}"),
+            answer: extract_answer(solution).unwrap_or_default(),
+        }
+    }
+}
+
+/// The number after the last answer marker, without thousands separators.
+fn extract_answer(generation: &str) -> Option<String> {
+    let re = Regex::new(r"####\s*(-?[0-9.,]+)").unwrap();
+    let (_, after_marker) = generation.rsplit_once(ANSWER_MARKER)?;
+    let number = re
+        .captures(&format!("{ANSWER_MARKER}{after_marker}"))?
+        .get(1)?
+        .as_str()
+        .replace(',', "");
+    Some(number.trim_end_matches('.').to_string())
+}
+
+impl GenerateUntilTask for GSM8K {
+    fn get_documents(&self) -> Vec<GenerationDocument> {
+        self.test_dataset
+            .iter()
+            .map(|row| GSM8K::row_to_document(&self.test_dataset, row))
+            .collect()
+    }
+
+    fn get_fewshot_documents(&self) -> Vec<GenerationDocument> {
+        self.train_dataset
+            .iter()
+            .map(|row| GSM8K::row_to_document(&self.train_dataset, row))
+            .collect()
+    }
+
+    fn stop_sequences(&self) -> Vec<String> {
+        vec!["Question:".to_string(), "\n\n".to_string()]
+    }
+
+    fn answer_extractor(&self) -> fn(&str) -> Option<String> {
+        extract_answer
+    }
+}
+
+impl Display for GSM8K {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", Self::name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_answer() {
+        assert_eq!(
+            extract_answer("She has 3 + 4 = 7 apples.\n#### 7").as_deref(),
+            Some("7")
+        );
+        assert_eq!(
+            extract_answer("#### 1 then #### -1,234.").as_deref(),
+            Some("-1234")
+        );
+        assert_eq!(extract_answer("The answer is 7."), None);
+    }
+}
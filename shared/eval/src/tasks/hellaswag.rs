@@ -81,6 +81,7 @@ impl Hellaswag {
             text,
             choices,
             answer,
+            category: None,
         }
     }
 }
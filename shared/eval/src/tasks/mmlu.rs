@@ -1,32 +1,108 @@
 use crate::{
     load_dataset,
-    traits::{Document, LogLikelihoodTask},
+    traits::{Aggregation, Document, LogLikelihoodTask},
     TaskType, ASCII_UPPERCASE,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use psyche_data_provider::{Dataset, ListAccessor, Row, RowAccessor, Split};
 use std::fmt::Display;
 
+pub const MMLU_SUBJECTS: [&str; 57] = [
+    "abstract_algebra",
+    "anatomy",
+    "astronomy",
+    "business_ethics",
+    "clinical_knowledge",
+    "college_biology",
+    "college_chemistry",
+    "college_computer_science",
+    "college_mathematics",
+    "college_medicine",
+    "college_physics",
+    "computer_security",
+    "conceptual_physics",
+    "econometrics",
+    "electrical_engineering",
+    "elementary_mathematics",
+    "formal_logic",
+    "global_facts",
+    "high_school_biology",
+    "high_school_chemistry",
+    "high_school_computer_science",
+    "high_school_european_history",
+    "high_school_geography",
+    "high_school_government_and_politics",
+    "high_school_macroeconomics",
+    "high_school_mathematics",
+    "high_school_microeconomics",
+    "high_school_physics",
+    "high_school_psychology",
+    "high_school_statistics",
+    "high_school_us_history",
+    "high_school_world_history",
+    "human_aging",
+    "human_sexuality",
+    "international_law",
+    "jurisprudence",
+    "logical_fallacies",
+    "machine_learning",
+    "management",
+    "marketing",
+    "medical_genetics",
+    "miscellaneous",
+    "moral_disputes",
+    "moral_scenarios",
+    "nutrition",
+    "philosophy",
+    "prehistory",
+    "professional_accounting",
+    "professional_law",
+    "professional_medicine",
+    "professional_psychology",
+    "public_relations",
+    "security_studies",
+    "sociology",
+    "us_foreign_policy",
+    "virology",
+    "world_religions",
+];
+
 pub struct MMLU {
     test_dataset: Dataset,
     validation_dataset: Dataset,
+    subject: Option<String>,
+    aggregation: Aggregation,
 }
 
 impl MMLU {
+    /// All subjects, averaged over every question.
     pub fn load() -> Result<TaskType> {
+        Self::load_with(None, Aggregation::Micro)
+    }
+
+    /// Only loads `subject` (one of [`MMLU_SUBJECTS`]) if it's set, otherwise all of them.
+    /// With [`Aggregation::Macro`], every subject counts the same, no matter how many questions it has.
+    pub fn load_with(subject: Option<&str>, aggregation: Aggregation) -> Result<TaskType> {
+        if let Some(subject) = subject {
+            if !MMLU_SUBJECTS.contains(&subject) {
+                bail!("Unknown MMLU subject {subject}");
+            }
+        }
         let ret = Self {
             test_dataset: load_dataset(
                 "hails/mmlu_no_train",
                 Some("main".to_owned()),
                 Split::Test,
-                None,
+                subject.map(str::to_owned),
             )?,
             validation_dataset: load_dataset(
                 "hails/mmlu_no_train",
                 Some("main".to_owned()),
                 Split::Validation,
-                None,
+                subject.map(str::to_owned),
             )?,
+            subject: subject.map(str::to_owned),
+            aggregation,
         };
         Ok(TaskType::LogLikelihood(Box::new(ret)))
     }
@@ -36,27 +112,45 @@ impl MMLU {
     }
 
     fn row_to_document(dataset: &Dataset, row: Row) -> Document {
-        let text = row
+        let question = row
             .get_string(dataset.get_column_id("question").unwrap())
-            .unwrap()
-            .to_owned();
+            .unwrap();
         let options = row
             .get_list(dataset.get_column_id("choices").unwrap())
             .unwrap();
         let options = (0..options.len())
-            .map(|i| format!("{}. {}", ASCII_UPPERCASE[i], options.get_string(i).unwrap()))
-            .collect::<Vec<_>>();
-        let choices = (0..options.len())
-            .map(|i| ASCII_UPPERCASE[i].to_owned())
+            .map(|i| options.get_string(i).unwrap().as_str())
             .collect::<Vec<_>>();
-        let text = format!("{}\n{}\nAnswer: ", text, options.join("\n"));
         let answer = row
             .get_long(dataset.get_column_id("answer").unwrap())
             .unwrap() as usize;
+        let subject = dataset
+            .get_column_id("subject")
+            .and_then(|column| row.get_string(column).ok())
+            .cloned();
+        Self::document(question, &options, answer, subject)
+    }
+
+    fn document(
+        question: &str,
+        options: &[&str],
+        answer: usize,
+        subject: Option<String>,
+    ) -> Document {
+        let options = options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("{}. {}", ASCII_UPPERCASE[i], option))
+            .collect::<Vec<_>>();
+        let choices = (0..options.len())
+            .map(|i| ASCII_UPPERCASE[i].to_owned())
+            .collect::<Vec<_>>();
+        let text = format!("{}\n{}\nAnswer: ", question, options.join("\n"));
         Document {
             text,
             choices,
             answer,
+            category: subject,
         }
     }
 }
@@ -75,10 +169,61 @@ impl LogLikelihoodTask for MMLU {
             .map(|row| MMLU::row_to_document(&self.validation_dataset, row))
             .collect()
     }
+
+    fn aggregation(&self) -> Aggregation {
+        self.aggregation
+    }
 }
 
 impl Display for MMLU {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", Self::name())
+        write!(f, "{}", Self::name())?;
+        if let Some(subject) = &self.subject {
+            write!(f, "_{subject}")?;
+        }
+        if self.aggregation == Aggregation::Macro {
+            write!(f, "_macro")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_from_fixture_rows() {
+        let rows = [
+            (
+                "Find the degree for the given field extension Q(sqrt(2)) over Q.",
+                ["0", "4", "2", "6"],
+                2,
+                "abstract_algebra",
+            ),
+            (
+                "Which of the following is the body cavity that contains the pituitary gland?",
+                ["Abdominal", "Cranial", "Pleural", "Spinal"],
+                1,
+                "anatomy",
+            ),
+        ];
+        let docs = rows
+            .iter()
+            .map(|(question, options, answer, subject)| {
+                MMLU::document(question, options, *answer, Some(subject.to_string()))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            docs[0].text,
+            "Find the degree for the given field extension Q(sqrt(2)) over Q.\nA. 0\nB. 4\nC. 2\nD. 6\nAnswer: "
+        );
+        for (doc, (_, _, answer, subject)) in docs.iter().zip(rows) {
+            assert_eq!(doc.choices, ["A", "B", "C", "D"]);
+            assert_eq!(doc.answer, answer);
+            assert_eq!(doc.category.as_deref(), Some(subject));
+        }
+        assert!(rows.iter().all(|row| MMLU_SUBJECTS.contains(&row.3)));
     }
 }
@@ -56,6 +56,7 @@ impl MMLUPro {
             text,
             choices,
             answer,
+            category: None,
         }
     }
 }
@@ -1,10 +1,14 @@
 mod arc;
+mod gsm8k;
 mod hellaswag;
 mod mmlu;
 mod mmlu_pro;
+mod truthfulqa;
 
 pub use arc::ArcChallenge;
 pub use arc::ArcEasy;
+pub use gsm8k::GSM8K;
 pub use hellaswag::Hellaswag;
-pub use mmlu::MMLU;
+pub use mmlu::{MMLU, MMLU_SUBJECTS};
 pub use mmlu_pro::MMLUPro;
+pub use truthfulqa::TruthfulQA;
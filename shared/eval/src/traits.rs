@@ -4,9 +4,45 @@ pub struct Document {
     pub text: String,
     pub choices: Vec<String>,
     pub answer: usize,
+    /// e.g. the subject of an MMLU question. Documents with a category are also scored per category.
+    pub category: Option<String>,
+}
+
+/// How a task's per-document scores are combined into its final score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregation {
+    /// Average over every document, so bigger categories weigh more.
+    #[default]
+    Micro,
+    /// Average within each category first, then average the categories, so each one weighs the same.
+    Macro,
 }
 
 pub trait LogLikelihoodTask: Send + Display {
     fn get_documents(&self) -> Vec<Document>;
     fn get_fewshot_documents(&self) -> Vec<Document>;
+    fn aggregation(&self) -> Aggregation {
+        Aggregation::Micro
+    }
+}
+
+/// A question whose answer is generated, rather than picked among choices.
+pub struct GenerationDocument {
+    pub text: String,
+    /// The worked solution, appended to the text when the document is a fewshot example.
+    pub target: String,
+    /// The final answer a generation is scored against.
+    pub answer: String,
+}
+
+pub trait GenerateUntilTask: Send + Display {
+    fn get_documents(&self) -> Vec<GenerationDocument>;
+    fn get_fewshot_documents(&self) -> Vec<GenerationDocument>;
+    /// Generation also stops at any of these, e.g. the start of the next question.
+    fn stop_sequences(&self) -> Vec<String>;
+    fn max_new_tokens(&self) -> usize {
+        256
+    }
+    /// Pulls the final answer out of a generation, to compare against the document's.
+    fn answer_extractor(&self) -> fn(&str) -> Option<String>;
 }
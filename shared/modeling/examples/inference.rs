@@ -2,8 +2,9 @@ use anyhow::{Error, Result};
 use clap::Parser;
 use psyche_data_provider::download_model_repo_sync;
 use psyche_modeling::{
-    auto_model_for_causal_lm_from_pretrained, auto_tokenizer, CommunicatorId, EosToks,
-    LogitsProcessor, Sampling, TokenOutputStream,
+    auto_model_for_causal_lm_from_pretrained, auto_tokenizer, tokenizer_eos_token_ids,
+    CommunicatorId, EosToks, LogitsProcessor, Sampling, SpecialTokens, StopCondition,
+    TokenOutputStream,
 };
 use std::{
     io::Write,
@@ -91,6 +92,16 @@ struct Args {
     #[arg(long)]
     tensor_parallelism: Option<usize>,
 
+    #[arg(long)]
+    bos_token_id: Option<i64>,
+
+    #[arg(long)]
+    eos_token_id: Vec<i64>,
+
+    /// Stop generating once the output ends with this text. Can be given more than once.
+    #[arg(long)]
+    stop: Vec<String>,
+
     prompt: Option<String>,
 }
 
@@ -116,8 +127,27 @@ fn inference(
             .as_ref()
             .map(|(id, rank, size, _)| (id.clone(), *rank, *size)),
         None,
+        Some(SpecialTokens {
+            bos_token_id: args.bos_token_id,
+            eos_token_id: EosToks::from_ids(&args.eos_token_id),
+        }),
     )?;
-    let eos_token_id = model.eos_token_ids();
+    let stop = StopCondition {
+        eos_token_ids: model
+            .eos_token_ids()
+            .or_else(|| tokenizer_eos_token_ids(&tokenizer)),
+        stop_sequences: args
+            .stop
+            .iter()
+            .map(|stop| {
+                tokenizer
+                    .encode(stop.as_str(), false)
+                    .map(|encoding| encoding.get_ids().iter().map(|id| *id as i64).collect())
+                    .map_err(Error::msg)
+            })
+            .collect::<Result<_>>()?,
+    };
+    let prompt_len = tokens.len();
     let mut logits_processor = {
         let temperature = args.temperature;
         let sampling = if temperature <= 0. {
@@ -153,26 +183,14 @@ fn inference(
         token_generated += 1;
         tokens.push(next_token as i64);
 
-        match eos_token_id {
-            Some(EosToks::Single(eos_tok_id)) if next_token as i64 == eos_tok_id => {
-                if rank == 0 {
-                    println!(
-                        "{}",
-                        tokenizer.tokenizer().decode(&[next_token], false).unwrap()
-                    );
-                }
-                break;
-            }
-            Some(EosToks::Multiple(ref eos_ids)) if eos_ids.contains(&(next_token as i64)) => {
-                if rank == 0 {
-                    println!(
-                        "{}",
-                        tokenizer.tokenizer().decode(&[next_token], false).unwrap()
-                    );
-                }
-                break;
+        if stop.should_stop(&tokens[prompt_len..]) {
+            if rank == 0 {
+                println!(
+                    "{}",
+                    tokenizer.tokenizer().decode(&[next_token], false).unwrap()
+                );
             }
-            _ => (),
+            break;
         }
 
         if let Some(t) = tokenizer.next_token(next_token)? {
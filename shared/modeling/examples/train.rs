@@ -1,6 +1,8 @@
 use anyhow::Result;
 use clap::Parser;
-use psyche_core::{BatchId, CancellableBarrier, CosineLR, OptimizerDefinition, Shuffle};
+use psyche_core::{
+    BatchId, CancellableBarrier, CosineLR, DistroConfig, OptimizerDefinition, Shuffle,
+};
 use psyche_data_provider::{download_model_repo_sync, LocalDataProvider};
 use psyche_modeling::{
     auto_model_for_causal_lm_from_pretrained, Batch, BatchData, CausalLM, CommunicatorId,
@@ -41,6 +43,9 @@ struct Args {
     #[arg(long, default_value_t = 0.1)]
     weight_decay: f32,
 
+    #[arg(long, default_value_t = false)]
+    exclude_1d_from_weight_decay: bool,
+
     #[arg(long, default_value_t = 1e-8)]
     eps: f32,
 
@@ -144,19 +149,23 @@ fn main() -> Result<()> {
     };
 
     let optimizer = match args.distro {
-        true => OptimizerDefinition::Distro {
+        true => OptimizerDefinition::Distro(DistroConfig {
             clip_grad_norm,
             compression_decay: args.compression_decay,
             compression_topk: args.compression_topk,
             compression_chunk: args.compression_chunk,
             quantize_1bit: args.distro_quantization,
+            momentum: 0.0,
+            dampening: 0.0,
+            nesterov: false,
             weight_decay: Some(args.weight_decay),
-        },
+        }),
         false => OptimizerDefinition::AdamW {
             betas: [args.beta1, args.beta2],
             weight_decay: args.weight_decay,
             eps: args.eps,
             clip_grad_norm,
+            exclude_1d_from_weight_decay: args.exclude_1d_from_weight_decay,
         },
     };
 
@@ -231,6 +240,7 @@ fn main() -> Result<()> {
                                 Some(device),
                                 id.map(|id| (id, tp, tp_world_size)),
                                 Some(args.sequence_length),
+                                None,
                             )?;
                             model.prepare_for_training();
                             Ok(model)
@@ -257,6 +267,7 @@ fn main() -> Result<()> {
                     optimizer,
                     args.micro_batch,
                     None,
+                    None,
                     args.grad_accum_in_fp32,
                     data_parallel,
                 ))
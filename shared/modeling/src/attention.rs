@@ -40,6 +40,7 @@ impl CausalSelfAttention {
         n_kvheads: i64,
         n_embd: i64,
         n_max_seq_len: i64,
+        bias: bool,
         use_sdpa: bool,
         comm: Option<Arc<Communicator>>,
     ) -> Self {
@@ -56,12 +57,12 @@ impl CausalSelfAttention {
         let size_kv = head_dim * n_kvheads;
 
         let q_proj =
-            ColumnParallelLinear::new(&vs / "q_proj", n_embd, size_q, false, false, comm.clone());
+            ColumnParallelLinear::new(&vs / "q_proj", n_embd, size_q, bias, false, comm.clone());
         let k_proj =
-            ColumnParallelLinear::new(&vs / "k_proj", n_embd, size_kv, false, false, comm.clone());
+            ColumnParallelLinear::new(&vs / "k_proj", n_embd, size_kv, bias, false, comm.clone());
         let v_proj =
-            ColumnParallelLinear::new(&vs / "v_proj", n_embd, size_kv, false, false, comm.clone());
-        let o_proj = RowParallelLinear::new(&vs / "o_proj", size_q, n_embd, false, true, comm);
+            ColumnParallelLinear::new(&vs / "v_proj", n_embd, size_kv, bias, false, comm.clone());
+        let o_proj = RowParallelLinear::new(&vs / "o_proj", size_q, n_embd, bias, true, comm);
 
         Self {
             q_proj,
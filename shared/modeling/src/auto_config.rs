@@ -1,6 +1,6 @@
 use crate::{
     safetensor_utils::load_safetensors_into_variables, tensor_parallelism::tensor_shard,
-    DeepseekConfig, LlamaConfig, LoadSafetensorsError,
+    DeepseekConfig, LanguageModelConfig, LlamaConfig, LoadSafetensorsError,
 };
 use std::{
     collections::{HashMap, HashSet},
@@ -36,6 +36,12 @@ pub enum ModelLoadError {
     #[error("Tried to use tensor parallelism with feature \"parallelism\" disabled")]
     TensorParallelismNotEnabled,
 
+    #[error("Tensor parallelism of {0} does not evenly divide the model's {1} attention heads")]
+    InvalidTensorParallelism(usize, usize),
+
+    #[error("Tensor parallelism of {0} is more than the model's {1} layers")]
+    TensorParallelismExceedsLayers(usize, usize),
+
     #[error("Failed to load safetensors from disk: {0}")]
     LoadSafetensorsError(#[from] LoadSafetensorsError),
 
@@ -49,8 +55,26 @@ pub enum ModelLoadError {
     WrongConfigType,
 }
 
+/// Adds the shapes of the weight, and bias if any, of a linear layer at `path`, as `nn::linear` creates them.
+pub(crate) fn add_linear_shapes(
+    shapes: &mut HashMap<String, Vec<i64>>,
+    path: &str,
+    in_features: i64,
+    out_features: i64,
+    bias: bool,
+) {
+    shapes.insert(format!("{path}.weight"), vec![out_features, in_features]);
+    if bias {
+        shapes.insert(format!("{path}.bias"), vec![out_features]);
+    }
+}
+
 pub trait ModelConfig: serde::Serialize + Clone {
     fn get_parameter_names(&self) -> Vec<String>;
+    /// The shape of every parameter, as it's stored in a checkpoint (i.e. unsharded).
+    fn get_parameter_shapes(&self) -> HashMap<String, Vec<i64>>;
+    /// Freshly initialized parameters on the CPU, deterministic for a given seed.
+    fn random_init_parameters(&self, seed: i64) -> HashMap<String, Tensor>;
 }
 
 #[derive(Clone)]
@@ -188,4 +212,27 @@ impl ModelConfig for AutoConfig {
             AutoConfig::Deepseek(config) => config.get_parameter_names(),
         }
     }
+
+    fn get_parameter_shapes(&self) -> HashMap<String, Vec<i64>> {
+        match self {
+            AutoConfig::Llama(config) => config.get_parameter_shapes(),
+            AutoConfig::Deepseek(config) => config.get_parameter_shapes(),
+        }
+    }
+
+    fn random_init_parameters(&self, seed: i64) -> HashMap<String, Tensor> {
+        match self {
+            AutoConfig::Llama(config) => config.random_init_parameters(seed),
+            AutoConfig::Deepseek(config) => config.random_init_parameters(seed),
+        }
+    }
+}
+
+impl AutoConfig {
+    pub fn vocab_size(&self) -> usize {
+        match self {
+            AutoConfig::Llama(config) => config.vocab_size(),
+            AutoConfig::Deepseek(config) => config.vocab_size(),
+        }
+    }
 }
@@ -1,10 +1,88 @@
 use crate::{
     AttentionImplementation, CausalLM, CommunicatorId, DeepseekForCausalLM, LlamaForCausalLM,
-    ModelLoadError, PretrainedSource,
+    ModelLoadError, PretrainedSource, SpecialTokens,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, OnceLock, RwLock},
 };
-use std::{path::PathBuf, sync::Arc};
 use tch::{Device, Kind};
 
+/// Everything [`auto_model_for_causal_lm_from_pretrained`] was called with,
+/// for the builder of the checkpoint's architecture to load it from.
+pub struct CausalLMLoadArgs {
+    pub repo_files: Vec<PathBuf>,
+    pub kind: Option<Kind>,
+    pub attn_implementation: Option<AttentionImplementation>,
+    pub device: Option<Device>,
+    pub tensor_parallelism_world: Option<(Arc<CommunicatorId>, usize, usize)>,
+    pub override_max_position_embeddings: Option<usize>,
+    pub override_special_tokens: Option<SpecialTokens>,
+}
+
+pub type CausalLMBuilder = fn(CausalLMLoadArgs) -> Result<Box<dyn CausalLM>, ModelLoadError>;
+
+fn llama(args: CausalLMLoadArgs) -> Result<Box<dyn CausalLM>, ModelLoadError> {
+    LlamaForCausalLM::from_pretrained(
+        &PretrainedSource::RepoFiles(args.repo_files),
+        args.kind,
+        args.attn_implementation,
+        args.device,
+        args.tensor_parallelism_world,
+        args.override_max_position_embeddings,
+        args.override_special_tokens,
+    )
+    .map(|x| Box::new(x) as Box<dyn CausalLM>)
+}
+
+fn deepseek(args: CausalLMLoadArgs) -> Result<Box<dyn CausalLM>, ModelLoadError> {
+    DeepseekForCausalLM::from_pretrained(
+        &PretrainedSource::RepoFiles(args.repo_files),
+        args.kind,
+        args.attn_implementation,
+        args.device,
+        args.tensor_parallelism_world,
+        args.override_max_position_embeddings,
+        args.override_special_tokens,
+    )
+    .map(|x| Box::new(x) as Box<dyn CausalLM>)
+}
+
+/// The builder for each `model_type` a checkpoint's config.json can have.
+fn registry() -> &'static RwLock<HashMap<String, CausalLMBuilder>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, CausalLMBuilder>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let builtin: [(&str, CausalLMBuilder); 3] = [
+            ("llama", llama),
+            ("deepseek_v2", deepseek),
+            ("deepseek_v3", deepseek),
+        ];
+        RwLock::new(
+            builtin
+                .into_iter()
+                .map(|(model_type, builder)| (model_type.to_string(), builder))
+                .collect(),
+        )
+    })
+}
+
+/// Makes [`auto_model_for_causal_lm_from_pretrained`] load checkpoints of `model_type` with `builder`,
+/// replacing whatever was registered for it before.
+pub fn register_causal_lm(model_type: &str, builder: CausalLMBuilder) {
+    registry()
+        .write()
+        .unwrap()
+        .insert(model_type.to_string(), builder);
+}
+
+/// Every `model_type` that [`auto_model_for_causal_lm_from_pretrained`] can load, sorted.
+pub fn registered_model_types() -> Vec<String> {
+    let mut model_types: Vec<String> = registry().read().unwrap().keys().cloned().collect();
+    model_types.sort();
+    model_types
+}
+
 pub fn auto_model_for_causal_lm_from_pretrained(
     repo_files: Vec<PathBuf>,
     kind: Option<Kind>,
@@ -12,6 +90,7 @@ pub fn auto_model_for_causal_lm_from_pretrained(
     device: Option<Device>,
     tensor_parallelism_world: Option<(Arc<CommunicatorId>, usize, usize)>,
     override_max_position_embeddings: Option<usize>,
+    override_special_tokens: Option<SpecialTokens>,
 ) -> Result<Box<dyn CausalLM>, ModelLoadError> {
     let config_json = std::fs::read_to_string(
         repo_files
@@ -28,25 +107,65 @@ pub fn auto_model_for_causal_lm_from_pretrained(
         .ok_or(ModelLoadError::WrongConfigType)?
         .as_str()
         .ok_or(ModelLoadError::WrongConfigType)?;
-    match model_type {
-        "llama" => LlamaForCausalLM::from_pretrained(
-            &PretrainedSource::RepoFiles(repo_files),
-            kind,
-            attn_implementation,
-            device,
-            tensor_parallelism_world,
-            override_max_position_embeddings,
-        )
-        .map(|x| Box::new(x) as Box<dyn CausalLM>),
-        "deepseek_v2" | "deepseek_v3" => DeepseekForCausalLM::from_pretrained(
-            &PretrainedSource::RepoFiles(repo_files),
-            kind,
-            attn_implementation,
-            device,
-            tensor_parallelism_world,
-            override_max_position_embeddings,
+    // copied out, so a builder can register architectures itself without deadlocking
+    let builder = *registry()
+        .read()
+        .unwrap()
+        .get(model_type)
+        .ok_or(ModelLoadError::WrongConfigType)?;
+    builder(CausalLMLoadArgs {
+        repo_files,
+        kind,
+        attn_implementation,
+        device,
+        tensor_parallelism_world,
+        override_max_position_embeddings,
+        override_special_tokens,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tiny_llama_config, tiny_model_for_causal_lm, AutoConfig};
+
+    fn tiny_custom(args: CausalLMLoadArgs) -> Result<Box<dyn CausalLM>, ModelLoadError> {
+        tiny_model_for_causal_lm(AutoConfig::Llama(tiny_llama_config()), 0, args.device)
+    }
+
+    fn load(model_type: &str) -> Result<Box<dyn CausalLM>, ModelLoadError> {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.json");
+        std::fs::write(&config, format!(r#"{{"model_type": "{model_type}"}}"#)).unwrap();
+        auto_model_for_causal_lm_from_pretrained(
+            vec![config],
+            None,
+            None,
+            Some(Device::Cpu),
+            None,
+            None,
+            None,
         )
-        .map(|x| Box::new(x) as Box<dyn CausalLM>),
-        _ => Err(ModelLoadError::WrongConfigType),
+    }
+
+    #[test]
+    fn test_registered_architecture_is_loadable() {
+        assert!(matches!(
+            load("tiny_custom"),
+            Err(ModelLoadError::WrongConfigType)
+        ));
+
+        register_causal_lm("tiny_custom", tiny_custom);
+        assert!(registered_model_types().contains(&"tiny_custom".to_string()));
+        let model = load("tiny_custom").unwrap();
+        assert_eq!(model.device(), Device::Cpu);
+    }
+
+    #[test]
+    fn test_builtin_architectures_are_registered() {
+        let model_types = registered_model_types();
+        for model_type in ["llama", "deepseek_v2", "deepseek_v3"] {
+            assert!(model_types.contains(&model_type.to_string()));
+        }
     }
 }
@@ -1,3 +1,4 @@
+use crate::EosToks;
 use std::path::PathBuf;
 use thiserror::Error;
 use tokenizers::Tokenizer;
@@ -17,3 +18,24 @@ pub fn auto_tokenizer(repo_files: &[PathBuf]) -> Result<Tokenizer, AutoTokenizer
         None => Err(AutoTokenizerError::FileNotFound),
     }
 }
+
+/// EOS tokens used by common tokenizers, for models whose config doesn't say which it uses.
+const KNOWN_EOS_TOKENS: [&str; 6] = [
+    "</s>",
+    "<|endoftext|>",
+    "<|end_of_text|>",
+    "<|eot_id|>",
+    "<|im_end|>",
+    "<eos>",
+];
+
+/// Looks for well-known EOS tokens in `tokenizer`'s vocabulary,
+/// as a fallback for when the model's config doesn't have an `eos_token_id`.
+pub fn tokenizer_eos_token_ids(tokenizer: &Tokenizer) -> Option<EosToks> {
+    let ids: Vec<i64> = KNOWN_EOS_TOKENS
+        .iter()
+        .filter_map(|token| tokenizer.token_to_id(token))
+        .map(|id| id as i64)
+        .collect();
+    EosToks::from_ids(&ids)
+}
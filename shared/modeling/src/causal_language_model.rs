@@ -19,6 +19,68 @@ pub enum EosToks {
     Multiple(Vec<i64>),
 }
 
+impl EosToks {
+    /// `None` for no ids, so an empty list doesn't make a set that never matches.
+    pub fn from_ids(ids: &[i64]) -> Option<Self> {
+        match ids {
+            [] => None,
+            [eos_token_id] => Some(EosToks::Single(*eos_token_id)),
+            eos_token_ids => Some(EosToks::Multiple(eos_token_ids.to_vec())),
+        }
+    }
+
+    pub fn contains(&self, token: i64) -> bool {
+        match self {
+            EosToks::Single(eos_token_id) => *eos_token_id == token,
+            EosToks::Multiple(eos_token_ids) => eos_token_ids.contains(&token),
+        }
+    }
+}
+
+/// When generation should stop: at any EOS token, or once the output ends with any of the stop sequences.
+/// Stop sequences can be several tokens long, e.g. a chat template's end of turn that isn't a special token.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct StopCondition {
+    pub eos_token_ids: Option<EosToks>,
+    #[serde(default)]
+    pub stop_sequences: Vec<Vec<i64>>,
+}
+
+impl StopCondition {
+    /// Whether `generated`, the tokens generated so far (not including the prompt), should be the last of them.
+    pub fn should_stop(&self, generated: &[i64]) -> bool {
+        let Some(last) = generated.last() else {
+            return false;
+        };
+        self.eos_token_ids
+            .as_ref()
+            .is_some_and(|eos| eos.contains(*last))
+            || self
+                .stop_sequences
+                .iter()
+                .any(|sequence| !sequence.is_empty() && generated.ends_with(sequence))
+    }
+}
+
+/// Special token ids that take precedence over the ones in the model's config,
+/// e.g. a chat template's end-of-turn token used as EOS.
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct SpecialTokens {
+    pub bos_token_id: Option<i64>,
+    pub eos_token_id: Option<EosToks>,
+}
+
+impl SpecialTokens {
+    pub fn apply<C: LanguageModelConfig>(&self, config: &mut C) {
+        if let Some(bos_token_id) = self.bos_token_id {
+            config.set_bos_token_id(bos_token_id);
+        }
+        if let Some(eos_token_id) = &self.eos_token_id {
+            config.set_eos_token_ids(eos_token_id.clone());
+        }
+    }
+}
+
 /// This trait is for any Causal Language Model that can be inferred,
 /// and thus can have backprop run on it.
 /// Its internal implementation is completely hidden, so this can be impl'd
@@ -51,10 +113,35 @@ pub trait LanguageModelConfig: ModelConfig + Send + Debug + serde::de::Deseriali
 
     fn rope_config(&self) -> Option<RoPEConfig>;
     fn num_attention_heads(&self) -> usize;
+    fn num_hidden_layers(&self) -> usize;
     fn rope_theta(&self) -> f32;
     fn max_position_embeddings(&self) -> usize;
     fn bos_token_id(&self) -> Option<i64>;
     fn eos_token_ids(&self) -> Option<EosToks>;
+    fn set_bos_token_id(&mut self, set: i64);
+    fn set_eos_token_ids(&mut self, set: EosToks);
+}
+
+/// Checks that a model with `config` can be sharded across `tensor_parallelism` devices.
+/// Every rank holds a slice of every layer, so there can't be more ranks than layers,
+/// and the attention heads have to split evenly between them.
+pub fn check_tensor_parallelism(
+    config: &impl LanguageModelConfig,
+    tensor_parallelism: usize,
+) -> Result<(), ModelLoadError> {
+    if tensor_parallelism > config.num_hidden_layers() {
+        return Err(ModelLoadError::TensorParallelismExceedsLayers(
+            tensor_parallelism,
+            config.num_hidden_layers(),
+        ));
+    }
+    if config.num_attention_heads() % tensor_parallelism != 0 {
+        return Err(ModelLoadError::InvalidTensorParallelism(
+            tensor_parallelism,
+            config.num_attention_heads(),
+        ));
+    }
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -79,6 +166,7 @@ pub type LanguageModelBuilder<M, C> = fn(
 ) -> Result<M, ModelLoadError>;
 
 impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLanguageModel<M, C> {
+    #[allow(clippy::too_many_arguments)]
     pub fn from_builder(
         builder: LanguageModelBuilder<M, C>,
         source: &PretrainedSource<C>,
@@ -87,6 +175,7 @@ impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLanguageModel<M, C>
         device: Option<Device>,
         tensor_parallelism_world: Option<(Arc<CommunicatorId>, usize, usize)>,
         override_max_position_embeddings: Option<usize>,
+        override_special_tokens: Option<SpecialTokens>,
     ) -> Result<Self, ModelLoadError> {
         let mut config = source.get_config()?;
 
@@ -98,6 +187,14 @@ impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLanguageModel<M, C>
             config.set_max_position_embeddings(override_max_position_embeddings);
         }
 
+        if let Some(override_special_tokens) = override_special_tokens {
+            override_special_tokens.apply(&mut config);
+        }
+
+        if let Some((_, _, world_size)) = &tensor_parallelism_world {
+            check_tensor_parallelism(&config, *world_size)?;
+        }
+
         let device = device.unwrap_or(Device::cuda_if_available());
         #[cfg(feature = "parallelism")]
         let comm = match tensor_parallelism_world {
@@ -286,3 +383,39 @@ impl<M: LanguageModelForward, C: LanguageModelConfig> CausalLM for CausalLanguag
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tiny_llama_config, LlamaConfig};
+
+    #[test]
+    fn test_tensor_parallelism_is_checked_against_layers() {
+        // 2 layers, 2 heads
+        let config = tiny_llama_config();
+        assert!(check_tensor_parallelism(&config, 1).is_ok());
+        assert!(check_tensor_parallelism(&config, 2).is_ok());
+
+        // 4 heads split fine across 4 ranks, but there are only 2 layers
+        let config = LlamaConfig {
+            num_attention_heads: 4,
+            num_key_value_heads: Some(4),
+            ..tiny_llama_config()
+        };
+        assert!(matches!(
+            check_tensor_parallelism(&config, 4),
+            Err(ModelLoadError::TensorParallelismExceedsLayers(4, 2))
+        ));
+
+        // enough layers, but the heads don't split evenly
+        let config = LlamaConfig {
+            num_hidden_layers: 4,
+            num_attention_heads: 2,
+            ..tiny_llama_config()
+        };
+        assert!(matches!(
+            check_tensor_parallelism(&config, 4),
+            Err(ModelLoadError::InvalidTensorParallelism(4, 2))
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use tch::Device;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DeviceSelectionError {
+    #[error("CUDA is not available. Pass --cpu-fallback to run on the CPU instead (very slow, only meant for testing)")]
+    CudaUnavailable,
+
+    #[error("the CPU fallback only runs on a single device, but {0} were requested")]
+    CpuFallbackMultipleDevices(usize),
+}
+
+/// Picks the CUDA device `index` out of `num_devices`.
+///
+/// If CUDA isn't available at all, this is an error unless `cpu_fallback` is set,
+/// in which case a single device runs on the CPU.
+pub fn cuda_or_cpu_fallback(
+    index: usize,
+    num_devices: usize,
+    cpu_fallback: bool,
+) -> Result<Device, DeviceSelectionError> {
+    select_device(index, num_devices, cpu_fallback, tch::Cuda::is_available())
+}
+
+fn select_device(
+    index: usize,
+    num_devices: usize,
+    cpu_fallback: bool,
+    cuda_available: bool,
+) -> Result<Device, DeviceSelectionError> {
+    match (cuda_available, cpu_fallback) {
+        (true, _) => Ok(Device::Cuda(index)),
+        (false, false) => Err(DeviceSelectionError::CudaUnavailable),
+        (false, true) if num_devices > 1 => Err(DeviceSelectionError::CpuFallbackMultipleDevices(
+            num_devices,
+        )),
+        (false, true) => Ok(Device::Cpu),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_device() {
+        assert_eq!(select_device(3, 4, false, true).unwrap(), Device::Cuda(3));
+        assert_eq!(select_device(0, 1, true, true).unwrap(), Device::Cuda(0));
+        assert!(matches!(
+            select_device(0, 1, false, false),
+            Err(DeviceSelectionError::CudaUnavailable)
+        ));
+        assert_eq!(select_device(0, 1, true, false).unwrap(), Device::Cpu);
+        assert!(matches!(
+            select_device(1, 2, true, false),
+            Err(DeviceSelectionError::CpuFallbackMultipleDevices(2))
+        ));
+    }
+}
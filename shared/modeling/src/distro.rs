@@ -2,11 +2,13 @@ use crate::{
     tensor_parallelism::{tensor_shard, unsharded_tensor_size},
     Communicator,
 };
+use psyche_core::DistroConfig;
 use std::{cmp::Ordering, collections::HashMap, f64::consts::PI, sync::Arc};
 use tch::{
     nn::{Optimizer, OptimizerConfig, Sgd, Shard, VarStore},
     Device, Kind, Tensor,
 };
+use thiserror::Error;
 
 #[cfg(feature = "parallelism")]
 use crate::tensor_parallelism::unshard_tensor;
@@ -323,6 +325,15 @@ impl TransformDCT {
     }
 }
 
+#[derive(Debug, Error)]
+pub enum DecompressError {
+    #[error("sparse indices are on {idx:?} but sparse values are on {val:?}")]
+    DeviceMismatch { idx: Device, val: Device },
+
+    #[error("got {idx:?} sparse indices but {val:?} sparse values")]
+    ShapeMismatch { idx: Vec<i64>, val: Vec<i64> },
+}
+
 pub struct CompressDCT;
 
 impl CompressDCT {
@@ -407,6 +418,46 @@ impl CompressDCT {
         x
     }
 
+    /// Like [`Self::decompress`], but works on any `device`, including the CPU,
+    /// and checks its inputs instead of letting torch panic on them.
+    pub fn decompress_to(
+        idx: &Tensor,
+        val: &Tensor,
+        xshape: &[i64],
+        totalk: i64,
+        kind: Kind,
+        device: Device,
+    ) -> Result<Tensor, DecompressError> {
+        if idx.device() != val.device() {
+            return Err(DecompressError::DeviceMismatch {
+                idx: idx.device(),
+                val: val.device(),
+            });
+        }
+        // indices are packed into bytes, so only their leading dimensions have to match
+        let (idx_shape, val_shape) = (idx.size(), val.size());
+        if idx_shape.len() != val_shape.len()
+            || idx_shape[..idx_shape.len().saturating_sub(1)]
+                != val_shape[..val_shape.len().saturating_sub(1)]
+        {
+            return Err(DecompressError::ShapeMismatch {
+                idx: idx_shape,
+                val: val_shape,
+            });
+        }
+        if idx.numel() == 0 {
+            return Ok(Tensor::zeros(xshape, (kind, device)));
+        }
+        Ok(Self::decompress(
+            &idx.to_device(device),
+            &val.to_device(device),
+            xshape,
+            totalk,
+            kind,
+            device,
+        ))
+    }
+
     pub fn batch_decompress(
         idx: &[Tensor],
         val: &[Tensor],
@@ -460,6 +511,22 @@ pub struct DistroResult {
     pub stats: Option<HashMap<String, f64>>,
 }
 
+impl DistroResult {
+    /// Scales this result's contribution to the aggregated gradient by `weight`.
+    /// Sign-packed values are unpacked first, since a boolean can't carry a weight.
+    pub fn scaled(&self, weight: f64) -> Self {
+        let sparse_val = if self.sparse_val.kind() == Kind::Bool {
+            Distro::unpack_tensor_sign_from_boolean(self.sparse_val.shallow_clone(), Kind::Float)
+        } else {
+            self.sparse_val.shallow_clone()
+        };
+        Self {
+            sparse_val: sparse_val * weight,
+            ..self.clone()
+        }
+    }
+}
+
 impl Clone for DistroResult {
     fn clone(&self) -> Self {
         Self {
@@ -485,20 +552,24 @@ pub struct Distro {
 }
 
 impl Distro {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         vs: &VarStore,
         compression_decay: f64,
         compression_chunk: i64,
         compression_topk: i64,
         weight_decay: f64,
+        momentum: f64,
+        dampening: f64,
+        nesterov: bool,
         comm: Option<Arc<Communicator>>,
     ) -> Self {
         let _no_grad = tch::no_grad_guard();
         let mut sgd: Optimizer = Sgd {
-            momentum: 0.0,
-            dampening: 0.0,
+            momentum,
+            dampening,
             wd: 0.0,
-            nesterov: false,
+            nesterov,
         }
         .build(vs, 0.1)
         .unwrap();
@@ -536,6 +607,24 @@ impl Distro {
         }
     }
 
+    pub fn from_config(
+        vs: &VarStore,
+        config: &DistroConfig,
+        comm: Option<Arc<Communicator>>,
+    ) -> Self {
+        Self::new(
+            vs,
+            config.compression_decay as f64,
+            config.compression_chunk as i64,
+            config.compression_topk as i64,
+            config.weight_decay.unwrap_or(0.0) as f64,
+            config.momentum as f64,
+            config.dampening as f64,
+            config.nesterov,
+            comm,
+        )
+    }
+
     pub fn generate(
         &mut self,
         prev_self_results: &[Vec<DistroResult>],
@@ -707,7 +796,8 @@ impl Distro {
                     if sparse_val.kind() == Kind::Bool {
                         Self::unpack_tensor_sign_from_boolean(sparse_val, val_kind)
                     } else {
-                        sparse_val
+                        // discounted results can be in a different dtype than the model
+                        sparse_val.to_kind(val_kind)
                     }
                 })
                 .collect::<Vec<_>>();
@@ -962,6 +1052,50 @@ mod tests {
         assert!(truth.allclose(&ret, 1e-4, 1e-8, false));
     }
 
+    #[test]
+    fn test_decompress_to_cpu() {
+        set_torch_rng_seed();
+        let truth = Tensor::randn([16, 64], (Kind::Float, Device::Cpu));
+        let (idx, val, xshape, totalk) = CompressDCT::compress(&truth, 48);
+        let ret = CompressDCT::decompress_to(&idx, &val, &xshape, totalk, Kind::Float, Device::Cpu)
+            .unwrap();
+        assert_eq!(ret.size(), truth.size());
+        assert_eq!(ret.device(), Device::Cpu);
+        // keeping the 48 largest of 64 values per row drops the smallest ones, which are near zero
+        let max_error = (&ret - &truth).abs().max().double_value(&[]);
+        let max_value = truth.abs().max().double_value(&[]);
+        assert!(
+            max_error < 0.25 * max_value,
+            "max error {max_error} for values up to {max_value}"
+        );
+
+        // nothing to scatter
+        let empty_idx = Tensor::zeros([16, 0], (Kind::Uint8, Device::Cpu));
+        let empty_val = Tensor::zeros([16, 0], (Kind::Float, Device::Cpu));
+        let ret = CompressDCT::decompress_to(
+            &empty_idx,
+            &empty_val,
+            &xshape,
+            totalk,
+            Kind::Float,
+            Device::Cpu,
+        )
+        .unwrap();
+        assert!(ret.equal(&Tensor::zeros([16, 64], (Kind::Float, Device::Cpu))));
+
+        assert!(matches!(
+            CompressDCT::decompress_to(
+                &idx,
+                &val.slice(0, 0, 8, 1),
+                &xshape,
+                totalk,
+                Kind::Float,
+                Device::Cpu
+            ),
+            Err(DecompressError::ShapeMismatch { .. })
+        ));
+    }
+
     #[test]
     fn test_encode_1d() {
         let a = Tensor::arange(8, (Kind::Float, Device::Cpu));
@@ -1163,6 +1297,79 @@ mod tests {
 
         assert!(input.sign().equal(&unquant));
     }
+
+    #[test]
+    fn test_scaled_result_discounts_values() {
+        let result = DistroResult {
+            sparse_idx: Tensor::from_slice(&[0i64, 1, 2]),
+            sparse_val: Tensor::from_slice(&[1.0f32, -2.0, 4.0]),
+            xshape: vec![3],
+            totalk: 3,
+            stats: None,
+        };
+        let scaled = result.scaled(0.5);
+        assert!(scaled
+            .sparse_val
+            .equal(&Tensor::from_slice(&[0.5f32, -1.0, 2.0])));
+        assert!(scaled.sparse_idx.equal(&result.sparse_idx));
+
+        // 1-bit results are unpacked to their signs before being discounted
+        let signs = DistroResult {
+            sparse_val: Distro::quantize_nozeros_tensor_to_boolean_sign(&result.sparse_val),
+            ..result
+        };
+        assert!(signs
+            .scaled(0.25)
+            .sparse_val
+            .equal(&Tensor::from_slice(&[0.25f32, -0.25, 0.25])));
+    }
+
+    #[test]
+    fn test_same_config_steps_identically() {
+        set_torch_rng_seed();
+        let config = DistroConfig {
+            clip_grad_norm: None,
+            weight_decay: Some(0.1),
+            compression_decay: 0.999,
+            compression_topk: 4,
+            compression_chunk: 8,
+            quantize_1bit: false,
+            momentum: 0.9,
+            dampening: 0.0,
+            nesterov: true,
+        };
+        let input = Tensor::randn([4, 16], (Kind::Float, Device::Cpu));
+        let initial = tch::nn::VarStore::new(Device::Cpu);
+        let _ = tch::nn::linear(initial.root() / "layer", 16, 8, Default::default());
+
+        let step = || {
+            let mut vs = tch::nn::VarStore::new(Device::Cpu);
+            let layer = tch::nn::linear(vs.root() / "layer", 16, 8, Default::default());
+            vs.copy(&initial).unwrap();
+            let mut distro = Distro::from_config(&vs, &config, None);
+            tch::nn::Module::forward(&layer, &input)
+                .square()
+                .mean(Kind::Float)
+                .backward();
+            let results = distro.generate(&[], 0.0, 1e-2, false);
+            distro.apply(std::slice::from_ref(&results), 1e-2);
+            (results, vs)
+        };
+        let (results_a, vs_a) = step();
+        let (results_b, vs_b) = step();
+
+        assert_eq!(results_a.len(), results_b.len());
+        for (a, b) in results_a.iter().zip(&results_b) {
+            assert!(a.sparse_idx.equal(&b.sparse_idx));
+            assert!(a.sparse_val.equal(&b.sparse_val));
+        }
+        let variables_b = vs_b.variables();
+        for (name, a) in vs_a.variables() {
+            assert!(a.equal(&variables_b[&name]), "{name} differs");
+        }
+        // and the step actually changed something
+        assert!(!vs_a.variables()["layer.weight"].equal(&initial.variables()["layer.weight"]));
+    }
 }
 
 #[cfg(test)]
@@ -1315,6 +1522,9 @@ mod tp_tests {
                 COMPRESSION_CHUNK,
                 COMPRESSION_TOPK,
                 WEIGHT_DECAY,
+                0.0,
+                0.0,
+                false,
                 None,
             );
 
@@ -1427,6 +1637,9 @@ mod tp_tests {
                         COMPRESSION_CHUNK,
                         COMPRESSION_TOPK,
                         WEIGHT_DECAY,
+                        0.0,
+                        0.0,
+                        false,
                         Some(comm.clone()),
                     );
 
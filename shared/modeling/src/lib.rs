@@ -4,6 +4,7 @@ mod auto_model;
 mod auto_tokenizer;
 mod batcher;
 mod causal_language_model;
+mod device;
 mod distro;
 mod dummy;
 mod fp32_gradient_accumulator;
@@ -13,7 +14,9 @@ mod rms_norm;
 mod rope;
 mod safetensor_utils;
 mod sampling;
+mod sanity_check;
 mod tensor_parallelism;
+mod tiny_model;
 mod token_output_stream;
 mod trainer;
 
@@ -21,14 +24,18 @@ pub use attention::CausalSelfAttention;
 pub use auto_config::{
     AttentionImplementation, AutoConfig, ModelConfig, ModelLoadError, PretrainedSource,
 };
-pub use auto_model::auto_model_for_causal_lm_from_pretrained;
-pub use auto_tokenizer::{auto_tokenizer, AutoTokenizerError};
+pub use auto_model::{
+    auto_model_for_causal_lm_from_pretrained, register_causal_lm, registered_model_types,
+    CausalLMBuilder, CausalLMLoadArgs,
+};
+pub use auto_tokenizer::{auto_tokenizer, tokenizer_eos_token_ids, AutoTokenizerError};
 pub use batcher::Batcher;
 pub use causal_language_model::{
-    CausalLM, CausalLanguageModel, EosToks, LanguageModelBuilder, LanguageModelConfig,
-    LanguageModelForward,
+    check_tensor_parallelism, CausalLM, CausalLanguageModel, EosToks, LanguageModelBuilder,
+    LanguageModelConfig, LanguageModelForward, SpecialTokens, StopCondition,
 };
-pub use distro::{CompressDCT, Distro, DistroResult, TransformDCT};
+pub use device::{cuda_or_cpu_fallback, DeviceSelectionError};
+pub use distro::{CompressDCT, DecompressError, Distro, DistroResult, TransformDCT};
 pub use dummy::{get_dummy_parameters, DummyModel};
 pub use fp32_gradient_accumulator::Fp32GradientAccumulator;
 pub use models::*;
@@ -36,18 +43,20 @@ pub use optimizer::Optimizer;
 pub use rms_norm::RMSNorm;
 pub use rope::{default_rope, rotate_half, yarn_get_mscale, RoPECache, RoPEConfig, RoPEType};
 pub use safetensor_utils::{
-    load_safetensors_into_variables, save_tensors_into_safetensors, LoadSafetensorsError,
-    SaveSafetensorsError,
+    check_safetensors_against_config, load_safetensors_into_variables,
+    save_tensors_into_safetensors, CheckpointMismatch, LoadSafetensorsError, SaveSafetensorsError,
 };
 pub use sampling::{LogitsProcessor, Sampling};
+pub use sanity_check::{check_forward_numerics, NumericsError};
 pub use tensor_parallelism::{
     unsharded_cpu_variables, AllReduce, ColumnParallelLinear, Communicator, CommunicatorId,
     CudaSynchronize, ParallelExpandHeads, RMSNormParallelInput, ReduceType, RowParallelLinear,
 };
+pub use tiny_model::{seeded_parameters, tiny_llama_config, tiny_model_for_causal_lm};
 pub use token_output_stream::TokenOutputStream;
 pub use trainer::{
-    ApplyDistroResultError, Batch, BatchData, DataParallel, ParallelModels, TrainOutput, Trainer,
-    TrainerThreadCommunicationError,
+    ApplyDistroResultError, Batch, BatchData, DataParallel, MicroBatchLimiter, ParallelModels,
+    TrainOutput, Trainer, TrainerThreadCommunicationError,
 };
 
 #[allow(unused)]
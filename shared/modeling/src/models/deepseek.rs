@@ -1,11 +1,12 @@
 use crate::{
-    auto_config::UseSDPA, rotate_half, yarn_get_mscale, AttentionImplementation, AutoConfig,
-    CausalLanguageModel, ColumnParallelLinear, Communicator, CommunicatorId, EosToks,
-    LanguageModelConfig, LanguageModelForward, ModelConfig, ModelLoadError, ParallelExpandHeads,
-    PretrainedSource, RMSNorm, RoPECache, RoPEConfig, RoPEType, RowParallelLinear,
+    auto_config::{add_linear_shapes, UseSDPA},
+    rotate_half, yarn_get_mscale, AttentionImplementation, AutoConfig, CausalLanguageModel,
+    ColumnParallelLinear, Communicator, CommunicatorId, EosToks, LanguageModelConfig,
+    LanguageModelForward, ModelConfig, ModelLoadError, ParallelExpandHeads, PretrainedSource,
+    RMSNorm, RoPECache, RoPEConfig, RoPEType, RowParallelLinear, SpecialTokens,
 };
 use std::fmt::Debug;
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tch::{
     nn::{
         self,
@@ -53,6 +54,7 @@ pub struct DeepseekConfig {
     pub qk_rope_head_dim: Option<usize>,
     pub v_head_dim: Option<usize>,
     pub attention_bias: Option<bool>,
+    pub mlp_bias: Option<bool>,
     // MoE
     pub n_routed_experts: Option<usize>,
     pub num_experts_per_tok: Option<usize>,
@@ -384,12 +386,13 @@ impl MLP {
     fn new(vs: nn::Path, config: &DeepseekConfig, comm: Option<Arc<Communicator>>) -> Self {
         let hidden_size = config.hidden_size as i64;
         let intermediate_size = config.intermediate_size as i64;
+        let mlp_bias = config.mlp_bias.unwrap_or(false);
 
         let gate_proj = ColumnParallelLinear::new(
             &vs / "gate_proj",
             hidden_size,
             intermediate_size,
-            false,
+            mlp_bias,
             false,
             comm.clone(),
         );
@@ -397,7 +400,7 @@ impl MLP {
             &vs / "up_proj",
             hidden_size,
             intermediate_size,
-            false,
+            mlp_bias,
             false,
             comm.clone(),
         );
@@ -405,7 +408,7 @@ impl MLP {
             &vs / "down_proj",
             intermediate_size,
             hidden_size,
-            false,
+            mlp_bias,
             true,
             comm,
         );
@@ -863,6 +866,7 @@ impl DeepseekForCausalLM {
         device: Option<Device>,
         tensor_parallelism_world: Option<(Arc<CommunicatorId>, usize, usize)>,
         override_max_position_embeddings: Option<usize>,
+        override_special_tokens: Option<SpecialTokens>,
     ) -> Result<Self, ModelLoadError> {
         Self::from_builder(
             Self::builder,
@@ -872,15 +876,13 @@ impl DeepseekForCausalLM {
             device,
             tensor_parallelism_world,
             override_max_position_embeddings,
+            override_special_tokens,
         )
     }
 }
 
-impl ModelConfig for DeepseekConfig {
-    // TODO: This is just a hacky solution to get the parameter names from the config
-    // but it is probably overkill. We should think about a better way to get them
-    // to make the p2p requests.
-    fn get_parameter_names(&self) -> Vec<String> {
+impl DeepseekConfig {
+    fn cpu_variables(&self) -> nn::VarStore {
         let mut variables: nn::VarStore = nn::VarStore::new(Device::Cpu);
         variables.set_kind(Kind::BFloat16);
         let _model = Deepseek::new(variables.root(), self, false, None);
@@ -896,8 +898,164 @@ impl ModelConfig for DeepseekConfig {
             c,
         );
 
-        let variables_lock = variables.variables_.lock().unwrap();
-        variables_lock.named_variables.keys().cloned().collect()
+        variables
+    }
+}
+
+impl DeepseekConfig {
+    fn add_mlp_shapes(
+        &self,
+        shapes: &mut HashMap<String, Vec<i64>>,
+        path: &str,
+        intermediate_size: i64,
+    ) {
+        let hidden_size = self.hidden_size as i64;
+        let bias = self.mlp_bias.unwrap_or(false);
+        for proj in ["gate_proj", "up_proj"] {
+            add_linear_shapes(
+                shapes,
+                &format!("{path}.{proj}"),
+                hidden_size,
+                intermediate_size,
+                bias,
+            );
+        }
+        add_linear_shapes(
+            shapes,
+            &format!("{path}.down_proj"),
+            intermediate_size,
+            hidden_size,
+            bias,
+        );
+    }
+
+    fn add_attention_shapes(&self, shapes: &mut HashMap<String, Vec<i64>>, path: &str) {
+        let hidden_size = self.hidden_size as i64;
+        let num_heads = self.num_attention_heads as i64;
+        let qk_rope_head_dim = self.qk_rope_head_dim.unwrap() as i64;
+        let qk_nope_head_dim = self.qk_nope_head_dim.unwrap() as i64;
+        let v_head_dim = self.v_head_dim.unwrap() as i64;
+        let q_head_dim = qk_nope_head_dim + qk_rope_head_dim;
+        let kv_lora_rank = self.kv_lora_rank.unwrap() as i64;
+        let bias = self.attention_bias.unwrap();
+
+        match self.q_lora_rank {
+            Some(q_lora_rank) => {
+                let q_lora_rank = q_lora_rank as i64;
+                add_linear_shapes(
+                    shapes,
+                    &format!("{path}.q_a_proj"),
+                    hidden_size,
+                    q_lora_rank,
+                    bias,
+                );
+                shapes.insert(format!("{path}.q_a_layernorm.weight"), vec![q_lora_rank]);
+                add_linear_shapes(
+                    shapes,
+                    &format!("{path}.q_b_proj"),
+                    q_lora_rank,
+                    num_heads * q_head_dim,
+                    false,
+                );
+            }
+            None => add_linear_shapes(
+                shapes,
+                &format!("{path}.q_proj"),
+                hidden_size,
+                num_heads * q_head_dim,
+                bias,
+            ),
+        }
+        add_linear_shapes(
+            shapes,
+            &format!("{path}.kv_a_proj_with_mqa"),
+            hidden_size,
+            kv_lora_rank + qk_rope_head_dim,
+            bias,
+        );
+        shapes.insert(format!("{path}.kv_a_layernorm.weight"), vec![kv_lora_rank]);
+        add_linear_shapes(
+            shapes,
+            &format!("{path}.kv_b_proj"),
+            kv_lora_rank,
+            num_heads * (qk_nope_head_dim + v_head_dim),
+            false,
+        );
+        add_linear_shapes(
+            shapes,
+            &format!("{path}.o_proj"),
+            num_heads * v_head_dim,
+            hidden_size,
+            bias,
+        );
+    }
+}
+
+impl ModelConfig for DeepseekConfig {
+    fn get_parameter_names(&self) -> Vec<String> {
+        self.get_parameter_shapes().into_keys().collect()
+    }
+
+    // mirrors the variables `cpu_variables` creates, without allocating them
+    fn get_parameter_shapes(&self) -> HashMap<String, Vec<i64>> {
+        let hidden_size = self.hidden_size as i64;
+        let vocab_size = self.vocab_size as i64;
+
+        let mut shapes = HashMap::new();
+        shapes.insert(
+            "model.embed_tokens.weight".to_string(),
+            vec![vocab_size, hidden_size],
+        );
+        shapes.insert("model.norm.weight".to_string(), vec![hidden_size]);
+        for layer in 0..self.num_hidden_layers {
+            let prefix = format!("model.layers.{layer}");
+            for norm in ["input_layernorm", "post_attention_layernorm"] {
+                shapes.insert(format!("{prefix}.{norm}.weight"), vec![hidden_size]);
+            }
+            self.add_attention_shapes(&mut shapes, &format!("{prefix}.self_attn"));
+
+            let mlp = format!("{prefix}.mlp");
+            let is_moe = self.n_routed_experts.is_some()
+                && layer >= self.first_k_dense_replace.unwrap()
+                && layer % self.moe_layer_freq.unwrap() == 0;
+            if !is_moe {
+                self.add_mlp_shapes(&mut shapes, &mlp, self.intermediate_size as i64);
+                continue;
+            }
+            let n_routed_experts = self.n_routed_experts.unwrap() as i64;
+            let moe_intermediate_size = self.moe_intermediate_size.unwrap() as i64;
+            for expert in 0..n_routed_experts {
+                self.add_mlp_shapes(
+                    &mut shapes,
+                    &format!("{mlp}.experts.{expert}"),
+                    moe_intermediate_size,
+                );
+            }
+            if let Some(n_shared_experts) = self.n_shared_experts {
+                self.add_mlp_shapes(
+                    &mut shapes,
+                    &format!("{mlp}.shared_experts"),
+                    moe_intermediate_size * n_shared_experts as i64,
+                );
+            }
+            shapes.insert(
+                format!("{mlp}.gate.weight"),
+                vec![n_routed_experts, hidden_size],
+            );
+            if self.topk_method == Some(TopKMethod::NoAuxTC) {
+                shapes.insert(
+                    format!("{mlp}.gate.e_score_correction_bias"),
+                    vec![n_routed_experts],
+                );
+            }
+        }
+        add_linear_shapes(&mut shapes, "lm_head", hidden_size, vocab_size, false);
+        shapes
+    }
+
+    fn random_init_parameters(&self, seed: i64) -> HashMap<String, Tensor> {
+        tch::manual_seed(seed);
+        self.cpu_variables().variables()
     }
 }
 
@@ -951,6 +1109,10 @@ impl LanguageModelConfig for DeepseekConfig {
         self.num_attention_heads
     }
 
+    fn num_hidden_layers(&self) -> usize {
+        self.num_hidden_layers
+    }
+
     fn rope_theta(&self) -> f32 {
         self.rope_theta
     }
@@ -966,4 +1128,68 @@ impl LanguageModelConfig for DeepseekConfig {
     fn eos_token_ids(&self) -> Option<crate::EosToks> {
         self.eos_token_id.clone()
     }
+
+    fn set_bos_token_id(&mut self, set: i64) {
+        self.bos_token_id = Some(set);
+    }
+
+    fn set_eos_token_ids(&mut self, set: EosToks) {
+        self.eos_token_id = Some(set);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny_config(q_lora_rank: Option<usize>) -> DeepseekConfig {
+        serde_json::from_value(serde_json::json!({
+            "hidden_size": 16,
+            "intermediate_size": 32,
+            "vocab_size": 64,
+            "num_hidden_layers": 3,
+            "num_attention_heads": 2,
+            "rms_norm_eps": 1e-6,
+            "rope_theta": 10000.0,
+            "max_position_embeddings": 64,
+            "tie_word_embeddings": false,
+            "bos_token_id": 1,
+            "eos_token_id": 2,
+            "rope_scaling": null,
+            "q_lora_rank": q_lora_rank,
+            "kv_lora_rank": 8,
+            "qk_nope_head_dim": 4,
+            "qk_rope_head_dim": 4,
+            "v_head_dim": 6,
+            "attention_bias": false,
+            "mlp_bias": false,
+            "n_routed_experts": 4,
+            "num_experts_per_tok": 2,
+            "moe_intermediate_size": 8,
+            "routed_scaling_factor": 1.0,
+            "n_group": 2,
+            "topk_group": 1,
+            "n_shared_experts": 1,
+            "first_k_dense_replace": 1,
+            "moe_layer_freq": 1,
+            "scoring_func": "sigmoid",
+            "topk_method": "noaux_tc",
+            "norm_topk_prob": true
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parameter_shapes_match_the_model() {
+        // covers both dense and MoE layers, with and without the low-rank query projection
+        for config in [tiny_config(Some(8)), tiny_config(None)] {
+            let allocated: HashMap<_, _> = config
+                .cpu_variables()
+                .variables()
+                .into_iter()
+                .map(|(name, tensor)| (name, tensor.size()))
+                .collect();
+            assert_eq!(config.get_parameter_shapes(), allocated);
+        }
+    }
 }
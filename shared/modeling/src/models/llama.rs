@@ -1,10 +1,13 @@
 use crate::{
-    auto_config::UseSDPA, default_rope, tensor_parallelism::Communicator, AttentionImplementation,
-    AutoConfig, CausalLanguageModel, CausalSelfAttention, ColumnParallelLinear, CommunicatorId,
-    EosToks, LanguageModelConfig, LanguageModelForward, ModelConfig, ModelLoadError,
-    PretrainedSource, RMSNorm, RoPECache, RoPEConfig, RowParallelLinear,
+    auto_config::{add_linear_shapes, UseSDPA},
+    default_rope,
+    tensor_parallelism::Communicator,
+    AttentionImplementation, AutoConfig, CausalLanguageModel, CausalSelfAttention,
+    ColumnParallelLinear, CommunicatorId, EosToks, LanguageModelConfig, LanguageModelForward,
+    ModelConfig, ModelLoadError, PretrainedSource, RMSNorm, RoPECache, RoPEConfig,
+    RowParallelLinear, SpecialTokens,
 };
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tch::{
     nn::{self, Module},
     Device, Kind, Tensor,
@@ -26,6 +29,10 @@ pub struct LlamaConfig {
     pub rope_scaling: Option<RoPEConfig>,
     pub max_position_embeddings: usize,
     pub tie_word_embeddings: bool,
+    #[serde(default)]
+    pub attention_bias: bool,
+    #[serde(default)]
+    pub mlp_bias: bool,
 }
 
 impl LlamaConfig {
@@ -48,6 +55,8 @@ impl LlamaConfig {
             rope_scaling: None,
             max_position_embeddings: 2048,
             tie_word_embeddings: false,
+            attention_bias: false,
+            mlp_bias: false,
         }
     }
 }
@@ -60,7 +69,13 @@ struct Mlp {
 }
 
 impl Mlp {
-    fn new(vs: nn::Path, n_embd: i64, n_hidden: i64, comm: Option<Arc<Communicator>>) -> Self {
+    fn new(
+        vs: nn::Path,
+        n_embd: i64,
+        n_hidden: i64,
+        bias: bool,
+        comm: Option<Arc<Communicator>>,
+    ) -> Self {
         let tp_size = comm.as_ref().map(|x| x.size()).unwrap_or(1);
         assert_eq!(
             n_hidden % tp_size,
@@ -72,20 +87,14 @@ impl Mlp {
             &vs / "gate_proj",
             n_embd,
             n_hidden,
-            false,
-            false,
-            comm.clone(),
-        );
-        let up_proj = ColumnParallelLinear::new(
-            &vs / "up_proj",
-            n_embd,
-            n_hidden,
-            false,
+            bias,
             false,
             comm.clone(),
         );
+        let up_proj =
+            ColumnParallelLinear::new(&vs / "up_proj", n_embd, n_hidden, bias, false, comm.clone());
         let down_proj =
-            RowParallelLinear::new(&vs / "down_proj", n_hidden, n_embd, false, true, comm);
+            RowParallelLinear::new(&vs / "down_proj", n_hidden, n_embd, bias, true, comm);
         Self {
             gate_proj,
             up_proj,
@@ -129,6 +138,7 @@ impl Block {
                 .unwrap_or(config.num_attention_heads) as i64,
             config.hidden_size as i64,
             (config.max_position_embeddings + 1) as i64,
+            config.attention_bias,
             use_sdpa,
             comm.clone(),
         );
@@ -141,6 +151,7 @@ impl Block {
             &vs / "mlp",
             config.hidden_size as i64,
             config.intermediate_size as i64,
+            config.mlp_bias,
             comm,
         );
         Self {
@@ -237,6 +248,7 @@ impl LlamaForCausalLM {
         device: Option<Device>,
         tensor_parallelism_world: Option<(Arc<CommunicatorId>, usize, usize)>,
         override_max_position_embeddings: Option<usize>,
+        override_special_tokens: Option<SpecialTokens>,
     ) -> Result<Self, ModelLoadError> {
         Self::from_builder(
             Self::builder,
@@ -246,15 +258,13 @@ impl LlamaForCausalLM {
             device,
             tensor_parallelism_world,
             override_max_position_embeddings,
+            override_special_tokens,
         )
     }
 }
 
-impl ModelConfig for LlamaConfig {
-    // TODO: This is just a hacky solution to get the parameter names from the config
-    // but it is probably overkill. We should think about a better way to get them
-    // to make the p2p requests.
-    fn get_parameter_names(&self) -> Vec<String> {
+impl LlamaConfig {
+    fn cpu_variables(&self) -> nn::VarStore {
         let mut variables: nn::VarStore = nn::VarStore::new(Device::Cpu);
         variables.set_kind(Kind::BFloat16);
         let _model = Llama::new(variables.root(), self, false, None);
@@ -270,8 +280,91 @@ impl ModelConfig for LlamaConfig {
             c,
         );
 
-        let variables_lock = variables.variables_.lock().unwrap();
-        variables_lock.named_variables.keys().cloned().collect()
+        variables
+    }
+}
+
+impl ModelConfig for LlamaConfig {
+    fn get_parameter_names(&self) -> Vec<String> {
+        self.get_parameter_shapes().into_keys().collect()
+    }
+
+    // mirrors the variables `cpu_variables` creates, without allocating them
+    fn get_parameter_shapes(&self) -> HashMap<String, Vec<i64>> {
+        let hidden_size = self.hidden_size as i64;
+        let intermediate_size = self.intermediate_size as i64;
+        let vocab_size = self.vocab_size as i64;
+        let head_dim = hidden_size / self.num_attention_heads as i64;
+        let size_q = head_dim * self.num_attention_heads as i64;
+        let size_kv = head_dim * self.num_key_value_heads() as i64;
+
+        let mut shapes = HashMap::new();
+        shapes.insert(
+            "model.embed_tokens.weight".to_string(),
+            vec![vocab_size, hidden_size],
+        );
+        shapes.insert("model.norm.weight".to_string(), vec![hidden_size]);
+        for layer in 0..self.num_hidden_layers {
+            let prefix = format!("model.layers.{layer}");
+            for norm in ["input_layernorm", "post_attention_layernorm"] {
+                shapes.insert(format!("{prefix}.{norm}.weight"), vec![hidden_size]);
+            }
+            let attn = format!("{prefix}.self_attn");
+            let bias = self.attention_bias;
+            add_linear_shapes(
+                &mut shapes,
+                &format!("{attn}.q_proj"),
+                hidden_size,
+                size_q,
+                bias,
+            );
+            add_linear_shapes(
+                &mut shapes,
+                &format!("{attn}.k_proj"),
+                hidden_size,
+                size_kv,
+                bias,
+            );
+            add_linear_shapes(
+                &mut shapes,
+                &format!("{attn}.v_proj"),
+                hidden_size,
+                size_kv,
+                bias,
+            );
+            add_linear_shapes(
+                &mut shapes,
+                &format!("{attn}.o_proj"),
+                size_q,
+                hidden_size,
+                bias,
+            );
+            let mlp = format!("{prefix}.mlp");
+            let bias = self.mlp_bias;
+            for proj in ["gate_proj", "up_proj"] {
+                add_linear_shapes(
+                    &mut shapes,
+                    &format!("{mlp}.{proj}"),
+                    hidden_size,
+                    intermediate_size,
+                    bias,
+                );
+            }
+            add_linear_shapes(
+                &mut shapes,
+                &format!("{mlp}.down_proj"),
+                intermediate_size,
+                hidden_size,
+                bias,
+            );
+        }
+        add_linear_shapes(&mut shapes, "lm_head", hidden_size, vocab_size, false);
+        shapes
+    }
+
+    fn random_init_parameters(&self, seed: i64) -> HashMap<String, Tensor> {
+        tch::manual_seed(seed);
+        self.cpu_variables().variables()
     }
 }
 
@@ -325,6 +418,10 @@ impl LanguageModelConfig for LlamaConfig {
         self.num_attention_heads
     }
 
+    fn num_hidden_layers(&self) -> usize {
+        self.num_hidden_layers
+    }
+
     fn rope_theta(&self) -> f32 {
         self.rope_theta
     }
@@ -340,4 +437,129 @@ impl LanguageModelConfig for LlamaConfig {
     fn eos_token_ids(&self) -> Option<EosToks> {
         self.eos_token_id.clone()
     }
+
+    fn set_bos_token_id(&mut self, set: i64) {
+        self.bos_token_id = Some(set);
+    }
+
+    fn set_eos_token_ids(&mut self, set: EosToks) {
+        self.eos_token_id = Some(set);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CausalLM;
+    use std::collections::HashSet;
+
+    const TINY_CONFIG: &str = r#"{
+        "hidden_size": 8,
+        "intermediate_size": 16,
+        "vocab_size": 32,
+        "num_hidden_layers": 2,
+        "num_attention_heads": 2,
+        "num_key_value_heads": 2,
+        "rms_norm_eps": 1e-5,
+        "bos_token_id": 1,
+        "eos_token_id": 2,
+        "rope_scaling": null,
+        "max_position_embeddings": 64,
+        "tie_word_embeddings": false
+    }"#;
+
+    fn parameter_names(config: serde_json::Value) -> HashSet<String> {
+        let config: LlamaConfig = serde_json::from_value(config).unwrap();
+        config.get_parameter_names().into_iter().collect()
+    }
+
+    #[test]
+    fn test_bias_flags_control_parameters() {
+        let unbiased: serde_json::Value = serde_json::from_str(TINY_CONFIG).unwrap();
+        let mut biased = unbiased.clone();
+        biased["attention_bias"] = true.into();
+        biased["mlp_bias"] = true.into();
+
+        let unbiased = parameter_names(unbiased);
+        let biased = parameter_names(biased);
+        assert!(!unbiased.iter().any(|name| name.ends_with(".bias")));
+
+        let mut expected_biases = HashSet::new();
+        for layer in 0..2 {
+            for proj in ["q_proj", "k_proj", "v_proj", "o_proj"] {
+                expected_biases.insert(format!("model.layers.{layer}.self_attn.{proj}.bias"));
+            }
+            for proj in ["gate_proj", "up_proj", "down_proj"] {
+                expected_biases.insert(format!("model.layers.{layer}.mlp.{proj}.bias"));
+            }
+        }
+        assert_eq!(
+            biased
+                .difference(&unbiased)
+                .cloned()
+                .collect::<HashSet<_>>(),
+            expected_biases
+        );
+        assert!(unbiased.is_subset(&biased));
+    }
+
+    #[test]
+    fn test_parameter_shapes_match_the_model() {
+        let mut config: serde_json::Value = serde_json::from_str(TINY_CONFIG).unwrap();
+        config["num_key_value_heads"] = 1.into();
+        config["attention_bias"] = true.into();
+        let config: LlamaConfig = serde_json::from_value(config).unwrap();
+
+        let allocated: HashMap<_, _> = config
+            .cpu_variables()
+            .variables()
+            .into_iter()
+            .map(|(name, tensor)| (name, tensor.size()))
+            .collect();
+        assert_eq!(config.get_parameter_shapes(), allocated);
+    }
+
+    #[test]
+    fn test_special_tokens_override() {
+        let config: LlamaConfig = serde_json::from_str(TINY_CONFIG).unwrap();
+        let parameters = Arc::new(config.random_init_parameters(0));
+        let source = PretrainedSource::ConfigAndTensors(config, parameters);
+        let load = |special_tokens| {
+            LlamaForCausalLM::from_pretrained(
+                &source,
+                None,
+                None,
+                Some(Device::Cpu),
+                None,
+                None,
+                special_tokens,
+            )
+            .unwrap()
+        };
+
+        let model = load(None);
+        assert_eq!(model.bos_token_id(), Some(1));
+        let eos = model.eos_token_ids().unwrap();
+        assert!(eos.contains(2));
+        assert!(!eos.contains(7));
+
+        // e.g. a chat template's end-of-turn token
+        let model = load(Some(SpecialTokens {
+            bos_token_id: Some(3),
+            eos_token_id: Some(EosToks::Multiple(vec![7, 8])),
+        }));
+        assert_eq!(model.bos_token_id(), Some(3));
+        let eos = model.eos_token_ids().unwrap();
+        assert!(eos.contains(7));
+        assert!(eos.contains(8));
+        assert!(!eos.contains(2));
+
+        // tokens not overridden come from the config
+        let model = load(Some(SpecialTokens {
+            bos_token_id: None,
+            eos_token_id: Some(EosToks::Single(7)),
+        }));
+        assert_eq!(model.bos_token_id(), Some(1));
+        assert!(model.eos_token_ids().unwrap().contains(7));
+    }
 }
@@ -1,6 +1,9 @@
 use crate::{CausalLM, Distro};
 use psyche_core::OptimizerDefinition;
-use tch::COptimizer;
+use tch::{nn::VarStore, COptimizer};
+
+const DECAY_GROUP: usize = 0;
+const NO_DECAY_GROUP: usize = 1;
 
 pub enum Optimizer {
     Torch {
@@ -23,46 +26,107 @@ impl Optimizer {
                 weight_decay,
                 eps,
                 clip_grad_norm,
+                exclude_1d_from_weight_decay,
             } => Self::Torch {
-                optimizer: {
-                    let mut adamw = COptimizer::adamw(
-                        1.0e-1,
-                        betas[0] as f64,
-                        betas[1] as f64,
-                        weight_decay as f64,
-                        eps as f64,
-                        false,
-                    )
-                    .unwrap();
-                    for (_, tensor) in model.variables().variables() {
-                        //let tensor = var.logical_tensor();
-                        adamw.add_parameters(&tensor, 0).unwrap();
-                    }
-                    adamw
-                },
-                clip_grad_norm,
-            },
-            OptimizerDefinition::Distro {
-                clip_grad_norm,
-                weight_decay,
-                compression_decay,
-                compression_topk,
-                compression_chunk,
-                quantize_1bit,
-            } => Self::Distro {
-                optimizer: Distro::new(
+                optimizer: adamw(
                     model.variables(),
-                    compression_decay as f64,
-                    compression_chunk as i64,
-                    compression_topk as i64,
-                    weight_decay.unwrap_or(0.0) as f64,
-                    model.communicator(),
-                )
-                .into(),
+                    betas,
+                    weight_decay,
+                    eps,
+                    exclude_1d_from_weight_decay,
+                ),
                 clip_grad_norm,
-                quantize_1bit,
+            },
+            OptimizerDefinition::Distro(config) => Self::Distro {
+                optimizer: Distro::from_config(model.variables(), &config, model.communicator())
+                    .into(),
+                clip_grad_norm: config.clip_grad_norm,
+                quantize_1bit: config.quantize_1bit,
             },
             OptimizerDefinition::Dummy => Self::Null,
         }
     }
 }
+
+fn adamw(
+    variables: &VarStore,
+    betas: [f32; 2],
+    weight_decay: f32,
+    eps: f32,
+    exclude_1d_from_weight_decay: bool,
+) -> COptimizer {
+    let mut adamw = COptimizer::adamw(
+        1.0e-1,
+        betas[0] as f64,
+        betas[1] as f64,
+        weight_decay as f64,
+        eps as f64,
+        false,
+    )
+    .unwrap();
+    let mut no_decay = false;
+    for (_, tensor) in variables.variables() {
+        let group = match exclude_1d_from_weight_decay && tensor.dim() <= 1 {
+            true => {
+                no_decay = true;
+                NO_DECAY_GROUP
+            }
+            false => DECAY_GROUP,
+        };
+        adamw.add_parameters(&tensor, group).unwrap();
+    }
+    if no_decay {
+        adamw.set_weight_decay_group(NO_DECAY_GROUP, 0.0).unwrap();
+    }
+    adamw
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tch::{nn, Device, Kind, Tensor};
+
+    const LR: f64 = 0.1;
+    const WEIGHT_DECAY: f32 = 0.5;
+
+    // with zero gradients, the only thing an AdamW step does is decay the weights
+    fn step_with_zero_grads(exclude_1d_from_weight_decay: bool) -> (Tensor, Tensor, Tensor) {
+        let vs = VarStore::new(Device::Cpu);
+        let root = vs.root();
+        let weight = root.var("mlp.weight", &[4, 4], nn::Init::Const(1.0));
+        let bias = root.var("mlp.bias", &[4], nn::Init::Const(1.0));
+        let norm = root.var("norm.weight", &[4], nn::Init::Const(1.0));
+
+        let mut optimizer = adamw(
+            &vs,
+            [0.9, 0.95],
+            WEIGHT_DECAY,
+            1e-8,
+            exclude_1d_from_weight_decay,
+        );
+        optimizer.set_learning_rate(LR).unwrap();
+        let loss = weight.sum(Kind::Float) + bias.sum(Kind::Float) + norm.sum(Kind::Float);
+        loss.multiply_scalar(0.0).backward();
+        optimizer.step().unwrap();
+
+        (weight, bias, norm)
+    }
+
+    #[test]
+    fn test_1d_params_excluded_from_weight_decay() {
+        let (weight, bias, norm) = step_with_zero_grads(true);
+        let decayed = 1.0 - LR * WEIGHT_DECAY as f64;
+        assert!(weight.allclose(&weight.full_like(decayed), 1e-6, 1e-6, false));
+        assert!(bias.allclose(&bias.ones_like(), 1e-6, 1e-6, false));
+        assert!(norm.allclose(&norm.ones_like(), 1e-6, 1e-6, false));
+    }
+
+    #[test]
+    fn test_uniform_weight_decay_by_default() {
+        let (weight, bias, norm) = step_with_zero_grads(false);
+        let decayed = 1.0 - LR * WEIGHT_DECAY as f64;
+        for tensor in [weight, bias, norm] {
+            assert!(tensor.allclose(&tensor.full_like(decayed), 1e-6, 1e-6, false));
+        }
+    }
+}
@@ -1,16 +1,18 @@
+use crate::ModelConfig;
 use safetensors::{slice::TensorIndexer, SafeTensors};
 use serde_json::json;
 use std::{
-    collections::{HashMap, HashSet},
-    io,
-    ops::Bound,
-    path::PathBuf,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, io,
+    ops::{Bound, Deref},
+    path::{Path, PathBuf},
 };
 use tch::{
     nn::{Shard, VarStore},
     Device, Kind, Tensor,
 };
 use thiserror::Error;
+use tracing::warn;
 
 const MAX_SAFETENSOR_PART_SIZE: usize = 1024 * 1024 * 1024 * 5;
 
@@ -40,9 +42,51 @@ pub enum LoadSafetensorsError {
     MissingVariables(HashSet<String>),
 }
 
+/// The bytes of a safetensors file, mapped where the platform allows it so loading a checkpoint
+/// doesn't need host RAM for all of it, and read into memory where it doesn't.
+enum SafetensorsContent {
+    Mapped(memmap2::Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for SafetensorsContent {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SafetensorsContent::Mapped(mmap) => mmap,
+            SafetensorsContent::Read(bytes) => bytes,
+        }
+    }
+}
+
+fn open_safetensors(path: &Path, mmap: bool) -> io::Result<SafetensorsContent> {
+    let file = std::fs::File::open(path)?;
+    if mmap {
+        match unsafe { memmap2::MmapOptions::new().map(&file) } {
+            Ok(mapped) => return Ok(SafetensorsContent::Mapped(mapped)),
+            Err(err) => warn!(
+                "Can't memory-map {}, reading it into memory instead: {err}",
+                path.display()
+            ),
+        }
+    }
+    std::fs::read(path).map(SafetensorsContent::Read)
+}
+
+/// Copies every variable of `vs` from the safetensors among `repo_files`, one tensor at a time,
+/// so besides the variables themselves only one (sharded) tensor is ever held in memory.
 pub fn load_safetensors_into_variables(
     vs: &mut VarStore,
     repo_files: &[PathBuf],
+) -> Result<(), LoadSafetensorsError> {
+    load_into_variables(vs, repo_files, true)
+}
+
+fn load_into_variables(
+    vs: &mut VarStore,
+    repo_files: &[PathBuf],
+    mmap: bool,
 ) -> Result<(), LoadSafetensorsError> {
     let _no_grad = tch::no_grad_guard();
     let mut unmatched = vs.variables().keys().cloned().collect::<HashSet<_>>();
@@ -50,8 +94,7 @@ pub fn load_safetensors_into_variables(
         x.extension()
             .is_some_and(|y| y.eq_ignore_ascii_case("safetensors"))
     }) {
-        let file = std::fs::File::open(path)?;
-        let content = unsafe { memmap2::MmapOptions::new().map(&file)? };
+        let content = open_safetensors(path, mmap)?;
         let safetensors = SafeTensors::deserialize(&content)?;
         let mut variables = vs.variables_.lock().unwrap();
         let shards = variables.shards.clone();
@@ -116,6 +159,100 @@ pub fn load_safetensors_into_variables(
     Ok(())
 }
 
+/// A tensor that's in a checkpoint but not in the model, or the other way around, or in both with different shapes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointMismatch {
+    Missing {
+        name: String,
+        expected: Vec<i64>,
+    },
+    Unexpected {
+        name: String,
+        shape: Vec<i64>,
+    },
+    Shape {
+        name: String,
+        expected: Vec<i64>,
+        actual: Vec<i64>,
+    },
+}
+
+impl CheckpointMismatch {
+    pub fn name(&self) -> &str {
+        match self {
+            CheckpointMismatch::Missing { name, .. }
+            | CheckpointMismatch::Unexpected { name, .. }
+            | CheckpointMismatch::Shape { name, .. } => name,
+        }
+    }
+}
+
+impl fmt::Display for CheckpointMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointMismatch::Missing { name, expected } => {
+                write!(
+                    f,
+                    "{name}: missing from checkpoint, expected shape {expected:?}"
+                )
+            }
+            CheckpointMismatch::Unexpected { name, shape } => {
+                write!(f, "{name}: shape {shape:?} in checkpoint, but not in model")
+            }
+            CheckpointMismatch::Shape {
+                name,
+                expected,
+                actual,
+            } => write!(f, "{name}: expected shape {expected:?}, got {actual:?}"),
+        }
+    }
+}
+
+/// Compares the names & shapes of the tensors in a checkpoint's safetensors to the parameters of the model described by `config`,
+/// without loading any tensor data. Returns every mismatch, sorted by tensor name, or nothing if the checkpoint fits.
+pub fn check_safetensors_against_config(
+    config: &impl ModelConfig,
+    repo_files: &[PathBuf],
+) -> Result<Vec<CheckpointMismatch>, LoadSafetensorsError> {
+    let mut actual = BTreeMap::new();
+    for path in repo_files.iter().filter(|x| {
+        x.extension()
+            .is_some_and(|y| y.eq_ignore_ascii_case("safetensors"))
+    }) {
+        let content = open_safetensors(path, true)?;
+        let safetensors = SafeTensors::deserialize(&content)?;
+        for (name, view) in safetensors.tensors() {
+            let shape: Vec<i64> = view.shape().iter().map(|&x| x as i64).collect();
+            actual.insert(name, shape);
+        }
+    }
+
+    let expected: BTreeMap<_, _> = config.get_parameter_shapes().into_iter().collect();
+    let mut mismatches: Vec<CheckpointMismatch> = expected
+        .iter()
+        .filter_map(|(name, expected)| match actual.get(name) {
+            None => Some(CheckpointMismatch::Missing {
+                name: name.clone(),
+                expected: expected.clone(),
+            }),
+            Some(actual) if actual != expected => Some(CheckpointMismatch::Shape {
+                name: name.clone(),
+                expected: expected.clone(),
+                actual: actual.clone(),
+            }),
+            Some(_) => None,
+        })
+        .collect();
+    mismatches.extend(
+        actual
+            .into_iter()
+            .filter(|(name, _)| !expected.contains_key(name))
+            .map(|(name, shape)| CheckpointMismatch::Unexpected { name, shape }),
+    );
+    mismatches.sort_by(|a, b| a.name().cmp(b.name()));
+    Ok(mismatches)
+}
+
 #[derive(Default)]
 struct FilePart {
     tensors: Vec<(String, Tensor)>,
@@ -206,3 +343,74 @@ pub fn save_tensors_into_safetensors(
         Ok(paths)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{seeded_parameters, tiny_llama_config};
+
+    fn zeroed_variables(parameters: &HashMap<String, Tensor>) -> VarStore {
+        let vs = VarStore::new(Device::Cpu);
+        for (name, tensor) in parameters {
+            let (path, leaf) = name.rsplit_once('.').unwrap();
+            let path = path.split('.').fold(vs.root(), |path, part| path / part);
+            path.zeros(leaf, &tensor.size());
+        }
+        vs
+    }
+
+    #[test]
+    fn test_mapped_and_read_checkpoints_load_the_same() {
+        let parameters = seeded_parameters(&tiny_llama_config(), 1234);
+        let dir = tempfile::tempdir().unwrap();
+        let files = save_tensors_into_safetensors(
+            parameters
+                .iter()
+                .map(|(name, tensor)| (name.clone(), tensor.shallow_clone()))
+                .collect(),
+            dir.path().to_owned(),
+        )
+        .unwrap();
+
+        let mut mapped = zeroed_variables(&parameters);
+        load_into_variables(&mut mapped, &files, true).unwrap();
+        let mut read = zeroed_variables(&parameters);
+        load_into_variables(&mut read, &files, false).unwrap();
+
+        let (mapped, read) = (mapped.variables(), read.variables());
+        assert_eq!(mapped.len(), parameters.len());
+        for (name, tensor) in &parameters {
+            assert!(
+                mapped[name].equal(tensor),
+                "{name} loaded wrong when mapped"
+            );
+            assert!(read[name].equal(tensor), "{name} loaded wrong when read");
+        }
+    }
+
+    #[test]
+    fn test_missing_variables_are_reported() {
+        let mut parameters = seeded_parameters(&tiny_llama_config(), 1234);
+        let dir = tempfile::tempdir().unwrap();
+        let files = save_tensors_into_safetensors(
+            parameters
+                .iter()
+                .map(|(name, tensor)| (name.clone(), tensor.shallow_clone()))
+                .collect(),
+            dir.path().to_owned(),
+        )
+        .unwrap();
+
+        parameters.insert(
+            "extra.weight".to_string(),
+            Tensor::zeros([2], (Kind::Float, Device::Cpu)),
+        );
+        let mut vs = zeroed_variables(&parameters);
+        match load_safetensors_into_variables(&mut vs, &files) {
+            Err(LoadSafetensorsError::MissingVariables(missing)) => {
+                assert_eq!(missing, HashSet::from(["extra.weight".to_string()]))
+            }
+            other => panic!("expected the extra variable to be missing, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::CausalLM;
+use tch::{Kind, Tensor};
+use thiserror::Error;
+
+const PROBE_LEN: i64 = 16;
+/// Logits past this are a sign of corrupted (e.g. misinterpreted dtype) weights, not a real model.
+const MAX_PLAUSIBLE_LOGIT: f64 = 1e4;
+
+#[derive(Debug, Error)]
+pub enum NumericsError {
+    #[error("forward pass produced non-finite logits")]
+    NonFinite,
+
+    #[error("forward pass produced implausibly large logits (max |logit| = {0})")]
+    OutOfRange(f64),
+
+    #[error("torch error: {0}")]
+    Torch(#[from] tch::TchError),
+}
+
+/// Runs a forward pass on a fixed probe input and checks that the logits are finite and plausibly sized.
+/// Meant to catch silently broken weights right after loading a checkpoint.
+pub fn check_forward_numerics(model: &mut dyn CausalLM) -> Result<(), NumericsError> {
+    let _no_grad = tch::no_grad_guard();
+    let probe = Tensor::arange(PROBE_LEN, (Kind::Int64, model.device())).unsqueeze(0);
+    let (logits, _) = model.forward(&probe, None, None);
+    let logits = logits.to_kind(Kind::Float);
+
+    if !bool::try_from(logits.isfinite().all())? {
+        return Err(NumericsError::NonFinite);
+    }
+    let max_logit = f64::try_from(logits.abs().max())?;
+    if max_logit > MAX_PLAUSIBLE_LOGIT {
+        return Err(NumericsError::OutOfRange(max_logit));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tiny_llama_config, LlamaConfig, LlamaForCausalLM, ModelConfig, PretrainedSource};
+    use std::{collections::HashMap, sync::Arc};
+    use tch::Device;
+
+    fn load(config: LlamaConfig, parameters: HashMap<String, Tensor>) -> LlamaForCausalLM {
+        LlamaForCausalLM::from_pretrained(
+            &PretrainedSource::ConfigAndTensors(config, Arc::new(parameters)),
+            None,
+            None,
+            Some(Device::Cpu),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_healthy_checkpoint_passes() {
+        let config = tiny_llama_config();
+        let parameters = config.random_init_parameters(0);
+        let mut model = load(config, parameters);
+        check_forward_numerics(&mut model).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_checkpoint_fails() {
+        let config = tiny_llama_config();
+
+        let mut parameters = config.random_init_parameters(0);
+        let _ = parameters
+            .get_mut("model.layers.0.mlp.down_proj.weight")
+            .unwrap()
+            .fill_(f64::NAN);
+        let mut model = load(config.clone(), parameters);
+        assert!(matches!(
+            check_forward_numerics(&mut model),
+            Err(NumericsError::NonFinite)
+        ));
+
+        // e.g. bf16 bytes read back as the wrong dtype
+        let mut parameters = config.random_init_parameters(0);
+        let _ = parameters.get_mut("lm_head.weight").unwrap().fill_(1e30);
+        let mut model = load(config, parameters);
+        assert!(matches!(
+            check_forward_numerics(&mut model),
+            Err(NumericsError::OutOfRange(_))
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    AutoConfig, CausalLM, DeepseekForCausalLM, LlamaConfig, LlamaForCausalLM, ModelConfig,
+    ModelLoadError, PretrainedSource,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::{collections::HashMap, sync::Arc};
+use tch::{Device, Kind, Tensor};
+
+/// Standard deviation of the weights, as in HF's default `initializer_range`.
+const INITIALIZER_RANGE: f32 = 0.02;
+
+/// A real, but tiny, Llama. Small enough to run a forward pass on any CPU in milliseconds.
+pub fn tiny_llama_config() -> LlamaConfig {
+    LlamaConfig {
+        hidden_size: 16,
+        intermediate_size: 32,
+        vocab_size: 64,
+        num_hidden_layers: 2,
+        num_attention_heads: 2,
+        num_key_value_heads: Some(2),
+        max_position_embeddings: 128,
+        ..LlamaConfig::dummy()
+    }
+}
+
+/// Initializes every parameter of the model described by `config` from `seed`.
+///
+/// Unlike [`ModelConfig::random_init_parameters`], this doesn't use torch's global RNG,
+/// so it gives the same parameters no matter what else is running in the process.
+pub fn seeded_parameters(config: &impl ModelConfig, seed: u64) -> HashMap<String, Tensor> {
+    let mut shapes: Vec<(String, Vec<i64>)> = config.get_parameter_shapes().into_iter().collect();
+    // the rng is consumed in order, so that order must not depend on the hashmap's
+    shapes.sort();
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    // uniform in [-bound, bound) has a standard deviation of INITIALIZER_RANGE
+    let bound = INITIALIZER_RANGE * 3f32.sqrt();
+    shapes
+        .into_iter()
+        .map(|(name, shape)| {
+            let tensor = if name.ends_with("norm.weight") {
+                Tensor::ones(shape.as_slice(), (Kind::Float, Device::Cpu))
+            } else if name.ends_with("bias") {
+                Tensor::zeros(shape.as_slice(), (Kind::Float, Device::Cpu))
+            } else {
+                let numel = shape.iter().product::<i64>() as usize;
+                let values: Vec<f32> = (0..numel).map(|_| rng.gen_range(-bound..bound)).collect();
+                Tensor::from_slice(&values).view(shape.as_slice())
+            };
+            (name, tensor)
+        })
+        .collect()
+}
+
+/// Builds a model from nothing but its config, with weights from [`seeded_parameters`].
+/// Two models built from the same config and seed give identical outputs.
+///
+/// Meant for tests and CI that can't download a model.
+/// Unlike [`DummyModel`](crate::DummyModel), this runs a real transformer, so its logits and losses are meaningful.
+pub fn tiny_model_for_causal_lm(
+    config: AutoConfig,
+    seed: u64,
+    device: Option<Device>,
+) -> Result<Box<dyn CausalLM>, ModelLoadError> {
+    let parameters = Arc::new(seeded_parameters(&config, seed));
+    match config {
+        AutoConfig::Llama(config) => LlamaForCausalLM::from_pretrained(
+            &PretrainedSource::ConfigAndTensors(config, parameters),
+            None,
+            None,
+            device,
+            None,
+            None,
+            None,
+        )
+        .map(|x| Box::new(x) as Box<dyn CausalLM>),
+        AutoConfig::Deepseek(config) => DeepseekForCausalLM::from_pretrained(
+            &PretrainedSource::ConfigAndTensors(config, parameters),
+            None,
+            None,
+            device,
+            None,
+            None,
+            None,
+        )
+        .map(|x| Box::new(x) as Box<dyn CausalLM>),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logits(seed: u64) -> Tensor {
+        let mut model = tiny_model_for_causal_lm(
+            AutoConfig::Llama(tiny_llama_config()),
+            seed,
+            Some(Device::Cpu),
+        )
+        .unwrap();
+        let input = Tensor::from_slice(&[1i64, 5, 9, 42, 7, 3]).view([1, -1]);
+        let _no_grad = tch::no_grad_guard();
+        let (logits, _) = model.forward(&input, None, None);
+        logits
+    }
+
+    #[test]
+    fn test_same_seed_gives_identical_logits() {
+        let a = logits(1234);
+        let b = logits(1234);
+        assert_eq!(a.size(), vec![1, 6, tiny_llama_config().vocab_size as i64]);
+        assert!(a.equal(&b));
+
+        assert!(!a.equal(&logits(4321)));
+    }
+}
@@ -3,7 +3,9 @@ use crate::{
     Distro, DistroResult, EosToks, Fp32GradientAccumulator, Optimizer, ReduceType,
 };
 use anyhow::{Error, Result};
-use psyche_core::{BatchId, CancellableBarrier, LearningRateSchedule, OptimizerDefinition};
+use psyche_core::{
+    BatchId, CancellableBarrier, CancelledBarrier, LearningRateSchedule, OptimizerDefinition,
+};
 use std::{
     collections::HashMap,
     ops::ControlFlow,
@@ -13,7 +15,7 @@ use std::{
 use tch::{Device, Kind, Tensor};
 use thiserror::Error;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, trace, trace_span, warn};
 
 #[cfg(feature = "parallelism")]
 use tch::CNCCL;
@@ -46,6 +48,88 @@ impl BatchData {
     }
 }
 
+impl BatchData {
+    /// Splits off the samples from `at` onwards, leaving the first `at` in `self`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        match self {
+            BatchData::CPU(items) => BatchData::CPU(items.split_off(at)),
+            BatchData::GPU(tensor) => {
+                let rest = tensor.narrow(0, at as i64, tensor.size()[0] - at as i64);
+                *tensor = tensor.narrow(0, 0, at as i64);
+                BatchData::GPU(rest)
+            }
+        }
+    }
+
+    /// Appends `other`'s samples after our own.
+    pub fn append(self, other: Self) -> Self {
+        match (self, other) {
+            (BatchData::CPU(mut items), BatchData::CPU(other)) => {
+                items.extend(other);
+                BatchData::CPU(items)
+            }
+            (BatchData::GPU(tensor), other) => {
+                let device = tensor.device();
+                let BatchData::GPU(other) = other.gpu(device) else {
+                    unreachable!()
+                };
+                BatchData::GPU(Tensor::cat(&[tensor, other], 0))
+            }
+            (cpu, BatchData::GPU(other)) => {
+                let device = other.device();
+                cpu.gpu(device).append(BatchData::GPU(other))
+            }
+        }
+    }
+}
+
+/// Caps how many micro-batches a step trains on.
+/// The samples past the cap are deferred to the next step, and trained on before that step's own samples,
+/// so which samples get deferred depends only on the order they were assigned in.
+#[derive(Debug, Default)]
+pub struct MicroBatchLimiter {
+    max_micro_batches: Option<usize>,
+    deferred: Option<BatchData>,
+}
+
+impl MicroBatchLimiter {
+    pub fn new(max_micro_batches: Option<usize>) -> Self {
+        Self {
+            max_micro_batches,
+            deferred: None,
+        }
+    }
+
+    /// The samples to train on this step: any deferred from previous steps, then `data`,
+    /// up to `max_micro_batches` micro-batches of `micro_batch_size`.
+    pub fn take(&mut self, data: BatchData, micro_batch_size: usize) -> BatchData {
+        let mut data = match self.deferred.take() {
+            Some(deferred) => deferred.append(data),
+            None => data,
+        };
+        if let Some(max_micro_batches) = self.max_micro_batches {
+            let max_samples = max_micro_batches * micro_batch_size;
+            if data.size() > max_samples {
+                let deferred = data.split_off(max_samples);
+                debug!(
+                    deferred = deferred.size(),
+                    "Batch exceeds {max_micro_batches} micro batches, deferring the rest to the next step"
+                );
+                self.deferred = Some(deferred);
+            }
+        }
+        data
+    }
+
+    /// How many samples are waiting for the next step.
+    pub fn num_deferred(&self) -> usize {
+        self.deferred
+            .as_ref()
+            .map(BatchData::size)
+            .unwrap_or_default()
+    }
+}
+
 impl Clone for BatchData {
     fn clone(&self) -> Self {
         match self {
@@ -159,6 +243,7 @@ impl Trainer {
         lr_scheduler: LearningRateSchedule,
         optimizer: OptimizerDefinition,
         micro_batch_size: usize,
+        max_micro_batches: Option<usize>,
         stats: Option<u32>,
         grad_accum_in_fp32: bool,
         data_parallel: Option<Vec<DataParallel>>,
@@ -199,6 +284,7 @@ impl Trainer {
                     optimizer,
                     index,
                     micro_batch_size,
+                    max_micro_batches,
                     lr_scheduler,
                     barrier,
                     stats,
@@ -219,6 +305,7 @@ impl Trainer {
     fn forward_backward(
         model: &mut dyn CausalLM,
         inputs: Tensor,
+        micro_batch: usize,
         barrier: &Arc<CancellableBarrier>,
         loss_scale: Option<f64>,
     ) -> Result<Option<Tensor>> {
@@ -227,12 +314,13 @@ impl Trainer {
             return Ok(None);
         }
         let device = inputs.device();
-        let (_, loss) = model.forward(&inputs, Some(&targets), None);
+        let (_, loss) = trace_span!("forward", micro_batch)
+            .in_scope(|| model.forward(&inputs, Some(&targets), None));
         let mut loss = loss.ok_or(Error::msg("No loss"))?;
         if let Some(loss_scale) = loss_scale {
             loss /= loss_scale;
         }
-        loss.backward();
+        trace_span!("backward", micro_batch).in_scope(|| loss.backward());
         if device.is_cuda() {
             device.cuda_synchronize();
         }
@@ -416,6 +504,7 @@ impl Trainer {
         mut optimizer: Optimizer,
         index: usize,
         micro_batch_size: usize,
+        max_micro_batches: Option<usize>,
         lr_scheduler: LearningRateSchedule,
         barrier: Arc<CancellableBarrier>,
         optim_stats_every_n_steps: Option<u32>,
@@ -455,6 +544,7 @@ impl Trainer {
         model.prepare_for_training();
 
         let mut grad_accum: Option<Fp32GradientAccumulator> = None;
+        let mut micro_batch_limiter = MicroBatchLimiter::new(max_micro_batches);
         let mut nonce = 0;
         loop {
             match assignment.recv() {
@@ -480,8 +570,10 @@ impl Trainer {
                     // }
 
                     debug!(batch_id=%batch.id, "model thread training on batch {}", batch.id);
+                    let _span = trace_span!("train", step, batch_id = %batch.id).entered();
 
-                    let batch_size = batch.data.size();
+                    let data = micro_batch_limiter.take(batch.data, micro_batch_size);
+                    let batch_size = data.size();
 
                     let mut grad_accum_steps = batch_size / micro_batch_size;
                     if batch_size % micro_batch_size != 0 {
@@ -496,7 +588,7 @@ impl Trainer {
                     }
                     let grad_accum_divisor = grad_accum_steps as f64;
 
-                    let micro_batches = match batch.data {
+                    let micro_batches = match data {
                         BatchData::CPU(data) => data
                             .chunks(micro_batch_size)
                             .map(|chunk| Tensor::from_slice2(chunk).to(model.device()))
@@ -561,6 +653,7 @@ impl Trainer {
                         match Self::forward_backward(
                             &mut *model,
                             micro_batch,
+                            index,
                             &barrier,
                             Some(grad_accum_divisor),
                         ) {
@@ -690,6 +783,7 @@ impl Trainer {
                     step,
                     warmup_lr_between,
                 }) => {
+                    let _span = trace_span!("optimizer_step", step).entered();
                     let lr = Self::get_lr(&lr_scheduler, step, warmup_lr_between);
                     if optimize_step(
                         &mut model,
@@ -726,6 +820,12 @@ impl Trainer {
                     }
                 }
                 Ok(ParallelAssignment::Extract {}) => {
+                    // all ranks must have finished their last step before we gather, otherwise
+                    // a checkpoint could mix parameters from different steps across shards
+                    if flush_and_wait(model.device(), &barrier).is_err() {
+                        error!("Extract barrier cancelled");
+                        return;
+                    }
                     match unsharded_cpu_variables(model.variables(), model.communicator()) {
                         Ok(variables) => {
                             if submission
@@ -853,6 +953,15 @@ impl CausalLM for Trainer {
     fn clip_grad_norm(&mut self, _max_grad_norm: f64) {}
 }
 
+/// Waits for any outstanding work on `device`, then for every rank to reach `barrier`.
+/// Anything read from the model afterwards reflects the same completed step on all ranks.
+fn flush_and_wait(device: Device, barrier: &CancellableBarrier) -> Result<usize, CancelledBarrier> {
+    if device.is_cuda() {
+        device.cuda_synchronize();
+    }
+    barrier.wait()
+}
+
 fn optimize_step(
     model: &mut Box<dyn CausalLM>,
     lr: f64,
@@ -902,3 +1011,318 @@ fn optimize_step(
     };
     ControlFlow::Continue(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_core::DistroConfig;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        thread,
+        time::Duration,
+    };
+    use tch::nn::VarStore;
+
+    #[test]
+    fn test_extract_waits_for_all_ranks() {
+        let barrier = CancellableBarrier::new(2);
+        let slow_rank_done = Arc::new(AtomicBool::new(false));
+
+        let slow_rank = {
+            let barrier = barrier.clone();
+            let slow_rank_done = slow_rank_done.clone();
+            thread::spawn(move || {
+                // still finishing its step
+                thread::sleep(Duration::from_millis(100));
+                slow_rank_done.store(true, Ordering::SeqCst);
+                flush_and_wait(Device::Cpu, &barrier).unwrap();
+            })
+        };
+
+        flush_and_wait(Device::Cpu, &barrier).unwrap();
+        // the checkpoint upload would happen here, which must only be after the slow rank is done
+        assert!(slow_rank_done.load(Ordering::SeqCst));
+
+        slow_rank.join().unwrap();
+    }
+
+    /// A tensor parallel rank whose forward pass takes `delay`. When its variables are read after
+    /// its own forward pass, it notes whether the other rank had finished its forward pass by then.
+    struct SteppingRank {
+        var_store: VarStore,
+        delay: Duration,
+        done: Arc<AtomicBool>,
+        other_done: Arc<AtomicBool>,
+        other_done_at_extract: Arc<std::sync::Mutex<Option<bool>>>,
+    }
+
+    impl SteppingRank {
+        fn new(delay: Duration, done: Arc<AtomicBool>, other_done: Arc<AtomicBool>) -> Self {
+            let var_store = VarStore::new(Device::Cpu);
+            let _ = var_store.root().zeros("weight", &[4]);
+            Self {
+                var_store,
+                delay,
+                done,
+                other_done,
+                other_done_at_extract: Default::default(),
+            }
+        }
+    }
+
+    impl CausalLM for SteppingRank {
+        fn forward(
+            &mut self,
+            _x: &Tensor,
+            _labels: Option<&Tensor>,
+            _num_logits_to_keep: Option<i64>,
+        ) -> (Tensor, Option<Tensor>) {
+            thread::sleep(self.delay);
+            self.done.store(true, Ordering::SeqCst);
+            (Tensor::zeros([1], (Kind::Float, Device::Cpu)), None)
+        }
+
+        fn bos_token_id(&self) -> Option<i64> {
+            None
+        }
+
+        fn eos_token_ids(&self) -> Option<EosToks> {
+            None
+        }
+
+        fn device(&self) -> Device {
+            Device::Cpu
+        }
+
+        fn variables(&self) -> &VarStore {
+            if self.done.load(Ordering::SeqCst) {
+                self.other_done_at_extract
+                    .lock()
+                    .unwrap()
+                    .get_or_insert(self.other_done.load(Ordering::SeqCst));
+            }
+            &self.var_store
+        }
+
+        fn communicator(&self) -> Option<Arc<Communicator>> {
+            None
+        }
+
+        fn prepare_for_training(&mut self) {}
+
+        fn clip_grad_norm(&mut self, _max_grad_norm: f64) {}
+    }
+
+    #[test]
+    fn test_tensor_parallel_extract_waits_for_pending_step() {
+        use psyche_core::ConstantLR;
+
+        let fast_done = Arc::new(AtomicBool::new(false));
+        let slow_done = Arc::new(AtomicBool::new(false));
+        let fast = SteppingRank::new(Duration::ZERO, fast_done.clone(), slow_done.clone());
+        let slow = SteppingRank::new(Duration::from_millis(200), slow_done, fast_done);
+        let fast_saw_slow_done = fast.other_done_at_extract.clone();
+
+        let trainer = Trainer::new(
+            vec![Box::new(fast), Box::new(slow)],
+            LearningRateSchedule::Constant(ConstantLR::new(1e-2, 0, 0.0)),
+            OptimizerDefinition::Dummy,
+            1,
+            None,
+            None,
+            false,
+            None,
+        );
+        // queue the extract right behind a step, like a checkpoint taken as soon as training ends,
+        // so the fast rank gets to it while the slow rank is still in its step
+        for (tx, _) in &trainer.models {
+            tx.send(ParallelAssignment::Forward {
+                data: Tensor::zeros([1, 4], (Kind::Int64, Device::Cpu)),
+                labels: None,
+                num_logits_to_keep: None,
+            })
+            .unwrap();
+            tx.send(ParallelAssignment::Extract).unwrap();
+        }
+        for (_, rx) in &trainer.models {
+            assert!(matches!(rx.recv().unwrap(), ParallelResult::Forward { .. }));
+            assert!(matches!(rx.recv().unwrap(), ParallelResult::Extract { .. }));
+        }
+        // the fast rank only read its weights for the checkpoint once the slow rank was done with its step
+        assert_eq!(*fast_saw_slow_done.lock().unwrap(), Some(true));
+    }
+
+    #[test]
+    fn test_extract_barrier_cancelled() {
+        let barrier = CancellableBarrier::new(2);
+        barrier.cancel();
+        assert!(flush_and_wait(Device::Cpu, &barrier).is_err());
+    }
+
+    fn samples(range: std::ops::Range<i32>) -> BatchData {
+        BatchData::CPU(range.map(|i| vec![i; 4]).collect())
+    }
+
+    fn first_tokens(data: BatchData) -> Vec<i32> {
+        let BatchData::CPU(items) = data else {
+            unreachable!()
+        };
+        items.into_iter().map(|x| x[0]).collect()
+    }
+
+    #[test]
+    fn test_micro_batch_cap_defers_the_rest() {
+        // 11 samples in micro batches of 4 would be 3 micro batches, but only 2 are allowed
+        let mut limiter = MicroBatchLimiter::new(Some(2));
+        let step = limiter.take(samples(0..11), 4);
+        // two full micro batches of 4 are trained, not two enlarged ones holding all 11
+        assert_eq!(step.size(), 2 * 4);
+        assert_eq!(first_tokens(step), (0..8).collect::<Vec<_>>());
+        assert_eq!(limiter.num_deferred(), 3);
+
+        // the deferred samples go first next step
+        let step = limiter.take(samples(100..111), 4);
+        assert_eq!(
+            first_tokens(step),
+            [8, 9, 10, 100, 101, 102, 103, 104].to_vec()
+        );
+        assert_eq!(limiter.num_deferred(), 6);
+
+        // every client deferring the same way agrees on what gets trained
+        let mut other = MicroBatchLimiter::new(Some(2));
+        other.take(samples(0..11), 4);
+        assert_eq!(
+            first_tokens(other.take(samples(100..111), 4)),
+            [8, 9, 10, 100, 101, 102, 103, 104].to_vec()
+        );
+
+        // under the cap, nothing is held back
+        let mut uncapped = MicroBatchLimiter::new(None);
+        assert_eq!(uncapped.take(samples(0..11), 4).size(), 11);
+        assert_eq!(uncapped.num_deferred(), 0);
+    }
+
+    #[test]
+    fn test_split_and_append_gpu_batch() {
+        let mut data = samples(0..5).gpu(Device::Cpu);
+        let rest = data.split_off(3);
+        assert_eq!(data.size(), 3);
+        assert_eq!(rest.size(), 2);
+        let data = rest.append(samples(5..7));
+        assert_eq!(data.size(), 4);
+        let BatchData::GPU(tensor) = data else {
+            unreachable!()
+        };
+        assert_eq!(
+            Vec::<i32>::try_from(tensor.select(1, 0).contiguous()).unwrap(),
+            vec![3, 4, 5, 6]
+        );
+    }
+
+    /// Records the name and fields of every span created, on any thread.
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<std::sync::Mutex<Vec<(String, Vec<(String, String)>)>>>);
+
+    #[derive(Default)]
+    struct FieldRecorder(Vec<(String, String)>);
+
+    impl tracing::field::Visit for FieldRecorder {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+    }
+
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut fields = FieldRecorder::default();
+            span.record(&mut fields);
+            let mut spans = self.0.lock().unwrap();
+            spans.push((span.metadata().name().to_string(), fields.0));
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, _: &tracing::Event<'_>) {}
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[test]
+    fn test_train_and_optimize_emit_spans() {
+        use crate::{tiny_llama_config, tiny_model_for_causal_lm, AutoConfig};
+        use psyche_core::{ClosedInterval, ConstantLR};
+
+        let recorder = SpanRecorder::default();
+        // the model threads don't inherit a thread-local subscriber
+        tracing::subscriber::set_global_default(recorder.clone()).unwrap();
+
+        let model =
+            tiny_model_for_causal_lm(AutoConfig::Llama(tiny_llama_config()), 0, Some(Device::Cpu))
+                .unwrap();
+        let trainer = Trainer::new(
+            vec![model],
+            LearningRateSchedule::Constant(ConstantLR::new(1e-2, 0, 0.0)),
+            OptimizerDefinition::Distro(DistroConfig {
+                clip_grad_norm: None,
+                weight_decay: None,
+                compression_decay: 0.999,
+                compression_topk: 2,
+                compression_chunk: 4,
+                quantize_1bit: false,
+                momentum: 0.0,
+                dampening: 0.0,
+                nesterov: false,
+            }),
+            1,
+            None,
+            None,
+            false,
+            None,
+        );
+        let tokens = (0..16).collect::<Vec<_>>();
+        let output = trainer
+            .train(
+                7,
+                Batch {
+                    id: BatchId(ClosedInterval::new(3, 4)),
+                    data: BatchData::CPU(vec![tokens.clone(), tokens]),
+                },
+                None,
+                false,
+                vec![],
+                Some(vec![]),
+                CancellationToken::new(),
+            )
+            .unwrap();
+        let distro_results = output.distro_results.unwrap();
+        output
+            .trainer
+            .optimize(8, None, Some(vec![distro_results]))
+            .unwrap();
+
+        let spans = recorder.0.lock().unwrap().clone();
+        let field = |name: &str, key: &str| -> Vec<String> {
+            spans
+                .iter()
+                .filter(|(span, _)| span == name)
+                .flat_map(|(_, fields)| fields.iter().filter(|(k, _)| k == key))
+                .map(|(_, value)| value.clone())
+                .collect()
+        };
+        assert_eq!(field("train", "step"), ["7"]);
+        assert_eq!(field("train", "batch_id"), ["B[3, 4]"]);
+        assert_eq!(field("forward", "micro_batch"), ["0", "1"]);
+        assert_eq!(field("backward", "micro_batch"), ["0", "1"]);
+        assert_eq!(field("optimizer_step", "step"), ["8"]);
+    }
+}
@@ -4,8 +4,8 @@ use clap::{ArgAction, Parser};
 use iroh::{PublicKey, RelayMap, RelayMode, RelayUrl};
 use psyche_network::Hash;
 use psyche_network::{
-    allowlist, fmt_bytes, BlobTicket, DiscoveryMode, NetworkConnection, NetworkEvent,
-    NetworkTUIState, NetworkTui, PeerList,
+    allowlist, fmt_bytes, BlobStoreConfig, BlobTicket, DiscoveryMode, GossipConfig,
+    NetworkConnection, NetworkEvent, NetworkTUIState, NetworkTui, ParameterServeLimit, PeerList,
 };
 use psyche_tui::{
     logging::LoggerWidget,
@@ -293,7 +293,16 @@ async fn main() -> Result<()> {
         peers,
         secret_key,
         allowlist::AllowAll,
+        BlobStoreConfig::Memory,
         4,
+        1,
+        ParameterServeLimit::Unlimited,
+        None,
+        None,
+        2,
+        GossipConfig::default(),
+        vec![],
+        None,
     )
     .await?;
 
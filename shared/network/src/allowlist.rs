@@ -1,10 +1,50 @@
-use std::collections::HashSet;
-use std::sync::{Arc, RwLock};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
 
 use iroh::NodeId;
+use thiserror::Error;
+use tracing::{info, warn};
 
 pub trait Allowlist: std::fmt::Debug + Clone {
     fn allowed(&self, addr: NodeId) -> bool;
+
+    /// Like [`Allowlist::allowed`], but says why a peer was turned away.
+    fn check(&self, addr: NodeId) -> Result<(), Rejection> {
+        if self.allowed(addr) {
+            Ok(())
+        } else {
+            Err(Rejection::NotAllowed)
+        }
+    }
+}
+
+/// Why an [`Allowlist`] rejected a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    NotAllowed,
+    RateLimited,
+}
+
+impl Rejection {
+    /// The reason given to the peer when closing its connection.
+    pub fn reason(&self) -> &'static [u8] {
+        match self {
+            Rejection::NotAllowed => b"not in allowlist",
+            Rejection::RateLimited => b"too many connection attempts",
+        }
+    }
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::NotAllowed => write!(f, "not in allowlist"),
+            Rejection::RateLimited => write!(f, "connecting too often"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -71,3 +111,354 @@ impl Default for AllowDynamic {
         Self::new()
     }
 }
+
+#[derive(Error, Debug)]
+pub enum FileAllowlistError {
+    #[error("failed to read allowlist {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("{path} line {line}: {key:?} is not a public key: {source}")]
+    InvalidKey {
+        path: PathBuf,
+        line: usize,
+        key: String,
+        source: iroh::KeyParsingError,
+    },
+}
+
+/// Allows the public keys listed in a file, one per line, and reloads them whenever the file changes,
+/// so the allowed set can be updated without a restart.
+/// Blank lines and lines starting with `#` are skipped.
+///
+/// If an edit leaves the file unreadable or with a bad key, the last good set stays in place until it's fixed.
+#[derive(Debug, Clone)]
+pub struct FileAllowlist {
+    path: PathBuf,
+    allowed_nodes: Arc<RwLock<HashSet<NodeId>>>,
+}
+
+impl FileAllowlist {
+    /// Loads `path` and checks it for changes every `poll_interval` for as long as any clone is alive.
+    /// Must be called from within a tokio runtime.
+    pub fn watch(
+        path: impl Into<PathBuf>,
+        poll_interval: Duration,
+    ) -> Result<Self, FileAllowlistError> {
+        let path = path.into();
+        let contents = read_allowlist_file(&path)?;
+        let allowed_nodes = Arc::new(RwLock::new(parse_allowlist(&path, &contents)?));
+        tokio::spawn(watch_allowlist_file(
+            path.clone(),
+            contents,
+            Arc::downgrade(&allowed_nodes),
+            poll_interval,
+        ));
+        Ok(Self {
+            path,
+            allowed_nodes,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn nodes(&self) -> HashSet<NodeId> {
+        self.allowed_nodes.read().expect("RwLock poisoned").clone()
+    }
+}
+
+impl Allowlist for FileAllowlist {
+    fn allowed(&self, addr: NodeId) -> bool {
+        self.allowed_nodes
+            .read()
+            .expect("RwLock poisoned")
+            .contains(&addr)
+    }
+}
+
+fn read_allowlist_file(path: &Path) -> Result<String, FileAllowlistError> {
+    std::fs::read_to_string(path).map_err(|source| FileAllowlistError::Read {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+fn parse_allowlist(path: &Path, contents: &str) -> Result<HashSet<NodeId>, FileAllowlistError> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, key)| {
+            NodeId::from_str(key).map_err(|source| FileAllowlistError::InvalidKey {
+                path: path.to_owned(),
+                line,
+                key: key.to_string(),
+                source,
+            })
+        })
+        .collect()
+}
+
+async fn watch_allowlist_file(
+    path: PathBuf,
+    mut contents: String,
+    allowed_nodes: Weak<RwLock<HashSet<NodeId>>>,
+    poll_interval: Duration,
+) {
+    let mut interval = tokio::time::interval(poll_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        // every clone of the allowlist is gone
+        let Some(allowed_nodes) = allowed_nodes.upgrade() else {
+            return;
+        };
+        let new_contents = match read_allowlist_file(&path) {
+            Ok(new_contents) => new_contents,
+            Err(err) => {
+                warn!("{err}, keeping the last loaded allowlist");
+                continue;
+            }
+        };
+        if new_contents == contents {
+            continue;
+        }
+        match parse_allowlist(&path, &new_contents) {
+            Ok(nodes) => {
+                info!(
+                    path = %path.display(),
+                    nodes = nodes.len(),
+                    "Reloaded allowlist"
+                );
+                *allowed_nodes.write().expect("RwLock poisoned") = nodes;
+            }
+            Err(err) => warn!("{err}, keeping the last loaded allowlist"),
+        }
+        contents = new_contents;
+    }
+}
+
+/// Connection attempts from one peer, as counted by a [`RateLimitedAllowlist`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionAttemptStats {
+    pub allowed: u64,
+    pub throttled: u64,
+}
+
+#[derive(Debug, Default)]
+struct PeerConnectionAttempts {
+    /// when the allowed attempts inside the current window happened, oldest first.
+    recent: VecDeque<Instant>,
+    stats: ConnectionAttemptStats,
+}
+
+/// Wraps another allowlist, and also rejects peers that connect more than `max_connections` times
+/// in any `interval`.
+///
+/// The window slides, so a peer that reconnects a few times (e.g. after moving to another relay)
+/// is only held back until its earlier attempts are older than `interval`, instead of being cut off for good.
+/// Rejected attempts don't count towards the limit.
+#[derive(Clone)]
+pub struct RateLimitedAllowlist<A: Allowlist> {
+    inner: A,
+    max_connections: usize,
+    interval: Duration,
+    attempts: Arc<Mutex<HashMap<NodeId, PeerConnectionAttempts>>>,
+}
+
+impl<A: Allowlist> RateLimitedAllowlist<A> {
+    pub fn new(inner: A, max_connections: usize, interval: Duration) -> Self {
+        Self {
+            inner,
+            max_connections,
+            interval,
+            attempts: Default::default(),
+        }
+    }
+
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Connection attempts so far from every peer the inner allowlist accepted.
+    pub fn stats(&self) -> HashMap<NodeId, ConnectionAttemptStats> {
+        self.attempts
+            .lock()
+            .expect("Mutex poisoned")
+            .iter()
+            .map(|(node_id, attempts)| (*node_id, attempts.stats))
+            .collect()
+    }
+
+    /// Peers that had at least one connection attempt rejected for going over the limit.
+    pub fn throttled_peers(&self) -> Vec<(NodeId, u64)> {
+        self.stats()
+            .into_iter()
+            .filter(|(_, stats)| stats.throttled > 0)
+            .map(|(node_id, stats)| (node_id, stats.throttled))
+            .collect()
+    }
+
+    fn check_at(&self, addr: NodeId, now: Instant) -> Result<(), Rejection> {
+        self.inner.check(addr)?;
+        let mut attempts = self.attempts.lock().expect("Mutex poisoned");
+        let peer = attempts.entry(addr).or_default();
+        while peer
+            .recent
+            .front()
+            .is_some_and(|attempt| now.saturating_duration_since(*attempt) >= self.interval)
+        {
+            peer.recent.pop_front();
+        }
+        if peer.recent.len() >= self.max_connections {
+            peer.stats.throttled += 1;
+            return Err(Rejection::RateLimited);
+        }
+        peer.recent.push_back(now);
+        peer.stats.allowed += 1;
+        Ok(())
+    }
+}
+
+impl<A: Allowlist> Allowlist for RateLimitedAllowlist<A> {
+    fn allowed(&self, addr: NodeId) -> bool {
+        self.check(addr).is_ok()
+    }
+
+    fn check(&self, addr: NodeId) -> Result<(), Rejection> {
+        self.check_at(addr, Instant::now())
+    }
+}
+
+impl<A: Allowlist> std::fmt::Debug for RateLimitedAllowlist<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimitedAllowlist")
+            .field("inner", &self.inner)
+            .field("max_connections", &self.max_connections)
+            .field("interval", &self.interval)
+            .field("throttled_peers", &self.throttled_peers())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id(i: u8) -> NodeId {
+        let mut bytes = [0u8; 32];
+        bytes[31] = i;
+        SecretKey::from_bytes(&bytes).public()
+    }
+
+    async fn wait_for_reload(allowlist: &FileAllowlist, expected: &HashSet<NodeId>) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while allowlist.nodes() != *expected {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("allowlist wasn't reloaded");
+    }
+
+    #[tokio::test]
+    async fn test_file_allowlist_reloads_on_change() {
+        let (kept, removed, added) = (node_id(1), node_id(2), node_id(3));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist");
+        std::fs::write(&path, format!("# operators\n{kept}\n\n{removed}\n")).unwrap();
+
+        let allowlist = FileAllowlist::watch(&path, Duration::from_millis(10)).unwrap();
+        assert_eq!(allowlist.check(kept), Ok(()));
+        assert_eq!(allowlist.check(removed), Ok(()));
+        assert_eq!(allowlist.check(added), Err(Rejection::NotAllowed));
+
+        std::fs::write(&path, format!("{kept}\n{added}\n")).unwrap();
+        wait_for_reload(&allowlist, &HashSet::from([kept, added])).await;
+        // the next connection from a removed key is turned away
+        assert_eq!(allowlist.check(removed), Err(Rejection::NotAllowed));
+        assert_eq!(allowlist.check(added), Ok(()));
+
+        // a broken edit keeps the last good set
+        std::fs::write(&path, format!("{kept}\nnot-a-key\n")).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(allowlist.nodes(), HashSet::from([kept, added]));
+
+        std::fs::write(&path, format!("{kept}\n")).unwrap();
+        wait_for_reload(&allowlist, &HashSet::from([kept])).await;
+        assert_eq!(allowlist.check(added), Err(Rejection::NotAllowed));
+    }
+
+    #[test]
+    fn test_file_allowlist_rejects_bad_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist");
+        std::fs::write(&path, format!("{}\nnot-a-key\n", node_id(1))).unwrap();
+
+        assert!(matches!(
+            FileAllowlist::watch(&path, Duration::from_secs(1)),
+            Err(FileAllowlistError::InvalidKey { line: 2, .. })
+        ));
+        assert!(matches!(
+            FileAllowlist::watch(dir.path().join("missing"), Duration::from_secs(1)),
+            Err(FileAllowlistError::Read { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rapid_connects_are_throttled() {
+        let (peer, other_peer, stranger) = (node_id(1), node_id(2), node_id(3));
+        let allowlist = RateLimitedAllowlist::new(
+            AllowDynamic::with_nodes([peer, other_peer]),
+            10,
+            Duration::from_secs(60),
+        );
+
+        let start = Instant::now();
+        let allowed = (0..100)
+            .filter(|i| {
+                allowlist
+                    .check_at(peer, start + Duration::from_millis(*i))
+                    .is_ok()
+            })
+            .count();
+        assert_eq!(allowed, 10);
+        assert_eq!(
+            allowlist.stats()[&peer],
+            ConnectionAttemptStats {
+                allowed: 10,
+                throttled: 90
+            }
+        );
+        assert_eq!(allowlist.throttled_peers(), vec![(peer, 90)]);
+
+        // other peers aren't affected, and the inner allowlist still decides
+        assert_eq!(allowlist.check_at(other_peer, start), Ok(()));
+        assert_eq!(
+            allowlist.check_at(stranger, start),
+            Err(Rejection::NotAllowed)
+        );
+        assert!(!allowlist.stats().contains_key(&stranger));
+
+        // still inside the window
+        assert_eq!(
+            allowlist.check_at(peer, start + Duration::from_secs(30)),
+            Err(Rejection::RateLimited)
+        );
+        // once the burst has left the window, the peer can reconnect
+        assert_eq!(
+            allowlist.check_at(peer, start + Duration::from_millis(60_010)),
+            Ok(())
+        );
+
+        // clones share their counts
+        let clone = allowlist.clone();
+        assert_eq!(clone.stats()[&peer].allowed, 11);
+    }
+}
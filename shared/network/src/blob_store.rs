@@ -0,0 +1,103 @@
+use anyhow::Result;
+use iroh::{endpoint::Connection, protocol::ProtocolHandler, Endpoint};
+use iroh_blobs::{
+    downloader::ConcurrencyLimits,
+    net_protocol::{Blobs, Builder},
+    provider::EventSender,
+    rpc::client::blobs::MemClient,
+    store::{fs, mem, GcConfig, Store},
+};
+use std::path::PathBuf;
+
+/// Where we keep the blobs we share and download.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum BlobStoreConfig {
+    /// In memory, so a restarted client has to download everything again, and stops seeding what it shared.
+    #[default]
+    Memory,
+    /// In a directory, so blobs and their tags survive restarts and can still be served afterwards.
+    Persistent { path: PathBuf },
+}
+
+/// The blobs protocol, over whichever store was configured.
+#[derive(Debug, Clone)]
+pub(crate) enum BlobStore {
+    Memory(Blobs<mem::Store>),
+    Persistent(Blobs<fs::Store>),
+}
+
+impl BlobStore {
+    pub async fn build(
+        config: &BlobStoreConfig,
+        concurrency_limits: ConcurrencyLimits,
+        events: Option<EventSender>,
+        endpoint: &Endpoint,
+    ) -> Result<Self> {
+        fn configure<S: Store>(
+            builder: Builder<S>,
+            concurrency_limits: ConcurrencyLimits,
+            events: Option<EventSender>,
+        ) -> Builder<S> {
+            let builder = builder.concurrency_limits(concurrency_limits);
+            match events {
+                Some(events) => builder.events(events),
+                None => builder,
+            }
+        }
+
+        Ok(match config {
+            BlobStoreConfig::Memory => {
+                Self::Memory(configure(Blobs::memory(), concurrency_limits, events).build(endpoint))
+            }
+            BlobStoreConfig::Persistent { path } => Self::Persistent(
+                configure(Blobs::persistent(path).await?, concurrency_limits, events)
+                    .build(endpoint),
+            ),
+        })
+    }
+
+    pub fn client(&self) -> MemClient {
+        match self {
+            Self::Memory(blobs) => blobs.client().clone(),
+            Self::Persistent(blobs) => blobs.client().clone(),
+        }
+    }
+
+    pub fn start_gc(&self, config: GcConfig) -> Result<()> {
+        match self {
+            Self::Memory(blobs) => blobs.start_gc(config),
+            Self::Persistent(blobs) => blobs.start_gc(config),
+        }
+    }
+
+    /// Serves blobs to a peer that connected with the blobs ALPN.
+    pub async fn handle_connection(&self, connection: Connection) {
+        match self {
+            Self::Memory(blobs) => {
+                iroh_blobs::provider::handle_connection(
+                    connection,
+                    blobs.store().clone(),
+                    blobs.events().clone(),
+                    blobs.rt().clone(),
+                )
+                .await
+            }
+            Self::Persistent(blobs) => {
+                iroh_blobs::provider::handle_connection(
+                    connection,
+                    blobs.store().clone(),
+                    blobs.events().clone(),
+                    blobs.rt().clone(),
+                )
+                .await
+            }
+        }
+    }
+
+    pub async fn shutdown(&self) {
+        match self {
+            Self::Memory(blobs) => (blobs as &dyn ProtocolHandler).shutdown().await,
+            Self::Persistent(blobs) => (blobs as &dyn ProtocolHandler).shutdown().await,
+        }
+    }
+}
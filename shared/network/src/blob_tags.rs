@@ -0,0 +1,69 @@
+use anyhow::Result;
+use futures_util::TryStreamExt;
+use iroh_blobs::{Hash, Tag};
+use std::time::Duration;
+
+use crate::blob_store::BlobStore;
+
+const BLOB_TAG_PREFIX: &str = "psyche/";
+
+/// How often the blob store deletes blobs that no tag references anymore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcPolicy {
+    /// Never delete blobs, even after their tags are retired.
+    Disabled,
+    /// Run a GC pass this often.
+    Every(Duration),
+}
+
+impl Default for GcPolicy {
+    fn default() -> Self {
+        Self::Every(Duration::from_secs(10))
+    }
+}
+
+/// The iroh tag keeping a blob we share or downloaded under one of our tags alive.
+/// The tag number is zero-padded so iroh's tags sort by it.
+pub(crate) fn blob_tag(tag: u32, hash: Hash) -> Tag {
+    Tag::from(format!("{BLOB_TAG_PREFIX}{tag:010}/{hash}"))
+}
+
+/// The tag number of one of our iroh tags, or `None` if someone else set it.
+pub(crate) fn parse_blob_tag(name: &Tag) -> Option<u32> {
+    std::str::from_utf8(&name.0)
+        .ok()?
+        .strip_prefix(BLOB_TAG_PREFIX)?
+        .split_once('/')?
+        .0
+        .parse()
+        .ok()
+}
+
+/// Every blob in the store under one of our tags, with its tag number.
+pub(crate) async fn list_blob_tags(blobs: &BlobStore) -> Result<Vec<(u32, Hash, Tag)>> {
+    blobs
+        .client()
+        .tags()
+        .list()
+        .await?
+        .try_filter_map(|info| async move {
+            Ok(parse_blob_tag(&info.name).map(|tag| (tag, info.hash, info.name)))
+        })
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_tag_roundtrip() {
+        let hash = Hash::new(b"blob");
+        assert_eq!(parse_blob_tag(&blob_tag(0, hash)), Some(0));
+        assert_eq!(parse_blob_tag(&blob_tag(u32::MAX, hash)), Some(u32::MAX));
+        assert_eq!(parse_blob_tag(&Tag::from("auto-1234")), None);
+        // tags sort by their number, not lexically
+        assert!(blob_tag(9, hash) < blob_tag(10, hash));
+    }
+}
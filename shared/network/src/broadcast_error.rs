@@ -0,0 +1,33 @@
+use thiserror::Error;
+
+use crate::FragmentError;
+
+#[derive(Error, Debug)]
+pub enum BroadcastError {
+    #[error("Message of {size} bytes is bigger than the maximum of {max} bytes gossip can carry")]
+    TooLarge { size: usize, max: usize },
+
+    #[error("No gossip peers to broadcast to")]
+    NoPeers,
+
+    #[error("Not subscribed to gossip channel \"{0}\"")]
+    UnknownChannel(String),
+
+    #[error("Failed to sign message: {0}")]
+    Sign(anyhow::Error),
+
+    #[error("Failed to split message into fragments: {0}")]
+    Fragment(FragmentError),
+
+    #[error("Failed to send message over gossip: {0}")]
+    Gossip(anyhow::Error),
+}
+
+impl From<FragmentError> for BroadcastError {
+    fn from(err: FragmentError) -> Self {
+        match err {
+            FragmentError::TooLarge { size, max } => BroadcastError::TooLarge { size, max },
+            err => BroadcastError::Fragment(err),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+};
+
+/// Highest zlib compression level. 0 stores the data uncompressed, but still in the zlib format.
+pub const MAX_COMPRESSION_LEVEL: u32 = 9;
+
+/// The first byte of every blob we share, saying how the rest of it is stored.
+const BLOB_RAW: u8 = 0;
+const BLOB_ZLIB: u8 = 1;
+const BLOB_ZSTD: u8 = 2;
+
+/// How the blobs we share are compressed. Downloaders tell from the blob itself, so peers can use different codecs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    /// Stored as is.
+    None,
+    #[default]
+    Zlib,
+    /// Faster, and denser on large DisTrO results, than zlib at the same level.
+    Zstd,
+}
+
+pub fn compress(data: &[u8], level: u32) -> std::io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// Fails instead of decompressing more than `limit` bytes, so a tiny zlib bomb can't make us allocate gigabytes.
+pub fn decompress(data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    read_limited(ZlibDecoder::new(data), limit)
+}
+
+fn read_limited(decoder: impl Read, limit: usize) -> io::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    decoder.take(limit as u64 + 1).read_to_end(&mut decoded)?;
+    if decoded.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("decompressed data is bigger than the limit of {limit} bytes"),
+        ));
+    }
+    Ok(decoded)
+}
+
+/// Compresses `data` with `codec` into a shareable blob, storing it raw when compression wouldn't make it smaller.
+/// Level 0 always stores it raw.
+pub fn encode_blob(data: &[u8], codec: Codec, level: u32) -> io::Result<Vec<u8>> {
+    let compressed = match codec {
+        _ if level == 0 => None,
+        Codec::None => None,
+        Codec::Zlib => {
+            let mut blob = vec![BLOB_ZLIB];
+            let mut encoder = ZlibEncoder::new(&mut blob, Compression::new(level));
+            encoder.write_all(data)?;
+            encoder.finish()?;
+            Some(blob)
+        }
+        Codec::Zstd => {
+            let mut blob = vec![BLOB_ZSTD];
+            zstd::stream::copy_encode(data, &mut blob, level as i32)?;
+            Some(blob)
+        }
+    };
+    if let Some(blob) = compressed.filter(|blob| blob.len() < data.len() + 1) {
+        return Ok(blob);
+    }
+    let mut blob = Vec::with_capacity(data.len() + 1);
+    blob.push(BLOB_RAW);
+    blob.extend_from_slice(data);
+    Ok(blob)
+}
+
+/// Undoes [`encode_blob`], failing instead of returning more than `limit` bytes.
+pub fn decode_blob(blob: &[u8], limit: usize) -> io::Result<Cow<'_, [u8]>> {
+    match blob.split_first() {
+        Some((&BLOB_RAW, data)) if data.len() <= limit => Ok(Cow::Borrowed(data)),
+        Some((&BLOB_RAW, _)) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("blob is bigger than the limit of {limit} bytes"),
+        )),
+        Some((&BLOB_ZLIB, data)) => decompress(data, limit).map(Cow::Owned),
+        Some((&BLOB_ZSTD, data)) => {
+            read_limited(zstd::stream::read::Decoder::new(data)?, limit).map(Cow::Owned)
+        }
+        Some((format, _)) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown blob format {format}"),
+        )),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "blob is empty")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressible_data() -> Vec<u8> {
+        (0..20_000)
+            .map(|i| {
+                format!(
+                    "step {} loss {:.3} batch {}\n",
+                    i / 16,
+                    (i % 97) as f32 / 7.0,
+                    i % 13
+                )
+            })
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_all_levels_round_trip() {
+        let data = compressible_data();
+        for level in 0..=MAX_COMPRESSION_LEVEL {
+            let compressed = compress(&data, level).unwrap();
+            assert_eq!(
+                decompress(&compressed, data.len()).unwrap(),
+                data,
+                "level {level}"
+            );
+        }
+        assert_eq!(
+            decompress(&compress(&[], 2).unwrap(), 0).unwrap(),
+            Vec::<u8>::new()
+        );
+    }
+
+    #[test]
+    fn test_higher_levels_compress_more() {
+        let data = compressible_data();
+        let stored = compress(&data, 0).unwrap().len();
+        let fast = compress(&data, 1).unwrap().len();
+        let best = compress(&data, MAX_COMPRESSION_LEVEL).unwrap().len();
+        assert!(stored > data.len());
+        assert!(
+            fast < stored,
+            "level 1 ({fast}) should beat level 0 ({stored})"
+        );
+        assert!(best < fast, "level 9 ({best}) should beat level 1 ({fast})");
+    }
+
+    #[test]
+    fn test_decompress_stops_at_limit() {
+        // a kilobyte or so that expands to 64 MiB
+        let bomb = compress(&vec![0u8; 64 * 1024 * 1024], MAX_COMPRESSION_LEVEL).unwrap();
+        assert!(bomb.len() < 128 * 1024);
+        let err = decompress(&bomb, 1024 * 1024).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let data = compressible_data();
+        let compressed = compress(&data, 2).unwrap();
+        assert!(decompress(&compressed, data.len() - 1).is_err());
+    }
+
+    #[test]
+    fn test_decompress_rejects_garbage() {
+        assert!(decompress(b"definitely not zlib", 1024).is_err());
+    }
+
+    #[test]
+    fn test_blobs_round_trip_compressed_or_raw() {
+        let data = compressible_data();
+        let compressed = encode_blob(&data, Codec::Zlib, 2).unwrap();
+        assert_eq!(compressed[0], BLOB_ZLIB);
+        assert!(compressed.len() < data.len());
+        assert_eq!(decode_blob(&compressed, data.len()).unwrap(), data);
+
+        // level 0 and incompressible data skip zlib entirely
+        let stored = encode_blob(&data, Codec::Zlib, 0).unwrap();
+        assert_eq!(stored[0], BLOB_RAW);
+        assert_eq!(stored.len(), data.len() + 1);
+        assert_eq!(decode_blob(&stored, data.len()).unwrap(), data);
+
+        let noise: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        assert_eq!(
+            encode_blob(&noise, Codec::Zlib, MAX_COMPRESSION_LEVEL).unwrap()[0],
+            BLOB_RAW
+        );
+    }
+
+    #[test]
+    fn test_blobs_round_trip_with_each_codec() {
+        let data = compressible_data();
+        for (codec, header) in [
+            (Codec::None, BLOB_RAW),
+            (Codec::Zlib, BLOB_ZLIB),
+            (Codec::Zstd, BLOB_ZSTD),
+        ] {
+            let blob = encode_blob(&data, codec, 3).unwrap();
+            assert_eq!(blob[0], header, "{codec:?}");
+            assert_eq!(decode_blob(&blob, data.len()).unwrap(), data, "{codec:?}");
+            assert!(
+                decode_blob(&blob, data.len() - 1).is_err(),
+                "{codec:?} should respect the limit"
+            );
+        }
+    }
+
+    #[test]
+    fn test_zstd_shrinks_distro_sized_blobs() {
+        // quantized DisTrO values are a few distinct bytes, spread over megabytes
+        let data: Vec<u8> = (0..4 * 1024 * 1024u32)
+            .map(|i| [0x3c, 0xbc, 0x00][(i.wrapping_mul(2654435761) >> 29) as usize % 3])
+            .collect();
+        let zstd = encode_blob(&data, Codec::Zstd, 3).unwrap();
+        assert_eq!(zstd[0], BLOB_ZSTD);
+        assert!(
+            zstd.len() < data.len() / 4,
+            "{} of {}",
+            zstd.len(),
+            data.len()
+        );
+        assert_eq!(decode_blob(&zstd, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decode_blob_rejects_codec_mismatch() {
+        let data = compressible_data();
+        let mut zstd = encode_blob(&data, Codec::Zstd, 3).unwrap();
+        zstd[0] = BLOB_ZLIB;
+        assert!(decode_blob(&zstd, data.len()).is_err());
+
+        let mut zlib = encode_blob(&data, Codec::Zlib, 3).unwrap();
+        zlib[0] = BLOB_ZSTD;
+        assert!(decode_blob(&zlib, data.len()).is_err());
+    }
+
+    #[test]
+    fn test_decode_blob_rejects_bad_blobs() {
+        let data = compressible_data();
+        for codec in [Codec::Zlib, Codec::Zstd] {
+            assert!(decode_blob(&encode_blob(&data, codec, 0).unwrap(), data.len() - 1).is_err());
+            assert!(decode_blob(&encode_blob(&data, codec, 2).unwrap(), data.len() - 1).is_err());
+        }
+        assert!(decode_blob(&[7, 1, 2, 3], 1024).is_err());
+        assert!(decode_blob(&[], 1024).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use iroh::{endpoint::RemoteInfo, NodeAddr, NodeId};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, net::SocketAddr, time::Instant};
+
+use crate::{state::State, DownloadFailed, NatProbe, NatType};
+
+/// How many download failures are kept around for [`EndpointDiagnostics`].
+const MAX_RECENT_DOWNLOAD_FAILURES: usize = 32;
+
+/// A snapshot of this node's p2p state, meant to be attached to connectivity bug reports.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointDiagnostics {
+    pub node_id: String,
+    pub bound_addresses: Vec<SocketAddr>,
+    pub relay_url: Option<String>,
+    /// `None` until the NAT probe finished, or if it's disabled.
+    pub nat_type: Option<NatType>,
+    pub peers: Vec<PeerDiagnostics>,
+    pub recent_download_failures: Vec<DownloadFailureDiagnostics>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerDiagnostics {
+    pub node_id: String,
+    pub connection_type: String,
+    pub latency_ms: Option<f64>,
+    pub bandwidth_bytes_per_sec: f64,
+    pub reputation: f64,
+    pub blobs_served: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadFailureDiagnostics {
+    pub hash: String,
+    pub from: String,
+    pub error: String,
+    pub secs_ago: f64,
+}
+
+impl EndpointDiagnostics {
+    pub(crate) fn new(
+        node_addr: NodeAddr,
+        peers: impl Iterator<Item = (RemoteInfo, f64)>,
+        state: &State,
+        nat_probe: Option<NatProbe>,
+    ) -> Self {
+        Self {
+            node_id: node_addr.node_id.to_string(),
+            bound_addresses: node_addr.direct_addresses.into_iter().collect(),
+            relay_url: node_addr.relay_url.map(|url| url.to_string()),
+            nat_type: nat_probe.map(|probe| probe.nat_type),
+            peers: peers
+                .map(|(info, bandwidth)| PeerDiagnostics {
+                    node_id: info.node_id.to_string(),
+                    connection_type: info.conn_type.to_string(),
+                    latency_ms: info.latency.map(|latency| latency.as_secs_f64() * 1000.0),
+                    bandwidth_bytes_per_sec: bandwidth,
+                    reputation: state
+                        .download_outcomes
+                        .get(&info.node_id)
+                        .copied()
+                        .unwrap_or_default()
+                        .reputation(),
+                    blobs_served: state.blobs_served.count(&info.node_id),
+                })
+                .collect(),
+            recent_download_failures: state.recent_download_failures.to_diagnostics(),
+        }
+    }
+
+    /// Pretty-printed JSON, ready to be pasted into a support request.
+    pub fn to_support_bundle(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[derive(Debug)]
+struct RecordedDownloadFailure {
+    at: Instant,
+    hash: iroh_blobs::Hash,
+    from: NodeId,
+    error: String,
+}
+
+/// The last few download failures, oldest first.
+#[derive(Debug, Default)]
+pub struct RecentDownloadFailures(VecDeque<RecordedDownloadFailure>);
+
+impl RecentDownloadFailures {
+    pub fn record(&mut self, failure: &DownloadFailed) {
+        if self.0.len() == MAX_RECENT_DOWNLOAD_FAILURES {
+            self.0.pop_front();
+        }
+        self.0.push_back(RecordedDownloadFailure {
+            at: Instant::now(),
+            hash: failure.blob_ticket.hash(),
+            from: failure.blob_ticket.node_addr().node_id,
+            error: format!("{:#}", failure.error),
+        });
+    }
+
+    fn to_diagnostics(&self) -> Vec<DownloadFailureDiagnostics> {
+        self.0
+            .iter()
+            .map(|failure| DownloadFailureDiagnostics {
+                hash: failure.hash.to_string(),
+                from: failure.from.to_string(),
+                error: failure.error.clone(),
+                secs_ago: failure.at.elapsed().as_secs_f64(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        allowlist::AllowAll, BlobStoreConfig, DiscoveryMode, NetworkConnection, NetworkEvent,
+    };
+    use iroh::RelayMode;
+    use iroh_blobs::{ticket::BlobTicket, BlobFormat};
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    type TestNetwork = NetworkConnection<String, String>;
+
+    async fn test_network() -> TestNetwork {
+        TestNetwork::init(
+            "diagnostics-test",
+            None,
+            None,
+            RelayMode::Disabled,
+            DiscoveryMode::Local,
+            vec![],
+            None,
+            AllowAll,
+            BlobStoreConfig::Memory,
+            4,
+            1,
+            crate::ParameterServeLimit::Unlimited,
+            None,
+            None,
+            2,
+            crate::GossipConfig::default(),
+            vec![],
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_recent_download_failures_are_capped() {
+        let ticket = BlobTicket::new(
+            NodeAddr::new(iroh::SecretKey::from_bytes(&[1; 32]).public()),
+            iroh_blobs::Hash::new(b"missing"),
+            BlobFormat::Raw,
+        )
+        .unwrap();
+        let mut failures = RecentDownloadFailures::default();
+        for i in 0..MAX_RECENT_DOWNLOAD_FAILURES + 3 {
+            failures.record(&DownloadFailed {
+                blob_ticket: ticket.clone(),
+                tag: i as u32,
+                kind: crate::DownloadFailureKind::Other,
+                error: anyhow::anyhow!("failure {i}"),
+                priority: crate::DownloadPriority::Normal,
+            });
+        }
+        let diagnostics = failures.to_diagnostics();
+        assert_eq!(diagnostics.len(), MAX_RECENT_DOWNLOAD_FAILURES);
+        assert_eq!(diagnostics[0].error, "failure 3");
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_after_connecting_to_a_peer() {
+        let mut provider = test_network().await;
+        let mut downloader = test_network().await;
+
+        // asking the provider for a blob it doesn't have connects us, and gives us a failure to report.
+        let ticket = BlobTicket::new(
+            provider.node_addr().await.unwrap(),
+            iroh_blobs::Hash::new(b"not shared by anyone"),
+            BlobFormat::Raw,
+        )
+        .unwrap();
+        downloader.start_download(ticket, 0, &[]).await.unwrap();
+        timeout(Duration::from_secs(30), async {
+            loop {
+                tokio::select! {
+                    event = downloader.poll_next() => {
+                        if let Some(NetworkEvent::DownloadFailed(_)) = event.unwrap() {
+                            return;
+                        }
+                    }
+                    _ = provider.poll_next() => {}
+                }
+            }
+        })
+        .await
+        .expect("download of a missing blob should fail");
+
+        let diagnostics = downloader.diagnostics().await.unwrap();
+        assert_eq!(diagnostics.node_id, downloader.node_id().to_string());
+        assert!(!diagnostics.bound_addresses.is_empty());
+        let peer = diagnostics
+            .peers
+            .iter()
+            .find(|peer| peer.node_id == provider.node_id().to_string())
+            .expect("provider should be a known peer");
+        assert_ne!(peer.connection_type, "none");
+        assert_eq!(diagnostics.recent_download_failures.len(), 1);
+        assert_eq!(
+            diagnostics.recent_download_failures[0].from,
+            provider.node_id().to_string()
+        );
+
+        let bundle: serde_json::Value =
+            serde_json::from_str(&diagnostics.to_support_bundle().unwrap()).unwrap();
+        for field in [
+            "node_id",
+            "bound_addresses",
+            "relay_url",
+            "nat_type",
+            "peers",
+            "recent_download_failures",
+        ] {
+            assert!(
+                bundle.get(field).is_some(),
+                "support bundle is missing {field}"
+            );
+        }
+        for field in [
+            "node_id",
+            "connection_type",
+            "latency_ms",
+            "bandwidth_bytes_per_sec",
+        ] {
+            assert!(
+                bundle["peers"][0].get(field).is_some(),
+                "peer diagnostics are missing {field}"
+            );
+        }
+
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    compression,
     p2p_model_sharing::{TransmittableModelConfig, TransmittableModelParameter},
     serialized_distro::TransmittableDistroResult,
     Networkable,
@@ -7,16 +8,31 @@ use crate::{
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
 use futures_util::future::select_all;
-use iroh::PublicKey;
-use iroh_blobs::{get::db::DownloadProgress, ticket::BlobTicket};
+use iroh::{endpoint::ConnectionError, NodeAddr, PublicKey};
+use iroh_blobs::{
+    get::{db::DownloadProgress as GetProgress, error::GetError},
+    ticket::BlobTicket,
+};
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::{
     sync::{mpsc, oneshot, Mutex},
     task::JoinHandle,
 };
 use tracing::{error, info, trace, warn};
 
+/// The most a downloaded blob may decompress to. Well above a parameter chunk or a large model's DisTrO results,
+/// but stops a peer from handing us a zlib bomb.
+const MAX_DECOMPRESSED_DOWNLOAD_SIZE: usize = 8 * 1024 * 1024 * 1024;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TransmittableDownload {
     DistroResult(TransmittableDistroResult),
@@ -24,11 +40,27 @@ pub enum TransmittableDownload {
     ModelConfig(TransmittableModelConfig),
 }
 
+impl TransmittableDownload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::DistroResult(_) => "DisTrO result",
+            Self::ModelParameter(_) => "model parameter",
+            Self::ModelConfig(_) => "model config",
+        }
+    }
+}
+
+/// Decodes a blob as added by [`crate::NetworkConnection::add_downloadable`].
+pub(crate) fn decode_download<D: for<'a> Deserialize<'a>>(blob: &[u8]) -> Result<D> {
+    let bytes = compression::decode_blob(blob, MAX_DECOMPRESSED_DOWNLOAD_SIZE)?;
+    Ok(postcard::from_bytes(&bytes)?)
+}
+
 #[derive(Debug)]
 struct Download {
     blob_ticket: BlobTicket,
     tag: u32,
-    download: mpsc::UnboundedReceiver<Result<DownloadProgress>>,
+    download: mpsc::UnboundedReceiver<Result<GetProgress>>,
     last_offset: u64,
     total_size: u64,
 }
@@ -52,7 +84,7 @@ impl Download {
     fn new(
         blob_ticket: BlobTicket,
         tag: u32,
-        download: mpsc::UnboundedReceiver<Result<DownloadProgress>>,
+        download: mpsc::UnboundedReceiver<Result<GetProgress>>,
     ) -> Self {
         Self {
             blob_ticket,
@@ -74,6 +106,102 @@ pub struct DownloadUpdate {
     pub all_done: bool,
 }
 
+/// How far back a download's throughput is averaged over.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How long a download has to have been going before we guess when it'll finish.
+const MIN_ETA_SAMPLE_PERIOD: Duration = Duration::from_secs(3);
+
+/// How an in-flight download is coming along.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DownloadProgress {
+    pub hash: iroh_blobs::Hash,
+    pub downloaded: u64,
+    /// Zero until the provider told us the blob's size.
+    pub total: u64,
+    pub bytes_per_sec: f64,
+    /// `None` until we know the size and have a few seconds of progress to go on.
+    pub eta: Option<Duration>,
+}
+
+/// The latest update for an in-flight download, and its recent progress.
+#[derive(Debug)]
+pub struct InFlightDownload {
+    pub latest: DownloadUpdate,
+    started: Instant,
+    // when each recent chunk arrived, and how big it was, oldest first.
+    recent: VecDeque<(Instant, u64)>,
+}
+
+impl InFlightDownload {
+    pub fn new(update: DownloadUpdate, now: Instant) -> Self {
+        let mut download = Self {
+            latest: update.clone(),
+            started: now,
+            recent: VecDeque::new(),
+        };
+        download.record(update, now);
+        download
+    }
+
+    pub fn record(&mut self, update: DownloadUpdate, now: Instant) {
+        if update.downloaded_size_delta > 0 {
+            self.recent.push_back((now, update.downloaded_size_delta));
+        }
+        while self
+            .recent
+            .front()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) > THROUGHPUT_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        self.latest = update;
+    }
+
+    /// Bytes per second over the last [`THROUGHPUT_WINDOW`], or since the download started if that's more recent.
+    pub fn bytes_per_sec(&self, now: Instant) -> f64 {
+        let period = now
+            .saturating_duration_since(self.started)
+            .min(THROUGHPUT_WINDOW);
+        if period.is_zero() {
+            return 0.0;
+        }
+        let window_start = now - period;
+        let bytes: u64 = self
+            .recent
+            .iter()
+            .filter(|(at, _)| *at > window_start)
+            .map(|(_, bytes)| bytes)
+            .sum();
+        bytes as f64 / period.as_secs_f64()
+    }
+
+    pub fn progress(&self, now: Instant) -> DownloadProgress {
+        let downloaded = self.latest.downloaded_size;
+        let total = self.latest.total_size;
+        let bytes_per_sec = self.bytes_per_sec(now);
+        let eta =
+            if total == 0 || now.saturating_duration_since(self.started) < MIN_ETA_SAMPLE_PERIOD {
+                None
+            } else if downloaded >= total {
+                Some(Duration::ZERO)
+            } else if bytes_per_sec > 0.0 {
+                Some(Duration::from_secs_f64(
+                    (total - downloaded) as f64 / bytes_per_sec,
+                ))
+            } else {
+                None
+            };
+        DownloadProgress {
+            hash: self.latest.blob_ticket.hash(),
+            downloaded,
+            total,
+            bytes_per_sec,
+            eta,
+        }
+    }
+}
+
 pub struct DownloadComplete<D: Networkable> {
     pub hash: iroh_blobs::Hash,
     pub from: PublicKey,
@@ -84,7 +212,139 @@ pub struct DownloadComplete<D: Networkable> {
 pub struct DownloadFailed {
     pub blob_ticket: BlobTicket,
     pub tag: u32,
+    pub kind: DownloadFailureKind,
     pub error: anyhow::Error,
+    /// The priority the download was started with, to retry it with the same one.
+    pub priority: DownloadPriority,
+}
+
+/// How urgently a download is needed. Queued downloads start highest priority first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum DownloadPriority {
+    /// Data for the current round.
+    #[default]
+    Normal,
+    /// What a client needs to catch up with the run, like the checkpoint's parameters.
+    /// Lower priority downloads don't start while any of these are queued or in flight,
+    /// so they don't compete with them for bandwidth.
+    High,
+}
+
+#[derive(Debug)]
+pub(crate) struct QueuedDownload {
+    pub ticket: BlobTicket,
+    pub tag: u32,
+    pub additional_peers_to_try: Vec<NodeAddr>,
+    pub priority: DownloadPriority,
+}
+
+/// Downloads waiting for a free slot, and the priorities of the ones holding the slots.
+#[derive(Debug, Default)]
+struct DownloadQueue {
+    queued: VecDeque<QueuedDownload>,
+    running: Vec<(iroh_blobs::Hash, DownloadPriority)>,
+}
+
+impl DownloadQueue {
+    /// Takes the oldest of the highest priority queued downloads, if one of the `max_running` slots is free
+    /// and no higher priority download holds one.
+    fn pop_startable(&mut self, max_running: usize) -> Option<QueuedDownload> {
+        if self.running.len() >= max_running {
+            return None;
+        }
+        let highest = self.queued.iter().map(|download| download.priority).max()?;
+        if self.running.iter().any(|(_, priority)| *priority > highest) {
+            return None;
+        }
+        let index = self
+            .queued
+            .iter()
+            .position(|download| download.priority == highest)?;
+        let download = self.queued.remove(index)?;
+        self.running
+            .push((download.ticket.hash(), download.priority));
+        Some(download)
+    }
+
+    /// Frees the slot of a download of `hash`, returning its priority.
+    fn finished(&mut self, hash: iroh_blobs::Hash) -> Option<DownloadPriority> {
+        let index = self
+            .running
+            .iter()
+            .position(|(running, _)| *running == hash)?;
+        Some(self.running.swap_remove(index).1)
+    }
+}
+
+/// Why a download failed, so callers can pick between retrying, fetching from another peer, or giving up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadFailureKind {
+    /// The peer didn't answer in time. Retrying, possibly from another peer, may work.
+    Timeout,
+    /// The connection to the peer was lost or refused. Retrying from another peer may work.
+    PeerDisconnected,
+    /// The peer sent data that doesn't match the blob's hash. Don't retry from this peer.
+    HashMismatch,
+    /// The blob verified, but isn't something we can decode.
+    /// The same hash will always give the same bytes, so retrying is pointless.
+    Undecodable,
+    /// Our local blob store failed. This isn't the peer's fault.
+    StoreError,
+    Other,
+}
+
+impl DownloadFailureKind {
+    /// Categorizes an error by the first cause in its chain that's a typed error we recognize.
+    /// Errors that crossed iroh-blobs' RPC boundary lost their types, and are [`Self::Other`].
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(Self::from_cause)
+            .unwrap_or(Self::Other)
+    }
+
+    fn from_cause(cause: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if cause.is::<tokio::time::error::Elapsed>() {
+            return Some(Self::Timeout);
+        }
+        if cause.is::<postcard::Error>() {
+            return Some(Self::Undecodable);
+        }
+        if let Some(err) = cause.downcast_ref::<GetError>() {
+            return match err {
+                GetError::RemoteReset(_) => Some(Self::PeerDisconnected),
+                GetError::NoncompliantNode(_) => Some(Self::HashMismatch),
+                GetError::LocalFailure(_) => Some(Self::StoreError),
+                GetError::NotFound(_) | GetError::Io(_) | GetError::BadRequest(_) => None,
+            };
+        }
+        if let Some(err) = cause.downcast_ref::<ConnectionError>() {
+            return match err {
+                ConnectionError::TimedOut => Some(Self::Timeout),
+                ConnectionError::Reset
+                | ConnectionError::ConnectionClosed(_)
+                | ConnectionError::ApplicationClosed(_) => Some(Self::PeerDisconnected),
+                _ => None,
+            };
+        }
+        if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+            return match err.kind() {
+                std::io::ErrorKind::TimedOut => Some(Self::Timeout),
+                std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::NotConnected
+                | std::io::ErrorKind::BrokenPipe => Some(Self::PeerDisconnected),
+                _ => None,
+            };
+        }
+        None
+    }
+
+    /// Whether downloading the same blob again has any chance of succeeding.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, Self::Undecodable)
+    }
 }
 
 impl<D: Networkable> Debug for DownloadComplete<D> {
@@ -120,6 +380,7 @@ pub struct DownloadManager<D: Networkable> {
     task_handle: Option<JoinHandle<()>>,
     event_receiver: mpsc::UnboundedReceiver<DownloadManagerEvent<D>>,
     tx_new_item: mpsc::UnboundedSender<()>,
+    queue: DownloadQueue,
 }
 
 impl<D: Networkable> Debug for DownloadManager<D> {
@@ -127,6 +388,7 @@ impl<D: Networkable> Debug for DownloadManager<D> {
         f.debug_struct("DownloadManager")
             .field("downloads", &self.downloads)
             .field("reading", &self.reading)
+            .field("queue", &self.queue)
             .finish()
     }
 }
@@ -145,6 +407,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
             task_handle: None,
             event_receiver,
             tx_new_item,
+            queue: DownloadQueue::default(),
         };
 
         let task_handle = tokio::spawn(async move {
@@ -179,7 +442,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         &mut self,
         blob_ticket: BlobTicket,
         tag: u32,
-        progress: mpsc::UnboundedReceiver<Result<DownloadProgress>>,
+        progress: mpsc::UnboundedReceiver<Result<GetProgress>>,
     ) {
         let downloads = self.downloads.clone();
         let sender = self.tx_new_item.clone();
@@ -195,6 +458,22 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         });
     }
 
+    /// Queues a download until [`Self::next_to_start`] hands it out.
+    pub(crate) fn enqueue(&mut self, download: QueuedDownload) {
+        self.queue.queued.push_back(download);
+    }
+
+    /// The next queued download to start, highest priority first, while fewer than `max_running` are in flight.
+    /// It holds its slot until it's [`Self::finished`].
+    pub(crate) fn next_to_start(&mut self, max_running: usize) -> Option<QueuedDownload> {
+        self.queue.pop_startable(max_running)
+    }
+
+    /// Frees the slot of a completed or failed download of `hash`, returning the priority it had.
+    pub(crate) fn finished(&mut self, hash: iroh_blobs::Hash) -> Option<DownloadPriority> {
+        self.queue.finished(hash)
+    }
+
     pub fn read(&mut self, blob_ticket: BlobTicket, tag: u32, download: oneshot::Receiver<Bytes>) {
         let reading = self.reading.clone();
         let sender = self.tx_new_item.clone();
@@ -224,7 +503,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
 
         #[derive(Debug)]
         enum FutureResult {
-            Download(usize, Result<DownloadProgress>),
+            Download(usize, Result<GetProgress>),
             Read(usize, Result<Bytes>),
         }
 
@@ -268,14 +547,14 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
 
     fn handle_download_progress(
         downloads: &mut Vec<Download>,
-        result: Result<DownloadProgress>,
+        result: Result<GetProgress>,
         index: usize,
     ) -> Option<DownloadManagerEvent<D>> {
         let download = &mut downloads[index];
         let event = match result {
             Ok(progress) => match progress {
-                DownloadProgress::InitialState(_) => None,
-                DownloadProgress::FoundLocal { size, .. } => {
+                GetProgress::InitialState(_) => None,
+                GetProgress::FoundLocal { size, .. } => {
                     Some(DownloadManagerEvent::Update(DownloadUpdate {
                         blob_ticket: download.blob_ticket.clone(),
                         tag: download.tag,
@@ -285,8 +564,8 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                         all_done: false,
                     }))
                 }
-                DownloadProgress::Connected => None,
-                DownloadProgress::Found { size, .. } => {
+                GetProgress::Connected => None,
+                GetProgress::Found { size, .. } => {
                     download.total_size = size;
                     Some(DownloadManagerEvent::Update(DownloadUpdate {
                         blob_ticket: download.blob_ticket.clone(),
@@ -297,8 +576,8 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                         all_done: false,
                     }))
                 }
-                DownloadProgress::FoundHashSeq { .. } => None,
-                DownloadProgress::Progress { offset, .. } => {
+                GetProgress::FoundHashSeq { .. } => None,
+                GetProgress::Progress { offset, .. } => {
                     let delta = offset.saturating_sub(download.last_offset);
                     download.last_offset = offset;
                     Some(DownloadManagerEvent::Update(DownloadUpdate {
@@ -310,29 +589,32 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                         all_done: false,
                     }))
                 }
-                DownloadProgress::Done { .. } => None,
-                DownloadProgress::AllDone(_) => {
-                    Some(DownloadManagerEvent::Update(DownloadUpdate {
-                        blob_ticket: download.blob_ticket.clone(),
-                        tag: download.tag,
-                        downloaded_size_delta: 0,
-                        downloaded_size: download.total_size,
-                        total_size: download.total_size,
-                        all_done: true,
-                    }))
-                }
-                DownloadProgress::Abort(err) => {
+                GetProgress::Done { .. } => None,
+                GetProgress::AllDone(_) => Some(DownloadManagerEvent::Update(DownloadUpdate {
+                    blob_ticket: download.blob_ticket.clone(),
+                    tag: download.tag,
+                    downloaded_size_delta: 0,
+                    downloaded_size: download.total_size,
+                    total_size: download.total_size,
+                    all_done: true,
+                })),
+                GetProgress::Abort(err) => {
+                    let error = err.into();
                     Some(DownloadManagerEvent::Failed(DownloadFailed {
                         blob_ticket: download.blob_ticket.clone(),
-                        error: err.into(),
+                        kind: DownloadFailureKind::from_error(&error),
+                        error,
                         tag: download.tag,
+                        priority: DownloadPriority::default(),
                     }))
                 }
             },
             Err(e) => Some(DownloadManagerEvent::Failed(DownloadFailed {
                 blob_ticket: download.blob_ticket.clone(),
+                kind: DownloadFailureKind::from_error(&e),
                 error: e,
                 tag: download.tag,
+                priority: DownloadPriority::default(),
             })),
         };
         match &event {
@@ -365,7 +647,7 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
         result: Result<Bytes>,
     ) -> Option<DownloadManagerEvent<D>> {
         match result {
-            Ok(bytes) => match postcard::from_bytes(&bytes) {
+            Ok(bytes) => match decode_download(&bytes) {
                 Ok(decoded) => Some(DownloadManagerEvent::Complete(DownloadComplete {
                     data: decoded,
                     from: downloader.blob_ticket.node_addr().node_id,
@@ -374,14 +656,182 @@ impl<D: Networkable + Send + 'static> DownloadManager<D> {
                 Err(err) => Some(DownloadManagerEvent::Failed(DownloadFailed {
                     blob_ticket: downloader.blob_ticket,
                     tag: downloader.tag,
-                    error: err.into(),
+                    kind: DownloadFailureKind::Undecodable,
+                    error: err,
+                    priority: DownloadPriority::default(),
                 })),
             },
             Err(e) => Some(DownloadManagerEvent::Failed(DownloadFailed {
                 blob_ticket: downloader.blob_ticket,
                 tag: downloader.tag,
+                kind: DownloadFailureKind::StoreError,
                 error: e,
+                priority: DownloadPriority::default(),
             })),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::{NodeAddr, SecretKey};
+    use iroh_blobs::BlobFormat;
+    use std::io;
+
+    fn kind_of(error: impl Into<anyhow::Error>) -> DownloadFailureKind {
+        DownloadFailureKind::from_error(&error.into())
+    }
+
+    #[tokio::test]
+    async fn test_download_failure_kinds() {
+        let elapsed = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        assert_eq!(kind_of(elapsed), DownloadFailureKind::Timeout);
+        assert_eq!(
+            kind_of(io::Error::from(io::ErrorKind::TimedOut)),
+            DownloadFailureKind::Timeout
+        );
+        assert_eq!(
+            kind_of(
+                anyhow::Error::from(io::Error::from(io::ErrorKind::ConnectionReset))
+                    .context("downloading blob")
+            ),
+            DownloadFailureKind::PeerDisconnected
+        );
+        assert_eq!(
+            kind_of(ConnectionError::TimedOut),
+            DownloadFailureKind::Timeout
+        );
+        assert_eq!(
+            kind_of(ConnectionError::Reset),
+            DownloadFailureKind::PeerDisconnected
+        );
+        assert_eq!(
+            kind_of(GetError::RemoteReset(anyhow!("stream reset"))),
+            DownloadFailureKind::PeerDisconnected
+        );
+        assert_eq!(
+            kind_of(
+                anyhow::Error::from(GetError::NoncompliantNode(anyhow!("leaf hash mismatch")))
+                    .context("downloading blob")
+            ),
+            DownloadFailureKind::HashMismatch
+        );
+        assert_eq!(
+            kind_of(GetError::LocalFailure(anyhow!("disk full"))),
+            DownloadFailureKind::StoreError
+        );
+        // only typed errors count, not what the message says
+        assert_eq!(
+            kind_of(anyhow!("remote reset: timed out")),
+            DownloadFailureKind::Other
+        );
+        assert_eq!(
+            kind_of(postcard::Error::DeserializeUnexpectedEnd),
+            DownloadFailureKind::Undecodable
+        );
+        assert_eq!(kind_of(anyhow!("not found")), DownloadFailureKind::Other);
+
+        assert!(DownloadFailureKind::Timeout.is_retryable());
+        assert!(!DownloadFailureKind::Undecodable.is_retryable());
+    }
+
+    #[test]
+    fn test_high_priority_downloads_start_first() {
+        let queued = |blob: &str, priority| QueuedDownload {
+            ticket: BlobTicket::new(
+                NodeAddr::new(SecretKey::from_bytes(&[1; 32]).public()),
+                iroh_blobs::Hash::new(blob),
+                BlobFormat::Raw,
+            )
+            .unwrap(),
+            tag: 0,
+            additional_peers_to_try: vec![],
+            priority,
+        };
+        let hash = |blob: &str| iroh_blobs::Hash::new(blob);
+        let mut queue = DownloadQueue::default();
+        for blob in ["round 1", "round 2", "round 3"] {
+            queue
+                .queued
+                .push_back(queued(blob, DownloadPriority::Normal));
+        }
+        let started = queue.pop_startable(2).unwrap();
+        assert_eq!(started.ticket.hash(), hash("round 1"));
+
+        // a catch-up download jumps the queue
+        queue
+            .queued
+            .push_back(queued("checkpoint", DownloadPriority::High));
+        let started = queue.pop_startable(2).unwrap();
+        assert_eq!(started.ticket.hash(), hash("checkpoint"));
+        assert_eq!(started.priority, DownloadPriority::High);
+
+        // no free slots
+        assert!(queue.pop_startable(2).is_none());
+        assert_eq!(
+            queue.finished(hash("round 1")),
+            Some(DownloadPriority::Normal)
+        );
+        // a free slot, but round data waits until the catch-up download is done
+        assert!(queue.pop_startable(2).is_none());
+        assert_eq!(
+            queue.finished(hash("checkpoint")),
+            Some(DownloadPriority::High)
+        );
+        assert_eq!(queue.finished(hash("checkpoint")), None);
+
+        let started: Vec<_> = std::iter::from_fn(|| queue.pop_startable(2))
+            .map(|download| download.ticket.hash())
+            .collect();
+        assert_eq!(started, [hash("round 2"), hash("round 3")]);
+        assert!(queue.queued.is_empty());
+    }
+
+    #[test]
+    fn test_download_throughput_and_eta() {
+        let hash = iroh_blobs::Hash::new(b"blob");
+        let ticket = BlobTicket::new(
+            NodeAddr::new(SecretKey::from_bytes(&[1; 32]).public()),
+            hash,
+            BlobFormat::Raw,
+        )
+        .unwrap();
+        let update = |downloaded_size_delta, downloaded_size, total_size| DownloadUpdate {
+            blob_ticket: ticket.clone(),
+            tag: 0,
+            downloaded_size_delta,
+            downloaded_size,
+            total_size,
+            all_done: false,
+        };
+        let start = Instant::now();
+        let secs = |secs| start + Duration::from_secs(secs);
+
+        // no size yet, so no ETA
+        let mut download = InFlightDownload::new(update(0, 0, 0), start);
+        assert_eq!(download.progress(start).eta, None);
+
+        download.record(update(0, 0, 1000), secs(0));
+        download.record(update(100, 100, 1000), secs(1));
+        download.record(update(100, 200, 1000), secs(2));
+        let progress = download.progress(secs(2));
+        assert_eq!(progress.hash, hash);
+        assert_eq!((progress.downloaded, progress.total), (200, 1000));
+        assert_eq!(progress.bytes_per_sec, 100.0);
+        // not enough samples to guess yet
+        assert_eq!(progress.eta, None);
+
+        download.record(update(100, 300, 1000), secs(3));
+        let progress = download.progress(secs(3));
+        assert_eq!(progress.bytes_per_sec, 100.0);
+        assert_eq!(progress.eta, Some(Duration::from_secs(7)));
+
+        // old samples leave the window, so a stall shows up
+        let progress = download.progress(secs(3 + THROUGHPUT_WINDOW.as_secs()));
+        assert_eq!(progress.bytes_per_sec, 0.0);
+        assert_eq!(progress.eta, None);
+    }
+}
@@ -0,0 +1,507 @@
+use crate::{SignedMessage, SignedMessageError};
+
+use bytes::{Bytes, BytesMut};
+use iroh::{PublicKey, SecretKey};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Sits where the signed message version would, marking a gossip message as one fragment of a bigger one.
+/// Nodes that don't know about fragments reject them as an unknown version instead of misreading them.
+pub const FRAGMENT_TAG: u8 = 0xff;
+
+/// Room left in every gossip message for our tag, signature and fragment header, and for gossip's own framing.
+pub const FRAGMENT_OVERHEAD: usize = 256;
+
+/// How many messages one peer can have partially sent us at once. Past that, their oldest one is dropped.
+const MAX_PARTIAL_MESSAGES_PER_SENDER: usize = 4;
+
+/// How many partially received messages we hold on to across all peers. Past that, the oldest one is dropped.
+/// With the per-message size cap, this bounds how much memory fragments can take up.
+const MAX_PARTIAL_MESSAGES: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum FragmentError {
+    #[error("Gossip max message size {0} leaves no room for data after the {FRAGMENT_OVERHEAD} bytes of fragment overhead")]
+    MaxMessageSizeTooSmall(usize),
+
+    #[error(
+        "Message of {size} bytes would need {count} fragments, more than the maximum of {}",
+        u16::MAX
+    )]
+    TooManyFragments { size: usize, count: usize },
+
+    #[error(transparent)]
+    Sign(#[from] anyhow::Error),
+
+    #[error(transparent)]
+    SignedMessage(#[from] SignedMessageError),
+
+    #[error("Message of {size} bytes is bigger than the maximum of {max} bytes")]
+    TooLarge { size: usize, max: usize },
+
+    #[error("Fragment {index} is out of range for a message of {count} fragments")]
+    InvalidIndex { index: u16, count: u16 },
+
+    #[error("Fragment of {size} bytes is bigger than the maximum of {max} bytes")]
+    FragmentTooLarge { size: usize, max: usize },
+
+    #[error("Fragment says message {message_id} has {count} fragments, but earlier ones said {expected}")]
+    CountMismatch {
+        message_id: u64,
+        count: u16,
+        expected: u16,
+    },
+}
+
+/// One ordered piece of a message too big to gossip whole.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Fragment {
+    /// Random, so fragments of different messages from the same sender don't get mixed up.
+    message_id: u64,
+    index: u16,
+    count: u16,
+    data: Bytes,
+}
+
+/// How many bytes of a message fit in one gossip message of at most `max_message_size` bytes.
+pub fn max_fragment_data_size(max_message_size: usize) -> Result<usize, FragmentError> {
+    match max_message_size.checked_sub(FRAGMENT_OVERHEAD) {
+        Some(size) if size > 0 => Ok(size),
+        _ => Err(FragmentError::MaxMessageSizeTooSmall(max_message_size)),
+    }
+}
+
+/// Splits an encoded [`SignedMessage`] into gossip messages of at most `max_message_size` bytes.
+/// A message that fits is returned as-is. Otherwise every fragment is signed on its own,
+/// so nobody else can slip their own data into the reassembled message.
+/// Messages bigger than `max_reassembled_size` are refused, since receivers would drop them anyway.
+pub fn fragment(
+    secret_key: &SecretKey,
+    encoded: Bytes,
+    max_message_size: usize,
+    max_reassembled_size: usize,
+) -> Result<Vec<Bytes>, FragmentError> {
+    let chunk_size = max_fragment_data_size(max_message_size)?;
+    if encoded.len() <= chunk_size {
+        return Ok(vec![encoded]);
+    }
+    if encoded.len() > max_reassembled_size {
+        return Err(FragmentError::TooLarge {
+            size: encoded.len(),
+            max: max_reassembled_size,
+        });
+    }
+
+    let count = encoded.len().div_ceil(chunk_size);
+    let count: u16 = count
+        .try_into()
+        .map_err(|_| FragmentError::TooManyFragments {
+            size: encoded.len(),
+            count,
+        })?;
+    let message_id = rand::random();
+    (0..count)
+        .map(|index| {
+            let start = index as usize * chunk_size;
+            let end = (start + chunk_size).min(encoded.len());
+            let fragment = Fragment {
+                message_id,
+                index,
+                count,
+                data: encoded.slice(start..end),
+            };
+            let signed = SignedMessage::sign_and_encode(secret_key, &fragment)?;
+            let mut tagged = BytesMut::with_capacity(signed.len() + 1);
+            tagged.extend_from_slice(&[FRAGMENT_TAG]);
+            tagged.extend_from_slice(&signed);
+            Ok(tagged.freeze())
+        })
+        .collect()
+}
+
+/// What came of a gossip message handed to [`FragmentBuffer::receive`].
+#[derive(Debug)]
+pub enum Received {
+    /// A message that was small enough to be sent whole.
+    Whole(Bytes),
+    /// The last missing fragment of a message from `from` arrived, and this is the message put back together.
+    Reassembled { from: PublicKey, bytes: Bytes },
+    /// A fragment of a message we don't have all of yet.
+    Pending,
+}
+
+#[derive(Debug)]
+struct PartialMessage {
+    count: u16,
+    // filled in as fragments arrive, so a bogus count doesn't make us allocate anything up front.
+    fragments: BTreeMap<u16, Bytes>,
+    first_seen: Instant,
+}
+
+/// Holds on to the fragments of each message until all of them arrived, in any order.
+/// Messages still incomplete `timeout` after their first fragment arrived are dropped,
+/// as are the oldest ones once a peer, or all peers together, have too many in flight.
+#[derive(Debug)]
+pub struct FragmentBuffer {
+    timeout: Duration,
+    chunk_size: usize,
+    max_reassembled_size: usize,
+    partial: HashMap<(PublicKey, u64), PartialMessage>,
+}
+
+impl FragmentBuffer {
+    /// `chunk_size` is the most data a fragment can carry, from [`max_fragment_data_size`],
+    /// and `max_reassembled_size` the biggest message we're willing to put back together.
+    pub fn new(timeout: Duration, chunk_size: usize, max_reassembled_size: usize) -> Self {
+        Self {
+            timeout,
+            chunk_size,
+            max_reassembled_size,
+            partial: HashMap::new(),
+        }
+    }
+
+    pub fn receive(&mut self, bytes: Bytes, now: Instant) -> Result<Received, FragmentError> {
+        if bytes.first() != Some(&FRAGMENT_TAG) {
+            return Ok(Received::Whole(bytes));
+        }
+        self.evict_expired(now);
+
+        let (from, fragment) = match SignedMessage::<Fragment>::verify_and_decode(&bytes[1..]) {
+            Ok(result) => result,
+            // an unversioned message can happen to start with the fragment tag
+            Err(_) if cfg!(feature = "legacy-unversioned-messages") => {
+                return Ok(Received::Whole(bytes))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let Fragment {
+            message_id,
+            index,
+            count,
+            data,
+        } = fragment;
+        if index >= count {
+            return Err(FragmentError::InvalidIndex { index, count });
+        }
+        if data.len() > self.chunk_size {
+            return Err(FragmentError::FragmentTooLarge {
+                size: data.len(),
+                max: self.chunk_size,
+            });
+        }
+        // every fragment but the last is a full chunk, so this is as big as the message can get.
+        let max_size = count as usize * self.chunk_size;
+        if max_size > self.max_reassembled_size {
+            return Err(FragmentError::TooLarge {
+                size: max_size,
+                max: self.max_reassembled_size,
+            });
+        }
+
+        if !self.partial.contains_key(&(from, message_id)) {
+            self.make_room_for(from);
+        }
+        let partial = self
+            .partial
+            .entry((from, message_id))
+            .or_insert_with(|| PartialMessage {
+                count,
+                fragments: BTreeMap::new(),
+                first_seen: now,
+            });
+        if partial.count != count {
+            return Err(FragmentError::CountMismatch {
+                message_id,
+                count,
+                expected: partial.count,
+            });
+        }
+        partial.fragments.entry(index).or_insert(data);
+        if partial.fragments.len() < count as usize {
+            return Ok(Received::Pending);
+        }
+
+        let partial = self.partial.remove(&(from, message_id)).unwrap();
+        let mut reassembled = BytesMut::new();
+        for data in partial.fragments.into_values() {
+            reassembled.extend_from_slice(&data);
+        }
+        Ok(Received::Reassembled {
+            from,
+            bytes: reassembled.freeze(),
+        })
+    }
+
+    /// Drops the oldest partial messages until a new one from `from` fits within the limits.
+    fn make_room_for(&mut self, from: PublicKey) {
+        let from_sender = self
+            .partial
+            .keys()
+            .filter(|(sender, _)| *sender == from)
+            .count();
+        if from_sender >= MAX_PARTIAL_MESSAGES_PER_SENDER {
+            self.evict_oldest(|sender| *sender == from);
+        }
+        if self.partial.len() >= MAX_PARTIAL_MESSAGES {
+            self.evict_oldest(|_| true);
+        }
+    }
+
+    fn evict_oldest(&mut self, from: impl Fn(&PublicKey) -> bool) {
+        let oldest = self
+            .partial
+            .iter()
+            .filter(|((sender, _), _)| from(sender))
+            .min_by_key(|(_, partial)| partial.first_seen)
+            .map(|(key, _)| *key);
+        if let Some(oldest) = oldest {
+            self.partial.remove(&oldest);
+        }
+    }
+
+    /// Drops every incomplete message whose first fragment arrived more than `timeout` ago.
+    /// Returns how many were dropped.
+    pub fn evict_expired(&mut self, now: Instant) -> usize {
+        let before = self.partial.len();
+        self.partial
+            .retain(|_, partial| now.duration_since(partial.first_seen) < self.timeout);
+        before - self.partial.len()
+    }
+
+    /// How many messages we have some, but not all, fragments of.
+    pub fn num_pending(&self) -> usize {
+        self.partial.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+
+    const MAX_MESSAGE_SIZE: usize = 1024;
+    const MAX_REASSEMBLED_SIZE: usize = 64 * 1024;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn buffer() -> FragmentBuffer {
+        FragmentBuffer::new(
+            TIMEOUT,
+            max_fragment_data_size(MAX_MESSAGE_SIZE).unwrap(),
+            MAX_REASSEMBLED_SIZE,
+        )
+    }
+
+    fn fragments_of(key: &SecretKey, payload: &Vec<u8>) -> Vec<Bytes> {
+        let encoded = SignedMessage::sign_and_encode(key, payload).unwrap();
+        fragment(key, encoded, MAX_MESSAGE_SIZE, MAX_REASSEMBLED_SIZE).unwrap()
+    }
+
+    fn secret_key(seed: u8) -> SecretKey {
+        SecretKey::from_bytes(&[seed; 32])
+    }
+
+    fn large_payload() -> Vec<u8> {
+        let mut payload = vec![0u8; 3 * max_fragment_data_size(MAX_MESSAGE_SIZE).unwrap()];
+        rand::thread_rng().fill_bytes(&mut payload);
+        payload
+    }
+
+    fn receive_all(buffer: &mut FragmentBuffer, fragments: Vec<Bytes>) -> Vec<Received> {
+        let now = Instant::now();
+        fragments
+            .into_iter()
+            .map(|fragment| buffer.receive(fragment, now).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_small_message_is_sent_whole() {
+        let key = secret_key(1);
+        let encoded = SignedMessage::sign_and_encode(&key, &vec![1u8, 2, 3]).unwrap();
+        let fragments = fragment(
+            &key,
+            encoded.clone(),
+            MAX_MESSAGE_SIZE,
+            MAX_REASSEMBLED_SIZE,
+        )
+        .unwrap();
+        assert_eq!(fragments, vec![encoded.clone()]);
+
+        let mut buffer = buffer();
+        match buffer.receive(encoded.clone(), Instant::now()).unwrap() {
+            Received::Whole(bytes) => assert_eq!(bytes, encoded),
+            other => panic!("expected the whole message, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_large_message_is_reassembled() {
+        let key = secret_key(1);
+        let payload = large_payload();
+        let encoded = SignedMessage::sign_and_encode(&key, &payload).unwrap();
+
+        let mut fragments = fragment(
+            &key,
+            encoded.clone(),
+            MAX_MESSAGE_SIZE,
+            MAX_REASSEMBLED_SIZE,
+        )
+        .unwrap();
+        // the encoded message is a little bigger than the payload, so it spills into a fourth fragment
+        assert_eq!(fragments.len(), 4);
+        assert!(fragments.iter().all(|f| f.len() <= MAX_MESSAGE_SIZE));
+
+        // gossip doesn't guarantee ordering
+        fragments.swap(0, 2);
+        let mut buffer = buffer();
+        let mut received = receive_all(&mut buffer, fragments);
+        let last = received.pop().unwrap();
+        assert!(received.iter().all(|r| matches!(r, Received::Pending)));
+
+        let Received::Reassembled { from, bytes } = last else {
+            panic!("expected the reassembled message, got {last:?}");
+        };
+        assert_eq!(from, key.public());
+        assert_eq!(bytes, encoded);
+        let (signer, decoded) = SignedMessage::<Vec<u8>>::verify_and_decode(&bytes).unwrap();
+        assert_eq!(signer, key.public());
+        assert_eq!(decoded, payload);
+        assert_eq!(buffer.num_pending(), 0);
+    }
+
+    #[test]
+    fn test_incomplete_message_is_evicted() {
+        let key = secret_key(1);
+        let encoded = SignedMessage::sign_and_encode(&key, &large_payload()).unwrap();
+        let mut fragments =
+            fragment(&key, encoded, MAX_MESSAGE_SIZE, MAX_REASSEMBLED_SIZE).unwrap();
+        let dropped = fragments.remove(1);
+
+        let start = Instant::now();
+        let mut buffer = buffer();
+        for fragment in fragments {
+            assert!(matches!(
+                buffer.receive(fragment, start).unwrap(),
+                Received::Pending
+            ));
+        }
+        assert_eq!(buffer.num_pending(), 1);
+
+        assert_eq!(buffer.evict_expired(start + TIMEOUT / 2), 0);
+        assert_eq!(buffer.evict_expired(start + TIMEOUT), 1);
+        assert_eq!(buffer.num_pending(), 0);
+
+        // the missing fragment showing up late doesn't resurrect the message
+        assert!(matches!(
+            buffer.receive(dropped, start + TIMEOUT).unwrap(),
+            Received::Pending
+        ));
+    }
+
+    #[test]
+    fn test_tampered_fragment_is_rejected() {
+        let key = secret_key(1);
+        let encoded = SignedMessage::sign_and_encode(&key, &large_payload()).unwrap();
+        let fragments = fragment(&key, encoded, MAX_MESSAGE_SIZE, MAX_REASSEMBLED_SIZE).unwrap();
+
+        let mut tampered = fragments[0].to_vec();
+        let last = tampered.len() - 100;
+        tampered[last] ^= 1;
+        let mut buffer = buffer();
+        assert!(matches!(
+            buffer.receive(tampered.into(), Instant::now()),
+            Err(FragmentError::SignedMessage(_))
+        ));
+        assert_eq!(buffer.num_pending(), 0);
+    }
+
+    #[test]
+    fn test_oversized_messages_are_refused() {
+        let key = secret_key(1);
+        let payload = vec![7u8; MAX_REASSEMBLED_SIZE];
+        let encoded = SignedMessage::sign_and_encode(&key, &payload).unwrap();
+        assert!(matches!(
+            fragment(
+                &key,
+                encoded.clone(),
+                MAX_MESSAGE_SIZE,
+                MAX_REASSEMBLED_SIZE
+            ),
+            Err(FragmentError::TooLarge { .. })
+        ));
+
+        // a sender with a bigger cap than ours: we refuse its first fragment, before buffering anything
+        let fragments =
+            fragment(&key, encoded, MAX_MESSAGE_SIZE, 2 * MAX_REASSEMBLED_SIZE).unwrap();
+        let mut buffer = buffer();
+        assert!(matches!(
+            buffer.receive(fragments[0].clone(), Instant::now()),
+            Err(FragmentError::TooLarge { .. })
+        ));
+        assert_eq!(buffer.num_pending(), 0);
+    }
+
+    #[test]
+    fn test_partial_messages_are_bounded() {
+        let start = Instant::now();
+        let mut buffer = buffer();
+
+        // one sender starting many messages only keeps its newest few
+        let spammer = secret_key(1);
+        for i in 0..MAX_PARTIAL_MESSAGES_PER_SENDER + 2 {
+            let fragments = fragments_of(&spammer, &large_payload());
+            let now = start + Duration::from_millis(i as u64);
+            assert!(matches!(
+                buffer.receive(fragments[0].clone(), now).unwrap(),
+                Received::Pending
+            ));
+        }
+        assert_eq!(buffer.num_pending(), MAX_PARTIAL_MESSAGES_PER_SENDER);
+
+        // and can't push out other senders' messages
+        let honest = secret_key(2);
+        let honest_fragments = fragments_of(&honest, &large_payload());
+        buffer
+            .receive(honest_fragments[0].clone(), start + Duration::from_secs(1))
+            .unwrap();
+        for (i, fragments) in (0..MAX_PARTIAL_MESSAGES_PER_SENDER)
+            .map(|_| fragments_of(&spammer, &large_payload()))
+            .enumerate()
+        {
+            let now = start + Duration::from_secs(2) + Duration::from_millis(i as u64);
+            buffer.receive(fragments[0].clone(), now).unwrap();
+        }
+        let mut received = None;
+        for fragment in honest_fragments.into_iter().skip(1) {
+            received = Some(
+                buffer
+                    .receive(fragment, start + Duration::from_secs(3))
+                    .unwrap(),
+            );
+        }
+        assert!(matches!(received, Some(Received::Reassembled { .. })));
+
+        // across many senders, the total is capped too
+        for seed in 3..3 + MAX_PARTIAL_MESSAGES as u8 {
+            let fragments = fragments_of(&secret_key(seed), &large_payload());
+            buffer
+                .receive(fragments[0].clone(), start + Duration::from_secs(4))
+                .unwrap();
+        }
+        assert_eq!(buffer.num_pending(), MAX_PARTIAL_MESSAGES);
+    }
+
+    #[test]
+    fn test_max_message_size_too_small() {
+        assert!(matches!(
+            max_fragment_data_size(FRAGMENT_OVERHEAD),
+            Err(FragmentError::MaxMessageSizeTooSmall(_))
+        ));
+        assert_eq!(max_fragment_data_size(FRAGMENT_OVERHEAD + 1).unwrap(), 1);
+    }
+}
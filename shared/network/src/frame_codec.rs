@@ -0,0 +1,205 @@
+//! How [`crate::TcpServer`] and [`crate::TcpClient`] frame their messages, whichever transport carries them.
+//!
+//! Every message is postcard-encoded and sent as one frame with a 4-byte big-endian length prefix.
+//! Over TCP the frames are written straight to the stream. Over WebSocket the same bytes are sent as
+//! binary messages of at most [`WS_CHUNK_SIZE`] bytes, so a frame can span several messages and a message
+//! can hold the end of one frame and the start of the next. Receivers put them back together with a [`FrameReassembler`].
+//! A browser client only has to do the same to talk to the server.
+
+use bytes::{Bytes, BytesMut};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt::Debug, io};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio_util::codec::{Decoder, Encoder, Framed, LengthDelimitedCodec};
+
+pub const MAX_FRAME_LENGTH: usize = 64 * 1024 * 1024;
+
+/// The largest WebSocket message a frame is split into.
+/// Browsers and proxies handle these far better than a single message per (up to 64MiB) frame.
+pub const WS_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) enum ServerToClientMessage<T: Debug> {
+    Challenge([u8; 32]),
+    Else(T),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) enum ClientToServerMessage<T: Debug> {
+    ChallengeResponse(Vec<u8>),
+    Else(T),
+}
+
+pub fn frame_codec() -> LengthDelimitedCodec {
+    LengthDelimitedCodec::builder()
+        .max_frame_length(MAX_FRAME_LENGTH)
+        .new_codec()
+}
+
+/// Collects frames out of the chunks a transport delivers them in.
+#[derive(Debug)]
+pub struct FrameReassembler {
+    codec: LengthDelimitedCodec,
+    buffer: BytesMut,
+}
+
+impl FrameReassembler {
+    pub fn new() -> Self {
+        Self {
+            codec: frame_codec(),
+            buffer: BytesMut::new(),
+        }
+    }
+
+    /// Adds bytes received from the transport, returning every frame they completed, in order.
+    pub fn push(&mut self, chunk: &[u8]) -> io::Result<Vec<BytesMut>> {
+        self.buffer.extend_from_slice(chunk);
+        let mut frames = Vec::new();
+        while let Some(frame) = self.codec.decode(&mut self.buffer)? {
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    /// Whether there's no partial frame waiting on more bytes.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+impl Default for FrameReassembler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Frames `payload` and cuts it into WebSocket messages of at most [`WS_CHUNK_SIZE`] bytes.
+pub fn encode_ws_chunks(payload: Bytes) -> io::Result<Vec<Vec<u8>>> {
+    let mut frame = BytesMut::new();
+    frame_codec().encode(payload, &mut frame)?;
+    Ok(frame.chunks(WS_CHUNK_SIZE).map(<[u8]>::to_vec).collect())
+}
+
+/// A connection between a [`crate::TcpServer`] and one of its clients.
+pub(crate) enum FramedConnection {
+    Tcp(Framed<TcpStream, LengthDelimitedCodec>),
+    WebSocket {
+        stream: Box<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+        reassembler: FrameReassembler,
+        // completed frames that arrived in the same message as an earlier one.
+        received: VecDeque<BytesMut>,
+    },
+}
+
+impl FramedConnection {
+    pub fn tcp(stream: TcpStream) -> Self {
+        Self::Tcp(Framed::new(stream, frame_codec()))
+    }
+
+    pub fn websocket(stream: WebSocketStream<MaybeTlsStream<TcpStream>>) -> Self {
+        Self::WebSocket {
+            stream: Box::new(stream),
+            reassembler: FrameReassembler::new(),
+            received: VecDeque::new(),
+        }
+    }
+
+    /// Sends `payload` as one frame, waiting until the transport has taken all of it,
+    /// so a slow reader holds back the sender instead of piling up data in memory.
+    pub async fn send(&mut self, payload: Vec<u8>) -> io::Result<()> {
+        match self {
+            Self::Tcp(framed) => framed.send(payload.into()).await,
+            Self::WebSocket { stream, .. } => {
+                for chunk in encode_ws_chunks(payload.into())? {
+                    stream
+                        .send(Message::Binary(chunk))
+                        .await
+                        .map_err(ws_io_error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The next frame, or `None` once the other side closed the connection.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe: bytes of a partially received frame are kept for the next call.
+    pub async fn next(&mut self) -> Option<io::Result<BytesMut>> {
+        match self {
+            Self::Tcp(framed) => framed.next().await,
+            Self::WebSocket {
+                stream,
+                reassembler,
+                received,
+            } => loop {
+                if let Some(frame) = received.pop_front() {
+                    return Some(Ok(frame));
+                }
+                match stream.next().await? {
+                    Ok(Message::Binary(chunk)) => match reassembler.push(&chunk) {
+                        Ok(frames) => received.extend(frames),
+                        Err(err) => return Some(Err(err)),
+                    },
+                    Ok(Message::Close(_)) => return None,
+                    // pings are answered by tungstenite itself, and nothing here sends text.
+                    Ok(_) => {}
+                    Err(err) => return Some(Err(ws_io_error(err))),
+                }
+            },
+        }
+    }
+}
+
+fn ws_io_error(err: tokio_tungstenite::tungstenite::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reassembles_frames_split_across_chunks() {
+        let payloads: Vec<Bytes> = [3 * WS_CHUNK_SIZE + 17, 5, 0, WS_CHUNK_SIZE - 4]
+            .into_iter()
+            .enumerate()
+            .map(|(i, len)| (0..len).map(|b| (b + i) as u8).collect::<Vec<_>>().into())
+            .collect();
+        let stream: Vec<u8> = payloads
+            .iter()
+            .flat_map(|payload| encode_ws_chunks(payload.clone()).unwrap())
+            .flatten()
+            .collect();
+
+        // deliver the stream in pieces that don't line up with frame boundaries
+        let mut reassembler = FrameReassembler::new();
+        let mut frames = Vec::new();
+        for chunk in stream.chunks(1000) {
+            frames.extend(reassembler.push(chunk).unwrap());
+        }
+        assert!(reassembler.is_empty());
+        assert_eq!(frames, payloads);
+    }
+
+    #[test]
+    fn test_ws_chunks_are_bounded() {
+        let chunks = encode_ws_chunks(vec![7u8; 2 * WS_CHUNK_SIZE].into()).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= WS_CHUNK_SIZE));
+        assert_eq!(
+            chunks.iter().map(Vec::len).sum::<usize>(),
+            2 * WS_CHUNK_SIZE + 4
+        );
+    }
+
+    #[test]
+    fn test_oversized_frames_are_rejected() {
+        let mut reassembler = FrameReassembler::new();
+        let header = ((MAX_FRAME_LENGTH + 1) as u32).to_be_bytes();
+        assert!(reassembler.push(&header).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use iroh_gossip::proto::{
+    hyparview::Ttl,
+    plumtree::{DeliveryScope, Round},
+    HyparviewConfig, PlumtreeConfig,
+};
+use std::time::Duration;
+
+/// How gossip messages spread through the mesh.
+#[derive(Debug, Clone, Copy)]
+pub struct GossipConfig {
+    /// How many peers we keep direct gossip connections to. Messages are pushed to all of them.
+    pub fanout: usize,
+    /// How many hops a join request is forwarded for before a peer has to accept it.
+    /// Raise it in large meshes so new peers end up connected further away from their bootstrap peer.
+    pub join_ttl: u16,
+    /// The biggest gossip message we send or accept, in bytes.
+    /// Broadcasts that don't fit are split into fragments, which receivers put back together.
+    pub max_message_size: usize,
+    /// Broadcast payloads bigger than this many bytes are zlib-compressed before signing. `None` never compresses.
+    pub compress_above: Option<usize>,
+    /// How long to hold on to the fragments of a broadcast that's still missing some before dropping it.
+    pub fragment_timeout: Duration,
+    /// The biggest broadcast we send or put back together from fragments, in bytes.
+    pub max_reassembled_size: usize,
+}
+
+impl Default for GossipConfig {
+    fn default() -> Self {
+        Self {
+            fanout: 8,
+            join_ttl: HyparviewConfig::default().active_random_walk_length.0,
+            max_message_size: 4096,
+            compress_above: Some(512),
+            fragment_timeout: Duration::from_secs(30),
+            max_reassembled_size: 4 * 1024 * 1024,
+        }
+    }
+}
+
+impl GossipConfig {
+    pub(crate) fn membership_config(&self) -> HyparviewConfig {
+        HyparviewConfig {
+            active_view_capacity: self.fanout,
+            active_random_walk_length: Ttl(self.join_ttl),
+            shuffle_interval: Duration::from_secs(30),
+            neighbor_request_timeout: Duration::from_secs(2),
+            ..HyparviewConfig::default()
+        }
+    }
+
+    pub(crate) fn broadcast_config(&self) -> PlumtreeConfig {
+        PlumtreeConfig {
+            graft_timeout_2: Duration::from_millis(200),
+            message_cache_retention: Duration::from_secs(60),
+            message_id_retention: Duration::from_secs(2 * 60),
+            ..PlumtreeConfig::default()
+        }
+    }
+}
+
+/// How many hops received gossip messages travelled, to monitor how deep propagation goes in the mesh.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GossipHopStats {
+    pub messages: u64,
+    pub total_hops: u64,
+    pub max_hops: u16,
+}
+
+impl GossipHopStats {
+    pub fn record(&mut self, hops: u16) {
+        self.messages += 1;
+        self.total_hops += hops as u64;
+        self.max_hops = self.max_hops.max(hops);
+    }
+
+    pub fn mean_hops(&self) -> Option<f64> {
+        (self.messages != 0).then(|| self.total_hops as f64 / self.messages as f64)
+    }
+}
+
+/// How many hops a message took to reach us, or `None` if a neighbor sent it to us directly.
+pub(crate) fn delivery_hops(scope: &DeliveryScope) -> Option<u16> {
+    match scope {
+        DeliveryScope::Swarm(round) => Some(round_hops(*round)),
+        DeliveryScope::Neighbors => None,
+    }
+}
+
+/// iroh-gossip doesn't give access to the number inside a [`Round`],
+/// but it serializes as nothing but that number.
+fn round_hops(round: Round) -> u16 {
+    postcard::to_allocvec(&round)
+        .ok()
+        .and_then(|bytes| postcard::from_bytes(&bytes).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gossip_config_is_applied() {
+        let config = GossipConfig {
+            fanout: 12,
+            join_ttl: 9,
+            max_message_size: 4096,
+            compress_above: None,
+            fragment_timeout: Duration::from_secs(30),
+            max_reassembled_size: 4 * 1024 * 1024,
+        };
+        let membership = config.membership_config();
+        assert_eq!(membership.active_view_capacity, 12);
+        assert_eq!(membership.active_random_walk_length.0, 9);
+        assert_eq!(membership.shuffle_interval, Duration::from_secs(30));
+
+        let defaults = GossipConfig::default().membership_config();
+        assert_eq!(defaults.active_view_capacity, 8);
+        assert_eq!(
+            defaults.active_random_walk_length,
+            HyparviewConfig::default().active_random_walk_length
+        );
+    }
+
+    #[test]
+    fn test_delivery_hops() {
+        assert_eq!(delivery_hops(&DeliveryScope::Neighbors), None);
+        assert_eq!(
+            delivery_hops(&DeliveryScope::Swarm(Round::from(3))),
+            Some(3)
+        );
+
+        let mut stats = GossipHopStats::default();
+        assert_eq!(stats.mean_hops(), None);
+        for hops in [1, 2, 6] {
+            stats.record(hops);
+        }
+        assert_eq!(stats.max_hops, 6);
+        assert_eq!(stats.mean_hops(), Some(3.0));
+    }
+}
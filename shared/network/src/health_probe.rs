@@ -0,0 +1,138 @@
+use anyhow::Result;
+use iroh::{endpoint::Connection, Endpoint, NodeAddr};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::time::timeout;
+
+use crate::time_sync::{unix_micros_now, TimeSyncExchange};
+
+pub const ALPN: &[u8] = b"psyche-health-probe/1";
+
+const PROBE_LEN: usize = 8;
+/// The echoed probe, followed by when the peer received it and when it answered, by its clock.
+const ANSWER_LEN: usize = PROBE_LEN + 16;
+
+#[derive(Debug, Error)]
+pub enum HealthProbeError {
+    #[error("no answer within {0:?}")]
+    TimedOut(Duration),
+
+    #[error("peer answered the probe with the wrong payload")]
+    BadAnswer,
+
+    #[error("probe failed: {0:#}")]
+    Failed(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HealthProbe {
+    pub round_trip: Duration,
+    /// How far ahead of ours the peer's clock is, see [`TimeSyncExchange::clock_offset_secs`].
+    pub clock_offset_secs: f64,
+}
+
+/// Echoes back every probe sent over this connection, with our timestamps, until the peer closes it.
+pub async fn answer_probes(connection: Connection) -> Result<()> {
+    while let Ok((mut send, mut recv)) = connection.accept_bi().await {
+        let mut answer = recv.read_to_end(PROBE_LEN).await?;
+        let received = unix_micros_now();
+        answer.extend_from_slice(&received.to_le_bytes());
+        answer.extend_from_slice(&unix_micros_now().to_le_bytes());
+        send.write_all(&answer).await?;
+        send.finish()?;
+    }
+    Ok(())
+}
+
+/// Connects to `node` and times a round trip of a small payload, estimating the peer's clock offset along the way.
+/// Peers that don't answer within `probe_timeout` are reported as [`HealthProbeError::TimedOut`].
+pub async fn probe_peer(
+    endpoint: &Endpoint,
+    node: impl Into<NodeAddr>,
+    probe_timeout: Duration,
+) -> Result<HealthProbe, HealthProbeError> {
+    let start = Instant::now();
+    let payload: [u8; PROBE_LEN] = rand::random();
+    let round_trip = async {
+        let connection = endpoint.connect(node, ALPN).await?;
+        let (mut send, mut recv) = connection.open_bi().await?;
+        let sent = unix_micros_now();
+        send.write_all(&payload).await?;
+        send.finish()?;
+        let answer = recv.read_to_end(ANSWER_LEN).await?;
+        let received = unix_micros_now();
+        connection.close(0u8.into(), b"probe done");
+        anyhow::Ok((sent, answer, received))
+    };
+    let (sent, answer, received) = timeout(probe_timeout, round_trip)
+        .await
+        .map_err(|_| HealthProbeError::TimedOut(probe_timeout))??;
+    if answer.len() != ANSWER_LEN || answer[..PROBE_LEN] != payload {
+        return Err(HealthProbeError::BadAnswer);
+    }
+    let peer_timestamp = |at: usize| i64::from_le_bytes(answer[at..at + 8].try_into().unwrap());
+    let exchange = TimeSyncExchange {
+        sent,
+        peer_received: peer_timestamp(PROBE_LEN),
+        peer_sent: peer_timestamp(PROBE_LEN + 8),
+        received,
+    };
+    Ok(HealthProbe {
+        round_trip: start.elapsed(),
+        clock_offset_secs: exchange.clock_offset_secs(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOW_PEER_DELAY: Duration = Duration::from_millis(300);
+
+    /// A peer that answers probes correctly, but only after [`SLOW_PEER_DELAY`].
+    async fn slow_peer() -> Result<(Endpoint, tokio::task::JoinHandle<()>)> {
+        let endpoint = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .alpns(vec![ALPN.to_vec()])
+            .bind()
+            .await?;
+        let accept_endpoint = endpoint.clone();
+        let accept_task = tokio::spawn(async move {
+            while let Some(incoming) = accept_endpoint.accept().await {
+                let Ok(connection) = incoming.await else {
+                    continue;
+                };
+                tokio::spawn(async move {
+                    tokio::time::sleep(SLOW_PEER_DELAY).await;
+                    let _ = answer_probes(connection).await;
+                });
+            }
+        });
+        Ok((endpoint, accept_task))
+    }
+
+    #[tokio::test]
+    async fn test_probe_timeout() -> Result<()> {
+        let (peer, accept_task) = slow_peer().await?;
+        let peer_addr = peer.node_addr().await?;
+        let client = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .bind()
+            .await?;
+
+        let probe = probe_peer(&client, peer_addr.clone(), SLOW_PEER_DELAY * 10).await?;
+        assert!(probe.round_trip >= SLOW_PEER_DELAY);
+        // same machine, same clock
+        assert!(probe.clock_offset_secs.abs() < SLOW_PEER_DELAY.as_secs_f64());
+
+        assert!(matches!(
+            probe_peer(&client, peer_addr, SLOW_PEER_DELAY / 3).await,
+            Err(HealthProbeError::TimedOut(_))
+        ));
+
+        accept_task.abort();
+        client.close().await;
+        peer.close().await;
+        Ok(())
+    }
+}
@@ -1,33 +1,38 @@
 use allowlist::Allowlist;
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use blob_store::BlobStore;
+use blob_tags::{blob_tag, list_blob_tags};
 use bytes::Bytes;
-use download_manager::{DownloadManager, DownloadManagerEvent, DownloadUpdate};
-use futures_util::StreamExt;
+use download_manager::{
+    decode_download, DownloadManager, DownloadManagerEvent, DownloadUpdate, InFlightDownload,
+    QueuedDownload,
+};
+use fragment::{FragmentBuffer, Received};
+use futures_util::{future::select_all, Stream, StreamExt};
 use iroh::endpoint::RemoteInfo;
 use iroh_blobs::{
     downloader::ConcurrencyLimits,
-    net_protocol::{Blobs, DownloadMode},
-    rpc::client::blobs::DownloadOptions,
-    store::mem::Store,
+    net_protocol::DownloadMode,
+    provider::EventSender,
+    rpc::client::blobs::{DownloadOptions, MemClient},
+    store::GcConfig,
     util::SetTagOption,
     BlobFormat,
 };
-use iroh_gossip::{
-    net::{Gossip, GossipEvent, GossipReceiver, GossipSender},
-    proto::{HyparviewConfig, PlumtreeConfig},
-};
+use iroh_gossip::net::{Gossip, GossipEvent, GossipReceiver, GossipSender};
 use p2p_model_sharing::{
-    ModelConfigSharingMessage, ParameterSharingMessage, MODEL_REQUEST_TIMEOUT_SECS,
+    ModelConfigSharingMessage, ParameterSharingMessage, ProviderEvents, MODEL_REQUEST_TIMEOUT_SECS,
 };
+use rand::Rng;
 use router::Router;
 use state::State;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
     hash::{DefaultHasher, Hash as _, Hasher},
     iter::Cycle,
     marker::PhantomData,
-    net::{IpAddr, Ipv4Addr, SocketAddrV4},
+    net::{Ipv4Addr, SocketAddrV4},
     ops::Sub,
     sync::{Arc, Mutex as StdMutex},
     time::{Duration, Instant},
@@ -43,7 +48,8 @@ use tokio::{
 };
 use tokio_util::{sync::CancellationToken, time::FutureExt};
 use tracing::{debug, error, info, trace, warn};
-use util::{fmt_relay_mode, gossip_topic};
+use upload_limit::ThrottledProviderEvents;
+use util::{fmt_relay_mode, gossip_subtopic, gossip_topic, idle_transport_config, interface_ipv4};
 
 pub use ed25519::Signature;
 pub use iroh::{endpoint::ConnectionType, NodeAddr, NodeId, RelayMode};
@@ -51,10 +57,21 @@ pub use iroh_blobs::{ticket::BlobTicket, Hash};
 
 pub mod allowlist;
 mod authenticable_identity;
+mod blob_store;
+mod blob_tags;
+mod broadcast_error;
+mod compression;
+mod diagnostics;
 mod download_manager;
+mod fragment;
+pub mod frame_codec;
+mod gossip;
+mod health_probe;
 mod local_discovery;
+mod nat_probe;
 mod p2p_model_sharing;
 mod peer_list;
+mod peer_quality;
 mod router;
 mod serde;
 mod serializable_kind;
@@ -63,34 +80,55 @@ mod serialized_distro;
 mod signed_message;
 mod state;
 mod tcp;
+mod time_sync;
 mod tui;
+mod upload_limit;
 mod util;
 
 pub use authenticable_identity::{raw_p2p_verify, AuthenticatableIdentity, FromSignedBytesError};
-pub use download_manager::{DownloadComplete, DownloadFailed, TransmittableDownload};
+pub use blob_store::BlobStoreConfig;
+pub use blob_tags::GcPolicy;
+pub use broadcast_error::BroadcastError;
+pub use compression::{Codec, MAX_COMPRESSION_LEVEL};
+pub use diagnostics::{DownloadFailureDiagnostics, EndpointDiagnostics, PeerDiagnostics};
+pub use download_manager::{
+    DownloadComplete, DownloadFailed, DownloadFailureKind, DownloadPriority, DownloadProgress,
+    TransmittableDownload,
+};
+pub use fragment::{FragmentError, FRAGMENT_OVERHEAD, FRAGMENT_TAG};
+pub use gossip::{GossipConfig, GossipHopStats};
+pub use health_probe::{probe_peer, HealthProbe, HealthProbeError};
 use iroh::defaults::DEFAULT_STUN_PORT;
 pub use iroh::{Endpoint, PublicKey, SecretKey};
 use iroh_relay::{RelayMap, RelayNode, RelayQuicConfig};
+pub use nat_probe::{classify_nat, probe_nat, stun_servers, NatProbe, NatType};
 pub use p2p_model_sharing::{
-    ModelRequestType, ModelSharing, SharableModel, SharableModelError, TransmittableModelConfig,
-    ALPN,
+    ConnectRetry, ModelRequestType, ModelSharing, ParameterServeLimit, SharableModel,
+    SharableModelError, TransmittableModelConfig, TransmittableModelParameter, ALPN,
 };
 pub use peer_list::PeerList;
+pub use peer_quality::{PeerQuality, STALE_PEER_AFTER};
 pub use serde::Networkable;
 pub use serialized_distro::{
     distro_results_from_reader, distro_results_to_bytes, SerializeDistroResultError,
-    SerializedDistroResult, TransmittableDistroResult,
+    SerializedDistroResult, SparseValueDtype, SparseValueEncoding, TransmittableDistroResult,
 };
-pub use signed_message::SignedMessage;
+pub use signed_message::{SignedMessage, SignedMessageError, SIGNED_MESSAGE_VERSION};
+pub use state::{DownloadOutcomes, PeerStatus};
 pub use tcp::{ClientNotification, TcpClient, TcpServer};
+pub use time_sync::TimeSyncExchange;
 pub use tui::{NetworkTUIState, NetworkTui};
+pub use upload_limit::{UploadRateLimiter, MIN_UPLOAD_RATE_LIMIT};
 use url::Url;
-pub use util::fmt_bytes;
+pub use util::{fmt_bytes, InterfaceLookupError};
 
 const USE_RELAY_HOSTNAME: &str = "use1-1.relay.psyche.iroh.link";
 const USW_RELAY_HOSTNAME: &str = "usw1-1.relay.psyche.iroh.link";
 const EUC_RELAY_HOSTNAME: &str = "euc1-1.relay.psyche.iroh.link";
 
+/// Enough room for the blob tickets of every chunk of a very large parameter.
+const MODEL_RESPONSE_MAX_SIZE: usize = 256 * 1024;
+
 /// How should this node discover other nodes?
 ///
 /// In almost all cases, you want "N0", for over-the-internet communication.
@@ -100,19 +138,43 @@ pub enum DiscoveryMode {
     Local,
     N0,
 }
+
+/// One of the run's gossip channels besides its main topic.
+#[derive(Debug)]
+struct GossipChannel {
+    name: String,
+    tx: GossipSender,
+    rx: GossipReceiver,
+}
+
 pub struct NetworkConnection<BroadcastMessage, Download>
 where
     BroadcastMessage: Networkable,
     Download: Networkable,
 {
     router: Arc<Router>,
-    blobs: Blobs<Store>,
+    blobs: BlobStore,
     state: State,
     gossip_tx: GossipSender,
     gossip_rx: GossipReceiver,
+    gossip_channels: Vec<GossipChannel>,
     rx_model_parameter_req: UnboundedReceiver<ParameterSharingMessage>,
     rx_model_config_req: UnboundedReceiver<ModelConfigSharingMessage>,
     download_manager: DownloadManager<Download>,
+    /// How many downloads run at once. The rest wait in the download manager's queue, by priority.
+    max_concurrent_downloads: usize,
+    compression_level: u32,
+    blob_codec: Codec,
+    gossip_compress_above: Option<usize>,
+    gossip_max_message_size: usize,
+    gossip_max_reassembled_size: usize,
+    fragments: FragmentBuffer,
+    nat_probe: Arc<StdMutex<Option<NatProbe>>>,
+    upload_limiter: UploadRateLimiter,
+    /// How many gossip neighbors [`Self::add_peers`] keeps us at. Set from [`GossipConfig::fanout`].
+    gossip_target_degree: usize,
+    /// Peers passed to [`Self::add_peers`] that we haven't joined yet.
+    pending_gossip_peers: VecDeque<NodeId>,
     _broadcast_message: PhantomData<BroadcastMessage>,
     _download: PhantomData<Download>,
     update_stats_interval: Interval,
@@ -129,6 +191,7 @@ where
             .field("blobs", &self.blobs)
             .field("gossip_tx", &self.gossip_tx)
             .field("gossip_rx", &self.gossip_rx)
+            .field("gossip_channels", &self.gossip_channels)
             .field("state", &self.state)
             .field("download_manager", &self.download_manager)
             .field("update_stats_interval", &self.update_stats_interval)
@@ -151,8 +214,29 @@ where
         bootstrap_peers: Vec<NodeAddr>,
         secret_key: Option<SecretKey>,
         allowlist: A,
+        blob_store: BlobStoreConfig,
         max_concurrent_downloads: usize,
+        max_concurrent_downloads_per_peer: usize,
+        parameter_serve_limit: ParameterServeLimit,
+        upload_rate_limit: Option<u64>,
+        idle_timeout: Option<Duration>,
+        compression_level: u32,
+        gossip_config: GossipConfig,
+        gossip_channels: Vec<String>,
+        nat_probe_timeout: Option<Duration>,
     ) -> Result<Self> {
+        if compression_level > MAX_COMPRESSION_LEVEL {
+            return Err(anyhow!(
+                "compression level {compression_level} is out of range, the maximum is {MAX_COMPRESSION_LEVEL}"
+            ));
+        }
+        if max_concurrent_downloads_per_peer == 0 {
+            return Err(anyhow!(
+                "max concurrent downloads per peer must be at least 1"
+            ));
+        }
+        let fragment_data_size = fragment::max_fragment_data_size(gossip_config.max_message_size)?;
+
         let secret_key = match secret_key {
             None => SecretKey::generate(&mut rand::rngs::OsRng),
             Some(key) => key,
@@ -163,28 +247,9 @@ where
         debug!("Using relay servers: {}", fmt_relay_mode(&relay_mode));
 
         let ipv4 = if let Some(if_name) = interface {
-            let (wildcard, if_name) = if if_name.ends_with("*") {
-                (true, if_name[..if_name.len() - 1].to_string())
-            } else {
-                (false, if_name)
-            };
-            let iface_ip = get_if_addrs::get_if_addrs()
-                .unwrap()
-                .iter()
-                .find_map(|interface| {
-                    (if wildcard {
-                        interface.name.starts_with(&if_name)
-                    } else {
-                        interface.name == if_name
-                    } && interface.ip().is_ipv4())
-                    .then_some(interface.ip())
-                });
-            let IpAddr::V4(v4) =
-                iface_ip.ok_or(anyhow!("no interface with name \"{if_name}\" found."))?
-            else {
-                unreachable!("checked in earlier if. should not be possible.")
-            };
-            v4
+            let interfaces =
+                get_if_addrs::get_if_addrs().context("failed to list network interfaces")?;
+            interface_ipv4(&interfaces, &if_name)?
         } else {
             Ipv4Addr::new(0, 0, 0, 0)
         };
@@ -195,6 +260,16 @@ where
                 .relay_mode(RelayMode::Custom(psyche_relay_map()))
                 .bind_addr_v4(SocketAddrV4::new(ipv4, port.unwrap_or(0)));
 
+            // active downloads and gossip traffic keep their connections open,
+            // and gossip will replace any neighbor whose connection idled out.
+            let endpoint = match idle_timeout {
+                Some(idle_timeout) => {
+                    info!("Closing p2p connections after {idle_timeout:?} without traffic");
+                    endpoint.transport_config(idle_transport_config(idle_timeout)?)
+                }
+                None => endpoint,
+            };
+
             let e = match discovery_mode {
                 DiscoveryMode::Local => endpoint.discovery(Box::new(
                     local_discovery::LocalTestDiscovery::new(public_key),
@@ -210,43 +285,61 @@ where
         info!("Our node addr: {}", node_addr.node_id);
         info!("Our join ticket: {}", PeerList(vec![node_addr]));
 
+        trace!("creating model parameter sharing...");
+        let (tx_model_parameter_req, rx_model_parameter_req) = mpsc::unbounded_channel();
+        let (tx_model_config_req, rx_model_config_req) = mpsc::unbounded_channel();
+        let model_parameter_sharing = ModelSharing::new(
+            tx_model_parameter_req,
+            tx_model_config_req,
+            parameter_serve_limit,
+        );
+        trace!("model parameter sharing created!");
+
         trace!("creating blobs...");
-        let blobs = Blobs::memory()
-            .concurrency_limits(ConcurrencyLimits {
-                max_concurrent_requests_per_node: 1,
-                max_concurrent_requests: max_concurrent_downloads,
-                max_open_connections: 512,
-                max_concurrent_dials_per_hash: 2,
-            })
-            .build(&endpoint);
+        // the throttle is always in place, so a limit can be set later. a limit of 0 means unlimited.
+        let upload_limiter = UploadRateLimiter::unlimited();
+        if let Some(bytes_per_sec) = upload_rate_limit.filter(|&limit| limit > 0) {
+            info!("Limiting blob uploads to {bytes_per_sec} bytes/sec");
+            upload_limiter.set_rate(Some(bytes_per_sec));
+        }
+        let mut state = State::new(15);
+        let events = Some(EventSender::new(Some(Arc::new(ProviderEvents {
+            serve_limiter: model_parameter_sharing.serve_limiter().cloned(),
+            throttle: ThrottledProviderEvents::new(upload_limiter.clone()),
+            blobs_served: state.blobs_served.clone(),
+        }))));
+        let blobs = BlobStore::build(
+            &blob_store,
+            blob_concurrency_limits(max_concurrent_downloads, max_concurrent_downloads_per_peer),
+            events,
+            &endpoint,
+        )
+        .await?;
+        if let BlobStoreConfig::Persistent { path } = &blob_store {
+            // they're served again as soon as the router is up, so peers can fetch them from any ticket we gave out before.
+            state.tagged_blobs = list_blob_tags(&blobs)
+                .await?
+                .into_iter()
+                .map(|(_, hash, _)| hash)
+                .collect();
+            info!(
+                "Serving {} blobs kept in {} from before",
+                state.tagged_blobs.len(),
+                path.display()
+            );
+        }
         trace!("blobs created!");
 
         trace!("creating gossip...");
+        debug!("Gossip config: {gossip_config:?}");
         let gossip = Gossip::builder()
-            .max_message_size(4096)
-            .membership_config(HyparviewConfig {
-                active_view_capacity: 8,
-                shuffle_interval: Duration::from_secs(30),
-                neighbor_request_timeout: Duration::from_secs(2),
-                ..HyparviewConfig::default()
-            })
-            .broadcast_config(PlumtreeConfig {
-                graft_timeout_2: Duration::from_millis(200),
-                message_cache_retention: Duration::from_secs(60),
-                message_id_retention: Duration::from_secs(2 * 60),
-                ..PlumtreeConfig::default()
-            })
+            .max_message_size(gossip_config.max_message_size)
+            .membership_config(gossip_config.membership_config())
+            .broadcast_config(gossip_config.broadcast_config())
             .spawn(endpoint.clone())
             .await?;
         trace!("gossip created!");
 
-        trace!("creating model parameter sharing...");
-        let (tx_model_parameter_req, rx_model_parameter_req) = mpsc::unbounded_channel();
-        let (tx_model_config_req, rx_model_config_req) = mpsc::unbounded_channel();
-        let model_parameter_sharing =
-            ModelSharing::new(tx_model_parameter_req, tx_model_config_req);
-        trace!("model parameter sharing created!");
-
         trace!("creating router...");
         let router = Arc::new(
             Router::spawn(
@@ -255,6 +348,8 @@ where
                 blobs.clone(),
                 model_parameter_sharing.clone(),
                 allowlist,
+                state.blobs_served.clone(),
+                state.gossip_connections.clone(),
             )
             .await?,
         );
@@ -279,8 +374,31 @@ where
                 bootstrap_peers.iter().map(|p| p.node_id).collect(),
             )?
             .split();
+        let gossip_channels = gossip_channels
+            .into_iter()
+            .map(|name| {
+                let (tx, rx) = gossip
+                    .subscribe(
+                        gossip_subtopic(run_id, &name),
+                        bootstrap_peers.iter().map(|p| p.node_id).collect(),
+                    )?
+                    .split();
+                anyhow::Ok(GossipChannel { name, tx, rx })
+            })
+            .collect::<Result<_>>()?;
         info!("Connected!");
 
+        // find out in the background whether we can expect direct connections to work.
+        let nat_probe = Arc::new(StdMutex::new(None));
+        if let Some(timeout) = nat_probe_timeout {
+            let nat_probe = nat_probe.clone();
+            tokio::spawn(async move {
+                let probe = nat_probe::probe_nat(&stun_servers(&psyche_relay_map()), timeout).await;
+                nat_probe::log_nat_probe(&probe);
+                *nat_probe.lock().unwrap() = Some(probe);
+            });
+        }
+
         // if this is not 1s, the bandwidth chart will be wrong.
         let update_stats_interval = interval(Duration::from_secs(1));
 
@@ -288,14 +406,30 @@ where
             blobs,
             gossip_rx,
             gossip_tx,
+            gossip_channels,
             rx_model_parameter_req,
             rx_model_config_req,
+            upload_limiter,
+            gossip_target_degree: gossip_config.fanout,
+            pending_gossip_peers: VecDeque::new(),
 
             router,
 
             update_stats_interval,
-            state: State::new(15),
+            state,
             download_manager: DownloadManager::new()?,
+            max_concurrent_downloads,
+            compression_level,
+            blob_codec: Codec::default(),
+            gossip_compress_above: gossip_config.compress_above,
+            gossip_max_message_size: gossip_config.max_message_size,
+            gossip_max_reassembled_size: gossip_config.max_reassembled_size,
+            fragments: FragmentBuffer::new(
+                gossip_config.fragment_timeout,
+                fragment_data_size,
+                gossip_config.max_reassembled_size,
+            ),
+            nat_probe,
             _broadcast_message: Default::default(),
             _download: Default::default(),
         })
@@ -309,37 +443,125 @@ where
         self.router.endpoint().node_id()
     }
 
-    /// Don't call this often / with many peers!
-    /// It can force disconnection of other gossip peers if we have too many.
+    /// Joins `peers` as gossip neighbors while staying at the target degree. To make room, we evict the
+    /// neighbors we've heard from least recently ourselves, rather than have gossip evict them at random,
+    /// and never one we're downloading from. Peers that don't fit yet are joined on a later stats tick.
     pub async fn add_peers(&mut self, peers: Vec<NodeId>) -> Result<()> {
+        let me = self.router.endpoint().node_id();
+        for peer in peers {
+            if peer != me && !self.pending_gossip_peers.contains(&peer) {
+                self.pending_gossip_peers.push_back(peer);
+            }
+        }
+        self.rebalance_gossip_peers().await
+    }
+
+    async fn rebalance_gossip_peers(&mut self) -> Result<()> {
+        let neighbors: HashSet<NodeId> = self.gossip_rx.neighbors().collect();
+        let downloading_from: HashSet<NodeId> = self
+            .state
+            .download_progesses
+            .values()
+            .map(|download| download.latest.blob_ticket.node_addr().node_id)
+            .collect();
+        let last_seen = &self.state.last_seen;
+        let GossipRebalance { join: peers, evict } = plan_gossip_rebalance(
+            &neighbors,
+            |peer| last_seen.get(peer).map(|status| status.last_seen),
+            &self.state.gossip_connections.evictable(),
+            &downloading_from,
+            &mut self.pending_gossip_peers,
+            self.gossip_target_degree,
+        );
+        for peer in evict {
+            debug!(name: "gossip_evict_peer", node_id = %peer, "evicting least recently active gossip neighbor {peer}");
+            self.state.gossip_connections.evict(peer);
+        }
+        if peers.is_empty() {
+            return Ok(());
+        }
         let peer_list = peers
             .iter()
             .map(|n| n.fmt_short())
             .collect::<Vec<_>>()
             .join(",");
-        debug!(name: "gossip_join_peers", peers=peer_list);
-        self.gossip_tx
-            .join_peers(
-                peers
-                    .into_iter()
-                    .filter(|p| p != &self.router.endpoint().node_id())
-                    .collect(),
-            )
-            .await?;
+        debug!(
+            name: "gossip_join_peers",
+            peers = peer_list,
+            pending = self.pending_gossip_peers.len()
+        );
+        for channel in &self.gossip_channels {
+            channel.tx.join_peers(peers.clone()).await?;
+        }
+        self.gossip_tx.join_peers(peers).await?;
         Ok(())
     }
 
-    pub async fn broadcast(&mut self, message: &BroadcastMessage) -> Result<()> {
-        let encoded_message =
-            SignedMessage::sign_and_encode(self.router.endpoint().secret_key(), message)?;
+    /// Signs `message` and sends it to the run's gossip topic, in fragments if it's too big for one gossip message.
+    /// Fails without sending anything if it's too big to send at all, or if we have no gossip peers yet.
+    pub async fn broadcast(&mut self, message: &BroadcastMessage) -> Result<(), BroadcastError> {
+        let fragments = self.encode_broadcast(message)?;
+        if self.gossip_rx.neighbors().next().is_none() {
+            return Err(BroadcastError::NoPeers);
+        }
+        for fragment in fragments {
+            self.gossip_tx
+                .broadcast(fragment)
+                .await
+                .map_err(BroadcastError::Gossip)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`Self::broadcast`], but on one of the gossip channels passed to [`Self::init`].
+    /// Only subscribers of that channel get the message.
+    pub async fn broadcast_on(
+        &mut self,
+        channel: &str,
+        message: &BroadcastMessage,
+    ) -> Result<(), BroadcastError> {
+        let fragments = self.encode_broadcast(message)?;
+        let channel = self
+            .gossip_channels
+            .iter()
+            .find(|c| c.name == channel)
+            .ok_or_else(|| BroadcastError::UnknownChannel(channel.to_string()))?;
+        if channel.rx.neighbors().next().is_none() {
+            return Err(BroadcastError::NoPeers);
+        }
+        for fragment in fragments {
+            channel
+                .tx
+                .broadcast(fragment)
+                .await
+                .map_err(BroadcastError::Gossip)?;
+        }
+        Ok(())
+    }
+
+    fn encode_broadcast(&self, message: &BroadcastMessage) -> Result<Vec<Bytes>, BroadcastError> {
+        let encoded_message = SignedMessage::sign_and_encode_compressed(
+            self.router.endpoint().secret_key(),
+            message,
+            self.gossip_compress_above,
+            self.compression_level,
+        )
+        .map_err(BroadcastError::Sign)?;
         let message_hash = hash_bytes(&encoded_message);
+        let fragments = fragment::fragment(
+            self.router.endpoint().secret_key(),
+            encoded_message,
+            self.gossip_max_message_size,
+            self.gossip_max_reassembled_size,
+        )?;
         debug!(
             name: "gossip_broadcast",
             message_hash = message_hash,
+            fragments = fragments.len(),
             "broadcasted gossip message with hash {message_hash}: {:?}",
             message
         );
-        Ok(self.gossip_tx.broadcast(encoded_message).await?)
+        Ok(fragments)
     }
 
     pub async fn start_download(
@@ -348,6 +570,59 @@ where
         tag: u32,
         additional_peers_to_try: &[NodeAddr],
     ) -> Result<()> {
+        self.start_download_with_priority(
+            ticket,
+            tag,
+            additional_peers_to_try,
+            DownloadPriority::Normal,
+        )
+        .await
+    }
+
+    /// Like [`Self::start_download`], but ahead of any queued downloads of lower priority.
+    /// At most `max_concurrent_downloads` run at once, the rest wait for a slot, highest priority first.
+    pub async fn start_download_with_priority(
+        &mut self,
+        ticket: BlobTicket,
+        tag: u32,
+        additional_peers_to_try: &[NodeAddr],
+        priority: DownloadPriority,
+    ) -> Result<()> {
+        // tracked from now on, so retiring its tag while it's queued still deletes it once it's read.
+        self.state
+            .downloading_blobs
+            .insert((tag, ticket.hash()), false);
+        self.download_manager.enqueue(QueuedDownload {
+            ticket,
+            tag,
+            additional_peers_to_try: additional_peers_to_try.to_vec(),
+            priority,
+        });
+        self.start_queued_downloads().await
+    }
+
+    async fn start_queued_downloads(&mut self) -> Result<()> {
+        while let Some(queued) = self
+            .download_manager
+            .next_to_start(self.max_concurrent_downloads)
+        {
+            let (tag, hash) = (queued.tag, queued.ticket.hash());
+            if let Err(err) = self.begin_download(queued).await {
+                self.download_manager.finished(hash);
+                self.state.downloading_blobs.remove(&(tag, hash));
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    async fn begin_download(&mut self, queued: QueuedDownload) -> Result<()> {
+        let QueuedDownload {
+            ticket,
+            tag,
+            additional_peers_to_try,
+            priority,
+        } = queued;
         let provider_node_id = ticket.node_addr().clone();
         let mut progress = self
             .blobs
@@ -359,16 +634,14 @@ where
                     nodes: std::iter::once(provider_node_id)
                         .chain(additional_peers_to_try.iter().cloned())
                         .collect(),
-                    tag: SetTagOption::Auto,
+                    tag: SetTagOption::Named(blob_tag(tag, ticket.hash())),
                     mode: DownloadMode::Queued,
                 },
             )
             .await?;
 
         let hash = ticket.hash();
-        self.state.currently_sharing_blobs.insert(hash);
-        self.state.blob_tags.insert((tag, hash));
-        debug!(name: "blob_download_start", hash = hash.fmt_short(), "started downloading blob {}", hash.fmt_short());
+        debug!(name: "blob_download_start", hash = hash.fmt_short(), ?priority, "started downloading blob {}", hash.fmt_short());
 
         let (tx, rx) = mpsc::unbounded_channel();
 
@@ -390,11 +663,81 @@ where
         Ok(())
     }
 
+    /// Asks `node` for the model and tokenizer config, and downloads and decodes it.
+    /// Unlike [`Self::start_download`], the download doesn't show up in [`Self::poll_next`].
+    pub async fn fetch_model_config(&self, node: NodeId) -> Result<TransmittableModelConfig> {
+        let tickets = request_model(
+            self.router.endpoint(),
+            node,
+            &ModelRequestType::Config,
+            ConnectRetry::default(),
+        )
+        .await?;
+        let [ticket] = tickets.as_slice() else {
+            bail!(
+                "Expected a single model config blob from {node}, got {}",
+                tickets.len()
+            );
+        };
+        match fetch_blob(self.blobs.client(), ticket.clone()).await? {
+            TransmittableDownload::ModelConfig(config) => Ok(config),
+            other => bail!("Expected a model config from {node}, got {}", other.kind()),
+        }
+    }
+
+    /// Asks `node` for the parameter `name`, returning a stream that downloads and decodes its chunks in order.
+    /// If `node` doesn't know the parameter, the error is a [`SharableModelError::ParameterUnknown`].
+    pub async fn fetch_parameter(
+        &self,
+        node: NodeId,
+        name: &str,
+    ) -> Result<impl Stream<Item = Result<TransmittableModelParameter>>> {
+        let tickets = request_model(
+            self.router.endpoint(),
+            node,
+            &ModelRequestType::Parameter(name.to_string()),
+            ConnectRetry::default(),
+        )
+        .await?;
+        let blobs = self.blobs.client();
+        let name = name.to_string();
+        Ok(futures_util::stream::iter(tickets).then(move |ticket| {
+            let blobs = blobs.clone();
+            let name = name.clone();
+            async move {
+                match fetch_blob(blobs, ticket).await? {
+                    TransmittableDownload::ModelParameter(parameter) => {
+                        let received = parameter.name()?;
+                        if received != name {
+                            bail!("Asked {node} for parameter {name}, got {received}");
+                        }
+                        Ok(parameter)
+                    }
+                    other => bail!(
+                        "Expected parameter {name} from {node}, got {}",
+                        other.kind()
+                    ),
+                }
+            }
+        }))
+    }
+
     pub async fn add_downloadable(&mut self, data: Download, tag: u32) -> Result<BlobTicket> {
+        // compressing a large parameter takes long enough to stall the runtime
+        let compression_level = self.compression_level;
+        let codec = self.blob_codec;
+        let (uncompressed_size, blob, hash) = tokio::task::spawn_blocking(move || {
+            let serialized = postcard::to_allocvec(&data)?;
+            let blob = compression::encode_blob(&serialized, codec, compression_level)?;
+            let hash = Hash::new(&blob);
+            anyhow::Ok((serialized.len(), blob, hash))
+        })
+        .await??;
+        let compression_ratio = uncompressed_size as f64 / blob.len() as f64;
         let blob_res = self
             .blobs
             .client()
-            .add_bytes(postcard::to_allocvec(&data)?)
+            .add_bytes_named(blob, blob_tag(tag, hash))
             .await?;
         let addr = self.router.endpoint().node_addr().await?;
         let blob_ticket = BlobTicket::new(addr, blob_res.hash, blob_res.format)?;
@@ -403,48 +746,112 @@ where
             name: "blob_upload",
             hash = blob_res.hash.fmt_short(),
             size = blob_res.size,
-            "blob added for upload with hash {} and size {}",
+            uncompressed_size,
+            compression_ratio,
+            "blob added for upload with hash {} and size {} ({:.2}x compression with {:?} at level {})",
             blob_res.hash.fmt_short(),
-            blob_res.size
+            blob_res.size,
+            compression_ratio,
+            codec,
+            self.compression_level
         );
 
-        let hash = blob_ticket.hash();
-        self.state.currently_sharing_blobs.insert(hash);
-        self.state.blob_tags.insert((tag, hash));
-
         Ok(blob_ticket)
     }
 
-    // TODO: there must be some clever way to do this using Iroh-blobs' built-in tagging system & GC.
+    /// Picks the codec [`Self::add_downloadable`] compresses blobs with from now on, at the configured compression level.
+    /// Downloaders decode any codec, so peers don't have to agree on one.
+    pub fn set_blob_codec(&mut self, codec: Codec) {
+        self.blob_codec = codec;
+    }
+
+    /// Caps the combined rate we serve blobs at, or lifts the cap with `None` or 0.
+    /// Applies to transfers already in progress too.
+    pub fn set_upload_rate_limit(&self, bytes_per_sec: Option<u64>) {
+        match bytes_per_sec.filter(|&limit| limit > 0) {
+            Some(limit) => info!("Limiting blob uploads to {limit} bytes/sec"),
+            None => info!("Not limiting blob uploads"),
+        }
+        self.upload_limiter.set_rate(bytes_per_sec);
+    }
+
+    /// Starts deleting blobs that none of our tags keep alive anymore.
+    /// Until it's started, retired blobs stay in the store. It can only be started once.
+    pub fn configure_gc(&self, policy: GcPolicy) -> Result<()> {
+        match policy {
+            GcPolicy::Disabled => Ok(()),
+            GcPolicy::Every(period) => {
+                debug!("Collecting untagged blobs every {period:?}");
+                self.blobs.start_gc(GcConfig {
+                    period,
+                    done_callback: None,
+                })
+            }
+        }
+    }
+
     pub fn remove_blobs_with_tag_less_than(&mut self, tag: u32) {
-        self.state.blob_tags.retain(|(t, _)| *t >= tag);
-        self.cleanup_untagged_blogs();
+        self.retire_blob_tags(move |t| t < tag);
     }
-    pub fn cleanup_untagged_blogs(&mut self) {
-        let expired_blobs: Vec<_> = self
-            .state
-            .currently_sharing_blobs
-            .iter()
-            .filter(|a| !self.state.blob_tags.iter().any(|(_, b)| *a == b))
-            .copied()
-            .collect();
-        for hash in expired_blobs.iter() {
-            self.state.currently_sharing_blobs.remove(hash);
+
+    pub fn remove_blobs_with_tag_equal_to(&mut self, tag: u32) {
+        self.retire_blob_tags(move |t| t == tag);
+    }
+
+    /// Drops the iroh tags of our retired tags, so the next GC pass deletes their blobs.
+    /// Downloads that are still in flight keep their tag until we've read them.
+    fn retire_blob_tags(&mut self, retired: impl Fn(u32) -> bool + Send + 'static) {
+        let mut in_flight = HashSet::new();
+        for ((tag, hash), retire_when_done) in self.state.downloading_blobs.iter_mut() {
+            if retired(*tag) {
+                *retire_when_done = true;
+                in_flight.insert((*tag, *hash));
+            }
         }
-        let client = self.blobs.client().clone();
-        tokio::task::spawn(async move {
-            for hash in expired_blobs {
-                if let Err(err) = client.delete_blob(hash).await {
-                    warn!("error deleting blob {hash}: {err}")
+        let blobs = self.blobs.clone();
+        tokio::spawn(async move {
+            let tags = match list_blob_tags(&blobs).await {
+                Ok(tags) => tags,
+                Err(err) => {
+                    warn!("error listing blob tags: {err:#}");
+                    return;
+                }
+            };
+            for (tag, hash, name) in tags {
+                if retired(tag) && !in_flight.contains(&(tag, hash)) {
+                    if let Err(err) = blobs.client().tags().delete(name).await {
+                        warn!("error deleting tag of blob {hash}: {err:#}")
+                    }
                 }
             }
         });
     }
 
-    // TODO: there must be some clever way to do this using Iroh-blobs' built-in tagging system & GC.
-    pub fn remove_blobs_with_tag_equal_to(&mut self, tag: u32) {
-        self.state.blob_tags.retain(|(t, _)| *t != tag);
-        self.cleanup_untagged_blogs();
+    /// Forgets downloads we're done with, dropping the tags of those that were retired while in flight.
+    fn finish_blob_downloads(&mut self, finished: impl Fn(u32, Hash) -> bool) {
+        let mut retired = vec![];
+        self.state
+            .downloading_blobs
+            .retain(|(tag, hash), retire_when_done| {
+                if !finished(*tag, *hash) {
+                    return true;
+                }
+                if *retire_when_done {
+                    retired.push(blob_tag(*tag, *hash));
+                }
+                false
+            });
+        if retired.is_empty() {
+            return;
+        }
+        let client = self.blobs.client();
+        tokio::spawn(async move {
+            for name in retired {
+                if let Err(err) = client.tags().delete(name.clone()).await {
+                    warn!("error deleting blob tag {name}: {err:#}")
+                }
+            }
+        });
     }
 
     pub async fn node_addr(&self) -> Result<NodeAddr> {
@@ -472,25 +879,79 @@ where
             .collect()
     }
 
+    /// How good our connection to `node` is, or `None` if we've never heard of it.
+    pub fn peer_quality(&self, node: NodeId) -> Option<PeerQuality> {
+        let info = self.router.endpoint().remote_info(node)?;
+        let bandwidth = self
+            .state
+            .bandwidth_tracker
+            .get_bandwidth_by_node(&node)
+            .unwrap_or_default();
+        Some(PeerQuality::from_remote_info(&info, bandwidth))
+    }
+
+    /// Everything we know about our endpoint and its peers, for diagnosing connectivity issues.
+    pub async fn diagnostics(&self) -> Result<EndpointDiagnostics> {
+        Ok(EndpointDiagnostics::new(
+            self.node_addr().await?,
+            self.remote_infos().into_iter(),
+            &self.state,
+            self.nat_probe(),
+        ))
+    }
+
+    /// The peers we've heard from in the last two minutes, sorted by id, as of the last stats update.
+    pub fn peer_statuses(&self) -> Vec<(PublicKey, PeerStatus)> {
+        let mut peers: Vec<_> = self
+            .state
+            .last_seen
+            .iter()
+            .map(|(peer_id, status)| (*peer_id, status.clone()))
+            .collect();
+        peers.sort_by_key(|(peer_id, _)| *peer_id);
+        peers
+    }
+
+    /// What kind of NAT we're behind, once the startup probe finished. `None` if it's still running or was disabled.
+    pub fn nat_probe(&self) -> Option<NatProbe> {
+        self.nat_probe.lock().unwrap().clone()
+    }
+
     pub async fn poll_next(&mut self) -> Result<Option<NetworkEvent<BroadcastMessage, Download>>> {
         // these are factored out to separate fns so rustfmt works on their contents :)
         select! {
             Some(event) = self.gossip_rx.next() => {
-                match parse_gossip_event(event.map_err(|ee| ee.into()), &self.gossip_rx) {
+                match parse_gossip_event(event.map_err(|ee| ee.into()), &self.gossip_rx, &mut self.state.gossip_hops, &mut self.fragments) {
                     Some(result) => Ok(Some(NetworkEvent::MessageReceived(result))),
                     None => Ok(None),
                 }
             }
+            Some((index, event)) = next_channel_event(&mut self.gossip_channels) => {
+                let channel = &self.gossip_channels[index];
+                match parse_gossip_event(event, &channel.rx, &mut self.state.gossip_hops, &mut self.fragments) {
+                    Some(result) => Ok(Some(NetworkEvent::ChannelMessage(channel.name.clone(), result))),
+                    None => Ok(None),
+                }
+            }
             update = self.download_manager.poll_next() => {
                 match update {
                     Some(DownloadManagerEvent::Complete(result)) => {
+                        self.download_manager.finished(result.hash);
+                        self.start_queued_downloads().await?;
+                        self.finish_blob_downloads(|_, hash| hash == result.hash);
+                        self.state.download_outcomes.entry(result.from).or_default().succeeded += 1;
                         Ok(Some(NetworkEvent::DownloadComplete(result)))
                     }
                     Some(DownloadManagerEvent::Update(update)) => {
                         Ok(self.on_download_update(update))
                     },
-                    Some(DownloadManagerEvent::Failed(result)) => {
+                    Some(DownloadManagerEvent::Failed(mut result)) => {
+                        result.priority = self.download_manager.finished(result.blob_ticket.hash()).unwrap_or_default();
+                        self.start_queued_downloads().await?;
                         self.state.download_progesses.remove(&result.blob_ticket.hash());
+                        self.finish_blob_downloads(|tag, hash| tag == result.tag && hash == result.blob_ticket.hash());
+                        self.state.recent_download_failures.record(&result);
+                        self.state.download_outcomes.entry(result.blob_ticket.node_addr().node_id).or_default().failed += 1;
                         Ok(Some(NetworkEvent::DownloadFailed(result)))
                     }
                     None => Ok(None),
@@ -504,6 +965,16 @@ where
             }
             _ = self.update_stats_interval.tick() => {
                 on_update_stats(self.router.endpoint(), &mut self.state).await?;
+                if !self.pending_gossip_peers.is_empty()
+                    || self.gossip_rx.neighbors().count() > self.gossip_target_degree
+                {
+                    self.rebalance_gossip_peers().await?;
+                }
+                self.state.tagged_blobs = list_blob_tags(&self.blobs).await?.into_iter().map(|(_, hash, _)| hash).collect();
+                let evicted = self.fragments.evict_expired(Instant::now());
+                if evicted > 0 {
+                    warn!("Dropped {evicted} gossip messages that were still missing fragments");
+                }
                 Ok(None)
             }
             else => { Ok(None) }
@@ -524,7 +995,7 @@ where
         if update.all_done {
             self.state.download_progesses.remove(&hash);
 
-            let blobs = self.blobs.client().clone();
+            let blobs = self.blobs.client();
             let (send, recv) = oneshot::channel();
             trace!(name: "blob_download_read_start", hash = hash.fmt_short());
             tokio::spawn(async move {
@@ -546,11 +1017,29 @@ where
             self.download_manager
                 .read(update.blob_ticket, update.tag, recv);
         } else {
-            self.state.download_progesses.insert(hash, update);
+            let now = Instant::now();
+            match self.state.download_progesses.get_mut(&hash) {
+                Some(download) => download.record(update, now),
+                None => {
+                    self.state
+                        .download_progesses
+                        .insert(hash, InFlightDownload::new(update, now));
+                }
+            }
         }
         None
     }
 
+    /// How each in-flight download is coming along, with its recent throughput and when it should finish.
+    pub fn download_progress(&self) -> Vec<DownloadProgress> {
+        let now = Instant::now();
+        self.state
+            .download_progesses
+            .values()
+            .map(|download| download.progress(now))
+            .collect()
+    }
+
     pub async fn get_all_peers(&self) -> Vec<(NodeAddr, ConnectionType)> {
         std::iter::once((
             self.router
@@ -577,43 +1066,171 @@ where
 }
 
 pub async fn request_model(
-    router: Arc<Router>,
+    endpoint: &Endpoint,
     node_addr: NodeId,
     request_type: &ModelRequestType,
-) -> Result<BlobTicket> {
-    let conn = router
-        .endpoint()
-        .connect(node_addr, p2p_model_sharing::ALPN)
-        .await?;
+    connect_retry: ConnectRetry,
+) -> Result<Vec<BlobTicket>> {
+    let mut backoff = connect_retry.initial_backoff;
+    let mut attempt = 1;
+    let parameter_blob_tickets = loop {
+        match try_request_model(endpoint, node_addr, request_type).await {
+            // the peer answered, so asking it again won't change its mind
+            Ok(response) => break response,
+            Err(err) if attempt < connect_retry.max_attempts => {
+                // jitter, so clients that failed together don't all retry together
+                let wait = backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+                debug!(
+                    peer = %node_addr,
+                    "Model request failed (attempt {attempt}/{}), retrying in {wait:?}: {err:#}",
+                    connect_retry.max_attempts
+                );
+                tokio::time::sleep(wait).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    };
+    parameter_blob_tickets.with_context(|| "Error parsing model parameter blob tickets".to_string())
+}
 
+/// Downloads the blob behind `ticket` and decodes it, tagged like other model downloads
+/// so it's cleaned up at the next train step.
+async fn fetch_blob(blobs: MemClient, ticket: BlobTicket) -> Result<TransmittableDownload> {
+    let hash = ticket.hash();
+    blobs
+        .download_with_opts(
+            hash,
+            DownloadOptions {
+                format: BlobFormat::Raw,
+                nodes: vec![ticket.node_addr().clone()],
+                tag: SetTagOption::Named(blob_tag(0, hash)),
+                mode: DownloadMode::Direct,
+            },
+        )
+        .await?
+        .finish()
+        .await
+        .with_context(|| format!("Failed to download blob {}", hash.fmt_short()))?;
+    let bytes = blobs.read_to_bytes(hash).await?;
+    decode_download(&bytes)
+}
+
+/// A single model request. Errors are transport failures worth retrying,
+/// while an error the peer sent back is returned as the inner `Result`.
+async fn try_request_model(
+    endpoint: &Endpoint,
+    node_addr: NodeId,
+    request_type: &ModelRequestType,
+) -> Result<Result<Vec<BlobTicket>, SharableModelError>> {
+    // the connection has to outlive the streams
+    let conn = endpoint.connect(node_addr, p2p_model_sharing::ALPN).await?;
     // Open a bidirectional QUIC stream
     let (mut send, mut recv) = conn.open_bi().await?;
 
     send.write_all(&request_type.to_bytes()).await?;
     send.finish()?;
 
-    // Receive parameter value blob ticket
-    let parameter_blob_ticket_bytes = recv
-        .read_to_end(16384)
+    // Receive parameter value blob tickets, one per chunk
+    let parameter_blob_tickets_bytes = recv
+        .read_to_end(MODEL_RESPONSE_MAX_SIZE)
         .timeout(Duration::from_secs(MODEL_REQUEST_TIMEOUT_SECS))
         .await??;
-    let parameter_blob_ticket: Result<BlobTicket, SharableModelError> =
-        postcard::from_bytes(&parameter_blob_ticket_bytes)?;
-    parameter_blob_ticket.with_context(|| "Error parsing model parameter blob ticket".to_string())
+    Ok(postcard::from_bytes(&parameter_blob_tickets_bytes)?)
+}
+
+/// The gossip neighbors to evict and the pending peers to join, see [`plan_gossip_rebalance`].
+#[derive(Debug, PartialEq)]
+struct GossipRebalance {
+    join: Vec<NodeId>,
+    evict: Vec<NodeId>,
+}
+
+/// Plans joining the `pending` peers while staying at `target_degree` gossip neighbors, the ones we're
+/// downloading from first, then in the order they were queued. Room is made by evicting the `evictable`
+/// neighbors we were `last_active` with longest ago, never the ones we're downloading from.
+/// Peers that don't fit stay queued, and ones that already are neighbors are dropped from the queue.
+fn plan_gossip_rebalance(
+    neighbors: &HashSet<NodeId>,
+    last_active: impl Fn(&NodeId) -> Option<Instant>,
+    evictable: &HashSet<NodeId>,
+    downloading_from: &HashSet<NodeId>,
+    pending: &mut VecDeque<NodeId>,
+    target_degree: usize,
+) -> GossipRebalance {
+    pending.retain(|peer| !neighbors.contains(peer));
+    // a stable sort, so the queue order holds within each group
+    pending
+        .make_contiguous()
+        .sort_by_key(|peer| !downloading_from.contains(peer));
+
+    let mut evict: Vec<NodeId> = neighbors
+        .iter()
+        .filter(|peer| evictable.contains(peer) && !downloading_from.contains(peer))
+        .copied()
+        .collect();
+    // peers we've never heard from sort first
+    evict.sort_by_key(&last_active);
+    evict.truncate((neighbors.len() + pending.len()).saturating_sub(target_degree));
+
+    let room = (target_degree + evict.len())
+        .saturating_sub(neighbors.len())
+        .min(pending.len());
+    GossipRebalance {
+        join: pending.drain(..room).collect(),
+        evict,
+    }
+}
+
+/// The next event from any of the gossip channels, with the index of the channel it came from.
+/// Never resolves without channels.
+async fn next_channel_event(
+    channels: &mut [GossipChannel],
+) -> Option<(usize, Result<iroh_gossip::net::Event>)> {
+    if channels.is_empty() {
+        return std::future::pending().await;
+    }
+    let (event, index, _) = select_all(
+        channels
+            .iter_mut()
+            .map(|channel| Box::pin(channel.rx.next())),
+    )
+    .await;
+    Some((index, event?.map_err(|err| err.into())))
 }
 
 fn parse_gossip_event<BroadcastMessage: Networkable>(
     event: Result<iroh_gossip::net::Event>,
     gossip: &GossipReceiver,
+    gossip_hops: &mut GossipHopStats,
+    fragments: &mut FragmentBuffer,
 ) -> Option<(PublicKey, BroadcastMessage)> {
     match event {
         Ok(iroh_gossip::net::Event::Gossip(GossipEvent::Received(msg))) => {
-            let message_hash = hash_bytes(&msg.content);
-            match SignedMessage::<BroadcastMessage>::verify_and_decode(&msg.content) {
+            let hops = gossip::delivery_hops(&msg.scope);
+            if let Some(hops) = hops {
+                gossip_hops.record(hops);
+            }
+            let (fragments_from, content) = match fragments.receive(msg.content, Instant::now()) {
+                Ok(Received::Whole(content)) => (None, content),
+                Ok(Received::Reassembled { from, bytes }) => (Some(from), bytes),
+                Ok(Received::Pending) => return None,
+                Err(err) => {
+                    warn!("Got a gossip message fragment delivered from {}, but could not verify / decode it! {err}", msg.delivered_from);
+                    return None;
+                }
+            };
+            let message_hash = hash_bytes(&content);
+            match SignedMessage::<BroadcastMessage>::verify_and_decode(&content) {
+                Ok(result) if fragments_from.is_some_and(|from| from != result.0) => {
+                    warn!("Got a gossip message from {} reassembled from fragments signed by someone else, dropping it", result.0);
+                }
                 Ok(result) => {
                     debug!(
                         name: "gossip_rx",
                         message_hash = message_hash,
+                        hops = ?hops,
                         "received gossip message with hash {message_hash}: {:?}",
                         result
                     );
@@ -653,15 +1270,29 @@ where
     D: Networkable,
 {
     MessageReceived((PublicKey, BM)),
+    /// A message broadcast on one of the gossip channels passed to [`NetworkConnection::init`].
+    ChannelMessage(String, (PublicKey, BM)),
     DownloadComplete(DownloadComplete<D>),
     DownloadFailed(DownloadFailed),
     ParameterRequest(
         String,
-        oneshot::Sender<Result<BlobTicket, SharableModelError>>,
+        oneshot::Sender<Result<Vec<BlobTicket>, SharableModelError>>,
     ),
     ModelConfigRequest(oneshot::Sender<Result<BlobTicket, SharableModelError>>),
 }
 
+fn blob_concurrency_limits(
+    max_concurrent_downloads: usize,
+    max_concurrent_downloads_per_peer: usize,
+) -> ConcurrencyLimits {
+    ConcurrencyLimits {
+        max_concurrent_requests_per_node: max_concurrent_downloads_per_peer,
+        max_concurrent_requests: max_concurrent_downloads,
+        max_open_connections: 512,
+        max_concurrent_dials_per_hash: 2,
+    }
+}
+
 async fn on_update_stats(endpoint: &Endpoint, stats: &mut State) -> Result<()> {
     let ticket = {
         let me = endpoint.node_addr().await?;
@@ -670,17 +1301,33 @@ async fn on_update_stats(endpoint: &Endpoint, stats: &mut State) -> Result<()> {
 
     stats.join_ticket = ticket;
 
-    for (peer_id, conn_type, last_recvd) in endpoint
+    for (info, last_recvd) in endpoint
         .remote_info_iter()
-        .filter_map(|i| i.last_received().map(|r| (i.node_id, i.conn_type, r)))
+        .filter_map(|i| i.last_received().map(|r| (i, r)))
     {
         // after 2 minutes with no comms, assume a client is disconnected.
         if last_recvd.as_secs() < 120 {
-            stats
-                .last_seen
-                .insert(peer_id, (conn_type, Instant::now().sub(last_recvd)));
+            stats.last_seen.insert(
+                info.node_id,
+                PeerStatus {
+                    conn_type: info.conn_type,
+                    last_seen: Instant::now().sub(last_recvd),
+                    latency: info.latency,
+                    bandwidth: stats
+                        .bandwidth_tracker
+                        .get_bandwidth_by_node(&info.node_id)
+                        .unwrap_or_default(),
+                    reputation: stats
+                        .download_outcomes
+                        .get(&info.node_id)
+                        .copied()
+                        .unwrap_or_default()
+                        .reputation(),
+                    blobs_served: stats.blobs_served.count(&info.node_id),
+                },
+            );
         } else {
-            stats.last_seen.remove(&peer_id);
+            stats.last_seen.remove(&info.node_id);
         }
     }
 
@@ -759,6 +1406,7 @@ pub async fn param_request_task(
     busy_peers: Arc<StdMutex<HashSet<PublicKey>>>,
     errored_peers: Arc<StdMutex<HashMap<PublicKey, usize>>>,
     num_peers: usize,
+    connect_retry: ConnectRetry,
     cancel_token: CancellationToken,
 ) {
     const MAX_ERRORS_PER_PEER: usize = 3;
@@ -775,12 +1423,19 @@ pub async fn param_request_task(
         }
 
         debug!(parameter = ?&model_request_type, peer = %peer_id, "Requesting parameter");
-        match request_model(router.clone(), peer_id, &model_request_type).await {
-            Ok(parameter_blob_ticket) => {
+        match request_model(
+            router.endpoint(),
+            peer_id,
+            &model_request_type,
+            connect_retry,
+        )
+        .await
+        {
+            Ok(new_parameter_blob_tickets) => {
                 parameter_blob_tickets
                     .lock()
                     .unwrap()
-                    .push(parameter_blob_ticket);
+                    .extend(new_parameter_blob_tickets);
                 busy_peers.lock().unwrap().remove(&peer_id);
                 // Continue to next parameter request
                 break;
@@ -803,3 +1458,832 @@ pub async fn param_request_task(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::TryStreamExt;
+    use rand::{distributions::Alphanumeric, Rng};
+    use tokio::time::{sleep, timeout};
+
+    type TestNetwork = NetworkConnection<String, String>;
+
+    async fn test_network(
+        max_concurrent_downloads_per_peer: usize,
+        upload_rate_limit: Option<u64>,
+    ) -> TestNetwork {
+        test_network_with(
+            max_concurrent_downloads_per_peer,
+            upload_rate_limit,
+            BlobStoreConfig::Memory,
+            vec![],
+        )
+        .await
+    }
+
+    async fn test_network_with(
+        max_concurrent_downloads_per_peer: usize,
+        upload_rate_limit: Option<u64>,
+        blob_store: BlobStoreConfig,
+        gossip_channels: Vec<String>,
+    ) -> TestNetwork {
+        test_network_of(
+            max_concurrent_downloads_per_peer,
+            upload_rate_limit,
+            blob_store,
+            gossip_channels,
+        )
+        .await
+    }
+
+    async fn test_network_of<D: Networkable>(
+        max_concurrent_downloads_per_peer: usize,
+        upload_rate_limit: Option<u64>,
+        blob_store: BlobStoreConfig,
+        gossip_channels: Vec<String>,
+    ) -> NetworkConnection<String, D> {
+        NetworkConnection::init(
+            "concurrency-test",
+            None,
+            None,
+            RelayMode::Disabled,
+            DiscoveryMode::Local,
+            vec![],
+            None,
+            allowlist::AllowAll,
+            blob_store,
+            4,
+            max_concurrent_downloads_per_peer,
+            ParameterServeLimit::Unlimited,
+            upload_rate_limit,
+            None,
+            0,
+            GossipConfig::default(),
+            gossip_channels,
+            None,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn test_blob_concurrency_limits() {
+        let limits = blob_concurrency_limits(8, 3);
+        assert_eq!(limits.max_concurrent_requests_per_node, 3);
+        assert_eq!(limits.max_concurrent_requests, 8);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_downloads_from_one_peer() {
+        let mut provider = test_network(1, None).await;
+        let mut downloader = test_network(2, None).await;
+
+        let mut tickets = vec![];
+        for tag in 0..2 {
+            // big enough for the transfers to take a while, and uncompressed.
+            let blob: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(32 * 1024 * 1024)
+                .map(char::from)
+                .collect();
+            tickets.push(provider.add_downloadable(blob, tag).await.unwrap());
+        }
+        for (tag, ticket) in tickets.iter().enumerate() {
+            downloader
+                .start_download(ticket.clone(), tag as u32, &[])
+                .await
+                .unwrap();
+        }
+
+        let mut both_in_progress = false;
+        let mut completed = 0;
+        timeout(Duration::from_secs(60), async {
+            while completed < tickets.len() {
+                tokio::select! {
+                    event = downloader.poll_next() => {
+                        match event.unwrap() {
+                            Some(NetworkEvent::DownloadComplete(_)) => completed += 1,
+                            Some(NetworkEvent::DownloadFailed(failed)) => panic!("download failed: {}", failed.error),
+                            _ => {}
+                        }
+                    }
+                    _ = provider.poll_next() => {}
+                }
+                both_in_progress |= downloader.state.download_progesses.len() == tickets.len();
+            }
+        })
+        .await
+        .expect("downloads should finish");
+        assert!(
+            both_in_progress,
+            "both blobs should have been downloading from the provider at once"
+        );
+
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    /// Downloads `size` random bytes from `provider`, returning whether it finished within `within`.
+    async fn download_finishes(
+        provider: &mut TestNetwork,
+        downloader: &mut TestNetwork,
+        size: usize,
+        within: Duration,
+    ) -> bool {
+        let blob: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(size)
+            .map(char::from)
+            .collect();
+        let ticket = provider.add_downloadable(blob, 0).await.unwrap();
+        downloader.start_download(ticket, 0, &[]).await.unwrap();
+        timeout(within, async {
+            loop {
+                tokio::select! {
+                    event = downloader.poll_next() => {
+                        match event.unwrap() {
+                            Some(NetworkEvent::DownloadComplete(_)) => break,
+                            Some(NetworkEvent::DownloadFailed(failed)) => panic!("download failed: {}", failed.error),
+                            _ => {}
+                        }
+                    }
+                    _ = provider.poll_next() => {}
+                }
+            }
+        })
+        .await
+        .is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_upload_rate_limit() {
+        const RATE: u64 = 256 * 1024;
+        const BLOB_SIZE: usize = 1024 * 1024;
+        let mut provider = test_network(1, Some(RATE)).await;
+        let mut downloader = test_network(1, None).await;
+
+        let start = Instant::now();
+        assert!(
+            download_finishes(
+                &mut provider,
+                &mut downloader,
+                BLOB_SIZE,
+                Duration::from_secs(60)
+            )
+            .await,
+            "download should finish"
+        );
+        let elapsed = start.elapsed();
+
+        // the first RATE bytes come out of the full bucket, the rest trickle out at RATE.
+        let expected = Duration::from_secs_f64((BLOB_SIZE as u64 - RATE) as f64 / RATE as f64);
+        assert!(
+            elapsed >= expected.mul_f64(0.8) && elapsed <= expected * 3,
+            "downloading {BLOB_SIZE} bytes at {RATE} bytes/sec took {elapsed:?}, expected about {expected:?}"
+        );
+
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_provider_awaits_throttled_progress_events() {
+        // at the minimum rate this blob takes minutes. it only finishes early if the provider
+        // sends chunks without waiting for the throttle's progress events to resolve.
+        let mut provider = test_network(1, Some(MIN_UPLOAD_RATE_LIMIT)).await;
+        let mut downloader = test_network(1, None).await;
+        assert!(
+            !download_finishes(
+                &mut provider,
+                &mut downloader,
+                256 * 1024,
+                Duration::from_secs(5)
+            )
+            .await,
+            "the provider should wait on the throttle between chunks"
+        );
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_zero_upload_rate_limit_is_unlimited() {
+        let mut provider = test_network(1, Some(0)).await;
+        let mut downloader = test_network(1, None).await;
+        assert!(
+            download_finishes(
+                &mut provider,
+                &mut downloader,
+                4 * 1024 * 1024,
+                Duration::from_secs(30)
+            )
+            .await,
+            "a limit of 0 shouldn't throttle uploads"
+        );
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_rate_limit_can_change_at_runtime() {
+        const RATE: u64 = 256 * 1024;
+        const BLOB_SIZE: usize = 1024 * 1024;
+        let mut provider = test_network(1, None).await;
+        let mut downloader = test_network(1, None).await;
+
+        provider.set_upload_rate_limit(Some(RATE));
+        let start = Instant::now();
+        assert!(
+            download_finishes(
+                &mut provider,
+                &mut downloader,
+                BLOB_SIZE,
+                Duration::from_secs(60)
+            )
+            .await,
+            "download should finish"
+        );
+        let elapsed = start.elapsed();
+        let expected = Duration::from_secs_f64((BLOB_SIZE as u64 - RATE) as f64 / RATE as f64);
+        assert!(
+            elapsed >= expected.mul_f64(0.8),
+            "downloading {BLOB_SIZE} bytes at {RATE} bytes/sec took {elapsed:?}, expected about {expected:?}"
+        );
+
+        provider.set_upload_rate_limit(None);
+        assert!(
+            download_finishes(
+                &mut provider,
+                &mut downloader,
+                4 * 1024 * 1024,
+                Duration::from_secs(30)
+            )
+            .await,
+            "lifting the limit should unthrottle uploads"
+        );
+
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_oversized_broadcast_is_too_large() {
+        let mut network = test_network(1, None).await;
+        let gossip_config = GossipConfig::default();
+        let message: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(gossip_config.max_reassembled_size * 2)
+            .map(char::from)
+            .collect();
+        let size = SignedMessage::sign_and_encode_compressed(
+            network.router.endpoint().secret_key(),
+            &message,
+            gossip_config.compress_above,
+            0,
+        )
+        .unwrap()
+        .len();
+
+        // checked before anything else, so it fails the same with no peers
+        match network.broadcast(&message).await {
+            Err(BroadcastError::TooLarge {
+                size: actual_size,
+                max,
+            }) => {
+                assert_eq!(actual_size, size);
+                assert_eq!(max, gossip_config.max_reassembled_size);
+            }
+            other => panic!("expected TooLarge, got {other:?}"),
+        }
+
+        assert!(matches!(
+            network.broadcast(&"small".to_string()).await,
+            Err(BroadcastError::NoPeers)
+        ));
+        network.shutdown().await.unwrap();
+    }
+
+    fn node_ids(count: usize) -> Vec<NodeId> {
+        (0..count)
+            .map(|_| SecretKey::generate(&mut rand::rngs::OsRng).public())
+            .collect()
+    }
+
+    #[test]
+    fn test_gossip_rebalance_converges_to_target_degree() {
+        let target_degree = 20;
+        let start = Instant::now();
+        let seeded = node_ids(20);
+        let new_peers = node_ids(20);
+        // we're mid-download from two of the neighbors and from two of the new peers
+        let downloading_from: HashSet<NodeId> =
+            seeded[..2].iter().chain(&new_peers[..2]).copied().collect();
+        // the seeded neighbors were last active in order, the first ones longest ago
+        let mut last_active: HashMap<NodeId, Instant> = seeded
+            .iter()
+            .enumerate()
+            .map(|(i, peer)| (*peer, start + Duration::from_secs(i as u64)))
+            .collect();
+
+        let mut neighbors: HashSet<NodeId> = seeded.iter().copied().collect();
+        let mut pending: VecDeque<NodeId> = new_peers.iter().copied().collect();
+        // re-adding current neighbors doesn't queue them
+        pending.extend(&seeded[..5]);
+
+        // rebalance on every tick, like the network does, until there's nothing left to do
+        let mut evicted = vec![];
+        let mut now = start + Duration::from_secs(60);
+        loop {
+            let rebalance = plan_gossip_rebalance(
+                &neighbors,
+                |peer| last_active.get(peer).copied(),
+                &neighbors.clone(),
+                &downloading_from,
+                &mut pending,
+                target_degree,
+            );
+            if rebalance.join.is_empty() && rebalance.evict.is_empty() {
+                break;
+            }
+            for peer in &rebalance.evict {
+                assert!(neighbors.remove(peer));
+            }
+            for peer in rebalance.join {
+                neighbors.insert(peer);
+                last_active.insert(peer, now);
+            }
+            evicted.extend(rebalance.evict);
+            assert_eq!(neighbors.len(), target_degree);
+            now += Duration::from_secs(1);
+        }
+
+        assert!(pending.is_empty());
+        assert_eq!(neighbors.len(), target_degree);
+        // everyone we're downloading from survived, and the last of the new peers got in
+        assert!(downloading_from.iter().all(|peer| neighbors.contains(peer)));
+        assert!(new_peers[18..].iter().all(|peer| neighbors.contains(peer)));
+        // the seeded neighbors made way least recently active first
+        assert_eq!(evicted[..18], seeded[2..]);
+
+        // neighbors we can't evict stay, even if that means not joining everyone yet
+        let mut pending: VecDeque<NodeId> = node_ids(3).into_iter().collect();
+        let rebalance = plan_gossip_rebalance(
+            &neighbors,
+            |peer| last_active.get(peer).copied(),
+            &HashSet::new(),
+            &downloading_from,
+            &mut pending,
+            target_degree,
+        );
+        assert_eq!(
+            rebalance,
+            GossipRebalance {
+                join: vec![],
+                evict: vec![]
+            }
+        );
+        assert_eq!(pending.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_evicted_gossip_neighbor_is_dropped_and_kept_out() {
+        let mut hub = test_network(1, None).await;
+        let mut peer = test_network(1, None).await;
+        let (hub_id, peer_id) = (hub.node_id(), peer.node_id());
+        peer.add_peers(vec![hub_id]).await.unwrap();
+
+        // drives both networks until `done` holds
+        async fn poll_until(
+            hub: &mut TestNetwork,
+            peer: &mut TestNetwork,
+            done: impl Fn(&TestNetwork, &TestNetwork) -> bool,
+        ) {
+            while !done(hub, peer) {
+                tokio::select! {
+                    _ = hub.poll_next() => {}
+                    _ = peer.poll_next() => {}
+                }
+            }
+        }
+
+        timeout(
+            Duration::from_secs(30),
+            poll_until(&mut hub, &mut peer, |hub, _| {
+                hub.neighbors().any(|n| n == peer_id)
+            }),
+        )
+        .await
+        .expect("the peer should become the hub's neighbor");
+
+        // the peer dialed the hub, so the hub holds its connection and can evict it
+        assert!(hub.state.gossip_connections.evictable().contains(&peer_id));
+        hub.state.gossip_connections.evict(peer_id);
+        timeout(
+            Duration::from_secs(30),
+            poll_until(&mut hub, &mut peer, |hub, peer| {
+                hub.neighbors().next().is_none() && peer.neighbors().next().is_none()
+            }),
+        )
+        .await
+        .expect("evicting should drop the neighbor on both sides");
+
+        // and it can't get back in right away
+        peer.add_peers(vec![hub_id]).await.unwrap();
+        assert!(timeout(
+            Duration::from_secs(3),
+            poll_until(&mut hub, &mut peer, |hub, _| hub
+                .neighbors()
+                .next()
+                .is_some()),
+        )
+        .await
+        .is_err());
+
+        for network in [hub, peer] {
+            network.shutdown().await.unwrap();
+        }
+    }
+
+    const GC_PERIOD: Duration = Duration::from_millis(50);
+
+    async fn stored_blobs(network: &TestNetwork) -> HashSet<Hash> {
+        network
+            .blobs
+            .client()
+            .list()
+            .await
+            .unwrap()
+            .map_ok(|info| info.hash)
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_gc_deletes_blobs_of_retired_tags() {
+        let mut network = test_network(1, None).await;
+        network.configure_gc(GcPolicy::Every(GC_PERIOD)).unwrap();
+
+        let mut hashes = HashMap::new();
+        for tag in 1..=10 {
+            let ticket = network
+                .add_downloadable(format!("blob {tag}"), tag)
+                .await
+                .unwrap();
+            hashes.insert(tag, ticket.hash());
+        }
+        assert_eq!(stored_blobs(&network).await.len(), 10);
+
+        network.remove_blobs_with_tag_less_than(5);
+        let kept: HashSet<_> = (5..=10).map(|tag| hashes[&tag]).collect();
+        timeout(Duration::from_secs(10), async {
+            while stored_blobs(&network).await != kept {
+                sleep(GC_PERIOD).await;
+            }
+        })
+        .await
+        .expect("GC should delete the blobs of retired tags");
+        // and later passes leave the rest alone
+        sleep(GC_PERIOD * 4).await;
+        assert_eq!(stored_blobs(&network).await, kept);
+
+        network.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retiring_a_tag_mid_download_keeps_its_blob_until_read() {
+        const BLOB_SIZE: usize = 1024 * 1024;
+        // slow enough for the tag to be retired while the download is still running
+        let mut provider = test_network(1, Some(256 * 1024)).await;
+        let mut downloader = test_network(1, None).await;
+        downloader.configure_gc(GcPolicy::Every(GC_PERIOD)).unwrap();
+
+        let blob: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(BLOB_SIZE)
+            .map(char::from)
+            .collect();
+        let ticket = provider.add_downloadable(blob.clone(), 3).await.unwrap();
+        downloader.start_download(ticket, 3, &[]).await.unwrap();
+
+        let mut retired_mid_download = false;
+        let downloaded = timeout(Duration::from_secs(60), async {
+            loop {
+                tokio::select! {
+                    event = downloader.poll_next() => {
+                        match event.unwrap() {
+                            Some(NetworkEvent::DownloadComplete(complete)) => break complete.data,
+                            Some(NetworkEvent::DownloadFailed(failed)) => panic!("download failed: {}", failed.error),
+                            _ => {}
+                        }
+                    }
+                    _ = provider.poll_next() => {}
+                }
+                if !retired_mid_download && !downloader.state.download_progesses.is_empty() {
+                    downloader.remove_blobs_with_tag_less_than(5);
+                    retired_mid_download = true;
+                }
+            }
+        })
+        .await
+        .expect("download should finish");
+        assert!(retired_mid_download);
+        assert_eq!(downloaded, blob);
+
+        // once it's been read, the retired tag is dropped after all
+        timeout(Duration::from_secs(10), async {
+            while !stored_blobs(&downloader).await.is_empty() {
+                sleep(GC_PERIOD).await;
+            }
+        })
+        .await
+        .expect("GC should delete the blob once its download finished");
+
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    type ModelNetwork = NetworkConnection<String, TransmittableDownload>;
+
+    /// Answers model requests like a client that has the model, splitting `parameter` into several chunks.
+    async fn serve_model(mut provider: ModelNetwork, parameter: Vec<u8>) {
+        loop {
+            match provider.poll_next().await {
+                Ok(Some(NetworkEvent::ModelConfigRequest(tx))) => {
+                    let config = TransmittableModelConfig::new(
+                        r#"{"model_type": "llama"}"#.to_string(),
+                        "tokenizer".to_string(),
+                    );
+                    let ticket = provider
+                        .add_downloadable(TransmittableDownload::ModelConfig(config), 0)
+                        .await
+                        .unwrap();
+                    tx.send(Ok(ticket)).unwrap();
+                }
+                Ok(Some(NetworkEvent::ParameterRequest(name, tx))) => {
+                    let response = if name == "known" {
+                        let mut tickets = Vec::new();
+                        for chunk in
+                            TransmittableModelParameter::chunked(name.into_bytes(), &parameter, 4)
+                        {
+                            tickets.push(
+                                provider
+                                    .add_downloadable(
+                                        TransmittableDownload::ModelParameter(chunk),
+                                        0,
+                                    )
+                                    .await
+                                    .unwrap(),
+                            );
+                        }
+                        Ok(tickets)
+                    } else {
+                        Err(SharableModelError::ParameterUnknown(name))
+                    };
+                    tx.send(response).unwrap();
+                }
+                Ok(_) => {}
+                Err(err) => panic!("provider failed: {err:#}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_model_config_and_parameters() {
+        let provider: ModelNetwork =
+            test_network_of(1, None, BlobStoreConfig::Memory, vec![]).await;
+        let requester: ModelNetwork =
+            test_network_of(1, None, BlobStoreConfig::Memory, vec![]).await;
+        let provider_addr = provider.router().endpoint().node_addr().await.unwrap();
+        let provider_id = provider_addr.node_id;
+        requester
+            .router()
+            .endpoint()
+            .add_node_addr(provider_addr)
+            .unwrap();
+        let serving = tokio::spawn(serve_model(provider, (0..10).collect()));
+
+        let config = requester.fetch_model_config(provider_id).await.unwrap();
+        assert_eq!(config.config, r#"{"model_type": "llama"}"#);
+        assert_eq!(config.tokenizer, "tokenizer");
+
+        let chunks: Vec<TransmittableModelParameter> = requester
+            .fetch_parameter(provider_id, "known")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 3);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.name().unwrap(), "known");
+            assert_eq!(chunk.chunk_index(), i as u32);
+            assert_eq!(chunk.num_chunks(), 3);
+        }
+
+        let Err(err) = requester.fetch_parameter(provider_id, "missing").await else {
+            panic!("fetching an unknown parameter should fail");
+        };
+        assert!(matches!(
+            err.downcast_ref::<SharableModelError>(),
+            Some(SharableModelError::ParameterUnknown(name)) if name == "missing"
+        ));
+
+        serving.abort();
+        requester.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_persistent_blob_store_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let blob_store = BlobStoreConfig::Persistent {
+            path: dir.path().to_owned(),
+        };
+
+        let mut network = test_network_with(1, None, blob_store.clone(), vec![]).await;
+        let ticket = network
+            .add_downloadable("kept across restarts".to_string(), 0)
+            .await
+            .unwrap();
+        network.shutdown().await.unwrap();
+        drop(network);
+
+        let network = test_network_with(1, None, blob_store, vec![]).await;
+        assert!(network.state.tagged_blobs.contains(&ticket.hash()));
+        let blob = network
+            .blobs
+            .client()
+            .read_to_bytes(ticket.hash())
+            .await
+            .unwrap();
+        assert_eq!(Hash::new(&blob), ticket.hash());
+
+        network.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_progress_reports_throughput_and_eta() {
+        const RATE: u64 = 256 * 1024;
+        let mut provider = test_network(1, Some(RATE)).await;
+        let mut downloader = test_network(1, None).await;
+
+        let blob: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(2 * 1024 * 1024)
+            .map(char::from)
+            .collect();
+        let ticket = provider.add_downloadable(blob, 0).await.unwrap();
+        downloader.start_download(ticket, 0, &[]).await.unwrap();
+
+        let mut with_eta = None;
+        timeout(Duration::from_secs(60), async {
+            loop {
+                tokio::select! {
+                    event = downloader.poll_next() => {
+                        match event.unwrap() {
+                            Some(NetworkEvent::DownloadComplete(_)) => break,
+                            Some(NetworkEvent::DownloadFailed(failed)) => panic!("download failed: {}", failed.error),
+                            _ => {}
+                        }
+                    }
+                    _ = provider.poll_next() => {}
+                }
+                if let [progress] = downloader.download_progress().as_slice() {
+                    if progress.eta.is_some() {
+                        with_eta.get_or_insert(progress.clone());
+                    }
+                }
+            }
+        })
+        .await
+        .expect("download should finish");
+
+        let progress = with_eta.expect("a few seconds in, the download should have had an ETA");
+        assert!(progress.total > progress.downloaded);
+        // throttled, so it's well below what a local transfer would manage
+        assert!(
+            progress.bytes_per_sec > 0.0 && progress.bytes_per_sec < RATE as f64 * 2.0,
+            "{progress:?}"
+        );
+        assert!(downloader.download_progress().is_empty());
+
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_status_after_download() {
+        let mut provider = test_network(1, None).await;
+        let mut downloader = test_network(1, None).await;
+
+        let blob: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(1024 * 1024)
+            .map(char::from)
+            .collect();
+        let ticket = provider.add_downloadable(blob, 0).await.unwrap();
+        downloader.start_download(ticket, 0, &[]).await.unwrap();
+
+        timeout(Duration::from_secs(60), async {
+            loop {
+                tokio::select! {
+                    event = downloader.poll_next() => {
+                        match event.unwrap() {
+                            Some(NetworkEvent::DownloadComplete(_)) => break,
+                            Some(NetworkEvent::DownloadFailed(failed)) => panic!("download failed: {}", failed.error),
+                            _ => {}
+                        }
+                    }
+                    _ = provider.poll_next() => {}
+                }
+            }
+        })
+        .await
+        .expect("download should finish");
+
+        for network in [&mut provider, &mut downloader] {
+            on_update_stats(network.router.endpoint(), &mut network.state)
+                .await
+                .unwrap();
+        }
+
+        let status_of = |network: &TestNetwork, peer: NodeId| {
+            network
+                .peer_statuses()
+                .into_iter()
+                .find(|(node_id, _)| *node_id == peer)
+                .map(|(_, status)| status)
+                .expect("peer should have been seen")
+        };
+
+        let provider_status = status_of(&downloader, provider.node_id());
+        assert!(provider_status.latency.is_some());
+        assert!(provider_status.bandwidth > 0.0, "{provider_status:?}");
+        assert!(provider_status.reputation > 0.5, "{provider_status:?}");
+        assert_eq!(provider_status.blobs_served, 0);
+
+        let downloader_status = status_of(&provider, downloader.node_id());
+        assert_eq!(downloader_status.blobs_served, 1);
+        assert_eq!(downloader_status.reputation, 0.5);
+
+        downloader.shutdown().await.unwrap();
+        provider.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_gossip_channels_are_isolated() {
+        let health = || vec!["health".to_string()];
+        let mut sender = test_network_with(1, None, BlobStoreConfig::Memory, health()).await;
+        let mut health_subscriber =
+            test_network_with(1, None, BlobStoreConfig::Memory, health()).await;
+        let mut main_subscriber = test_network(1, None).await;
+        let sender_id = sender.node_id();
+        health_subscriber.add_peers(vec![sender_id]).await.unwrap();
+        main_subscriber.add_peers(vec![sender_id]).await.unwrap();
+
+        let mut broadcast = interval(Duration::from_millis(500));
+        let (mut got_health, mut got_main) = (false, false);
+        timeout(Duration::from_secs(30), async {
+            // keep broadcasting until the meshes have formed and both messages made it through
+            while !(got_health && got_main) {
+                tokio::select! {
+                    _ = broadcast.tick() => {
+                        // fails with NoPeers until the subscribers have joined
+                        let _ = sender.broadcast_on("health", &"beacon".to_string()).await;
+                        let _ = sender.broadcast(&"training".to_string()).await;
+                    }
+                    event = health_subscriber.poll_next() => {
+                        if let Some(NetworkEvent::ChannelMessage(channel, (from, message))) = event.unwrap() {
+                            assert_eq!((channel.as_str(), from, message.as_str()), ("health", sender_id, "beacon"));
+                            got_health = true;
+                        }
+                    }
+                    event = main_subscriber.poll_next() => {
+                        match event.unwrap() {
+                            Some(NetworkEvent::MessageReceived((_, message))) => {
+                                assert_eq!(message, "training", "a health beacon reached the main topic");
+                                got_main = true;
+                            }
+                            Some(NetworkEvent::ChannelMessage(channel, _)) => {
+                                panic!("got a message on channel {channel} without subscribing to it")
+                            }
+                            _ => {}
+                        }
+                    }
+                    _ = sender.poll_next() => {}
+                }
+            }
+        })
+        .await
+        .expect("both subscribers should get the messages meant for them");
+
+        assert!(matches!(
+            sender.broadcast_on("unknown", &"x".to_string()).await,
+            Err(BroadcastError::UnknownChannel(channel)) if channel == "unknown"
+        ));
+
+        for network in [sender, health_subscriber, main_subscriber] {
+            network.shutdown().await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use iroh_relay::RelayMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+use tokio::net::{lookup_host, UdpSocket};
+use tracing::{debug, info, warn};
+
+// just enough of STUN (RFC 5389) to ask a server which address it sees us at.
+const MAGIC_COOKIE: u32 = 0x2112_a442;
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const HEADER_LEN: usize = 20;
+
+/// How our NAT maps our address, as seen from the outside.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NatType {
+    /// Every server sees us at the same address, so peers can hole-punch to us directly.
+    /// Also what no NAT at all looks like.
+    Cone,
+    /// Each server sees us at a different address, so direct connections will mostly fail,
+    /// and traffic to peers will go through the relays.
+    Symmetric,
+    /// Fewer than two STUN servers answered, so there's nothing to compare.
+    Unknown,
+}
+
+impl Display for NatType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NatType::Cone => write!(f, "cone"),
+            NatType::Symmetric => write!(f, "symmetric"),
+            NatType::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// The result of asking a few STUN servers, from the same local socket, which address they see us at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NatProbe {
+    pub nat_type: NatType,
+    /// each server that answered, and where it saw us.
+    pub mapped_addresses: Vec<(SocketAddr, SocketAddr)>,
+}
+
+/// Classifies our NAT from the addresses that servers at different IPs saw us at.
+pub fn classify_nat(mapped_addresses: &[(SocketAddr, SocketAddr)]) -> NatType {
+    let mut by_server_ip: Vec<(IpAddr, SocketAddr)> = Vec::new();
+    for (server, mapped) in mapped_addresses {
+        if !by_server_ip.iter().any(|(ip, _)| *ip == server.ip()) {
+            by_server_ip.push((server.ip(), *mapped));
+        }
+    }
+    match by_server_ip.as_slice() {
+        [] | [_] => NatType::Unknown,
+        [(_, first), rest @ ..] => {
+            if rest.iter().all(|(_, mapped)| mapped == first) {
+                NatType::Cone
+            } else {
+                NatType::Symmetric
+            }
+        }
+    }
+}
+
+/// The STUN servers of every relay in `relay_map`.
+pub fn stun_servers(relay_map: &RelayMap) -> Vec<(String, u16)> {
+    relay_map
+        .nodes()
+        .filter_map(|node| {
+            node.url
+                .host_str()
+                .map(|host| (host.to_string(), node.stun_port))
+        })
+        .collect()
+}
+
+/// Asks every server in `stun_servers` which address it sees us at, waiting up to `timeout` for each,
+/// and classifies our NAT from the answers.
+pub async fn probe_nat(stun_servers: &[(String, u16)], timeout: Duration) -> NatProbe {
+    let mut mapped_addresses = Vec::new();
+    match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await {
+        Ok(socket) => {
+            for (host, port) in stun_servers {
+                let server = match lookup_host((host.as_str(), *port))
+                    .await
+                    .ok()
+                    .and_then(|mut addrs| addrs.find(SocketAddr::is_ipv4))
+                {
+                    Some(server) => server,
+                    None => {
+                        debug!("Couldn't resolve STUN server {host}:{port}");
+                        continue;
+                    }
+                };
+                match tokio::time::timeout(timeout, stun_binding(&socket, server)).await {
+                    Ok(Ok(mapped)) => mapped_addresses.push((server, mapped)),
+                    Ok(Err(err)) => debug!("STUN request to {server} failed: {err}"),
+                    Err(_) => debug!("STUN request to {server} timed out"),
+                }
+            }
+        }
+        Err(err) => warn!("Couldn't bind a socket to probe our NAT type: {err}"),
+    }
+    NatProbe {
+        nat_type: classify_nat(&mapped_addresses),
+        mapped_addresses,
+    }
+}
+
+/// Logs what kind of NAT we're behind, and what that means for connecting to peers.
+pub(crate) fn log_nat_probe(probe: &NatProbe) {
+    match probe.nat_type {
+        NatType::Symmetric => warn!(
+            "We're behind a symmetric NAT (seen at {:?}), direct connections to peers will likely fail and go through relays instead.",
+            probe.mapped_addresses
+        ),
+        nat_type => info!(
+            "NAT type: {nat_type} (seen at {:?})",
+            probe.mapped_addresses
+        ),
+    }
+}
+
+async fn stun_binding(socket: &UdpSocket, server: SocketAddr) -> std::io::Result<SocketAddr> {
+    let transaction_id: [u8; 12] = rand::random();
+    socket
+        .send_to(&binding_request(transaction_id), server)
+        .await?;
+    let mut buf = [0u8; 1024];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        if from != server {
+            continue;
+        }
+        if let Some(mapped) = parse_binding_response(&buf[..len], transaction_id) {
+            return Ok(mapped);
+        }
+    }
+}
+
+fn binding_request(transaction_id: [u8; 12]) -> [u8; HEADER_LEN] {
+    let mut request = [0u8; HEADER_LEN];
+    request[0..2].copy_from_slice(&BINDING_REQUEST.to_be_bytes());
+    // no attributes, so the length stays 0
+    request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request[8..20].copy_from_slice(&transaction_id);
+    request
+}
+
+/// The address the server saw us at, if `response` is a successful answer to our request.
+fn parse_binding_response(response: &[u8], transaction_id: [u8; 12]) -> Option<SocketAddr> {
+    if response.len() < HEADER_LEN
+        || u16::from_be_bytes([response[0], response[1]]) != BINDING_SUCCESS
+        || response[4..8] != MAGIC_COOKIE.to_be_bytes()
+        || response[8..20] != transaction_id
+    {
+        return None;
+    }
+    let len = u16::from_be_bytes([response[2], response[3]]) as usize;
+    let mut attributes = response.get(HEADER_LEN..HEADER_LEN + len)?;
+    let mut mapped = None;
+    while attributes.len() >= 4 {
+        let kind = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let value_len = u16::from_be_bytes([attributes[2], attributes[3]]) as usize;
+        let value = attributes.get(4..4 + value_len)?;
+        match kind {
+            ATTR_XOR_MAPPED_ADDRESS => return parse_address(value, Some(&response[4..20])),
+            ATTR_MAPPED_ADDRESS => mapped = parse_address(value, None),
+            _ => {}
+        }
+        // attributes are padded to 4 bytes
+        let padded_len = (4 + value_len).next_multiple_of(4);
+        attributes = attributes.get(padded_len..).unwrap_or_default();
+    }
+    mapped
+}
+
+/// Parses a (XOR-)MAPPED-ADDRESS. `xor` is the magic cookie followed by the transaction id.
+fn parse_address(value: &[u8], xor: Option<&[u8]>) -> Option<SocketAddr> {
+    let family = *value.get(1)?;
+    let mut port = u16::from_be_bytes([*value.get(2)?, *value.get(3)?]);
+    let mut ip = value.get(4..)?.to_vec();
+    if let Some(xor) = xor {
+        port ^= (MAGIC_COOKIE >> 16) as u16;
+        for (byte, mask) in ip.iter_mut().zip(xor) {
+            *byte ^= mask;
+        }
+    }
+    let ip = match family {
+        0x01 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(ip.get(..4)?).ok()?)),
+        0x02 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(ip.get(..16)?).ok()?)),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What a STUN server would answer when it sees us at `mapped`.
+    fn mock_response(transaction_id: [u8; 12], mapped: SocketAddr) -> Vec<u8> {
+        let SocketAddr::V4(mapped) = mapped else {
+            unreachable!("only ipv4 in these tests")
+        };
+        let mut response = vec![];
+        response.extend_from_slice(&BINDING_SUCCESS.to_be_bytes());
+        response.extend_from_slice(&12u16.to_be_bytes());
+        response.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        response.extend_from_slice(&transaction_id);
+        // a SOFTWARE attribute that has to be skipped, padded from 3 to 4 bytes
+        response.extend_from_slice(&[0x80, 0x22, 0, 3, b'f', b'o', b'o', 0]);
+        // XOR-MAPPED-ADDRESS
+        response.extend_from_slice(&ATTR_XOR_MAPPED_ADDRESS.to_be_bytes());
+        response.extend_from_slice(&8u16.to_be_bytes());
+        response.extend_from_slice(&[0, 0x01]);
+        response.extend_from_slice(&(mapped.port() ^ (MAGIC_COOKIE >> 16) as u16).to_be_bytes());
+        let ip = u32::from(*mapped.ip()) ^ MAGIC_COOKIE;
+        response.extend_from_slice(&ip.to_be_bytes());
+        // fix up the length now that we know it
+        let len = (response.len() - HEADER_LEN) as u16;
+        response[2..4].copy_from_slice(&len.to_be_bytes());
+        response
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_binding_response() {
+        let transaction_id = [7; 12];
+        let request = binding_request(transaction_id);
+        assert_eq!(&request[0..2], &[0, 1]);
+        assert_eq!(&request[8..], &transaction_id);
+
+        let response = mock_response(transaction_id, addr("203.0.113.9:40123"));
+        assert_eq!(
+            parse_binding_response(&response, transaction_id),
+            Some(addr("203.0.113.9:40123"))
+        );
+        // an answer to someone else's request
+        assert_eq!(parse_binding_response(&response, [8; 12]), None);
+        assert_eq!(
+            parse_binding_response(&response[..10], transaction_id),
+            None
+        );
+    }
+
+    #[test]
+    fn test_classify_symmetric_vs_cone_nat() {
+        let servers = [
+            addr("198.51.100.1:3478"),
+            addr("198.51.100.2:3478"),
+            addr("198.51.100.3:3478"),
+        ];
+        let probe = |mapped: [&str; 3]| -> Vec<(SocketAddr, SocketAddr)> {
+            servers
+                .iter()
+                .zip(mapped)
+                .enumerate()
+                .map(|(i, (server, mapped))| {
+                    let transaction_id = [i as u8; 12];
+                    let response = mock_response(transaction_id, addr(mapped));
+                    (
+                        *server,
+                        parse_binding_response(&response, transaction_id).unwrap(),
+                    )
+                })
+                .collect()
+        };
+
+        // same public address and port for everyone
+        let cone = probe(["203.0.113.9:40123"; 3]);
+        assert_eq!(classify_nat(&cone), NatType::Cone);
+
+        // a new port for every destination
+        let symmetric = probe([
+            "203.0.113.9:40123",
+            "203.0.113.9:40124",
+            "203.0.113.9:40125",
+        ]);
+        assert_eq!(classify_nat(&symmetric), NatType::Symmetric);
+
+        // one answer, or several from the same server, can't tell the two apart
+        assert_eq!(classify_nat(&symmetric[..1]), NatType::Unknown);
+        assert_eq!(
+            classify_nat(&[
+                (servers[0], addr("203.0.113.9:40123")),
+                (addr("198.51.100.1:3479"), addr("203.0.113.9:40124")),
+            ]),
+            NatType::Unknown
+        );
+        assert_eq!(classify_nat(&[]), NatType::Unknown);
+    }
+}
@@ -1,20 +1,33 @@
 use anyhow::Result;
+use futures_util::future::BoxFuture;
 use iroh::{endpoint::Connection, protocol::ProtocolHandler};
-use iroh_blobs::ticket::BlobTicket;
+use iroh_blobs::{
+    provider::{CustomEventSender, Event as ProviderEvent},
+    ticket::BlobTicket,
+    Hash,
+};
 use psyche_core::BoxedFuture;
 use std::collections::{hash_map::Entry, HashMap, HashSet};
 use std::io::{Cursor, Write};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
 use tch::Tensor;
 use thiserror::Error;
 use tokenizers::Tokenizer;
-use tokio::sync::{mpsc::UnboundedSender, oneshot};
+use tokio::sync::{mpsc::UnboundedSender, oneshot, OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinHandle;
 use tracing::{debug, trace};
 
-use crate::{NetworkConnection, Networkable, TransmittableDownload};
+use crate::{
+    state::BlobsServed, upload_limit::ThrottledProviderEvents, NetworkConnection, Networkable,
+    TransmittableDownload,
+};
 
-pub const ALPN: &[u8] = b"model-sharing/0";
+pub const ALPN: &[u8] = b"model-sharing/1";
 pub const MODEL_REQUEST_TIMEOUT_SECS: u64 = 10;
+/// Serialized parameters larger than this are split into several blobs,
+/// so they can be fetched in parallel and a failed transfer only retries one chunk.
+pub const PARAMETER_CHUNK_SIZE: usize = 32 * 1024 * 1024;
 
 #[derive(Error, Debug, serde::Serialize, serde::Deserialize)]
 pub enum SharableModelError {
@@ -50,6 +63,10 @@ pub enum SharableModelError {
     LoadThreadCrashed,
     #[error("P2P add download error: {0}")]
     P2PAddDownloadError(String),
+    #[error("Too many concurrent parameter requests, try another peer")]
+    TooManyRequests,
+    #[error("Invalid chunk {1} of {2} for parameter {0}")]
+    InvalidParameterChunk(String, u32, u32),
 }
 
 // This convertions are done manually since the original errors does not implement serialize and deserialize
@@ -90,7 +107,7 @@ pub enum ModelRequestType {
 pub enum ParameterSharingMessage {
     Get(
         String,
-        oneshot::Sender<Result<BlobTicket, SharableModelError>>,
+        oneshot::Sender<Result<Vec<BlobTicket>, SharableModelError>>,
     ),
 }
 
@@ -98,23 +115,103 @@ pub enum ModelConfigSharingMessage {
     Get(oneshot::Sender<Result<BlobTicket, SharableModelError>>),
 }
 
+/// One chunk of a serialized model parameter. Small parameters are sent as a single chunk.
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct TransmittableModelParameter {
     param_name_bytes: Vec<u8>,
     param_value_bytes: Vec<u8>,
+    chunk_index: u32,
+    num_chunks: u32,
 }
 
 impl TransmittableModelParameter {
-    fn new(param_name_bytes: Vec<u8>, param_value_bytes: Vec<u8>) -> Self {
-        Self {
-            param_name_bytes,
-            param_value_bytes,
+    /// Splits a serialized parameter into chunks of at most `chunk_size` bytes.
+    pub(crate) fn chunked(
+        param_name_bytes: Vec<u8>,
+        param_value_bytes: &[u8],
+        chunk_size: usize,
+    ) -> Vec<Self> {
+        if param_value_bytes.is_empty() {
+            return vec![Self {
+                param_name_bytes,
+                param_value_bytes: Vec::new(),
+                chunk_index: 0,
+                num_chunks: 1,
+            }];
         }
+        let num_chunks = param_value_bytes.len().div_ceil(chunk_size) as u32;
+        param_value_bytes
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk_index, chunk)| Self {
+                param_name_bytes: param_name_bytes.clone(),
+                param_value_bytes: chunk.to_vec(),
+                chunk_index: chunk_index as u32,
+                num_chunks,
+            })
+            .collect()
     }
 
     pub fn name(&self) -> Result<String, SharableModelError> {
         Ok(String::from_utf8(self.param_name_bytes.clone())?)
     }
+
+    pub fn chunk_index(&self) -> u32 {
+        self.chunk_index
+    }
+
+    pub fn num_chunks(&self) -> u32 {
+        self.num_chunks
+    }
+}
+
+/// Collects the chunks of a single parameter as they're downloaded, in any order.
+#[derive(Debug)]
+struct ParameterChunks {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+}
+
+impl ParameterChunks {
+    fn new(num_chunks: u32) -> Self {
+        Self {
+            chunks: vec![None; num_chunks as usize],
+            received: 0,
+        }
+    }
+
+    /// Stores a chunk, returning the reassembled parameter bytes once every chunk has arrived.
+    fn insert(
+        &mut self,
+        param_name: &str,
+        chunk: TransmittableModelParameter,
+    ) -> Result<Option<Vec<u8>>, SharableModelError> {
+        let invalid = || {
+            SharableModelError::InvalidParameterChunk(
+                param_name.to_string(),
+                chunk.chunk_index,
+                chunk.num_chunks,
+            )
+        };
+        if chunk.num_chunks as usize != self.chunks.len() {
+            return Err(invalid());
+        }
+        let Some(slot) = self.chunks.get_mut(chunk.chunk_index as usize) else {
+            return Err(invalid());
+        };
+        if slot.is_some() {
+            return Err(SharableModelError::ParameterAlreadyAdded);
+        }
+        *slot = Some(chunk.param_value_bytes);
+        self.received += 1;
+
+        if self.received < self.chunks.len() {
+            return Ok(None);
+        }
+        Ok(Some(
+            self.chunks.drain(..).flatten().collect::<Vec<_>>().concat(),
+        ))
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -136,9 +233,10 @@ impl TransmittableModelConfig {
 pub struct SharableModel {
     parameters: Option<HashMap<String, Option<Tensor>>>,
     serializing_parameters: Option<
-        HashMap<String, JoinHandle<Result<TransmittableModelParameter, SharableModelError>>>,
+        HashMap<String, JoinHandle<Result<Vec<TransmittableModelParameter>, SharableModelError>>>,
     >,
-    serialized_parameters: Option<HashMap<String, BlobTicket>>,
+    serialized_parameters: Option<HashMap<String, Vec<BlobTicket>>>,
+    parameter_chunks: HashMap<String, ParameterChunks>,
     model_config: Option<String>,
     tokenizer_config: Option<Tokenizer>,
     config_and_tokenizer_ticket: Option<BlobTicket>,
@@ -154,6 +252,7 @@ impl SharableModel {
             parameters: None,
             serializing_parameters: None,
             serialized_parameters: None,
+            parameter_chunks: HashMap::new(),
             tx_params_response: None,
             model_config: None,
             tokenizer_config: None,
@@ -202,10 +301,16 @@ impl SharableModel {
                     param_name_buffer.write_all(param_name.as_bytes())?;
                     parameter.save_to_stream(&mut param_value_buffer)?;
 
-                    let transmittable_parameter =
-                        TransmittableModelParameter::new(param_name_buffer, param_value_buffer);
+                    let transmittable_parameter = TransmittableModelParameter::chunked(
+                        param_name_buffer,
+                        &param_value_buffer,
+                        PARAMETER_CHUNK_SIZE,
+                    );
 
-                    trace!("Finished serializing parameter {param_name} for sharing");
+                    trace!(
+                        "Finished serializing parameter {param_name} for sharing in {} chunks",
+                        transmittable_parameter.len()
+                    );
                     Ok(transmittable_parameter)
                 }),
             );
@@ -231,7 +336,7 @@ impl SharableModel {
         param_name: &str,
         p2p: &mut NetworkConnection<B, TransmittableDownload>,
         tag: u32,
-    ) -> Result<BlobTicket, SharableModelError> {
+    ) -> Result<Vec<BlobTicket>, SharableModelError> {
         let Some(loading_parameters) = self.serializing_parameters.as_mut() else {
             return Err(SharableModelError::ParametersNotInitialized);
         };
@@ -240,26 +345,32 @@ impl SharableModel {
         };
 
         match loaded_parameters.get(param_name) {
-            Some(blob_ticket) => {
+            Some(blob_tickets) => {
                 trace!("Using cached downloadable for {param_name}");
-                Ok(blob_ticket.clone())
+                Ok(blob_tickets.clone())
             }
             None => match loading_parameters.remove(param_name) {
                 Some(loading) => {
                     trace!("Waiting for {param_name} parameter to finish serializing");
-                    let transmittable_parameter = loading
+                    let transmittable_chunks = loading
                         .await
                         .map_err(|_| SharableModelError::LoadThreadCrashed)??;
-                    let transmittable_download =
-                        TransmittableDownload::ModelParameter(transmittable_parameter);
                     trace!("Adding paramerter downloadable {param_name}");
-                    let blob_ticket = p2p
-                        .add_downloadable(transmittable_download, tag)
-                        .await
-                        .map_err(|err| SharableModelError::P2PAddDownloadError(err.to_string()))?;
-                    loaded_parameters.insert(param_name.to_string(), blob_ticket.clone());
+                    let mut blob_tickets = Vec::with_capacity(transmittable_chunks.len());
+                    for transmittable_chunk in transmittable_chunks {
+                        let transmittable_download =
+                            TransmittableDownload::ModelParameter(transmittable_chunk);
+                        let blob_ticket = p2p
+                            .add_downloadable(transmittable_download, tag)
+                            .await
+                            .map_err(|err| {
+                            SharableModelError::P2PAddDownloadError(err.to_string())
+                        })?;
+                        blob_tickets.push(blob_ticket);
+                    }
+                    loaded_parameters.insert(param_name.to_string(), blob_tickets.clone());
                     trace!("Finished adding paramerter downloadable {param_name}");
-                    Ok(blob_ticket)
+                    Ok(blob_tickets)
                 }
                 None => Err(SharableModelError::ParameterUnknown(param_name.to_string())),
             },
@@ -325,10 +436,12 @@ impl SharableModel {
             parameters.insert(param_name.clone(), None);
         }
         self.parameters = Some(parameters);
+        self.parameter_chunks.clear();
         self.tx_params_response = Some(tx_params_response);
     }
 
-    // Add new parameter downloaded from another peer
+    // Add new parameter chunk downloaded from another peer. The parameter is
+    // loaded once all of its chunks have been added.
     pub async fn add_parameter(
         &mut self,
         parameter: TransmittableModelParameter,
@@ -337,9 +450,38 @@ impl SharableModel {
             return Err(SharableModelError::ParametersNotInitialized);
         };
 
-        // Deserialize model parameter
         let param_name = parameter.name()?;
-        let buf_reader = Cursor::new(parameter.param_value_bytes);
+        match parameters.get(&param_name) {
+            None => return Err(SharableModelError::ParameterUnknown(param_name)),
+            Some(Some(_)) => return Err(SharableModelError::ParameterAlreadyAdded),
+            Some(None) => {}
+        }
+
+        // Reassemble the chunks of the parameter
+        let param_value_bytes = match self.parameter_chunks.entry(param_name.clone()) {
+            Entry::Occupied(mut chunks) => {
+                let param_value_bytes = chunks.get_mut().insert(&param_name, parameter)?;
+                if param_value_bytes.is_some() {
+                    chunks.remove();
+                }
+                param_value_bytes
+            }
+            Entry::Vacant(chunks) => {
+                let mut new_chunks = ParameterChunks::new(parameter.num_chunks);
+                let param_value_bytes = new_chunks.insert(&param_name, parameter)?;
+                if param_value_bytes.is_none() {
+                    chunks.insert(new_chunks);
+                }
+                param_value_bytes
+            }
+        };
+        let Some(param_value_bytes) = param_value_bytes else {
+            trace!("Waiting for remaining chunks of parameter {param_name}");
+            return Ok(());
+        };
+
+        // Deserialize model parameter
+        let buf_reader = Cursor::new(param_value_bytes);
         trace!("Start loading parameter {param_name}");
         let param_value = tokio::task::spawn_blocking(move || Tensor::load_from_stream(buf_reader))
             .await
@@ -427,42 +569,265 @@ impl SharableModel {
     }
 }
 
+/// Caps how many parameter requests this node serves at the same time,
+/// so that a node seeding the model isn't overwhelmed by newly joined peers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParameterServeLimit {
+    /// Serve every request as soon as it arrives.
+    #[default]
+    Unlimited,
+    /// Serve at most this many requests at once, answering the rest with
+    /// [`SharableModelError::TooManyRequests`] so they can try another peer.
+    Reject(usize),
+    /// Serve at most this many requests at once, holding the rest until a slot frees up.
+    Queue(usize),
+}
+
+/// How many times to try a model request against a peer,
+/// so a connection hiccup isn't immediately counted as the peer failing.
+/// Errors the peer answers with aren't retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectRetry {
+    pub max_attempts: u32,
+    /// Wait before the second attempt, doubled for every attempt after that.
+    /// Each wait is randomly shortened by up to half.
+    pub initial_backoff: Duration,
+}
+
+impl Default for ConnectRetry {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// How long a parameter serve keeps its slot when the requester never fetches all of its blobs.
+const SERVE_SLOT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone)]
+pub(crate) struct ServeLimiter {
+    permits: Arc<Semaphore>,
+    queue: bool,
+    serving: Arc<StdMutex<Serving>>,
+}
+
+/// The serving slots waiting on blob transfers, tracked from the blobs provider's events.
+#[derive(Debug, Default)]
+struct Serving {
+    // one entry per blob a requester has yet to fetch, all sharing the slot of their request
+    slots: HashMap<Hash, Vec<Arc<OwnedSemaphorePermit>>>,
+    // the blob each in-flight blobs request asked for, keyed by (connection, request)
+    transfers: HashMap<(u64, u64), Hash>,
+}
+
+impl Serving {
+    fn release(&mut self, hash: &Hash) {
+        if let Entry::Occupied(mut slots) = self.slots.entry(*hash) {
+            slots.get_mut().pop();
+            if slots.get().is_empty() {
+                slots.remove();
+            }
+        }
+    }
+}
+
+impl ServeLimiter {
+    pub(crate) fn new(limit: ParameterServeLimit) -> Option<Self> {
+        let (max_concurrent, queue) = match limit {
+            ParameterServeLimit::Unlimited => return None,
+            ParameterServeLimit::Reject(max_concurrent) => (max_concurrent, false),
+            ParameterServeLimit::Queue(max_concurrent) => (max_concurrent, true),
+        };
+        Some(Self {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            queue,
+            serving: Default::default(),
+        })
+    }
+
+    /// Takes a serving slot, which is released when the returned permit is dropped.
+    pub(crate) async fn acquire(&self) -> Result<OwnedSemaphorePermit, SharableModelError> {
+        if self.queue {
+            self.permits
+                .clone()
+                .acquire_owned()
+                .await
+                .map_err(|_| SharableModelError::TooManyRequests)
+        } else {
+            self.permits
+                .clone()
+                .try_acquire_owned()
+                .map_err(|_| SharableModelError::TooManyRequests)
+        }
+    }
+
+    /// Keeps `permit` until each of `blobs` has been sent once, or [`SERVE_SLOT_TIMEOUT`] has passed.
+    pub(crate) fn hold_until_served(
+        &self,
+        permit: OwnedSemaphorePermit,
+        blobs: impl IntoIterator<Item = Hash>,
+    ) {
+        let permit = Arc::new(permit);
+        let blobs: Vec<Hash> = blobs.into_iter().collect();
+        {
+            let mut serving = self.serving.lock().unwrap();
+            for hash in &blobs {
+                serving.slots.entry(*hash).or_default().push(permit.clone());
+            }
+        }
+
+        let serving = Arc::downgrade(&self.serving);
+        let permit = Arc::downgrade(&permit);
+        tokio::spawn(async move {
+            tokio::time::sleep(SERVE_SLOT_TIMEOUT).await;
+            let (Some(serving), Some(permit)) = (serving.upgrade(), permit.upgrade()) else {
+                return;
+            };
+            debug!("Releasing a parameter serving slot whose blobs were never fetched");
+            let mut serving = serving.lock().unwrap();
+            for hash in blobs {
+                if let Some(slots) = serving.slots.get_mut(&hash) {
+                    slots.retain(|slot| !Arc::ptr_eq(slot, &permit));
+                    if slots.is_empty() {
+                        serving.slots.remove(&hash);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Frees a serving slot once the blobs provider has sent all of its blobs.
+    /// An aborted transfer counts as sent, since the requester will fetch it elsewhere.
+    pub(crate) fn on_provider_event(&self, event: &ProviderEvent) {
+        let mut serving = self.serving.lock().unwrap();
+        match *event {
+            ProviderEvent::GetRequestReceived {
+                connection_id,
+                request_id,
+                hash,
+            } => {
+                serving.transfers.insert((connection_id, request_id), hash);
+            }
+            ProviderEvent::TransferBlobCompleted { hash, .. } => serving.release(&hash),
+            ProviderEvent::TransferCompleted {
+                connection_id,
+                request_id,
+                ..
+            } => {
+                serving.transfers.remove(&(connection_id, request_id));
+            }
+            ProviderEvent::TransferAborted {
+                connection_id,
+                request_id,
+                ..
+            } => {
+                if let Some(hash) = serving.transfers.remove(&(connection_id, request_id)) {
+                    serving.release(&hash);
+                }
+            }
+            ProviderEvent::ConnectionClosed { connection_id } => {
+                serving
+                    .transfers
+                    .retain(|(connection, _), _| *connection != connection_id);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Hands the blobs provider's events to the parameter serve limiter, the served blob counts and the upload throttle.
+#[derive(Debug)]
+pub(crate) struct ProviderEvents {
+    pub serve_limiter: Option<ServeLimiter>,
+    pub throttle: ThrottledProviderEvents,
+    pub blobs_served: BlobsServed,
+}
+
+impl CustomEventSender for ProviderEvents {
+    fn send(&self, event: ProviderEvent) -> BoxFuture<'static, ()> {
+        if let Some(serve_limiter) = &self.serve_limiter {
+            serve_limiter.on_provider_event(&event);
+        }
+        self.blobs_served.on_provider_event(&event);
+        self.throttle.send(event)
+    }
+
+    fn try_send(&self, event: ProviderEvent) {
+        if let Some(serve_limiter) = &self.serve_limiter {
+            serve_limiter.on_provider_event(&event);
+        }
+        self.blobs_served.on_provider_event(&event);
+        self.throttle.try_send(event);
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ModelSharing {
     tx_model_parameter_req: UnboundedSender<ParameterSharingMessage>,
     tx_model_config_req: UnboundedSender<ModelConfigSharingMessage>,
+    serve_limiter: Option<ServeLimiter>,
 }
 
 impl ModelSharing {
     pub fn new(
         tx_model_parameter_req: UnboundedSender<ParameterSharingMessage>,
         tx_model_config_req: UnboundedSender<ModelConfigSharingMessage>,
+        parameter_serve_limit: ParameterServeLimit,
     ) -> Self {
         Self {
             tx_model_parameter_req,
             tx_model_config_req,
+            serve_limiter: ServeLimiter::new(parameter_serve_limit),
         }
     }
+
+    pub(crate) fn serve_limiter(&self) -> Option<&ServeLimiter> {
+        self.serve_limiter.as_ref()
+    }
+
     pub(crate) fn _accept_connection(
         connection: Connection,
         tx_model_parameter_req: UnboundedSender<ParameterSharingMessage>,
         tx_model_config_req: UnboundedSender<ModelConfigSharingMessage>,
+        serve_limiter: Option<ServeLimiter>,
     ) -> BoxedFuture<Result<()>> {
         Box::pin(async move {
             let (mut send, mut recv) = connection.accept_bi().await?;
             let model_request_type_bytes = recv.read_to_end(1000).await?;
             let model_request_type = ModelRequestType::from_bytes(&model_request_type_bytes)?;
-            let blob_ticket = match model_request_type {
-                ModelRequestType::Parameter(parameter_request) => {
+            let blob_tickets = match model_request_type {
+                ModelRequestType::Parameter(parameter_request) => 'serve: {
+                    // Take a serving slot, held until the requester has fetched the parameter blobs
+                    let permit = match &serve_limiter {
+                        Some(limiter) => {
+                            match limiter.acquire().await {
+                                Ok(permit) => Some(permit),
+                                Err(err) => {
+                                    debug!("Rejecting request for parameter {parameter_request}: {err}");
+                                    break 'serve Err(err);
+                                }
+                            }
+                        }
+                        None => None,
+                    };
+
                     // Create channel for requesting the model parameter to the client backend
                     // and add a new blob for it
                     let (tx_req, rx_req) =
-                        oneshot::channel::<Result<BlobTicket, SharableModelError>>();
+                        oneshot::channel::<Result<Vec<BlobTicket>, SharableModelError>>();
                     let request = ParameterSharingMessage::Get(parameter_request, tx_req);
                     tx_model_parameter_req.send(request)?;
 
-                    // Receive the blob ticket and forward it to the requesting client
-                    rx_req.await?
+                    // Receive the blob tickets and forward them to the requesting client
+                    let blob_tickets = rx_req.await?;
+                    if let (Some(limiter), Some(permit), Ok(tickets)) =
+                        (&serve_limiter, permit, &blob_tickets)
+                    {
+                        limiter.hold_until_served(permit, tickets.iter().map(|t| t.hash()));
+                    }
+                    blob_tickets
                 }
                 ModelRequestType::Config => {
                     // Create channel for requesting the model config to the client backend and add a new blob for it
@@ -472,10 +837,10 @@ impl ModelSharing {
                     tx_model_config_req.send(request)?;
 
                     // Receive the blob ticket and forward it to the requesting client
-                    rx_req.await?
+                    rx_req.await?.map(|ticket| vec![ticket])
                 }
             };
-            let data = postcard::to_stdvec(&blob_ticket)?;
+            let data = postcard::to_stdvec(&blob_tickets)?;
             send.write_all(&data).await?;
             send.finish()?;
 
@@ -490,8 +855,15 @@ impl ModelSharing {
     pub fn accept_connection(&self, connection: Connection) -> BoxedFuture<Result<()>> {
         let tx_model_parameter_req = self.tx_model_parameter_req.clone();
         let tx_model_config_req = self.tx_model_config_req.clone();
+        let serve_limiter = self.serve_limiter.clone();
         Box::pin(async move {
-            Self::_accept_connection(connection, tx_model_parameter_req, tx_model_config_req).await
+            Self::_accept_connection(
+                connection,
+                tx_model_parameter_req,
+                tx_model_config_req,
+                serve_limiter,
+            )
+            .await
         })
     }
 }
@@ -500,8 +872,377 @@ impl ProtocolHandler for ModelSharing {
     fn accept(&self, connection: Connection) -> BoxedFuture<Result<()>> {
         let tx_model_parameter_req = self.tx_model_parameter_req.clone();
         let tx_model_config_req = self.tx_model_config_req.clone();
+        let serve_limiter = self.serve_limiter.clone();
         Box::pin(async move {
-            Self::_accept_connection(connection, tx_model_parameter_req, tx_model_config_req).await
+            Self::_accept_connection(
+                connection,
+                tx_model_parameter_req,
+                tx_model_config_req,
+                serve_limiter,
+            )
+            .await
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_serve_limit_rejects_excess_requests() {
+        let limiter = ServeLimiter::new(ParameterServeLimit::Reject(2)).unwrap();
+
+        let first = limiter.acquire().await.unwrap();
+        let _second = limiter.acquire().await.unwrap();
+        assert!(matches!(
+            limiter.acquire().await,
+            Err(SharableModelError::TooManyRequests)
+        ));
+
+        // a finished serve frees up a slot
+        drop(first);
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_serve_limit_queues_excess_requests() {
+        let limiter = ServeLimiter::new(ParameterServeLimit::Queue(2)).unwrap();
+
+        let first = limiter.acquire().await.unwrap();
+        let _second = limiter.acquire().await.unwrap();
+
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!queued.is_finished());
+
+        drop(first);
+        timeout(Duration::from_secs(1), queued)
+            .await
+            .expect("queued request should be served once a slot frees up")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_slot_held_until_blobs_are_sent() {
+        let limiter = ServeLimiter::new(ParameterServeLimit::Reject(1)).unwrap();
+        let (first_chunk, second_chunk) = (Hash::new(b"chunk 0"), Hash::new(b"chunk 1"));
+
+        let permit = limiter.acquire().await.unwrap();
+        limiter.hold_until_served(permit, [first_chunk, second_chunk]);
+        assert!(limiter.acquire().await.is_err());
+
+        limiter.on_provider_event(&ProviderEvent::TransferBlobCompleted {
+            connection_id: 0,
+            request_id: 0,
+            hash: first_chunk,
+            index: 0,
+            size: 1024,
+        });
+        assert!(limiter.acquire().await.is_err());
+
+        // an aborted transfer frees the slot too, the requester retries it elsewhere
+        limiter.on_provider_event(&ProviderEvent::GetRequestReceived {
+            connection_id: 0,
+            request_id: 1,
+            hash: second_chunk,
+        });
+        limiter.on_provider_event(&ProviderEvent::TransferAborted {
+            connection_id: 0,
+            request_id: 1,
+            stats: None,
+        });
+        assert!(limiter.acquire().await.is_ok());
+    }
+
+    #[test]
+    fn test_serve_limit_unlimited() {
+        assert!(ServeLimiter::new(ParameterServeLimit::Unlimited).is_none());
+    }
+
+    #[test]
+    fn test_chunked_parameter_reassembles() {
+        const CHUNK_SIZE: usize = 1024;
+        let param_value_bytes: Vec<u8> = (0..10 * CHUNK_SIZE + 123).map(|i| i as u8).collect();
+
+        let mut chunks = TransmittableModelParameter::chunked(
+            b"model.embed_tokens.weight".to_vec(),
+            &param_value_bytes,
+            CHUNK_SIZE,
+        );
+        assert_eq!(chunks.len(), 11);
+        assert!(chunks.iter().all(|c| c.num_chunks() == 11));
+
+        // chunks can finish downloading in any order
+        chunks.reverse();
+        let mut reassembler = ParameterChunks::new(11);
+        let mut reassembled = None;
+        for chunk in chunks {
+            assert!(reassembled.is_none());
+            reassembled = reassembler
+                .insert("model.embed_tokens.weight", chunk)
+                .unwrap();
+        }
+        assert_eq!(reassembled.unwrap(), param_value_bytes);
+    }
+
+    #[test]
+    fn test_chunked_parameter_rejects_bad_chunks() {
+        let chunks = TransmittableModelParameter::chunked(b"w".to_vec(), &[1, 2, 3, 4, 5], 2);
+        assert_eq!(chunks.len(), 3);
+
+        let mut reassembler = ParameterChunks::new(3);
+        assert!(reassembler
+            .insert("w", chunks[0].clone())
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            reassembler.insert("w", chunks[0].clone()),
+            Err(SharableModelError::ParameterAlreadyAdded)
+        ));
+
+        let mut wrong_count = ParameterChunks::new(2);
+        assert!(matches!(
+            wrong_count.insert("w", chunks[2].clone()),
+            Err(SharableModelError::InvalidParameterChunk(_, 2, 3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chunked_tensor_parameter_loads() {
+        let tensor = Tensor::randn([64, 64], (tch::Kind::Float, tch::Device::Cpu));
+        let mut param_value_bytes = Vec::new();
+        tensor.save_to_stream(&mut param_value_bytes).unwrap();
+        let chunks = TransmittableModelParameter::chunked(
+            b"weight".to_vec(),
+            &param_value_bytes,
+            param_value_bytes.len() / 4,
+        );
+        assert!(chunks.len() > 1);
+
+        let (tx_params, rx_params) = oneshot::channel();
+        let mut sharable_model = SharableModel::empty();
+        sharable_model.initialize_parameters(&["weight".to_string()], tx_params);
+        for chunk in chunks {
+            assert!(!sharable_model.is_download_complete());
+            sharable_model.add_parameter(chunk).await.unwrap();
+        }
+        assert!(sharable_model.is_download_complete());
+
+        sharable_model.send_init_parameters().unwrap();
+        let parameters = rx_params.await.unwrap();
+        assert!(parameters["weight"].equal(&tensor));
+    }
+
+    #[tokio::test]
+    async fn test_request_model_retries_connect() -> Result<()> {
+        use crate::request_model;
+        use iroh::Endpoint;
+        use iroh_blobs::BlobFormat;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::sync::mpsc;
+
+        // the server doesn't speak model sharing yet, so the first connection attempt is refused
+        let server = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .alpns(vec![b"not-model-sharing/0".to_vec()])
+            .bind()
+            .await?;
+        let client = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .bind()
+            .await?;
+        let server_addr = server.node_addr().await?;
+        client.add_node_addr(server_addr.clone())?;
+
+        let (tx_model_parameter_req, _rx_model_parameter_req) = mpsc::unbounded_channel();
+        let (tx_model_config_req, mut rx_model_config_req) = mpsc::unbounded_channel();
+        let model_sharing = ModelSharing::new(
+            tx_model_parameter_req,
+            tx_model_config_req,
+            ParameterServeLimit::Unlimited,
+        );
+
+        let refused = Arc::new(AtomicUsize::new(0));
+        let accept_task = tokio::spawn({
+            let server = server.clone();
+            let refused = refused.clone();
+            async move {
+                while let Some(incoming) = server.accept().await {
+                    match incoming.await {
+                        Ok(connection) => {
+                            let _ = model_sharing.accept_connection(connection).await;
+                        }
+                        Err(_) => {
+                            refused.fetch_add(1, Ordering::SeqCst);
+                            server.set_alpns(vec![ALPN.to_vec()]).unwrap();
+                        }
+                    }
+                }
+            }
+        });
+
+        let ticket = BlobTicket::new(
+            server_addr.clone(),
+            iroh_blobs::Hash::new(b"model config"),
+            BlobFormat::Raw,
+        )?;
+        tokio::spawn({
+            let ticket = ticket.clone();
+            async move {
+                if let Some(ModelConfigSharingMessage::Get(tx)) = rx_model_config_req.recv().await {
+                    let _ = tx.send(Ok(ticket));
+                }
+            }
+        });
+
+        let tickets = request_model(
+            &client,
+            server_addr.node_id,
+            &ModelRequestType::Config,
+            ConnectRetry {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(200),
+            },
+        )
+        .await?;
+        assert_eq!(tickets, vec![ticket]);
+        assert_eq!(refused.load(Ordering::SeqCst), 1);
+
+        accept_task.abort();
+        client.close().await;
+        server.close().await;
+        Ok(())
+    }
+
+    /// A model sharing server whose config requests are answered by the test,
+    /// and a client that knows how to reach it.
+    async fn model_config_server() -> Result<(
+        iroh::Endpoint,
+        iroh::Endpoint,
+        tokio::sync::mpsc::UnboundedReceiver<ModelConfigSharingMessage>,
+        JoinHandle<()>,
+    )> {
+        use iroh::Endpoint;
+        use tokio::sync::mpsc;
+
+        let server = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .alpns(vec![ALPN.to_vec()])
+            .bind()
+            .await?;
+        let client = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .bind()
+            .await?;
+        client.add_node_addr(server.node_addr().await?)?;
+
+        let (tx_model_parameter_req, _rx_model_parameter_req) = mpsc::unbounded_channel();
+        let (tx_model_config_req, rx_model_config_req) = mpsc::unbounded_channel();
+        let model_sharing = ModelSharing::new(
+            tx_model_parameter_req,
+            tx_model_config_req,
+            ParameterServeLimit::Unlimited,
+        );
+        let accept_task = tokio::spawn({
+            let server = server.clone();
+            async move {
+                while let Some(incoming) = server.accept().await {
+                    if let Ok(connection) = incoming.await {
+                        let model_sharing = model_sharing.clone();
+                        tokio::spawn(async move {
+                            let _ = model_sharing.accept_connection(connection).await;
+                        });
+                    }
+                }
+            }
+        });
+        Ok((server, client, rx_model_config_req, accept_task))
+    }
+
+    #[tokio::test]
+    async fn test_request_model_retries_failed_requests() -> Result<()> {
+        use crate::request_model;
+        use iroh_blobs::BlobFormat;
+
+        let (server, client, mut rx_model_config_req, accept_task) = model_config_server().await?;
+        let ticket = BlobTicket::new(
+            server.node_addr().await?,
+            iroh_blobs::Hash::new(b"model config"),
+            BlobFormat::Raw,
+        )?;
+
+        // the first two requests die mid-flight: dropping the reply drops the connection
+        let answer_task = tokio::spawn({
+            let ticket = ticket.clone();
+            async move {
+                let mut requests = 0;
+                while let Some(ModelConfigSharingMessage::Get(tx)) =
+                    rx_model_config_req.recv().await
+                {
+                    requests += 1;
+                    if requests > 2 {
+                        let _ = tx.send(Ok(ticket.clone()));
+                    }
+                }
+                requests
+            }
+        });
+
+        let tickets = request_model(
+            &client,
+            server.node_id(),
+            &ModelRequestType::Config,
+            ConnectRetry {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(100),
+            },
+        )
+        .await?;
+        assert_eq!(tickets, vec![ticket]);
+
+        accept_task.abort();
+        client.close().await;
+        server.close().await;
+        assert_eq!(answer_task.await?, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_model_does_not_retry_peer_errors() -> Result<()> {
+        use crate::request_model;
+
+        let (server, client, mut rx_model_config_req, accept_task) = model_config_server().await?;
+        let answer_task = tokio::spawn(async move {
+            let mut requests = 0;
+            while let Some(ModelConfigSharingMessage::Get(tx)) = rx_model_config_req.recv().await {
+                requests += 1;
+                let _ = tx.send(Err(SharableModelError::TooManyRequests));
+            }
+            requests
+        });
+
+        let result = request_model(
+            &client,
+            server.node_id(),
+            &ModelRequestType::Config,
+            ConnectRetry {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(100),
+            },
+        )
+        .await;
+        assert!(result.is_err());
+
+        accept_task.abort();
+        client.close().await;
+        server.close().await;
+        assert_eq!(answer_task.await?, 1);
+        Ok(())
+    }
+}
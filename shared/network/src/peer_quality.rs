@@ -0,0 +1,136 @@
+use iroh::endpoint::{ConnectionType, RemoteInfo};
+use std::time::Duration;
+
+/// A peer we haven't heard from in this long is probably gone.
+pub const STALE_PEER_AFTER: Duration = Duration::from_secs(2 * 60);
+
+/// Round trip time at which the latency component drops to one half.
+const HALF_SCORE_LATENCY: Duration = Duration::from_millis(100);
+
+/// Bandwidth in bytes/sec at which the bandwidth component reaches one half.
+const HALF_SCORE_BANDWIDTH: f64 = 1024.0 * 1024.0;
+
+/// How a stale peer's score is scaled down.
+const STALE_PENALTY: f64 = 0.05;
+
+/// How good our connection to a peer is, as a 0.0–1.0 `score` for picking between peers,
+/// along with the 0.0–1.0 components it's made of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PeerQuality {
+    pub score: f64,
+    /// direct beats mixed beats relayed, and no connection at all scores 0.
+    pub connection: f64,
+    /// 1 at no latency, falling off as it grows. Unknown latency scores low.
+    pub latency: f64,
+    /// 0 with nothing downloaded from the peer, approaching 1 the faster it's been.
+    pub bandwidth: f64,
+    /// we haven't heard from the peer within [`STALE_PEER_AFTER`], if ever.
+    pub stale: bool,
+}
+
+impl PeerQuality {
+    pub fn new(
+        conn_type: &ConnectionType,
+        latency: Option<Duration>,
+        last_received: Option<Duration>,
+        bytes_per_sec: f64,
+    ) -> Self {
+        let connection = match conn_type {
+            ConnectionType::Direct(_) => 1.0,
+            ConnectionType::Mixed(..) => 0.7,
+            ConnectionType::Relay(_) => 0.4,
+            ConnectionType::None => 0.0,
+        };
+        let latency = match latency {
+            Some(latency) => 1.0 / (1.0 + latency.as_secs_f64() / HALF_SCORE_LATENCY.as_secs_f64()),
+            None => 0.25,
+        };
+        let bytes_per_sec = bytes_per_sec.max(0.0);
+        let bandwidth = bytes_per_sec / (bytes_per_sec + HALF_SCORE_BANDWIDTH);
+        let stale = !last_received.is_some_and(|ago| ago <= STALE_PEER_AFTER);
+
+        let score = 0.5 * connection + 0.3 * latency + 0.2 * bandwidth;
+        Self {
+            score: if stale { score * STALE_PENALTY } else { score },
+            connection,
+            latency,
+            bandwidth,
+            stale,
+        }
+    }
+
+    pub fn from_remote_info(info: &RemoteInfo, bytes_per_sec: f64) -> Self {
+        Self::new(
+            &info.conn_type,
+            info.latency,
+            info.last_received(),
+            bytes_per_sec,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECENTLY: Option<Duration> = Some(Duration::from_secs(1));
+
+    fn direct() -> ConnectionType {
+        ConnectionType::Direct("127.0.0.1:1234".parse().unwrap())
+    }
+
+    fn relay() -> ConnectionType {
+        ConnectionType::Relay("https://relay.example.com".parse().unwrap())
+    }
+
+    #[test]
+    fn test_direct_beats_relay() {
+        let latency = Some(Duration::from_millis(50));
+        let direct = PeerQuality::new(&direct(), latency, RECENTLY, 0.0);
+        let relayed = PeerQuality::new(&relay(), latency, RECENTLY, 0.0);
+        assert!(direct.score > relayed.score);
+        assert_eq!((direct.connection, relayed.connection), (1.0, 0.4));
+        assert_eq!(direct.latency, relayed.latency);
+        assert!(!direct.stale && !relayed.stale);
+
+        // a fast relay still loses to a slow direct connection without bandwidth to show for it
+        let fast_relay = PeerQuality::new(&relay(), Some(Duration::from_millis(10)), RECENTLY, 0.0);
+        let slow_direct =
+            PeerQuality::new(&direct(), Some(Duration::from_millis(300)), RECENTLY, 0.0);
+        assert!(slow_direct.score > fast_relay.score);
+    }
+
+    #[test]
+    fn test_latency_and_bandwidth_raise_the_score() {
+        let slow = PeerQuality::new(&direct(), Some(Duration::from_millis(500)), RECENTLY, 0.0);
+        let fast = PeerQuality::new(&direct(), Some(Duration::from_millis(5)), RECENTLY, 0.0);
+        assert!(fast.score > slow.score);
+        assert_eq!(
+            PeerQuality::new(&direct(), Some(HALF_SCORE_LATENCY), RECENTLY, 0.0).latency,
+            0.5
+        );
+
+        let seeding = PeerQuality::new(&direct(), Some(Duration::from_millis(5)), RECENTLY, 1e7);
+        assert!(seeding.score > fast.score);
+        assert!(seeding.bandwidth > 0.9 && seeding.bandwidth < 1.0);
+        assert!(seeding.score <= 1.0);
+    }
+
+    #[test]
+    fn test_stale_peers_score_near_zero() {
+        let latency = Some(Duration::from_millis(5));
+        for last_received in [None, Some(STALE_PEER_AFTER + Duration::from_secs(1))] {
+            let stale = PeerQuality::new(&direct(), latency, last_received, 1e7);
+            assert!(stale.stale);
+            assert!(stale.score < 0.05, "{stale:?}");
+        }
+        let gone = PeerQuality::new(&ConnectionType::None, None, None, 0.0);
+        assert_eq!(gone.connection, 0.0);
+        assert!(gone.score < 0.01);
+        // even a relayed peer is better than a stale direct one
+        assert!(
+            PeerQuality::new(&relay(), None, RECENTLY, 0.0).score
+                > PeerQuality::new(&direct(), latency, None, 1e7).score
+        );
+    }
+}
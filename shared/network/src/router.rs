@@ -1,15 +1,19 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use iroh_blobs::{net_protocol::Blobs, store::mem::Store};
 use iroh_gossip::net::Gossip;
 use tokio::{sync::Mutex, task::JoinSet};
 use tokio_util::{sync::CancellationToken, task::AbortOnDropHandle};
-use tracing::{error, info_span, trace, warn, Instrument};
+use tracing::{debug, error, info_span, trace, warn, Instrument};
 
 use iroh::{protocol::ProtocolHandler, Endpoint};
 
-use crate::{p2p_model_sharing, Allowlist, ModelSharing};
+use crate::{
+    blob_store::BlobStore,
+    health_probe, p2p_model_sharing,
+    state::{BlobsServed, GossipConnections},
+    Allowlist, ModelSharing,
+};
 
 /// TODO: This entire struct can be replaced with the builtin Router using the new connection
 /// limiting functionality in Iroh:
@@ -75,14 +79,17 @@ impl Router {
     pub async fn spawn<A: Allowlist + 'static + Send>(
         endpoint: Endpoint,
         gossip: Gossip,
-        blobs: Blobs<Store>,
+        blobs: BlobStore,
         p2p_model_sharing: ModelSharing,
         allowlist: A,
+        blobs_served: BlobsServed,
+        gossip_connections: GossipConnections,
     ) -> Result<Self> {
         if let Err(err) = endpoint.set_alpns(vec![
             iroh_blobs::ALPN.to_vec(),
             iroh_gossip::ALPN.to_vec(),
             p2p_model_sharing::ALPN.to_vec(),
+            health_probe::ALPN.to_vec(),
         ]) {
             shutdown(&endpoint, gossip, blobs, p2p_model_sharing).await;
             return Err(err);
@@ -144,8 +151,10 @@ impl Router {
                             let blobs = blobs.clone();
                             let allowlist = allowlist.clone();
                             let p2p_model_sharing = p2p_model_sharing.clone();
+                            let blobs_served = blobs_served.clone();
+                            let gossip_connections = gossip_connections.clone();
                             join_set.spawn(async move {
-                                token.run_until_cancelled(handle_connection(incoming, gossip, blobs, p2p_model_sharing, allowlist)).await
+                                token.run_until_cancelled(handle_connection(incoming, gossip, blobs, p2p_model_sharing, allowlist, blobs_served, gossip_connections)).await
                             }.instrument(info_span!("router.accept")));
                         },
                     }
@@ -173,7 +182,7 @@ impl Router {
 async fn shutdown(
     endpoint: &Endpoint,
     gossip: Gossip,
-    blobs: Blobs<Store>,
+    blobs: BlobStore,
     p2p_model_sharing: ModelSharing,
 ) {
     // We ignore all errors during shutdown.
@@ -182,7 +191,7 @@ async fn shutdown(
         endpoint.close(),
         // Shutdown protocol handlers, using the ProtocolHandler shutdown impl.
         (&gossip as &dyn ProtocolHandler).shutdown(),
-        (&blobs as &dyn ProtocolHandler).shutdown(),
+        blobs.shutdown(),
         (&p2p_model_sharing as &dyn ProtocolHandler).shutdown(),
     );
 }
@@ -190,9 +199,11 @@ async fn shutdown(
 async fn handle_connection<A: Allowlist + 'static + Send>(
     incoming: iroh::endpoint::Incoming,
     gossip: Gossip,
-    blobs: Blobs<Store>,
+    blobs: BlobStore,
     p2p_model_sharing: ModelSharing,
     allowlist: Box<A>,
+    blobs_served: BlobsServed,
+    gossip_connections: GossipConnections,
 ) {
     let mut connecting = match incoming.accept() {
         Ok(conn) => conn,
@@ -227,28 +238,36 @@ async fn handle_connection<A: Allowlist + 'static + Send>(
         }
     };
 
-    if !allowlist.allowed(node_id) {
+    if let Err(rejection) = allowlist.check(node_id) {
         // kill connection completely!
-        connection.close(0u8.into(), b"not in allowlist");
-        warn!(
-            "Killing attemption connection: Node ID {node_id} is not in allowlist {allowlist:#?}."
-        );
+        connection.close(0u8.into(), rejection.reason());
+        warn!("Killing attempted connection from node ID {node_id}: {rejection}. Allowlist: {allowlist:#?}");
         return;
     }
 
     if alpn == iroh_gossip::ALPN {
+        if !gossip_connections.accept(node_id, &connection) {
+            connection.close(0u8.into(), b"recently evicted from gossip neighbors");
+            debug!("Refusing gossip connection from {node_id}, we evicted it recently");
+            return;
+        }
         if let Err(err) = gossip.handle_connection(connection).await {
             warn!("Handling incoming gossip connection ended with error: {err}");
         };
     } else if alpn == iroh_blobs::ALPN {
-        let db = blobs.store().clone();
-        let events = blobs.events().clone();
-        let rt = blobs.rt().clone();
-        iroh_blobs::provider::handle_connection(connection, db, events, rt).await;
+        // the blobs provider reports transfers by the connection's stable id
+        let connection_id = connection.stable_id() as u64;
+        blobs_served.connected(connection_id, node_id);
+        blobs.handle_connection(connection).await;
+        blobs_served.disconnected(connection_id);
     } else if alpn == p2p_model_sharing::ALPN {
         if let Err(err) = p2p_model_sharing.accept_connection(connection).await {
             warn!("Handling incoming p2p model sharing connection ended with error: {err}")
         }
+    } else if alpn == health_probe::ALPN {
+        if let Err(err) = health_probe::answer_probes(connection).await {
+            debug!("Answering health probes ended with error: {err}")
+        }
     } else {
         warn!("Ignoring connection: unsupported ALPN protocol");
         return;
@@ -261,6 +280,7 @@ mod tests {
 
     use futures_util::future::join_all;
     use iroh::SecretKey;
+    use iroh_blobs::net_protocol::Blobs;
     use iroh_gossip::{
         net::{Event, GossipEvent, Message},
         proto::TopicId,
@@ -269,7 +289,8 @@ mod tests {
 
     use crate::{
         allowlist::{AllowAll, AllowDynamic},
-        ModelSharing,
+        util::idle_transport_config,
+        ModelSharing, ParameterServeLimit,
     };
 
     use super::*;
@@ -277,12 +298,16 @@ mod tests {
     #[tokio::test]
     async fn test_shutdown() -> Result<()> {
         let endpoint = Endpoint::builder().bind().await?;
-        let blobs = Blobs::memory().build(&endpoint);
+        let blobs = BlobStore::Memory(Blobs::memory().build(&endpoint));
         let gossip = Gossip::builder().spawn(endpoint.clone()).await?;
         let (tx_model_parameter_req, _rx_model_parameter_req) =
             tokio::sync::mpsc::unbounded_channel();
         let (tx_model_config_req, _rx_model_config_req) = tokio::sync::mpsc::unbounded_channel();
-        let p2p_model_sharing = ModelSharing::new(tx_model_parameter_req, tx_model_config_req);
+        let p2p_model_sharing = ModelSharing::new(
+            tx_model_parameter_req,
+            tx_model_config_req,
+            ParameterServeLimit::Unlimited,
+        );
 
         let router = Router::spawn(
             endpoint.clone(),
@@ -290,6 +315,8 @@ mod tests {
             blobs.clone(),
             p2p_model_sharing.clone(),
             AllowAll,
+            BlobsServed::default(),
+            GossipConnections::default(),
         )
         .await?;
 
@@ -343,14 +370,17 @@ mod tests {
                 .map(|k| async {
                     let allowlist = AllowDynamic::with_nodes(pubkeys.clone());
                     let endpoint = Endpoint::builder().secret_key(k).bind().await?;
-                    let blobs = Blobs::memory().build(&endpoint);
+                    let blobs = BlobStore::Memory(Blobs::memory().build(&endpoint));
                     let gossip = Gossip::builder().spawn(endpoint.clone()).await?;
                     let (tx_model_parameter_req, _rx_model_parameter_req) =
                         tokio::sync::mpsc::unbounded_channel();
                     let (tx_model_config_req, _rx_model_parameter_req) =
                         tokio::sync::mpsc::unbounded_channel();
-                    let p2p_model_sharing =
-                        ModelSharing::new(tx_model_parameter_req, tx_model_config_req);
+                    let p2p_model_sharing = ModelSharing::new(
+                        tx_model_parameter_req,
+                        tx_model_config_req,
+                        ParameterServeLimit::Unlimited,
+                    );
 
                     Ok((
                         gossip.clone(),
@@ -360,6 +390,8 @@ mod tests {
                             blobs.clone(),
                             p2p_model_sharing.clone(),
                             allowlist,
+                            BlobsServed::default(),
+                            GossipConnections::default(),
                         )
                         .await?,
                         endpoint.node_addr().await?,
@@ -453,4 +485,68 @@ mod tests {
 
         Ok(())
     }
+
+    /// Opens two connections between a pair of nodes with an idle timeout,
+    /// keeps sending data over one of them, and checks that only the other one is closed.
+    #[tokio::test]
+    async fn test_idle_connection_closed() -> Result<()> {
+        const TEST_ALPN: &[u8] = b"idle-test/0";
+        const IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+
+        let server = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .transport_config(idle_transport_config(IDLE_TIMEOUT)?)
+            .alpns(vec![TEST_ALPN.to_vec()])
+            .bind()
+            .await?;
+        let client = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .transport_config(idle_transport_config(IDLE_TIMEOUT)?)
+            .bind()
+            .await?;
+
+        // drain everything the client sends us
+        let accept_server = server.clone();
+        let accept_task = tokio::spawn(async move {
+            while let Some(incoming) = accept_server.accept().await {
+                let Ok(connection) = incoming.await else {
+                    continue;
+                };
+                tokio::spawn(async move {
+                    while let Ok(mut recv) = connection.accept_uni().await {
+                        let _ = recv.read_to_end(1024).await;
+                    }
+                });
+            }
+        });
+
+        let server_addr = server.node_addr().await?;
+        let idle = client.connect(server_addr.clone(), TEST_ALPN).await?;
+        let active = client.connect(server_addr, TEST_ALPN).await?;
+
+        let active_sender = active.clone();
+        let send_task = tokio::spawn(async move {
+            while let Ok(mut send) = active_sender.open_uni().await {
+                if send.write_all(b"ping").await.is_err() || send.finish().is_err() {
+                    break;
+                }
+                tokio::time::sleep(IDLE_TIMEOUT / 5).await;
+            }
+        });
+
+        tokio::time::sleep(IDLE_TIMEOUT * 3).await;
+
+        assert!(matches!(
+            idle.close_reason(),
+            Some(iroh::endpoint::ConnectionError::TimedOut)
+        ));
+        assert!(active.close_reason().is_none());
+
+        send_task.abort();
+        accept_task.abort();
+        client.close().await;
+        server.close().await;
+
+        Ok(())
+    }
 }
@@ -7,18 +7,74 @@ use std::{
     fmt,
     io::{BufReader, Read},
     num::TryFromIntError,
+    str::FromStr,
 };
-use tch::Device;
+use tch::{Device, Kind, Tensor};
 use thiserror::Error;
 
-use crate::serializable_tensor::SerializableTensor;
+use crate::{serializable_kind::SerializableKind, serializable_tensor::SerializableTensor};
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+/// The precision used to transmit the sparse values of a DisTrO result.
+/// Lower precisions shrink payloads at the cost of some reconstruction error.
+/// 1-bit quantized results are always sent as-is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SparseValueDtype {
+    /// Send the values in the dtype they were computed in.
+    #[default]
+    Full,
+    Fp16,
+    Bf16,
+    /// Symmetric int8 quantization, with one scale per result.
+    Int8,
+}
+
+impl FromStr for SparseValueDtype {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(Self::Full),
+            "fp16" => Ok(Self::Fp16),
+            "bf16" => Ok(Self::Bf16),
+            "int8" => Ok(Self::Int8),
+            _ => Err(format!(
+                "invalid sparse value dtype \"{s}\", expected one of full, fp16, bf16, int8"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SparseValueDtype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "full"),
+            Self::Fp16 => write!(f, "fp16"),
+            Self::Bf16 => write!(f, "bf16"),
+            Self::Int8 => write!(f, "int8"),
+        }
+    }
+}
+
+/// How `sparse_val` was encoded, so it can be turned back into its original dtype.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SparseValueEncoding {
+    Raw,
+    Cast {
+        original: SerializableKind,
+    },
+    Int8 {
+        scale: f32,
+        original: SerializableKind,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct SerializedDistroResult {
     pub sparse_idx: SerializableTensor,
     pub sparse_val: SerializableTensor,
     pub xshape: Vec<u16>,
     pub totalk: u32,
+    pub sparse_val_encoding: SparseValueEncoding,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -38,6 +94,9 @@ impl TransmittableDistroResult {
         for result in &self.distro_results {
             hasher.update(result.sparse_idx.raw_tensor_data());
             hasher.update(result.sparse_val.raw_tensor_data());
+            if let SparseValueEncoding::Int8 { scale, .. } = result.sparse_val_encoding {
+                hasher.update(scale.to_be_bytes());
+            }
         }
         hasher.finalize().into()
     }
@@ -51,20 +110,76 @@ pub enum SerializeDistroResultError {
     ShapeInt(#[from] TryFromIntError),
 }
 
-impl TryFrom<&DistroResult> for SerializedDistroResult {
-    type Error = SerializeDistroResultError;
-    fn try_from(value: &DistroResult) -> std::result::Result<Self, Self::Error> {
+impl SerializedDistroResult {
+    pub fn encode(
+        value: &DistroResult,
+        sparse_value_dtype: SparseValueDtype,
+    ) -> Result<Self, SerializeDistroResultError> {
+        let original = value.sparse_val.kind();
+        let (sparse_val, sparse_val_encoding) = match sparse_value_dtype {
+            _ if original == Kind::Bool => {
+                (value.sparse_val.shallow_clone(), SparseValueEncoding::Raw)
+            }
+            SparseValueDtype::Full => (value.sparse_val.shallow_clone(), SparseValueEncoding::Raw),
+            SparseValueDtype::Fp16 | SparseValueDtype::Bf16 => {
+                let kind = match sparse_value_dtype {
+                    SparseValueDtype::Fp16 => Kind::Half,
+                    _ => Kind::BFloat16,
+                };
+                (
+                    value.sparse_val.f_to_kind(kind)?,
+                    SparseValueEncoding::Cast {
+                        original: original.into(),
+                    },
+                )
+            }
+            SparseValueDtype::Int8 => {
+                let absmax = value.sparse_val.f_abs()?.f_max()?.double_value(&[]) as f32;
+                let scale = if absmax > 0.0 { absmax / 127.0 } else { 1.0 };
+                let quantized = (value.sparse_val.f_to_kind(Kind::Float)? / scale as f64)
+                    .f_round()?
+                    .f_clamp(-127, 127)?
+                    .f_to_kind(Kind::Int8)?;
+                (
+                    quantized,
+                    SparseValueEncoding::Int8 {
+                        scale,
+                        original: original.into(),
+                    },
+                )
+            }
+        };
         Ok(Self {
             sparse_idx: (&value.sparse_idx).try_into()?,
-            sparse_val: (&value.sparse_val).try_into()?,
+            sparse_val: (&sparse_val).try_into()?,
             xshape: value
                 .xshape
                 .iter()
                 .map(|&x| u16::try_from(x))
                 .collect::<Result<Vec<u16>, _>>()?,
             totalk: value.totalk as u32,
+            sparse_val_encoding,
         })
     }
+
+    /// Decodes `sparse_val` back into the dtype it was computed in.
+    pub fn decode_sparse_val(&self) -> Result<Tensor, tch::TchError> {
+        let sparse_val = Tensor::try_from(&self.sparse_val)?;
+        match &self.sparse_val_encoding {
+            SparseValueEncoding::Raw => Ok(sparse_val),
+            SparseValueEncoding::Cast { original } => sparse_val.f_to_kind(original.into()),
+            SparseValueEncoding::Int8 { scale, original } => {
+                (sparse_val.f_to_kind(Kind::Float)? * *scale as f64).f_to_kind(original.into())
+            }
+        }
+    }
+}
+
+impl TryFrom<&DistroResult> for SerializedDistroResult {
+    type Error = SerializeDistroResultError;
+    fn try_from(value: &DistroResult) -> std::result::Result<Self, Self::Error> {
+        Self::encode(value, SparseValueDtype::Full)
+    }
 }
 
 impl TryFrom<&SerializedDistroResult> for DistroResult {
@@ -73,7 +188,7 @@ impl TryFrom<&SerializedDistroResult> for DistroResult {
     fn try_from(value: &SerializedDistroResult) -> std::result::Result<Self, Self::Error> {
         let mut distro_result = Self {
             sparse_idx: (&value.sparse_idx).try_into()?,
-            sparse_val: (&value.sparse_val).try_into()?,
+            sparse_val: value.decode_sparse_val()?,
             xshape: value.xshape.iter().map(|x| *x as i64).collect(),
             totalk: value.totalk as i64,
             stats: None,
@@ -180,9 +295,10 @@ impl<R: Read> Iterator for DistroResultIterator<R> {
 
 #[cfg(test)]
 mod tests {
-    use psyche_modeling::CompressDCT;
+    use psyche_modeling::{set_torch_rng_seed, CompressDCT, DistroResult};
     use tch::{Device, Kind, Tensor};
 
+    use super::*;
     use crate::serializable_tensor::SerializableTensor;
 
     #[test]
@@ -224,4 +340,96 @@ mod tests {
 
         assert!(decompressed_signed.equal(&signed_truth));
     }
+
+    #[test]
+    fn test_roundtrip_sparse_value_dtypes() {
+        set_torch_rng_seed();
+        let truth = Tensor::randn([64, 64], (Kind::Float, Device::Cpu));
+        let (sparse_idx, sparse_val, xshape, totalk) = CompressDCT::compress(&truth, 16);
+        let distro_result = DistroResult {
+            sparse_idx,
+            sparse_val,
+            xshape,
+            totalk,
+            stats: None,
+        };
+        let absmax = distro_result.sparse_val.abs().max().double_value(&[]);
+
+        for (dtype, max_relative_error) in [
+            (SparseValueDtype::Full, 0.0),
+            (SparseValueDtype::Fp16, 1e-3),
+            (SparseValueDtype::Bf16, 1e-2),
+            (SparseValueDtype::Int8, 1.0 / 127.0),
+        ] {
+            let serialized = SerializedDistroResult::encode(&distro_result, dtype).unwrap();
+
+            // goes over the wire just like the real thing
+            let bytes = distro_results_to_bytes(&[serialized.clone()]).unwrap();
+            let read_back: Vec<_> = distro_results_from_reader(bytes.as_slice())
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(read_back, vec![serialized]);
+
+            let decoded = DistroResult::try_from(&read_back[0]).unwrap();
+            assert_eq!(decoded.sparse_val.kind(), Kind::Float, "{dtype}");
+            assert!(decoded.sparse_idx.equal(&distro_result.sparse_idx));
+
+            let max_error = (&decoded.sparse_val - &distro_result.sparse_val)
+                .abs()
+                .max()
+                .double_value(&[]);
+            assert!(
+                max_error <= absmax * max_relative_error,
+                "{dtype}: max error {max_error} for values up to {absmax}"
+            );
+
+            // and it still decompresses to something readable
+            let decompressed = CompressDCT::decompress(
+                &decoded.sparse_idx,
+                &decoded.sparse_val,
+                &decoded.xshape,
+                decoded.totalk,
+                Kind::Float,
+                Device::Cpu,
+            );
+            assert_eq!(decompressed.size(), truth.size());
+            assert_eq!(decompressed.isfinite().all().int64_value(&[]), 1);
+        }
+    }
+
+    #[test]
+    fn test_sparse_value_dtype_sizes() {
+        let sparse_val = Tensor::randn([1024], (Kind::Float, Device::Cpu));
+        let distro_result = DistroResult {
+            sparse_idx: Tensor::arange(1024, (Kind::Int64, Device::Cpu)),
+            sparse_val,
+            xshape: vec![32, 32],
+            totalk: 1024,
+            stats: None,
+        };
+        let size = |dtype| {
+            SerializedDistroResult::encode(&distro_result, dtype)
+                .unwrap()
+                .sparse_val
+                .raw_tensor_data()
+                .len()
+        };
+        assert_eq!(size(SparseValueDtype::Full), 4096);
+        assert_eq!(size(SparseValueDtype::Fp16), 2048);
+        assert_eq!(size(SparseValueDtype::Bf16), 2048);
+        assert_eq!(size(SparseValueDtype::Int8), 1024);
+    }
+
+    #[test]
+    fn test_parse_sparse_value_dtype() {
+        for dtype in [
+            SparseValueDtype::Full,
+            SparseValueDtype::Fp16,
+            SparseValueDtype::Bf16,
+            SparseValueDtype::Int8,
+        ] {
+            assert_eq!(dtype.to_string().parse::<SparseValueDtype>(), Ok(dtype));
+        }
+        assert!("fp8".parse::<SparseValueDtype>().is_err());
+    }
 }
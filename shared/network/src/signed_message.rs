@@ -1,39 +1,319 @@
-use crate::Networkable;
+use crate::{compression, Networkable};
 
 use anyhow::Result;
 use bytes::Bytes;
 use iroh::{PublicKey, SecretKey};
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
+use thiserror::Error;
+
+/// Prepended to every encoded message, so the signing scheme can change without old nodes
+/// misreading new messages as garbage.
+pub const SIGNED_MESSAGE_VERSION: u8 = 1;
+
+/// The most a compressed payload may expand to. Gossip messages are small, this only stops zlib bombs.
+pub const MAX_DECOMPRESSED_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum SignedMessageError {
+    #[error("Message is empty")]
+    Empty,
+
+    #[error(
+        "Unknown signed message version {0}, we only understand version {SIGNED_MESSAGE_VERSION}"
+    )]
+    UnknownVersion(u8),
+
+    #[error("Failed to decode message: {0}")]
+    Decode(#[from] postcard::Error),
+
+    #[error("Failed to decompress message: {0}")]
+    Decompress(#[from] std::io::Error),
+
+    #[error("Invalid signature: {0}")]
+    Signature(#[from] ed25519::Error),
+}
+
+/// How the signed payload is encoded on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PayloadFormat {
+    Raw,
+    Zlib,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SignedMessage<T: Networkable> {
     from: PublicKey,
+    format: PayloadFormat,
     data: Bytes,
     signature: ed25519::Signature,
     _t: PhantomData<T>,
 }
 
 impl<T: Networkable> SignedMessage<T> {
-    pub fn verify_and_decode(bytes: &[u8]) -> Result<(PublicKey, T)> {
+    pub fn verify_and_decode(bytes: &[u8]) -> Result<(PublicKey, T), SignedMessageError> {
+        let (&version, rest) = bytes.split_first().ok_or(SignedMessageError::Empty)?;
+        match version {
+            SIGNED_MESSAGE_VERSION => Self::verify_and_decode_v1(rest).or_else(|err| {
+                // a v0 message can happen to start with the v1 tag
+                if cfg!(feature = "legacy-unversioned-messages") {
+                    Self::verify_and_decode_v0(bytes).map_err(|_| err)
+                } else {
+                    Err(err)
+                }
+            }),
+            _ if cfg!(feature = "legacy-unversioned-messages") => Self::verify_and_decode_v0(bytes)
+                .map_err(|_| SignedMessageError::UnknownVersion(version)),
+            _ => Err(SignedMessageError::UnknownVersion(version)),
+        }
+    }
+
+    fn verify_and_decode_v1(bytes: &[u8]) -> Result<(PublicKey, T), SignedMessageError> {
         let signed_message: Self = postcard::from_bytes(bytes)?;
-        let key: PublicKey = signed_message.from;
-        key.verify(&signed_message.data, &signed_message.signature)?;
-        let message: T = postcard::from_bytes(&signed_message.data)?;
-        Ok((signed_message.from, message))
+        signed_message.from.verify(
+            &signed_bytes(
+                SIGNED_MESSAGE_VERSION,
+                signed_message.format,
+                &signed_message.data,
+            ),
+            &signed_message.signature,
+        )?;
+        signed_message.decode()
+    }
+
+    /// Messages from nodes that predate the version tag and compression, which signed just the raw payload.
+    fn verify_and_decode_v0(bytes: &[u8]) -> Result<(PublicKey, T), SignedMessageError> {
+        let signed_message: UnversionedSignedMessage = postcard::from_bytes(bytes)?;
+        signed_message
+            .from
+            .verify(&signed_message.data, &signed_message.signature)?;
+        Ok((
+            signed_message.from,
+            postcard::from_bytes(&signed_message.data)?,
+        ))
+    }
+
+    fn decode(self) -> Result<(PublicKey, T), SignedMessageError> {
+        let message: T = match self.format {
+            PayloadFormat::Raw => postcard::from_bytes(&self.data)?,
+            PayloadFormat::Zlib => postcard::from_bytes(&compression::decompress(
+                &self.data,
+                MAX_DECOMPRESSED_MESSAGE_SIZE,
+            )?)?,
+        };
+        Ok((self.from, message))
     }
 
     pub fn sign_and_encode(secret_key: &SecretKey, message: &T) -> Result<Bytes> {
-        let data: Bytes = postcard::to_stdvec(&message)?.into();
-        let signature = secret_key.sign(&data);
+        Self::sign_and_encode_compressed(secret_key, message, None, 0)
+    }
+
+    /// Like [`Self::sign_and_encode`], but zlib-compresses payloads bigger than `compress_above` bytes.
+    /// Payloads that don't get smaller are sent raw.
+    pub fn sign_and_encode_compressed(
+        secret_key: &SecretKey,
+        message: &T,
+        compress_above: Option<usize>,
+        compression_level: u32,
+    ) -> Result<Bytes> {
+        let raw = postcard::to_stdvec(&message)?;
+        let (format, data): (_, Bytes) = match compress_above {
+            Some(threshold) if raw.len() > threshold => {
+                let compressed = compression::compress(&raw, compression_level)?;
+                if compressed.len() < raw.len() {
+                    (PayloadFormat::Zlib, compressed.into())
+                } else {
+                    (PayloadFormat::Raw, raw.into())
+                }
+            }
+            _ => (PayloadFormat::Raw, raw.into()),
+        };
+        let signature = secret_key.sign(&signed_bytes(SIGNED_MESSAGE_VERSION, format, &data));
         let from: PublicKey = secret_key.public();
         let signed_message = Self {
             from,
+            format,
             data,
             signature,
             _t: Default::default(),
         };
-        let encoded = postcard::to_stdvec(&signed_message)?;
+        let encoded = postcard::to_extend(&signed_message, vec![SIGNED_MESSAGE_VERSION])?;
         Ok(encoded.into())
     }
 }
+
+/// The wire layout of a [`SignedMessage`] before it was versioned.
+#[derive(Debug, Serialize, Deserialize)]
+struct UnversionedSignedMessage {
+    from: PublicKey,
+    data: Bytes,
+    signature: ed25519::Signature,
+}
+
+// the version and format are signed along with the payload, so they can't be changed in transit.
+fn signed_bytes(version: u8, format: PayloadFormat, data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len() + 2);
+    bytes.push(version);
+    bytes.push(format as u8);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Broadcast {
+        step: u32,
+        payload: Vec<u8>,
+    }
+
+    fn format(encoded: &[u8]) -> PayloadFormat {
+        postcard::from_bytes::<SignedMessage<Broadcast>>(&encoded[1..])
+            .unwrap()
+            .format
+    }
+
+    fn secret_key() -> SecretKey {
+        SecretKey::from_bytes(&[7; 32])
+    }
+
+    #[test]
+    fn test_large_broadcast_is_compressed_and_decodes() {
+        let message = Broadcast {
+            step: 12,
+            payload: b"committee proof ".repeat(256),
+        };
+        let encoded =
+            SignedMessage::sign_and_encode_compressed(&secret_key(), &message, Some(512), 2)
+                .unwrap();
+        assert_eq!(format(&encoded), PayloadFormat::Zlib);
+        assert!(encoded.len() < message.payload.len() / 4);
+
+        let (from, decoded) = SignedMessage::<Broadcast>::verify_and_decode(&encoded).unwrap();
+        assert_eq!(from, secret_key().public());
+        assert_eq!(decoded, message);
+    }
+
+    #[test]
+    fn test_zlib_bomb_is_rejected() {
+        let message = Broadcast {
+            step: 12,
+            payload: vec![0; MAX_DECOMPRESSED_MESSAGE_SIZE + 1],
+        };
+        let encoded =
+            SignedMessage::sign_and_encode_compressed(&secret_key(), &message, Some(512), 9)
+                .unwrap();
+        assert!(encoded.len() < 64 * 1024);
+        assert!(matches!(
+            SignedMessage::<Broadcast>::verify_and_decode(&encoded),
+            Err(SignedMessageError::Decompress(_))
+        ));
+    }
+
+    #[test]
+    fn test_small_broadcast_stays_raw() {
+        let message = Broadcast {
+            step: 12,
+            payload: vec![1, 2, 3],
+        };
+        let encoded =
+            SignedMessage::sign_and_encode_compressed(&secret_key(), &message, Some(512), 2)
+                .unwrap();
+        assert_eq!(format(&encoded), PayloadFormat::Raw);
+        let (_, decoded) = SignedMessage::<Broadcast>::verify_and_decode(&encoded).unwrap();
+        assert_eq!(decoded, message);
+    }
+
+    fn small_broadcast() -> Broadcast {
+        Broadcast {
+            step: 3,
+            payload: vec![4, 5, 6],
+        }
+    }
+
+    #[test]
+    fn test_message_is_tagged_with_version() {
+        let encoded = SignedMessage::sign_and_encode(&secret_key(), &small_broadcast()).unwrap();
+        assert_eq!(encoded[0], SIGNED_MESSAGE_VERSION);
+        let (from, decoded) = SignedMessage::<Broadcast>::verify_and_decode(&encoded).unwrap();
+        assert_eq!(from, secret_key().public());
+        assert_eq!(decoded, small_broadcast());
+    }
+
+    #[test]
+    fn test_unknown_version_is_rejected() {
+        let mut encoded = SignedMessage::sign_and_encode(&secret_key(), &small_broadcast())
+            .unwrap()
+            .to_vec();
+        encoded[0] = SIGNED_MESSAGE_VERSION + 1;
+        assert!(matches!(
+            SignedMessage::<Broadcast>::verify_and_decode(&encoded),
+            Err(SignedMessageError::UnknownVersion(v)) if v == SIGNED_MESSAGE_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn test_truncated_message_is_rejected() {
+        let encoded = SignedMessage::sign_and_encode(&secret_key(), &small_broadcast()).unwrap();
+        assert!(matches!(
+            SignedMessage::<Broadcast>::verify_and_decode(&[]),
+            Err(SignedMessageError::Empty)
+        ));
+        for len in [1, 10, encoded.len() - 1] {
+            assert!(matches!(
+                SignedMessage::<Broadcast>::verify_and_decode(&encoded[..len]),
+                Err(SignedMessageError::Decode(_))
+            ));
+        }
+    }
+
+    /// How nodes from before versioning and compression encoded messages, kept verbatim.
+    mod baseline {
+        use super::*;
+
+        #[derive(Debug, Serialize, Deserialize)]
+        pub struct SignedMessage<T: Networkable> {
+            from: PublicKey,
+            data: Bytes,
+            signature: ed25519::Signature,
+            _t: PhantomData<T>,
+        }
+
+        impl<T: Networkable> SignedMessage<T> {
+            pub fn sign_and_encode(secret_key: &SecretKey, message: &T) -> Result<Bytes> {
+                let data: Bytes = postcard::to_stdvec(&message)?.into();
+                let signature = secret_key.sign(&data);
+                let from: PublicKey = secret_key.public();
+                let signed_message = Self {
+                    from,
+                    data,
+                    signature,
+                    _t: Default::default(),
+                };
+                let encoded = postcard::to_stdvec(&signed_message)?;
+                Ok(encoded.into())
+            }
+        }
+    }
+
+    #[test]
+    fn test_untagged_message_only_accepted_during_migration() {
+        let untagged =
+            baseline::SignedMessage::sign_and_encode(&secret_key(), &small_broadcast()).unwrap();
+
+        let decoded = SignedMessage::<Broadcast>::verify_and_decode(&untagged);
+        if cfg!(feature = "legacy-unversioned-messages") {
+            let (from, decoded) = decoded.unwrap();
+            assert_eq!(from, secret_key().public());
+            assert_eq!(decoded, small_broadcast());
+        } else {
+            assert!(decoded.is_err());
+        }
+
+        // stripping the tag off a new message doesn't make it a valid old one, the version is signed.
+        let tagged = SignedMessage::sign_and_encode(&secret_key(), &small_broadcast()).unwrap();
+        assert!(SignedMessage::<Broadcast>::verify_and_decode(&tagged[1..]).is_err());
+    }
+}
@@ -1,23 +1,156 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
-use iroh::{endpoint::ConnectionType, NodeId, PublicKey};
+use iroh::{
+    endpoint::{Connection, ConnectionType},
+    NodeId, PublicKey,
+};
+use iroh_blobs::provider::Event as ProviderEvent;
+
+use crate::{
+    diagnostics::RecentDownloadFailures, download_manager::InFlightDownload,
+    gossip::GossipHopStats, peer_list::PeerList,
+};
+
+/// What we last knew about a peer's connection, as of the last stats update.
+#[derive(Debug, Clone)]
+pub struct PeerStatus {
+    pub conn_type: ConnectionType,
+    pub last_seen: Instant,
+    /// The round-trip time iroh measured to the peer.
+    pub latency: Option<Duration>,
+    /// How fast we've been downloading from the peer lately, in bytes/sec.
+    pub bandwidth: f64,
+    /// How likely a download from the peer is to succeed, from 0 to 1. See [`DownloadOutcomes::reputation`].
+    pub reputation: f64,
+    /// How many blobs we've sent the peer.
+    pub blobs_served: u64,
+}
+
+/// How the downloads from one peer turned out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadOutcomes {
+    pub succeeded: u64,
+    pub failed: u64,
+}
+
+impl DownloadOutcomes {
+    /// The share of downloads that succeeded, starting from 0.5 with one imagined success and failure,
+    /// so a single failure doesn't write off a peer we know nothing else about.
+    pub fn reputation(&self) -> f64 {
+        (self.succeeded + 1) as f64 / (self.succeeded + self.failed + 2) as f64
+    }
+}
 
-use crate::{download_manager::DownloadUpdate, peer_list::PeerList};
+/// Counts the blobs we send each peer. The blobs provider only reports connections,
+/// so the router tells it which peer each blobs connection is from.
+#[derive(Debug, Clone, Default)]
+pub struct BlobsServed(Arc<Mutex<BlobsServedInner>>);
+
+#[derive(Debug, Default)]
+struct BlobsServedInner {
+    connections: HashMap<u64, NodeId>,
+    counts: HashMap<NodeId, u64>,
+}
+
+impl BlobsServed {
+    pub(crate) fn connected(&self, connection_id: u64, node_id: NodeId) {
+        let mut inner = self.0.lock().expect("Mutex poisoned");
+        inner.connections.insert(connection_id, node_id);
+    }
+
+    pub(crate) fn disconnected(&self, connection_id: u64) {
+        let mut inner = self.0.lock().expect("Mutex poisoned");
+        inner.connections.remove(&connection_id);
+    }
+
+    pub(crate) fn on_provider_event(&self, event: &ProviderEvent) {
+        if let ProviderEvent::TransferBlobCompleted { connection_id, .. } = event {
+            let mut inner = self.0.lock().expect("Mutex poisoned");
+            if let Some(node_id) = inner.connections.get(connection_id).copied() {
+                *inner.counts.entry(node_id).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn count(&self, node_id: &NodeId) -> u64 {
+        let inner = self.0.lock().expect("Mutex poisoned");
+        inner.counts.get(node_id).copied().unwrap_or_default()
+    }
+}
+
+/// How long a gossip neighbor we evicted has to wait before it can connect to us again,
+/// so it doesn't just rejoin right away.
+pub const GOSSIP_EVICTION_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// The gossip connections peers opened to us, so we can evict a neighbor by closing its connection.
+/// Gossip doesn't hand out the connections it dials itself, so only neighbors that dialed us can be evicted.
+#[derive(Debug, Clone, Default)]
+pub struct GossipConnections(Arc<Mutex<GossipConnectionsInner>>);
+
+#[derive(Debug, Default)]
+struct GossipConnectionsInner {
+    connections: HashMap<NodeId, Connection>,
+    evicted: HashMap<NodeId, Instant>,
+}
+
+impl GossipConnections {
+    /// Whether to accept a gossip connection from `node_id`, which we don't if we evicted it recently.
+    pub(crate) fn accept(&self, node_id: NodeId, connection: &Connection) -> bool {
+        let mut inner = self.0.lock().expect("Mutex poisoned");
+        if let Some(evicted_at) = inner.evicted.get(&node_id) {
+            if evicted_at.elapsed() < GOSSIP_EVICTION_COOLDOWN {
+                return false;
+            }
+            inner.evicted.remove(&node_id);
+        }
+        inner.connections.insert(node_id, connection.clone());
+        true
+    }
+
+    /// The peers with an open gossip connection to us.
+    pub(crate) fn evictable(&self) -> HashSet<NodeId> {
+        let mut inner = self.0.lock().expect("Mutex poisoned");
+        inner
+            .connections
+            .retain(|_, connection| connection.close_reason().is_none());
+        inner.connections.keys().copied().collect()
+    }
+
+    /// Closes `node_id`'s gossip connection, which drops it as a neighbor on every gossip topic.
+    pub(crate) fn evict(&self, node_id: NodeId) {
+        let mut inner = self.0.lock().expect("Mutex poisoned");
+        if let Some(connection) = inner.connections.remove(&node_id) {
+            connection.close(0u8.into(), b"evicted from gossip neighbors");
+        }
+        inner
+            .evicted
+            .retain(|_, evicted_at| evicted_at.elapsed() < GOSSIP_EVICTION_COOLDOWN);
+        inner.evicted.insert(node_id, Instant::now());
+    }
+}
 
 #[derive(Debug)]
 pub struct State {
     pub join_ticket: PeerList,
-    pub last_seen: HashMap<PublicKey, (ConnectionType, Instant)>,
+    pub last_seen: HashMap<PublicKey, PeerStatus>,
     pub bandwidth_tracker: BandwidthTracker,
     pub bandwidth_history: VecDeque<f64>,
-    pub download_progesses: HashMap<iroh_blobs::Hash, DownloadUpdate>,
+    pub download_progesses: HashMap<iroh_blobs::Hash, InFlightDownload>,
+    pub recent_download_failures: RecentDownloadFailures,
+    pub gossip_hops: GossipHopStats,
+    pub download_outcomes: HashMap<NodeId, DownloadOutcomes>,
+    pub blobs_served: BlobsServed,
+    pub gossip_connections: GossipConnections,
 
-    pub currently_sharing_blobs: HashSet<iroh_blobs::Hash>,
-    pub blob_tags: HashSet<(u32, iroh_blobs::Hash)>,
+    /// Blobs we're downloading under one of our tags, and whether that tag was retired while they were in flight.
+    pub downloading_blobs: HashMap<(u32, iroh_blobs::Hash), bool>,
+    /// The blobs in our store under one of our tags, as of the last stats update.
+    pub tagged_blobs: HashSet<iroh_blobs::Hash>,
 }
 
 impl State {
@@ -28,8 +161,13 @@ impl State {
             bandwidth_tracker: BandwidthTracker::new(bandwidth_average_period),
             bandwidth_history: Default::default(),
             download_progesses: Default::default(),
-            currently_sharing_blobs: Default::default(),
-            blob_tags: Default::default(),
+            recent_download_failures: Default::default(),
+            gossip_hops: Default::default(),
+            download_outcomes: Default::default(),
+            blobs_served: Default::default(),
+            gossip_connections: Default::default(),
+            downloading_blobs: Default::default(),
+            tagged_blobs: Default::default(),
         }
     }
 }
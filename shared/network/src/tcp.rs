@@ -1,9 +1,11 @@
-use crate::{AuthenticatableIdentity, Networkable};
+use crate::{
+    frame_codec::{ClientToServerMessage, FramedConnection, ServerToClientMessage},
+    AuthenticatableIdentity, Networkable,
+};
 
 use anyhow::{anyhow, bail};
-use futures_util::{SinkExt, StreamExt};
+use futures_util::StreamExt;
 use rand::RngCore;
-use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fmt::Debug, io, marker::PhantomData, net::SocketAddr, sync::Arc};
 use thiserror::Error;
 use tokio::{
@@ -14,23 +16,9 @@ use tokio::{
         Mutex,
     },
 };
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio_tungstenite::MaybeTlsStream;
 use tracing::{debug, error, info};
 
-const MAX_FRAME_LENGTH: usize = 64 * 1024 * 1024;
-
-#[derive(Serialize, Deserialize, Debug)]
-enum ServerToClientMessage<T: Debug> {
-    Challenge([u8; 32]),
-    Else(T),
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-enum ClientToServerMessage<T: Debug> {
-    ChallengeResponse(Vec<u8>),
-    Else(T),
-}
-
 pub enum ClientNotification<T: Debug, U: Debug> {
     Message(T),
     Disconnected(U),
@@ -46,8 +34,10 @@ where
     _phantom: PhantomData<ToServerMessage>,
 
     incoming_msg_stream: tokio_stream::wrappers::UnboundedReceiverStream<(I, ToServerMessage)>,
+    incoming_tx: mpsc::UnboundedSender<(I, ToServerMessage)>,
     send_msg: mpsc::UnboundedSender<(I, ToClientMessage)>,
     local_addr: SocketAddr,
+    disconnected_tx: mpsc::UnboundedSender<I>,
     disconnected_rx: mpsc::UnboundedReceiver<I>,
 }
 
@@ -78,15 +68,21 @@ where
 
         tokio::spawn({
             let clients = clients.clone();
+            let incoming_tx = incoming_tx.clone();
+            let disconnected_tx = disconnected_tx.clone();
             async move {
                 while let Ok((stream, _)) = listener.accept().await {
                     let clients = clients.clone();
                     let incoming_tx = incoming_tx.clone();
                     let disconnected_tx = disconnected_tx.clone();
                     tokio::spawn(async move {
-                        if let Err(e) =
-                            Self::handle_connection(stream, clients, incoming_tx, disconnected_tx)
-                                .await
+                        if let Err(e) = Self::handle_connection(
+                            FramedConnection::tcp(stream),
+                            clients,
+                            incoming_tx,
+                            disconnected_tx,
+                        )
+                        .await
                         {
                             error!("Error handling connection: {:?}", e);
                         }
@@ -112,8 +108,10 @@ where
             _phantom: Default::default(),
             clients,
             incoming_msg_stream: tokio_stream::wrappers::UnboundedReceiverStream::new(incoming_rx),
+            incoming_tx,
             send_msg,
             local_addr,
+            disconnected_tx,
             disconnected_rx,
         })
     }
@@ -122,23 +120,55 @@ where
         &self.local_addr
     }
 
+    /// Also accepts clients over WebSocket on `addr`, e.g. from a browser that can't open raw TCP.
+    /// They go through the same challenge and show up alongside the TCP clients.
+    /// Returns the address it's listening on.
+    pub async fn listen_websocket(&self, addr: SocketAddr) -> Result<SocketAddr, ConnectError> {
+        let listener = TcpListener::bind(addr).await.map_err(ConnectError::Bind)?;
+        let local_addr = listener.local_addr().map_err(ConnectError::GetLocalAddr)?;
+        info!("Server listening for WebSocket clients on: {}", local_addr);
+
+        let clients = self.clients.clone();
+        let incoming_tx = self.incoming_tx.clone();
+        let disconnected_tx = self.disconnected_tx.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let clients = clients.clone();
+                let incoming_tx = incoming_tx.clone();
+                let disconnected_tx = disconnected_tx.clone();
+                tokio::spawn(async move {
+                    let result = async {
+                        let stream =
+                            tokio_tungstenite::accept_async(MaybeTlsStream::Plain(stream)).await?;
+                        Self::handle_connection(
+                            FramedConnection::websocket(stream),
+                            clients,
+                            incoming_tx,
+                            disconnected_tx,
+                        )
+                        .await
+                    }
+                    .await;
+                    if let Err(e) = result {
+                        error!("Error handling WebSocket connection: {:?}", e);
+                    }
+                });
+            }
+        });
+        Ok(local_addr)
+    }
+
     async fn handle_connection(
-        stream: TcpStream,
+        mut framed: FramedConnection,
         clients: Arc<Mutex<HashMap<I, mpsc::UnboundedSender<ToClient>>>>,
         incoming_tx: mpsc::UnboundedSender<(I, ToServer)>,
         disconnected_tx: mpsc::UnboundedSender<I>,
     ) -> anyhow::Result<()> {
-        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
-
         // Generate and send challenge
         let mut challenge = [0u8; 32];
         rand::thread_rng().fill_bytes(&mut challenge);
         framed
-            .send(
-                ServerToClientMessage::<ToClient>::Challenge(challenge)
-                    .to_bytes()
-                    .into(),
-            )
+            .send(ServerToClientMessage::<ToClient>::Challenge(challenge).to_bytes())
             .await?;
         debug!("New client joined - sent challenge {:?}", challenge);
 
@@ -166,7 +196,7 @@ where
         loop {
             tokio::select! {
                 Some(message) = client_rx.recv() => {
-                    framed.send(ServerToClientMessage::Else(message).to_bytes().into()).await?;
+                    framed.send(ServerToClientMessage::Else(message).to_bytes()).await?;
                 }
                 result = framed.next() => match result {
                     Some(Ok(bytes)) => {
@@ -237,7 +267,7 @@ where
     ToClientMessage: Networkable + Debug + Send + Sync + 'static,
 {
     identity: I,
-    framed: Framed<TcpStream, LengthDelimitedCodec>,
+    framed: FramedConnection,
     _phantom: PhantomData<(ToServerMessage, ToClientMessage)>,
 }
 
@@ -254,11 +284,25 @@ where
     ) -> anyhow::Result<Self> {
         let stream = TcpStream::connect(addr).await?;
         info!("Connected to server at: {}", addr);
+        Self::authenticate(FramedConnection::tcp(stream), identity, private_key).await
+    }
 
-        let mut codec = LengthDelimitedCodec::new();
-        codec.set_max_frame_length(MAX_FRAME_LENGTH);
-        let mut framed = Framed::new(stream, codec);
+    /// Like [`Self::connect`], but to a server's [`TcpServer::listen_websocket`] at a `ws://` url.
+    pub async fn connect_websocket(
+        url: &str,
+        identity: I,
+        private_key: I::PrivateKey,
+    ) -> anyhow::Result<Self> {
+        let (stream, _) = tokio_tungstenite::connect_async(url).await?;
+        info!("Connected to server over WebSocket at: {}", url);
+        Self::authenticate(FramedConnection::websocket(stream), identity, private_key).await
+    }
 
+    async fn authenticate(
+        mut framed: FramedConnection,
+        identity: I,
+        private_key: I::PrivateKey,
+    ) -> anyhow::Result<Self> {
         // Receive challenge
         let challenge = match Self::receive_message(&mut framed).await? {
             ServerToClientMessage::Challenge(c) => c,
@@ -268,11 +312,7 @@ where
         // Sign and send challenge response
         let response = identity.to_signed_challenge_bytes(&private_key, challenge);
         framed
-            .send(
-                ClientToServerMessage::<ToServer>::ChallengeResponse(response)
-                    .to_bytes()
-                    .into(),
-            )
+            .send(ClientToServerMessage::<ToServer>::ChallengeResponse(response).to_bytes())
             .await?;
 
         Ok(Self {
@@ -283,7 +323,7 @@ where
     }
 
     async fn receive_message(
-        framed: &mut Framed<TcpStream, LengthDelimitedCodec>,
+        framed: &mut FramedConnection,
     ) -> anyhow::Result<ServerToClientMessage<ToClient>> {
         let bytes = framed
             .next()
@@ -295,7 +335,7 @@ where
     pub async fn send(&mut self, message: ToServer) -> anyhow::Result<()> {
         Ok(self
             .framed
-            .send(ClientToServerMessage::Else(message).to_bytes().into())
+            .send(ClientToServerMessage::Else(message).to_bytes())
             .await?)
     }
 
@@ -316,3 +356,100 @@ where
         &self.identity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{frame_codec::WS_CHUNK_SIZE, FromSignedBytesError};
+    use psyche_core::BatchId;
+    use std::fmt::Display;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct TestIdentity([u8; 32]);
+
+    impl Display for TestIdentity {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}", self.0[0])
+        }
+    }
+
+    impl AuthenticatableIdentity for TestIdentity {
+        type PrivateKey = ();
+
+        fn from_signed_challenge_bytes(
+            bytes: &[u8],
+            challenge: [u8; 32],
+        ) -> Result<Self, FromSignedBytesError> {
+            let (signed_challenge, identity) = bytes.split_at(32);
+            if signed_challenge != challenge {
+                return Err(FromSignedBytesError::MismatchedChallenge(
+                    challenge,
+                    signed_challenge.into(),
+                ));
+            }
+            Ok(Self(
+                identity
+                    .try_into()
+                    .map_err(|_| FromSignedBytesError::Deserialize)?,
+            ))
+        }
+
+        fn to_signed_challenge_bytes(&self, _private_key: &(), challenge: [u8; 32]) -> Vec<u8> {
+            [challenge, self.0].concat()
+        }
+
+        fn get_p2p_public_key(&self) -> &[u8; 32] {
+            &self.0
+        }
+
+        fn raw_p2p_sign(&self, _private_key: &(), _bytes: &[u8]) -> [u8; 64] {
+            unimplemented!()
+        }
+    }
+
+    type BatchServer = TcpServer<TestIdentity, BatchId, Vec<Vec<i32>>>;
+    type BatchClient = TcpClient<TestIdentity, BatchId, Vec<Vec<i32>>>;
+
+    // big enough that every response spans several WebSocket messages
+    fn samples(data_ids: BatchId) -> Vec<Vec<i32>> {
+        data_ids
+            .iter()
+            .map(|id| vec![id as i32; WS_CHUNK_SIZE / 4])
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_websocket_and_tcp_clients_share_a_server() {
+        let mut server = BatchServer::start("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let ws_addr = server
+            .listen_websocket("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let tcp_addr = *server.local_addr();
+        tokio::spawn(async move {
+            while let Some(event) = server.next().await {
+                if let ClientNotification::Message((from, data_ids)) = event {
+                    server.send_to(from, samples(data_ids)).await.unwrap();
+                }
+            }
+        });
+
+        let mut tcp_client = BatchClient::connect(&tcp_addr.to_string(), TestIdentity([1; 32]), ())
+            .await
+            .unwrap();
+        let mut ws_client =
+            BatchClient::connect_websocket(&format!("ws://{ws_addr}"), TestIdentity([2; 32]), ())
+                .await
+                .unwrap();
+
+        for start in [0, 3] {
+            let data_ids = BatchId((start, start + 2).into());
+            ws_client.send(data_ids).await.unwrap();
+            tcp_client.send(data_ids).await.unwrap();
+            assert_eq!(ws_client.receive().await.unwrap(), samples(data_ids));
+            assert_eq!(tcp_client.receive().await.unwrap(), samples(data_ids));
+        }
+    }
+}
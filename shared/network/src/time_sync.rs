@@ -0,0 +1,79 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Our clock, in microseconds since the unix epoch.
+pub fn unix_micros_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_micros() as i64)
+        .unwrap_or_default()
+}
+
+/// The four timestamps of one request/response exchange with a peer, as in NTP.
+/// `sent` and `received` are read from our clock, the other two from the peer's.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSyncExchange {
+    pub sent: i64,
+    pub peer_received: i64,
+    pub peer_sent: i64,
+    pub received: i64,
+}
+
+impl TimeSyncExchange {
+    /// How far ahead of ours the peer's clock is, in seconds. Negative if it's behind.
+    /// Assumes the request and the response took as long as each other,
+    /// so it can be off by up to half the round trip if they didn't.
+    pub fn clock_offset_secs(&self) -> f64 {
+        ((self.peer_received - self.sent) + (self.peer_sent - self.received)) as f64 / 2e6
+    }
+
+    /// Time spent on the network, leaving out however long the peer took to answer.
+    pub fn round_trip(&self) -> Duration {
+        let round_trip = (self.received - self.sent) - (self.peer_sent - self.peer_received);
+        Duration::from_micros(round_trip.max(0) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: i64 = 1_000;
+    const SEC: i64 = 1_000_000;
+
+    #[test]
+    fn test_offset_from_skewed_exchange() {
+        // the peer's clock is 5s ahead, 40ms each way on the network, and it takes 10ms to answer.
+        let skew = 5 * SEC;
+        let sent = 1_700_000_000 * SEC;
+        let peer_received = sent + 40 * MS + skew;
+        let peer_sent = peer_received + 10 * MS;
+        let received = sent + 90 * MS;
+        let exchange = TimeSyncExchange {
+            sent,
+            peer_received,
+            peer_sent,
+            received,
+        };
+        assert_eq!(exchange.clock_offset_secs(), 5.0);
+        assert_eq!(exchange.round_trip(), Duration::from_millis(80));
+
+        // a clock that's behind gives a negative offset
+        let exchange = TimeSyncExchange {
+            peer_received: peer_received - 2 * skew,
+            peer_sent: peer_sent - 2 * skew,
+            ..exchange
+        };
+        assert_eq!(exchange.clock_offset_secs(), -5.0);
+        assert_eq!(exchange.round_trip(), Duration::from_millis(80));
+
+        // with a lopsided network, the error is at most half the round trip
+        let exchange = TimeSyncExchange {
+            sent,
+            peer_received: sent + 70 * MS + skew,
+            peer_sent: sent + 80 * MS + skew,
+            received: sent + 90 * MS,
+        };
+        assert_eq!(exchange.round_trip(), Duration::from_millis(80));
+        assert!((exchange.clock_offset_secs() - 5.0).abs() <= 0.04);
+    }
+}
@@ -1,28 +1,143 @@
-use crate::{peer_list::PeerList, util::fmt_bytes, NetworkConnection, Networkable};
+use crate::{
+    peer_list::PeerList, util::fmt_bytes, GossipHopStats, NetworkConnection, Networkable,
+    PeerStatus,
+};
 
 use iroh::{endpoint::ConnectionType, PublicKey};
-use psyche_tui::ratatui::{
-    buffer::Buffer,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style, Stylize},
-    symbols,
-    widgets::{
-        Axis, Block, Borders, Chart, Dataset, GraphType, List, ListItem, Padding, Paragraph,
-        Widget, Wrap,
+use psyche_tui::{
+    crossterm::event::{Event, KeyCode, MouseEventKind},
+    ratatui::{
+        buffer::Buffer,
+        layout::{Constraint, Direction, Layout, Rect},
+        style::{Color, Modifier, Style, Stylize},
+        symbols,
+        widgets::{
+            Axis, Block, Borders, Cell, Chart, Dataset, GraphType, List, ListItem, Padding,
+            Paragraph, Row, Table, Widget, Wrap,
+        },
     },
 };
 use std::{
     collections::{HashMap, VecDeque},
-    ops::Sub,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 #[derive(Default, Debug)]
-pub struct NetworkTui;
+pub struct NetworkTui {
+    /// how many peers are scrolled past at the top of the peers panel
+    peers_scroll: usize,
+}
+
+impl NetworkTui {
+    fn render_peers(
+        &mut self,
+        area: Rect,
+        buf: &mut Buffer,
+        peers: &[(PublicKey, PeerStatus)],
+        gossip_hops: &GossipHopStats,
+        now: Instant,
+    ) {
+        // borders and the header take up three rows
+        let visible_rows = area.height.saturating_sub(3) as usize;
+        self.peers_scroll = self
+            .peers_scroll
+            .min(peers.len().saturating_sub(visible_rows));
+        let mut title = if peers.len() > visible_rows {
+            format!(
+                "Peers ({}-{} of {}, scroll with ↑/↓)",
+                self.peers_scroll + 1,
+                (self.peers_scroll + visible_rows).min(peers.len()),
+                peers.len()
+            )
+        } else {
+            format!("Peers ({})", peers.len())
+        };
+        if let Some(mean_hops) = gossip_hops.mean_hops() {
+            title.push_str(&format!(
+                ", gossip hops: {mean_hops:.1} avg, {} max",
+                gossip_hops.max_hops
+            ));
+        }
+
+        let rows =
+            peers
+                .iter()
+                .skip(self.peers_scroll)
+                .take(visible_rows)
+                .map(|(peer_id, status)| {
+                    let last_seen = now
+                        .saturating_duration_since(status.last_seen)
+                        .as_secs_f64();
+                    let (conn_type, color) = match status.conn_type {
+                        ConnectionType::Direct(_) => ("direct", Color::Green),
+                        // relayed traffic means hole punching to this peer failed
+                        ConnectionType::Relay(_) => ("relay", Color::Red),
+                        ConnectionType::Mixed(..) => ("mixed", Color::Yellow),
+                        ConnectionType::None => ("none", Color::DarkGray),
+                    };
+                    Row::new([
+                        Cell::from(peer_id.fmt_short().to_string()),
+                        Cell::from(conn_type.fg(color)),
+                        Cell::from(format!("{last_seen:.1}s ago")),
+                        Cell::from(
+                            status
+                                .latency
+                                .map(|latency| format!("{}ms", latency.as_millis()))
+                                .unwrap_or_else(|| "-".to_string()),
+                        ),
+                        Cell::from(format!("{}/s", fmt_bytes(status.bandwidth.round()))),
+                        Cell::from(format!("{:.2}", status.reputation)),
+                        Cell::from(status.blobs_served.to_string()),
+                    ])
+                });
+        Table::new(
+            rows,
+            [
+                Constraint::Length(12),
+                Constraint::Length(8),
+                Constraint::Length(12),
+                Constraint::Length(8),
+                Constraint::Length(12),
+                Constraint::Length(6),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new([
+                "node",
+                "type",
+                "last seen",
+                "latency",
+                "download",
+                "rep",
+                "served",
+            ])
+            .bold(),
+        )
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .render(area, buf);
+    }
+}
 
 impl psyche_tui::CustomWidget for NetworkTui {
     type Data = NetworkTUIState;
 
+    fn on_ui_event(&mut self, event: &Event) {
+        let scroll_up = match event {
+            Event::Key(key) if key.code == KeyCode::Up => true,
+            Event::Key(key) if key.code == KeyCode::Down => false,
+            Event::Mouse(mouse) if mouse.kind == MouseEventKind::ScrollUp => true,
+            Event::Mouse(mouse) if mouse.kind == MouseEventKind::ScrollDown => false,
+            _ => return,
+        };
+        // clamped to the number of peers when rendering
+        self.peers_scroll = if scroll_up {
+            self.peers_scroll.saturating_sub(1)
+        } else {
+            self.peers_scroll.saturating_add(1)
+        };
+    }
+
     fn render(&mut self, area: Rect, buf: &mut Buffer, state: &Self::Data) {
         if let Some(state) = &state.inner {
             let chunks = Layout::default()
@@ -52,28 +167,13 @@ impl psyche_tui::CustomWidget for NetworkTui {
                     )
                     .render(chunks[0], buf);
 
-                List::new(state.last_seen.iter().map(
-                    |(peer_id, (peer_connection_method, last_seen_instant))| {
-                        let last_seen_time = Instant::now().sub(*last_seen_instant).as_secs_f64();
-                        let li = ListItem::new(format!(
-                            "{} ({}): {:.2} seconds ago",
-                            peer_id.fmt_short(),
-                            peer_connection_method,
-                            last_seen_time
-                        ));
-                        if last_seen_time < 1.0 {
-                            li.bg(Color::LightYellow).fg(Color::Black)
-                        } else {
-                            li
-                        }
-                    },
-                ))
-                .block(
-                    Block::default()
-                        .title("Recently Seen Peers")
-                        .borders(Borders::ALL),
-                )
-                .render(chunks[1], buf);
+                self.render_peers(
+                    chunks[1],
+                    buf,
+                    &state.peers,
+                    &state.gossip_hops,
+                    Instant::now(),
+                );
             }
 
             // Upload & Download
@@ -93,11 +193,17 @@ impl psyche_tui::CustomWidget for NetworkTui {
 
                     List::new(state.downloads.iter().map(|(hash, download)| {
                         let percent = 100.0 * (download.downloaded as f64 / download.total as f64);
+                        let eta = match download.eta {
+                            Some(eta) => format!("{}s", eta.as_secs()),
+                            None => "?".to_string(),
+                        };
                         ListItem::new(format!(
-                            "[{:02.1}%] {}/{}: {}",
+                            "[{:02.1}%] {}/{} at {}/s, ETA {}: {}",
                             percent,
                             fmt_bytes(download.downloaded as f64),
                             fmt_bytes(download.total as f64),
+                            fmt_bytes(download.bytes_per_sec),
+                            eta,
                             hash,
                         ))
                     }))
@@ -190,12 +296,16 @@ impl psyche_tui::CustomWidget for NetworkTui {
 pub struct UIDownloadProgress {
     downloaded: u64,
     total: u64,
+    bytes_per_sec: f64,
+    eta: Option<Duration>,
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct NetworkTUIStateInner {
     pub join_ticket: PeerList,
-    pub last_seen: HashMap<PublicKey, (ConnectionType, Instant)>,
+    /// peers we've heard from recently, sorted by id so they don't jump around between renders
+    pub peers: Vec<(PublicKey, PeerStatus)>,
+    pub gossip_hops: GossipHopStats,
     // pub data_per_sec_per_client: HashMap<PublicKey, f64>,
     pub total_data_per_sec: f64,
     pub download_bandwidth_history: VecDeque<f64>,
@@ -220,28 +330,135 @@ where
         Self {
             inner: Some(NetworkTUIStateInner {
                 join_ticket: s.join_ticket.clone(),
-                last_seen: s.last_seen.clone(),
+                peers: nc.peer_statuses(),
+                gossip_hops: s.gossip_hops,
                 total_data_per_sec: s.bandwidth_tracker.get_total_bandwidth(),
                 download_bandwidth_history: s.bandwidth_history.clone(),
-                downloads: s
-                    .download_progesses
-                    .iter()
-                    .map(|(key, dl)| {
+                downloads: nc
+                    .download_progress()
+                    .into_iter()
+                    .map(|dl| {
                         (
-                            key.to_string(),
+                            dl.hash.to_string(),
                             UIDownloadProgress {
-                                downloaded: dl.downloaded_size,
-                                total: dl.total_size,
+                                downloaded: dl.downloaded,
+                                total: dl.total,
+                                bytes_per_sec: dl.bytes_per_sec,
+                                eta: dl.eta,
                             },
                         )
                     })
                     .collect(),
-                blob_hashes: s
-                    .currently_sharing_blobs
-                    .iter()
-                    .map(|blob| blob.to_string())
-                    .collect(),
+                blob_hashes: s.tagged_blobs.iter().map(|blob| blob.to_string()).collect(),
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn peer(seed: u8) -> PublicKey {
+        SecretKey::from_bytes(&[seed; 32]).public()
+    }
+
+    fn line(buf: &Buffer, y: u16) -> String {
+        (0..buf.area.width).map(|x| buf[(x, y)].symbol()).collect()
+    }
+
+    /// The column `needle` starts at, counting the multi-byte border as one.
+    fn column(line: &str, needle: &str) -> u16 {
+        line[..line.find(needle).unwrap()].chars().count() as u16
+    }
+
+    #[test]
+    fn test_peers_panel() {
+        let now = Instant::now();
+        let direct = "127.0.0.1:4000".parse().unwrap();
+        let relay: iroh::RelayUrl = "https://relay.example.com".parse().unwrap();
+        let peers = vec![
+            (
+                peer(1),
+                PeerStatus {
+                    conn_type: ConnectionType::Direct(direct),
+                    last_seen: now - Duration::from_millis(1500),
+                    latency: Some(Duration::from_millis(42)),
+                    bandwidth: 2048.0,
+                    reputation: 0.75,
+                    blobs_served: 12,
+                },
+            ),
+            (
+                peer(2),
+                PeerStatus {
+                    conn_type: ConnectionType::Relay(relay.clone()),
+                    last_seen: now - Duration::from_secs(7),
+                    latency: None,
+                    bandwidth: 0.0,
+                    reputation: 0.5,
+                    blobs_served: 0,
+                },
+            ),
+            (
+                peer(3),
+                PeerStatus {
+                    conn_type: ConnectionType::Mixed(direct, relay),
+                    last_seen: now,
+                    latency: Some(Duration::from_millis(130)),
+                    bandwidth: 0.0,
+                    reputation: 0.5,
+                    blobs_served: 0,
+                },
+            ),
+        ];
+
+        // room for the header and two peers
+        let area = Rect::new(0, 0, 90, 5);
+        let mut gossip_hops = GossipHopStats::default();
+        let mut tui = NetworkTui::default();
+        let mut buf = Buffer::empty(area);
+        tui.render_peers(area, &mut buf, &peers, &gossip_hops, now);
+
+        assert!(line(&buf, 0).contains("Peers (1-2 of 3"));
+        assert!(!line(&buf, 0).contains("gossip hops"));
+        assert!(line(&buf, 1).contains("node"));
+        assert!(line(&buf, 1).contains("latency"));
+        let first = line(&buf, 2);
+        assert!(first.contains(&peer(1).fmt_short().to_string()));
+        assert!(first.contains("direct"));
+        assert!(first.contains("1.5s ago"));
+        assert!(first.contains("42ms"));
+        assert!(first.contains("2.00 KB/s"));
+        assert!(first.contains("0.75"));
+        assert!(first.contains("12"));
+        let second = line(&buf, 3);
+        assert!(second.contains(&peer(2).fmt_short().to_string()));
+        assert!(second.contains("relay"));
+        assert!(second.contains("7.0s ago"));
+        assert!(second.contains(" - "));
+
+        // relayed peers stand out from direct ones
+        let type_column = column(&first, "direct");
+        assert_eq!(buf[(type_column, 2)].fg, Color::Green);
+        assert_eq!(buf[(type_column, 3)].fg, Color::Red);
+
+        // scrolling past the end stops at the last peer
+        for _ in 0..5 {
+            tui.on_ui_event(&Event::Key(KeyCode::Down.into()));
+        }
+        for hops in [1, 2] {
+            gossip_hops.record(hops);
+        }
+        let mut buf = Buffer::empty(area);
+        tui.render_peers(area, &mut buf, &peers, &gossip_hops, now);
+        assert!(line(&buf, 0).contains("Peers (2-3 of 3"));
+        assert!(line(&buf, 0).contains("gossip hops: 1.5 avg, 2 max"));
+        assert!(line(&buf, 2).contains(&peer(2).fmt_short().to_string()));
+        let third = line(&buf, 3);
+        assert!(third.contains(&peer(3).fmt_short().to_string()));
+        assert!(third.contains("mixed"));
+        assert_eq!(buf[(type_column, 3)].fg, Color::Yellow);
+    }
+}
@@ -0,0 +1,258 @@
+use futures_util::future::BoxFuture;
+use iroh_blobs::{
+    provider::{CustomEventSender, Event},
+    Hash,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Nonzero caps below this are raised to it, so a transfer always makes some progress.
+pub const MIN_UPLOAD_RATE_LIMIT: u64 = 1024;
+
+/// iroh-blobs reads and sends blobs in chunk groups of this size,
+/// so the bucket holds at least one of them.
+const MIN_BURST_BYTES: u64 = 16 * 1024;
+
+/// A token bucket shared by every blob transfer we serve, keeping their combined upload under a byte rate.
+///
+/// Taking more bytes than are in the bucket always succeeds and leaves it in debt,
+/// which the caller then waits out, so no chunk is ever too big to send.
+#[derive(Debug, Clone)]
+pub struct UploadRateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// bytes/sec and capacity, or `None` while uploads are unlimited.
+    rate: Option<(f64, f64)>,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl UploadRateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let limiter = Self::unlimited();
+        limiter.set_rate(Some(bytes_per_sec));
+        limiter
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate: None,
+                tokens: 0.0,
+                last_refill: Instant::now(),
+            })),
+        }
+    }
+
+    /// Changes the cap shared by every transfer, including the ones in progress.
+    /// `None` or 0 lifts it.
+    pub fn set_rate(&self, bytes_per_sec: Option<u64>) {
+        let rate = bytes_per_sec.filter(|&limit| limit > 0).map(|bytes_per_sec| {
+            if bytes_per_sec < MIN_UPLOAD_RATE_LIMIT {
+                warn!(
+                    "Upload rate limit of {bytes_per_sec} bytes/sec is too low, using {MIN_UPLOAD_RATE_LIMIT} bytes/sec instead"
+                );
+            }
+            let bytes_per_sec = bytes_per_sec.max(MIN_UPLOAD_RATE_LIMIT) as f64;
+            (bytes_per_sec, bytes_per_sec.max(MIN_BURST_BYTES as f64))
+        });
+        let mut bucket = self.bucket.lock().unwrap();
+        // start from a full bucket, as if the new cap had always been in place.
+        bucket.tokens = rate.map_or(0.0, |(_, capacity)| capacity);
+        bucket.last_refill = Instant::now();
+        bucket.rate = rate;
+    }
+
+    /// The current cap in bytes/sec, or `None` if uploads are unlimited.
+    pub fn rate(&self) -> Option<u64> {
+        self.bucket
+            .lock()
+            .unwrap()
+            .rate
+            .map(|(bytes_per_sec, _)| bytes_per_sec as u64)
+    }
+
+    /// Takes `bytes` from the bucket, returning how long to wait before sending more.
+    fn take(&self, bytes: u64) -> Duration {
+        let mut bucket = self.bucket.lock().unwrap();
+        let Some((bytes_per_sec, capacity)) = bucket.rate else {
+            return Duration::ZERO;
+        };
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.last_refill).as_secs_f64() * bytes_per_sec;
+        bucket.tokens = (bucket.tokens + refill).min(capacity) - bytes as f64;
+        bucket.last_refill = now;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / bytes_per_sec)
+        }
+    }
+
+    pub async fn acquire(&self, bytes: u64) {
+        let wait = self.take(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Throttles the blobs provider by holding up its transfer progress events,
+/// which it awaits between chunks of a blob it's sending.
+#[derive(Debug)]
+pub(crate) struct ThrottledProviderEvents {
+    limiter: UploadRateLimiter,
+    // how far each (connection, request) has sent of the blob it's on
+    sent: Mutex<HashMap<(u64, u64), (Hash, u64)>>,
+}
+
+impl ThrottledProviderEvents {
+    pub fn new(limiter: UploadRateLimiter) -> Self {
+        Self {
+            limiter,
+            sent: Default::default(),
+        }
+    }
+
+    /// Returns how many bytes were sent since the last event for this request.
+    fn newly_sent(&self, event: &Event) -> u64 {
+        let mut sent = self.sent.lock().unwrap();
+        match *event {
+            Event::TransferProgress {
+                connection_id,
+                request_id,
+                hash,
+                end_offset,
+            } => {
+                let entry = sent.entry((connection_id, request_id)).or_insert((hash, 0));
+                if entry.0 != hash {
+                    *entry = (hash, 0);
+                }
+                let newly_sent = end_offset.saturating_sub(entry.1);
+                entry.1 = entry.1.max(end_offset);
+                newly_sent
+            }
+            Event::TransferCompleted {
+                connection_id,
+                request_id,
+                ..
+            }
+            | Event::TransferAborted {
+                connection_id,
+                request_id,
+                ..
+            } => {
+                sent.remove(&(connection_id, request_id));
+                0
+            }
+            // a dropped connection may not report its in-flight requests as aborted.
+            Event::ConnectionClosed { connection_id } => {
+                sent.retain(|(connection, _), _| *connection != connection_id);
+                0
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl CustomEventSender for ThrottledProviderEvents {
+    fn send(&self, event: Event) -> BoxFuture<'static, ()> {
+        let bytes = self.newly_sent(&event);
+        let limiter = self.limiter.clone();
+        Box::pin(async move { limiter.acquire(bytes).await })
+    }
+
+    fn try_send(&self, event: Event) {
+        // can't wait here, so put the bucket in debt for the next awaited event to pay off.
+        self.limiter.take(self.newly_sent(&event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_limiter_holds_the_configured_rate() {
+        let limiter = UploadRateLimiter::new(64 * 1024);
+        let start = Instant::now();
+        // the first 64KiB fit in the bucket, the next 64KiB take a second.
+        for _ in 0..8 {
+            limiter.acquire(16 * 1024).await;
+        }
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(900) && elapsed < Duration::from_millis(1500),
+            "took {elapsed:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tiny_limit_still_makes_progress() {
+        for limit in [1, MIN_UPLOAD_RATE_LIMIT - 1] {
+            let limiter = UploadRateLimiter::new(limit);
+            timeout(Duration::from_secs(1), limiter.acquire(MIN_BURST_BYTES))
+                .await
+                .expect("a full bucket should let a chunk through");
+            // bigger than the bucket, but it's a finite wait at the minimum rate, not a stall.
+            let wait = limiter.take(MIN_BURST_BYTES);
+            assert!(wait > Duration::from_secs(15) && wait <= Duration::from_secs(16));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_can_change() {
+        let limiter = UploadRateLimiter::unlimited();
+        assert_eq!(limiter.rate(), None);
+        assert_eq!(limiter.take(u64::MAX), Duration::ZERO);
+
+        limiter.set_rate(Some(64 * 1024));
+        assert_eq!(limiter.rate(), Some(64 * 1024));
+        // a full bucket, then debt at the new rate
+        assert_eq!(limiter.take(64 * 1024), Duration::ZERO);
+        let wait = limiter.take(32 * 1024);
+        assert!(wait > Duration::from_millis(450) && wait <= Duration::from_millis(500));
+
+        limiter.set_rate(Some(0));
+        assert_eq!(limiter.rate(), None);
+        assert_eq!(limiter.take(u64::MAX), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_finished_transfers_are_forgotten() {
+        let events = ThrottledProviderEvents::new(UploadRateLimiter::new(MIN_UPLOAD_RATE_LIMIT));
+        let hash = Hash::new(b"blob");
+        let progress = |connection_id, request_id, end_offset| Event::TransferProgress {
+            connection_id,
+            request_id,
+            hash,
+            end_offset,
+        };
+
+        assert_eq!(events.newly_sent(&progress(0, 0, 100)), 100);
+        assert_eq!(events.newly_sent(&progress(0, 0, 250)), 150);
+        events.newly_sent(&Event::TransferAborted {
+            connection_id: 0,
+            request_id: 0,
+            stats: None,
+        });
+        assert!(events.sent.lock().unwrap().is_empty());
+
+        events.newly_sent(&progress(1, 0, 100));
+        events.newly_sent(&progress(1, 1, 100));
+        events.newly_sent(&progress(2, 0, 100));
+        events.newly_sent(&Event::ConnectionClosed { connection_id: 1 });
+        assert_eq!(
+            events.sent.lock().unwrap().keys().collect::<Vec<_>>(),
+            vec![&(2, 0)]
+        );
+    }
+}
@@ -1,8 +1,13 @@
-use iroh::RelayMode;
+use anyhow::Result;
+use get_if_addrs::{IfAddr, Interface};
+use iroh::{endpoint::TransportConfig, RelayMode};
 use iroh_gossip::proto::TopicId;
 use sha2::{Digest, Sha256};
+use std::{net::Ipv4Addr, time::Duration};
+use thiserror::Error;
 
 const GOSSIP_TOPIC: &str = "psyche gossip";
+const GOSSIP_CHANNEL_TOPIC: &str = "psyche gossip channel";
 
 pub fn gossip_topic(run_id: &str) -> TopicId {
     let mut hasher = Sha256::new();
@@ -12,6 +17,71 @@ pub fn gossip_topic(run_id: &str) -> TopicId {
     TopicId::from_bytes(result.into())
 }
 
+/// The topic of one of a run's gossip channels besides its main one, e.g. for health beacons.
+/// Channels are separate topics, so their subscribers don't get each other's messages.
+pub fn gossip_subtopic(run_id: &str, channel: &str) -> TopicId {
+    let mut hasher = Sha256::new();
+    hasher.update(GOSSIP_CHANNEL_TOPIC);
+    // so the run id and channel name can't run into each other
+    hasher.update((run_id.len() as u64).to_le_bytes());
+    hasher.update(run_id);
+    hasher.update(channel);
+    let result = hasher.finalize();
+    TopicId::from_bytes(result.into())
+}
+
+/// The transport settings iroh's endpoint builder starts from. Setting our own transport config replaces
+/// them wholesale, and iroh doesn't expose them, so they're mirrored here.
+fn iroh_transport_config() -> TransportConfig {
+    let mut transport_config = TransportConfig::default();
+    transport_config.keep_alive_interval(Some(Duration::from_secs(1)));
+    transport_config
+}
+
+/// QUIC transport settings that close connections after `idle_timeout` without any traffic,
+/// instead of keeping them alive forever. Closed connections are redialed on demand.
+/// Everything else is left as iroh configures it.
+pub fn idle_transport_config(idle_timeout: Duration) -> Result<TransportConfig> {
+    let mut transport_config = iroh_transport_config();
+    transport_config
+        .max_idle_timeout(Some(idle_timeout.try_into()?))
+        .keep_alive_interval(None);
+    Ok(transport_config)
+}
+
+#[derive(Debug, Error)]
+pub enum InterfaceLookupError {
+    #[error("no interface with name \"{0}\" found.")]
+    NotFound(String),
+
+    #[error("interface \"{0}\" has no IPv4 address.")]
+    NoIpv4Address(String),
+}
+
+/// Finds the IPv4 address of the interface named `if_name`.
+/// If `if_name` ends with `*`, it matches the first interface with an IPv4 address whose name starts with the rest.
+pub fn interface_ipv4(
+    interfaces: &[Interface],
+    if_name: &str,
+) -> Result<Ipv4Addr, InterfaceLookupError> {
+    let matching = |interface: &&Interface| match if_name.strip_suffix('*') {
+        Some(prefix) => interface.name.starts_with(prefix),
+        None => interface.name == if_name,
+    };
+    let mut found = false;
+    for interface in interfaces.iter().filter(matching) {
+        found = true;
+        if let IfAddr::V4(addr) = &interface.addr {
+            return Ok(addr.ip);
+        }
+    }
+    Err(if found {
+        InterfaceLookupError::NoIpv4Address(if_name.to_string())
+    } else {
+        InterfaceLookupError::NotFound(if_name.to_string())
+    })
+}
+
 pub fn fmt_relay_mode(relay_mode: &RelayMode) -> String {
     match relay_mode {
         RelayMode::Disabled => "None".to_string(),
@@ -46,3 +116,63 @@ pub fn fmt_bytes(bytes: f64) -> String {
         format!("{:.2} PB", bytes / PB)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use get_if_addrs::{Ifv4Addr, Ifv6Addr};
+    use std::net::Ipv6Addr;
+
+    #[test]
+    fn test_gossip_subtopics_are_distinct() {
+        let health = gossip_subtopic("run", "health");
+        assert_eq!(health, gossip_subtopic("run", "health"));
+        assert_ne!(health, gossip_topic("run"));
+        assert_ne!(health, gossip_subtopic("run", "beacons"));
+        assert_ne!(health, gossip_subtopic("other run", "health"));
+        assert_ne!(gossip_subtopic("ab", "c"), gossip_subtopic("a", "bc"));
+    }
+
+    fn v4(name: &str, ip: Ipv4Addr) -> Interface {
+        Interface {
+            name: name.to_string(),
+            addr: IfAddr::V4(Ifv4Addr {
+                ip,
+                netmask: Ipv4Addr::new(255, 255, 255, 0),
+                broadcast: None,
+            }),
+        }
+    }
+
+    fn v6(name: &str) -> Interface {
+        Interface {
+            name: name.to_string(),
+            addr: IfAddr::V6(Ifv6Addr {
+                ip: Ipv6Addr::LOCALHOST,
+                netmask: Ipv6Addr::UNSPECIFIED,
+                broadcast: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_interface_ipv4() {
+        let eth0 = Ipv4Addr::new(10, 0, 0, 2);
+        let interfaces = [v6("wg0"), v6("eth0"), v4("eth0", eth0), v6("ib0")];
+
+        assert_eq!(interface_ipv4(&interfaces, "eth0").unwrap(), eth0);
+        assert_eq!(interface_ipv4(&interfaces, "eth*").unwrap(), eth0);
+        assert!(matches!(
+            interface_ipv4(&interfaces, "enp3s0"),
+            Err(InterfaceLookupError::NotFound(name)) if name == "enp3s0"
+        ));
+        assert!(matches!(
+            interface_ipv4(&interfaces, "wg0"),
+            Err(InterfaceLookupError::NoIpv4Address(name)) if name == "wg0"
+        ));
+        assert!(matches!(
+            interface_ipv4(&interfaces, "ib*"),
+            Err(InterfaceLookupError::NoIpv4Address(_))
+        ));
+    }
+}
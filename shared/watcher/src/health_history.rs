@@ -0,0 +1,132 @@
+use psyche_coordinator::{ClientState, Coordinator, RunState};
+use psyche_core::NodeIdentity;
+use std::collections::{HashMap, VecDeque};
+
+/// How many health check results are kept per client.
+pub const HEALTH_HISTORY_LEN: usize = 32;
+
+/// How long, in seconds, the history of a client that isn't in the run anymore is kept.
+pub const HEALTH_HISTORY_EXPIRY_SECS: u64 = 60 * 60;
+
+/// The result of the health checks against one client in one round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthSample {
+    /// Unix timestamp of the start of the round.
+    pub timestamp: u64,
+    pub passed: bool,
+    /// The client's index in the round, as health checks against it prove.
+    pub committee_index: u64,
+}
+
+/// The last [`HEALTH_HISTORY_LEN`] health check results of every client, to tell a flapping client from one that's gone.
+#[derive(Debug)]
+pub struct HealthHistory<T: NodeIdentity> {
+    samples: HashMap<T, VecDeque<HealthSample>>,
+    last_round: Option<(u16, u32)>,
+}
+
+impl<T: NodeIdentity> Default for HealthHistory<T> {
+    fn default() -> Self {
+        Self {
+            samples: HashMap::new(),
+            last_round: None,
+        }
+    }
+}
+
+impl<T: NodeIdentity> HealthHistory<T> {
+    /// Records how each client fared in the coordinator's current round, once per round.
+    pub fn record(&mut self, coordinator: &Coordinator<T>) {
+        if !matches!(
+            coordinator.run_state,
+            RunState::RoundTrain | RunState::RoundWitness
+        ) {
+            return;
+        }
+        let Some(round) = coordinator.current_round() else {
+            return;
+        };
+        let this_round = (coordinator.progress.epoch, round.height);
+        if self.last_round == Some(this_round) {
+            return;
+        }
+        self.last_round = Some(this_round);
+
+        let timestamp = coordinator.run_state_start_unix_timestamp;
+        for (index, client) in coordinator.epoch_state.clients.iter().enumerate() {
+            self.push(
+                client.id,
+                HealthSample {
+                    timestamp,
+                    passed: client.state == ClientState::Healthy,
+                    committee_index: index as u64,
+                },
+            );
+        }
+        self.expire(timestamp);
+    }
+
+    pub fn push(&mut self, id: T, sample: HealthSample) {
+        let samples = self.samples.entry(id).or_default();
+        if samples.len() == HEALTH_HISTORY_LEN {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+        // keeps `get` a plain slice. cheap, since the buffer is tiny.
+        samples.make_contiguous();
+    }
+
+    /// Forgets clients with no samples in the [`HEALTH_HISTORY_EXPIRY_SECS`] before `now`.
+    pub fn expire(&mut self, now: u64) {
+        self.samples.retain(|_, samples| {
+            samples
+                .back()
+                .is_some_and(|last| last.timestamp + HEALTH_HISTORY_EXPIRY_SECS >= now)
+        });
+    }
+
+    /// The client's health check results, oldest first.
+    pub fn get(&self, id: &T) -> &[HealthSample] {
+        self.samples
+            .get(id)
+            .map(|samples| samples.as_slices().0)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_coordinator::test_utils::TestClientId;
+
+    #[test]
+    fn test_history_keeps_the_latest_window() {
+        let mut history = HealthHistory::default();
+        let flapping = TestClientId(1);
+        let samples: Vec<_> = (0..HEALTH_HISTORY_LEN as u64 * 2 + 3)
+            .map(|i| HealthSample {
+                timestamp: 1000 + i * 10,
+                passed: i % 2 == 0,
+                committee_index: 4,
+            })
+            .collect();
+        for sample in &samples {
+            history.push(flapping, *sample);
+        }
+
+        let window = history.get(&flapping);
+        assert_eq!(window.len(), HEALTH_HISTORY_LEN);
+        assert_eq!(window, &samples[samples.len() - HEALTH_HISTORY_LEN..]);
+        assert!(window
+            .windows(2)
+            .all(|pair| pair[0].passed != pair[1].passed));
+        assert!(history.get(&TestClientId(2)).is_empty());
+
+        // clients that stop showing up age out
+        let last = samples.last().unwrap().timestamp;
+        history.expire(last + HEALTH_HISTORY_EXPIRY_SECS);
+        assert_eq!(history.get(&flapping).len(), HEALTH_HISTORY_LEN);
+        history.expire(last + HEALTH_HISTORY_EXPIRY_SECS + 1);
+        assert!(history.get(&flapping).is_empty());
+    }
+}
@@ -1,7 +1,11 @@
+mod health_history;
 mod traits;
 mod tui;
 mod watcher;
 
+pub use health_history::{
+    HealthHistory, HealthSample, HEALTH_HISTORY_EXPIRY_SECS, HEALTH_HISTORY_LEN,
+};
 pub use traits::{Backend, OpportunisticData};
 pub use tui::{CoordinatorTui, CoordinatorTuiState, TuiRunState};
 pub use watcher::BackendWatcher;
@@ -19,4 +19,6 @@ pub trait Backend<T: NodeIdentity>: Send + Sync {
     async fn send_witness(&mut self, opportunistic_data: OpportunisticData) -> Result<()>;
     async fn send_health_check(&mut self, health_check: HealthChecks<T>) -> Result<()>;
     async fn send_checkpoint(&mut self, checkpoint: model::HubRepo) -> Result<()>;
+    /// Leaves the running epoch as `Withdrawn`, so this client is neither paid nor slashed for it.
+    async fn send_withdraw(&mut self) -> Result<()>;
 }
@@ -3,6 +3,7 @@ use std::{
     time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+use crate::health_history::HealthHistory;
 use psyche_coordinator::{model::Model, Coordinator, RunState};
 use psyche_core::NodeIdentity;
 use psyche_tui::ratatui::{
@@ -23,16 +24,25 @@ impl psyche_tui::CustomWidget for CoordinatorTui {
         {
             let vsplit = Layout::vertical(Constraint::from_fills([1, 1])).split(coord_split[0]);
             {
-                Paragraph::new(format!("{}", state.run_state))
-                    .block(Block::bordered().title("Run state"))
-                    .render(vsplit[0], buf);
+                Paragraph::new(match &state.connection {
+                    Some(connection) => format!("{}\nCoordinator {connection}", state.run_state),
+                    None => format!("{}", state.run_state),
+                })
+                .block(Block::bordered().title("Run state"))
+                .render(vsplit[0], buf);
             }
             {
                 Paragraph::new(
                     state
                         .clients
                         .iter()
-                        .map(|c| c.to_string().into())
+                        .zip(state.health.iter().map(Some).chain(std::iter::repeat(None)))
+                        .map(|(c, health)| match health {
+                            Some(health) if !health.is_empty() => {
+                                format!("{c} {}", health_sparkline(health)).into()
+                            }
+                            _ => c.to_string().into(),
+                        })
                         .collect::<Vec<Line>>(),
                 )
                 .block(Block::bordered().title("Clients this round"))
@@ -71,6 +81,14 @@ impl psyche_tui::CustomWidget for CoordinatorTui {
     }
 }
 
+/// One bar per health check, oldest first: full if it passed, a sliver if it didn't.
+fn health_sparkline(health: &[bool]) -> String {
+    health
+        .iter()
+        .map(|passed| if *passed { '█' } else { '▁' })
+        .collect()
+}
+
 #[derive(Default, Debug, Clone)]
 pub enum TuiRunState {
     #[default]
@@ -169,10 +187,14 @@ pub struct CoordinatorTuiState {
     pub run_state: TuiRunState,
     pub height: u32,
     pub clients: Vec<String>,
+    /// Recent health check results of each of `clients`, oldest first. Empty if nobody's keeping track.
+    pub health: Vec<Vec<bool>>,
     pub data_source: String,
     pub model_checkpoint: String,
     pub exited_clients: usize,
     pub pending_pause: bool,
+    /// How the connection to the coordinator is doing, for backends where it can drop.
+    pub connection: Option<String>,
 }
 
 impl<T: NodeIdentity> From<&Coordinator<T>> for CoordinatorTuiState {
@@ -187,6 +209,8 @@ impl<T: NodeIdentity> From<&Coordinator<T>> for CoordinatorTuiState {
                 .iter()
                 .map(|c| format!("{:?}", c.id))
                 .collect(),
+            health: Vec::new(),
+            connection: None,
             data_source: match &value.model {
                 Model::LLM(l) => format!("{:?}", l.data_type),
             },
@@ -198,3 +222,20 @@ impl<T: NodeIdentity> From<&Coordinator<T>> for CoordinatorTuiState {
         }
     }
 }
+
+impl CoordinatorTuiState {
+    /// Adds the health check results of the clients, for the sparklines next to them.
+    pub fn with_health_history<T: NodeIdentity>(
+        mut self,
+        coordinator: &Coordinator<T>,
+        history: &HealthHistory<T>,
+    ) -> Self {
+        self.health = coordinator
+            .epoch_state
+            .clients
+            .iter()
+            .map(|c| history.get(&c.id).iter().map(|s| s.passed).collect())
+            .collect();
+        self
+    }
+}
@@ -1,4 +1,7 @@
-use crate::traits::Backend;
+use crate::{
+    health_history::{HealthHistory, HealthSample},
+    traits::Backend,
+};
 use anyhow::Result;
 use psyche_coordinator::{Client, Coordinator, RunState};
 use psyche_core::NodeIdentity;
@@ -12,6 +15,7 @@ where
     backend: B,
     client_lookup: HashMap<[u8; 32], Client<T>>,
     state: Option<Coordinator<T>>,
+    health_history: HealthHistory<T>,
 }
 
 impl<T, B> BackendWatcher<T, B>
@@ -24,6 +28,7 @@ where
             backend,
             client_lookup: HashMap::new(),
             state: None,
+            health_history: HealthHistory::default(),
         }
     }
 
@@ -43,6 +48,7 @@ where
                     .map(|client| (*client.id.get_p2p_public_key(), *client)),
             );
         }
+        self.health_history.record(&new_state);
         let old_state = replace(&mut self.state, Some(new_state));
         let new_state = self.state.as_ref().unwrap();
 
@@ -61,6 +67,15 @@ where
         &mut self.backend
     }
 
+    /// The client's last health check results, oldest first. Empty for clients we haven't seen in a while.
+    pub fn health_history(&self, id: &T) -> &[HealthSample] {
+        self.health_history.get(id)
+    }
+
+    pub fn health_histories(&self) -> &HealthHistory<T> {
+        &self.health_history
+    }
+
     pub fn get_client_for_p2p_public_key(&self, p2p_public_key: &[u8; 32]) -> Option<&Client<T>> {
         self.client_lookup.get(p2p_public_key)
     }
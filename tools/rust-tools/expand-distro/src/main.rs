@@ -49,17 +49,15 @@ fn main() -> anyhow::Result<()> {
     let distro_results_iter = distro_results_from_reader(io::stdin());
 
     for serialized_result in distro_results_iter {
-        let mut result: DistroResult = (&serialized_result?).try_into()?;
-        result.sparse_idx = result.sparse_idx.to_device(target_device);
-        result.sparse_val = result.sparse_val.to_device(target_device);
-        let decompressed = CompressDCT::decompress(
+        let result: DistroResult = (&serialized_result?).try_into()?;
+        let decompressed = CompressDCT::decompress_to(
             &result.sparse_idx,
             &result.sparse_val,
             &result.xshape,
             result.totalk,
             target_type,
             target_device,
-        );
+        )?;
 
         let flat: Vec<f32> = (&decompressed.flatten(0, -1)).try_into()?;
         let bytes = flat.into_iter().map(|f| f.to_le_bytes());
@@ -1,6 +1,7 @@
 use clap::Parser;
 use plotters::prelude::*;
 use psyche_coordinator::{model::Model, CoordinatorConfig};
+use psyche_core::LearningRateSchedule;
 use serde::Deserialize;
 use std::path::PathBuf;
 
@@ -82,6 +83,14 @@ fn main() -> anyhow::Result<()> {
 
     chart.draw_series(LineSeries::new(all_vals, &RED))?;
 
+    // mark where each segment of a piecewise schedule takes over
+    if let LearningRateSchedule::Piecewise(piecewise) = lr {
+        chart.draw_series(piecewise.segments().iter().skip(1).map(|segment| {
+            let x = segment.start_step as f64;
+            PathElement::new(vec![(x, min), (x, max)], BLUE.mix(0.5))
+        }))?;
+    }
+
     root.present()?;
 
     Ok(())
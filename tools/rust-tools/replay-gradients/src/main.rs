@@ -0,0 +1,376 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use psyche_coordinator::{model::Model, CoordinatorConfig};
+use psyche_core::{BatchId, ClosedInterval};
+use psyche_modeling::{
+    auto_model_for_causal_lm_from_pretrained, save_tensors_into_safetensors, DistroResult, Trainer,
+};
+use psyche_network::distro_results_from_reader;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[allow(clippy::large_enum_variant)] // it's only used for generating the docs correctly.
+#[derive(Parser, Debug)]
+enum Commands {
+    // Applies the results in a --write-gradients-dir to a checkpoint, in step order, and saves the resulting model.
+    Replay {
+        /// The run's state.toml, used for its LR schedule and optimizer.
+        #[clap(long)]
+        config_path: PathBuf,
+
+        /// Local directory with the base checkpoint's config.json and safetensors.
+        #[clap(long)]
+        checkpoint: PathBuf,
+
+        /// Directory of results written by clients with --write-gradients-dir.
+        #[clap(long)]
+        gradients_dir: PathBuf,
+
+        /// Directory to save the resulting model's safetensors to.
+        #[clap(long)]
+        output: PathBuf,
+
+        #[clap(long, default_value_t = false)]
+        cpu: bool,
+    },
+    // Prints the help, optionally as markdown. Used for docs generation.
+    #[clap(hide = true)]
+    PrintAllHelp {
+        #[arg(long, required = true)]
+        markdown: bool,
+    },
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[allow(unused)]
+    pub config: CoordinatorConfig,
+    pub model: Model,
+}
+
+/// Recorded results, by the step they were trained at, then by batch.
+type RecordedResults = BTreeMap<u32, BTreeMap<BatchId, PathBuf>>;
+
+/// Parses the batch id as written by `BatchId`'s `Display`, i.e. `B5` or `B[5, 8]`.
+fn parse_batch_id(s: &str) -> Option<BatchId> {
+    let s = s.strip_prefix('B')?;
+    let (start, end) = match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        Some(interval) => {
+            let (start, end) = interval.split_once(',')?;
+            (start.trim().parse().ok()?, end.trim().parse().ok()?)
+        }
+        None => {
+            let index = s.parse().ok()?;
+            (index, index)
+        }
+    };
+    Some(BatchId(ClosedInterval::new(start, end)))
+}
+
+/// Parses `result-{identity}-step{step}-batch{batch_id}.vec-postcard`, as written by the client.
+fn parse_result_file_name(file_name: &str) -> Option<(u32, BatchId)> {
+    let rest = file_name
+        .strip_prefix("result-")?
+        .strip_suffix(".vec-postcard")?;
+    let (rest, batch_id) = rest.rsplit_once("-batch")?;
+    let (_identity, step) = rest.rsplit_once("-step")?;
+    Some((step.parse().ok()?, parse_batch_id(batch_id)?))
+}
+
+fn find_recorded_results(gradients_dir: &Path) -> Result<RecordedResults> {
+    let mut results = RecordedResults::new();
+    for entry in std::fs::read_dir(gradients_dir)
+        .with_context(|| format!("failed to read {}", gradients_dir.display()))?
+    {
+        let path = entry?.path();
+        let Some((step, batch_id)) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_result_file_name)
+        else {
+            eprintln!("Skipping {}, not a recorded result", path.display());
+            continue;
+        };
+        // several clients may have trained the same batch, it's only applied once
+        if let Some(existing) = results
+            .entry(step)
+            .or_default()
+            .insert(batch_id, path.clone())
+        {
+            eprintln!(
+                "Batch {batch_id} of step {step} recorded more than once, using {} over {}",
+                path.display(),
+                existing.display()
+            );
+        }
+    }
+    Ok(results)
+}
+
+fn read_recorded_result(path: &Path) -> Result<Vec<DistroResult>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    distro_results_from_reader(file)
+        .map(|serialized| {
+            let serialized = serialized.map_err(|err| anyhow!("{}: {err}", path.display()))?;
+            Ok((&serialized).try_into()?)
+        })
+        .collect()
+}
+
+/// Applies the recorded results step by step, the same way clients do:
+/// all of a step's results are applied together, in batch order, at the following step's learning rate.
+fn replay(mut trainer: Trainer, recorded: &RecordedResults) -> Result<Trainer> {
+    for (step, batches) in recorded {
+        let results = batches
+            .values()
+            .map(|path| read_recorded_result(path))
+            .collect::<Result<Vec<_>>>()?;
+        eprintln!("Applying {} results from step {step}", results.len());
+        trainer = trainer.optimize(step + 1, None, Some(results))?;
+    }
+    Ok(trainer)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let (config_path, checkpoint, gradients_dir, output, cpu) = match args.command {
+        Commands::PrintAllHelp { markdown } => {
+            // This is a required argument for the time being.
+            assert!(markdown);
+
+            let () = clap_markdown::print_help_markdown::<Args>();
+
+            return Ok(());
+        }
+        Commands::Replay {
+            config_path,
+            checkpoint,
+            gradients_dir,
+            output,
+            cpu,
+        } => (config_path, checkpoint, gradients_dir, output, cpu),
+    };
+
+    let config: Config = toml::from_str(&std::fs::read_to_string(&config_path)?)?;
+    let Model::LLM(llm) = config.model;
+
+    let recorded = find_recorded_results(&gradients_dir)?;
+    if recorded.is_empty() {
+        bail!("no recorded results in {}", gradients_dir.display());
+    }
+
+    let repo_files = std::fs::read_dir(&checkpoint)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    let device = if cpu {
+        tch::Device::Cpu
+    } else {
+        tch::Device::cuda_if_available()
+    };
+    let mut model = auto_model_for_causal_lm_from_pretrained(
+        repo_files,
+        Some(tch::Kind::BFloat16),
+        None,
+        Some(device),
+        None,
+        None,
+        None,
+    )?;
+    model.prepare_for_training();
+
+    let trainer = Trainer::new(
+        vec![model],
+        llm.lr_schedule,
+        llm.optimizer,
+        1,
+        None,
+        None,
+        false,
+        None,
+    );
+    let mut trainer = replay(trainer, &recorded)?;
+
+    let variables = trainer.extract()?;
+    let files = save_tensors_into_safetensors(variables, output)?;
+    for file in files {
+        eprintln!("Wrote {}", file.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_core::{ConstantLR, DistroConfig, LearningRateSchedule, OptimizerDefinition};
+    use psyche_modeling::{
+        tiny_llama_config, Batch, BatchData, CausalLM, CompressDCT, LlamaForCausalLM, ModelConfig,
+        PretrainedSource, TransformDCT,
+    };
+    use psyche_network::{distro_results_to_bytes, SerializedDistroResult, SparseValueDtype};
+    use std::{collections::HashMap, sync::Arc};
+    use tch::{Device, Tensor};
+    use tokio_util::sync::CancellationToken;
+
+    const LR: f64 = 1e-2;
+    const COMPRESSION_CHUNK: i64 = 4;
+
+    fn model(parameters: &HashMap<String, Tensor>) -> LlamaForCausalLM {
+        let parameters = parameters
+            .iter()
+            .map(|(name, tensor)| (name.clone(), tensor.copy()))
+            .collect();
+        let mut model = LlamaForCausalLM::from_pretrained(
+            &PretrainedSource::ConfigAndTensors(tiny_llama_config(), Arc::new(parameters)),
+            None,
+            None,
+            Some(Device::Cpu),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        model.prepare_for_training();
+        model
+    }
+
+    fn trainer(parameters: &HashMap<String, Tensor>) -> Trainer {
+        Trainer::new(
+            vec![Box::new(model(parameters))],
+            LearningRateSchedule::Constant(ConstantLR::new(LR, 0, 0.0)),
+            OptimizerDefinition::Distro(DistroConfig {
+                clip_grad_norm: None,
+                weight_decay: None,
+                compression_decay: 0.999,
+                compression_topk: 2,
+                compression_chunk: COMPRESSION_CHUNK as u16,
+                quantize_1bit: false,
+                momentum: 0.0,
+                dampening: 0.0,
+                nesterov: false,
+            }),
+            1,
+            None,
+            None,
+            false,
+            None,
+        )
+    }
+
+    fn train(trainer: Trainer, step: u32, seed: i32) -> (Trainer, Vec<SerializedDistroResult>) {
+        let tokens = (0..16).map(|i| (i * seed) % 32).collect::<Vec<_>>();
+        let output = trainer
+            .train(
+                step,
+                Batch {
+                    id: BatchId(ClosedInterval::new(0, 0)),
+                    data: BatchData::CPU(vec![tokens]),
+                },
+                None,
+                false,
+                vec![],
+                Some(vec![]),
+                CancellationToken::new(),
+            )
+            .unwrap();
+        let results = output
+            .distro_results
+            .unwrap()
+            .iter()
+            .map(|result| SerializedDistroResult::encode(result, SparseValueDtype::Full).unwrap())
+            .collect();
+        (output.trainer, results)
+    }
+
+    #[test]
+    fn test_parse_result_file_name() {
+        assert_eq!(
+            parse_result_file_name("result-abc-step3-batchB[4, 7].vec-postcard"),
+            Some((3, BatchId(ClosedInterval::new(4, 7))))
+        );
+        assert_eq!(
+            parse_result_file_name("result-some-client-step12-batchB9.vec-postcard"),
+            Some((12, BatchId(ClosedInterval::new(9, 9))))
+        );
+        assert_eq!(parse_result_file_name("model.safetensors"), None);
+    }
+
+    #[test]
+    fn test_replay_two_recorded_results() {
+        let parameters = tiny_llama_config().random_init_parameters(0);
+
+        // record two steps of training, the way a client with --write-gradients-dir would
+        let (recording_trainer, step_1) = train(trainer(&parameters), 1, 3);
+        let (_, step_2) = train(recording_trainer, 2, 5);
+        let gradients_dir = tempfile::tempdir().unwrap();
+        for (step, results) in [(1, &step_1), (2, &step_2)] {
+            std::fs::write(
+                gradients_dir.path().join(format!(
+                    "result-client-step{step}-batchB[0, 3].vec-postcard"
+                )),
+                distro_results_to_bytes(results).unwrap(),
+            )
+            .unwrap();
+        }
+
+        let recorded = find_recorded_results(gradients_dir.path()).unwrap();
+        let mut replayed = replay(trainer(&parameters), &recorded).unwrap();
+
+        // work out the expected weights from the results themselves: with no momentum or weight decay,
+        // each step moves every weight by the LR, against the sign of its decompressed gradient
+        let model = model(&parameters);
+        let variables = model.variables().variables();
+        let trainable = model.variables().trainable_variables();
+        let mut transform = TransformDCT::new(
+            &trainable
+                .iter()
+                .map(|variable| (variable.shallow_clone(), None))
+                .collect::<Vec<_>>(),
+            COMPRESSION_CHUNK,
+        );
+        let mut expected = trainable.iter().map(Tensor::copy).collect::<Vec<_>>();
+        for results in [&step_1, &step_2] {
+            let results = results
+                .iter()
+                .map(|result| DistroResult::try_from(result).unwrap())
+                .collect::<Vec<_>>();
+            for (index, weight) in expected.iter_mut().enumerate() {
+                let result = &results[index];
+                let gradient = transform.decode(&CompressDCT::decompress(
+                    &result.sparse_idx,
+                    &result.sparse_val,
+                    &result.xshape,
+                    result.totalk,
+                    weight.kind(),
+                    Device::Cpu,
+                ));
+                *weight = &*weight - gradient.sign() * LR;
+            }
+        }
+
+        let replayed = replayed.extract().unwrap();
+        assert_eq!(replayed.len(), variables.len());
+        let mut changed = false;
+        for (name, variable) in &variables {
+            let index = trainable
+                .iter()
+                .position(|trainable| trainable.data_ptr() == variable.data_ptr())
+                .unwrap();
+            assert!(
+                replayed[name].allclose(&expected[index], 0.0, 1e-6, false),
+                "{name} differs"
+            );
+            changed |= !expected[index].equal(&parameters[name]);
+        }
+        assert!(changed, "replaying should have updated the weights");
+    }
+}
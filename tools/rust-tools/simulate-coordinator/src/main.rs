@@ -0,0 +1,115 @@
+use anyhow::Result;
+use clap::Parser;
+use psyche_coordinator::RunConfig;
+use simulation::{simulate, Assumptions};
+use std::path::PathBuf;
+
+mod simulation;
+
+#[derive(Parser, Debug)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// The run's state.toml, with its coordinator & model config.
+    #[clap(required = true)]
+    config_path: Option<PathBuf>,
+
+    /// How many clients to assume are training. Defaults to the config's `init_min_clients`.
+    #[clap(long)]
+    clients: Option<u16>,
+
+    /// Training throughput of a single client, in tokens per second.
+    #[clap(long)]
+    tokens_per_sec: u64,
+
+    /// Stop the simulation after this many seconds, instead of at the end of the run.
+    #[clap(long)]
+    duration_secs: Option<u64>,
+
+    /// Only print every Nth step.
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    print_every: u32,
+}
+
+#[allow(clippy::large_enum_variant)] // it's only used for generating the docs correctly.
+#[derive(Parser, Debug)]
+enum Commands {
+    // Prints the help, optionally as markdown. Used for docs generation.
+    #[clap(hide = true)]
+    PrintAllHelp {
+        #[arg(long, required = true)]
+        markdown: bool,
+    },
+}
+
+fn fmt_timestamp(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, (secs / 60) % 60, secs % 60)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if let Some(Commands::PrintAllHelp { markdown }) = args.command {
+        // This is a required argument for the time being.
+        assert!(markdown);
+
+        let () = clap_markdown::print_help_markdown::<Args>();
+
+        return Ok(());
+    }
+
+    let config_path = args.config_path.unwrap();
+    let run_config = RunConfig::from_toml(&std::fs::read_to_string(&config_path)?)?;
+    run_config.check()?;
+
+    let assumptions = Assumptions {
+        clients: args.clients.unwrap_or(run_config.config.init_min_clients),
+        tokens_per_sec_per_client: args.tokens_per_sec,
+        duration_secs: args.duration_secs,
+    };
+    let timeline = simulate(&run_config, &assumptions)?;
+
+    println!(
+        "{:>10}  {:>5}  {:>7}  {:>5}  {:>5}  {:>10}",
+        "time", "epoch", "step", "round", "batch", "lr"
+    );
+    let mut epoch_ends = timeline.epoch_ends.iter().peekable();
+    for round in &timeline.rounds {
+        while let Some(end) = epoch_ends.next_if(|end| **end < round.timestamp) {
+            println!("{:>10}  cooldown", fmt_timestamp(*end));
+        }
+        if round.step % args.print_every != 0 && round.height != 0 {
+            continue;
+        }
+        println!(
+            "{:>10}  {:>5}  {:>7}  {:>5}  {:>5}  {:>10.3e}",
+            fmt_timestamp(round.timestamp),
+            round.epoch,
+            round.step,
+            round.height,
+            round.batch_size,
+            round.lr
+        );
+    }
+    for end in epoch_ends {
+        println!("{:>10}  cooldown", fmt_timestamp(*end));
+    }
+
+    match timeline.finished_at {
+        Some(finished_at) => println!(
+            "run finishes after {} ({} steps, {} epochs)",
+            fmt_timestamp(finished_at),
+            timeline.rounds.len(),
+            timeline.epoch_ends.len()
+        ),
+        None => println!(
+            "run still going after {} ({} steps, {} epochs ended)",
+            fmt_timestamp(args.duration_secs.unwrap_or_default()),
+            timeline.rounds.len(),
+            timeline.epoch_ends.len()
+        ),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,322 @@
+use anchor_lang::{prelude::borsh, AnchorDeserialize, AnchorSerialize, Space};
+use anyhow::{anyhow, bail, Result};
+use bytemuck::Zeroable;
+use psyche_coordinator::{
+    model::Model, CommitteeSelection, Coordinator, CoordinatorEpochState, CoordinatorProgress,
+    RunConfig, RunState, Witness,
+};
+use psyche_core::{FixedString, NodeIdentity};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use ts_rs::TS;
+
+/// The coordinator only needs clients to have distinct ids, so simulated ones are just numbered,
+/// with a p2p key derived from that number.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Zeroable,
+    Serialize,
+    Deserialize,
+    AnchorSerialize,
+    AnchorDeserialize,
+    TS,
+)]
+pub struct SimulatedClient {
+    id: u64,
+    p2p_public_key: [u8; 32],
+}
+
+impl SimulatedClient {
+    pub fn new(id: u64) -> Self {
+        let mut p2p_public_key = [0; 32];
+        for chunk in p2p_public_key.chunks_exact_mut(8) {
+            chunk.copy_from_slice(&id.to_le_bytes());
+        }
+        Self { id, p2p_public_key }
+    }
+}
+
+impl fmt::Display for SimulatedClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "client-{}", self.id)
+    }
+}
+
+impl AsRef<[u8]> for SimulatedClient {
+    fn as_ref(&self) -> &[u8] {
+        bytemuck::bytes_of(&self.id)
+    }
+}
+
+impl Space for SimulatedClient {
+    const INIT_SPACE: usize = 8 + 32;
+}
+
+impl NodeIdentity for SimulatedClient {
+    fn get_p2p_public_key(&self) -> &[u8; 32] {
+        &self.p2p_public_key
+    }
+}
+
+/// What we assume about the clients, since the config doesn't say.
+#[derive(Debug, Clone, Copy)]
+pub struct Assumptions {
+    pub clients: u16,
+    /// training throughput of a single client
+    pub tokens_per_sec_per_client: u64,
+    /// stop after this many seconds, even if the run isn't finished
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedRound {
+    /// seconds since the run started
+    pub timestamp: u64,
+    pub epoch: u16,
+    pub step: u32,
+    pub height: u32,
+    pub batch_size: u16,
+    pub lr: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timeline {
+    pub rounds: Vec<SimulatedRound>,
+    /// when each epoch's cooldown started
+    pub epoch_ends: Vec<u64>,
+    pub finished_at: Option<u64>,
+}
+
+/// Runs the real coordinator state machine one second at a time, with every client
+/// training each round at the assumed throughput and every selected witness witnessing
+/// as soon as training is done. Warmup and cooldown take their full configured time.
+pub fn simulate(run_config: &RunConfig, assumptions: &Assumptions) -> Result<Timeline> {
+    let config = &run_config.config;
+    if assumptions.clients < config.init_min_clients.max(config.min_clients) {
+        bail!(
+            "{} clients can never start an epoch, the run needs at least {}",
+            assumptions.clients,
+            config.init_min_clients.max(config.min_clients)
+        );
+    }
+    let tokens_per_sec = assumptions.clients as u64 * assumptions.tokens_per_sec_per_client;
+    if tokens_per_sec == 0 {
+        bail!("clients need a nonzero throughput to make progress");
+    }
+
+    let clients: Vec<_> = (0..assumptions.clients as u64)
+        .map(SimulatedClient::new)
+        .collect();
+    let mut coordinator = Coordinator::<SimulatedClient>::zeroed();
+    coordinator.run_id = FixedString::from_str_truncated("simulation");
+    coordinator.config = run_config.config;
+    coordinator.model = run_config.model;
+    coordinator.progress = CoordinatorProgress::default();
+    coordinator.epoch_state = CoordinatorEpochState::default();
+    coordinator.run_state = RunState::Paused;
+    coordinator.resume(0).map_err(|err| anyhow!("{err:?}"))?;
+
+    let Model::LLM(llm) = &run_config.model;
+    let mut timeline = Timeline::default();
+    let mut round_started = 0;
+    let mut witnessed = true;
+    let mut now = 0;
+    loop {
+        now += 1;
+        if assumptions
+            .duration_secs
+            .is_some_and(|duration| now > duration)
+        {
+            break;
+        }
+
+        if !witnessed {
+            let round = coordinator.current_round_unchecked();
+            let batch_tokens = coordinator.get_target_global_batch_size(Some(round)) as u64
+                * coordinator.get_sequence_length() as u64;
+            let round_train_secs = batch_tokens.div_ceil(tokens_per_sec);
+            if coordinator.run_state == RunState::RoundWitness
+                || now >= round_started + round_train_secs
+            {
+                witness_round(&mut coordinator, now)?;
+                witnessed = true;
+            }
+        }
+
+        let pending_clients =
+            (coordinator.run_state == RunState::WaitingForMembers).then(|| clients.iter());
+        coordinator
+            .tick(pending_clients, now, now)
+            .map_err(|err| anyhow!("coordinator tick failed at {now}s: {err:?}"))?;
+
+        let just_changed = coordinator.run_state_start_unix_timestamp == now;
+        match coordinator.run_state {
+            RunState::RoundTrain if just_changed => {
+                let round = coordinator.current_round_unchecked();
+                timeline.rounds.push(SimulatedRound {
+                    timestamp: now,
+                    epoch: coordinator.progress.epoch,
+                    step: coordinator.progress.step,
+                    height: round.height,
+                    batch_size: coordinator.get_target_global_batch_size(Some(round)),
+                    lr: llm.lr_schedule.get_lr(coordinator.progress.step),
+                });
+                round_started = now;
+                witnessed = false;
+            }
+            RunState::Cooldown if just_changed => timeline.epoch_ends.push(now),
+            RunState::Finished => {
+                timeline.finished_at = Some(now);
+                break;
+            }
+            _ => {}
+        }
+    }
+    Ok(timeline)
+}
+
+fn witness_round(coordinator: &mut Coordinator<SimulatedClient>, now: u64) -> Result<()> {
+    let selection =
+        CommitteeSelection::from_coordinator(coordinator, 0).map_err(|err| anyhow!("{err:?}"))?;
+    let witnesses: Vec<_> = coordinator
+        .epoch_state
+        .clients
+        .iter()
+        .enumerate()
+        .map(|(index, client)| (client.id, selection.get_witness(index as u64)))
+        .filter(|(_, proof)| proof.witness.is_true())
+        .collect();
+    for (id, proof) in witnesses {
+        coordinator
+            .witness(
+                &id,
+                Witness {
+                    proof,
+                    ..Default::default()
+                },
+                now,
+            )
+            .map_err(|err| anyhow!("{id} failed to witness at {now}s: {err:?}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_coordinator::{
+        model::LLM, CoordinatorConfig, DataAssignmentStrategy, PendingClientsFullPolicy,
+        WAITING_FOR_MEMBERS_EXTRA_SECONDS,
+    };
+
+    const WARMUP_TIME: u64 = 20;
+    const COOLDOWN_TIME: u64 = 10;
+    const ROUND_WITNESS_TIME: u64 = 2;
+
+    fn run_config(rounds_per_epoch: u32, total_steps: u32) -> RunConfig {
+        RunConfig {
+            config: CoordinatorConfig {
+                warmup_time: WARMUP_TIME,
+                cooldown_time: COOLDOWN_TIME,
+                max_round_train_time: 60,
+                round_witness_time: ROUND_WITNESS_TIME,
+                witness_timeout: 0,
+                global_batch_size_warmup_tokens: 0,
+                rounds_per_epoch,
+                total_steps,
+                init_min_clients: 2,
+                min_clients: 2,
+                witness_nodes: 2,
+                warmup_grace_period: 0,
+                witness_quorum: 0,
+                global_batch_size_start: 8,
+                global_batch_size_end: 8,
+                verification_percent: 0,
+                witness_quorum_percent: 0,
+                committee_salt: FixedString::new(),
+                witness_salt: FixedString::new(),
+                num_stored_rounds: 0,
+                data_assignment_strategy: DataAssignmentStrategy::Contiguous,
+                max_pending_clients: 0,
+                pending_clients_full_policy: PendingClientsFullPolicy::Reject,
+                dataset_samples: 0,
+                max_data_epochs: 0,
+                auto_resume_after: 0,
+            },
+            model: Model::LLM(LLM::dummy()),
+        }
+    }
+
+    #[test]
+    fn test_round_count_matches_hand_computation() {
+        let run_config = run_config(1000, 100_000);
+        let Model::LLM(llm) = &run_config.model;
+        // 2 clients at 1024 tokens/sec train a batch of 8 * 2048 tokens in 8 seconds
+        let assumptions = Assumptions {
+            clients: 2,
+            tokens_per_sec_per_client: 1024,
+            duration_secs: Some(600),
+        };
+        let train_secs = 8 * llm.max_seq_len as u64 / (2 * 1024);
+        assert_eq!(train_secs, 8);
+
+        let first_round = WAITING_FOR_MEMBERS_EXTRA_SECONDS + WARMUP_TIME;
+        let round_secs = train_secs + ROUND_WITNESS_TIME;
+        let expected_rounds = (600 - first_round) / round_secs + 1;
+
+        let timeline = simulate(&run_config, &assumptions).unwrap();
+        assert_eq!(timeline.rounds.len() as u64, expected_rounds);
+        assert_eq!(timeline.rounds[0].timestamp, first_round);
+        assert_eq!(timeline.rounds[1].timestamp, first_round + round_secs);
+        assert_eq!(timeline.rounds.last().unwrap().step, expected_rounds as u32);
+        assert!(timeline.epoch_ends.is_empty());
+        assert_eq!(timeline.finished_at, None);
+    }
+
+    #[test]
+    fn test_epochs_and_finish() {
+        let timeline = simulate(
+            &run_config(5, 8),
+            &Assumptions {
+                clients: 2,
+                tokens_per_sec_per_client: 1024,
+                duration_secs: None,
+            },
+        )
+        .unwrap();
+
+        // the first epoch's last round ends in a cooldown, then we wait for members and warm up again
+        let first_cooldown = 23 + 4 * 10 + 10;
+        assert_eq!(timeline.epoch_ends[0], first_cooldown);
+        let second_epoch =
+            first_cooldown + COOLDOWN_TIME + WAITING_FOR_MEMBERS_EXTRA_SECONDS + WARMUP_TIME;
+        assert_eq!(timeline.rounds[5].timestamp, second_epoch);
+        assert_eq!(timeline.rounds[5].epoch, 1);
+        assert_eq!(timeline.rounds[5].height, 0);
+        assert_eq!(timeline.rounds[5].step, 6);
+
+        // epochs always run all their rounds, so the run only finishes
+        // after the cooldown of the epoch that passes total_steps
+        assert_eq!(timeline.rounds.len(), 10);
+        assert_eq!(timeline.rounds[9].step, 10);
+        let second_cooldown = second_epoch + 5 * 10;
+        assert_eq!(timeline.epoch_ends, vec![first_cooldown, second_cooldown]);
+        assert_eq!(timeline.finished_at, Some(second_cooldown + COOLDOWN_TIME));
+    }
+
+    #[test]
+    fn test_simulated_clients_have_distinct_p2p_keys() {
+        let (a, b) = (SimulatedClient::new(1), SimulatedClient::new(2));
+        assert_ne!(a.get_p2p_public_key(), b.get_p2p_public_key());
+        assert_eq!(
+            a.get_p2p_public_key(),
+            SimulatedClient::new(1).get_p2p_public_key()
+        );
+    }
+}
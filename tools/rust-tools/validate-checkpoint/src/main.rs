@@ -0,0 +1,166 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use psyche_modeling::{
+    check_safetensors_against_config, AutoConfig, CheckpointMismatch, DeepseekConfig, LlamaConfig,
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser, Debug)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Local directory with the checkpoint's safetensors.
+    #[clap(long, required = true)]
+    checkpoint: Option<PathBuf>,
+
+    /// The config.json of the intended architecture. Defaults to the checkpoint's own.
+    #[clap(long)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+enum Commands {
+    // Prints the help, optionally as markdown. Used for docs generation.
+    #[clap(hide = true)]
+    PrintAllHelp {
+        #[arg(long, required = true)]
+        markdown: bool,
+    },
+}
+
+/// Parses an HF-style config.json into the config of the architecture named by its `model_type`.
+fn load_config(path: &Path) -> Result<AutoConfig> {
+    let config_json = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let config_json: serde_json::Value = serde_json::from_str(&config_json)?;
+    let model_type = config_json
+        .get("model_type")
+        .and_then(|x| x.as_str())
+        .ok_or_else(|| anyhow!("{} has no model_type", path.display()))?;
+    Ok(match model_type {
+        "llama" => AutoConfig::Llama(serde_json::from_value::<LlamaConfig>(config_json)?),
+        "deepseek_v2" | "deepseek_v3" => {
+            AutoConfig::Deepseek(serde_json::from_value::<DeepseekConfig>(config_json)?)
+        }
+        model_type => bail!("unsupported model_type {model_type}"),
+    })
+}
+
+fn validate(config: &AutoConfig, checkpoint: &Path) -> Result<Vec<CheckpointMismatch>> {
+    let repo_files = std::fs::read_dir(checkpoint)
+        .with_context(|| format!("failed to read {}", checkpoint.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    if !repo_files.iter().any(|x| {
+        x.extension()
+            .is_some_and(|y| y.eq_ignore_ascii_case("safetensors"))
+    }) {
+        bail!("no safetensors in {}", checkpoint.display());
+    }
+    Ok(check_safetensors_against_config(config, &repo_files)?)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if let Some(Commands::PrintAllHelp { markdown }) = args.command {
+        // This is a required argument for the time being.
+        assert!(markdown);
+
+        let () = clap_markdown::print_help_markdown::<Args>();
+
+        return Ok(());
+    }
+
+    let checkpoint = args.checkpoint.unwrap();
+    let config_path = args
+        .config
+        .unwrap_or_else(|| checkpoint.join("config.json"));
+    let config = load_config(&config_path)?;
+
+    let mismatches = validate(&config, &checkpoint)?;
+    if mismatches.is_empty() {
+        println!("{} matches {}", checkpoint.display(), config_path.display());
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        println!("{mismatch}");
+    }
+    bail!(
+        "{} tensors in {} don't match {}",
+        mismatches.len(),
+        checkpoint.display(),
+        config_path.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use psyche_modeling::{save_tensors_into_safetensors, tiny_llama_config, ModelConfig};
+    use tch::{Kind, Tensor};
+
+    #[test]
+    fn test_matching_checkpoint() {
+        let config = tiny_llama_config();
+        let checkpoint = tempfile::tempdir().unwrap();
+        save_tensors_into_safetensors(
+            config.random_init_parameters(0),
+            checkpoint.path().to_path_buf(),
+        )
+        .unwrap();
+
+        let mismatches = validate(&AutoConfig::Llama(config), checkpoint.path()).unwrap();
+        assert_eq!(mismatches, vec![]);
+    }
+
+    #[test]
+    fn test_mismatched_checkpoint_reports_offending_tensors() {
+        // a checkpoint for a model with a bigger vocab, with one layer's weights lost, and one tensor we don't know about
+        let mut parameters = LlamaConfig {
+            vocab_size: 128,
+            ..tiny_llama_config()
+        }
+        .random_init_parameters(0);
+        parameters
+            .remove("model.layers.1.mlp.down_proj.weight")
+            .unwrap();
+        parameters.insert(
+            "model.layers.1.self_attn.rotary_emb.inv_freq".to_string(),
+            Tensor::zeros([4], (Kind::Float, tch::Device::Cpu)),
+        );
+        let checkpoint = tempfile::tempdir().unwrap();
+        save_tensors_into_safetensors(parameters, checkpoint.path().to_path_buf()).unwrap();
+
+        let mismatches =
+            validate(&AutoConfig::Llama(tiny_llama_config()), checkpoint.path()).unwrap();
+        assert_eq!(
+            mismatches,
+            vec![
+                CheckpointMismatch::Shape {
+                    name: "lm_head.weight".to_string(),
+                    expected: vec![64, 16],
+                    actual: vec![128, 16],
+                },
+                CheckpointMismatch::Shape {
+                    name: "model.embed_tokens.weight".to_string(),
+                    expected: vec![64, 16],
+                    actual: vec![128, 16],
+                },
+                CheckpointMismatch::Missing {
+                    name: "model.layers.1.mlp.down_proj.weight".to_string(),
+                    expected: vec![16, 32],
+                },
+                CheckpointMismatch::Unexpected {
+                    name: "model.layers.1.self_attn.rotary_emb.inv_freq".to_string(),
+                    shape: vec![4],
+                },
+            ]
+        );
+        assert_eq!(
+            mismatches[2].to_string(),
+            "model.layers.1.mlp.down_proj.weight: missing from checkpoint, expected shape [16, 32]"
+        );
+    }
+}
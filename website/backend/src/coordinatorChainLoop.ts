@@ -342,6 +342,17 @@ export async function startWatchCoordinatorChainLoop(
 						})
 						break
 					}
+					case 'withdraw': {
+						const runPdaAddr = i.accounts[1].toString()
+						const coordinatorAddr = i.accounts[2].toString()
+						runUpdates.getAndTouchCurrentRun({
+							runPdaAddr,
+							coordinatorAddr,
+							decoded,
+							tx,
+						})
+						break
+					}
 					default: {
 						const _missed_tx: never = decoded
 						throw new Error(
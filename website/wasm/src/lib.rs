@@ -1,3 +1,4 @@
+use psyche_coordinator::WitnessParticipation;
 use psyche_core::LearningRateSchedule;
 use psyche_solana_coordinator::{coordinator_account_from_bytes, ClientId, CoordinatorAccount};
 use serde::ser::Serialize;
@@ -9,6 +10,7 @@ const TS_COORDINATOR_DEF: &str = r#"
 import { CoordinatorInstanceState } from "./CoordinatorInstanceState.js";
 import { ClientId } from "./ClientId.js";
 import { LearningRateSchedule } from "./LearningRateSchedule.js";
+import { WitnessParticipation } from "./WitnessParticipation.js";
 
 export type PsycheCoordinator = CoordinatorInstanceState;
 "#;
@@ -20,6 +22,27 @@ pub fn load_coordinator_from_bytes(bytes: Vec<u8>) -> Result<JsValue, JsError> {
     ))?)
 }
 
+/// Witness participation in each round the coordinator still stores, oldest first.
+#[wasm_bindgen(unchecked_return_type = "Array<WitnessParticipation<ClientId>>")]
+pub fn witness_participation_from_bytes(bytes: Vec<u8>) -> Result<JsValue, JsError> {
+    let coordinator = &coordinator_account_from_bytes(&bytes)?.state.coordinator;
+    let mut rounds: Vec<_> = coordinator
+        .epoch_state
+        .rounds
+        .iter()
+        .take(coordinator.config.num_stored_rounds())
+        .filter(|round| !round.witnesses.is_empty())
+        .collect();
+    rounds.sort_by_key(|round| round.height);
+    let participation: Vec<_> = rounds
+        .into_iter()
+        .map(|round| coordinator.witness_participation(round))
+        .collect();
+    Ok(participation.serialize(
+        &serde_wasm_bindgen::Serializer::new().serialize_large_number_types_as_bigints(true),
+    )?)
+}
+
 #[wasm_bindgen]
 pub fn lr_at_step(
     #[wasm_bindgen(unchecked_param_type = "LearningRateSchedule")] lr: JsValue,
@@ -38,3 +61,8 @@ pub struct DummyCoordinatorAccount(CoordinatorAccount);
 #[derive(TS)]
 #[ts(export)]
 pub struct DummyClientId(ClientId);
+
+#[allow(dead_code)]
+#[derive(TS)]
+#[ts(export)]
+pub struct DummyWitnessParticipation(WitnessParticipation<ClientId>);